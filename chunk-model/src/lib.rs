@@ -6,7 +6,77 @@ use std::collections::BTreeMap;
 
 /// Semantic version of the NDJSON/JSON record schema (major bumps are breaking).
 pub const SCHEMA_MAJOR: u16 = 1;
-pub const SCHEMA_MINOR: u16 = 0;
+pub const SCHEMA_MINOR: u16 = 2;
+
+/// Meta key holding the detected language of a chunk (e.g., "ja", "en").
+pub const META_LANG: &str = "lang";
+
+/// Meta key set to "true" on chunks flagged by the ingest quality gate.
+pub const META_LOW_QUALITY: &str = "low_quality";
+
+/// Meta key holding the chunk text's token count under the embedder's tokenizer.
+pub const META_TOKENS: &str = "tokens";
+
+/// Meta key holding the document-blob key of the rendered image of a chunk's first page.
+pub const META_PAGE_IMAGE: &str = "page_image";
+
+/// `FileRecord` meta key holding the id of the embedding model that embedded the document.
+pub const META_EMBED_MODEL_ID: &str = "embed.model_id";
+
+/// `FileRecord` meta key holding the embedding dimension the document was embedded at.
+pub const META_EMBED_DIM: &str = "embed.dim";
+
+/// `FileRecord` meta key holding the fingerprint (model file and settings) of the embedder.
+pub const META_EMBED_FINGERPRINT: &str = "embed.fingerprint";
+
+/// `extra` key under which `quarantine_extra_conflicts` moves colliding entries.
+pub const EXTRA_CONFLICT_KEY: &str = "_conflict";
+
+/// Top-level `FileRecord` keys: current fields plus historical names (SQLite storage columns
+/// that show up in raw table dumps).
+pub const FILE_RECORD_KNOWN_KEYS: &[&str] = &[
+    "schema_version", "doc_id", "doc_revision", "source_uri", "source_mime", "file_size_bytes",
+    "content_sha256", "page_count", "extracted_at", "created_at_meta", "updated_at_meta",
+    "title_guess", "author_guess", "dominant_lang", "tags", "ingest_tool", "ingest_tool_version",
+    "reader_backend", "ocr_used", "ocr_langs", "chunk_count", "total_tokens", "meta",
+    // historical
+    "tags_json", "ocr_langs_json", "meta_json", "extra_json",
+];
+
+/// Top-level `ChunkRecord` keys: current fields plus historical names (see `FILE_RECORD_KNOWN_KEYS`).
+pub const CHUNK_RECORD_KNOWN_KEYS: &[&str] = &[
+    "schema_version", "doc_id", "chunk_id", "source_uri", "source_mime", "extracted_at",
+    "page_start", "page_end", "text", "section_path", "meta", "block_kinds", "seq",
+    // historical
+    "section_path_json", "meta_json", "extra_json", "block_kinds_json", "text_sha256",
+];
+
+/// `extra` keys that collide with one of `known` (sorted). Such keys either shadow a field on
+/// re-serialization or carry data of a renamed field that would otherwise be silently ignored.
+pub fn extra_conflicts(extra: &BTreeMap<String, Value>, known: &[&str]) -> Vec<String> {
+    extra.keys().filter(|k| known.contains(&k.as_str())).cloned().collect()
+}
+
+/// Move colliding `extra` entries under `extra["_conflict"]` and return their keys.
+/// A non-object value already stored at `_conflict` is kept under `_conflict._conflict`.
+pub fn quarantine_extra_conflicts(extra: &mut BTreeMap<String, Value>, known: &[&str]) -> Vec<String> {
+    let keys = extra_conflicts(extra, known);
+    if keys.is_empty() {
+        return keys;
+    }
+    let slot = extra
+        .entry(EXTRA_CONFLICT_KEY.to_string())
+        .or_insert_with(|| Value::Object(Default::default()));
+    if !slot.is_object() {
+        let prev = slot.take();
+        *slot = Value::Object([(EXTRA_CONFLICT_KEY.to_string(), prev)].into_iter().collect());
+    }
+    let moved: Vec<(String, Value)> = keys.iter().filter_map(|k| extra.remove_entry(k)).collect();
+    if let Some(Value::Object(bucket)) = extra.get_mut(EXTRA_CONFLICT_KEY) {
+        bucket.extend(moved);
+    }
+    keys
+}
 
 /// Opaque document identifier. String keeps it flexible (UUID/ULID/hash).
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -16,6 +86,14 @@ pub struct DocumentId(pub String);
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ChunkId(pub String);
 
+impl ChunkId {
+    /// Numeric `#N` suffix of the conventional `<doc>#N` id form, if present.
+    /// Fallback ordering for records written before `ChunkRecord::seq` existed.
+    pub fn seq_suffix(&self) -> Option<u32> {
+        self.0.rsplit_once('#').and_then(|(_, n)| n.parse().ok())
+    }
+}
+
 /// Logical section path like ["Ⅰ 基本的考え方", "Ⅹ－５ 補償"].
 pub type SectionPath = Vec<String>;
 
@@ -99,6 +177,10 @@ pub struct ChunkRecord {
     pub doc_id: DocumentId,
     /// Unique id for this chunk.
     pub chunk_id: ChunkId,
+    /// Intra-document ordinal (0-based) of this chunk; document order and neighbors follow it.
+    /// `None` on records before schema 1.2, whose order comes from `ChunkId::seq_suffix`.
+    #[serde(default)]
+    pub seq: Option<u32>,
     /// Source URI (file://, s3://, etc.).
     pub source_uri: String,
     /// Source MIME/type string (e.g., "application/pdf").
@@ -114,13 +196,39 @@ pub struct ChunkRecord {
     pub section_path: Option<SectionPath>,
     /// Lightweight metadata bag.
     pub meta: BTreeMap<String, String>,
+    /// Distinct content types of the source blocks this chunk covers, in first-seen order.
+    /// Empty when the producer did not track block structure (records before schema 1.1).
+    #[serde(default)]
+    pub block_kinds: Vec<BlockKind>,
 
     /// Forward-compatible extension area. Namespaced keys recommended (e.g., "layout.span").
     #[serde(flatten)]
     pub extra: BTreeMap<String, Value>,
 }
 
+impl FileRecord {
+    /// `extra` keys colliding with known or historical field names.
+    pub fn extra_conflicts(&self) -> Vec<String> {
+        extra_conflicts(&self.extra, FILE_RECORD_KNOWN_KEYS)
+    }
+
+    /// Move colliding `extra` keys under `extra["_conflict"]`; returns the moved keys.
+    pub fn quarantine_extra_conflicts(&mut self) -> Vec<String> {
+        quarantine_extra_conflicts(&mut self.extra, FILE_RECORD_KNOWN_KEYS)
+    }
+}
+
 impl ChunkRecord {
+    /// `extra` keys colliding with known or historical field names.
+    pub fn extra_conflicts(&self) -> Vec<String> {
+        extra_conflicts(&self.extra, CHUNK_RECORD_KNOWN_KEYS)
+    }
+
+    /// Move colliding `extra` keys under `extra["_conflict"]`; returns the moved keys.
+    pub fn quarantine_extra_conflicts(&mut self) -> Vec<String> {
+        quarantine_extra_conflicts(&mut self.extra, CHUNK_RECORD_KNOWN_KEYS)
+    }
+
     /// Soft validation suitable for ingestion.
     pub fn validate_soft(&self) -> Result<(), String> {
         if self.text.trim().is_empty() {
@@ -49,7 +49,7 @@ fn main() {
 
     let mut filters: Vec<FilterClause> = Vec::new();
     if let Some(d) = doc_id { filters.push(FilterClause { kind: FilterKind::Must, op: FilterOp::DocIdEq(d) }); }
-    if let Some(p) = prefix { filters.push(FilterClause { kind: FilterKind::Must, op: FilterOp::SourceUriPrefix(p) }); }
+    if let Some(p) = prefix { filters.push(FilterClause { kind: FilterKind::Must, op: FilterOp::SourceUriPrefix { prefix: p, case_insensitive: false } }); }
     if start.is_some() || end.is_some() {
         filters.push(FilterClause { kind: FilterKind::Must, op: FilterOp::RangeIsoDate { key: "extracted_at".into(), start, end, start_incl: true, end_incl: false } });
     }
@@ -36,6 +36,7 @@ fn make_sample() -> Vec<ChunkRecord> {
             schema_version: SCHEMA_MAJOR,
             doc_id: DocumentId("doc-001".into()),
             chunk_id: ChunkId("doc-001#0".into()),
+            seq: None,
             source_uri: "file:///sample/ja.txt".into(),
             source_mime: "text/plain".into(),
             extracted_at: "2024-06-01T00:00:00Z".into(),
@@ -44,12 +45,14 @@ fn make_sample() -> Vec<ChunkRecord> {
             text: "こんにちは 世界。日本語の分かち書きテスト。".into(),
             section_path: Some(vec!["はじめに".into()]),
             meta: Default::default(),
+            block_kinds: Vec::new(),
             extra: Default::default(),
         },
         ChunkRecord {
             schema_version: SCHEMA_MAJOR,
             doc_id: DocumentId("doc-002".into()),
             chunk_id: ChunkId("doc-002#0".into()),
+            seq: None,
             source_uri: "file:///sample/en.txt".into(),
             source_mime: "text/plain".into(),
             extracted_at: "2024-07-01T00:00:00Z".into(),
@@ -58,6 +61,7 @@ fn make_sample() -> Vec<ChunkRecord> {
             text: "hello world. this is a sample English chunk.".into(),
             section_path: Some(vec!["intro".into()]),
             meta: Default::default(),
+            block_kinds: Vec::new(),
             extra: Default::default(),
         },
     ]
@@ -21,6 +21,7 @@ fn make_rec(id: &str, text: &str, extracted_at: &str) -> ChunkRecord {
         schema_version: SCHEMA_MAJOR,
         doc_id: DocumentId("doc-001".into()),
         chunk_id: ChunkId(id.into()),
+        seq: None,
         source_uri: "memory://demo".into(),
         source_mime: "text/plain".into(),
         extracted_at: extracted_at.into(),
@@ -29,6 +30,7 @@ fn make_rec(id: &str, text: &str, extracted_at: &str) -> ChunkRecord {
         text: text.into(),
         section_path: None,
         meta: BTreeMap::new(),
+        block_kinds: Vec::new(),
         extra: BTreeMap::new(),
     }
 }
@@ -57,7 +59,7 @@ fn main() {
         }},
     ];
 
-    let opts = SearchOptions { top_k: 5, fetch_factor: 5 };
+    let opts = SearchOptions { top_k: 5, fetch_factor: 5, ..Default::default() };
     let store = NullStore;
     let hits = idx.search_ids(&store, "hello", &filters, &opts);
 
@@ -0,0 +1,103 @@
+//! On-disk cache of embedding vectors, keyed by SHA-256 of the model id and the embedded
+//! text, so re-ingesting unchanged text skips the model. Entries of another model never
+//! match, which is how a model change invalidates them.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+
+use crate::StoreError;
+
+/// SQLite-backed embedding cache. Opened in WAL mode, so other processes can read it while
+/// one writes; within a process the connection is shared behind a lock.
+pub struct EmbedCache {
+    conn: Mutex<Connection>,
+}
+
+/// A cached vector with the token count reported when it was embedded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedEmbedding {
+    pub vector: Vec<f32>,
+    pub tokens: usize,
+}
+
+impl EmbedCache {
+    /// Open or create the cache database at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        let conn = Connection::open(path).map_err(|e| StoreError::Backend(e.to_string()))?;
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| StoreError::Backend(e.to_string()))?;
+        conn.busy_timeout(Duration::from_secs(5)).map_err(|e| StoreError::Backend(e.to_string()))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS embed_cache (
+                key TEXT PRIMARY KEY,
+                model_id TEXT NOT NULL,
+                tokens INTEGER NOT NULL,
+                vector BLOB NOT NULL
+            );",
+        )
+        .map_err(|e| StoreError::Backend(e.to_string()))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Cache key of `text` embedded by `model_id`.
+    pub fn key(model_id: &str, text: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(model_id.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(text.as_bytes());
+        hasher.finalize().iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Cached entries for `keys`; keys without an entry are absent from the map.
+    pub fn get_many(&self, keys: &[String]) -> Result<HashMap<String, CachedEmbedding>, StoreError> {
+        let conn = self.conn.lock().map_err(|_| StoreError::Backend("embed cache lock poisoned".into()))?;
+        let mut stmt = conn
+            .prepare_cached("SELECT tokens, vector FROM embed_cache WHERE key = ?1")
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let mut out = HashMap::new();
+        for key in keys {
+            if out.contains_key(key) { continue; }
+            let mut rows = stmt.query([key]).map_err(|e| StoreError::Backend(e.to_string()))?;
+            if let Some(row) = rows.next().map_err(|e| StoreError::Backend(e.to_string()))? {
+                let tokens: i64 = row.get(0).map_err(|e| StoreError::Backend(e.to_string()))?;
+                let blob: Vec<u8> = row.get(1).map_err(|e| StoreError::Backend(e.to_string()))?;
+                let vector = blob.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+                out.insert(key.clone(), CachedEmbedding { vector, tokens: tokens.max(0) as usize });
+            }
+        }
+        Ok(out)
+    }
+
+    /// Store `(key, entry)` pairs for `model_id` in one transaction, replacing existing ones.
+    pub fn put_many(&self, model_id: &str, entries: &[(String, CachedEmbedding)]) -> Result<(), StoreError> {
+        let mut conn = self.conn.lock().map_err(|_| StoreError::Backend("embed cache lock poisoned".into()))?;
+        let tx = conn.transaction().map_err(|e| StoreError::Backend(e.to_string()))?;
+        {
+            let mut stmt = tx
+                .prepare_cached("INSERT OR REPLACE INTO embed_cache(key, model_id, tokens, vector) VALUES (?1, ?2, ?3, ?4)")
+                .map_err(|e| StoreError::Backend(e.to_string()))?;
+            for (key, entry) in entries {
+                let blob: Vec<u8> = entry.vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+                stmt.execute(params![key, model_id, entry.tokens as i64, blob]).map_err(|e| StoreError::Backend(e.to_string()))?;
+            }
+        }
+        tx.commit().map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Number of cached vectors.
+    pub fn len(&self) -> Result<usize, StoreError> {
+        let conn = self.conn.lock().map_err(|_| StoreError::Backend("embed cache lock poisoned".into()))?;
+        conn.query_row("SELECT count(*) FROM embed_cache", [], |r| r.get::<_, i64>(0))
+            .map(|n| n.max(0) as usize)
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// True when nothing is cached.
+    pub fn is_empty(&self) -> Result<bool, StoreError> {
+        self.len().map(|n| n == 0)
+    }
+}
@@ -1,7 +1,7 @@
 use chunk_model::{ChunkId, ChunkRecord};
 
-use crate::sqlite_repo::SqliteRepo;
-use crate::{SearchHit, TextMatch, ChunkStoreRead, TextSearcher, FilterClause, FilterKind, FilterOp, SearchOptions, IndexCaps, TextIndexMaintainer, IndexError};
+use crate::sqlite_repo::{block_kind_name, block_kinds_in_sql, eq_sql, filename_filter_sql, in_list_sql, like_filter_sql, meta_extract_sql, section_prefix_sql, SqliteRepo};
+use crate::{str_filter_matches, StrMatch, SearchHit, TextMatch, ChunkStoreRead, TextSearcher, FilterClause, FilterKind, FilterOp, SearchOptions, IndexCaps, TextIndexMaintainer, IndexError};
 
 /// FTS5-backed text search over the SQLite primary store.
 /// Index maintenance is handled by SQLite triggers in the store.
@@ -13,7 +13,7 @@ impl Fts5Index {
 
     /// Convenience search (no filters) with defaults.
     pub fn search_simple(&self, repo: &SqliteRepo, query: &str, limit: usize) -> Vec<SearchHit> {
-        let opts = SearchOptions { top_k: limit, fetch_factor: 10 };
+        let opts = SearchOptions { top_k: limit, fetch_factor: 10, ..Default::default() };
         self.search(repo, query, &[], &opts)
     }
 
@@ -36,7 +36,7 @@ impl Fts5Index {
         for rec in recs {
             if !matches_filters(&rec, &post) { continue; }
             if let Some(score) = score_map.get(&rec.chunk_id.0) {
-                hits.push(SearchHit { chunk: rec, score: *score });
+                hits.push(SearchHit { chunk: rec, score: *score, fallback: false, components: None });
             }
         }
         // Preserve ordering of matches
@@ -56,6 +56,8 @@ impl TextIndexMaintainer for Fts5Index {
         // Deletion should be performed in primary store; triggers update FTS.
         Ok(())
     }
+
+    fn in_primary_store(&self) -> bool { true }
 }
 
 impl TextSearcher for Fts5Index {
@@ -66,7 +68,8 @@ impl TextSearcher for Fts5Index {
             can_prefilter_doc_id_eq: true,
             can_prefilter_doc_id_in: true,
             can_prefilter_source_prefix: true,
-            can_prefilter_meta: false,
+            can_prefilter_meta: true,
+            can_prefilter_meta_eq: true,
             can_prefilter_range_numeric: false,
             can_prefilter_range_date: false,
         }
@@ -115,30 +118,93 @@ impl TextSearcher for Fts5Index {
                 }
                 FilterOp::DocIdIn(vs) => {
                     if !vs.is_empty() {
-                        sql_with_rank.push_str(" AND c.doc_id IN (");
-                        sql_fallback.push_str(" AND c.doc_id IN (");
-                        for i in 0..vs.len() {
-                            if i > 0 { sql_with_rank.push(','); sql_fallback.push(','); }
-                            sql_with_rank.push('?');
-                            sql_fallback.push('?');
-                            params.push(vs[i].clone().into());
-                        }
-                        sql_with_rank.push(')');
-                        sql_fallback.push(')');
+                        let (cond, vals) = in_list_sql("c.doc_id", vs, false);
+                        sql_with_rank.push_str(&format!(" AND {cond}"));
+                        sql_fallback.push_str(&format!(" AND {cond}"));
+                        params.extend(vals.into_iter().map(Into::into));
+                    }
+                }
+                FilterOp::SourceUriPrefix { prefix, case_insensitive } => {
+                    let (cond, vals) = like_filter_sql("c.source_uri", prefix, true, *case_insensitive);
+                    sql_with_rank.push_str(&format!(" AND {cond}"));
+                    sql_fallback.push_str(&format!(" AND {cond}"));
+                    params.extend(vals.into_iter().map(Into::into));
+                }
+                FilterOp::SourceFilenamePrefix(n) | FilterOp::SourceFilenameContains(n) => {
+                    let prefix = matches!(fc.op, FilterOp::SourceFilenamePrefix(_));
+                    if let Some((cond, vals)) = filename_filter_sql("c.source_uri", n, prefix) {
+                        sql_with_rank.push_str(&format!(" AND {cond}"));
+                        sql_fallback.push_str(&format!(" AND {cond}"));
+                        params.extend(vals.into_iter().map(Into::into));
+                    }
+                }
+                FilterOp::DocIdNotIn(vs) => {
+                    if !vs.is_empty() {
+                        let (cond, vals) = in_list_sql("c.doc_id", vs, true);
+                        sql_with_rank.push_str(&format!(" AND {cond}"));
+                        sql_fallback.push_str(&format!(" AND {cond}"));
+                        params.extend(vals.into_iter().map(Into::into));
+                    }
+                }
+                FilterOp::MetaNe { key, value } => {
+                    let expr = meta_extract_sql("c.meta_json", key);
+                    sql_with_rank.push_str(&format!(" AND {expr} IS NOT ?"));
+                    sql_fallback.push_str(&format!(" AND {expr} IS NOT ?"));
+                    params.push(value.clone().into());
+                }
+                FilterOp::MetaEq { key, value, case_insensitive } => {
+                    let expr = meta_extract_sql("c.meta_json", key);
+                    let eq = eq_sql(*case_insensitive);
+                    sql_with_rank.push_str(&format!(" AND {expr} {eq}"));
+                    sql_fallback.push_str(&format!(" AND {expr} {eq}"));
+                    params.push(value.clone().into());
+                }
+                FilterOp::MetaPrefix { key, prefix: needle, case_insensitive } | FilterOp::MetaContains { key, substring: needle, case_insensitive } => {
+                    let prefix = matches!(fc.op, FilterOp::MetaPrefix { .. });
+                    let (cond, vals) = like_filter_sql(&meta_extract_sql("c.meta_json", key), needle, prefix, *case_insensitive);
+                    sql_with_rank.push_str(&format!(" AND {cond}"));
+                    sql_fallback.push_str(&format!(" AND {cond}"));
+                    params.extend(vals.into_iter().map(Into::into));
+                }
+                FilterOp::MetaIn { key, values } => {
+                    if !values.is_empty() {
+                        let (cond, vals) = in_list_sql(&meta_extract_sql("c.meta_json", key), values, false);
+                        sql_with_rank.push_str(&format!(" AND {cond}"));
+                        sql_fallback.push_str(&format!(" AND {cond}"));
+                        params.extend(vals.into_iter().map(Into::into));
                     }
                 }
-                FilterOp::SourceUriPrefix(prefix) => {
-                    sql_with_rank.push_str(" AND c.source_uri LIKE ?");
-                    sql_fallback.push_str(" AND c.source_uri LIKE ?");
-                    params.push(format!("{}%", prefix).into());
+                FilterOp::BlockKindIn(kinds) => {
+                    if !kinds.is_empty() {
+                        let marks = vec!["?"; kinds.len()].join(",");
+                        let cond = block_kinds_in_sql("c.block_kinds_json", &marks);
+                        sql_with_rank.push_str(&format!(" AND {cond}"));
+                        sql_fallback.push_str(&format!(" AND {cond}"));
+                        for k in kinds { params.push(block_kind_name(k).into()); }
+                    }
+                }
+                FilterOp::SectionPathPrefix(prefix) => {
+                    if !prefix.is_empty() {
+                        let cond = section_prefix_sql("c.section_path_json", prefix.len());
+                        sql_with_rank.push_str(&format!(" AND {cond}"));
+                        sql_fallback.push_str(&format!(" AND {cond}"));
+                        for c in prefix { params.push(c.clone().into()); }
+                    }
                 }
                 _ => {}
             }
         }
 
+        // Language prefilter on the meta tag written at ingest
+        if let Some(lang) = &opts.lang {
+            sql_with_rank.push_str(" AND json_extract(c.meta_json, '$.lang') = ?");
+            sql_fallback.push_str(" AND json_extract(c.meta_json, '$.lang') = ?");
+            params.push(lang.clone().into());
+        }
+
         sql_with_rank.push_str(" ORDER BY rank LIMIT ?");
         sql_fallback.push_str(" LIMIT ?");
-        let fetch_n = (opts.top_k.saturating_mul(opts.fetch_factor)).max(opts.top_k);
+        let fetch_n = opts.fetch_n();
         params.push((fetch_n as i64).into());
 
         let conn = sqlite.conn();
@@ -173,14 +239,7 @@ fn plan_filters<'a>(idx: &impl TextSearcher, filters: &'a [FilterClause]) -> (Ve
     let mut pre = Vec::new();
     let mut post = Vec::new();
     for f in filters {
-        let supported = match &f.op {
-            FilterOp::DocIdEq(_) => caps.can_prefilter_doc_id_eq,
-            FilterOp::DocIdIn(_) => caps.can_prefilter_doc_id_in,
-            FilterOp::SourceUriPrefix(_) => caps.can_prefilter_source_prefix,
-            FilterOp::MetaEq { .. } | FilterOp::MetaIn { .. } => caps.can_prefilter_meta,
-            FilterOp::RangeNumeric { .. } => caps.can_prefilter_range_numeric,
-            FilterOp::RangeIsoDate { .. } => caps.can_prefilter_range_date,
-        };
+        let supported = caps.supports(&f.op);
         if supported && f.kind != FilterKind::PostOnly {
             pre.push(f.clone());
         } else {
@@ -195,13 +254,33 @@ fn matches_filters(rec: &ChunkRecord, post: &[FilterClause]) -> bool {
         match &f.op {
             FilterOp::DocIdEq(v) => { if &rec.doc_id.0 != v { continue 'outer; } }
             FilterOp::DocIdIn(vs) => { if !vs.iter().any(|v| v == &rec.doc_id.0) { continue 'outer; } }
-            FilterOp::SourceUriPrefix(prefix) => { if !rec.source_uri.starts_with(prefix) { continue 'outer; } }
-            FilterOp::MetaEq { key, value } => {
-                match rec.meta.get(key) { Some(v) if v == value => {}, _ => continue 'outer }
+            FilterOp::SourceUriPrefix { prefix, case_insensitive } => {
+                if !str_filter_matches(&rec.source_uri, prefix, StrMatch::Prefix, *case_insensitive) { continue 'outer; }
+            }
+            FilterOp::SourceFilenamePrefix(n) => { if !crate::filename_filter_matches(&rec.source_uri, n, true) { continue 'outer; } }
+            FilterOp::SourceFilenameContains(n) => { if !crate::filename_filter_matches(&rec.source_uri, n, false) { continue 'outer; } }
+            FilterOp::DocIdNotIn(vs) => { if vs.iter().any(|v| v == &rec.doc_id.0) { continue 'outer; } }
+            FilterOp::MetaNe { key, value } => {
+                if rec.meta.get(key) == Some(value) { continue 'outer; }
+            }
+            FilterOp::MetaEq { key, value, case_insensitive } => {
+                match rec.meta.get(key) { Some(v) if str_filter_matches(v, value, StrMatch::Eq, *case_insensitive) => {}, _ => continue 'outer }
             }
             FilterOp::MetaIn { key, values } => {
                 match rec.meta.get(key) { Some(v) if values.iter().any(|x| x == v) => {}, _ => continue 'outer }
             }
+            FilterOp::MetaPrefix { key, prefix, case_insensitive } => {
+                if !rec.meta.get(key).is_some_and(|v| str_filter_matches(v, prefix, StrMatch::Prefix, *case_insensitive)) { continue 'outer; }
+            }
+            FilterOp::MetaContains { key, substring, case_insensitive } => {
+                if !rec.meta.get(key).is_some_and(|v| str_filter_matches(v, substring, StrMatch::Contains, *case_insensitive)) { continue 'outer; }
+            }
+            FilterOp::BlockKindIn(kinds) => {
+                if !kinds.is_empty() && !rec.block_kinds.iter().any(|k| kinds.contains(k)) { continue 'outer; }
+            }
+            FilterOp::SectionPathPrefix(prefix) => {
+                if !rec.section_path.as_deref().unwrap_or_default().starts_with(prefix) { continue 'outer; }
+            }
             FilterOp::RangeNumeric { key, min, max, min_incl, max_incl } => {
                 // value can be in a reserved field or meta
                 let val_str = value_for_key(rec, key);
@@ -1,86 +1,580 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use chunk_model::ChunkId;
 use hnsw_rs::prelude::*;
 
-use crate::{ChunkStoreRead, FilterClause, SearchOptions, TextMatch, VectorSearcher};
+use crate::{ChunkStoreRead, FilterClause, FilterExpr, FilterOp, SearchOptions, TextMatch, VectorSearcher};
 
-/// HNSW-based vector index (Cosine distance). Persists by snapshotting vectors + id map.
+/// HNSW-based vector index (Cosine by default, see `HnswMetric`). Persists by
+/// snapshotting vectors + id map.
 pub struct HnswIndex {
     dim: usize,
-    hnsw: Hnsw<'static, f32, DistCosine>,
+    hnsw: Graph,
     /// Map chunk_id -> internal label
     id_map: HashMap<String, usize>,
     /// Reverse map internal label -> chunk_id
     rev_map: Vec<String>,
-    /// Stored vectors for persistence and rebuild
-    vectors: Vec<Vec<f32>>,
+    /// Stored vectors for persistence and rebuild, at `dtype` precision
+    vectors: VectorBuf,
     /// Tombstoned labels (deleted)
     tombstones: HashSet<usize>,
+    /// Chunk ids deleted since the last `flush_deletes`/`save`
+    unflushed_deletes: Vec<String>,
+    /// Batch size from which an `upsert` into an empty index builds the graph in parallel
+    bulk_build_min: usize,
+    /// Whether stored (and therefore query) vectors are L2-normalized
+    normalized: bool,
+    /// Identifier of the model that produced the vectors, persisted with the snapshot
+    model_fingerprint: Option<String>,
+    /// Graph construction parameters, persisted with the snapshot
+    params: HnswParams,
+    /// Set by `load` when `dir` was unreadable and the `.prev` snapshot was loaded instead
+    from_previous: bool,
 }
 
-impl HnswIndex {
-    pub fn new(dim: usize, expected: usize) -> Self {
-        let max_nb_conn = 16;
-        let ef_c = 200;
+/// Graph construction parameters, saved with the snapshot so `load` and `compact` rebuild
+/// the same layout. Higher `m` and `ef_construction` raise recall at the cost of build time
+/// and memory (`m` links per node and layer). The defaults (16, 200) suit corpora up to a
+/// few hundred thousand chunks; around 32/400 helps recall on larger or harder ones, while
+/// 8/100 builds faster when a small store can afford a little recall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HnswParams {
+    /// Max links per node (`M`).
+    pub m: usize,
+    /// Candidate list size while inserting (`ef_construction`).
+    pub ef_construction: usize,
+    /// Expected number of vectors; sizes the layer allocation. The index still accepts
+    /// more, with slightly worse layer balance.
+    pub max_elements: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self { m: 16, ef_construction: 200, max_elements: 10_000 }
+    }
+}
+
+/// Default connectivity with `max_elements` as the capacity, as in `HnswIndex::new(dim, 10_000)`.
+impl From<usize> for HnswParams {
+    fn from(max_elements: usize) -> Self {
+        Self { max_elements, ..Self::default() }
+    }
+}
+
+impl HnswParams {
+    fn to_sidecar(self) -> String {
+        format!("m={}\nef_construction={}\nmax_elements={}\n", self.m, self.ef_construction, self.max_elements)
+    }
+
+    fn parse_sidecar(txt: &str) -> Option<Self> {
+        let mut params = Self::default();
+        for line in txt.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (key, value) = line.split_once('=')?;
+            let value: usize = value.trim().parse().ok()?;
+            match key.trim() {
+                "m" => params.m = value,
+                "ef_construction" => params.ef_construction = value,
+                "max_elements" => params.max_elements = value,
+                _ => {}
+            }
+        }
+        Some(params)
+    }
+}
+
+/// Default `bulk_build_min`: below this, one-by-one inserts are cheap enough.
+pub const DEFAULT_BULK_BUILD_MIN: usize = 1000;
+
+/// Sidecar file listing deleted chunk ids (one per line) on top of `map.tsv`.
+const TOMBSTONES_FILE: &str = "tombstones.tsv";
+/// Snapshot file holding the metric name; absent means cosine (older snapshots).
+const METRIC_FILE: &str = "metric.txt";
+/// Snapshot file holding `true`/`false` for `normalized`; absent means not normalized.
+const NORMALIZED_FILE: &str = "normalized.txt";
+/// Snapshot file holding the vector dimension; absent means read it from the vectors.
+const DIM_FILE: &str = "dim.txt";
+/// Snapshot file holding the model fingerprint; absent when none was set.
+const MODEL_FILE: &str = "model.txt";
+/// Snapshot file holding `HnswParams` as `key=value` lines; absent means the defaults.
+const PARAMS_FILE: &str = "params.txt";
+/// Allowed deviation of a vector's L2 norm from 1 in a normalized index.
+const UNIT_NORM_TOLERANCE: f32 = 1e-3;
+
+/// Errors from loading an HNSW snapshot.
+#[derive(Debug, thiserror::Error)]
+pub enum HnswError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The snapshot was built with vectors of another dimension (a different embedding model).
+    #[error("HNSW snapshot has dimension {found} but {expected} was expected; reindex with the current embedder")]
+    DimensionMismatch { expected: usize, found: usize },
+}
+
+/// Distance metric of the graph. It fixes how `knn_ids` fills `TextMatch`:
+/// `raw_score` is the metric's native value (cosine similarity, dot product, or Euclidean
+/// distance for `L2`), `score` is normalized so larger is always better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HnswMetric {
+    #[default]
+    Cosine,
+    /// Inner product; meant for vectors that are already normalized.
+    Dot,
+    L2,
+}
+
+impl HnswMetric {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HnswMetric::Cosine => "cosine",
+            HnswMetric::Dot => "dot",
+            HnswMetric::L2 => "l2",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "cosine" => Some(HnswMetric::Cosine),
+            "dot" => Some(HnswMetric::Dot),
+            "l2" => Some(HnswMetric::L2),
+            _ => None,
+        }
+    }
+
+    /// Native metric value from a graph distance (cosine/dot graphs store `1 - similarity`).
+    fn raw_score(self, dist: f32) -> f32 {
+        match self {
+            HnswMetric::Cosine | HnswMetric::Dot => 1.0 - dist,
+            HnswMetric::L2 => dist,
+        }
+    }
+
+    /// Larger-is-better score from a graph distance.
+    fn score(self, dist: f32) -> f32 {
+        match self {
+            HnswMetric::Cosine | HnswMetric::Dot => 1.0 - dist,
+            HnswMetric::L2 => 1.0 / (1.0 + dist),
+        }
+    }
+}
+
+/// `1 - <a, b>` without `DistDot`'s assertion that the product is at most 1, which
+/// rounding on normalized vectors can trip.
+#[derive(Default, Clone, Copy)]
+struct DistInnerProduct;
+
+impl Distance<f32> for DistInnerProduct {
+    fn eval(&self, va: &[f32], vb: &[f32]) -> f32 {
+        1.0 - va.iter().zip(vb).map(|(a, b)| a * b).sum::<f32>()
+    }
+}
+
+/// The graph for each metric (`Hnsw` is generic over its distance).
+enum Graph {
+    Cosine(Hnsw<'static, f32, DistCosine>),
+    Dot(Hnsw<'static, f32, DistInnerProduct>),
+    L2(Hnsw<'static, f32, DistL2>),
+}
+
+impl Graph {
+    fn new(metric: HnswMetric, params: HnswParams) -> Self {
         let num_layers = 16;
-        let hnsw = Hnsw::<f32, DistCosine>::new(max_nb_conn, expected, num_layers, ef_c, DistCosine {});
-        Self { dim, hnsw, id_map: HashMap::new(), rev_map: Vec::new(), vectors: Vec::new(), tombstones: HashSet::new() }
+        let (m, expected, ef_c) = (params.m.max(1), params.max_elements.max(1), params.ef_construction.max(1));
+        match metric {
+            HnswMetric::Cosine => Graph::Cosine(Hnsw::new(m, expected, num_layers, ef_c, DistCosine {})),
+            HnswMetric::Dot => Graph::Dot(Hnsw::new(m, expected, num_layers, ef_c, DistInnerProduct)),
+            HnswMetric::L2 => Graph::L2(Hnsw::new(m, expected, num_layers, ef_c, DistL2 {})),
+        }
+    }
+
+    fn metric(&self) -> HnswMetric {
+        match self {
+            Graph::Cosine(_) => HnswMetric::Cosine,
+            Graph::Dot(_) => HnswMetric::Dot,
+            Graph::L2(_) => HnswMetric::L2,
+        }
+    }
+
+    fn insert(&self, v: &[f32], label: usize) {
+        match self {
+            Graph::Cosine(h) => h.insert((v, label)),
+            Graph::Dot(h) => h.insert((v, label)),
+            Graph::L2(h) => h.insert((v, label)),
+        }
+    }
+
+    fn parallel_insert(&self, batch: &[(&Vec<f32>, usize)]) {
+        match self {
+            Graph::Cosine(h) => h.parallel_insert(batch),
+            Graph::Dot(h) => h.parallel_insert(batch),
+            Graph::L2(h) => h.parallel_insert(batch),
+        }
+    }
+
+    fn search(&self, query: &[f32], knn: usize, ef: usize) -> Vec<Neighbour> {
+        match self {
+            Graph::Cosine(h) => h.search(query, knn, ef),
+            Graph::Dot(h) => h.search(query, knn, ef),
+            Graph::L2(h) => h.search(query, knn, ef),
+        }
+    }
+}
+/// Snapshot file names per dtype; the file present on disk tells `load` the precision.
+const VECTORS_F32_FILE: &str = "vectors.bin";
+const VECTORS_F16_FILE: &str = "vectors.f16.bin";
+
+/// Storage precision of snapshot (and side-buffer) vectors. Distances are always
+/// computed in f32; `F16` halves the snapshot size at a small loss of precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VectorDtype {
+    #[default]
+    F32,
+    F16,
+}
+
+impl VectorDtype {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VectorDtype::F32 => "f32",
+            VectorDtype::F16 => "f16",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "f32" => Some(VectorDtype::F32),
+            "f16" => Some(VectorDtype::F16),
+            _ => None,
+        }
+    }
+
+    /// Bytes per stored vector component.
+    pub fn width(self) -> usize {
+        match self {
+            VectorDtype::F32 => 4,
+            VectorDtype::F16 => 2,
+        }
     }
+}
+
+/// Side-buffer of vectors kept for snapshots and rebuilds, one entry per label.
+enum VectorBuf {
+    F32(Vec<Vec<f32>>),
+    F16(Vec<Vec<u16>>),
+}
+
+impl VectorBuf {
+    fn new(dtype: VectorDtype) -> Self {
+        match dtype {
+            VectorDtype::F32 => VectorBuf::F32(Vec::new()),
+            VectorDtype::F16 => VectorBuf::F16(Vec::new()),
+        }
+    }
+
+    fn dtype(&self) -> VectorDtype {
+        match self {
+            VectorBuf::F32(_) => VectorDtype::F32,
+            VectorBuf::F16(_) => VectorDtype::F16,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            VectorBuf::F32(v) => v.len(),
+            VectorBuf::F16(v) => v.len(),
+        }
+    }
+
+    fn push(&mut self, v: &[f32]) {
+        match self {
+            VectorBuf::F32(buf) => buf.push(v.to_vec()),
+            VectorBuf::F16(buf) => buf.push(v.iter().map(|&x| f32_to_f16(x)).collect()),
+        }
+    }
+
+    /// Vector for `label`, widened to f32 (the precision the graph works in).
+    fn get(&self, label: usize) -> Vec<f32> {
+        match self {
+            VectorBuf::F32(buf) => buf[label].clone(),
+            VectorBuf::F16(buf) => buf[label].iter().map(|&h| f16_to_f32(h)).collect(),
+        }
+    }
+
+    /// Keep only `labels`, in the given order.
+    fn select(&mut self, labels: &[usize]) {
+        match self {
+            VectorBuf::F32(buf) => *buf = labels.iter().map(|&l| std::mem::take(&mut buf[l])).collect(),
+            VectorBuf::F16(buf) => *buf = labels.iter().map(|&l| std::mem::take(&mut buf[l])).collect(),
+        }
+    }
+
+    fn convert(&mut self, dtype: VectorDtype) {
+        if self.dtype() == dtype { return; }
+        let widened: Vec<Vec<f32>> = (0..self.len()).map(|l| self.get(l)).collect();
+        *self = VectorBuf::new(dtype);
+        for v in &widened { self.push(v); }
+    }
+
+    /// Raw little-endian bytes of `label` at storage precision.
+    fn write_le<W: std::io::Write>(&self, label: usize, w: &mut W) -> std::io::Result<()> {
+        match self {
+            VectorBuf::F32(buf) => w.write_all(bytemuck::cast_slice(&buf[label][..])),
+            VectorBuf::F16(buf) => {
+                for h in &buf[label] { w.write_all(&h.to_le_bytes())?; }
+                Ok(())
+            }
+        }
+    }
+}
 
-    /// Upsert vectors; duplicate chunk_id replaces previous vector by reinsert (no true delete in HNSW).
-    pub fn upsert(&mut self, items: &[(ChunkId, Vec<f32>)]) {
+impl HnswIndex {
+    /// Empty cosine index. `params` is an `HnswParams` or just the expected vector count.
+    pub fn new(dim: usize, params: impl Into<HnswParams>) -> Self {
+        Self::with_metric(dim, params, HnswMetric::Cosine)
+    }
+
+    /// Empty index using `metric`; it is saved with the snapshot and restored by `load`.
+    pub fn with_metric(dim: usize, params: impl Into<HnswParams>, metric: HnswMetric) -> Self {
+        let params = params.into();
+        let hnsw = Graph::new(metric, params);
+        Self { dim, hnsw, id_map: HashMap::new(), rev_map: Vec::new(), vectors: VectorBuf::new(VectorDtype::F32), tombstones: HashSet::new(), unflushed_deletes: Vec::new(), bulk_build_min: DEFAULT_BULK_BUILD_MIN, normalized: false, model_fingerprint: None, params, from_previous: false }
+    }
+
+    pub fn metric(&self) -> HnswMetric { self.hnsw.metric() }
+
+    /// Graph construction parameters (saved with the snapshot).
+    pub fn params(&self) -> HnswParams { self.params }
+
+    /// Whether the index holds L2-normalized vectors (saved with the snapshot).
+    pub fn normalized(&self) -> bool { self.normalized }
+
+    /// Declare the vectors as L2-normalized. Cosine and `Dot` then rank identically, and
+    /// vectors off the unit sphere are rejected by `upsert` and `knn_ids`.
+    pub fn set_normalized(&mut self, normalized: bool) { self.normalized = normalized; }
+
+    /// Model identifier recorded with the snapshot, if any.
+    pub fn model_fingerprint(&self) -> Option<&str> { self.model_fingerprint.as_deref() }
+
+    /// Record which model produced the vectors; `save` persists it next to the dimension.
+    pub fn set_model_fingerprint(&mut self, fingerprint: Option<String>) { self.model_fingerprint = fingerprint; }
+
+    /// True when `load` fell back to the previous snapshot because the current one was
+    /// missing or unreadable, so changes saved after that snapshot are not in the index.
+    pub fn loaded_from_previous(&self) -> bool { self.from_previous }
+
+    /// `Err` when the index is normalized but `v` is not a unit vector.
+    fn check_norm(&self, v: &[f32]) -> Result<(), crate::IndexError> {
+        if !self.normalized { return Ok(()); }
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if (norm - 1.0).abs() > UNIT_NORM_TOLERANCE {
+            return Err(crate::IndexError::Backend(format!("index stores normalized vectors but got a vector with L2 norm {norm:.4}")));
+        }
+        Ok(())
+    }
+
+    /// Storage precision of the vector snapshot.
+    pub fn dtype(&self) -> VectorDtype { self.vectors.dtype() }
+
+    /// Change the storage precision; already stored vectors are converted and the next
+    /// `save` writes the snapshot in the new format. Vectors inserted while `F16` is set
+    /// enter the graph rounded to f16, so a reload reproduces the same neighbors.
+    pub fn set_dtype(&mut self, dtype: VectorDtype) { self.vectors.convert(dtype); }
+
+    /// Bytes held by stored vector components (excluding the graph itself).
+    pub fn vector_bytes(&self) -> usize { self.vectors.len() * self.dim * self.dtype().width() }
+
+    /// Set the batch size from which `upsert` into an empty index inserts all vectors in
+    /// parallel (rayon, via `Hnsw::parallel_insert`) instead of one by one. 0 disables it.
+    pub fn set_bulk_build_min(&mut self, n: usize) { self.bulk_build_min = n; }
+
+    /// Upsert vectors; a duplicate chunk_id tombstones its previous label and is inserted
+    /// under a fresh one (HNSW has no true delete). Rebuild recommended for heavy churn.
+    /// A large first batch (see `set_bulk_build_min`) is inserted in parallel. The capacity
+    /// grows as needed (see `reserve`). In a normalized index a non-unit vector fails the
+    /// whole batch before anything is inserted.
+    pub fn upsert(&mut self, items: &[(ChunkId, Vec<f32>)]) -> Result<(), crate::IndexError> {
+        for (_, v) in items { self.check_norm(v)?; }
+        self.reserve(items.len());
+        let bulk = self.rev_map.is_empty() && self.bulk_build_min > 0 && items.len() >= self.bulk_build_min;
         for (cid, v) in items {
             if v.len() != self.dim { continue; }
-            let label = if let Some(&lbl) = self.id_map.get(&cid.0) {
-                // naive: just insert again; HNSW has no delete. Rebuild recommended for heavy churn.
-                lbl
-            } else {
-                let lbl = self.rev_map.len();
-                self.id_map.insert(cid.0.clone(), lbl);
-                self.rev_map.push(cid.0.clone());
-                self.vectors.push(v.clone());
-                lbl
-            };
-            let _ = self.hnsw.insert((&v[..], label));
+            if let Some(&old) = self.id_map.get(&cid.0) {
+                self.tombstones.insert(old);
+            }
+            // Re-inserted after a delete: the pending tombstone must not hide it on reload
+            self.unflushed_deletes.retain(|d| d != &cid.0);
+            let label = self.rev_map.len();
+            self.id_map.insert(cid.0.clone(), label);
+            self.rev_map.push(cid.0.clone());
+            self.vectors.push(v);
+            if !bulk { self.hnsw.insert(&self.vectors.get(label), label); }
         }
-        // optional dump
+        // Labels superseded within the batch are already tombstoned; leave them out of the graph
+        if bulk { self.insert_live_parallel(); }
+        Ok(())
     }
 
-    /// Snapshot vectors + map to a directory (rebuilds index on load).
+    /// Make room for `additional` more vectors. hnsw_rs sizes its layers from the expected
+    /// count and cannot grow in place, so past `params().max_elements` the graph is rebuilt
+    /// with at least twice the capacity and the live vectors are reinserted under their
+    /// labels. `upsert` calls this itself; calling it up front avoids repeated rebuilds when
+    /// the final size is known. The new capacity is saved with the snapshot.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.rev_map.len() + additional;
+        if needed <= self.params.max_elements { return; }
+        self.params.max_elements = needed.max(self.params.max_elements.saturating_mul(2));
+        self.hnsw = Graph::new(self.metric(), self.params);
+        self.insert_live_parallel();
+    }
+
+    /// Insert every non-tombstoned stored vector into the graph, in parallel.
+    fn insert_live_parallel(&self) {
+        let live: Vec<(Vec<f32>, usize)> = (0..self.vectors.len())
+            .filter(|l| !self.tombstones.contains(l))
+            .map(|l| (self.vectors.get(l), l))
+            .collect();
+        if live.is_empty() { return; }
+        let batch: Vec<(&Vec<f32>, usize)> = live.iter().map(|(v, l)| (v, *l)).collect();
+        self.hnsw.parallel_insert(&batch);
+    }
+
+    /// Snapshot vectors + map to a directory (rebuilds index on load). The snapshot is
+    /// written to the sibling `<dir>.tmp` and renamed into place, so a crash mid-save never
+    /// leaves `dir` half-written; the snapshot it replaces is kept at `<dir>.prev`, which
+    /// `load` falls back to.
     pub fn save<P: AsRef<Path>>(&self, dir: P) -> std::io::Result<()> {
         let dir = dir.as_ref();
-        fs::create_dir_all(dir)?;
-        let map_path = dir.join("map.tsv.tmp");
-        let vec_path = dir.join("vectors.bin.tmp");
+        let tmp = sibling_dir(dir, "tmp");
+        if tmp.exists() { fs::remove_dir_all(&tmp)?; }
+        fs::create_dir_all(&tmp)?;
+        self.write_snapshot(&tmp)?;
+        swap_in_dir(dir, &tmp)
+    }
+
+    /// Write every snapshot file into the empty directory `dir`, synced to disk. The vector
+    /// file name follows `dtype`.
+    fn write_snapshot(&self, dir: &Path) -> std::io::Result<()> {
+        use std::io::Write;
+        let vec_file = match self.dtype() {
+            VectorDtype::F32 => VECTORS_F32_FILE,
+            VectorDtype::F16 => VECTORS_F16_FILE,
+        };
+        // Tombstoned labels are dropped, compacting the snapshot
+        let live: Vec<usize> = (0..self.rev_map.len()).filter(|l| !self.tombstones.contains(l)).collect();
         {
-            let mut w = fs::File::create(&map_path)?;
-            for (i, cid) in self.rev_map.iter().enumerate() {
-                use std::io::Write;
-                writeln!(w, "{i}\t{cid}")?;
+            let mut w = std::io::BufWriter::new(fs::File::create(dir.join("map.tsv"))?);
+            for (i, &lbl) in live.iter().enumerate() {
+                writeln!(w, "{i}\t{}", self.rev_map[lbl])?;
             }
+            w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
         }
         {
-            let mut w = fs::File::create(&vec_path)?;
-            use std::io::Write;
-            // binary: [u32 dim][f32..] repeated
-            for v in &self.vectors {
-                let dim = v.len() as u32;
-                w.write_all(&dim.to_le_bytes())?;
-                let bytes: &[u8] = bytemuck::cast_slice(&v[..]);
-                w.write_all(bytes)?;
+            let mut w = std::io::BufWriter::new(fs::File::create(dir.join(vec_file))?);
+            // binary: [u32 dim][f32.. or f16..] repeated
+            for &lbl in &live {
+                w.write_all(&(self.dim as u32).to_le_bytes())?;
+                self.vectors.write_le(lbl, &mut w)?;
             }
+            w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
         }
-        fs::rename(map_path, dir.join("map.tsv"))?;
-        fs::rename(vec_path, dir.join("vectors.bin"))?;
+        fs::write(dir.join(METRIC_FILE), self.metric().as_str())?;
+        fs::write(dir.join(NORMALIZED_FILE), if self.normalized { "true" } else { "false" })?;
+        fs::write(dir.join(DIM_FILE), self.dim.to_string())?;
+        fs::write(dir.join(PARAMS_FILE), self.params.to_sidecar())?;
+        if let Some(m) = &self.model_fingerprint { fs::write(dir.join(MODEL_FILE), m)?; }
         Ok(())
     }
 
-    /// Load snapshot and rebuild HNSW.
-    pub fn load<P: AsRef<Path>>(dir: P, dim: usize) -> std::io::Result<Self> {
+    /// True when `dir` or its `.prev` fallback (see `save`) holds a snapshot to `load`.
+    pub fn snapshot_exists<P: AsRef<Path>>(dir: P) -> bool {
         let dir = dir.as_ref();
+        dir.join("map.tsv").exists() || sibling_dir(dir, "prev").join("map.tsv").exists()
+    }
+
+    /// Persist deletes made since the last flush by appending their chunk ids to the
+    /// tombstone sidecar, leaving the vector snapshot untouched (O(deleted), not O(N)).
+    pub fn flush_deletes<P: AsRef<Path>>(&mut self, dir: P) -> std::io::Result<()> {
+        if self.unflushed_deletes.is_empty() { return Ok(()); }
+        use std::io::Write;
+        let mut w = fs::OpenOptions::new().create(true).append(true).open(dir.as_ref().join(TOMBSTONES_FILE))?;
+        for cid in &self.unflushed_deletes { writeln!(w, "{cid}")?; }
+        w.sync_all()?;
+        self.unflushed_deletes.clear();
+        Ok(())
+    }
+
+    /// True when `chunk_id` has a live (non-deleted) vector.
+    pub fn contains(&self, chunk_id: &str) -> bool {
+        self.id_map.get(chunk_id).is_some_and(|l| !self.tombstones.contains(l))
+    }
+
+    /// The stored vector of `chunk_id` (as f32, normalized if the index normalizes), if live.
+    pub fn vector(&self, chunk_id: &str) -> Option<Vec<f32>> {
+        self.id_map.get(chunk_id).filter(|l| !self.tombstones.contains(l)).map(|&l| self.vectors.get(l))
+    }
+
+    /// Chunk ids with a live (non-deleted) vector, in label order.
+    pub fn live_ids(&self) -> impl Iterator<Item = &str> {
+        self.rev_map.iter().enumerate().filter(|(l, _)| !self.tombstones.contains(l)).map(|(_, cid)| cid.as_str())
+    }
+
+    /// Share of labels that are tombstoned (0.0 for an empty index).
+    pub fn tombstone_ratio(&self) -> f32 {
+        if self.rev_map.is_empty() { return 0.0; }
+        self.tombstones.len() as f32 / self.rev_map.len() as f32
+    }
+
+    /// Rebuild the graph from live vectors only, dropping tombstones and renumbering labels.
+    /// Call `save` afterwards to shrink the on-disk snapshot as well.
+    pub fn compact(&mut self) {
+        if self.tombstones.is_empty() { return; }
+        let live: Vec<usize> = (0..self.rev_map.len()).filter(|l| !self.tombstones.contains(l)).collect();
+        let hnsw = Graph::new(self.metric(), HnswParams { max_elements: self.params.max_elements.max(live.len()), ..self.params });
+        let mut id_map = HashMap::with_capacity(live.len());
+        let mut rev_map = Vec::with_capacity(live.len());
+        for (new_lbl, &old) in live.iter().enumerate() {
+            hnsw.insert(&self.vectors.get(old), new_lbl);
+            id_map.insert(self.rev_map[old].clone(), new_lbl);
+            rev_map.push(std::mem::take(&mut self.rev_map[old]));
+        }
+        self.vectors.select(&live);
+        self.hnsw = hnsw;
+        self.id_map = id_map;
+        self.rev_map = rev_map;
+        self.tombstones.clear();
+    }
+
+    /// Model fingerprint recorded in the snapshot at `dir`, without loading it.
+    pub fn snapshot_model_fingerprint<P: AsRef<Path>>(dir: P) -> std::io::Result<Option<String>> {
+        match fs::read_to_string(dir.as_ref().join(MODEL_FILE)) {
+            Ok(m) => Ok(Some(m.trim().to_string()).filter(|m| !m.is_empty())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Load snapshot and rebuild HNSW. The dtype is taken from the snapshot file present.
+    /// Fails with `HnswError::DimensionMismatch` when the snapshot was built for another
+    /// dimension than `dim`, before any vector is read into the graph. When `dir` is missing
+    /// or unreadable (e.g. truncated), the previous snapshot kept by `save` is loaded instead
+    /// and `loaded_from_previous` reports it.
+    pub fn load<P: AsRef<Path>>(dir: P, dim: usize) -> Result<Self, HnswError> {
+        let dir = dir.as_ref();
+        let prev = sibling_dir(dir, "prev");
+        match Self::load_snapshot(dir, dim) {
+            Err(HnswError::Io(e)) if prev.join("map.tsv").exists() => match Self::load_snapshot(&prev, dim) {
+                Ok(this) => Ok(Self { from_previous: true, ..this }),
+                Err(_) => Err(HnswError::Io(e)),
+            },
+            res => res,
+        }
+    }
+
+    fn load_snapshot(dir: &Path, dim: usize) -> Result<Self, HnswError> {
+        if let Some(found) = snapshot_dimension(dir)? {
+            if found != dim { return Err(HnswError::DimensionMismatch { expected: dim, found }); }
+        }
         let map_txt = fs::read_to_string(dir.join("map.tsv"))?;
         let mut rev_map: Vec<String> = Vec::new();
         for line in map_txt.lines() {
@@ -88,26 +582,63 @@ impl HnswIndex {
             let _idx = it.next();
             if let Some(cid) = it.next() { rev_map.push(cid.to_string()); }
         }
-        let mut vectors: Vec<Vec<f32>> = Vec::with_capacity(rev_map.len());
-        let mut r = std::io::BufReader::new(fs::File::open(dir.join("vectors.bin"))?);
+        let dtype = if dir.join(VECTORS_F16_FILE).exists() { VectorDtype::F16 } else { VectorDtype::F32 };
+        let vec_file = match dtype { VectorDtype::F32 => VECTORS_F32_FILE, VectorDtype::F16 => VECTORS_F16_FILE };
+        let mut vectors = VectorBuf::new(dtype);
+        let mut r = std::io::BufReader::new(fs::File::open(dir.join(vec_file))?);
         use std::io::Read;
         loop {
             let mut len_buf = [0u8; 4];
             if let Err(_) = r.read_exact(&mut len_buf) { break; }
             let l = u32::from_le_bytes(len_buf) as usize;
-            let mut vbytes = vec![0u8; 4 * l];
+            if l != dim {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("HNSW vector of length {l} in a {dim}-dim snapshot")).into());
+            }
+            let mut vbytes = vec![0u8; dtype.width() * l];
             r.read_exact(&mut vbytes)?;
-            let vf32: Vec<f32> = bytemuck::cast_slice(&vbytes).to_vec();
-            vectors.push(vf32);
+            match &mut vectors {
+                VectorBuf::F32(buf) => buf.push(bytemuck::pod_collect_to_vec(&vbytes)),
+                VectorBuf::F16(buf) => buf.push(vbytes.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect()),
+            }
+        }
+        if vectors.len() != rev_map.len() {
+            let msg = format!("partial HNSW snapshot: {} ids but {} vectors", rev_map.len(), vectors.len());
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, msg).into());
         }
-        let expected = vectors.len().max(1000);
-        let hnsw = Hnsw::<f32, DistCosine>::new(16, expected, 16, 200, DistCosine {});
+        // Soft deletes flushed after the snapshot: keep their labels but leave them out of the graph
+        let deleted: HashSet<String> = match fs::read_to_string(dir.join(TOMBSTONES_FILE)) {
+            Ok(txt) => txt.lines().filter(|l| !l.is_empty()).map(str::to_string).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(e.into()),
+        };
+        // Snapshots without params were built with the defaults and a capacity of the count
+        let params = match fs::read_to_string(dir.join(PARAMS_FILE)) {
+            Ok(txt) => HnswParams::parse_sidecar(&txt)
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid HNSW params: {txt}")))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HnswParams::from(vectors.len().max(1000)),
+            Err(e) => return Err(e.into()),
+        };
+        let metric = match fs::read_to_string(dir.join(METRIC_FILE)) {
+            Ok(name) => HnswMetric::parse(name.trim())
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("unknown HNSW metric: {name}")))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HnswMetric::Cosine,
+            Err(e) => return Err(e.into()),
+        };
+        let normalized = match fs::read_to_string(dir.join(NORMALIZED_FILE)) {
+            Ok(v) => v.trim() == "true",
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e.into()),
+        };
+        let model_fingerprint = Self::snapshot_model_fingerprint(dir)?;
+        let hnsw = Graph::new(metric, HnswParams { max_elements: params.max_elements.max(vectors.len()), ..params });
         let mut id_map = HashMap::new();
-        for (i, v) in vectors.iter().enumerate() {
-            id_map.insert(rev_map[i].clone(), i);
-            let _ = hnsw.insert((&v[..], i));
+        let mut tombstones = HashSet::new();
+        for (i, cid) in rev_map.iter().enumerate().take(vectors.len()) {
+            if deleted.contains(cid) { tombstones.insert(i); continue; }
+            id_map.insert(cid.clone(), i);
+            hnsw.insert(&vectors.get(i), i);
         }
-        let this = Self { dim, hnsw, id_map, rev_map, vectors, tombstones: HashSet::new() };
+        let this = Self { dim, hnsw, id_map, rev_map, vectors, tombstones, unflushed_deletes: Vec::new(), bulk_build_min: DEFAULT_BULK_BUILD_MIN, normalized, model_fingerprint, params, from_previous: false };
         Ok(this)
     }
 }
@@ -118,42 +649,217 @@ impl VectorSearcher for HnswIndex {
 
     fn knn_ids(
         &self,
-        _store: &dyn ChunkStoreRead,
+        store: &dyn ChunkStoreRead,
         query: &[f32],
-        _filters: &[FilterClause],
+        filters: &[FilterClause],
+        opts: &SearchOptions,
+    ) -> Result<Vec<TextMatch>, crate::IndexError> {
+        // A query off the unit sphere of a normalized index (see `set_normalized`) is an error
+        self.check_norm(query)?;
+        Ok(self.knn_ids_expr(store, query, &FilterExpr::from_clauses(filters), opts))
+    }
+}
+
+impl HnswIndex {
+    /// HNSW has no filter pushdown; clauses are post-filtered with candidate widening.
+    pub fn caps(&self) -> crate::IndexCaps { crate::IndexCaps::NONE }
+
+    /// `knn_ids` over a filter tree. HNSW has no metadata, so every leaf (whatever its
+    /// `FilterKind`) is post-filtered against records resolved through `store`.
+    pub fn knn_ids_expr(
+        &self,
+        store: &dyn ChunkStoreRead,
+        query: &[f32],
+        expr: &FilterExpr,
         opts: &SearchOptions,
     ) -> Vec<TextMatch> {
         if query.len() != self.dim || opts.top_k == 0 { return Vec::new(); }
-        let ef_s = (opts.top_k.saturating_mul(opts.fetch_factor)).max(opts.top_k);
-        let knn = self.hnsw.search(query, opts.top_k * 5, ef_s);
-        let mut out = Vec::new();
-        // No prefilter; apply post-filter on metadata if needed (needs external store if required)
+        // ef is passed per search call, so overrides never leak into other queries
+        let ef_s = opts.hnsw_ef_search.unwrap_or_else(|| opts.fetch_n()).max(opts.top_k);
+        let restricted = opts.lang.is_some() || !expr.is_empty();
+        // Restrictions need a wider candidate pool since some neighbors get dropped
+        let knn_n = if restricted { ef_s.max(opts.top_k * 5) } else { opts.top_k * 5 };
+        // Tombstoned neighbors are skipped below; over-fetch in proportion to keep top_k filled
+        let live = self.rev_map.len().saturating_sub(self.tombstones.len()).max(1);
+        let knn_n = if self.tombstones.is_empty() { knn_n } else { (knn_n * self.rev_map.len()).div_ceil(live) };
+        let knn = self.hnsw.search(query, knn_n, ef_s);
+        let mut cands: Vec<TextMatch> = Vec::new();
         for el in knn {
             let label = el.d_id;
             if self.tombstones.contains(&label) { continue; }
+            let metric = self.metric();
+            let score = metric.score(el.distance);
+            if opts.min_score.is_some_and(|min| score < min) { continue; }
             let cid = &self.rev_map[label];
-            let dist = el.distance; // cosine distance (smaller is better)
-            let score = 1.0f32 - (dist as f32); // similarity approx
-            out.push(TextMatch { chunk_id: ChunkId(cid.clone()), score, raw_score: dist as f32 });
-            if out.len() >= opts.top_k { break; }
+            cands.push(TextMatch { chunk_id: ChunkId(cid.clone()), score, raw_score: metric.raw_score(el.distance) });
         }
-        out
+        if restricted {
+            // HNSW keeps no metadata; resolve language/doc/meta restrictions through the store
+            let ids: Vec<ChunkId> = cands.iter().map(|m| m.chunk_id.clone()).collect();
+            let allowed: HashSet<String> = match store.get_chunks_by_ids(&ids) {
+                Ok(recs) => recs
+                    .into_iter()
+                    .filter(|r| match &opts.lang {
+                        Some(l) => r.meta.get(chunk_model::META_LANG) == Some(l),
+                        None => true,
+                    })
+                    .filter(|r| expr.eval(&mut |c| record_matches(r, &c.op)))
+                    .map(|r| r.chunk_id.0)
+                    .collect(),
+                Err(_) => HashSet::new(),
+            };
+            cands.retain(|m| allowed.contains(&m.chunk_id.0));
+        }
+        cands.truncate(opts.top_k);
+        cands
     }
 }
 
 impl crate::VectorIndexMaintainer for HnswIndex {
     fn upsert_vectors(&mut self, items: &[(chunk_model::ChunkId, Vec<f32>)]) -> Result<(), crate::IndexError> {
-        self.upsert(items);
-        Ok(())
+        self.upsert(items)
     }
 
+    /// Soft delete: tombstone the labels (skipped by `knn_ids`) and queue the ids for
+    /// `flush_deletes`. Physical removal happens in `compact`.
     fn delete_by_ids(&mut self, ids: &[chunk_model::ChunkId]) -> Result<(), crate::IndexError> {
         for cid in ids {
-            if let Some(&lbl) = self.id_map.get(&cid.0) {
+            if let Some(lbl) = self.id_map.remove(&cid.0) {
                 self.tombstones.insert(lbl);
+                self.unflushed_deletes.push(cid.0.clone());
             }
         }
         Ok(())
     }
 }
+
+/// `<dir>.<suffix>` next to `dir`, used for the staging and previous copies of a snapshot.
+pub fn sibling_dir(dir: &Path, suffix: &str) -> PathBuf {
+    let name = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    dir.with_file_name(format!("{name}.{suffix}"))
+}
+
+/// Move the fully written `staged` directory into place at `live`. The contents it replaces
+/// are kept at `<live>.prev`; if the final rename fails they are moved back. Without a live
+/// dir (a crash between the two renames) `.prev` is the last good copy.
+pub fn swap_in_dir(live: &Path, staged: &Path) -> std::io::Result<()> {
+    let prev = sibling_dir(live, "prev");
+    if live.exists() {
+        if prev.exists() { fs::remove_dir_all(&prev)?; }
+        fs::rename(live, &prev)?;
+    }
+    if let Err(e) = fs::rename(staged, live) {
+        if !live.exists() && prev.exists() { let _ = fs::rename(&prev, live); }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Dimension a snapshot was built for: `dim.txt`, or the length prefix of the first
+/// stored vector for snapshots that predate it. `None` for an empty legacy snapshot.
+fn snapshot_dimension(dir: &Path) -> std::io::Result<Option<usize>> {
+    match fs::read_to_string(dir.join(DIM_FILE)) {
+        Ok(v) => return v.trim().parse().map(Some)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid HNSW dimension: {v}"))),
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        Err(_) => {}
+    }
+    let vec_file = if dir.join(VECTORS_F16_FILE).exists() { VECTORS_F16_FILE } else { VECTORS_F32_FILE };
+    let mut len_buf = [0u8; 4];
+    use std::io::Read;
+    match fs::File::open(dir.join(vec_file)).and_then(|mut f| f.read_exact(&mut len_buf)) {
+        Ok(()) => Ok(Some(u32::from_le_bytes(len_buf) as usize)),
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::UnexpectedEof) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// `v` as IEEE 754 half-precision bits, the form `VectorDtype::F16` stores.
+pub fn encode_f16(v: &[f32]) -> Vec<u16> { v.iter().map(|&x| f32_to_f16(x)).collect() }
+
+/// Widen half-precision bits from `encode_f16` back to f32.
+pub fn decode_f16(bits: &[u16]) -> Vec<f32> { bits.iter().map(|&h| f16_to_f32(h)).collect() }
+
+/// f32 -> IEEE 754 half bits, rounding to nearest even.
+fn f32_to_f16(x: f32) -> u16 {
+    let b = x.to_bits();
+    let sign = ((b >> 16) & 0x8000) as u16;
+    let exp = ((b >> 23) & 0xff) as i32;
+    let man = b & 0x7f_ffff;
+    if exp == 0xff {
+        return sign | 0x7c00 | if man != 0 { 0x200 } else { 0 };
+    }
+    let e = exp - 127 + 15;
+    if e >= 0x1f { return sign | 0x7c00; }
+    let (half, rem, halfway) = if e <= 0 {
+        // Subnormal (or zero) in half precision
+        if e < -10 { return sign; }
+        let m = man | 0x80_0000;
+        let shift = (14 - e) as u32;
+        (m >> shift, m & ((1 << shift) - 1), 1u32 << (shift - 1))
+    } else {
+        (((e as u32) << 10) | (man >> 13), man & 0x1fff, 0x1000)
+    };
+    // A carry out of the mantissa correctly bumps the exponent (up to infinity)
+    let rounded = if rem > halfway || (rem == halfway && half & 1 == 1) { half + 1 } else { half };
+    sign | rounded as u16
+}
+
+/// IEEE 754 half bits -> f32 (exact).
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let man = (h & 0x3ff) as u32;
+    let bits = match exp {
+        0 => {
+            let v = man as f32 * 2f32.powi(-24);
+            return if sign != 0 { -v } else { v };
+        }
+        0x1f => sign | 0x7f80_0000 | (man << 13),
+        _ => sign | ((exp + 127 - 15) << 23) | (man << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Evaluate one filter op against a record, consistently with the SQL prefilter.
+fn record_matches(rec: &chunk_model::ChunkRecord, op: &FilterOp) -> bool {
+    match op {
+        FilterOp::DocIdEq(v) => &rec.doc_id.0 == v,
+        FilterOp::DocIdIn(vs) => vs.iter().any(|v| v == &rec.doc_id.0),
+        FilterOp::SourceUriPrefix { prefix, case_insensitive } => crate::str_filter_matches(&rec.source_uri, prefix, crate::StrMatch::Prefix, *case_insensitive),
+        FilterOp::SourceFilenamePrefix(n) => crate::filename_filter_matches(&rec.source_uri, n, true),
+        FilterOp::SourceFilenameContains(n) => crate::filename_filter_matches(&rec.source_uri, n, false),
+        FilterOp::MetaEq { key, value, case_insensitive } => rec.meta.get(key).is_some_and(|v| crate::str_filter_matches(v, value, crate::StrMatch::Eq, *case_insensitive)),
+        FilterOp::MetaIn { key, values } => rec.meta.get(key).is_some_and(|v| values.contains(v)),
+        FilterOp::MetaPrefix { key, prefix, case_insensitive } => rec.meta.get(key).is_some_and(|v| crate::str_filter_matches(v, prefix, crate::StrMatch::Prefix, *case_insensitive)),
+        FilterOp::MetaContains { key, substring, case_insensitive } => rec.meta.get(key).is_some_and(|v| crate::str_filter_matches(v, substring, crate::StrMatch::Contains, *case_insensitive)),
+        FilterOp::DocIdNotIn(vs) => !vs.iter().any(|v| v == &rec.doc_id.0),
+        // Absent key counts as "not equal", matching the SQL `IS NOT` prefilter
+        FilterOp::MetaNe { key, value } => rec.meta.get(key) != Some(value),
+        FilterOp::BlockKindIn(kinds) => kinds.is_empty() || rec.block_kinds.iter().any(|k| kinds.contains(k)),
+        FilterOp::SectionPathPrefix(prefix) => rec.section_path.as_deref().unwrap_or_default().starts_with(prefix),
+        FilterOp::RangeNumeric { key, min, max, min_incl, max_incl } => {
+            let Some(n) = field_value(rec, key).and_then(|v| v.parse::<f64>().ok()) else { return false };
+            let lo_ok = match min { Some(lo) => if *min_incl { n >= *lo } else { n > *lo }, None => true };
+            let hi_ok = match max { Some(hi) => if *max_incl { n <= *hi } else { n < *hi }, None => true };
+            lo_ok && hi_ok
+        }
+        FilterOp::RangeIsoDate { key, start, end, start_incl, end_incl } => {
+            let Some(v) = field_value(rec, key) else { return false };
+            let lo_ok = match start { Some(s) => if *start_incl { v >= *s } else { v > *s }, None => true };
+            let hi_ok = match end { Some(e) => if *end_incl { v <= *e } else { v < *e }, None => true };
+            lo_ok && hi_ok
+        }
+    }
+}
+
+/// Reserved record fields by name, falling back to `meta[key]`.
+fn field_value(rec: &chunk_model::ChunkRecord, key: &str) -> Option<String> {
+    match key {
+        "extracted_at" => Some(rec.extracted_at.clone()),
+        "page_start" => rec.page_start.map(|v| v.to_string()),
+        "page_end" => rec.page_end.map(|v| v.to_string()),
+        _ => rec.meta.get(key).cloned(),
+    }
+}
 
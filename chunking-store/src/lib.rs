@@ -4,8 +4,9 @@ pub mod fts5_index;
 pub mod tantivy_index;
 pub mod hnsw_index;
 pub mod orchestrator;
+pub mod embed_cache;
 
-use chunk_model::ChunkRecord;
+use chunk_model::{BlockKind, ChunkRecord};
 
 /// Thin abstraction for the primary storage engine (DB-agnostic).
 pub trait ChunkPrimaryStore {
@@ -19,10 +20,146 @@ pub trait ChunkPrimaryStore {
 
 /// Placeholder for text search engines (FTS5/Tantivy, etc.).
 /// Concrete engines will expose their own constructors; common querying shape is unified via helpers.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct SearchHit {
     pub chunk: ChunkRecord,
+    /// Fused relevance. Per-signal scores are normalized to 0..1 (larger is better) and fused
+    /// with weights normalized to sum to 1, so this is nominally 0..1 as well; backends whose
+    /// raw scores escape that range can push it slightly outside. Use `confidence()` for display.
+    pub score: f32,
+    /// True when the hit was filled in by `SearchOptions::empty_fallback` rather than
+    /// relevance ranking (its score is 0.0 and carries no meaning).
+    pub fallback: bool,
+    /// Per-signal scores behind `score`, set by fused (hybrid) searches; `None` elsewhere.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<ScoreBreakdown>,
+}
+
+/// How a fused score was made up: each signal's normalized score (`None` when the chunk was
+/// not among that signal's candidates) and the normalized weight it was fused with. `text` is
+/// the engine's default query; `text_and`/`text_or` are the token-AND/OR Tantivy queries used
+/// by four-way fusion and stay `None` (weight 0) otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize)]
+pub struct ScoreBreakdown {
+    pub text: Option<f32>,
+    pub text_and: Option<f32>,
+    pub text_or: Option<f32>,
+    pub vector: Option<f32>,
+    pub text_weight: f32,
+    pub text_and_weight: f32,
+    pub text_or_weight: f32,
+    pub vector_weight: f32,
+}
+
+impl ScoreBreakdown {
+    /// Weighted contribution of all text signals to the fused score.
+    pub fn text_contribution(&self) -> f32 {
+        self.text_weight * self.text.unwrap_or(0.0)
+            + self.text_and_weight * self.text_and.unwrap_or(0.0)
+            + self.text_or_weight * self.text_or.unwrap_or(0.0)
+    }
+
+    /// Weighted contribution of the vector signal to the fused score.
+    pub fn vector_contribution(&self) -> f32 {
+        self.vector_weight * self.vector.unwrap_or(0.0)
+    }
+
+    /// The fused score: the sum of both contributions.
+    pub fn fused(&self) -> f32 {
+        self.text_contribution() + self.vector_contribution()
+    }
+}
+
+impl SearchHit {
+    /// Display confidence in 0..=1: the fused score clamped to its nominal range.
+    /// Monotonic (non-decreasing) in `score`; fallback hits and NaN scores report 0.0.
+    pub fn confidence(&self) -> f32 {
+        if self.fallback || self.score.is_nan() { return 0.0; }
+        self.score.clamp(0.0, 1.0)
+    }
+}
+
+/// Hits of one document, for file-centric result lists ("Report.pdf — p.42 — …matched text…").
+#[derive(Debug, Clone)]
+pub struct DocGroup {
+    pub doc_id: chunk_model::DocumentId,
+    pub source_uri: String,
+    /// Score of the best hit.
     pub score: f32,
+    /// `page_start` of the best hit; None for unpaged sources.
+    pub best_page: Option<u32>,
+    /// Excerpt of the best hit around the first query term (see `snippet_around`).
+    pub snippet: String,
+    /// All hits of the document, best first.
+    pub hits: Vec<SearchHit>,
+}
+
+/// Group hits by `doc_id`, keeping documents in order of their best hit. Each group reports
+/// the best hit's page and a snippet of at most `snippet_chars` characters (plus ellipses).
+pub fn group_hits_by_doc(mut hits: Vec<SearchHit>, query: &str, snippet_chars: usize) -> Vec<DocGroup> {
+    hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    let mut groups: Vec<DocGroup> = Vec::new();
+    let mut pos: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    for hit in hits {
+        match pos.get(&hit.chunk.doc_id.0) {
+            Some(&i) => groups[i].hits.push(hit),
+            None => {
+                pos.insert(hit.chunk.doc_id.0.clone(), groups.len());
+                groups.push(DocGroup {
+                    doc_id: hit.chunk.doc_id.clone(),
+                    source_uri: hit.chunk.source_uri.clone(),
+                    score: hit.score,
+                    best_page: hit.chunk.page_start,
+                    snippet: snippet_around(&hit.chunk.text, query, snippet_chars),
+                    hits: vec![hit],
+                });
+            }
+        }
+    }
+    groups
+}
+
+/// Keep at most `max_per_doc` hits per `doc_id` from ranked `hits`, preserving their order.
+pub fn cap_hits_per_doc(hits: &mut Vec<SearchHit>, max_per_doc: usize) {
+    let mut seen: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    hits.retain(|h| {
+        let n = seen.entry(h.chunk.doc_id.0.clone()).or_insert(0);
+        *n += 1;
+        *n <= max_per_doc
+    });
+}
+
+/// Window of `max_chars` characters around the earliest case-insensitive occurrence of any
+/// whitespace-separated query term (the text start when none occurs), with `…` marking cuts.
+pub fn snippet_around(text: &str, query: &str, max_chars: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max_chars { return text.to_string(); }
+    let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let lower: Vec<char> = chars.iter().map(|c| fold(*c)).collect();
+    let first = query
+        .split_whitespace()
+        .filter_map(|term| {
+            let term: Vec<char> = term.chars().map(fold).collect();
+            lower.windows(term.len()).position(|w| w == term.as_slice())
+        })
+        .min()
+        .unwrap_or(0);
+    // Lead in with a little context before the match
+    let start = first.saturating_sub(max_chars / 3).min(chars.len() - max_chars);
+    let end = start + max_chars;
+    let mut out = String::new();
+    if start > 0 { out.push('…'); }
+    out.extend(&chars[start..end]);
+    if end < chars.len() { out.push('…'); }
+    out
+}
+
+/// Share of each weight as a percentage of their sum (negative weights count as 0).
+/// Returns all zeros when no weight is positive, so front ends can render signal mixes uniformly.
+pub fn to_percentages(weights: &[f32]) -> Vec<f32> {
+    let total: f32 = weights.iter().map(|w| w.max(0.0)).sum();
+    if total <= 0.0 { return vec![0.0; weights.len()]; }
+    weights.iter().map(|w| w.max(0.0) / total * 100.0).collect()
 }
 
 /// Store-agnostic text match result (IDs only). Useful for composing with any primary store.
@@ -31,16 +168,28 @@ pub struct TextMatch {
     pub chunk_id: chunk_model::ChunkId,
     /// Normalized score (larger is better, preferably in 0..1).
     pub score: f32,
-    /// Backend-native raw score (for diagnostics/fusion).
+    /// Backend-native raw score (for diagnostics/fusion): BM25 for text backends; for HNSW
+    /// the metric's own value, i.e. similarity for cosine/dot and distance for L2
+    /// (see `hnsw_index::HnswMetric`).
     pub raw_score: f32,
 }
 
 /// Optional read-side abstraction so indexes can fetch full records in a store-agnostic way.
 pub trait ChunkStoreRead {
+    /// Records for `ids` in the same order as `ids`; ids not in the store are skipped.
     fn get_chunks_by_ids(
         &self,
         ids: &[chunk_model::ChunkId],
     ) -> Result<Vec<ChunkRecord>, StoreError>;
+    /// One entry per input id, in input order, `None` where the id is not in the store.
+    fn get_chunks_by_ids_keep_missing(
+        &self,
+        ids: &[chunk_model::ChunkId],
+    ) -> Result<Vec<Option<ChunkRecord>>, StoreError> {
+        let found: std::collections::HashMap<String, ChunkRecord> =
+            self.get_chunks_by_ids(ids)?.into_iter().map(|r| (r.chunk_id.0.clone(), r)).collect();
+        Ok(ids.iter().map(|id| found.get(&id.0).cloned()).collect())
+    }
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
@@ -48,54 +197,281 @@ pub trait ChunkStoreRead {
 // Filters and query options
 // ------------------------------
 
-#[derive(Debug, Clone, PartialEq)]
+/// Ops with a `case_insensitive` flag fold ASCII letters only (`A`-`Z` to `a`-`z`), the same
+/// as SQLite's `NOCASE` and `LIKE`. Other characters compare as-is: `É`/`é` and full-width
+/// `Ｔ`/`ｔ` stay distinct, and nothing is accent-folded. Japanese has no case, so the flag
+/// does not change matches on kana or kanji.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum FilterOp {
     DocIdEq(String),
     DocIdIn(Vec<String>),
-    SourceUriPrefix(String),
-    MetaEq { key: String, value: String },
+    /// `source_uri` starts with `prefix`; `%` and `_` are literal. Also reads the older
+    /// `{"SourceUriPrefix": "<prefix>"}` form as a case-sensitive prefix.
+    #[serde(deserialize_with = "source_uri_prefix_compat")]
+    SourceUriPrefix { prefix: String, #[serde(default)] case_insensitive: bool },
+    /// Basename of `source_uri` (see `source_filename`) starts with this string, case-sensitively.
+    /// Also matches the percent-encoded form of the string, so `"2024 q1"` finds `2024%20q1.pdf`.
+    /// An empty string imposes no restriction.
+    SourceFilenamePrefix(String),
+    /// Basename of `source_uri` contains this string; same rules as `SourceFilenamePrefix`.
+    SourceFilenameContains(String),
+    MetaEq { key: String, value: String, #[serde(default)] case_insensitive: bool },
     MetaIn { key: String, values: Vec<String> },
+    /// `meta[key]` starts with `prefix` (case-sensitive). Chunks without the key never match.
+    MetaPrefix { key: String, prefix: String, #[serde(default)] case_insensitive: bool },
+    /// `meta[key]` contains `substring` (case-sensitive). Chunks without the key never match.
+    MetaContains { key: String, substring: String, #[serde(default)] case_insensitive: bool },
+    /// Exclude these documents. An empty list excludes nothing.
+    DocIdNotIn(Vec<String>),
+    /// `meta[key] != value`; chunks without the key count as not equal (they match).
+    MetaNe { key: String, value: String },
+    /// Chunks covering at least one block of these kinds (`ChunkRecord::block_kinds`).
+    /// An empty list imposes no restriction.
+    BlockKindIn(Vec<BlockKind>),
+    /// Chunks whose `section_path` starts with these components (each compared exactly).
+    /// An empty prefix imposes no restriction.
+    SectionPathPrefix(Vec<String>),
     /// Numeric range on a field (e.g., meta value). Missing/parse-failed values do not match.
     RangeNumeric { key: String, min: Option<f64>, max: Option<f64>, min_incl: bool, max_incl: bool },
     /// ISO 8601 string range (lexicographic compare). Works for fields like `extracted_at` or ISO dates in meta.
     RangeIsoDate { key: String, start: Option<String>, end: Option<String>, start_incl: bool, end_incl: bool },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Fields of `FilterOp::SourceUriPrefix` from either its struct form or the older bare string.
+fn source_uri_prefix_compat<'de, D: serde::Deserializer<'de>>(d: D) -> Result<(String, bool), D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Legacy(String),
+        Current { prefix: String, #[serde(default)] case_insensitive: bool },
+    }
+    Ok(match <Repr as serde::Deserialize>::deserialize(d)? {
+        Repr::Legacy(prefix) => (prefix, false),
+        Repr::Current { prefix, case_insensitive } => (prefix, case_insensitive),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum FilterKind {
     Must,
     PreferPre,
     PostOnly,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct FilterClause {
     pub kind: FilterKind,
     pub op: FilterOp,
 }
 
+/// Boolean tree of filter clauses. Plain `&[FilterClause]` lists are an implicit `And`.
+/// `FilterKind` still applies per leaf: each index decides per leaf whether it can prefilter
+/// or must post-filter it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    And(Vec<FilterExpr>),
+    Or(Vec<FilterExpr>),
+    Leaf(FilterClause),
+}
+
+impl FilterExpr {
+    /// Wrap a clause list into the equivalent implicit `And`.
+    pub fn from_clauses(clauses: &[FilterClause]) -> Self {
+        FilterExpr::And(clauses.iter().cloned().map(FilterExpr::Leaf).collect())
+    }
+
+    /// True when the tree contains no leaf at all.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            FilterExpr::Leaf(_) => false,
+            FilterExpr::And(c) | FilterExpr::Or(c) => c.iter().all(FilterExpr::is_empty),
+        }
+    }
+
+    /// Evaluate the tree with a per-leaf predicate. Empty `And` is true, empty `Or` is false.
+    pub fn eval(&self, leaf: &mut impl FnMut(&FilterClause) -> bool) -> bool {
+        match self {
+            FilterExpr::Leaf(c) => leaf(c),
+            FilterExpr::And(c) => c.iter().all(|e| e.eval(leaf)),
+            FilterExpr::Or(c) => c.iter().any(|e| e.eval(leaf)),
+        }
+    }
+}
+
+/// Basename of a source URI: the text after the last `/` or `\`, or the whole URI when it has
+/// no separator. Nothing is stripped or decoded (query strings stay, `%20` stays `%20`).
+pub fn source_filename(uri: &str) -> &str {
+    uri.rfind(['/', '\\']).map_or(uri, |i| &uri[i + 1..])
+}
+
+/// Needles a filename filter compares against the raw basename: the string itself and, when
+/// different, its percent-encoded form (RFC 3986 unreserved characters kept, uppercase hex).
+pub fn filename_needles(s: &str) -> Vec<String> {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{b:02X}"));
+        }
+    }
+    if encoded == s { vec![s.to_string()] } else { vec![s.to_string(), encoded] }
+}
+
+/// `FilterOp::SourceFilenamePrefix` / `SourceFilenameContains` evaluated on a record's URI.
+pub fn filename_filter_matches(uri: &str, needle: &str, prefix: bool) -> bool {
+    let name = source_filename(uri);
+    filename_needles(needle).iter().any(|n| if prefix { name.starts_with(n.as_str()) } else { name.contains(n.as_str()) })
+}
+
+/// How `str_filter_matches` compares a value with a needle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StrMatch {
+    Eq,
+    Prefix,
+    Contains,
+}
+
+/// Post-filter counterpart of the SQL string ops, folding ASCII case when `case_insensitive`.
+pub(crate) fn str_filter_matches(value: &str, needle: &str, how: StrMatch, case_insensitive: bool) -> bool {
+    if case_insensitive {
+        return str_filter_matches(&value.to_ascii_lowercase(), &needle.to_ascii_lowercase(), how, false);
+    }
+    match how {
+        StrMatch::Eq => value == needle,
+        StrMatch::Prefix => value.starts_with(needle),
+        StrMatch::Contains => value.contains(needle),
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct IndexCaps {
     pub can_prefilter_doc_id_eq: bool,
     pub can_prefilter_doc_id_in: bool,
     pub can_prefilter_source_prefix: bool,
     pub can_prefilter_meta: bool,
+    /// Exact (case-sensitive) `MetaEq` and `MetaIn` only; implied by `can_prefilter_meta`.
+    pub can_prefilter_meta_eq: bool,
     pub can_prefilter_range_numeric: bool,
     pub can_prefilter_range_date: bool,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl IndexCaps {
+    /// No pushdown at all: every clause is post-filtered (e.g., HNSW).
+    pub const NONE: IndexCaps = IndexCaps {
+        can_prefilter_doc_id_eq: false,
+        can_prefilter_doc_id_in: false,
+        can_prefilter_source_prefix: false,
+        can_prefilter_meta: false,
+        can_prefilter_meta_eq: false,
+        can_prefilter_range_numeric: false,
+        can_prefilter_range_date: false,
+    };
+
+    /// Whether an index with these caps can apply `op` before ranking.
+    /// Block kinds and section paths are JSON columns, and filename ops need SQL string functions
+    /// on `source_uri`, so they follow the meta capability.
+    pub fn supports(&self, op: &FilterOp) -> bool {
+        match op {
+            FilterOp::DocIdEq(_) => self.can_prefilter_doc_id_eq,
+            FilterOp::DocIdIn(_) | FilterOp::DocIdNotIn(_) => self.can_prefilter_doc_id_in,
+            FilterOp::SourceUriPrefix { .. } => self.can_prefilter_source_prefix,
+            FilterOp::MetaEq { case_insensitive: false, .. } | FilterOp::MetaIn { .. } => self.can_prefilter_meta || self.can_prefilter_meta_eq,
+            FilterOp::MetaEq { .. } | FilterOp::MetaNe { .. }
+            | FilterOp::MetaPrefix { .. } | FilterOp::MetaContains { .. }
+            | FilterOp::BlockKindIn(_) | FilterOp::SectionPathPrefix(_)
+            | FilterOp::SourceFilenamePrefix(_) | FilterOp::SourceFilenameContains(_) => self.can_prefilter_meta,
+            FilterOp::RangeNumeric { .. } => self.can_prefilter_range_numeric,
+            FilterOp::RangeIsoDate { .. } => self.can_prefilter_range_date,
+        }
+    }
+}
+
+/// Where a backend applies a filter clause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterPlacement {
+    /// Pushed into the backend query; does not cost recall.
+    Prefilter,
+    /// Checked on fetched candidates; too small a fetch can drop matches.
+    PostFilter,
+}
+
+/// Pushdown decision for one clause on one backend (see `plan_filter_pushdown`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterPlan {
+    pub backend: &'static str,
+    pub clause: FilterClause,
+    pub placement: FilterPlacement,
+}
+
+/// Explain how a backend with `caps` splits `filters`: supported ops are prefiltered
+/// unless the clause is `FilterKind::PostOnly`.
+pub fn plan_filter_pushdown(backend: &'static str, caps: &IndexCaps, filters: &[FilterClause]) -> Vec<FilterPlan> {
+    filters
+        .iter()
+        .map(|f| {
+            let placement = if f.kind != FilterKind::PostOnly && caps.supports(&f.op) {
+                FilterPlacement::Prefilter
+            } else {
+                FilterPlacement::PostFilter
+            };
+            FilterPlan { backend, clause: f.clone(), placement }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
 pub struct SearchOptions {
     pub top_k: usize,
     pub fetch_factor: usize,
+    /// Restrict results to chunks tagged with this language (`meta["lang"]`), e.g. "ja".
+    pub lang: Option<String>,
+    /// What to return when no signal produced any hit. Default `None` (empty result).
+    pub empty_fallback: EmptyFallback,
+    /// Per-query HNSW `ef_search` (candidate list size): higher trades latency for recall.
+    /// `None` uses `top_k * fetch_factor` (at least `top_k`). Never changes index state.
+    pub hnsw_ef_search: Option<usize>,
+    /// Floor on the candidates each signal fetches before fusion, so a tiny `top_k` still
+    /// leaves room for cross-signal agreement. 0 keeps `top_k * fetch_factor`. Searchers still
+    /// return at most `top_k` vector matches; hybrid search widens the signals' `top_k` to
+    /// `fetch_n()` and truncates only the fused result to `top_k`.
+    pub min_fetch: usize,
+    /// Drop vector matches whose `score` (cosine similarity for a cosine index) is below
+    /// this before fusion, so narrow queries return fewer than `top_k` hits instead of
+    /// padding with weak neighbors. `None` keeps every candidate.
+    pub min_score: Option<f32>,
+    /// Cap on hits from any one `doc_id`, keeping each document's best-ranked ones, so a
+    /// single long document cannot crowd out the rest. Applied before truncating to `top_k`;
+    /// `None` keeps every hit.
+    pub max_per_doc: Option<usize>,
 }
 
 impl Default for SearchOptions {
     fn default() -> Self {
-        SearchOptions { top_k: 10, fetch_factor: 10 }
+        SearchOptions { top_k: 10, fetch_factor: 10, lang: None, empty_fallback: EmptyFallback::None, hnsw_ef_search: None, min_fetch: 0, min_score: None, max_per_doc: None }
     }
 }
 
+impl SearchOptions {
+    /// Candidates a signal fetches: `max(top_k * fetch_factor, min_fetch)`, at least `top_k`.
+    pub fn fetch_n(&self) -> usize {
+        self.top_k.saturating_mul(self.fetch_factor).max(self.min_fetch).max(self.top_k)
+    }
+}
+
+/// Policy for filling an otherwise empty hybrid result. Fallback hits honor the filters
+/// and are flagged via `SearchHit::fallback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyFallback {
+    /// Return nothing.
+    #[default]
+    None,
+    /// Return the most recently extracted chunks.
+    Recent,
+    /// Return a random sample of chunks.
+    Random,
+}
+
 pub trait TextSearcher {
     fn name(&self) -> &'static str;
     fn caps(&self) -> IndexCaps;
@@ -106,6 +482,25 @@ pub trait TextSearcher {
         filters: &[FilterClause],
         opts: &SearchOptions,
     ) -> Vec<TextMatch>;
+    /// `search_ids` plus the best-matching excerpt of each hit. Backends that cannot locate
+    /// term offsets (e.g., FTS5) keep this default and return `None` snippets.
+    fn search_ids_with_snippets(
+        &self,
+        store: &dyn ChunkStoreRead,
+        query: &str,
+        filters: &[FilterClause],
+        opts: &SearchOptions,
+    ) -> Vec<(TextMatch, Option<TextSnippet>)> {
+        self.search_ids(store, query, filters, opts).into_iter().map(|m| (m, None)).collect()
+    }
+}
+
+/// Best-matching window of a text hit. `highlights` are byte ranges into `text` covering the
+/// matched terms, for `<mark>`-style rendering.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextSnippet {
+    pub text: String,
+    pub highlights: Vec<(u32, u32)>,
 }
 
 // ---------------
@@ -116,13 +511,14 @@ pub trait VectorSearcher {
     fn name(&self) -> &'static str;
     fn dimension(&self) -> usize;
     /// KNN over vectors. Implementations may apply pre-filters if supported; otherwise use post-filtering.
+    /// A query the index cannot serve (e.g. violating its normalization) is an error, not "no hits".
     fn knn_ids(
         &self,
         store: &dyn ChunkStoreRead,
         query: &[f32],
         filters: &[FilterClause],
         opts: &SearchOptions,
-    ) -> Vec<TextMatch>;
+    ) -> Result<Vec<TextMatch>, IndexError>;
 }
 
 // ---------------
@@ -142,6 +538,9 @@ pub trait TextIndexMaintainer {
     fn delete_by_ids(&self, ids: &[chunk_model::ChunkId]) -> Result<(), IndexError>;
     /// Optional helper: delete by document ids (metadata-driven)
     fn delete_by_doc_ids(&self, doc_ids: &[String]) -> Result<(), IndexError> { let _ = doc_ids; Ok(()) }
+    /// True when the index lives in the primary store and follows its transactions (e.g.
+    /// trigger-maintained FTS5), so rolling the DB back also undoes it.
+    fn in_primary_store(&self) -> bool { false }
 }
 
 pub trait VectorIndexMaintainer {
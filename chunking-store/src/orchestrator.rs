@@ -1,6 +1,6 @@
-use crate::sqlite_repo::SqliteRepo;
+use crate::sqlite_repo::{SqliteRepo, StagedChunks};
 use crate::{FilterClause, TextIndexMaintainer, VectorIndexMaintainer, StoreError, ChunkPrimaryStore};
-use chunk_model::{ChunkRecord, ChunkId};
+use chunk_model::{ChunkRecord, ChunkId, FileRecord};
 
 #[derive(Debug, thiserror::Error)]
 pub enum OrchestratorError {
@@ -8,6 +8,10 @@ pub enum OrchestratorError {
     Store(#[from] StoreError),
     #[error("index error: {0}")]
     Index(String),
+    /// A step failed after backends that cannot roll back (`applied`) took the batch; the DB
+    /// itself was rolled back.
+    #[error("partial ingest: {error} (DB rolled back; already applied to {applied:?})")]
+    PartialIngest { applied: Vec<String>, error: String },
 }
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -37,25 +41,53 @@ pub fn delete_by_filter_orchestrated(
     loop {
         let ids = repo.list_chunk_ids_by_filter(filters, batch, 0)?;
         if ids.is_empty() { break; }
-        report.total_ids += ids.len();
-        report.batches += 1;
-
-        let n = repo.delete_by_ids(&ids)?;
-        report.db_deleted += n;
+        delete_batch(repo, &ids, text_indexes, vector_indexes, &mut report)?;
+    }
+    Ok(report)
+}
 
-        for ti in text_indexes {
-            ti.delete_by_ids(&ids).map_err(|e| OrchestratorError::Index(format!("{e}")))?;
-            report.text_delete_attempts += ids.len();
-        }
-        for vi in vector_indexes.iter_mut() {
-            vi.delete_by_ids(&ids).map_err(|e| OrchestratorError::Index(format!("{e}")))?;
-            report.vector_delete_attempts += ids.len();
-        }
+/// Delete known chunk IDs in batches of `batch_size`, each batch from the DB first and then
+/// from the indexes, like `delete_by_filter_orchestrated`.
+pub fn delete_ids_orchestrated(
+    repo: &mut SqliteRepo,
+    ids: &[ChunkId],
+    batch_size: usize,
+    text_indexes: &[&dyn TextIndexMaintainer],
+    vector_indexes: &mut [&mut dyn VectorIndexMaintainer],
+) -> Result<DeleteReport, OrchestratorError> {
+    let mut report = DeleteReport::default();
+    for ids in ids.chunks(batch_size.max(1)) {
+        delete_batch(repo, ids, text_indexes, vector_indexes, &mut report)?;
     }
     Ok(report)
 }
 
-/// Ingest orchestrator: upsert into DB, then update text/vector indexes.
+fn delete_batch(
+    repo: &mut SqliteRepo,
+    ids: &[ChunkId],
+    text_indexes: &[&dyn TextIndexMaintainer],
+    vector_indexes: &mut [&mut dyn VectorIndexMaintainer],
+    report: &mut DeleteReport,
+) -> Result<(), OrchestratorError> {
+    report.total_ids += ids.len();
+    report.batches += 1;
+
+    let n = repo.delete_by_ids(ids)?;
+    report.db_deleted += n;
+
+    for ti in text_indexes {
+        ti.delete_by_ids(ids).map_err(|e| OrchestratorError::Index(format!("{e}")))?;
+        report.text_delete_attempts += ids.len();
+    }
+    for vi in vector_indexes.iter_mut() {
+        vi.delete_by_ids(ids).map_err(|e| OrchestratorError::Index(format!("{e}")))?;
+        report.vector_delete_attempts += ids.len();
+    }
+    Ok(())
+}
+
+/// Ingest orchestrator: upsert into DB, then update text/vector indexes. The DB rows are
+/// committed only once every index accepted the batch (see `stage_ingest_chunks`).
 pub fn ingest_chunks_orchestrated(
     repo: &mut SqliteRepo,
     records: &[ChunkRecord],
@@ -64,16 +96,74 @@ pub fn ingest_chunks_orchestrated(
     vectors: Option<&[(ChunkId, Vec<f32>)]>,
 ) -> Result<(), OrchestratorError> {
     if records.is_empty() { return Ok(()); }
-    repo.upsert_chunks(records.to_vec())?;
+    stage_ingest_chunks(repo, records, text_indexes, vector_indexes, vectors)?.commit()?;
     // Ensure FTS5 is populated in rare cases where triggers lag at first creation
     let _ = repo.maybe_rebuild_fts();
-    for ti in text_indexes {
-        ti.upsert(records).map_err(|e| OrchestratorError::Index(format!("{e}")))?;
-    }
+    Ok(())
+}
+
+/// Stage an ingest: upsert the records in an open DB transaction, then hand the batch to the
+/// vector indexes and the text indexes. Any failure rolls the DB back, so for that batch
+/// either all backends take it (after `StagedIngest::commit`) or none does. Two limits: a
+/// failed vector index leaves its in-memory changes for the caller to discard, and text
+/// indexes outside the DB (`in_primary_store` false) that already took the batch cannot be
+/// undone; those are reported in `OrchestratorError::PartialIngest`.
+pub fn stage_ingest_chunks<'a>(
+    repo: &'a mut SqliteRepo,
+    records: &[ChunkRecord],
+    text_indexes: &[&dyn TextIndexMaintainer],
+    vector_indexes: &mut [&mut dyn VectorIndexMaintainer],
+    vectors: Option<&[(ChunkId, Vec<f32>)]>,
+) -> Result<StagedIngest<'a>, OrchestratorError> {
+    let mut staged = StagedIngest { db: repo.stage_chunks(records)?, applied: Vec::new() };
     if let Some(v) = vectors {
         for vi in vector_indexes.iter_mut() {
-            vi.upsert_vectors(v).map_err(|e| OrchestratorError::Index(format!("{e}")))?;
+            if let Err(e) = vi.upsert_vectors(v) { return Err(staged.abort(e.to_string())); }
         }
     }
-    Ok(())
+    for (i, ti) in text_indexes.iter().enumerate() {
+        if let Err(e) = ti.upsert(records) { return Err(staged.abort(e.to_string())); }
+        if !ti.in_primary_store() { staged.applied.push(format!("text index #{i}")); }
+    }
+    Ok(staged)
+}
+
+/// A batch the indexes already hold while its DB rows are still uncommitted. Save index
+/// snapshots before `commit` so a failed save can `abort` and leave the DB untouched;
+/// dropping it also rolls the DB back.
+pub struct StagedIngest<'a> {
+    db: StagedChunks<'a>,
+    applied: Vec<String>,
+}
+
+impl StagedIngest<'_> {
+    pub fn commit(self) -> Result<(), OrchestratorError> {
+        let applied = self.applied;
+        self.db.commit().map_err(|e| if applied.is_empty() { e.into() } else { OrchestratorError::PartialIngest { applied, error: e.to_string() } })
+    }
+
+    /// Add autocomplete terms in the staged DB transaction (see `SqliteRepo::add_suggest_terms`).
+    pub fn add_suggest_terms(&self, terms: &[(String, u64)], max_terms: usize) -> Result<(), StoreError> {
+        self.db.add_suggest_terms(terms, max_terms)
+    }
+
+    /// Write `file` in the staged DB transaction, so it commits or rolls back with the chunks.
+    pub fn upsert_file(&self, file: &FileRecord) -> Result<(), StoreError> {
+        self.db.upsert_file(file)
+    }
+
+    /// Hand the batch to one more text index, e.g. once snapshots are saved. An index outside
+    /// the DB is recorded as `name` in a later `PartialIngest`; a failure rolls the DB back.
+    pub fn upsert_text_index(mut self, name: &str, index: &dyn TextIndexMaintainer, records: &[ChunkRecord]) -> Result<Self, OrchestratorError> {
+        if let Err(e) = index.upsert(records) { return Err(self.abort(e.to_string())); }
+        if !index.in_primary_store() { self.applied.push(name.to_string()); }
+        Ok(self)
+    }
+
+    /// Roll the DB back after a failed step and turn `error` into the error to report.
+    pub fn abort(self, error: impl Into<String>) -> OrchestratorError {
+        let _ = self.db.rollback();
+        let error = error.into();
+        if self.applied.is_empty() { OrchestratorError::Index(error) } else { OrchestratorError::PartialIngest { applied: self.applied, error } }
+    }
 }
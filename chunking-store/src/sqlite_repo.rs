@@ -3,15 +3,285 @@ use std::path::Path;
 
 use chunk_model::{ChunkId, ChunkRecord, DocumentId, FileRecord};
 use serde_json::Value as JsonValue;
-use rusqlite::{params, Connection, TransactionBehavior};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, TransactionBehavior};
 
-use crate::{ChunkPrimaryStore, ChunkStoreRead, StoreError, FilterClause, FilterOp};
+use crate::{ChunkPrimaryStore, ChunkStoreRead, StoreError, FilterClause, FilterExpr, FilterOp};
+
+/// Ids bound per `IN (...)` query, well under SQLite's parameter limit (999 before 3.32).
+const IDS_PER_QUERY: usize = 500;
+
+/// Column list matching `chunk_from_row`.
+const CHUNK_COLUMNS: &str = "schema_version, chunk_id, doc_id, source_uri, source_mime, extracted_at, page_start, page_end, text, section_path_json, meta_json, extra_json, block_kinds_json, seq";
+/// Number of columns in `CHUNK_COLUMNS`, i.e. the index of the first column selected after them.
+const CHUNK_COLUMN_COUNT: usize = column_count(CHUNK_COLUMNS);
+
+/// Number of entries in a comma-separated column list.
+const fn column_count(columns: &str) -> usize {
+    let bytes = columns.as_bytes();
+    let (mut n, mut i) = (1, 0);
+    while i < bytes.len() {
+        if bytes[i] == b',' { n += 1; }
+        i += 1;
+    }
+    n
+}
+
+/// Document-order key: `seq`, or the numeric `#N` suffix of `chunk_id` for rows still missing it
+/// (`rtrim` strips the '#'-free tail, leaving the offset of the last '#').
+const SEQ_ORDER_SQL: &str =
+    "COALESCE(seq, CAST(substr(chunk_id, length(rtrim(chunk_id, replace(chunk_id, '#', ''))) + 1) AS INTEGER))";
+
+/// Decode a `chunks` row selected with `CHUNK_COLUMNS`.
+fn chunk_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ChunkRecord> {
+    let schema_version: i64 = row.get(0)?;
+    let page_start_opt: Option<i64> = row.get(6).ok();
+    let page_end_opt: Option<i64> = row.get(7).ok();
+    let section_path_json: String = row.get(9)?;
+    let meta_json: String = row.get(10)?;
+    let extra_json: String = row.get(11)?;
+    let block_kinds_json: Option<String> = row.get(12)?;
+    let seq: Option<i64> = row.get(13)?;
+    let chunk_id = ChunkId(row.get(1)?);
+    Ok(ChunkRecord {
+        schema_version: schema_version as u16,
+        doc_id: DocumentId(row.get(2)?),
+        seq: seq.and_then(|v| u32::try_from(v).ok()).or_else(|| chunk_id.seq_suffix()),
+        chunk_id,
+        source_uri: row.get(3)?,
+        source_mime: row.get(4)?,
+        extracted_at: row.get(5)?,
+        page_start: page_start_opt.and_then(|v| u32::try_from(v).ok()),
+        page_end: page_end_opt.and_then(|v| u32::try_from(v).ok()),
+        text: row.get(8)?,
+        section_path: serde_json::from_str(&section_path_json).ok(),
+        meta: serde_json::from_str(&meta_json).unwrap_or_default(),
+        block_kinds: block_kinds_json.and_then(|j| serde_json::from_str(&j).ok()).unwrap_or_default(),
+        extra: serde_json::from_str(&extra_json).unwrap_or_default(),
+    })
+}
+
+/// Column list matching `file_from_row`.
+const FILE_COLUMNS: &str = "doc_id, schema_version, doc_revision, source_uri, source_mime, file_size_bytes, content_sha256, page_count, extracted_at, created_at_meta, updated_at_meta, title_guess, author_guess, dominant_lang, tags_json, ingest_tool, ingest_tool_version, reader_backend, ocr_used, ocr_langs_json, chunk_count, total_tokens, meta_json, extra_json";
+/// Number of columns in `FILE_COLUMNS`, i.e. the index of the first column selected after them.
+const FILE_COLUMN_COUNT: usize = column_count(FILE_COLUMNS);
+
+/// Decode a `files` row selected with `FILE_COLUMNS`.
+fn file_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<FileRecord> {
+    let doc_id: String = row.get(0)?;
+    let schema_version: i64 = row.get(1)?;
+    let doc_revision: Option<i64> = row.get(2).ok();
+    let source_uri: String = row.get(3)?;
+    let source_mime: String = row.get(4)?;
+    let file_size_bytes: Option<i64> = row.get(5).ok();
+    let content_sha256: Option<String> = row.get(6).ok();
+    let page_count: Option<i64> = row.get(7).ok();
+    let extracted_at: String = row.get(8)?;
+    let created_at_meta: Option<String> = row.get(9).ok();
+    let updated_at_meta: Option<String> = row.get(10).ok();
+    let title_guess: Option<String> = row.get(11).ok();
+    let author_guess: Option<String> = row.get(12).ok();
+    let dominant_lang: Option<String> = row.get(13).ok();
+    let tags_json: String = row.get(14)?;
+    let ingest_tool: Option<String> = row.get(15).ok();
+    let ingest_tool_version: Option<String> = row.get(16).ok();
+    let reader_backend: Option<String> = row.get(17).ok();
+    let ocr_used_opt: Option<i64> = row.get(18).ok();
+    let ocr_langs_json: String = row.get(19)?;
+    let chunk_count: Option<i64> = row.get(20).ok();
+    let total_tokens: Option<i64> = row.get(21).ok();
+    let meta_json: String = row.get(22)?;
+    let extra_json: String = row.get(23)?;
+
+    let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
+    let ocr_langs: Vec<String> = serde_json::from_str(&ocr_langs_json).unwrap_or_default();
+    let meta: std::collections::BTreeMap<String, String> = serde_json::from_str(&meta_json).unwrap_or_default();
+    let extra: std::collections::BTreeMap<String, JsonValue> = serde_json::from_str(&extra_json).unwrap_or_default();
+
+    Ok(FileRecord {
+        schema_version: schema_version as u16,
+        doc_id: DocumentId(doc_id),
+        doc_revision: doc_revision.and_then(|v| u32::try_from(v).ok()),
+        source_uri,
+        source_mime,
+        file_size_bytes: file_size_bytes.and_then(|v| u64::try_from(v).ok()),
+        content_sha256,
+        page_count: page_count.and_then(|v| u32::try_from(v).ok()),
+        extracted_at,
+        created_at_meta,
+        updated_at_meta,
+        title_guess,
+        author_guess,
+        dominant_lang,
+        tags,
+        ingest_tool,
+        ingest_tool_version,
+        reader_backend,
+        ocr_used: ocr_used_opt.map(|v| v != 0),
+        ocr_langs,
+        chunk_count: chunk_count.and_then(|v| u32::try_from(v).ok()),
+        total_tokens: total_tokens.and_then(|v| u32::try_from(v).ok()),
+        meta,
+        extra,
+    })
+}
+
+/// Paged iterator over all chunks (see `SqliteRepo::iter_chunks`). Yields an error at most once,
+/// then stops.
+pub struct ChunkIter<'a> {
+    repo: &'a SqliteRepo,
+    page_size: usize,
+    after_rowid: i64,
+    page: std::vec::IntoIter<ChunkRecord>,
+    done: bool,
+}
+
+impl Iterator for ChunkIter<'_> {
+    type Item = Result<ChunkRecord, StoreError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(rec) = self.page.next() { return Some(Ok(rec)); }
+        if self.done { return None; }
+        match self.repo.chunk_page_after(self.after_rowid, self.page_size) {
+            Ok(rows) => {
+                if rows.len() < self.page_size { self.done = true; }
+                let (last, _) = rows.last()?;
+                self.after_rowid = *last;
+                self.page = rows.into_iter().map(|(_, rec)| rec).collect::<Vec<_>>().into_iter();
+                self.page.next().map(Ok)
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
 
 /// SQLite-backed primary store. FTS5 text search lives in `fts5_index`.
 pub struct SqliteRepo {
     conn: Connection,
 }
 
+/// Chunk rows written by `SqliteRepo::stage_chunks` in a still-open transaction. Dropping
+/// it without `commit` rolls the rows back.
+pub struct StagedChunks<'a> {
+    tx: rusqlite::Transaction<'a>,
+}
+
+impl StagedChunks<'_> {
+    pub fn commit(self) -> Result<(), StoreError> {
+        self.tx.commit().map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    pub fn rollback(self) -> Result<(), StoreError> {
+        self.tx.rollback().map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// `SqliteRepo::add_suggest_terms` inside the staged transaction, so the terms commit or
+    /// roll back with the chunks.
+    pub fn add_suggest_terms(&self, terms: &[(String, u64)], max_terms: usize) -> Result<(), StoreError> {
+        write_suggest_terms(&self.tx, terms, max_terms).map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// `SqliteRepo::upsert_file` inside the staged transaction.
+    pub fn upsert_file(&self, file: &FileRecord) -> Result<(), StoreError> {
+        write_file(&self.tx, file).map_err(|e| StoreError::Backend(e.to_string()))
+    }
+}
+
+/// Upsert `terms` into `suggest_terms`, then keep only the `max_terms` most frequent
+/// (ties by term); 0 keeps everything.
+fn write_suggest_terms(conn: &Connection, terms: &[(String, u64)], max_terms: usize) -> rusqlite::Result<()> {
+    if terms.is_empty() { return Ok(()); }
+    {
+        let mut stmt = conn.prepare("INSERT INTO suggest_terms(term, freq) VALUES (?1, ?2) ON CONFLICT(term) DO UPDATE SET freq = freq + excluded.freq")?;
+        for (term, count) in terms {
+            stmt.execute(params![term, *count as i64])?;
+        }
+    }
+    if max_terms > 0 {
+        let n: i64 = conn.query_row("SELECT COUNT(*) FROM suggest_terms", [], |r| r.get(0))?;
+        if n as usize > max_terms {
+            conn.execute(
+                "DELETE FROM suggest_terms WHERE term NOT IN (SELECT term FROM suggest_terms ORDER BY freq DESC, term LIMIT ?1)",
+                [max_terms as i64],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Upsert one FileRecord into the files table keyed by doc_id.
+fn write_file(conn: &Connection, file: &FileRecord) -> rusqlite::Result<()> {
+    let tags_json = serde_json::to_string(&file.tags).unwrap_or_else(|_| "[]".to_string());
+    let ocr_langs_json = serde_json::to_string(&file.ocr_langs).unwrap_or_else(|_| "[]".to_string());
+    let meta_json = serde_json::to_string(&file.meta).unwrap_or_else(|_| "{}".to_string());
+    let extra_json = serde_json::to_string(&file.extra).unwrap_or_else(|_| "{}".to_string());
+    conn.execute(
+        r#"
+        INSERT INTO files (
+            doc_id, schema_version, doc_revision, source_uri, source_mime,
+            file_size_bytes, content_sha256, page_count, extracted_at, created_at_meta,
+            updated_at_meta, title_guess, author_guess, dominant_lang, tags_json,
+            ingest_tool, ingest_tool_version, reader_backend, ocr_used, ocr_langs_json,
+            chunk_count, total_tokens, meta_json, extra_json
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)
+        ON CONFLICT(doc_id) DO UPDATE SET
+            schema_version=excluded.schema_version,
+            doc_revision=excluded.doc_revision,
+            source_uri=excluded.source_uri,
+            source_mime=excluded.source_mime,
+            file_size_bytes=excluded.file_size_bytes,
+            content_sha256=excluded.content_sha256,
+            page_count=excluded.page_count,
+            extracted_at=excluded.extracted_at,
+            created_at_meta=excluded.created_at_meta,
+            updated_at_meta=excluded.updated_at_meta,
+            title_guess=excluded.title_guess,
+            author_guess=excluded.author_guess,
+            dominant_lang=excluded.dominant_lang,
+            tags_json=excluded.tags_json,
+            ingest_tool=excluded.ingest_tool,
+            ingest_tool_version=excluded.ingest_tool_version,
+            reader_backend=excluded.reader_backend,
+            ocr_used=excluded.ocr_used,
+            ocr_langs_json=excluded.ocr_langs_json,
+            chunk_count=excluded.chunk_count,
+            total_tokens=excluded.total_tokens,
+            meta_json=excluded.meta_json,
+            extra_json=excluded.extra_json
+        ;
+        "#,
+        params![
+            file.doc_id.0,
+            file.schema_version as i64,
+            file.doc_revision.map(|v| v as i64),
+            file.source_uri,
+            file.source_mime,
+            file.file_size_bytes.map(|v| v as i64),
+            file.content_sha256,
+            file.page_count.map(|v| v as i64),
+            file.extracted_at,
+            file.created_at_meta,
+            file.updated_at_meta,
+            file.title_guess,
+            file.author_guess,
+            file.dominant_lang,
+            tags_json,
+            file.ingest_tool,
+            file.ingest_tool_version,
+            file.reader_backend,
+            file.ocr_used.map(|b| if b { 1i64 } else { 0i64 }),
+            ocr_langs_json,
+            file.chunk_count.map(|v| v as i64),
+            file.total_tokens.map(|v| v as i64),
+            meta_json,
+            extra_json,
+        ],
+    )?;
+    Ok(())
+}
+
 impl SqliteRepo {
     /// Open an in-memory repository and initialize schema.
     pub fn new() -> Self {
@@ -29,6 +299,16 @@ impl SqliteRepo {
         Ok(repo)
     }
 
+    /// Open an existing file-backed repository read-only. Skips schema init/migrations,
+    /// so any write attempted through this handle fails at the SQLite level.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        Ok(Self { conn })
+    }
+
     #[allow(dead_code)]
     pub(crate) fn conn(&self) -> &Connection { &self.conn }
 
@@ -55,7 +335,10 @@ impl SqliteRepo {
                 section_path_json TEXT NOT NULL,
                 meta_json TEXT NOT NULL,
                 extra_json TEXT NOT NULL,
-                vector BLOB
+                vector BLOB,
+                text_sha256 TEXT,
+                block_kinds_json TEXT,
+                seq INTEGER
             );
 
             CREATE UNIQUE INDEX IF NOT EXISTS idx_chunks_chunk_id ON chunks(chunk_id);
@@ -111,349 +394,121 @@ impl SqliteRepo {
                 extra_json TEXT NOT NULL
             );
             CREATE INDEX IF NOT EXISTS idx_files_source_uri ON files(source_uri);
+            CREATE INDEX IF NOT EXISTS idx_files_content_sha256 ON files(content_sha256);
+
+            -- Store-level key/value settings (e.g., embedding reference vector)
+            CREATE TABLE IF NOT EXISTS store_meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            -- File ingests keyed by path and content hash ('' when unknown): 'started' while
+            -- running (left behind by a crash), 'completed' once journaled for a resumable ingest
+            CREATE TABLE IF NOT EXISTS ingest_journal (
+                source_uri TEXT NOT NULL,
+                content_sha256 TEXT NOT NULL,
+                state TEXT NOT NULL DEFAULT 'completed',
+                doc_id TEXT,
+                started_at TEXT,
+                completed_at TEXT,
+                PRIMARY KEY (source_uri, content_sha256)
+            );
+
+            -- Opaque per-document attachments (thumbnails, summaries), keyed by (doc_id, key)
+            CREATE TABLE IF NOT EXISTS document_blobs (
+                doc_id TEXT NOT NULL,
+                key TEXT NOT NULL,
+                blob BLOB NOT NULL,
+                PRIMARY KEY (doc_id, key)
+            );
+
+            -- Autocomplete vocabulary: indexed terms and past queries with their frequency
+            CREATE TABLE IF NOT EXISTS suggest_terms (
+                term TEXT PRIMARY KEY,
+                freq INTEGER NOT NULL
+            );
+
+            -- Logged searches and their returned chunks (best first), with optional relevance labels
+            CREATE TABLE IF NOT EXISTS search_log (
+                log_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                query TEXT NOT NULL,
+                logged_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS search_log_hits (
+                log_id INTEGER NOT NULL,
+                rank INTEGER NOT NULL,
+                chunk_id TEXT NOT NULL,
+                label REAL,
+                PRIMARY KEY (log_id, rank)
+            );
             "#,
         )?;
         // Best-effort migration for older tables missing page_start/page_end
         let _ = self.conn.execute("ALTER TABLE chunks ADD COLUMN page_start INTEGER", []);
         let _ = self.conn.execute("ALTER TABLE chunks ADD COLUMN page_end INTEGER", []);
+        // Checksum column; rows written before it existed stay NULL (unverifiable)
+        let _ = self.conn.execute("ALTER TABLE chunks ADD COLUMN text_sha256 TEXT", []);
+        // Block kinds (schema 1.1); NULL on older rows decodes as an empty list
+        let _ = self.conn.execute("ALTER TABLE chunks ADD COLUMN block_kinds_json TEXT", []);
+        // Explicit ordinal (schema 1.2); backfill older rows from their `#N` id suffix
+        if self.conn.execute("ALTER TABLE chunks ADD COLUMN seq INTEGER", []).is_ok() {
+            self.conn.execute(&format!("UPDATE chunks SET seq = {SEQ_ORDER_SQL} WHERE seq IS NULL AND chunk_id LIKE '%#%'"), [])?;
+        }
+        self.conn.execute("CREATE INDEX IF NOT EXISTS idx_chunks_doc_seq ON chunks(doc_id, seq)", [])?;
+        // Journals keyed by content hash alone treated copies of a file as already ingested, and
+        // older ones only held completed files; rebuild them keyed by (source_uri, content_sha256)
+        // with the in-flight state columns
+        let keyed_by_path: bool = self.conn.query_row(
+            "SELECT pk > 0 FROM pragma_table_info('ingest_journal') WHERE name = 'source_uri'",
+            [],
+            |r| r.get(0),
+        )?;
+        let has_state: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('ingest_journal') WHERE name = 'state'",
+            [],
+            |r| r.get(0),
+        )?;
+        if !keyed_by_path || !has_state {
+            self.conn.execute_batch(
+                r#"
+                BEGIN;
+                ALTER TABLE ingest_journal RENAME TO ingest_journal_v1;
+                CREATE TABLE ingest_journal (
+                    source_uri TEXT NOT NULL,
+                    content_sha256 TEXT NOT NULL,
+                    state TEXT NOT NULL DEFAULT 'completed',
+                    doc_id TEXT,
+                    started_at TEXT,
+                    completed_at TEXT,
+                    PRIMARY KEY (source_uri, content_sha256)
+                );
+                INSERT INTO ingest_journal(source_uri, content_sha256, completed_at)
+                    SELECT source_uri, content_sha256, completed_at FROM ingest_journal_v1;
+                DROP TABLE ingest_journal_v1;
+                COMMIT;
+                "#,
+            )?;
+        }
         Ok(())
     }
 
     /// Upsert one FileRecord into the files table keyed by doc_id.
     pub fn upsert_file(&self, file: &FileRecord) -> rusqlite::Result<()> {
-        let tags_json = serde_json::to_string(&file.tags).unwrap_or_else(|_| "[]".to_string());
-        let ocr_langs_json = serde_json::to_string(&file.ocr_langs).unwrap_or_else(|_| "[]".to_string());
-        let meta_json = serde_json::to_string(&file.meta).unwrap_or_else(|_| "{}".to_string());
-        let extra_json = serde_json::to_string(&file.extra).unwrap_or_else(|_| "{}".to_string());
-        self.conn
-            .execute(
-                r#"
-                INSERT INTO files (
-                    doc_id, schema_version, doc_revision, source_uri, source_mime,
-                    file_size_bytes, content_sha256, page_count, extracted_at, created_at_meta,
-                    updated_at_meta, title_guess, author_guess, dominant_lang, tags_json,
-                    ingest_tool, ingest_tool_version, reader_backend, ocr_used, ocr_langs_json,
-                    chunk_count, total_tokens, meta_json, extra_json
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)
-                ON CONFLICT(doc_id) DO UPDATE SET
-                    schema_version=excluded.schema_version,
-                    doc_revision=excluded.doc_revision,
-                    source_uri=excluded.source_uri,
-                    source_mime=excluded.source_mime,
-                    file_size_bytes=excluded.file_size_bytes,
-                    content_sha256=excluded.content_sha256,
-                    page_count=excluded.page_count,
-                    extracted_at=excluded.extracted_at,
-                    created_at_meta=excluded.created_at_meta,
-                    updated_at_meta=excluded.updated_at_meta,
-                    title_guess=excluded.title_guess,
-                    author_guess=excluded.author_guess,
-                    dominant_lang=excluded.dominant_lang,
-                    tags_json=excluded.tags_json,
-                    ingest_tool=excluded.ingest_tool,
-                    ingest_tool_version=excluded.ingest_tool_version,
-                    reader_backend=excluded.reader_backend,
-                    ocr_used=excluded.ocr_used,
-                    ocr_langs_json=excluded.ocr_langs_json,
-                    chunk_count=excluded.chunk_count,
-                    total_tokens=excluded.total_tokens,
-                    meta_json=excluded.meta_json,
-                    extra_json=excluded.extra_json
-                ;
-                "#,
-                params![
-                    file.doc_id.0,
-                    file.schema_version as i64,
-                    file.doc_revision.map(|v| v as i64),
-                    file.source_uri,
-                    file.source_mime,
-                    file.file_size_bytes.map(|v| v as i64),
-                    file.content_sha256,
-                    file.page_count.map(|v| v as i64),
-                    file.extracted_at,
-                    file.created_at_meta,
-                    file.updated_at_meta,
-                    file.title_guess,
-                    file.author_guess,
-                    file.dominant_lang,
-                    tags_json,
-                    file.ingest_tool,
-                    file.ingest_tool_version,
-                    file.reader_backend,
-                    file.ocr_used.map(|b| if b { 1i64 } else { 0i64 }),
-                    ocr_langs_json,
-                    file.chunk_count.map(|v| v as i64),
-                    file.total_tokens.map(|v| v as i64),
-                    meta_json,
-                    extra_json,
-                ],
-            )?
-            ;
-        Ok(())
+        write_file(&self.conn, file)
     }
 
     /// List FileRecords with pagination.
     pub fn list_files(&self, limit: usize, offset: usize) -> rusqlite::Result<Vec<FileRecord>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT doc_id, schema_version, doc_revision, source_uri, source_mime, file_size_bytes, content_sha256, page_count, extracted_at, created_at_meta, updated_at_meta, title_guess, author_guess, dominant_lang, tags_json, ingest_tool, ingest_tool_version, reader_backend, ocr_used, ocr_langs_json, chunk_count, total_tokens, meta_json, extra_json FROM files ORDER BY extracted_at DESC LIMIT ?1 OFFSET ?2"
-        )?;
-        let rows = stmt.query_map(params![limit as i64, offset as i64], |row| {
-            let doc_id: String = row.get(0)?;
-            let schema_version: i64 = row.get(1)?;
-            let doc_revision: Option<i64> = row.get(2).ok();
-            let source_uri: String = row.get(3)?;
-            let source_mime: String = row.get(4)?;
-            let file_size_bytes: Option<i64> = row.get(5).ok();
-            let content_sha256: Option<String> = row.get(6).ok();
-            let page_count: Option<i64> = row.get(7).ok();
-            let extracted_at: String = row.get(8)?;
-            let created_at_meta: Option<String> = row.get(9).ok();
-            let updated_at_meta: Option<String> = row.get(10).ok();
-            let title_guess: Option<String> = row.get(11).ok();
-            let author_guess: Option<String> = row.get(12).ok();
-            let dominant_lang: Option<String> = row.get(13).ok();
-            let tags_json: String = row.get(14)?;
-            let ingest_tool: Option<String> = row.get(15).ok();
-            let ingest_tool_version: Option<String> = row.get(16).ok();
-            let reader_backend: Option<String> = row.get(17).ok();
-            let ocr_used_opt: Option<i64> = row.get(18).ok();
-            let ocr_langs_json: String = row.get(19)?;
-            let chunk_count: Option<i64> = row.get(20).ok();
-            let total_tokens: Option<i64> = row.get(21).ok();
-            let meta_json: String = row.get(22)?;
-            let extra_json: String = row.get(23)?;
-
-            let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
-            let ocr_langs: Vec<String> = serde_json::from_str(&ocr_langs_json).unwrap_or_default();
-            let meta: std::collections::BTreeMap<String, String> = serde_json::from_str(&meta_json).unwrap_or_default();
-            let extra: std::collections::BTreeMap<String, JsonValue> = serde_json::from_str(&extra_json).unwrap_or_default();
-
-            Ok(FileRecord {
-                schema_version: schema_version as u16,
-                doc_id: DocumentId(doc_id),
-                doc_revision: doc_revision.and_then(|v| u32::try_from(v).ok()),
-                source_uri,
-                source_mime,
-                file_size_bytes: file_size_bytes.and_then(|v| u64::try_from(v).ok()),
-                content_sha256,
-                page_count: page_count.and_then(|v| u32::try_from(v).ok()),
-                extracted_at,
-                created_at_meta,
-                updated_at_meta,
-                title_guess,
-                author_guess,
-                dominant_lang,
-                tags,
-                ingest_tool,
-                ingest_tool_version,
-                reader_backend,
-                ocr_used: ocr_used_opt.map(|v| v != 0),
-                ocr_langs,
-                chunk_count: chunk_count.and_then(|v| u32::try_from(v).ok()),
-                total_tokens: total_tokens.and_then(|v| u32::try_from(v).ok()),
-                meta,
-                extra,
-            })
-        })?;
+        let mut stmt = self.conn.prepare(&format!("SELECT {FILE_COLUMNS} FROM files ORDER BY extracted_at DESC, doc_id LIMIT ?1 OFFSET ?2"))?;
+        let rows = stmt.query_map(params![limit as i64, offset as i64], file_from_row)?;
         let mut out = Vec::new();
         for r in rows { out.push(r?); }
         Ok(out)
     }
 
-    /// Delete files rows by doc_id list. Returns affected rows.
-    pub fn delete_files_by_doc_ids(&self, doc_ids: &[String]) -> rusqlite::Result<usize> {
-        if doc_ids.is_empty() { return Ok(0); }
-        let mut placeholders = String::from("(");
-        for i in 0..doc_ids.len() { if i > 0 { placeholders.push(','); } placeholders.push('?'); }
-        placeholders.push(')');
-        let sql = format!("DELETE FROM files WHERE doc_id IN {}", placeholders);
-        let params: Vec<&str> = doc_ids.iter().map(|s| s.as_str()).collect();
-        let n = self.conn.execute(&sql, rusqlite::params_from_iter(params.iter()))?;
-        Ok(n)
-    }
-
-    /// Remove files that have no remaining chunks (best-effort cleanup).
-    pub fn cleanup_orphan_files(&self) -> rusqlite::Result<usize> {
-        let n = self.conn.execute(
-            "DELETE FROM files WHERE doc_id NOT IN (SELECT DISTINCT doc_id FROM chunks)",
-            [],
-        )?;
-        Ok(n)
-    }
-
-    /// Ensure FTS content table is populated; rebuild if empty while chunks has rows.
-    pub fn maybe_rebuild_fts(&self) -> rusqlite::Result<()> {
-        let chunks_cnt: i64 = self.conn.query_row("SELECT count(*) FROM chunks", [], |r| r.get(0))?;
-        if chunks_cnt == 0 { return Ok(()); }
-        let fts_cnt: i64 = self.conn.query_row("SELECT count(*) FROM chunks_fts", [], |r| r.get(0)).unwrap_or(0);
-        if fts_cnt == 0 {
-            // Rebuild FTS index from content table
-            let _ = self.conn.execute("INSERT INTO chunks_fts(chunks_fts) VALUES('rebuild')", []);
-        }
-        Ok(())
-    }
-
-    /// Return (chunks_count, chunks_fts_count) for debugging.
-    pub fn counts(&self) -> rusqlite::Result<(i64, i64)> {
-        let chunks_cnt: i64 = self.conn.query_row("SELECT count(*) FROM chunks", [], |r| r.get(0))?;
-        let fts_cnt: i64 = self.conn.query_row("SELECT count(*) FROM chunks_fts", [], |r| r.get(0)).unwrap_or(0);
-        Ok((chunks_cnt, fts_cnt))
-    }
-
-    /// Return count of rows matching an FTS5 MATCH query (for debugging).
-    pub fn fts_match_count(&self, query: &str) -> rusqlite::Result<i64> {
-        self.conn.query_row(
-            "SELECT count(*) FROM chunks_fts WHERE chunks_fts MATCH ?1",
-            [query],
-            |r| r.get(0),
-        )
-    }
-
-    /// List chunk IDs matching filters with pagination.
-    pub fn list_chunk_ids_by_filter(
-        &self,
-        filters: &[crate::FilterClause],
-        limit: usize,
-        offset: usize,
-    ) -> Result<Vec<ChunkId>, StoreError> {
-        let mut where_sql = String::from("WHERE 1=1");
-        let mut params: Vec<rusqlite::types::Value> = Vec::new();
-
-        for f in filters {
-            match &f.op {
-                crate::FilterOp::DocIdEq(v) => {
-                    where_sql.push_str(" AND doc_id = ?");
-                    params.push(v.clone().into());
-                }
-                crate::FilterOp::DocIdIn(vs) => {
-                    if !vs.is_empty() {
-                        where_sql.push_str(" AND doc_id IN (");
-                        for i in 0..vs.len() {
-                            if i > 0 { where_sql.push(','); }
-                            where_sql.push('?');
-                            params.push(vs[i].clone().into());
-                        }
-                        where_sql.push(')');
-                    }
-                }
-                crate::FilterOp::SourceUriPrefix(p) => {
-                    where_sql.push_str(" AND source_uri LIKE ?");
-                    params.push(format!("{}%", p).into());
-                }
-                // Range on extracted_at (ISO 8601 strings) using lexicographic compare
-                crate::FilterOp::RangeIsoDate { key, start, end, start_incl, end_incl } => {
-                    if key == "extracted_at" {
-                        if let Some(s) = start {
-                            if *start_incl {
-                                where_sql.push_str(" AND extracted_at >= ?");
-                            } else {
-                                where_sql.push_str(" AND extracted_at > ?");
-                            }
-                            params.push(s.clone().into());
-                        }
-                        if let Some(e) = end {
-                            if *end_incl {
-                                where_sql.push_str(" AND extracted_at <= ?");
-                            } else {
-                                where_sql.push_str(" AND extracted_at < ?");
-                            }
-                            params.push(e.clone().into());
-                        }
-                    }
-                }
-                // Meta equality via JSON1
-                crate::FilterOp::MetaEq { key, value } => {
-                    where_sql.push_str(" AND json_extract(meta_json, ?) = ?");
-                    let path = format!("$.\"{}\"", key.replace('"', "\""));
-                    params.push(path.into());
-                    params.push(value.clone().into());
-                }
-                // Meta IN via JSON1
-                crate::FilterOp::MetaIn { key, values } => {
-                    if !values.is_empty() {
-                        where_sql.push_str(" AND json_extract(meta_json, ?) IN (");
-                        let path = format!("$.\"{}\"", key.replace('"', "\""));
-                        params.push(path.into());
-                        for i in 0..values.len() {
-                            if i > 0 { where_sql.push(','); }
-                            where_sql.push('?');
-                            params.push(values[i].clone().into());
-                        }
-                        where_sql.push(')');
-                    }
-                }
-                // Numeric range on columns (page_start/page_end) or meta via JSON1 + CAST
-                crate::FilterOp::RangeNumeric { key, min, max, min_incl, max_incl } => {
-                    let mut push_bound = |col: &str, is_min: bool, incl: bool, val: f64| {
-                        where_sql.push_str(" AND ");
-                        where_sql.push_str(col);
-                        where_sql.push_str(" ");
-                        if is_min { if incl { where_sql.push_str(">= ?"); } else { where_sql.push_str("> ?"); } }
-                        else { if incl { where_sql.push_str("<= ?"); } else { where_sql.push_str("< ?"); } }
-                        params.push(val.into());
-                    };
-                    match key.as_str() {
-                        "page_start" => {
-                            if let Some(lo) = min { push_bound("page_start", true, *min_incl, *lo as f64); }
-                            if let Some(hi) = max { push_bound("page_start", false, *max_incl, *hi as f64); }
-                        }
-                        "page_end" => {
-                            if let Some(lo) = min { push_bound("page_end", true, *min_incl, *lo as f64); }
-                            if let Some(hi) = max { push_bound("page_end", false, *max_incl, *hi as f64); }
-                        }
-                        _ => {
-                            if let Some(lo) = min {
-                                where_sql.push_str(" AND CAST(json_extract(meta_json, ?) AS REAL) ");
-                                if *min_incl { where_sql.push_str(">= ?"); } else { where_sql.push_str("> ?"); }
-                                let path = format!("$.\"{}\"", key.replace('"', "\""));
-                                params.push(path.into());
-                                params.push((*lo as f64).into());
-                            }
-                            if let Some(hi) = max {
-                                where_sql.push_str(" AND CAST(json_extract(meta_json, ?) AS REAL) ");
-                                if *max_incl { where_sql.push_str("<= ?"); } else { where_sql.push_str("< ?"); }
-                                let path = format!("$.\"{}\"", key.replace('"', "\""));
-                                params.push(path.into());
-                                params.push((*hi as f64).into());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        let sql = format!(
-            "SELECT chunk_id FROM chunks {} ORDER BY rowid LIMIT ? OFFSET ?",
-            where_sql
-        );
-        params.push((limit as i64).into());
-        params.push((offset as i64).into());
-
-        let mut stmt = self.conn
-            .prepare(&sql)
-            .map_err(|e| StoreError::Backend(e.to_string()))?;
-
-        let rows = stmt
-            .query_map(rusqlite::params_from_iter(params.into_iter()), |row| {
-                let cid: String = row.get(0)?;
-                Ok(ChunkId(cid))
-            })
-            .map_err(|e| StoreError::Backend(e.to_string()))?;
-
-        let mut out = Vec::new();
-        for r in rows {
-            out.push(r.map_err(|e| StoreError::Backend(e.to_string()))?);
-        }
-        Ok(out)
-    }
-}
-
-impl ChunkPrimaryStore for SqliteRepo {
-    fn upsert_chunks(&mut self, chunks: Vec<ChunkRecord>) -> Result<(), StoreError> {
-        if chunks.is_empty() {
-            return Ok(());
-        }
-
+    /// Upsert chunks like `upsert_chunks`, but leave the transaction open so the caller can
+    /// update other backends first and then commit or roll back the rows as one unit.
+    pub fn stage_chunks(&mut self, chunks: &[ChunkRecord]) -> Result<StagedChunks<'_>, StoreError> {
         let tx = self
             .conn
             .transaction_with_behavior(TransactionBehavior::Immediate)
@@ -465,8 +520,8 @@ impl ChunkPrimaryStore for SqliteRepo {
             INSERT INTO chunks (
                 schema_version, chunk_id, doc_id, source_uri, source_mime, extracted_at,
                 page_start, page_end,
-                text, section_path_json, meta_json, extra_json, vector
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, NULL)
+                text, section_path_json, meta_json, extra_json, vector, text_sha256, block_kinds_json, seq
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, NULL, ?13, ?14, ?15)
             ON CONFLICT(chunk_id) DO UPDATE SET
                 schema_version=excluded.schema_version,
                 doc_id=excluded.doc_id,
@@ -478,7 +533,10 @@ impl ChunkPrimaryStore for SqliteRepo {
                 text=excluded.text,
                 section_path_json=excluded.section_path_json,
                 meta_json=excluded.meta_json,
-                extra_json=excluded.extra_json
+                extra_json=excluded.extra_json,
+                text_sha256=excluded.text_sha256,
+                block_kinds_json=excluded.block_kinds_json,
+                seq=excluded.seq
             ;
             "#,
             )
@@ -500,6 +558,11 @@ impl ChunkPrimaryStore for SqliteRepo {
                 Ok(s) => s,
                 Err(e) => return Err(StoreError::Backend(e.to_string())),
             };
+            let text_sha256 = text_sha256(&rec.text);
+            let block_kinds_json = match serde_json::to_string(&rec.block_kinds) {
+                Ok(s) => s,
+                Err(e) => return Err(StoreError::Backend(e.to_string())),
+            };
 
             stmt
                 .execute(params![
@@ -515,333 +578,1017 @@ impl ChunkPrimaryStore for SqliteRepo {
                     section_json,
                     meta_json,
                     extra_json,
+                    text_sha256,
+                    block_kinds_json,
+                    rec.seq.map(i64::from),
                 ])
                 .map_err(|e| StoreError::Backend(e.to_string()))?;
         }
         drop(stmt);
-        tx.commit()
-            .map_err(|e| StoreError::Backend(e.to_string()))
-    }
-
-    fn delete_by_ids(&mut self, ids: &[chunk_model::ChunkId]) -> Result<usize, StoreError> {
-        if ids.is_empty() { return Ok(0); }
-        let tx = self.conn.transaction().map_err(|e| StoreError::Backend(e.to_string()))?;
-        let mut placeholders = String::from("(");
-        for i in 0..ids.len() { if i > 0 { placeholders.push(','); } placeholders.push('?'); }
-        placeholders.push(')');
-        let sql = format!("DELETE FROM chunks WHERE chunk_id IN {}", placeholders);
-        let params: Vec<&str> = ids.iter().map(|c| c.0.as_str()).collect();
-        let n = tx.execute(&sql, rusqlite::params_from_iter(params.iter()))
-            .map_err(|e| StoreError::Backend(e.to_string()))?;
-        tx.commit().map_err(|e| StoreError::Backend(e.to_string()))?;
-        Ok(n)
+        Ok(StagedChunks { tx })
     }
 
-    fn delete_by_filter(&mut self, filters: &[FilterClause]) -> Result<usize, StoreError> {
-        if filters.is_empty() { return Ok(0); }
-        // Support a subset: DocIdEq/In, SourceUriPrefix, and some meta/date-range combined with AND
-        let mut where_sql = String::from("WHERE 1=1");
-        let mut params: Vec<rusqlite::types::Value> = Vec::new();
-        for f in filters {
-            match &f.op {
-                FilterOp::DocIdEq(v) => { where_sql.push_str(" AND doc_id = ?"); params.push(v.clone().into()); }
-                FilterOp::DocIdIn(vs) => {
-                    if !vs.is_empty() {
-                        where_sql.push_str(" AND doc_id IN (");
-                        for i in 0..vs.len() { if i>0 { where_sql.push(','); } where_sql.push('?'); params.push(vs[i].clone().into()); }
-                        where_sql.push(')');
-                    }
-                }
-                FilterOp::SourceUriPrefix(p) => { where_sql.push_str(" AND source_uri LIKE ?"); params.push(format!("{}%", p).into()); }
-                FilterOp::MetaEq { key, value } => {
-                    where_sql.push_str(" AND json_extract(meta_json, ?) = ?");
-                    let path = format!("$.\"{}\"", key.replace('"', "\""));
-                    params.push(path.into());
-                    params.push(value.clone().into());
-                }
-                FilterOp::MetaIn { key, values } => {
-                    if !values.is_empty() {
-                        where_sql.push_str(" AND json_extract(meta_json, ?) IN (");
-                        let path = format!("$.\"{}\"", key.replace('"', "\""));
-                        params.push(path.into());
-                        for i in 0..values.len() { if i>0 { where_sql.push(','); } where_sql.push('?'); params.push(values[i].clone().into()); }
-                        where_sql.push(')');
-                    }
-                }
-                FilterOp::RangeIsoDate { key, start, end, start_incl, end_incl } => {
-                    if key == "extracted_at" {
-                        if let Some(s) = start { where_sql.push_str(if *start_incl {" AND extracted_at >= ?"} else {" AND extracted_at > ?"}); params.push(s.clone().into()); }
-                        if let Some(e) = end { where_sql.push_str(if *end_incl {" AND extracted_at <= ?"} else {" AND extracted_at < ?"}); params.push(e.clone().into()); }
-                    }
-                }
-                FilterOp::RangeNumeric { key, min, max, min_incl, max_incl } => {
-                    let push_bound = |sql: &mut String, col: &str, is_min: bool, incl: bool| {
-                        sql.push_str(" AND ");
-                        sql.push_str(col);
-                        if is_min { if incl { sql.push_str(" >= ?"); } else { sql.push_str(" > ?"); } }
-                        else { if incl { sql.push_str(" <= ?"); } else { sql.push_str(" < ?"); } }
-                    };
-                    match key.as_str() {
-                        "page_start" => {
-                            if let Some(lo) = min { push_bound(&mut where_sql, "page_start", true, *min_incl); params.push((*lo as f64).into()); }
-                            if let Some(hi) = max { push_bound(&mut where_sql, "page_start", false, *max_incl); params.push((*hi as f64).into()); }
-                        }
-                        "page_end" => {
-                            if let Some(lo) = min { push_bound(&mut where_sql, "page_end", true, *min_incl); params.push((*lo as f64).into()); }
-                            if let Some(hi) = max { push_bound(&mut where_sql, "page_end", false, *max_incl); params.push((*hi as f64).into()); }
-                        }
-                        _ => {
-                            if let Some(lo) = min {
-                                where_sql.push_str(" AND CAST(json_extract(meta_json, ?) AS REAL) ");
-                                where_sql.push_str(if *min_incl { ">= ?" } else { "> ?" });
-                                let path = format!("$.\"{}\"", key.replace('"', "\""));
-                                params.push(path.into());
-                                params.push((*lo as f64).into());
-                            }
-                            if let Some(hi) = max {
-                                where_sql.push_str(" AND CAST(json_extract(meta_json, ?) AS REAL) ");
-                                where_sql.push_str(if *max_incl { "<= ?" } else { "< ?" });
-                                let path = format!("$.\"{}\"", key.replace('"', "\""));
-                                params.push(path.into());
-                                params.push((*hi as f64).into());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        let sql = format!("DELETE FROM chunks {}", where_sql);
-        let n = self.conn.execute(&sql, rusqlite::params_from_iter(params.into_iter()))
-            .map_err(|e| StoreError::Backend(e.to_string()))?;
-        Ok(n)
+    /// FileRecord of one document, if stored.
+    pub fn get_file(&self, doc_id: &str) -> Result<Option<FileRecord>, StoreError> {
+        self.conn
+            .query_row(&format!("SELECT {FILE_COLUMNS} FROM files WHERE doc_id = ?1"), [doc_id], file_from_row)
+            .optional()
+            .map_err(|e| StoreError::Backend(e.to_string()))
     }
-}
-
-impl ChunkStoreRead for SqliteRepo {
-    fn get_chunks_by_ids(&self, ids: &[ChunkId]) -> Result<Vec<ChunkRecord>, StoreError> {
-        if ids.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        // Build `IN (?, ?, ...)` placeholder list
-        let mut placeholders = String::new();
-        placeholders.push('(');
-        for i in 0..ids.len() {
-            if i > 0 { placeholders.push(','); }
-            placeholders.push('?');
-        }
-        placeholders.push(')');
-
-        let sql = format!(
-            "SELECT schema_version, chunk_id, doc_id, source_uri, source_mime, extracted_at, page_start, page_end, text, section_path_json, meta_json, extra_json FROM chunks WHERE chunk_id IN {}",
-            placeholders
-        );
 
+    /// FileRecords of several documents in one query, keyed by doc id; ids without a stored
+    /// file are simply absent.
+    pub fn get_files_by_doc_ids(&self, doc_ids: &[String]) -> Result<HashMap<String, FileRecord>, StoreError> {
+        if doc_ids.is_empty() { return Ok(HashMap::new()); }
+        let (cond, binds) = in_list_sql("doc_id", doc_ids, false);
         let mut stmt = self
             .conn
-            .prepare(&sql)
+            .prepare(&format!("SELECT {FILE_COLUMNS} FROM files WHERE {cond}"))
             .map_err(|e| StoreError::Backend(e.to_string()))?;
-
-        // Bind parameters
-        let params_vec: Vec<&str> = ids.iter().map(|c| c.0.as_str()).collect();
-        let mut map: HashMap<String, ChunkRecord> = HashMap::with_capacity(ids.len());
-
         let rows = stmt
-            .query_map(rusqlite::params_from_iter(params_vec.iter()), |row| {
-                let schema_version: i64 = row.get(0)?;
-                let chunk_id: String = row.get(1)?;
-                let doc_id: String = row.get(2)?;
-                let source_uri: String = row.get(3)?;
-                let source_mime: String = row.get(4)?;
-                let extracted_at: String = row.get(5)?;
-                let page_start_opt: Option<i64> = row.get(6).ok();
-                let page_end_opt: Option<i64> = row.get(7).ok();
-                let text: String = row.get(8)?;
-                let section_path_json: String = row.get(9)?;
-                let meta_json: String = row.get(10)?;
-                let extra_json: String = row.get(11)?;
-
-                let section_path: Option<Vec<String>> = serde_json::from_str(&section_path_json).ok();
-                let meta: std::collections::BTreeMap<String, String> = serde_json::from_str(&meta_json).unwrap_or_default();
-                let extra: std::collections::BTreeMap<String, serde_json::Value> = serde_json::from_str(&extra_json).unwrap_or_default();
-
-                Ok(ChunkRecord {
-                    schema_version: schema_version as u16,
-                    doc_id: DocumentId(doc_id.clone()),
-                    chunk_id: ChunkId(chunk_id.clone()),
-                    source_uri,
-                    source_mime,
-                    extracted_at,
-                    page_start: page_start_opt.and_then(|v| u32::try_from(v).ok()),
-                    page_end: page_end_opt.and_then(|v| u32::try_from(v).ok()),
-                    text,
-                    section_path,
-                    meta,
-                    extra,
-                })
-            })
+            .query_map(rusqlite::params_from_iter(binds.iter()), file_from_row)
             .map_err(|e| StoreError::Backend(e.to_string()))?;
-
+        let mut out = HashMap::new();
         for r in rows {
-            let rec = r.map_err(|e| StoreError::Backend(e.to_string()))?;
-            map.insert(rec.chunk_id.0.clone(), rec);
-        }
-
-        // Preserve requested order
-        let mut out = Vec::with_capacity(ids.len());
-        for id in ids {
-            if let Some(rec) = map.remove(&id.0) { out.push(rec); }
+            let file = r.map_err(|e| StoreError::Backend(e.to_string()))?;
+            out.insert(file.doc_id.0.clone(), file);
         }
         Ok(out)
     }
 
-    fn as_any(&self) -> &dyn std::any::Any { self }
-}
+    /// A stored FileRecord whose `content_sha256` equals `hex` (the first by doc id), if any.
+    pub fn find_file_by_sha256(&self, hex: &str) -> Result<Option<FileRecord>, StoreError> {
+        self.conn
+            .query_row(&format!("SELECT {FILE_COLUMNS} FROM files WHERE content_sha256 = ?1 ORDER BY doc_id LIMIT 1"), [hex], file_from_row)
+            .optional()
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
 
-impl SqliteRepo {
-    /// Fetch a single chunk by its chunk_id.
-    pub fn get_chunk_by_id(&self, id: &ChunkId) -> Result<Option<ChunkRecord>, StoreError> {
+    /// Distinct document ids owning chunks, sorted, with pagination.
+    pub fn list_doc_ids(&self, limit: usize, offset: usize) -> Result<Vec<String>, StoreError> {
         let mut stmt = self
             .conn
-            .prepare("SELECT schema_version, chunk_id, doc_id, source_uri, source_mime, extracted_at, page_start, page_end, text, section_path_json, meta_json, extra_json FROM chunks WHERE chunk_id = ?1")
-            .map_err(|e| StoreError::Backend(e.to_string()))?;
-        let mut rows = stmt
-            .query([id.0.as_str()])
-            .map_err(|e| StoreError::Backend(e.to_string()))?;
-        if let Some(row) = rows.next().map_err(|e| StoreError::Backend(e.to_string()))? {
-            let schema_version: i64 = row.get(0).map_err(|e| StoreError::Backend(e.to_string()))?;
-            let chunk_id: String = row.get(1).map_err(|e| StoreError::Backend(e.to_string()))?;
-            let doc_id: String = row.get(2).map_err(|e| StoreError::Backend(e.to_string()))?;
-            let source_uri: String = row.get(3).map_err(|e| StoreError::Backend(e.to_string()))?;
-            let source_mime: String = row.get(4).map_err(|e| StoreError::Backend(e.to_string()))?;
-            let extracted_at: String = row.get(5).map_err(|e| StoreError::Backend(e.to_string()))?;
-            let page_start_opt: Option<i64> = row.get(6).ok();
-            let page_end_opt: Option<i64> = row.get(7).ok();
-            let text: String = row.get(8).map_err(|e| StoreError::Backend(e.to_string()))?;
-            let section_path_json: String = row.get(9).map_err(|e| StoreError::Backend(e.to_string()))?;
-            let meta_json: String = row.get(10).map_err(|e| StoreError::Backend(e.to_string()))?;
-            let extra_json: String = row.get(11).map_err(|e| StoreError::Backend(e.to_string()))?;
-
-            let section_path: Option<Vec<String>> = serde_json::from_str(&section_path_json).ok();
-            let meta: std::collections::BTreeMap<String, String> = serde_json::from_str(&meta_json).unwrap_or_default();
-            let extra: std::collections::BTreeMap<String, JsonValue> = serde_json::from_str(&extra_json).unwrap_or_default();
-
-            Ok(Some(ChunkRecord {
-                schema_version: schema_version as u16,
-                doc_id: DocumentId(doc_id),
-                chunk_id: ChunkId(chunk_id),
-                source_uri,
-                source_mime,
-                extracted_at,
-                page_start: page_start_opt.and_then(|v| u32::try_from(v).ok()),
-                page_end: page_end_opt.and_then(|v| u32::try_from(v).ok()),
-                text,
-                section_path,
-                meta,
-                extra,
-            }))
-        } else {
-            Ok(None)
-        }
+            .prepare("SELECT DISTINCT doc_id FROM chunks ORDER BY doc_id LIMIT ?1 OFFSET ?2")
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map(params![limit as i64, offset as i64], |r| r.get(0))
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        rows.map(|r| r.map_err(|e| StoreError::Backend(e.to_string()))).collect()
     }
 
-    /// Return previous and next chunks within the same document, ordered by rowid.
-    pub fn get_neighbor_chunks(&self, id: &ChunkId) -> Result<(Option<ChunkRecord>, Option<ChunkRecord>), StoreError> {
-        // Find doc_id and rowid for the current chunk
-        let (doc_id, rowid): (String, i64) = self
-            .conn
-            .query_row(
-                "SELECT doc_id, rowid FROM chunks WHERE chunk_id = ?1",
-                [id.0.as_str()],
-                |r| Ok((r.get(0)?, r.get(1)?)),
-            )
+    /// Count FileRecords. With no filters every file is counted; otherwise only files
+    /// that own at least one chunk matching all `filters` (same semantics as chunk filters).
+    pub fn count_files(&self, filters: &[crate::FilterClause]) -> Result<u64, StoreError> {
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+        let sql = if filters.is_empty() {
+            "SELECT COUNT(*) FROM files".to_string()
+        } else {
+            let mut where_sql = String::from("WHERE 1=1");
+            for f in filters { push_filter_sql(&f.op, &mut where_sql, &mut params); }
+            format!("SELECT COUNT(*) FROM files WHERE doc_id IN (SELECT doc_id FROM chunks {where_sql})")
+        };
+        let n: i64 = self.conn
+            .query_row(&sql, rusqlite::params_from_iter(params), |row| row.get(0))
             .map_err(|e| StoreError::Backend(e.to_string()))?;
+        Ok(n.max(0) as u64)
+    }
 
-        // Previous
-        let prev: Option<ChunkRecord> = {
-            let mut stmt = self
-                .conn
-                .prepare("SELECT schema_version, chunk_id, doc_id, source_uri, source_mime, extracted_at, page_start, page_end, text, section_path_json, meta_json, extra_json FROM chunks WHERE doc_id = ?1 AND rowid < ?2 ORDER BY rowid DESC LIMIT 1")
-                .map_err(|e| StoreError::Backend(e.to_string()))?;
-            let mut rows = stmt
-                .query([doc_id.as_str(), &rowid.to_string()])
-                .map_err(|e| StoreError::Backend(e.to_string()))?;
-            if let Some(row) = rows.next().map_err(|e| StoreError::Backend(e.to_string()))? {
-                let schema_version: i64 = row.get(0).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let chunk_id: String = row.get(1).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let doc_id2: String = row.get(2).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let source_uri: String = row.get(3).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let source_mime: String = row.get(4).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let extracted_at: String = row.get(5).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let page_start_opt: Option<i64> = row.get(6).ok();
-                let page_end_opt: Option<i64> = row.get(7).ok();
-                let text: String = row.get(8).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let section_path_json: String = row.get(9).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let meta_json: String = row.get(10).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let extra_json: String = row.get(11).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let section_path: Option<Vec<String>> = serde_json::from_str(&section_path_json).ok();
-                let meta: std::collections::BTreeMap<String, String> = serde_json::from_str(&meta_json).unwrap_or_default();
-                let extra: std::collections::BTreeMap<String, JsonValue> = serde_json::from_str(&extra_json).unwrap_or_default();
-                Some(ChunkRecord {
-                    schema_version: schema_version as u16,
-                    doc_id: DocumentId(doc_id2),
-                    chunk_id: ChunkId(chunk_id),
-                    source_uri,
-                    source_mime,
-                    extracted_at,
-                    page_start: page_start_opt.and_then(|v| u32::try_from(v).ok()),
-                    page_end: page_end_opt.and_then(|v| u32::try_from(v).ok()),
-                    text,
-                    section_path,
-                    meta,
-                    extra,
+    /// Number of chunks matching `filters`, using the same prefilter SQL as
+    /// `list_chunk_ids_by_filter` and `delete_by_filter`, without fetching any row.
+    pub fn count_by_filter(&self, filters: &[crate::FilterClause]) -> Result<i64, StoreError> {
+        let mut where_sql = String::from("WHERE 1=1");
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+        for f in filters { push_filter_sql(&f.op, &mut where_sql, &mut params); }
+        self.conn
+            .query_row(&format!("SELECT COUNT(*) FROM chunks {where_sql}"), rusqlite::params_from_iter(params), |row| row.get(0))
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// `count_by_filter` broken down per document: `(doc_id, chunks)` sorted by doc id,
+    /// documents without a matching chunk left out.
+    pub fn count_by_filter_per_doc(&self, filters: &[crate::FilterClause]) -> Result<Vec<(String, i64)>, StoreError> {
+        let mut where_sql = String::from("WHERE 1=1");
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+        for f in filters { push_filter_sql(&f.op, &mut where_sql, &mut params); }
+        let mut stmt = self.conn
+            .prepare(&format!("SELECT doc_id, COUNT(*) FROM chunks {where_sql} GROUP BY doc_id ORDER BY doc_id"))
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(params), |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// One page of documents (same order as `list_files`) with live chunk aggregates counted
+    /// from the chunks table, so they stay exact after partial deletes.
+    pub fn list_documents(&self, limit: usize, offset: usize) -> Result<Vec<DocSummary>, StoreError> {
+        let sql = format!(
+            "SELECT {FILE_COLUMNS},
+                (SELECT COUNT(*) FROM chunks c WHERE c.doc_id = files.doc_id),
+                (SELECT MIN(page_start) FROM chunks c WHERE c.doc_id = files.doc_id),
+                (SELECT MAX(COALESCE(page_end, page_start)) FROM chunks c WHERE c.doc_id = files.doc_id)
+             FROM files ORDER BY extracted_at DESC, doc_id LIMIT ?1 OFFSET ?2"
+        );
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map(params![limit as i64, offset as i64], |row| {
+                let n: i64 = row.get(FILE_COLUMN_COUNT)?;
+                Ok(DocSummary {
+                    file: file_from_row(row)?,
+                    live_chunks: n.max(0) as u64,
+                    page_min: row.get::<_, Option<i64>>(FILE_COLUMN_COUNT + 1)?.map(|p| p as u32),
+                    page_max: row.get::<_, Option<i64>>(FILE_COLUMN_COUNT + 2)?.map(|p| p as u32),
                 })
-            } else { None }
+            })
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// One page of FileRecords together with the total file count, so callers can
+    /// paginate exactly without a second round trip.
+    pub fn list_files_page(&self, limit: usize, offset: usize) -> Result<(Vec<FileRecord>, u64), StoreError> {
+        let files = self.list_files(limit, offset).map_err(|e| StoreError::Backend(e.to_string()))?;
+        let total = self.count_files(&[])?;
+        Ok((files, total))
+    }
+
+    /// Delete files rows by doc_id list. Returns affected rows.
+    pub fn delete_files_by_doc_ids(&self, doc_ids: &[String]) -> rusqlite::Result<usize> {
+        let mut n = 0;
+        for batch in doc_ids.chunks(IDS_PER_QUERY) {
+            let placeholders = vec!["?"; batch.len()].join(",");
+            n += self.conn.execute(&format!("DELETE FROM files WHERE doc_id IN ({placeholders})"), rusqlite::params_from_iter(batch.iter()))?;
+            // Attachments go with their document
+            self.conn.execute(&format!("DELETE FROM document_blobs WHERE doc_id IN ({placeholders})"), rusqlite::params_from_iter(batch.iter()))?;
+        }
+        Ok(n)
+    }
+
+    /// Remove files that have no remaining chunks (best-effort cleanup).
+    pub fn cleanup_orphan_files(&self) -> rusqlite::Result<usize> {
+        let n = self.conn.execute(
+            "DELETE FROM files WHERE doc_id NOT IN (SELECT DISTINCT doc_id FROM chunks)",
+            [],
+        )?;
+        Ok(n)
+    }
+
+    /// Files without chunks and chunks without a FileRecord, without changing anything
+    /// (`cleanup_orphan_files` removes the former).
+    pub fn find_orphans(&self) -> Result<OrphanReport, StoreError> {
+        let ids = |sql: &str| -> Result<Vec<String>, StoreError> {
+            let mut stmt = self.conn.prepare(sql).map_err(|e| StoreError::Backend(e.to_string()))?;
+            let rows = stmt.query_map([], |r| r.get::<_, String>(0)).map_err(|e| StoreError::Backend(e.to_string()))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>().map_err(|e| StoreError::Backend(e.to_string()))
         };
+        Ok(OrphanReport {
+            files_without_chunks: ids("SELECT doc_id FROM files WHERE doc_id NOT IN (SELECT DISTINCT doc_id FROM chunks) ORDER BY doc_id")?
+                .into_iter()
+                .map(DocumentId)
+                .collect(),
+            chunks_without_file: ids("SELECT chunk_id FROM chunks WHERE doc_id NOT IN (SELECT doc_id FROM files) ORDER BY rowid")?
+                .into_iter()
+                .map(ChunkId)
+                .collect(),
+        })
+    }
 
-        // Next
-        let next: Option<ChunkRecord> = {
+    /// Ensure FTS content table is populated; rebuild if empty while chunks has rows.
+    pub fn maybe_rebuild_fts(&self) -> rusqlite::Result<()> {
+        let chunks_cnt: i64 = self.conn.query_row("SELECT count(*) FROM chunks", [], |r| r.get(0))?;
+        if chunks_cnt == 0 { return Ok(()); }
+        let fts_cnt: i64 = self.conn.query_row("SELECT count(*) FROM chunks_fts", [], |r| r.get(0)).unwrap_or(0);
+        if fts_cnt == 0 {
+            // Rebuild FTS index from content table
+            let _ = self.conn.execute("INSERT INTO chunks_fts(chunks_fts) VALUES('rebuild')", []);
+        }
+        Ok(())
+    }
+
+    /// Rebuild the FTS5 mirror from the chunks table unconditionally (one transaction).
+    pub fn rebuild_fts(&self) -> rusqlite::Result<()> {
+        self.conn.execute("INSERT INTO chunks_fts(chunks_fts) VALUES('rebuild')", []).map(|_| ())
+    }
+
+    /// Reclaim space after large deletes: merge FTS5 segments, VACUUM, then truncate the WAL
+    /// so the main database file reflects the smaller size.
+    pub fn compact(&self) -> rusqlite::Result<()> {
+        self.conn.execute("INSERT INTO chunks_fts(chunks_fts) VALUES('optimize')", [])?;
+        self.conn.execute_batch("VACUUM")?;
+        self.conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+    }
+
+    /// Iterate over every chunk in insertion (rowid) order, fetching `page_size` rows per query
+    /// so memory stays bounded on large stores.
+    pub fn iter_chunks(&self, page_size: usize) -> ChunkIter<'_> {
+        ChunkIter { repo: self, page_size: page_size.max(1), after_rowid: 0, page: Vec::new().into_iter(), done: false }
+    }
+
+    fn chunk_page_after(&self, after_rowid: i64, limit: usize) -> Result<Vec<(i64, ChunkRecord)>, StoreError> {
+        let sql = format!("SELECT {CHUNK_COLUMNS}, rowid FROM chunks WHERE rowid > ?1 ORDER BY rowid LIMIT ?2");
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map(params![after_rowid, limit as i64], |row| Ok((row.get::<_, i64>(CHUNK_COLUMN_COUNT)?, chunk_from_row(row)?)))
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        rows.map(|r| r.map_err(|e| StoreError::Backend(e.to_string()))).collect()
+    }
+
+    /// Return (chunks_count, chunks_fts_count) for debugging.
+    pub fn counts(&self) -> rusqlite::Result<(i64, i64)> {
+        let chunks_cnt: i64 = self.conn.query_row("SELECT count(*) FROM chunks", [], |r| r.get(0))?;
+        let fts_cnt: i64 = self.conn.query_row("SELECT count(*) FROM chunks_fts", [], |r| r.get(0)).unwrap_or(0);
+        Ok((chunks_cnt, fts_cnt))
+    }
+
+    /// Return count of rows matching an FTS5 MATCH query (for debugging).
+    pub fn fts_match_count(&self, query: &str) -> rusqlite::Result<i64> {
+        self.conn.query_row(
+            "SELECT count(*) FROM chunks_fts WHERE chunks_fts MATCH ?1",
+            [query],
+            |r| r.get(0),
+        )
+    }
+
+    /// List chunk IDs matching filters with pagination.
+    pub fn list_chunk_ids_by_filter(
+        &self,
+        filters: &[crate::FilterClause],
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<ChunkId>, StoreError> {
+        self.list_chunk_ids_ordered(filters, "rowid", limit, offset)
+    }
+
+    /// List chunk IDs matching a filter tree with pagination.
+    pub fn list_chunk_ids_by_filter_expr(&self, expr: &FilterExpr, limit: usize, offset: usize) -> Result<Vec<ChunkId>, StoreError> {
+        let mut where_sql = String::from("WHERE ");
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+        push_filter_expr_sql(expr, &mut where_sql, &mut params);
+        let sql = format!("SELECT chunk_id FROM chunks {where_sql} ORDER BY rowid LIMIT ? OFFSET ?");
+        params.push((limit as i64).into());
+        params.push((offset as i64).into());
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(params), |row| Ok(ChunkId(row.get(0)?)))
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let mut out = Vec::new();
+        for r in rows { out.push(r.map_err(|e| StoreError::Backend(e.to_string()))?); }
+        Ok(out)
+    }
+
+    /// Delete chunks matching a filter tree. A tree without any leaf deletes nothing
+    /// (mirrors `delete_by_filter` with no clauses).
+    pub fn delete_by_filter_expr(&mut self, expr: &FilterExpr) -> Result<usize, StoreError> {
+        if expr.is_empty() { return Ok(0); }
+        let mut where_sql = String::from("WHERE ");
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+        push_filter_expr_sql(expr, &mut where_sql, &mut params);
+        self.conn
+            .execute(&format!("DELETE FROM chunks {where_sql}"), rusqlite::params_from_iter(params))
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Most recently extracted chunk IDs matching filters (newest first).
+    pub fn list_recent_chunk_ids(&self, filters: &[crate::FilterClause], limit: usize) -> Result<Vec<ChunkId>, StoreError> {
+        self.list_chunk_ids_ordered(filters, "extracted_at DESC, rowid DESC", limit, 0)
+    }
+
+    /// Random sample of chunk IDs matching filters.
+    pub fn list_random_chunk_ids(&self, filters: &[crate::FilterClause], limit: usize) -> Result<Vec<ChunkId>, StoreError> {
+        self.list_chunk_ids_ordered(filters, "RANDOM()", limit, 0)
+    }
+
+    fn list_chunk_ids_ordered(
+        &self,
+        filters: &[crate::FilterClause],
+        order_by: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<ChunkId>, StoreError> {
+        let mut where_sql = String::from("WHERE 1=1");
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+        for f in filters { push_filter_sql(&f.op, &mut where_sql, &mut params); }
+
+        let sql = format!(
+            "SELECT chunk_id FROM chunks {} ORDER BY {} LIMIT ? OFFSET ?",
+            where_sql, order_by
+        );
+        params.push((limit as i64).into());
+        params.push((offset as i64).into());
+
+        let mut stmt = self.conn
+            .prepare(&sql)
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(params.into_iter()), |row| {
+                let cid: String = row.get(0)?;
+                Ok(ChunkId(cid))
+            })
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+
+        let mut out = Vec::new();
+        for r in rows {
+            out.push(r.map_err(|e| StoreError::Backend(e.to_string()))?);
+        }
+        Ok(out)
+    }
+}
+
+impl ChunkPrimaryStore for SqliteRepo {
+    fn upsert_chunks(&mut self, chunks: Vec<ChunkRecord>) -> Result<(), StoreError> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        self.stage_chunks(&chunks)?.commit()
+    }
+
+    fn delete_by_ids(&mut self, ids: &[chunk_model::ChunkId]) -> Result<usize, StoreError> {
+        if ids.is_empty() { return Ok(0); }
+        let tx = self.conn.transaction().map_err(|e| StoreError::Backend(e.to_string()))?;
+        let mut n = 0;
+        for batch in ids.chunks(IDS_PER_QUERY) {
+            let placeholders = vec!["?"; batch.len()].join(",");
+            n += tx.execute(&format!("DELETE FROM chunks WHERE chunk_id IN ({placeholders})"), rusqlite::params_from_iter(batch.iter().map(|c| c.0.as_str())))
+                .map_err(|e| StoreError::Backend(e.to_string()))?;
+        }
+        tx.commit().map_err(|e| StoreError::Backend(e.to_string()))?;
+        Ok(n)
+    }
+
+    fn delete_by_filter(&mut self, filters: &[FilterClause]) -> Result<usize, StoreError> {
+        if filters.is_empty() { return Ok(0); }
+        let mut where_sql = String::from("WHERE 1=1");
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+        for f in filters { push_filter_sql(&f.op, &mut where_sql, &mut params); }
+        let sql = format!("DELETE FROM chunks {}", where_sql);
+        let n = self.conn.execute(&sql, rusqlite::params_from_iter(params.into_iter()))
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        Ok(n)
+    }
+}
+
+impl ChunkStoreRead for SqliteRepo {
+    fn get_chunks_by_ids(&self, ids: &[ChunkId]) -> Result<Vec<ChunkRecord>, StoreError> {
+        Ok(self.get_chunks_by_ids_keep_missing(ids)?.into_iter().flatten().collect())
+    }
+
+    fn get_chunks_by_ids_keep_missing(&self, ids: &[ChunkId]) -> Result<Vec<Option<ChunkRecord>>, StoreError> {
+        let mut map: HashMap<String, ChunkRecord> = HashMap::with_capacity(ids.len());
+        for batch in ids.chunks(IDS_PER_QUERY) {
+            let placeholders = vec!["?"; batch.len()].join(",");
+            let sql = format!("SELECT {CHUNK_COLUMNS} FROM chunks WHERE chunk_id IN ({placeholders})");
             let mut stmt = self
                 .conn
-                .prepare("SELECT schema_version, chunk_id, doc_id, source_uri, source_mime, extracted_at, page_start, page_end, text, section_path_json, meta_json, extra_json FROM chunks WHERE doc_id = ?1 AND rowid > ?2 ORDER BY rowid ASC LIMIT 1")
+                .prepare_cached(&sql)
                 .map_err(|e| StoreError::Backend(e.to_string()))?;
-            let mut rows = stmt
-                .query([doc_id.as_str(), &rowid.to_string()])
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(batch.iter().map(|c| c.0.as_str())), chunk_from_row)
                 .map_err(|e| StoreError::Backend(e.to_string()))?;
-            if let Some(row) = rows.next().map_err(|e| StoreError::Backend(e.to_string()))? {
-                let schema_version: i64 = row.get(0).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let chunk_id: String = row.get(1).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let doc_id2: String = row.get(2).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let source_uri: String = row.get(3).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let source_mime: String = row.get(4).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let extracted_at: String = row.get(5).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let page_start_opt: Option<i64> = row.get(6).ok();
-                let page_end_opt: Option<i64> = row.get(7).ok();
-                let text: String = row.get(8).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let section_path_json: String = row.get(9).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let meta_json: String = row.get(10).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let extra_json: String = row.get(11).map_err(|e| StoreError::Backend(e.to_string()))?;
-                let section_path: Option<Vec<String>> = serde_json::from_str(&section_path_json).ok();
-                let meta: std::collections::BTreeMap<String, String> = serde_json::from_str(&meta_json).unwrap_or_default();
-                let extra: std::collections::BTreeMap<String, JsonValue> = serde_json::from_str(&extra_json).unwrap_or_default();
-                Some(ChunkRecord {
-                    schema_version: schema_version as u16,
-                    doc_id: DocumentId(doc_id2),
-                    chunk_id: ChunkId(chunk_id),
-                    source_uri,
-                    source_mime,
-                    extracted_at,
-                    page_start: page_start_opt.and_then(|v| u32::try_from(v).ok()),
-                    page_end: page_end_opt.and_then(|v| u32::try_from(v).ok()),
-                    text,
-                    section_path,
-                    meta,
-                    extra,
-                })
-            } else { None }
-        };
+            for r in rows {
+                let rec = r.map_err(|e| StoreError::Backend(e.to_string()))?;
+                map.insert(rec.chunk_id.0.clone(), rec);
+            }
+        }
+
+        // Preserve requested order; a repeated id gets its record each time
+        Ok(ids.iter().map(|id| map.get(&id.0).cloned()).collect())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+}
+
+impl SqliteRepo {
+    /// Fetch a single chunk by its chunk_id.
+    pub fn get_chunk_by_id(&self, id: &ChunkId) -> Result<Option<ChunkRecord>, StoreError> {
+        let sql = format!("SELECT {CHUNK_COLUMNS} FROM chunks WHERE chunk_id = ?1");
+        self.conn
+            .query_row(&sql, [id.0.as_str()], chunk_from_row)
+            .optional()
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Return previous and next chunks within the same document in document order (`seq`, or the
+    /// `#N` id suffix for rows without one; ties by rowid).
+    pub fn get_neighbor_chunks(&self, id: &ChunkId) -> Result<(Option<ChunkRecord>, Option<ChunkRecord>), StoreError> {
+        // Find doc_id and document-order position (seq, rowid) for the current chunk
+        let (doc_id, seq, rowid): (String, i64, i64) = self
+            .conn
+            .query_row(
+                &format!("SELECT doc_id, {SEQ_ORDER_SQL}, rowid FROM chunks WHERE chunk_id = ?1"),
+                [id.0.as_str()],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+
+        // Previous
+        let prev_sql = format!(
+            "SELECT {CHUNK_COLUMNS} FROM chunks WHERE doc_id = ?1 AND ({SEQ_ORDER_SQL}, rowid) < (?2, ?3) \
+             ORDER BY {SEQ_ORDER_SQL} DESC, rowid DESC LIMIT 1"
+        );
+        let prev = self
+            .conn
+            .query_row(&prev_sql, params![doc_id, seq, rowid], chunk_from_row)
+            .optional()
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+
+        // Next
+        let next_sql = format!(
+            "SELECT {CHUNK_COLUMNS} FROM chunks WHERE doc_id = ?1 AND ({SEQ_ORDER_SQL}, rowid) > (?2, ?3) \
+             ORDER BY {SEQ_ORDER_SQL}, rowid LIMIT 1"
+        );
+        let next = self
+            .conn
+            .query_row(&next_sql, params![doc_id, seq, rowid], chunk_from_row)
+            .optional()
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
 
         Ok((prev, next))
     }
+
+    /// Page through a document's chunks in document order: `seq`, falling back to the numeric
+    /// `#N` suffix of `chunk_id` for rows without one (so `#2` precedes `#10`); ties by insertion order.
+    pub fn get_chunks_by_doc_id(&self, doc_id: &str, limit: usize, offset: usize) -> Result<Vec<ChunkRecord>, StoreError> {
+        let sql = format!("SELECT {CHUNK_COLUMNS} FROM chunks WHERE doc_id = ?1 ORDER BY {SEQ_ORDER_SQL}, rowid LIMIT ?2 OFFSET ?3");
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map(params![doc_id, limit as i64, offset as i64], chunk_from_row)
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        rows.map(|r| r.map_err(|e| StoreError::Backend(e.to_string()))).collect()
+    }
+
+    /// Return all chunks of the same document sharing the given chunk's `section_path`, in document order.
+    /// Chunks without a section path only return themselves.
+    pub fn get_section_chunks(&self, id: &ChunkId) -> Result<Vec<ChunkRecord>, StoreError> {
+        let (doc_id, section_json): (String, String) = self
+            .conn
+            .query_row(
+                "SELECT doc_id, section_path_json FROM chunks WHERE chunk_id = ?1",
+                [id.0.as_str()],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let has_section = matches!(serde_json::from_str::<Vec<String>>(&section_json), Ok(p) if !p.is_empty());
+        if !has_section {
+            return Ok(self.get_chunk_by_id(id)?.into_iter().collect());
+        }
+        let sql = format!("SELECT {CHUNK_COLUMNS} FROM chunks WHERE doc_id = ?1 AND section_path_json = ?2 ORDER BY {SEQ_ORDER_SQL}, rowid");
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map([doc_id.as_str(), section_json.as_str()], chunk_from_row)
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let mut out = Vec::new();
+        for r in rows { out.push(r.map_err(|e| StoreError::Backend(e.to_string()))?); }
+        Ok(out)
+    }
+
+    /// Recompute each chunk's text checksum and compare it with the stored `text_sha256`.
+    /// Rows without a stored checksum (written before it existed) are counted as unchecked.
+    pub fn verify_integrity(&self) -> Result<IntegrityReport, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT chunk_id, text, text_sha256 FROM chunks ORDER BY rowid")
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, Option<String>>(2)?)))
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let mut report = IntegrityReport::default();
+        for r in rows {
+            let (chunk_id, text, stored) = r.map_err(|e| StoreError::Backend(e.to_string()))?;
+            match stored {
+                None => report.unchecked += 1,
+                Some(sum) => {
+                    report.checked += 1;
+                    if sum != text_sha256(&text) { report.corrupt.push(ChunkId(chunk_id)); }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Read a store-level setting from `store_meta`.
+    pub fn get_store_meta(&self, key: &str) -> Result<Option<String>, StoreError> {
+        self.conn
+            .query_row("SELECT value FROM store_meta WHERE key = ?1", [key], |r| r.get(0))
+            .optional()
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Insert or replace a store-level setting in `store_meta`.
+    pub fn set_store_meta(&self, key: &str, value: &str) -> Result<(), StoreError> {
+        self.conn
+            .execute("INSERT OR REPLACE INTO store_meta(key, value) VALUES (?1, ?2)", [key, value])
+            .map(|_| ())
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// True when `source_uri` with this content hash is recorded as completed in `ingest_journal`.
+    pub fn ingest_journal_contains(&self, source_uri: &str, content_sha256: &str) -> Result<bool, StoreError> {
+        self.conn
+            .query_row(
+                "SELECT 1 FROM ingest_journal WHERE source_uri = ?1 AND content_sha256 = ?2 AND state = 'completed'",
+                [source_uri, content_sha256],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|r| r.is_some())
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Record in `ingest_journal` that `source_uri` started ingesting as `doc_id`, replacing
+    /// any earlier unfinished entry for the path. `None` hashes are stored as `''`.
+    pub fn record_ingest_started(&self, source_uri: &str, content_sha256: Option<&str>, doc_id: &str, started_at: &str) -> Result<(), StoreError> {
+        let sha = content_sha256.unwrap_or("");
+        self.conn
+            .execute("DELETE FROM ingest_journal WHERE source_uri = ?1 AND state = 'started'", [source_uri])
+            .and_then(|_| {
+                self.conn.execute(
+                    "INSERT INTO ingest_journal(source_uri, content_sha256, state, doc_id, started_at) VALUES (?1, ?2, 'started', ?3, ?4)
+                     ON CONFLICT(source_uri, content_sha256) DO UPDATE SET state = 'started', doc_id = ?3, started_at = ?4, completed_at = NULL",
+                    [source_uri, sha, doc_id, started_at],
+                )
+            })
+            .map(|_| ())
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Record a fully ingested file in `ingest_journal`, completing its started entry if any.
+    pub fn record_ingest_completed(&self, source_uri: &str, content_sha256: &str, completed_at: &str) -> Result<(), StoreError> {
+        self.conn
+            .execute(
+                "INSERT INTO ingest_journal(source_uri, content_sha256, state, completed_at) VALUES (?1, ?2, 'completed', ?3)
+                 ON CONFLICT(source_uri, content_sha256) DO UPDATE SET state = 'completed', completed_at = ?3",
+                [source_uri, content_sha256, completed_at],
+            )
+            .map(|_| ())
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Drop the `ingest_journal` entry of `source_uri` with this hash (`None` as in
+    /// `record_ingest_started`). Returns whether one existed.
+    pub fn remove_ingest_entry(&self, source_uri: &str, content_sha256: Option<&str>) -> Result<bool, StoreError> {
+        self.conn
+            .execute(
+                "DELETE FROM ingest_journal WHERE source_uri = ?1 AND content_sha256 = ?2",
+                [source_uri, content_sha256.unwrap_or("")],
+            )
+            .map(|n| n > 0)
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Entries still in the 'started' state, i.e. ingests cut off by a crash, in start order.
+    pub fn list_started_ingests(&self) -> Result<Vec<StartedIngest>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT source_uri, content_sha256, COALESCE(doc_id, ''), COALESCE(started_at, '') FROM ingest_journal
+                 WHERE state = 'started' ORDER BY started_at, rowid",
+            )
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map([], |r| {
+                let sha: String = r.get(1)?;
+                Ok(StartedIngest {
+                    source_uri: r.get(0)?,
+                    content_sha256: Some(sha).filter(|s| !s.is_empty()),
+                    doc_id: r.get(2)?,
+                    started_at: r.get(3)?,
+                })
+            })
+            .map_err(|e| StoreError::Backend(e.to_string()))?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        Ok(rows)
+    }
+
+    /// Forget all journal entries so the next resumable ingest processes every file again.
+    pub fn clear_ingest_journal(&self) -> Result<usize, StoreError> {
+        self.conn.execute("DELETE FROM ingest_journal", []).map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Insert or replace the attachment `key` of document `doc_id`.
+    pub fn put_doc_blob(&self, doc_id: &str, key: &str, blob: &[u8]) -> Result<(), StoreError> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO document_blobs(doc_id, key, blob) VALUES (?1, ?2, ?3)",
+                rusqlite::params![doc_id, key, blob],
+            )
+            .map(|_| ())
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Attachment `key` of document `doc_id`, if stored.
+    pub fn get_doc_blob(&self, doc_id: &str, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        self.conn
+            .query_row("SELECT blob FROM document_blobs WHERE doc_id = ?1 AND key = ?2", [doc_id, key], |r| r.get(0))
+            .optional()
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// `(key, size in bytes)` of every attachment of `doc_id`, ordered by key.
+    pub fn list_doc_blobs(&self, doc_id: &str) -> Result<Vec<(String, u64)>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, length(blob) FROM document_blobs WHERE doc_id = ?1 ORDER BY key")
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map([doc_id], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)? as u64)))
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Remove attachment `key` of `doc_id`; true when it existed.
+    pub fn delete_doc_blob(&self, doc_id: &str, key: &str) -> Result<bool, StoreError> {
+        self.conn
+            .execute("DELETE FROM document_blobs WHERE doc_id = ?1 AND key = ?2", [doc_id, key])
+            .map(|n| n > 0)
+            .map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Add `count` to the frequency of each term in `suggest_terms` (inserting new ones). When
+    /// the table then holds more than `max_terms` terms (0 = no cap), the least frequent are
+    /// pruned.
+    pub fn add_suggest_terms(&mut self, terms: &[(String, u64)], max_terms: usize) -> Result<(), StoreError> {
+        if terms.is_empty() { return Ok(()); }
+        let tx = self.conn.transaction().map_err(|e| StoreError::Backend(e.to_string()))?;
+        write_suggest_terms(&tx, terms, max_terms).map_err(|e| StoreError::Backend(e.to_string()))?;
+        tx.commit().map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Up to `limit` terms starting with `prefix`, most frequent first (ties by term).
+    pub fn suggest_terms(&self, prefix: &str, limit: usize) -> Result<Vec<(String, u64)>, StoreError> {
+        // Range scan on the primary key instead of LIKE, so `%`/`_` in the prefix are literal
+        let upper = format!("{prefix}\u{10FFFF}");
+        let mut stmt = self
+            .conn
+            .prepare("SELECT term, freq FROM suggest_terms WHERE term >= ?1 AND term < ?2 ORDER BY freq DESC, term LIMIT ?3")
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let rows = stmt
+            .query_map(rusqlite::params![prefix, upper, limit as i64], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)? as u64)))
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map_err(|e| StoreError::Backend(e.to_string()))
+    }
+
+    /// Record a search of `query` that returned `chunk_ids` (best first); returns its log id.
+    pub fn log_search(&mut self, query: &str, logged_at: &str, chunk_ids: &[ChunkId]) -> Result<i64, StoreError> {
+        let tx = self.conn.transaction().map_err(|e| StoreError::Backend(e.to_string()))?;
+        tx.execute("INSERT INTO search_log(query, logged_at) VALUES (?1, ?2)", rusqlite::params![query, logged_at])
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let log_id = tx.last_insert_rowid();
+        {
+            let mut stmt = tx
+                .prepare("INSERT INTO search_log_hits(log_id, rank, chunk_id) VALUES (?1, ?2, ?3)")
+                .map_err(|e| StoreError::Backend(e.to_string()))?;
+            for (rank, id) in chunk_ids.iter().enumerate() {
+                stmt.execute(rusqlite::params![log_id, rank as i64, id.0]).map_err(|e| StoreError::Backend(e.to_string()))?;
+            }
+        }
+        tx.commit().map_err(|e| StoreError::Backend(e.to_string()))?;
+        Ok(log_id)
+    }
+
+    /// Attach a relevance label (e.g., 1.0 for a click) to `chunk_id` in logged search
+    /// `log_id`; false when that search did not return the chunk.
+    pub fn set_search_label(&self, log_id: i64, chunk_id: &ChunkId, label: f32) -> Result<bool, StoreError> {
+        let n = self
+            .conn
+            .execute("UPDATE search_log_hits SET label = ?3 WHERE log_id = ?1 AND chunk_id = ?2", rusqlite::params![log_id, chunk_id.0, label as f64])
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        Ok(n > 0)
+    }
+
+    /// Up to `limit` logged searches with a log id above `after_id`, oldest first.
+    pub fn list_search_log(&self, after_id: i64, limit: usize) -> Result<Vec<SearchLogEntry>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT log_id, query, logged_at FROM search_log WHERE log_id > ?1 ORDER BY log_id LIMIT ?2")
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let mut entries = stmt
+            .query_map(rusqlite::params![after_id, limit as i64], |r| {
+                Ok(SearchLogEntry { log_id: r.get(0)?, query: r.get(1)?, logged_at: r.get(2)?, hits: Vec::new() })
+            })
+            .map_err(|e| StoreError::Backend(e.to_string()))?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        let mut hits_stmt = self
+            .conn
+            .prepare("SELECT chunk_id, label FROM search_log_hits WHERE log_id = ?1 ORDER BY rank")
+            .map_err(|e| StoreError::Backend(e.to_string()))?;
+        for entry in &mut entries {
+            entry.hits = hits_stmt
+                .query_map([entry.log_id], |r| Ok((ChunkId(r.get(0)?), r.get::<_, Option<f64>>(1)?.map(|l| l as f32))))
+                .map_err(|e| StoreError::Backend(e.to_string()))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| StoreError::Backend(e.to_string()))?;
+        }
+        Ok(entries)
+    }
+
+    /// Create (if absent) an expression index on `meta[key]` so equality filters on that key
+    /// (e.g., a collection tag) avoid a full table scan. The filter must use the same
+    /// expression, see [`meta_extract_sql`].
+    pub fn ensure_meta_index(&self, key: &str) -> Result<(), StoreError> {
+        let ident: String = key.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        let sql = format!(
+            "CREATE INDEX IF NOT EXISTS idx_chunks_meta_{ident} ON chunks({})",
+            meta_extract_sql("meta_json", key)
+        );
+        self.conn.execute_batch(&sql).map_err(|e| StoreError::Backend(e.to_string()))
+    }
+}
+
+/// Outcome of [`SqliteRepo::verify_integrity`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// Rows whose checksum was recomputed.
+    pub checked: usize,
+    /// Rows without a stored checksum.
+    pub unchecked: usize,
+    /// Chunks whose text no longer matches the stored checksum.
+    pub corrupt: Vec<ChunkId>,
+}
+
+/// Outcome of [`SqliteRepo::find_orphans`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrphanReport {
+    /// FileRecords whose document has no chunks left.
+    pub files_without_chunks: Vec<DocumentId>,
+    /// Chunks whose document has no FileRecord.
+    pub chunks_without_file: Vec<ChunkId>,
+}
+
+/// A stored document with aggregates over its current chunks, see [`SqliteRepo::list_documents`].
+#[derive(Debug, Clone)]
+pub struct DocSummary {
+    pub file: FileRecord,
+    /// Chunks stored right now; 0 marks an orphaned FileRecord.
+    pub live_chunks: u64,
+    /// Lowest `page_start` and highest page end over the chunks, if any chunk has pages.
+    pub page_min: Option<u32>,
+    pub page_max: Option<u32>,
+}
+
+/// An unfinished `ingest_journal` entry, see [`SqliteRepo::list_started_ingests`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartedIngest {
+    pub source_uri: String,
+    pub content_sha256: Option<String>,
+    pub doc_id: String,
+    /// RFC 3339 time the ingest started.
+    pub started_at: String,
+}
+
+/// One logged search, see [`SqliteRepo::log_search`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchLogEntry {
+    pub log_id: i64,
+    pub query: String,
+    pub logged_at: String,
+    /// Returned chunks, best first, with their label if one was set.
+    pub hits: Vec<(ChunkId, Option<f32>)>,
+}
+
+fn text_sha256(text: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let digest = Sha256::digest(text.as_bytes());
+    let mut hex = String::with_capacity(64);
+    for b in digest { hex.push_str(&format!("{:02x}", b)); }
+    hex
+}
+
+/// SQL expression extracting `meta[key]` from the given JSON column, with the path inlined
+/// as a literal so SQLite can match it against expression indexes.
+pub fn meta_extract_sql(column: &str, key: &str) -> String {
+    let path = format!("$.\"{}\"", key.replace('"', "\\\"")).replace('\'', "''");
+    format!("json_extract({column}, '{path}')")
+}
+
+/// SQL condition true when the JSON array in `column` holds any of the bound kinds (`marks` is `?,?,...`).
+pub fn block_kinds_in_sql(column: &str, marks: &str) -> String {
+    format!("EXISTS (SELECT 1 FROM json_each({column}) WHERE json_each.value IN ({marks}))")
+}
+
+/// SQL condition comparing the first `n` components of the JSON array in `column` with `n` bound values.
+pub fn section_prefix_sql(column: &str, n: usize) -> String {
+    (0..n).map(|i| format!("json_extract({column}, '$[{i}]') = ?")).collect::<Vec<_>>().join(" AND ")
+}
+
+/// SQL expression for the basename of the URI in `column` (see `crate::source_filename`):
+/// `rtrim` strips the non-separator tail, so its length is the offset of the last separator.
+pub fn source_filename_sql(column: &str) -> String {
+    let u = format!("replace({column}, '\\', '/')");
+    format!("substr({u}, length(rtrim({u}, replace({u}, '/', ''))) + 1)")
+}
+
+/// SQL condition (with its bound values) for a filename prefix/contains filter over `column`.
+/// Compares with `substr`/`instr` rather than `LIKE`, so it is case-sensitive and `%`/`_` are literal.
+pub fn filename_filter_sql(column: &str, needle: &str, prefix: bool) -> Option<(String, Vec<String>)> {
+    if needle.is_empty() { return None; }
+    let name = source_filename_sql(column);
+    let needles = crate::filename_needles(needle);
+    let conds: Vec<String> = needles
+        .iter()
+        .map(|_| if prefix { format!("substr({name}, 1, length(?)) = ?") } else { format!("instr({name}, ?) > 0") })
+        .collect();
+    let params = needles.into_iter().flat_map(|n| if prefix { vec![n.clone(), n] } else { vec![n] }).collect();
+    Some((format!("({})", conds.join(" OR ")), params))
+}
+
+/// `expr IN (...)` (or `NOT IN`) over `values`, with its bound values. Lists longer than
+/// `IDS_PER_QUERY` bind one JSON array read through `json_each`, so a filter of any length
+/// stays under SQLite's parameter limit.
+pub fn in_list_sql(expr: &str, values: &[String], negate: bool) -> (String, Vec<String>) {
+    let not = if negate { "NOT " } else { "" };
+    if values.len() <= IDS_PER_QUERY {
+        let marks = vec!["?"; values.len()].join(",");
+        (format!("{expr} {not}IN ({marks})"), values.to_vec())
+    } else {
+        let array = serde_json::to_string(values).unwrap_or_else(|_| "[]".into());
+        (format!("{expr} {not}IN (SELECT value FROM json_each(?))"), vec![array])
+    }
+}
+
+/// Escape `%`, `_` and `\` in `s` for a `LIKE ... ESCAPE '\'` pattern.
+pub fn like_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '\\') { out.push('\\'); }
+        out.push(c);
+    }
+    out
+}
+
+/// SQL condition (with its bound values) for a prefix or substring match on `expr`; `%` and
+/// `_` in `needle` are literal. `LIKE` folds ASCII case, so a case-sensitive match adds a
+/// `substr`/`instr` check to agree with the Rust post-filter. A NULL `expr` (absent meta key)
+/// never matches.
+pub fn like_filter_sql(expr: &str, needle: &str, prefix: bool, case_insensitive: bool) -> (String, Vec<String>) {
+    let escaped = like_escape(needle);
+    let pattern = if prefix { format!("{escaped}%") } else { format!("%{escaped}%") };
+    let like = format!("{expr} LIKE ? ESCAPE '\\'");
+    match (case_insensitive, prefix) {
+        (true, _) => (like, vec![pattern]),
+        (false, true) => (format!("({like} AND substr({expr}, 1, length(?)) = ?)"), vec![pattern, needle.to_string(), needle.to_string()]),
+        (false, false) => (format!("({like} AND instr({expr}, ?) > 0)"), vec![pattern, needle.to_string()]),
+    }
+}
+
+/// `= ?`, with `COLLATE NOCASE` (ASCII folding) when `case_insensitive`.
+pub fn eq_sql(case_insensitive: bool) -> &'static str {
+    if case_insensitive { "= ? COLLATE NOCASE" } else { "= ?" }
+}
+
+/// Serialized name of a block kind as stored in `block_kinds_json`.
+pub fn block_kind_name(kind: &chunk_model::BlockKind) -> String {
+    match serde_json::to_value(kind) {
+        Ok(JsonValue::String(s)) => s,
+        _ => format!("{kind:?}"),
+    }
+}
+
+/// Append the SQL condition(s) for one filter op as ` AND ...` terms over the chunks table.
+fn push_filter_sql(op: &FilterOp, where_sql: &mut String, params: &mut Vec<rusqlite::types::Value>) {
+    match op {
+        FilterOp::DocIdEq(v) => {
+            where_sql.push_str(" AND doc_id = ?");
+            params.push(v.clone().into());
+        }
+        // As in the in-memory post-filters, an empty IN matches nothing
+        FilterOp::DocIdIn(vs) if vs.is_empty() => where_sql.push_str(" AND 1=0"),
+        FilterOp::DocIdIn(vs) => {
+            let (cond, vals) = in_list_sql("doc_id", vs, false);
+            where_sql.push_str(&format!(" AND {cond}"));
+            params.extend(vals.into_iter().map(Into::into));
+        }
+        FilterOp::SourceUriPrefix { prefix, case_insensitive } => {
+            let (cond, vals) = like_filter_sql("source_uri", prefix, true, *case_insensitive);
+            where_sql.push_str(&format!(" AND {cond}"));
+            params.extend(vals.into_iter().map(Into::into));
+        }
+        FilterOp::SourceFilenamePrefix(n) | FilterOp::SourceFilenameContains(n) => {
+            let prefix = matches!(op, FilterOp::SourceFilenamePrefix(_));
+            if let Some((cond, vals)) = filename_filter_sql("source_uri", n, prefix) {
+                where_sql.push_str(&format!(" AND {cond}"));
+                params.extend(vals.into_iter().map(Into::into));
+            }
+        }
+        // ISO 8601 range (lexicographic compare) on extracted_at or a meta value
+        FilterOp::RangeIsoDate { key, start, end, start_incl, end_incl } => {
+            let col = if key == "extracted_at" { key.clone() } else { meta_extract_sql("meta_json", key) };
+            if let Some(s) = start {
+                where_sql.push_str(&format!(" AND {col} {} ?", if *start_incl { ">=" } else { ">" }));
+                params.push(s.clone().into());
+            }
+            if let Some(e) = end {
+                where_sql.push_str(&format!(" AND {col} {} ?", if *end_incl { "<=" } else { "<" }));
+                params.push(e.clone().into());
+            }
+        }
+        FilterOp::DocIdNotIn(vs) => {
+            if !vs.is_empty() {
+                let (cond, vals) = in_list_sql("doc_id", vs, true);
+                where_sql.push_str(&format!(" AND {cond}"));
+                params.extend(vals.into_iter().map(Into::into));
+            }
+        }
+        // Meta inequality; `IS NOT` also keeps rows where the key is absent (NULL)
+        FilterOp::MetaNe { key, value } => {
+            where_sql.push_str(&format!(" AND {} IS NOT ?", meta_extract_sql("meta_json", key)));
+            params.push(value.clone().into());
+        }
+        // Meta equality via JSON1
+        FilterOp::MetaEq { key, value, case_insensitive } => {
+            where_sql.push_str(&format!(" AND {} {}", meta_extract_sql("meta_json", key), eq_sql(*case_insensitive)));
+            params.push(value.clone().into());
+        }
+        // Partial meta match; the key must be present
+        FilterOp::MetaPrefix { key, prefix: needle, case_insensitive } | FilterOp::MetaContains { key, substring: needle, case_insensitive } => {
+            let prefix = matches!(op, FilterOp::MetaPrefix { .. });
+            let (cond, vals) = like_filter_sql(&meta_extract_sql("meta_json", key), needle, prefix, *case_insensitive);
+            where_sql.push_str(&format!(" AND {cond}"));
+            params.extend(vals.into_iter().map(Into::into));
+        }
+        // Meta IN via JSON1; empty matches nothing
+        FilterOp::MetaIn { values, .. } if values.is_empty() => where_sql.push_str(" AND 1=0"),
+        FilterOp::MetaIn { key, values } => {
+            let (cond, vals) = in_list_sql(&meta_extract_sql("meta_json", key), values, false);
+            where_sql.push_str(&format!(" AND {cond}"));
+            params.extend(vals.into_iter().map(Into::into));
+        }
+        // Any stored block kind in the list via JSON1 json_each
+        FilterOp::BlockKindIn(kinds) => {
+            if !kinds.is_empty() {
+                let marks = vec!["?"; kinds.len()].join(",");
+                where_sql.push_str(&format!(" AND {}", block_kinds_in_sql("block_kinds_json", &marks)));
+                for k in kinds { params.push(block_kind_name(k).into()); }
+            }
+        }
+        // Outline subtree: exact match on each leading component of the stored path
+        FilterOp::SectionPathPrefix(prefix) => {
+            if !prefix.is_empty() {
+                where_sql.push_str(&format!(" AND {}", section_prefix_sql("section_path_json", prefix.len())));
+                for c in prefix { params.push(c.clone().into()); }
+            }
+        }
+        // Numeric range on columns (page_start/page_end) or meta via JSON1 + CAST
+        FilterOp::RangeNumeric { key, min, max, min_incl, max_incl } => {
+            let mut push_bound = |col: &str, is_min: bool, incl: bool, val: f64| {
+                where_sql.push_str(" AND ");
+                where_sql.push_str(col);
+                where_sql.push_str(" ");
+                if is_min { if incl { where_sql.push_str(">= ?"); } else { where_sql.push_str("> ?"); } }
+                else { if incl { where_sql.push_str("<= ?"); } else { where_sql.push_str("< ?"); } }
+                params.push(val.into());
+            };
+            match key.as_str() {
+                "page_start" => {
+                    if let Some(lo) = min { push_bound("page_start", true, *min_incl, *lo as f64); }
+                    if let Some(hi) = max { push_bound("page_start", false, *max_incl, *hi as f64); }
+                }
+                "page_end" => {
+                    if let Some(lo) = min { push_bound("page_end", true, *min_incl, *lo as f64); }
+                    if let Some(hi) = max { push_bound("page_end", false, *max_incl, *hi as f64); }
+                }
+                _ => {
+                    if let Some(lo) = min {
+                        where_sql.push_str(" AND CAST(json_extract(meta_json, ?) AS REAL) ");
+                        if *min_incl { where_sql.push_str(">= ?"); } else { where_sql.push_str("> ?"); }
+                        let path = format!("$.\"{}\"", key.replace('"', "\""));
+                        params.push(path.into());
+                        params.push((*lo as f64).into());
+                    }
+                    if let Some(hi) = max {
+                        where_sql.push_str(" AND CAST(json_extract(meta_json, ?) AS REAL) ");
+                        if *max_incl { where_sql.push_str("<= ?"); } else { where_sql.push_str("< ?"); }
+                        let path = format!("$.\"{}\"", key.replace('"', "\""));
+                        params.push(path.into());
+                        params.push((*hi as f64).into());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Append a parenthesized boolean condition for a filter tree. Empty `And` is true,
+/// empty `Or` is false.
+fn push_filter_expr_sql(expr: &FilterExpr, where_sql: &mut String, params: &mut Vec<rusqlite::types::Value>) {
+    match expr {
+        FilterExpr::Leaf(clause) => {
+            where_sql.push_str("(1=1");
+            push_filter_sql(&clause.op, where_sql, params);
+            where_sql.push(')');
+        }
+        FilterExpr::And(children) | FilterExpr::Or(children) => {
+            let (joiner, empty) = if matches!(expr, FilterExpr::And(_)) { (" AND ", "(1=1)") } else { (" OR ", "(1=0)") };
+            if children.is_empty() { where_sql.push_str(empty); return; }
+            where_sql.push('(');
+            for (i, child) in children.iter().enumerate() {
+                if i > 0 { where_sql.push_str(joiner); }
+                push_filter_expr_sql(child, where_sql, params);
+            }
+            where_sql.push(')');
+        }
+    }
 }
 
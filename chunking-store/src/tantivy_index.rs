@@ -6,6 +6,78 @@
 #[cfg(feature = "tantivy-impl")]
 pub use real::{TantivyIndex, TokenCombine};
 
+/// Filter pushdown supported by the Tantivy searcher. Meta equality/IN is pushed down on
+/// indexes that have the `meta` field; other meta ops and numeric ranges are post-filtered.
+pub const TANTIVY_CAPS: crate::IndexCaps = crate::IndexCaps {
+    can_prefilter_doc_id_eq: true,
+    can_prefilter_doc_id_in: true,
+    can_prefilter_source_prefix: true,
+    can_prefilter_meta: false,
+    can_prefilter_meta_eq: true,
+    can_prefilter_range_numeric: false,
+    can_prefilter_range_date: true,
+};
+
+/// Analyzer for the `text` field. It is persisted in the index schema (as the field's
+/// tokenizer name), so reopening an index always uses the tokenizer it was built with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenizerKind {
+    /// Tantivy's default: splits on non-alphanumerics and lowercases.
+    Default,
+    /// Lowercased character n-grams of `min..=max` chars. Bigrams (`min = max = 2`) give
+    /// dictionary-free recall on Japanese text.
+    Ngram { min: usize, max: usize },
+    /// Lindera morphological analysis (IPADIC); requires the `lindera` feature.
+    Lindera,
+}
+
+impl Default for TokenizerKind {
+    fn default() -> Self {
+        if cfg!(feature = "lindera") { TokenizerKind::Lindera } else { TokenizerKind::Default }
+    }
+}
+
+impl TokenizerKind {
+    /// Name registered with Tantivy and stored in the schema.
+    pub fn name(self) -> String {
+        match self {
+            TokenizerKind::Default => "default".into(),
+            TokenizerKind::Ngram { min, max } => format!("ngram_{min}_{max}"),
+            TokenizerKind::Lindera => "ja".into(),
+        }
+    }
+
+    /// Inverse of `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(TokenizerKind::Default),
+            "ja" => Some(TokenizerKind::Lindera),
+            _ => {
+                let (min, max) = name.strip_prefix("ngram_")?.split_once('_')?;
+                Some(TokenizerKind::Ngram { min: min.parse().ok()?, max: max.parse().ok()? })
+            }
+        }
+    }
+}
+
+/// Options for opening a Tantivy index. `tokenizer` only applies when a new index is
+/// created; `heading_boost` is a query-time setting and always applies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TantivyOpts {
+    pub tokenizer: TokenizerKind,
+    /// Weight of `section_path` heading matches relative to body text in `search_ids`.
+    pub heading_boost: f32,
+}
+
+/// Default `TantivyOpts::heading_boost`.
+pub const DEFAULT_HEADING_BOOST: f32 = 3.0;
+
+impl Default for TantivyOpts {
+    fn default() -> Self {
+        Self { tokenizer: TokenizerKind::default(), heading_boost: DEFAULT_HEADING_BOOST }
+    }
+}
+
 #[cfg(not(feature = "tantivy-impl"))]
 pub struct TantivyIndex;
 
@@ -18,16 +90,7 @@ impl TantivyIndex {
 #[cfg(not(feature = "tantivy-impl"))]
 impl crate::TextSearcher for TantivyIndex {
     fn name(&self) -> &'static str { "tantivy" }
-    fn caps(&self) -> crate::IndexCaps {
-        crate::IndexCaps {
-            can_prefilter_doc_id_eq: true,
-            can_prefilter_doc_id_in: true,
-            can_prefilter_source_prefix: true,
-            can_prefilter_meta: false,
-            can_prefilter_range_numeric: false,
-            can_prefilter_range_date: true,
-        }
-    }
+    fn caps(&self) -> crate::IndexCaps { TANTIVY_CAPS }
     fn search_ids(&self, _store: &dyn crate::ChunkStoreRead, _query: &str, _filters: &[crate::FilterClause], _opts: &crate::SearchOptions) -> Vec<crate::TextMatch> { Vec::new() }
 }
 
@@ -36,13 +99,15 @@ mod real {
     use chunk_model::ChunkRecord;
     use chrono::DateTime;
     use tantivy::collector::TopDocs;
-    use tantivy::query::{BooleanQuery, Occur, QueryParser, RangeQuery, TermQuery};
+    use tantivy::query::{BooleanQuery, Occur, PhraseQuery, QueryParser, RangeQuery, RegexQuery, TermQuery};
     use tantivy::schema::{IndexRecordOption, NumericOptions, Schema, STRING, STORED, TextFieldIndexing, TextOptions};
     use tantivy::schema::Value as _;
+    use tantivy::snippet::SnippetGenerator;
     use tantivy::{Index, Term};
     use tantivy::doc;
-    use tantivy::tokenizer::TokenStream;
-    use crate::{ChunkStoreRead, FilterClause, FilterOp, IndexCaps, SearchOptions, TextMatch, TextSearcher};
+    use tantivy::tokenizer::{LowerCaser, NgramTokenizer, TextAnalyzer, TokenStream};
+    use super::{TantivyOpts, TokenizerKind};
+    use crate::{ChunkStoreRead, FilterClause, FilterOp, IndexCaps, SearchOptions, TextMatch, TextSearcher, TextSnippet};
     // use std::ops::Range;
     use std::path::Path;
 
@@ -57,29 +122,69 @@ mod real {
         f_source_uri: tantivy::schema::Field,
         f_extracted_at: tantivy::schema::Field,
         f_extracted_at_ts: tantivy::schema::Field,
+        /// Chunk language tag; absent on indexes created before the field existed.
+        f_lang: Option<tantivy::schema::Field>,
+        /// Chunk `section_path` headings, analyzed like `f_text`; absent on older indexes.
+        f_heading: Option<tantivy::schema::Field>,
+        /// One `meta_term` per chunk meta entry; absent on older indexes.
+        f_meta: Option<tantivy::schema::Field>,
+        /// Analyzer of `f_text`, used for both indexing and querying.
+        tokenizer: TokenizerKind,
+        heading_boost: f32,
     }
 
     #[derive(Debug, Clone, Copy)]
-    pub enum TokenCombine { AND, OR }
+    pub enum TokenCombine {
+        AND,
+        OR,
+        /// Tokens must appear in query order; `slop` is the number of extra positions
+        /// allowed between them (0 = exact phrase).
+        Phrase { slop: u32 },
+    }
 
     impl TantivyIndex {
-        fn build_schema() -> (Schema, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field) {
+        fn build_schema(tokenizer: TokenizerKind) -> (Schema, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field, tantivy::schema::Field) {
             let mut schema_builder = Schema::builder();
             let mut text_indexing = TextFieldIndexing::default();
-            text_indexing = text_indexing.set_tokenizer("ja");
+            text_indexing = text_indexing.set_tokenizer(&tokenizer.name());
             text_indexing = text_indexing.set_index_option(IndexRecordOption::WithFreqsAndPositions);
             let text_options = TextOptions::default().set_indexing_options(text_indexing);
-            let text = schema_builder.add_text_field("text", text_options);
+            let text = schema_builder.add_text_field("text", text_options.clone());
             let chunk_id = schema_builder.add_text_field("chunk_id", STRING | STORED);
             let doc_id = schema_builder.add_text_field("doc_id", STRING);
             let source_uri = schema_builder.add_text_field("source_uri", STRING);
             let extracted_at = schema_builder.add_text_field("extracted_at", STRING);
             let num_opts = NumericOptions::default().set_fast().set_indexed();
             let extracted_at_ts = schema_builder.add_i64_field("extracted_at_ts", num_opts);
+            let lang = schema_builder.add_text_field("lang", STRING);
+            let heading = schema_builder.add_text_field("heading", text_options);
+            let meta = schema_builder.add_text_field("meta", STRING);
             let schema = schema_builder.build();
-            (schema, text, chunk_id, doc_id, source_uri, extracted_at, extracted_at_ts)
+            (schema, text, chunk_id, doc_id, source_uri, extracted_at, extracted_at_ts, lang, heading, meta)
+        }
+
+        /// Make `kind` available under its schema name ("default" is built in).
+        fn register_tokenizer(index: &Index, kind: TokenizerKind) -> tantivy::Result<()> {
+            match kind {
+                TokenizerKind::Default => Ok(()),
+                TokenizerKind::Ngram { min, max } => {
+                    let analyzer = TextAnalyzer::builder(NgramTokenizer::new(min, max, false)?).filter(LowerCaser).build();
+                    index.tokenizers().register(&kind.name(), analyzer);
+                    Ok(())
+                }
+                #[cfg(feature = "lindera")]
+                TokenizerKind::Lindera => {
+                    Self::register_ja_tokenizer(index);
+                    Ok(())
+                }
+                #[cfg(not(feature = "lindera"))]
+                TokenizerKind::Lindera => Err(tantivy::TantivyError::InvalidArgument(
+                    "the Lindera tokenizer requires the `lindera` feature".into(),
+                )),
+            }
         }
 
+        #[cfg(feature = "lindera")]
         fn register_ja_tokenizer(index: &Index) {
             use lindera::dictionary::load_dictionary;
             use lindera::mode::Mode;
@@ -112,25 +217,35 @@ mod real {
         }
 
         pub fn new_ram() -> tantivy::Result<Self> {
-            let (schema, text, chunk_id, doc_id, source_uri, extracted_at, extracted_at_ts) = Self::build_schema();
+            Self::new_ram_with_opts(TantivyOpts::default())
+        }
+
+        pub fn new_ram_with_opts(opts: TantivyOpts) -> tantivy::Result<Self> {
+            let (schema, text, chunk_id, doc_id, source_uri, extracted_at, extracted_at_ts, lang, heading, meta) = Self::build_schema(opts.tokenizer);
             let index = Index::create_in_ram(schema.clone());
-            Self::register_ja_tokenizer(&index);
+            Self::register_tokenizer(&index, opts.tokenizer)?;
             let reader = index.reader()?;
-            Ok(Self { schema, index, reader, f_text: text, f_chunk_id: chunk_id, f_doc_id: doc_id, f_source_uri: source_uri, f_extracted_at: extracted_at, f_extracted_at_ts: extracted_at_ts })
+            Ok(Self { schema, index, reader, f_text: text, f_chunk_id: chunk_id, f_doc_id: doc_id, f_source_uri: source_uri, f_extracted_at: extracted_at, f_extracted_at_ts: extracted_at_ts, f_lang: Some(lang), f_heading: Some(heading), f_meta: Some(meta), tokenizer: opts.tokenizer, heading_boost: opts.heading_boost })
         }
 
         /// Open an existing on-disk index at `path`, or create a new one if absent.
         pub fn open_or_create_dir<P: AsRef<Path>>(path: P) -> tantivy::Result<Self> {
+            Self::open_or_create_dir_with_opts(path, TantivyOpts::default())
+        }
+
+        /// Like `open_or_create_dir`; `opts` only apply when a new index is created. An
+        /// existing index keeps the tokenizer recorded in its schema.
+        pub fn open_or_create_dir_with_opts<P: AsRef<Path>>(path: P, opts: TantivyOpts) -> tantivy::Result<Self> {
             let dir = path.as_ref();
             std::fs::create_dir_all(dir).map_err(|e| tantivy::TantivyError::IoError(e.into()))?;
             let index = match Index::open_in_dir(dir) {
                 Ok(idx) => idx,
                 Err(_) => {
-                    let (schema, text, chunk_id, doc_id, source_uri, extracted_at, extracted_at_ts) = Self::build_schema();
+                    let (schema, text, chunk_id, doc_id, source_uri, extracted_at, extracted_at_ts, lang, heading, meta) = Self::build_schema(opts.tokenizer);
                     let idx = Index::create_in_dir(dir, schema.clone())?;
-                    Self::register_ja_tokenizer(&idx);
+                    Self::register_tokenizer(&idx, opts.tokenizer)?;
                     let reader = idx.reader()?;
-                    return Ok(Self { schema, index: idx, reader, f_text: text, f_chunk_id: chunk_id, f_doc_id: doc_id, f_source_uri: source_uri, f_extracted_at: extracted_at, f_extracted_at_ts: extracted_at_ts });
+                    return Ok(Self { schema, index: idx, reader, f_text: text, f_chunk_id: chunk_id, f_doc_id: doc_id, f_source_uri: source_uri, f_extracted_at: extracted_at, f_extracted_at_ts: extracted_at_ts, f_lang: Some(lang), f_heading: Some(heading), f_meta: Some(meta), tokenizer: opts.tokenizer, heading_boost: opts.heading_boost });
                 }
             };
             // existing index: derive fields by name
@@ -141,11 +256,25 @@ mod real {
             let f_source_uri = schema.get_field("source_uri")?;
             let f_extracted_at = schema.get_field("extracted_at")?;
             let f_extracted_at_ts = schema.get_field("extracted_at_ts")?;
-            Self::register_ja_tokenizer(&index);
+            let f_lang = schema.get_field("lang").ok();
+            let f_heading = schema.get_field("heading").ok();
+            let f_meta = schema.get_field("meta").ok();
+            let name = match schema.get_field_entry(f_text).field_type() {
+                tantivy::schema::FieldType::Str(o) => o.get_indexing_options().map(|i| i.tokenizer().to_string()),
+                _ => None,
+            };
+            let tokenizer = name
+                .as_deref()
+                .and_then(TokenizerKind::from_name)
+                .ok_or_else(|| tantivy::TantivyError::InvalidArgument(format!("unknown text tokenizer in index: {name:?}")))?;
+            Self::register_tokenizer(&index, tokenizer)?;
             let reader = index.reader()?;
-            Ok(Self { schema, index, reader, f_text, f_chunk_id, f_doc_id, f_source_uri, f_extracted_at, f_extracted_at_ts })
+            Ok(Self { schema, index, reader, f_text, f_chunk_id, f_doc_id, f_source_uri, f_extracted_at, f_extracted_at_ts, f_lang, f_heading, f_meta, tokenizer, heading_boost: opts.heading_boost })
         }
 
+        /// Tokenizer of the `text` field (as recorded in the index schema).
+        pub fn tokenizer(&self) -> TokenizerKind { self.tokenizer }
+
         pub fn upsert_records(&self, records: &[ChunkRecord]) -> tantivy::Result<()> {
             let mut writer = self.index.writer(50_000_000)?;
             for rec in records {
@@ -163,15 +292,83 @@ mod real {
                 if let Some(ts) = parse_rfc3339_to_ts(&rec.extracted_at) {
                     doc.add_i64(self.f_extracted_at_ts, ts);
                 }
+                if let (Some(f), Some(lang)) = (self.f_lang, rec.meta.get(chunk_model::META_LANG)) {
+                    doc.add_text(f, lang);
+                }
+                if let (Some(f), Some(path)) = (self.f_heading, rec.section_path.as_ref().filter(|p| !p.is_empty())) {
+                    doc.add_text(f, path.join(" / "));
+                }
+                if let Some(f) = self.f_meta {
+                    for (k, v) in &rec.meta { doc.add_text(f, meta_term(k, v)); }
+                }
                 let _ = writer.add_document(doc);
             }
             writer.commit()?;
             self.reader.reload()?;
             Ok(())
+        }
+
+        /// Chunk ids of every live document in the index (as of the last reader reload).
+        pub fn chunk_ids(&self) -> tantivy::Result<Vec<String>> {
+            let searcher = self.reader.searcher();
+            let mut out = Vec::new();
+            for addr in searcher.search(&tantivy::query::AllQuery, &tantivy::collector::DocSetCollector)? {
+                let doc = searcher.doc::<tantivy::schema::document::TantivyDocument>(addr)?;
+                if let Some(v) = doc.get_first(self.f_chunk_id).and_then(|v| v.as_str()) { out.push(v.to_string()); }
+            }
+            Ok(out)
+        }
+
+        /// Term query restricting hits to `opts.lang`. On indexes without the `lang` field
+        /// nothing can match, so an impossible term is returned instead of silently ignoring it.
+        fn lang_query(&self, opts: &SearchOptions) -> Option<Box<dyn tantivy::query::Query>> {
+            let lang = opts.lang.as_ref()?;
+            let term = match self.f_lang {
+                Some(f) => Term::from_field_text(f, lang),
+                None => Term::from_field_text(self.f_chunk_id, ""),
+            };
+            Some(Box::new(TermQuery::new(term, IndexRecordOption::Basic)))
+        }
+
+        /// `MetaEq`/`MetaIn` clauses as term queries on the `meta` field. Indexes without the
+        /// field return none; callers post-filter those ops (see `caps`).
+        fn meta_queries(&self, filters: &[FilterClause]) -> Vec<(Occur, Box<dyn tantivy::query::Query>)> {
+            let Some(f) = self.f_meta else { return Vec::new() };
+            let term = |k: &str, v: &str| -> Box<dyn tantivy::query::Query> {
+                Box::new(TermQuery::new(Term::from_field_text(f, &meta_term(k, v)), IndexRecordOption::Basic))
+            };
+            let mut out = Vec::new();
+            for fc in filters {
+                match &fc.op {
+                    FilterOp::MetaEq { key, value, case_insensitive: false } => out.push((Occur::Must, term(key, value))),
+                    FilterOp::MetaIn { key, values } if !values.is_empty() => {
+                        let any: Vec<(Occur, Box<dyn tantivy::query::Query>)> = values.iter().map(|v| (Occur::Should, term(key, v))).collect();
+                        out.push((Occur::Must, Box::new(BooleanQuery::from(any))));
+                    }
+                    _ => {}
+                }
+            }
+            out
+        }
+
+        /// `SourceUriPrefix` clauses as regex queries on the untokenized `source_uri` field. With
+        /// `case_insensitive` each ASCII letter matches either case, as in the other backends.
+        fn source_prefix_queries(&self, filters: &[FilterClause]) -> Vec<(Occur, Box<dyn tantivy::query::Query>)> {
+            filters
+                .iter()
+                .filter_map(|fc| match &fc.op {
+                    FilterOp::SourceUriPrefix { prefix, case_insensitive } => {
+                        let pattern = format!("{}.*", prefix_regex(prefix, *case_insensitive));
+                        let q = RegexQuery::from_pattern(&pattern, self.f_source_uri).ok()?;
+                        Some((Occur::Must, Box::new(q) as Box<dyn tantivy::query::Query>))
+                    }
+                    _ => None,
+                })
+                .collect()
         }
 
         /// Build a query by tokenizing the input with the field analyzer and
-        /// combining terms via AND/OR (avoids overly strict phrase matching).
+        /// combining terms via AND/OR, or as a (sloppy) phrase with `TokenCombine::Phrase`.
         pub fn search_ids_tokenized(
             &self,
             _store: &dyn ChunkStoreRead,
@@ -183,22 +380,36 @@ mod real {
             if query.trim().is_empty() || opts.top_k == 0 { return Vec::new(); }
 
             // 1) Tokenize the query using the field analyzer for `text`.
-            let mut toks: Vec<String> = Vec::new();
+            //    Positions come from the same analyzer used at index time, so phrase
+            //    offsets line up with the indexed token positions.
+            let mut toks: Vec<(usize, String)> = Vec::new();
             if let Ok(mut analyzer) = self.index.tokenizer_for_field(self.f_text) {
                 let mut ts = analyzer.token_stream(query);
                 while ts.advance() {
                     let t = ts.token();
-                    if !t.text.is_empty() { toks.push(t.text.clone()); }
+                    if !t.text.is_empty() { toks.push((t.position, t.text.clone())); }
                 }
             }
             if toks.is_empty() { return Vec::new(); }
 
-            // 2) Build boolean of terms.
+            // 2) Build boolean of terms, or a single phrase query.
             let mut clauses: Vec<(Occur, Box<dyn tantivy::query::Query>)> = Vec::new();
-            for tk in &toks {
-                let term = Term::from_field_text(self.f_text, tk);
-                let tq = TermQuery::new(term, IndexRecordOption::Basic);
-                clauses.push((match combine { TokenCombine::AND => Occur::Must, TokenCombine::OR => Occur::Should }, Box::new(tq)));
+            match combine {
+                TokenCombine::Phrase { slop } if toks.len() > 1 => {
+                    let base = toks[0].0;
+                    let terms: Vec<(usize, Term)> = toks
+                        .iter()
+                        .map(|(pos, tk)| (pos - base, Term::from_field_text(self.f_text, tk)))
+                        .collect();
+                    clauses.push((Occur::Must, Box::new(PhraseQuery::new_with_offset_and_slop(terms, slop))));
+                }
+                _ => {
+                    for (_, tk) in &toks {
+                        let term = Term::from_field_text(self.f_text, tk);
+                        let tq = TermQuery::new(term, IndexRecordOption::Basic);
+                        clauses.push((match combine { TokenCombine::OR => Occur::Should, _ => Occur::Must }, Box::new(tq)));
+                    }
+                }
             }
 
             // 3) Append filters (same as default implementation)
@@ -222,15 +433,18 @@ mod real {
             if !doc_terms.is_empty() {
                 clauses.push((Occur::Must, Box::new(BooleanQuery::from(doc_terms))));
             }
-
-            // source_uri prefix
+            // doc_id exclusions
             for fc in filters {
-                if let FilterOp::SourceUriPrefix(p) = &fc.op {
-                    let uri_parser = QueryParser::for_index(&self.index, vec![self.f_source_uri]);
-                    let qstr = format!("{}*", escape_term(p));
-                    if let Ok(q) = uri_parser.parse_query(&qstr) { clauses.push((Occur::Must, q)); }
+                if let FilterOp::DocIdNotIn(vs) = &fc.op {
+                    for v in vs {
+                        let term = Term::from_field_text(self.f_doc_id, v);
+                        clauses.push((Occur::MustNot, Box::new(TermQuery::new(term, IndexRecordOption::Basic))));
+                    }
                 }
             }
+
+            // source_uri prefix
+            clauses.extend(self.source_prefix_queries(filters));
             // extracted_at range
             for fc in filters {
                 if let FilterOp::RangeIsoDate { key, start, end, start_incl, end_incl } = &fc.op {
@@ -248,11 +462,17 @@ mod real {
                     }
                 }
             }
+
+            // meta equality prefilter
+            clauses.extend(self.meta_queries(filters));
+
+            // language prefilter
+            if let Some(q) = self.lang_query(opts) { clauses.push((Occur::Must, q)); }
 
             // 4) Execute
             let combined = BooleanQuery::from(clauses);
             let searcher = self.reader.searcher();
-            let fetch_n = (opts.top_k.saturating_mul(opts.fetch_factor)).max(opts.top_k);
+            let fetch_n = opts.fetch_n();
             let top_docs = match searcher.search(&combined, &TopDocs::with_limit(fetch_n)) { Ok(hits) => hits, Err(_) => return Vec::new() };
             let mut out = Vec::with_capacity(top_docs.len());
             for (raw_score, addr) in top_docs {
@@ -271,14 +491,19 @@ mod real {
 
     impl TextSearcher for TantivyIndex {
         fn name(&self) -> &'static str { "tantivy" }
-        fn caps(&self) -> IndexCaps { IndexCaps { can_prefilter_doc_id_eq: true, can_prefilter_doc_id_in: true, can_prefilter_source_prefix: true, can_prefilter_meta: false, can_prefilter_range_numeric: false, can_prefilter_range_date: true } }
+        fn caps(&self) -> IndexCaps {
+            IndexCaps { can_prefilter_meta_eq: self.f_meta.is_some(), ..super::TANTIVY_CAPS }
+        }
         fn search_ids(&self, _store: &dyn ChunkStoreRead, query: &str, filters: &[FilterClause], opts: &SearchOptions) -> Vec<TextMatch> {
             if query.trim().is_empty() || opts.top_k == 0 { return Vec::new(); }
 
             let mut clauses: Vec<(Occur, Box<dyn tantivy::query::Query>)> = Vec::new();
 
-            // Text part on text field only
-            let text_parser = QueryParser::for_index(&self.index, vec![self.f_text]);
+            // Text part over body text and headings; heading matches are boosted
+            let mut fields = vec![self.f_text];
+            fields.extend(self.f_heading);
+            let mut text_parser = QueryParser::for_index(&self.index, fields);
+            if let Some(f) = self.f_heading { text_parser.set_field_boost(f, self.heading_boost); }
             let text_q = match text_parser.parse_query(query) { Ok(q) => q, Err(_) => return Vec::new() };
             clauses.push((Occur::Must, text_q));
 
@@ -302,18 +527,19 @@ mod real {
             if !doc_terms.is_empty() {
                 clauses.push((Occur::Must, Box::new(BooleanQuery::from(doc_terms))));
             }
-
-            // source_uri prefix via QueryParser on source_uri field with wildcard
+            // doc_id exclusions
             for fc in filters {
-                if let FilterOp::SourceUriPrefix(p) = &fc.op {
-                    let uri_parser = QueryParser::for_index(&self.index, vec![self.f_source_uri]);
-                    let qstr = format!("{}*", escape_term(p));
-                    if let Ok(q) = uri_parser.parse_query(&qstr) {
-                        clauses.push((Occur::Must, q));
+                if let FilterOp::DocIdNotIn(vs) = &fc.op {
+                    for v in vs {
+                        let term = Term::from_field_text(self.f_doc_id, v);
+                        clauses.push((Occur::MustNot, Box::new(TermQuery::new(term, IndexRecordOption::Basic))));
                     }
                 }
             }
 
+            // source_uri prefix as a regex over the raw source_uri term
+            clauses.extend(self.source_prefix_queries(filters));
+
             // extracted_at range using numeric epoch fast field
             for fc in filters {
                 if let FilterOp::RangeIsoDate { key, start, end, start_incl, end_incl } = &fc.op {
@@ -339,9 +565,15 @@ mod real {
                 }
             }
 
+            // meta equality prefilter
+            clauses.extend(self.meta_queries(filters));
+
+            // language prefilter
+            if let Some(q) = self.lang_query(opts) { clauses.push((Occur::Must, q)); }
+
             let combined = BooleanQuery::from(clauses);
             let searcher = self.reader.searcher();
-            let fetch_n = (opts.top_k.saturating_mul(opts.fetch_factor)).max(opts.top_k);
+            let fetch_n = opts.fetch_n();
             let top_docs = match searcher.search(&combined, &TopDocs::with_limit(fetch_n)) { Ok(hits) => hits, Err(_) => return Vec::new() };
             let mut out = Vec::with_capacity(top_docs.len());
             for (raw_score, addr) in top_docs {
@@ -356,6 +588,36 @@ mod real {
             }
             out
         }
+
+        fn search_ids_with_snippets(&self, store: &dyn ChunkStoreRead, query: &str, filters: &[FilterClause], opts: &SearchOptions) -> Vec<(TextMatch, Option<TextSnippet>)> {
+            let matches = self.search_ids(store, query, filters, opts);
+            let mut snippets = self.snippets_for(store, query, &matches);
+            matches.into_iter().map(|m| { let s = snippets.remove(&m.chunk_id.0); (m, s) }).collect()
+        }
+    }
+
+    /// Characters per snippet window produced by `search_ids_with_snippets`.
+    const SNIPPET_MAX_CHARS: usize = 150;
+
+    impl TantivyIndex {
+        /// Best window of each hit's text via Tantivy's `SnippetGenerator`. The text field is not
+        /// stored in the index, so chunk text is read from `store`.
+        fn snippets_for(&self, store: &dyn ChunkStoreRead, query: &str, matches: &[TextMatch]) -> std::collections::HashMap<String, TextSnippet> {
+            let mut out = std::collections::HashMap::new();
+            let text_parser = QueryParser::for_index(&self.index, vec![self.f_text]);
+            let Ok(text_q) = text_parser.parse_query(query) else { return out };
+            let searcher = self.reader.searcher();
+            let Ok(mut generator) = SnippetGenerator::create(&searcher, &*text_q, self.f_text) else { return out };
+            generator.set_max_num_chars(SNIPPET_MAX_CHARS);
+            let ids: Vec<chunk_model::ChunkId> = matches.iter().map(|m| m.chunk_id.clone()).collect();
+            for rec in store.get_chunks_by_ids(&ids).unwrap_or_default() {
+                let snippet = generator.snippet(&rec.text);
+                if snippet.is_empty() { continue; }
+                let highlights = snippet.highlighted().iter().map(|r| (r.start as u32, r.end as u32)).collect();
+                out.insert(rec.chunk_id.0, TextSnippet { text: snippet.fragment().to_string(), highlights });
+            }
+            out
+        }
     }
 
     impl crate::TextIndexMaintainer for TantivyIndex {
@@ -383,7 +645,25 @@ mod real {
     }
 
     fn escape_q(s: &str) -> String { s.replace('"', "\\\"") }
-    fn escape_term(s: &str) -> String { s.replace(' ', "\\ ") }
+    /// `prefix` as a literal regex; with `case_insensitive` ASCII letters become `[xX]` classes.
+    fn prefix_regex(prefix: &str, case_insensitive: bool) -> String {
+        let mut out = String::with_capacity(prefix.len() * 2);
+        for c in prefix.chars() {
+            if case_insensitive && c.is_ascii_alphabetic() {
+                out.push('[');
+                out.push(c.to_ascii_lowercase());
+                out.push(c.to_ascii_uppercase());
+                out.push(']');
+            } else {
+                if "\\.+*?()|[]{}^$#&-~".contains(c) { out.push('\\'); }
+                out.push(c);
+            }
+        }
+        out
+    }
+    /// Indexed form of one meta entry; the unit separator cannot occur in keys written by the
+    /// chunkers, so `key`/`value` pairs never collide.
+    fn meta_term(key: &str, value: &str) -> String { format!("{key}\u{1f}{value}") }
     fn parse_rfc3339_to_ts(s: &str) -> Option<i64> { if s.is_empty() { None } else { DateTime::parse_from_rfc3339(s).ok().map(|dt| dt.timestamp()) } }
 }
 
@@ -0,0 +1,21 @@
+use chunking_store::embed_cache::{CachedEmbedding, EmbedCache};
+
+#[test]
+fn embed_cache_round_trips_and_misses_for_another_model() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("embed_cache.db");
+    let entry = CachedEmbedding { vector: vec![0.25, -1.5, 3.0], tokens: 7 };
+    {
+        let cache = EmbedCache::open(&path).unwrap();
+        assert!(cache.is_empty().unwrap());
+        cache.put_many("model-a", &[(EmbedCache::key("model-a", "hello"), entry.clone())]).unwrap();
+    }
+
+    // Reopen: entries persist and are found only under the model that produced them.
+    let cache = EmbedCache::open(&path).unwrap();
+    assert_eq!(cache.len().unwrap(), 1);
+    let keys = vec![EmbedCache::key("model-a", "hello"), EmbedCache::key("model-b", "hello"), EmbedCache::key("model-a", "other")];
+    let hits = cache.get_many(&keys).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits.get(&keys[0]), Some(&entry));
+}
@@ -0,0 +1,36 @@
+use chunking_store::tantivy_index::TANTIVY_CAPS;
+use chunking_store::{plan_filter_pushdown, FilterClause, FilterKind, FilterOp, FilterPlacement, IndexCaps};
+
+#[test]
+fn pushdown_follows_backend_caps_and_filter_kind() {
+    let filters = vec![
+        FilterClause { kind: FilterKind::Must, op: FilterOp::DocIdEq("doc-1".into()) },
+        FilterClause { kind: FilterKind::Must, op: FilterOp::MetaEq { key: "lang".into(), value: "ja".into(), case_insensitive: false } },
+        FilterClause { kind: FilterKind::PostOnly, op: FilterOp::SourceUriPrefix { prefix: "file://".into(), case_insensitive: false } },
+    ];
+    let placements = |caps: &IndexCaps| -> Vec<FilterPlacement> {
+        plan_filter_pushdown("test", caps, &filters).into_iter().map(|p| p.placement).collect()
+    };
+    // Tantivy indexes doc ids and exact meta values; PostOnly is never pushed down.
+    assert_eq!(
+        placements(&TANTIVY_CAPS),
+        vec![FilterPlacement::Prefilter, FilterPlacement::Prefilter, FilterPlacement::PostFilter]
+    );
+    assert!(placements(&IndexCaps::NONE).iter().all(|p| *p == FilterPlacement::PostFilter));
+
+    let plans = plan_filter_pushdown("tantivy", &TANTIVY_CAPS, &filters);
+    assert!(plans.iter().all(|p| p.backend == "tantivy"));
+    assert_eq!(plans[1].clause, filters[1]);
+}
+
+#[test]
+fn source_uri_prefix_reads_the_legacy_string_form() {
+    let legacy: FilterOp = serde_json::from_str(r#"{"SourceUriPrefix":"file:///docs/"}"#).expect("legacy form");
+    assert_eq!(legacy, FilterOp::SourceUriPrefix { prefix: "file:///docs/".into(), case_insensitive: false });
+
+    let current = FilterOp::SourceUriPrefix { prefix: "file:///Docs/".into(), case_insensitive: true };
+    let json = serde_json::to_string(&current).expect("serialize");
+    assert_eq!(serde_json::from_str::<FilterOp>(&json).expect("round trip"), current);
+    let defaulted: FilterOp = serde_json::from_str(r#"{"SourceUriPrefix":{"prefix":"a"}}"#).expect("flag defaults");
+    assert_eq!(defaulted, FilterOp::SourceUriPrefix { prefix: "a".into(), case_insensitive: false });
+}
@@ -0,0 +1,269 @@
+use std::time::Instant;
+
+use chunk_model::ChunkId;
+use chunking_store::hnsw_index::{HnswIndex, HnswMetric, HnswParams, VectorDtype};
+use chunking_store::sqlite_repo::SqliteRepo;
+use chunking_store::{SearchOptions, VectorSearcher};
+
+/// Deterministic pseudo-random vectors (xorshift), no extra dev-dependency.
+fn synthetic(n: usize, dim: usize) -> Vec<(ChunkId, Vec<f32>)> {
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+    };
+    (0..n).map(|i| (ChunkId(format!("v{i}")), (0..dim).map(|_| next()).collect())).collect()
+}
+
+#[test]
+fn bulk_build_matches_incremental_results() {
+    let items = synthetic(1200, 16);
+    let repo = SqliteRepo::new();
+
+    let mut incremental = HnswIndex::new(16, items.len());
+    incremental.set_bulk_build_min(0);
+    let t = Instant::now();
+    incremental.upsert(&items).expect("upsert vectors");
+    let incremental_time = t.elapsed();
+
+    let mut bulk = HnswIndex::new(16, items.len());
+    bulk.set_bulk_build_min(1000);
+    let t = Instant::now();
+    bulk.upsert(&items).expect("upsert vectors");
+    let bulk_time = t.elapsed();
+
+    let opts = SearchOptions { top_k: 1, ..Default::default() };
+    for (cid, v) in items.iter().step_by(53) {
+        let a = incremental.knn_ids(&repo, v, &[], &opts).expect("knn search");
+        let b = bulk.knn_ids(&repo, v, &[], &opts).expect("knn search");
+        assert_eq!(a[0].chunk_id, *cid);
+        assert_eq!(b[0].chunk_id, *cid);
+    }
+
+    // Snapshots are identical: both reload to the same neighbours
+    let dir = tempfile::tempdir().expect("create temp dir");
+    bulk.save(dir.path()).expect("save bulk-built index");
+    let reloaded = HnswIndex::load(dir.path(), 16).expect("reload");
+    assert_eq!(reloaded.knn_ids(&repo, &items[5].1, &[], &opts).expect("knn search")[0].chunk_id, items[5].0);
+
+    // Parallel insertion only pays off with more than one core
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    if cores > 1 {
+        assert!(bulk_time < incremental_time, "bulk {bulk_time:?} vs incremental {incremental_time:?}");
+    }
+}
+
+#[test]
+fn f16_snapshot_reloads_with_matching_results_at_half_the_size() {
+    let items = synthetic(300, 32);
+    let repo = SqliteRepo::new();
+    let build = |dtype: VectorDtype| {
+        let mut h = HnswIndex::new(32, items.len());
+        h.set_dtype(dtype);
+        h.upsert(&items).expect("upsert vectors");
+        h
+    };
+    let full = build(VectorDtype::F32);
+    let half = build(VectorDtype::F16);
+    assert_eq!(half.vector_bytes() * 2, full.vector_bytes());
+
+    let full_dir = tempfile::tempdir().expect("create temp dir");
+    let half_dir = tempfile::tempdir().expect("create temp dir");
+    full.save(full_dir.path()).expect("save f32");
+    half.save(half_dir.path()).expect("save f16");
+    let size = |dir: &std::path::Path| std::fs::read_dir(dir).expect("list snapshot").map(|e| e.expect("entry").metadata().expect("metadata").len()).sum::<u64>();
+    assert!(size(half_dir.path()) < size(full_dir.path()));
+
+    let reloaded = HnswIndex::load(half_dir.path(), 32).expect("reload f16");
+    assert_eq!(reloaded.dtype(), VectorDtype::F16);
+    let opts = SearchOptions { top_k: 3, ..Default::default() };
+    for (_, v) in items.iter().step_by(29) {
+        let a = full.knn_ids(&repo, v, &[], &opts).expect("knn search");
+        let b = reloaded.knn_ids(&repo, v, &[], &opts).expect("knn search");
+        assert_eq!(a[0].chunk_id, b[0].chunk_id);
+        for (x, y) in a.iter().zip(&b) {
+            assert!((x.score - y.score).abs() < 1e-2, "{} vs {}", x.score, y.score);
+        }
+    }
+}
+
+#[test]
+fn raw_score_carries_the_native_metric_value() {
+    let a = vec![1.0f32, 2.0, 2.0];
+    let b = vec![2.0f32, 1.0, 2.0];
+    let dot: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let cosine = dot / (norm(&a) * norm(&b));
+    let l2 = a.iter().zip(&b).map(|(x, y)| (x - y) * (x - y)).sum::<f32>().sqrt();
+    let repo = SqliteRepo::new();
+    let opts = SearchOptions { top_k: 1, ..Default::default() };
+    let nearest = |metric: HnswMetric| {
+        let mut h = HnswIndex::with_metric(3, 10, metric);
+        h.upsert(&[(ChunkId("b".into()), b.clone())]).expect("upsert vectors");
+        h.knn_ids(&repo, &a, &[], &opts).expect("knn search").remove(0)
+    };
+
+    let cos = nearest(HnswMetric::Cosine);
+    assert!((cos.raw_score - cosine).abs() < 1e-5, "cosine similarity {} vs {cosine}", cos.raw_score);
+    assert!((cos.score - cosine).abs() < 1e-5);
+
+    let euclid = nearest(HnswMetric::L2);
+    assert!((euclid.raw_score - l2).abs() < 1e-5, "l2 distance {} vs {l2}", euclid.raw_score);
+    assert!(euclid.score > 0.0 && euclid.score < 1.0);
+
+    // The metric survives a snapshot round trip
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let mut h = HnswIndex::with_metric(3, 10, HnswMetric::L2);
+    h.upsert(&[(ChunkId("b".into()), b.clone())]).expect("upsert vectors");
+    h.save(dir.path()).expect("save");
+    assert_eq!(HnswIndex::load(dir.path(), 3).expect("reload").metric(), HnswMetric::L2);
+}
+
+#[test]
+fn normalized_index_rejects_unnormalized_queries() {
+    let repo = SqliteRepo::new();
+    let opts = SearchOptions { top_k: 1, ..Default::default() };
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let mut h = HnswIndex::with_metric(2, 10, HnswMetric::Dot);
+    h.set_normalized(true);
+    chunking_store::VectorIndexMaintainer::upsert_vectors(&mut h, &[(ChunkId("a".into()), vec![0.6, 0.8])])
+        .expect("unit vectors are accepted");
+    assert!(chunking_store::VectorIndexMaintainer::upsert_vectors(&mut h, &[(ChunkId("b".into()), vec![3.0, 4.0])]).is_err());
+    // The inherent upsert checks too, and rejects the whole batch
+    assert!(h.upsert(&[(ChunkId("c".into()), vec![1.0, 0.0]), (ChunkId("d".into()), vec![3.0, 4.0])]).is_err());
+    h.save(dir.path()).expect("save");
+
+    let h = HnswIndex::load(dir.path(), 2).expect("reload");
+    assert!(h.normalized());
+    let hits = h.knn_ids(&repo, &[1.0, 0.0], &[], &opts).expect("unit query is accepted");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].chunk_id.0, "a");
+    assert!(h.knn_ids(&repo, &[2.0, 0.0], &[], &opts).is_err());
+}
+
+#[test]
+fn load_refuses_a_snapshot_built_for_another_dimension() {
+    use chunking_store::hnsw_index::HnswError;
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let mut h = HnswIndex::new(8, 16);
+    h.set_model_fingerprint(Some("model-768".into()));
+    h.upsert(&synthetic(4, 8)).expect("upsert vectors");
+    h.save(dir.path()).expect("save");
+
+    let reloaded = HnswIndex::load(dir.path(), 8).expect("reload at the same dimension");
+    assert_eq!(reloaded.model_fingerprint(), Some("model-768"));
+    match HnswIndex::load(dir.path(), 12) {
+        Err(HnswError::DimensionMismatch { expected: 12, found: 8 }) => {}
+        other => panic!("expected a dimension mismatch, got {:?}", other.map(|_| ())),
+    }
+
+    // Snapshots written before dim.txt existed are checked against their vectors
+    std::fs::remove_file(dir.path().join("dim.txt")).expect("drop dim sidecar");
+    assert!(matches!(HnswIndex::load(dir.path(), 12), Err(HnswError::DimensionMismatch { expected: 12, found: 8 })));
+    assert!(HnswIndex::load(dir.path(), 8).is_ok());
+}
+
+#[test]
+fn min_score_drops_weak_neighbors_without_padding() {
+    let repo = SqliteRepo::new();
+    let mut h = HnswIndex::new(2, 10);
+    h.upsert(&[
+        (ChunkId("same".into()), vec![1.0, 0.0]),
+        (ChunkId("near".into()), vec![0.8, 0.6]),
+        (ChunkId("orthogonal".into()), vec![0.0, 1.0]),
+        (ChunkId("opposite".into()), vec![-1.0, 0.0]),
+    ]).expect("upsert vectors");
+    let ids = |min_score: Option<f32>| -> Vec<String> {
+        let opts = SearchOptions { top_k: 4, min_score, ..Default::default() };
+        h.knn_ids(&repo, &[1.0, 0.0], &[], &opts).expect("knn search").into_iter().map(|m| m.chunk_id.0).collect()
+    };
+    assert_eq!(ids(None).len(), 4);
+    assert_eq!(ids(Some(0.5)), vec!["same", "near"]);
+    assert!(ids(Some(1.5)).is_empty());
+}
+
+#[test]
+fn build_params_survive_save_load_and_compact() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let params = HnswParams { m: 8, ef_construction: 64, max_elements: 500 };
+    let mut h = HnswIndex::new(2, params);
+    h.upsert(&[(ChunkId("a".into()), vec![1.0, 0.0]), (ChunkId("b".into()), vec![0.0, 1.0])]).expect("upsert vectors");
+    h.save(dir.path()).expect("save");
+
+    let mut reloaded = HnswIndex::load(dir.path(), 2).expect("load");
+    assert_eq!(reloaded.params(), params);
+    chunking_store::VectorIndexMaintainer::delete_by_ids(&mut reloaded, &[ChunkId("a".into())]).expect("delete");
+    reloaded.compact();
+    assert_eq!(reloaded.params(), params);
+
+    // Snapshots without the sidecar fall back to the defaults
+    std::fs::remove_file(dir.path().join("params.txt")).expect("drop params sidecar");
+    let legacy = HnswIndex::load(dir.path(), 2).expect("load legacy");
+    assert_eq!((legacy.params().m, legacy.params().ef_construction), (16, 200));
+    assert_eq!(HnswIndex::new(2, 10_000).params(), HnswParams::default());
+}
+
+#[test]
+fn upsert_past_capacity_grows_the_graph_and_keeps_every_vector() {
+    let repo = SqliteRepo::new();
+    let mut h = HnswIndex::new(2, HnswParams { max_elements: 4, ..Default::default() });
+    h.set_bulk_build_min(0);
+    let items: Vec<(ChunkId, Vec<f32>)> = (0..20)
+        .map(|i| {
+            let a = i as f32 * 0.3;
+            (ChunkId(format!("c{i}")), vec![a.cos(), a.sin()])
+        })
+        .collect();
+    for pair in items.chunks(3) { h.upsert(pair).expect("upsert vectors"); }
+    assert!(h.params().max_elements >= 20, "capacity {}", h.params().max_elements);
+    assert_eq!(h.params().m, 16);
+
+    let opts = SearchOptions { top_k: 20, ..Default::default() };
+    for (cid, v) in &items {
+        let hits = h.knn_ids(&repo, v, &[], &opts).expect("knn search");
+        assert_eq!(hits.len(), 20);
+        assert_eq!(hits[0].chunk_id.0, cid.0);
+    }
+}
+
+#[test]
+fn load_falls_back_to_the_previous_snapshot_when_the_live_one_is_partial() {
+    let tmp = tempfile::tempdir().expect("create temp dir");
+    let dir = tmp.path().join("hnsw");
+    let items = synthetic(20, 8);
+    let mut idx = HnswIndex::new(8, 100);
+    idx.upsert(&items[..10]).expect("upsert vectors");
+    idx.save(&dir).expect("first save");
+    idx.upsert(&items[10..]).expect("upsert vectors");
+    idx.save(&dir).expect("second save");
+    let loaded = HnswIndex::load(&dir, 8).expect("load");
+    assert_eq!(loaded.live_ids().count(), 20);
+    assert!(!loaded.loaded_from_previous());
+
+    // A save killed midway used to leave the vector file truncated
+    let vectors = dir.join("vectors.bin");
+    let len = std::fs::metadata(&vectors).expect("vectors").len();
+    std::fs::OpenOptions::new().write(true).open(&vectors).expect("open").set_len(len / 2).expect("truncate");
+    let fallback = HnswIndex::load(&dir, 8).expect("fallback load");
+    assert_eq!(fallback.live_ids().count(), 10);
+    assert!(fallback.loaded_from_previous());
+
+    // Killed between moving the live dir aside and renaming the new one in
+    std::fs::remove_dir_all(&dir).expect("remove live dir");
+    assert!(HnswIndex::snapshot_exists(&dir));
+    assert_eq!(HnswIndex::load(&dir, 8).expect("fallback load").live_ids().count(), 10);
+}
+
+#[test]
+fn vector_returns_live_stored_vectors_only() {
+    let items = synthetic(3, 8);
+    let mut idx = HnswIndex::new(8, 100);
+    idx.upsert(&items).expect("upsert vectors");
+    let (id, v) = &items[1];
+    assert_eq!(idx.vector(&id.0).as_deref(), Some(v.as_slice()));
+    chunking_store::VectorIndexMaintainer::delete_by_ids(&mut idx, std::slice::from_ref(id)).expect("delete");
+    assert!(idx.vector(&id.0).is_none());
+    assert!(idx.vector("unknown").is_none());
+}
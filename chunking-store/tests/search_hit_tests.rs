@@ -0,0 +1,87 @@
+use chunk_model::{ChunkId, ChunkRecord, DocumentId, SCHEMA_MAJOR};
+use chunking_store::{cap_hits_per_doc, group_hits_by_doc, snippet_around, to_percentages, SearchHit};
+
+fn hit(score: f32) -> SearchHit {
+    let chunk = ChunkRecord {
+        schema_version: SCHEMA_MAJOR,
+        doc_id: DocumentId("doc".into()),
+        chunk_id: ChunkId("doc#0".into()),
+        seq: None,
+        source_uri: "doc.txt".into(),
+        source_mime: "text/plain".into(),
+        extracted_at: String::new(),
+        page_start: None,
+        page_end: None,
+        text: "text".into(),
+        section_path: None,
+        meta: Default::default(),
+        block_kinds: Vec::new(),
+        extra: Default::default(),
+    };
+    SearchHit { chunk, score, fallback: false, components: None }
+}
+
+#[test]
+fn confidence_is_bounded_and_monotonic_in_score() {
+    let scores = [-2.0f32, -0.1, 0.0, 0.05, 0.3, 0.5, 0.99, 1.0, 1.7, 40.0];
+    let conf: Vec<f32> = scores.iter().map(|s| hit(*s).confidence()).collect();
+    assert!(conf.iter().all(|c| (0.0..=1.0).contains(c)), "out of range: {conf:?}");
+    assert!(conf.windows(2).all(|w| w[0] <= w[1]), "not monotonic: {conf:?}");
+    assert_eq!(hit(0.42).confidence(), 0.42);
+    assert_eq!(hit(f32::NAN).confidence(), 0.0);
+    assert_eq!(SearchHit { fallback: true, ..hit(0.9) }.confidence(), 0.0);
+}
+
+#[test]
+fn percentages_share_the_positive_weights() {
+    assert_eq!(to_percentages(&[1.0, 3.0]), vec![25.0, 75.0]);
+    assert_eq!(to_percentages(&[2.0, -1.0, 0.0, 2.0]), vec![50.0, 0.0, 0.0, 50.0]);
+    assert_eq!(to_percentages(&[0.0, 0.0]), vec![0.0, 0.0]);
+}
+
+fn paged_hit(doc: &str, page: u32, text: &str, score: f32) -> SearchHit {
+    let mut h = hit(score);
+    h.chunk.doc_id = DocumentId(doc.into());
+    h.chunk.chunk_id = ChunkId(format!("{doc}#{page}"));
+    h.chunk.source_uri = format!("{doc}.pdf");
+    h.chunk.page_start = Some(page);
+    h.chunk.page_end = Some(page);
+    h.chunk.text = text.into();
+    h
+}
+
+#[test]
+fn grouped_hits_report_the_best_chunks_page_and_snippet() {
+    let filler = "Background material. ".repeat(10);
+    let hits = vec![
+        paged_hit("report", 3, "Revenue overview.", 0.4),
+        paged_hit("notes", 1, "Revenue notes.", 0.6),
+        paged_hit("report", 42, &format!("{filler}Quarterly revenue grew 12 percent. {filler}"), 0.9),
+    ];
+    let groups = group_hits_by_doc(hits, "revenue grew", 40);
+    assert_eq!(groups.iter().map(|g| g.doc_id.0.as_str()).collect::<Vec<_>>(), vec!["report", "notes"]);
+    let report = &groups[0];
+    assert_eq!(report.best_page, report.hits[0].chunk.page_start);
+    assert_eq!(report.best_page, Some(42));
+    assert_eq!(report.hits.len(), 2);
+    assert!(report.snippet.contains("revenue grew"), "snippet: {}", report.snippet);
+    assert!(report.snippet.starts_with('…') && report.snippet.ends_with('…'));
+    assert_eq!(snippet_around("short text", "missing", 40), "short text");
+}
+
+#[test]
+fn per_doc_cap_keeps_each_documents_best_hits_in_rank_order() {
+    let in_doc = |doc: &str, seq: u32, score: f32| {
+        let mut h = hit(score);
+        h.chunk.doc_id = DocumentId(doc.into());
+        h.chunk.chunk_id = ChunkId(format!("{doc}#{seq}"));
+        h
+    };
+    let mut hits = vec![in_doc("a", 0, 0.9), in_doc("a", 1, 0.8), in_doc("a", 2, 0.7), in_doc("b", 0, 0.6), in_doc("a", 3, 0.5), in_doc("b", 1, 0.4)];
+    cap_hits_per_doc(&mut hits, 2);
+    let ids: Vec<&str> = hits.iter().map(|h| h.chunk.chunk_id.0.as_str()).collect();
+    assert_eq!(ids, ["a#0", "a#1", "b#0", "b#1"]);
+
+    cap_hits_per_doc(&mut hits, 0);
+    assert!(hits.is_empty());
+}
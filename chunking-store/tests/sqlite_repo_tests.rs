@@ -0,0 +1,745 @@
+use chunk_model::{BlockKind, ChunkId, ChunkRecord, DocumentId, FileRecord, SCHEMA_MAJOR};
+use chunking_store::hnsw_index::HnswIndex;
+use chunking_store::sqlite_repo::SqliteRepo;
+use chunking_store::{ChunkPrimaryStore, ChunkStoreRead, FilterClause, FilterExpr, FilterKind, FilterOp, SearchOptions, VectorSearcher};
+
+fn chunk(id: &str, text: &str) -> ChunkRecord {
+    doc_chunk("doc-1", id, text)
+}
+
+fn doc_chunk(doc: &str, id: &str, text: &str) -> ChunkRecord {
+    ChunkRecord {
+        schema_version: SCHEMA_MAJOR,
+        doc_id: DocumentId(doc.into()),
+        chunk_id: ChunkId(id.into()),
+        seq: None,
+        source_uri: format!("file://{doc}.txt"),
+        source_mime: "text/plain".into(),
+        extracted_at: String::new(),
+        page_start: None,
+        page_end: None,
+        text: text.into(),
+        section_path: None,
+        meta: Default::default(),
+        block_kinds: Vec::new(),
+        extra: Default::default(),
+    }
+}
+
+fn file(doc: &str) -> FileRecord {
+    FileRecord {
+        schema_version: SCHEMA_MAJOR,
+        doc_id: DocumentId(doc.into()),
+        doc_revision: None,
+        source_uri: format!("file://{doc}.txt"),
+        source_mime: "text/plain".into(),
+        file_size_bytes: None,
+        content_sha256: None,
+        page_count: None,
+        extracted_at: String::new(),
+        created_at_meta: None,
+        updated_at_meta: None,
+        title_guess: None,
+        author_guess: None,
+        dominant_lang: None,
+        tags: Vec::new(),
+        ingest_tool: None,
+        ingest_tool_version: None,
+        reader_backend: None,
+        ocr_used: None,
+        ocr_langs: Vec::new(),
+        chunk_count: None,
+        total_tokens: None,
+        meta: Default::default(),
+        extra: Default::default(),
+    }
+}
+
+#[test]
+fn verify_integrity_flags_only_the_tampered_chunk() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let db = dir.path().join("chunks.db");
+    let mut repo = SqliteRepo::open(&db).expect("open repo");
+    repo.upsert_chunks(vec![chunk("c1", "alpha text"), chunk("c2", "beta text"), chunk("c3", "gamma text")])
+        .expect("upsert chunks");
+    let clean = repo.verify_integrity().expect("verify clean store");
+    assert_eq!((clean.checked, clean.unchecked), (3, 0));
+    assert!(clean.corrupt.is_empty());
+
+    // Simulate on-disk corruption by rewriting the text behind the repo's back.
+    let raw = rusqlite::Connection::open(&db).expect("open raw connection");
+    raw.execute("UPDATE chunks SET text = 'beta texT' WHERE chunk_id = 'c2'", []).expect("tamper text");
+    drop(raw);
+
+    let report = repo.verify_integrity().expect("verify tampered store");
+    assert_eq!(report.checked, 3);
+    assert_eq!(report.corrupt, vec![ChunkId("c2".into())]);
+}
+
+#[test]
+fn negation_filters_agree_between_sql_prefilter_and_vector_post_filter() {
+    let mut repo = SqliteRepo::new();
+    let mut en = doc_chunk("doc-en", "en", "english text");
+    en.meta.insert("lang".into(), "en".into());
+    let mut ja = doc_chunk("doc-ja", "ja", "japanese text");
+    ja.meta.insert("lang".into(), "ja".into());
+    let untagged = doc_chunk("doc-none", "none", "untagged text");
+    repo.upsert_chunks(vec![en, ja, untagged]).expect("upsert chunks");
+
+    let mut hnsw = HnswIndex::new(2, 16);
+    hnsw.upsert(&[
+        (ChunkId("en".into()), vec![1.0, 0.0]),
+        (ChunkId("ja".into()), vec![0.9, 0.1]),
+        (ChunkId("none".into()), vec![0.8, 0.2]),
+    ]).expect("upsert vectors");
+
+    let must = |op: FilterOp| vec![FilterClause { kind: FilterKind::Must, op }];
+    let cases = [
+        (must(FilterOp::MetaNe { key: "lang".into(), value: "en".into() }), vec!["ja", "none"]),
+        (must(FilterOp::DocIdNotIn(vec!["doc-ja".into(), "doc-none".into()])), vec!["en"]),
+        (must(FilterOp::DocIdNotIn(Vec::new())), vec!["en", "ja", "none"]),
+    ];
+    let opts = SearchOptions { top_k: 3, ..Default::default() };
+    for (filters, expected) in cases {
+        let mut sql: Vec<String> = repo
+            .list_chunk_ids_by_filter(&filters, 10, 0)
+            .expect("list ids")
+            .into_iter()
+            .map(|c| c.0)
+            .collect();
+        sql.sort();
+        assert_eq!(sql, expected, "sql prefilter for {filters:?}");
+
+        let mut knn: Vec<String> = hnsw.knn_ids(&repo, &[1.0, 0.0], &filters, &opts).expect("knn search").into_iter().map(|m| m.chunk_id.0).collect();
+        knn.sort();
+        assert_eq!(knn, expected, "vector post-filter for {filters:?}");
+    }
+}
+
+#[test]
+fn or_filter_tree_selects_union_in_sql_vector_and_delete_paths() {
+    let mut repo = SqliteRepo::new();
+    let mut a = doc_chunk("doc-a", "a", "first text");
+    a.meta.insert("section".into(), "補償".into());
+    let b = doc_chunk("doc-b", "b", "second text");
+    let c = doc_chunk("doc-c", "c", "third text");
+    repo.upsert_chunks(vec![a, b, c]).expect("upsert chunks");
+
+    let mut hnsw = HnswIndex::new(2, 16);
+    hnsw.upsert(&[
+        (ChunkId("a".into()), vec![1.0, 0.0]),
+        (ChunkId("b".into()), vec![0.9, 0.1]),
+        (ChunkId("c".into()), vec![0.8, 0.2]),
+    ]).expect("upsert vectors");
+
+    let leaf = |kind: FilterKind, op: FilterOp| FilterExpr::Leaf(FilterClause { kind, op });
+    let expr = FilterExpr::Or(vec![
+        leaf(FilterKind::PreferPre, FilterOp::DocIdIn(vec!["doc-b".into()])),
+        leaf(FilterKind::PostOnly, FilterOp::MetaEq { key: "section".into(), value: "補償".into(), case_insensitive: false }),
+    ]);
+
+    let ids = |v: Vec<ChunkId>| { let mut v: Vec<String> = v.into_iter().map(|c| c.0).collect(); v.sort(); v };
+    assert_eq!(ids(repo.list_chunk_ids_by_filter_expr(&expr, 10, 0).expect("list")), vec!["a", "b"]);
+
+    let opts = SearchOptions { top_k: 3, ..Default::default() };
+    let knn = hnsw.knn_ids_expr(&repo, &[1.0, 0.0], &expr, &opts).into_iter().map(|m| m.chunk_id).collect();
+    assert_eq!(ids(knn), vec!["a", "b"]);
+
+    // A flat clause list behaves like an implicit And
+    let flat = vec![FilterClause { kind: FilterKind::Must, op: FilterOp::DocIdIn(vec!["doc-a".into(), "doc-b".into()]) }];
+    let knn = hnsw.knn_ids(&repo, &[1.0, 0.0], &flat, &opts).expect("knn search").into_iter().map(|m| m.chunk_id).collect();
+    assert_eq!(ids(knn), vec!["a", "b"]);
+
+    assert_eq!(repo.delete_by_filter_expr(&FilterExpr::Or(Vec::new())).expect("no-op delete"), 0);
+    assert_eq!(repo.delete_by_filter_expr(&expr).expect("delete"), 2);
+    assert_eq!(ids(repo.list_chunk_ids_by_filter(&[], 10, 0).expect("list rest")), vec!["c"]);
+}
+
+#[test]
+fn empty_in_leaf_inside_or_does_not_widen_a_delete() {
+    let mut repo = SqliteRepo::new();
+    let records = vec![doc_chunk("doc-a", "a", "first text"), doc_chunk("doc-b", "b", "second text"), doc_chunk("doc-c", "c", "third text")];
+    repo.upsert_chunks(records).expect("upsert chunks");
+
+    let leaf = |op: FilterOp| FilterExpr::Leaf(FilterClause { kind: FilterKind::Must, op });
+    let ids = |v: Vec<ChunkId>| { let mut v: Vec<String> = v.into_iter().map(|c| c.0).collect(); v.sort(); v };
+    let empty_in = FilterExpr::Or(vec![
+        leaf(FilterOp::DocIdEq("doc-a".into())),
+        leaf(FilterOp::DocIdIn(Vec::new())),
+        leaf(FilterOp::MetaIn { key: "section".into(), values: Vec::new() }),
+    ]);
+    assert_eq!(ids(repo.list_chunk_ids_by_filter_expr(&empty_in, 10, 0).expect("list")), vec!["a"]);
+    // An empty NOT IN excludes nothing, so it still restricts nothing under And
+    let empty_not_in = FilterExpr::And(vec![leaf(FilterOp::DocIdNotIn(Vec::new())), leaf(FilterOp::DocIdEq("doc-b".into()))]);
+    assert_eq!(ids(repo.list_chunk_ids_by_filter_expr(&empty_not_in, 10, 0).expect("list")), vec!["b"]);
+
+    assert_eq!(repo.delete_by_filter_expr(&empty_in).expect("delete"), 1);
+    assert_eq!(ids(repo.list_chunk_ids_by_filter(&[], 10, 0).expect("list rest")), vec!["b", "c"]);
+}
+
+#[test]
+fn empty_in_flat_filter_matches_nothing_and_deletes_nothing() {
+    let mut repo = SqliteRepo::new();
+    let records = vec![doc_chunk("doc-a", "a", "first text"), doc_chunk("doc-b", "b", "second text")];
+    repo.upsert_chunks(records).expect("upsert chunks");
+
+    let must = |op: FilterOp| vec![FilterClause { kind: FilterKind::Must, op }];
+    let empty_doc_ids = must(FilterOp::DocIdIn(Vec::new()));
+    let empty_meta = must(FilterOp::MetaIn { key: "section".into(), values: Vec::new() });
+    assert!(repo.list_chunk_ids_by_filter(&empty_doc_ids, 10, 0).expect("list").is_empty());
+    assert_eq!(repo.count_by_filter(&empty_meta).expect("count"), 0);
+
+    assert_eq!(repo.delete_by_filter(&empty_doc_ids).expect("delete"), 0);
+    assert_eq!(repo.delete_by_filter(&empty_meta).expect("delete"), 0);
+    assert_eq!(repo.count_by_filter(&[]).expect("count rest"), 2);
+}
+
+#[test]
+fn ingest_journal_is_keyed_by_path_and_hash_after_migration() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let db = dir.path().join("chunks.db");
+    let raw = rusqlite::Connection::open(&db).expect("open raw connection");
+    raw.execute_batch(
+        "CREATE TABLE ingest_journal (content_sha256 TEXT PRIMARY KEY, source_uri TEXT NOT NULL, completed_at TEXT NOT NULL);
+         INSERT INTO ingest_journal VALUES ('abc', '/docs/a.txt', '2024-01-01T00:00:00Z');",
+    )
+    .expect("create hash-keyed journal");
+    drop(raw);
+
+    let repo = SqliteRepo::open(&db).expect("open repo");
+    assert!(repo.ingest_journal_contains("/docs/a.txt", "abc").expect("migrated entry"));
+    // A copy of the same content under another path is not ingested yet
+    assert!(!repo.ingest_journal_contains("/docs/copy-of-a.txt", "abc").expect("copy lookup"));
+    repo.record_ingest_completed("/docs/copy-of-a.txt", "abc", "2024-01-02T00:00:00Z").expect("journal copy");
+    assert!(repo.ingest_journal_contains("/docs/copy-of-a.txt", "abc").expect("copy journaled"));
+    assert!(repo.ingest_journal_contains("/docs/a.txt", "abc").expect("original kept"));
+}
+
+#[test]
+fn ingest_journal_tracks_started_entries_until_completed_or_removed() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let repo = SqliteRepo::open(dir.path().join("chunks.db")).expect("open repo");
+    repo.record_ingest_started("/docs/a.txt", Some("abc"), "doc-a", "2024-01-01T00:00:00Z").expect("start a");
+    repo.record_ingest_started("/docs/b.txt", None, "doc-b", "2024-01-01T00:00:01Z").expect("start b");
+    let started = repo.list_started_ingests().expect("list");
+    assert_eq!(started.iter().map(|e| (e.source_uri.as_str(), e.content_sha256.as_deref())).collect::<Vec<_>>(), [("/docs/a.txt", Some("abc")), ("/docs/b.txt", None)]);
+    assert!(!repo.ingest_journal_contains("/docs/a.txt", "abc").expect("started is not completed"));
+
+    repo.record_ingest_completed("/docs/a.txt", "abc", "2024-01-01T00:01:00Z").expect("complete a");
+    assert!(repo.ingest_journal_contains("/docs/a.txt", "abc").expect("completed"));
+    assert!(repo.remove_ingest_entry("/docs/b.txt", None).expect("remove b"));
+    assert!(repo.list_started_ingests().expect("list").is_empty());
+}
+
+#[test]
+fn block_kinds_round_trip_and_filter() {
+    let mut repo = SqliteRepo::new();
+    let mut code = chunk("code", "fn main() {}");
+    code.block_kinds = vec![BlockKind::Paragraph, BlockKind::Code];
+    let mut table = chunk("table", "a | b");
+    table.block_kinds = vec![BlockKind::Table];
+    let legacy = chunk("legacy", "plain text");
+    repo.upsert_chunks(vec![code, table, legacy]).expect("upsert chunks");
+
+    let got = repo.get_chunks_by_ids(&[ChunkId("code".into())]).expect("get chunk");
+    assert_eq!(got[0].block_kinds, vec![BlockKind::Paragraph, BlockKind::Code]);
+
+    let filters = vec![FilterClause { kind: FilterKind::Must, op: FilterOp::BlockKindIn(vec![BlockKind::Code, BlockKind::Table]) }];
+    let mut ids: Vec<String> = repo.list_chunk_ids_by_filter(&filters, 10, 0).expect("list ids").into_iter().map(|c| c.0).collect();
+    ids.sort();
+    assert_eq!(ids, vec!["code", "table"]);
+
+    // Records serialized before block kinds existed still decode, with no kinds.
+    let mut json = serde_json::to_value(chunk("old", "text")).expect("serialize");
+    json.as_object_mut().expect("object").remove("block_kinds");
+    let old: ChunkRecord = serde_json::from_value(json).expect("deserialize legacy record");
+    assert!(old.block_kinds.is_empty());
+}
+
+#[test]
+fn section_path_prefix_matches_whole_components_only() {
+    let mut repo = SqliteRepo::new();
+    let with_path = |id: &str, path: &[&str]| {
+        let mut c = chunk(id, "section text");
+        c.section_path = Some(path.iter().map(|s| s.to_string()).collect());
+        c
+    };
+    repo.upsert_chunks(vec![
+        with_path("ch1", &["第1章"]),
+        with_path("ch1-comp", &["第1章", "補償"]),
+        with_path("ch2-comp", &["第2章", "補償"]),
+        chunk("no-path", "section text"),
+    ])
+    .expect("upsert chunks");
+    let mut hnsw = HnswIndex::new(2, 16);
+    hnsw.upsert(&[
+        (ChunkId("ch1".into()), vec![1.0, 0.0]),
+        (ChunkId("ch1-comp".into()), vec![0.9, 0.1]),
+        (ChunkId("ch2-comp".into()), vec![0.8, 0.2]),
+        (ChunkId("no-path".into()), vec![0.7, 0.3]),
+    ]).expect("upsert vectors");
+
+    let prefix = |p: &[&str]| vec![FilterClause { kind: FilterKind::Must, op: FilterOp::SectionPathPrefix(p.iter().map(|s| s.to_string()).collect()) }];
+    let cases = [
+        (prefix(&["第1章"]), vec!["ch1", "ch1-comp"]),
+        (prefix(&["第1章", "補償"]), vec!["ch1-comp"]),
+        (prefix(&["第1章", "補"]), vec![]),
+        (prefix(&["補償"]), vec![]),
+    ];
+    let opts = SearchOptions { top_k: 4, ..Default::default() };
+    for (filters, expected) in cases {
+        let mut sql: Vec<String> = repo.list_chunk_ids_by_filter(&filters, 10, 0).expect("list ids").into_iter().map(|c| c.0).collect();
+        sql.sort();
+        assert_eq!(sql, expected, "sql prefilter for {filters:?}");
+        let mut knn: Vec<String> = hnsw.knn_ids(&repo, &[1.0, 0.0], &filters, &opts).expect("knn search").into_iter().map(|m| m.chunk_id.0).collect();
+        knn.sort();
+        assert_eq!(knn, expected, "vector post-filter for {filters:?}");
+    }
+}
+
+#[test]
+fn source_filename_filters_match_the_uri_basename() {
+    let mut repo = SqliteRepo::new();
+    let at = |id: &str, uri: &str| ChunkRecord { source_uri: uri.into(), ..chunk(id, "report text") };
+    repo.upsert_chunks(vec![
+        at("unix", "file:///data/2024-budget.pdf"),
+        at("dir-2024", "file:///2024-archive/budget.pdf"),
+        at("windows", "C:\\docs\\2024-plan.docx"),
+        at("bare", "2024-notes.txt"),
+        at("encoded", "https://example.com/files/2024%20q1%20review.pdf?v=2"),
+        at("lower", "file:///data/review_2024.PDF"),
+    ])
+    .expect("upsert chunks");
+    let mut hnsw = HnswIndex::new(2, 16);
+    let ids = ["unix", "dir-2024", "windows", "bare", "encoded", "lower"];
+    hnsw.upsert(&ids.iter().map(|id| (ChunkId((*id).into()), vec![1.0, 0.0])).collect::<Vec<_>>()).expect("upsert vectors");
+
+    let must = |op: FilterOp| vec![FilterClause { kind: FilterKind::Must, op }];
+    let cases = [
+        (must(FilterOp::SourceFilenamePrefix("2024-".into())), vec!["bare", "unix", "windows"]),
+        (must(FilterOp::SourceFilenamePrefix("2024 q1".into())), vec!["encoded"]),
+        (must(FilterOp::SourceFilenameContains("review".into())), vec!["encoded", "lower"]),
+        (must(FilterOp::SourceFilenameContains("_2024.PDF".into())), vec!["lower"]),
+        (must(FilterOp::SourceFilenameContains("_2024.pdf".into())), vec![]),
+        (must(FilterOp::SourceFilenameContains("archive".into())), vec![]),
+        (must(FilterOp::SourceFilenamePrefix(String::new())), vec!["bare", "dir-2024", "encoded", "lower", "unix", "windows"]),
+    ];
+    let opts = SearchOptions { top_k: 6, ..Default::default() };
+    for (filters, expected) in cases {
+        let mut sql: Vec<String> = repo.list_chunk_ids_by_filter(&filters, 10, 0).expect("list ids").into_iter().map(|c| c.0).collect();
+        sql.sort();
+        assert_eq!(sql, expected, "sql prefilter for {filters:?}");
+        let mut knn: Vec<String> = hnsw.knn_ids(&repo, &[1.0, 0.0], &filters, &opts).expect("knn search").into_iter().map(|m| m.chunk_id.0).collect();
+        knn.sort();
+        assert_eq!(knn, expected, "vector post-filter for {filters:?}");
+    }
+}
+
+#[test]
+fn meta_prefix_and_contains_filters_match_partially_and_skip_absent_keys() {
+    let mut repo = SqliteRepo::new();
+    let tagged = |id: &str, dept: Option<&str>| {
+        let mut c = chunk(id, "ledger text");
+        if let Some(d) = dept { c.meta.insert("dept".into(), d.into()); }
+        c
+    };
+    repo.upsert_chunks(vec![
+        tagged("tax", Some("finance/tax")),
+        tagged("audit", Some("finance/audit")),
+        tagged("upper", Some("Finance/tax")),
+        tagged("wild", Some("fin%nce_x/tax")),
+        tagged("hr", Some("hr/finance")),
+        tagged("none", None),
+    ])
+    .expect("upsert chunks");
+    let mut hnsw = HnswIndex::new(2, 16);
+    let ids = ["tax", "audit", "upper", "wild", "hr", "none"];
+    hnsw.upsert(&ids.iter().map(|id| (ChunkId((*id).into()), vec![1.0, 0.0])).collect::<Vec<_>>()).expect("upsert vectors");
+
+    let must = |op: FilterOp| vec![FilterClause { kind: FilterKind::Must, op }];
+    let prefix = |p: &str| must(FilterOp::MetaPrefix { key: "dept".into(), prefix: p.into(), case_insensitive: false });
+    let contains = |p: &str| must(FilterOp::MetaContains { key: "dept".into(), substring: p.into(), case_insensitive: false });
+    let cases = [
+        (prefix("finance"), vec!["audit", "tax"]),
+        (prefix("fin%"), vec!["wild"]),
+        (prefix("fin_nce"), vec![]),
+        (contains("/tax"), vec!["tax", "upper", "wild"]),
+        (contains("nce_"), vec!["wild"]),
+        (contains("finance"), vec!["audit", "hr", "tax"]),
+        (prefix(""), vec!["audit", "hr", "tax", "upper", "wild"]),
+    ];
+    let opts = SearchOptions { top_k: 6, ..Default::default() };
+    for (filters, expected) in cases {
+        let mut sql: Vec<String> = repo.list_chunk_ids_by_filter(&filters, 10, 0).expect("list ids").into_iter().map(|c| c.0).collect();
+        sql.sort();
+        assert_eq!(sql, expected, "sql prefilter for {filters:?}");
+        let mut knn: Vec<String> = hnsw.knn_ids(&repo, &[1.0, 0.0], &filters, &opts).expect("knn search").into_iter().map(|m| m.chunk_id.0).collect();
+        knn.sort();
+        assert_eq!(knn, expected, "vector post-filter for {filters:?}");
+    }
+}
+
+#[test]
+fn case_insensitive_filters_fold_ascii_only() {
+    let mut repo = SqliteRepo::new();
+    let at = |id: &str, uri: &str, city: &str| {
+        let mut c = ChunkRecord { source_uri: uri.into(), ..chunk(id, "travel notes") };
+        c.meta.insert("city".into(), city.into());
+        c
+    };
+    repo.upsert_chunks(vec![
+        at("upper", "file:///Docs/a.txt", "Tokyo"),
+        at("lower", "file:///docs/b.txt", "tokyo"),
+        at("accent", "file:///docs/c.txt", "TÓKYO"),
+        at("wide", "file:///docs/d.txt", "Ｔｏｋｙｏ"),
+        at("kanji", "file:///docs/e.txt", "東京"),
+    ])
+    .expect("upsert chunks");
+    let mut hnsw = HnswIndex::new(2, 16);
+    let ids = ["upper", "lower", "accent", "wide", "kanji"];
+    hnsw.upsert(&ids.iter().map(|id| (ChunkId((*id).into()), vec![1.0, 0.0])).collect::<Vec<_>>()).expect("upsert vectors");
+
+    let must = |op: FilterOp| vec![FilterClause { kind: FilterKind::Must, op }];
+    let eq = |v: &str, ci: bool| must(FilterOp::MetaEq { key: "city".into(), value: v.into(), case_insensitive: ci });
+    let cases = [
+        (eq("TOKYO", false), vec![]),
+        (eq("TOKYO", true), vec!["lower", "upper"]),
+        (eq("tókyo", true), vec![]),
+        (eq("東京", true), vec!["kanji"]),
+        (must(FilterOp::MetaPrefix { key: "city".into(), prefix: "to".into(), case_insensitive: true }), vec!["lower", "upper"]),
+        (must(FilterOp::MetaContains { key: "city".into(), substring: "KY".into(), case_insensitive: true }), vec!["accent", "lower", "upper"]),
+        (must(FilterOp::SourceUriPrefix { prefix: "file:///docs/".into(), case_insensitive: false }), vec!["accent", "kanji", "lower", "wide"]),
+        (must(FilterOp::SourceUriPrefix { prefix: "file:///docs/".into(), case_insensitive: true }), vec!["accent", "kanji", "lower", "upper", "wide"]),
+    ];
+    let opts = SearchOptions { top_k: 5, ..Default::default() };
+    for (filters, expected) in cases {
+        let mut sql: Vec<String> = repo.list_chunk_ids_by_filter(&filters, 10, 0).expect("list ids").into_iter().map(|c| c.0).collect();
+        sql.sort();
+        assert_eq!(sql, expected, "sql prefilter for {filters:?}");
+        let mut knn: Vec<String> = hnsw.knn_ids(&repo, &[1.0, 0.0], &filters, &opts).expect("knn search").into_iter().map(|m| m.chunk_id.0).collect();
+        knn.sort();
+        assert_eq!(knn, expected, "vector post-filter for {filters:?}");
+    }
+}
+
+#[test]
+fn chunks_by_ids_follow_input_order_across_batches_and_report_gaps() {
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks((0..1200).map(|i| chunk(&format!("c{i}"), "text")).collect()).expect("upsert chunks");
+
+    // Descending ids with a missing one in between, spanning several IN batches
+    let mut ids: Vec<ChunkId> = (0..1200).rev().map(|i| ChunkId(format!("c{i}"))).collect();
+    ids.insert(600, ChunkId("absent".into()));
+
+    let got: Vec<String> = repo.get_chunks_by_ids(&ids).expect("get chunks").into_iter().map(|c| c.chunk_id.0).collect();
+    let expected: Vec<String> = (0..1200).rev().map(|i| format!("c{i}")).collect();
+    assert_eq!(got, expected);
+
+    let kept = repo.get_chunks_by_ids_keep_missing(&ids).expect("get chunks keeping gaps");
+    assert_eq!(kept.len(), ids.len());
+    assert!(kept[600].is_none());
+    assert_eq!(kept[0].as_ref().map(|c| c.chunk_id.0.as_str()), Some("c1199"));
+    assert_eq!(kept[601].as_ref().map(|c| c.chunk_id.0.as_str()), Some("c599"));
+    assert_eq!(kept.iter().filter(|c| c.is_none()).count(), 1);
+}
+
+#[test]
+fn id_lists_beyond_the_sqlite_parameter_limit_work() {
+    const N: usize = 5_000;
+    let mut repo = SqliteRepo::new();
+    let docs: Vec<String> = (0..N).map(|i| format!("doc-{i}")).collect();
+    repo.upsert_chunks(docs.iter().map(|d| doc_chunk(d, &format!("{d}#0"), "text")).collect()).expect("upsert chunks");
+    for d in &docs { repo.upsert_file(&file(d)).expect("upsert file"); }
+    let ids: Vec<ChunkId> = docs.iter().map(|d| ChunkId(format!("{d}#0"))).collect();
+
+    assert_eq!(repo.get_chunks_by_ids(&ids).expect("get chunks").len(), N);
+    let must = |op: FilterOp| vec![FilterClause { kind: FilterKind::Must, op }];
+    let listed = repo.list_chunk_ids_by_filter(&must(FilterOp::DocIdIn(docs.clone())), N + 1, 0).expect("doc id in");
+    assert_eq!(listed.len(), N);
+    let rest = repo.list_chunk_ids_by_filter(&must(FilterOp::DocIdNotIn(docs[1..].to_vec())), N + 1, 0).expect("doc id not in");
+    assert_eq!(rest, vec![ChunkId("doc-0#0".into())]);
+
+    assert_eq!(repo.delete_files_by_doc_ids(&docs).expect("delete files"), N);
+    assert_eq!(repo.delete_by_ids(&ids).expect("delete chunks"), N);
+    assert!(repo.get_chunks_by_ids(&ids).expect("get chunks").is_empty());
+}
+
+#[test]
+fn files_by_doc_ids_are_fetched_in_one_lookup_and_skip_missing_docs() {
+    let repo = SqliteRepo::new();
+    let docs: Vec<String> = (0..600).map(|i| format!("doc-{i}")).collect();
+    for d in &docs {
+        let mut f = file(d);
+        f.title_guess = Some(format!("Title of {d}"));
+        repo.upsert_file(&f).expect("upsert file");
+    }
+
+    let wanted = vec!["doc-3".to_string(), "doc-404".to_string(), "missing".to_string()];
+    let files = repo.get_files_by_doc_ids(&wanted).expect("get files");
+    assert_eq!(files.len(), 2);
+    assert_eq!(files["doc-404"].title_guess.as_deref(), Some("Title of doc-404"));
+    assert!(!files.contains_key("missing"));
+
+    // Above the per-statement bind limit the ids go through one JSON array
+    assert_eq!(repo.get_files_by_doc_ids(&docs).expect("get files").len(), docs.len());
+    assert!(repo.get_files_by_doc_ids(&[]).expect("get files").is_empty());
+}
+
+#[test]
+fn doc_chunks_page_in_numeric_suffix_order() {
+    let mut repo = SqliteRepo::new();
+    // Inserted out of order so rowid order differs from chunk order
+    let ids = ["a.txt#10", "a.txt#2", "a.txt#0", "a.txt#11", "a.txt#1"];
+    repo.upsert_chunks(ids.iter().map(|id| doc_chunk("doc-a", id, "text")).chain([doc_chunk("doc-b", "b.txt#3", "other")]).collect())
+        .expect("upsert chunks");
+
+    let page = |limit, offset| -> Vec<String> {
+        repo.get_chunks_by_doc_id("doc-a", limit, offset).expect("doc chunks").into_iter().map(|c| c.chunk_id.0).collect()
+    };
+    assert_eq!(page(10, 0), ["a.txt#0", "a.txt#1", "a.txt#2", "a.txt#10", "a.txt#11"]);
+    assert_eq!(page(2, 2), ["a.txt#2", "a.txt#10"]);
+    assert!(page(10, 5).is_empty());
+}
+
+#[test]
+fn document_order_follows_seq_and_falls_back_to_id_suffix_for_legacy_rows() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let db = dir.path().join("seq.db");
+    let mut repo = SqliteRepo::open(&db).expect("open repo");
+    // Producer with opaque ids: only `seq` carries the order
+    let opaque = |id: &str, seq: u32| ChunkRecord { seq: Some(seq), ..doc_chunk("doc-u", id, "text") };
+    repo.upsert_chunks(vec![opaque("u-c", 2), opaque("u-a", 0), opaque("u-b", 1)]).expect("upsert opaque");
+    repo.upsert_chunks(vec![doc_chunk("doc-l", "l.txt#10", "text"), doc_chunk("doc-l", "l.txt#9", "text")]).expect("upsert legacy");
+
+    let order = |repo: &SqliteRepo, doc: &str| -> Vec<(String, Option<u32>)> {
+        repo.get_chunks_by_doc_id(doc, 10, 0).expect("doc chunks").into_iter().map(|c| (c.chunk_id.0, c.seq)).collect()
+    };
+    assert_eq!(order(&repo, "doc-u"), [("u-a".to_string(), Some(0)), ("u-b".into(), Some(1)), ("u-c".into(), Some(2))]);
+    let (prev, next) = repo.get_neighbor_chunks(&ChunkId("u-b".into())).expect("neighbors");
+    assert_eq!((prev.map(|c| c.chunk_id.0), next.map(|c| c.chunk_id.0)), (Some("u-a".into()), Some("u-c".into())));
+
+    // A pre-1.2 record without `seq` is ordered by its id suffix, not as seq 0
+    let mut legacy = serde_json::to_value(doc_chunk("doc-j", "j.txt#10", "text")).expect("to json");
+    legacy.as_object_mut().expect("object").remove("seq");
+    let legacy: ChunkRecord = serde_json::from_value(legacy).expect("from json");
+    assert_eq!(legacy.seq, None);
+    repo.upsert_chunks(vec![legacy, ChunkRecord { seq: Some(9), ..doc_chunk("doc-j", "j.txt#9", "text") }]).expect("upsert json");
+    assert_eq!(order(&repo, "doc-j"), [("j.txt#9".to_string(), Some(9)), ("j.txt#10".into(), Some(10))]);
+    drop(repo);
+
+    // Simulate a pre-1.2 table: no seq column at all
+    let conn = rusqlite::Connection::open(&db).expect("raw open");
+    conn.execute_batch("DROP INDEX idx_chunks_doc_seq; ALTER TABLE chunks DROP COLUMN seq;").expect("drop seq");
+    drop(conn);
+    let repo = SqliteRepo::open(&db).expect("reopen migrates");
+    assert_eq!(order(&repo, "doc-l"), [("l.txt#9".to_string(), Some(9)), ("l.txt#10".into(), Some(10))]);
+    let (prev, _) = repo.get_neighbor_chunks(&ChunkId("l.txt#10".into())).expect("neighbors");
+    assert_eq!(prev.map(|c| c.chunk_id.0), Some("l.txt#9".into()));
+}
+
+#[test]
+fn iter_chunks_pages_through_every_chunk_in_insertion_order() {
+    let mut repo = SqliteRepo::new();
+    let mut records: Vec<ChunkRecord> = (0..5).map(|i| chunk(&format!("c{i}"), &format!("text {i}"))).collect();
+    records[3].extra.insert("origin".into(), serde_json::json!({ "page": 7 }));
+    repo.upsert_chunks(records).expect("upsert chunks");
+
+    let all: Vec<ChunkRecord> = repo.iter_chunks(2).collect::<Result<_, _>>().expect("iterate chunks");
+    let ids: Vec<&str> = all.iter().map(|c| c.chunk_id.0.as_str()).collect();
+    assert_eq!(ids, vec!["c0", "c1", "c2", "c3", "c4"]);
+    assert_eq!(all[3].extra.get("origin"), Some(&serde_json::json!({ "page": 7 })));
+}
+
+#[test]
+fn min_fetch_never_returns_more_than_top_k_vector_matches() {
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks(vec![chunk("a", "alpha"), chunk("b", "beta"), chunk("c", "gamma")]).expect("upsert chunks");
+    let mut hnsw = HnswIndex::new(2, 16);
+    hnsw.upsert(&[
+        (ChunkId("a".into()), vec![1.0, 0.0]),
+        (ChunkId("b".into()), vec![0.9, 0.1]),
+        (ChunkId("c".into()), vec![0.8, 0.2]),
+    ]).expect("upsert vectors");
+    let query = [1.0, 0.0];
+
+    let narrow = SearchOptions { top_k: 1, fetch_factor: 1, ..Default::default() };
+    let wide = SearchOptions { min_fetch: 3, ..narrow.clone() };
+    assert_eq!(wide.fetch_n(), 3);
+    // The floor widens the fetch, not the result: callers widen `top_k` to see more
+    let ids = |opts: &SearchOptions| hnsw.knn_ids(&repo, &query, &[], opts).expect("knn search").into_iter().map(|m| m.chunk_id.0).collect::<Vec<_>>();
+    assert_eq!(ids(&narrow), vec!["a"]);
+    assert_eq!(ids(&wide), vec!["a"]);
+    assert_eq!(ids(&SearchOptions { top_k: wide.fetch_n(), ..wide.clone() }), vec!["a", "b", "c"]);
+}
+
+#[test]
+fn list_files_page_reports_the_same_total_for_every_page() {
+    let mut repo = SqliteRepo::new();
+    const N: usize = 7;
+    for i in 0..N { repo.upsert_file(&file(&format!("doc-{i}"))).expect("upsert file"); }
+    repo.upsert_chunks(vec![doc_chunk("doc-0", "c0", "alpha"), doc_chunk("doc-1", "c1", "beta")]).expect("upsert chunks");
+
+    let mut seen = 0;
+    for offset in [0, 3, 6, 9] {
+        let (page, total) = repo.list_files_page(3, offset).expect("list files page");
+        assert_eq!(total, N as u64, "total at offset {offset}");
+        seen += page.len();
+    }
+    assert_eq!(seen, N);
+    assert_eq!(repo.count_files(&[]).expect("count"), N as u64);
+    let only_doc1 = [FilterClause { kind: FilterKind::Must, op: FilterOp::DocIdEq("doc-1".into()) }];
+    assert_eq!(repo.count_files(&only_doc1).expect("count filtered"), 1);
+}
+
+#[test]
+fn doc_blobs_round_trip_by_doc_and_key() {
+    let repo = SqliteRepo::new();
+    let png: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0x00, 0xff, 0x10, 0x00];
+    repo.put_doc_blob("doc-1", "thumbnail", &png).expect("put thumbnail");
+    repo.put_doc_blob("doc-1", "summary", "要約".as_bytes()).expect("put summary");
+    repo.put_doc_blob("doc-2", "thumbnail", &[1, 2, 3]).expect("put other doc");
+
+    assert_eq!(repo.get_doc_blob("doc-1", "thumbnail").expect("get"), Some(png.clone()));
+    assert_eq!(repo.get_doc_blob("doc-1", "missing").expect("get missing"), None);
+    assert_eq!(
+        repo.list_doc_blobs("doc-1").expect("list"),
+        vec![("summary".to_string(), "要約".len() as u64), ("thumbnail".to_string(), png.len() as u64)]
+    );
+
+    repo.delete_files_by_doc_ids(&["doc-1".to_string()]).expect("delete file");
+    assert!(repo.list_doc_blobs("doc-1").expect("list after delete").is_empty());
+    assert!(repo.get_doc_blob("doc-2", "thumbnail").expect("get other").is_some());
+}
+
+#[test]
+fn suggest_terms_match_prefix_in_frequency_order() {
+    let mut repo = SqliteRepo::new();
+    let terms = |v: &[(&str, u64)]| v.iter().map(|(t, n)| (t.to_string(), *n)).collect::<Vec<_>>();
+    repo.add_suggest_terms(&terms(&[("search", 3), ("seal", 1), ("sea", 2), ("rust", 9), ("se%", 1)]), 0).expect("seed terms");
+    repo.add_suggest_terms(&terms(&[("seal", 5)]), 0).expect("bump seal");
+
+    let got = repo.suggest_terms("sea", 10).expect("suggest");
+    assert_eq!(got, terms(&[("seal", 6), ("search", 3), ("sea", 2)]));
+    assert_eq!(repo.suggest_terms("se", 1).expect("limit").len(), 1);
+    assert_eq!(repo.suggest_terms("se%", 10).expect("literal prefix"), terms(&[("se%", 1)]));
+
+    // A cap prunes the least frequent terms, ties by term
+    repo.add_suggest_terms(&terms(&[("sequel", 2)]), 3).expect("capped add");
+    assert_eq!(repo.suggest_terms("", 10).expect("all terms"), terms(&[("rust", 9), ("seal", 6), ("search", 3)]));
+}
+
+#[test]
+fn search_log_keeps_hit_order_and_labels() {
+    let mut repo = SqliteRepo::new();
+    let ids = |v: &[&str]| v.iter().map(|s| ChunkId(s.to_string())).collect::<Vec<_>>();
+    let first = repo.log_search("rust", "2024-01-01T00:00:00Z", &ids(&["c2", "c1"])).expect("log first");
+    let second = repo.log_search("sqlite", "2024-01-02T00:00:00Z", &ids(&["c3"])).expect("log second");
+    assert!(repo.set_search_label(first, &ChunkId("c1".into()), 1.0).expect("label"));
+    assert!(!repo.set_search_label(second, &ChunkId("c1".into()), 1.0).expect("label missing hit"));
+
+    let log = repo.list_search_log(0, 10).expect("list");
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0].query, "rust");
+    assert_eq!(log[0].hits, vec![(ChunkId("c2".into()), None), (ChunkId("c1".into()), Some(1.0))]);
+    assert_eq!(repo.list_search_log(first, 10).expect("page").iter().map(|e| e.log_id).collect::<Vec<_>>(), vec![second]);
+}
+
+struct FailingText { external: bool, fail: bool }
+
+impl chunking_store::TextIndexMaintainer for FailingText {
+    fn upsert(&self, _records: &[ChunkRecord]) -> Result<(), chunking_store::IndexError> {
+        if self.fail { Err(chunking_store::IndexError::Backend("text index down".into())) } else { Ok(()) }
+    }
+    fn delete_by_ids(&self, _ids: &[ChunkId]) -> Result<(), chunking_store::IndexError> { Ok(()) }
+    fn in_primary_store(&self) -> bool { !self.external }
+}
+
+struct FailingVectors;
+
+impl chunking_store::VectorIndexMaintainer for FailingVectors {
+    fn upsert_vectors(&mut self, _items: &[(ChunkId, Vec<f32>)]) -> Result<(), chunking_store::IndexError> {
+        Err(chunking_store::IndexError::Backend("vector index down".into()))
+    }
+    fn delete_by_ids(&mut self, _ids: &[ChunkId]) -> Result<(), chunking_store::IndexError> { Ok(()) }
+}
+
+#[test]
+fn staged_ingest_rolls_the_db_back_when_a_later_step_fails() {
+    use chunking_store::orchestrator::{stage_ingest_chunks, OrchestratorError};
+    let mut repo = SqliteRepo::new();
+    let records = vec![chunk("doc-1#0", "alpha"), chunk("doc-1#1", "beta")];
+    let vectors = vec![(ChunkId("doc-1#0".into()), vec![1.0, 0.0]), (ChunkId("doc-1#1".into()), vec![0.0, 1.0])];
+    let stored = |repo: &SqliteRepo| repo.counts().unwrap().0;
+
+    // A failing vector index: nothing else has been applied yet
+    let err = stage_ingest_chunks(&mut repo, &records, &[], &mut [&mut FailingVectors], Some(&vectors)).err().expect("vector step fails");
+    assert!(matches!(err, OrchestratorError::Index(_)), "{err}");
+    assert_eq!(stored(&repo), 0);
+
+    // An external text index took the batch before another one failed
+    let mut hnsw = HnswIndex::new(2, 10);
+    let ok_external = FailingText { external: true, fail: false };
+    let in_db = FailingText { external: false, fail: false };
+    let down = FailingText { external: true, fail: true };
+    let texts: [&dyn chunking_store::TextIndexMaintainer; 3] = [&in_db, &ok_external, &down];
+    let err = stage_ingest_chunks(&mut repo, &records, &texts, &mut [&mut hnsw], Some(&vectors)).err().expect("text step fails");
+    assert!(matches!(&err, OrchestratorError::PartialIngest { applied, .. } if applied == &["text index #1".to_string()]), "{err}");
+    assert_eq!(stored(&repo), 0);
+
+    // A step outside the orchestrator (e.g. a snapshot save) can still abort, or commit
+    let staged = stage_ingest_chunks(&mut repo, &records, &[], &mut [&mut hnsw], Some(&vectors)).expect("stage");
+    assert!(matches!(staged.abort("disk full"), OrchestratorError::Index(_)));
+    assert_eq!(stored(&repo), 0);
+    stage_ingest_chunks(&mut repo, &records, &[], &mut [&mut hnsw], Some(&vectors)).expect("stage").commit().expect("commit");
+    assert_eq!(stored(&repo), 2);
+}
+
+#[test]
+fn count_by_filter_matches_the_listed_ids() {
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks(vec![
+        doc_chunk("doc-a", "doc-a#0", "one"),
+        doc_chunk("doc-a", "doc-a#1", "two"),
+        doc_chunk("doc-b", "doc-b#0", "three"),
+        doc_chunk("doc-c", "doc-c#0", "four"),
+    ])
+    .expect("upsert");
+    let filters = [FilterClause { kind: FilterKind::Must, op: FilterOp::DocIdIn(vec!["doc-a".into(), "doc-b".into()]) }];
+
+    assert_eq!(repo.count_by_filter(&[]).expect("count all"), 4);
+    let n = repo.count_by_filter(&filters).expect("count");
+    assert_eq!(n as usize, repo.list_chunk_ids_by_filter(&filters, 100, 0).expect("list").len());
+    assert_eq!(n, 3);
+    assert_eq!(repo.count_by_filter_per_doc(&filters).expect("per doc"), vec![("doc-a".to_string(), 2), ("doc-b".to_string(), 1)]);
+}
+
+#[test]
+fn list_documents_counts_live_chunks_and_flags_orphans() {
+    let mut repo = SqliteRepo::new();
+    let paged = |id: &str, start: u32, end: Option<u32>| ChunkRecord { page_start: Some(start), page_end: end, ..doc_chunk("doc-a", id, "text") };
+    repo.upsert_chunks(vec![paged("doc-a#0", 2, Some(3)), paged("doc-a#1", 4, None), paged("doc-a#2", 7, Some(9))]).expect("upsert");
+    for doc in ["doc-a", "doc-b"] {
+        repo.upsert_file(&FileRecord { chunk_count: Some(3), ..file(doc) }).expect("upsert file");
+    }
+    repo.delete_by_ids(&[ChunkId("doc-a#2".into())]).expect("delete");
+
+    let mut docs = repo.list_documents(10, 0).expect("list documents");
+    docs.sort_by(|a, b| a.file.doc_id.0.cmp(&b.file.doc_id.0));
+    let summary: Vec<_> = docs.iter().map(|d| (d.file.doc_id.0.as_str(), d.live_chunks, d.page_min, d.page_max)).collect();
+    assert_eq!(summary, [("doc-a", 2, Some(2), Some(4)), ("doc-b", 0, None, None)]);
+}
+
+#[test]
+fn find_orphans_reports_both_sides_without_deleting() {
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks(vec![doc_chunk("doc-a", "doc-a#0", "kept"), doc_chunk("doc-b", "doc-b#0", "no file")]).expect("upsert");
+    for doc in ["doc-a", "doc-c"] {
+        repo.upsert_file(&file(doc)).expect("upsert file");
+    }
+
+    let report = repo.find_orphans().expect("find orphans");
+    assert_eq!(report.files_without_chunks, [DocumentId("doc-c".into())]);
+    assert_eq!(report.chunks_without_file, [ChunkId("doc-b#0".into())]);
+    assert_eq!(repo.find_orphans().expect("again"), report);
+    assert_eq!(repo.count_by_filter(&[]).expect("count"), 2);
+}
@@ -0,0 +1,147 @@
+#![cfg(feature = "tantivy-impl")]
+
+use chunk_model::{ChunkId, ChunkRecord, DocumentId, SCHEMA_MAJOR};
+use chunking_store::sqlite_repo::SqliteRepo;
+use chunking_store::tantivy_index::{TantivyIndex, TantivyOpts, TokenCombine, TokenizerKind};
+use chunking_store::{ChunkPrimaryStore, FilterClause, FilterKind, FilterOp, SearchOptions, TextSearcher};
+
+fn chunk(id: &str, text: &str) -> ChunkRecord {
+    ChunkRecord {
+        schema_version: SCHEMA_MAJOR,
+        doc_id: DocumentId("doc-1".into()),
+        chunk_id: ChunkId(id.into()),
+        seq: None,
+        source_uri: "file://doc-1.txt".into(),
+        source_mime: "text/plain".into(),
+        extracted_at: String::new(),
+        page_start: None,
+        page_end: None,
+        text: text.into(),
+        section_path: None,
+        meta: Default::default(),
+        block_kinds: Vec::new(),
+        extra: Default::default(),
+    }
+}
+
+#[test]
+fn phrase_combine_requires_token_order_within_slop() {
+    let records = vec![
+        chunk("ordered", "The keeper lit the lamp at dusk."),
+        chunk("scattered", "The lamp lit up. Much later, far away, the old keeper slept."),
+    ];
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks(records.clone()).expect("upsert chunks");
+    let ti = TantivyIndex::new_ram().expect("ram index");
+    ti.upsert_records(&records).expect("index records");
+    let opts = SearchOptions { top_k: 5, ..Default::default() };
+    let ids = |query: &str, combine: TokenCombine| {
+        let mut ids: Vec<String> = ti.search_ids_tokenized(&repo, query, &[], &opts, combine).into_iter().map(|m| m.chunk_id.0).collect();
+        ids.sort();
+        ids
+    };
+
+    assert_eq!(ids("keeper lit", TokenCombine::AND), vec!["ordered", "scattered"]);
+    assert_eq!(ids("keeper lit", TokenCombine::Phrase { slop: 0 }), vec!["ordered"]);
+    assert!(ids("keeper lamp", TokenCombine::Phrase { slop: 0 }).is_empty());
+    assert_eq!(ids("keeper lamp", TokenCombine::Phrase { slop: 8 }), vec!["ordered"]);
+}
+
+#[test]
+fn ngram_tokenizer_is_persisted_and_reused_on_reopen() {
+    let records = vec![chunk("ja", "損害賠償を請求する。"), chunk("en", "Claims for damages.")];
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks(records.clone()).expect("upsert chunks");
+    let opts = SearchOptions { top_k: 5, ..Default::default() };
+    let dir = tempfile::tempdir().expect("create temp dir");
+
+    let bigram = TantivyOpts { tokenizer: TokenizerKind::Ngram { min: 2, max: 2 }, ..Default::default() };
+    {
+        let ti = TantivyIndex::open_or_create_dir_with_opts(dir.path(), bigram).expect("create index");
+        ti.upsert_records(&records).expect("index records");
+    }
+    // Reopening with other options keeps the tokenizer recorded in the index
+    let ti = TantivyIndex::open_or_create_dir_with_opts(dir.path(), TantivyOpts { tokenizer: TokenizerKind::Default, ..Default::default() }).expect("reopen index");
+    assert_eq!(ti.tokenizer(), TokenizerKind::Ngram { min: 2, max: 2 });
+    let hits = ti.search_ids(&repo, "賠償", &[], &opts);
+    assert_eq!(hits.iter().map(|m| m.chunk_id.0.as_str()).collect::<Vec<_>>(), vec!["ja"]);
+
+    // The whitespace/punctuation-based default keeps the Japanese sentence as one token
+    let plain = TantivyIndex::new_ram_with_opts(TantivyOpts { tokenizer: TokenizerKind::Default, ..Default::default() }).expect("ram index");
+    plain.upsert_records(&records).expect("index records");
+    assert!(plain.search_ids(&repo, "賠償", &[], &opts).is_empty());
+}
+
+#[test]
+fn heading_matches_outrank_body_matches_with_boost() {
+    let mut in_heading = chunk("heading", "Spending went up this quarter.");
+    in_heading.section_path = Some(vec!["Budget".into(), "Overview".into()]);
+    let mut in_body = chunk("body", "The budget overview lists spending.");
+    in_body.section_path = Some(vec!["Notes".into()]);
+    let records = vec![in_heading, in_body];
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks(records.clone()).expect("upsert chunks");
+    let opts = SearchOptions { top_k: 5, ..Default::default() };
+    let ranked = |heading_boost: f32| {
+        let ti = TantivyIndex::new_ram_with_opts(TantivyOpts { tokenizer: TokenizerKind::Default, heading_boost }).expect("ram index");
+        ti.upsert_records(&records).expect("index records");
+        ti.search_ids(&repo, "budget", &[], &opts).into_iter().map(|m| m.chunk_id.0).collect::<Vec<_>>()
+    };
+
+    assert_eq!(ranked(3.0), vec!["heading", "body"]);
+    // A zero boost leaves only body-text relevance
+    assert_eq!(ranked(0.0)[0], "body");
+}
+
+#[test]
+fn meta_equality_filters_are_applied_inside_the_index() {
+    let mut tagged = chunk("tagged", "Quarterly budget review.");
+    tagged.meta.insert("collection".into(), "finance".into());
+    let mut other = chunk("other", "Budget notes for the offsite.");
+    other.meta.insert("collection".into(), "events".into());
+    let records = vec![tagged, other];
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks(records.clone()).expect("upsert chunks");
+    let ti = TantivyIndex::new_ram().expect("ram index");
+    ti.upsert_records(&records).expect("index records");
+    assert!(ti.caps().supports(&FilterOp::MetaEq { key: "collection".into(), value: "finance".into(), case_insensitive: false }));
+
+    // top_k 1 would keep the wrong chunk if the filter only ran after ranking
+    let opts = SearchOptions { top_k: 1, fetch_factor: 1, ..Default::default() };
+    let ids = |op: FilterOp| {
+        let filters = [FilterClause { kind: FilterKind::Must, op }];
+        ti.search_ids(&repo, "budget", &filters, &opts).into_iter().map(|m| m.chunk_id.0).collect::<Vec<_>>()
+    };
+    assert_eq!(ids(FilterOp::MetaEq { key: "collection".into(), value: "events".into(), case_insensitive: false }), vec!["other"]);
+    assert_eq!(ids(FilterOp::MetaEq { key: "collection".into(), value: "finance".into(), case_insensitive: false }), vec!["tagged"]);
+    assert_eq!(ids(FilterOp::MetaIn { key: "collection".into(), values: vec!["events".into()] }), vec!["other"]);
+    assert!(ids(FilterOp::MetaEq { key: "collection".into(), value: "legal".into(), case_insensitive: false }).is_empty());
+}
+
+#[test]
+fn source_uri_prefix_honours_case_insensitive() {
+    let mut upper = chunk("upper", "Harbour cranes unload timber.");
+    upper.source_uri = "file:///Docs/a.txt".into();
+    let mut lower = chunk("lower", "Harbour cranes load grain.");
+    lower.source_uri = "file:///docs/b.txt".into();
+    let records = vec![upper, lower];
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks(records.clone()).expect("upsert chunks");
+    let ti = TantivyIndex::new_ram().expect("ram index");
+    ti.upsert_records(&records).expect("index records");
+    let opts = SearchOptions { top_k: 5, ..Default::default() };
+    let filter = |case_insensitive: bool| {
+        vec![FilterClause { kind: FilterKind::Must, op: FilterOp::SourceUriPrefix { prefix: "file:///docs/".into(), case_insensitive } }]
+    };
+    let sorted = |matches: Vec<chunking_store::TextMatch>| {
+        let mut ids: Vec<String> = matches.into_iter().map(|m| m.chunk_id.0).collect();
+        ids.sort();
+        ids
+    };
+
+    for ci in [false, true] {
+        let expected = if ci { vec!["lower", "upper"] } else { vec!["lower"] };
+        assert_eq!(sorted(ti.search_ids(&repo, "harbour", &filter(ci), &opts)), expected);
+        assert_eq!(sorted(ti.search_ids_tokenized(&repo, "harbour cranes", &filter(ci), &opts, TokenCombine::AND)), expected);
+    }
+}
@@ -0,0 +1,64 @@
+use chunk_model::ChunkId;
+#[cfg(feature = "tantivy-impl")]
+use chunk_model::{ChunkRecord, DocumentId, SCHEMA_MAJOR};
+use chunking_store::sqlite_repo::SqliteRepo;
+use chunking_store::{SearchOptions, TextSearcher};
+
+#[cfg(feature = "tantivy-impl")]
+fn chunk(id: &str, text: &str) -> ChunkRecord {
+    ChunkRecord {
+        schema_version: SCHEMA_MAJOR,
+        doc_id: DocumentId("doc-1".into()),
+        chunk_id: ChunkId(id.into()),
+        seq: None,
+        source_uri: "file://doc-1.txt".into(),
+        source_mime: "text/plain".into(),
+        extracted_at: String::new(),
+        page_start: None,
+        page_end: None,
+        text: text.into(),
+        section_path: None,
+        meta: Default::default(),
+        block_kinds: Vec::new(),
+        extra: Default::default(),
+    }
+}
+
+#[cfg(feature = "tantivy-impl")]
+#[test]
+fn tantivy_snippets_highlight_the_matched_term() {
+    use chunking_store::tantivy_index::TantivyIndex;
+    use chunking_store::ChunkPrimaryStore;
+    let filler = "Unrelated filler sentence. ".repeat(20);
+    let records = vec![chunk("c1", &format!("{filler}The lighthouse keeper lit the lamp. {filler}"))];
+    let mut repo = SqliteRepo::new();
+    repo.upsert_chunks(records.clone()).expect("upsert chunks");
+    let ti = TantivyIndex::new_ram().expect("ram index");
+    ti.upsert_records(&records).expect("index records");
+
+    let opts = SearchOptions { top_k: 5, ..Default::default() };
+    let hits = ti.search_ids_with_snippets(&repo, "lighthouse", &[], &opts);
+    let snippet = hits[0].1.as_ref().expect("tantivy produces a snippet");
+    assert!(snippet.text.len() < records[0].text.len());
+    let (start, end) = snippet.highlights[0];
+    assert_eq!(snippet.text[start as usize..end as usize].to_lowercase(), "lighthouse");
+}
+
+/// Stand-in for a backend without term offsets (like FTS5): only `search_ids` is implemented.
+struct IdsOnly;
+
+impl TextSearcher for IdsOnly {
+    fn name(&self) -> &'static str { "ids-only" }
+    fn caps(&self) -> chunking_store::IndexCaps { chunking_store::IndexCaps::NONE }
+    fn search_ids(&self, _store: &dyn chunking_store::ChunkStoreRead, _query: &str, _filters: &[chunking_store::FilterClause], _opts: &SearchOptions) -> Vec<chunking_store::TextMatch> {
+        vec![chunking_store::TextMatch { chunk_id: ChunkId("c1".into()), score: 1.0, raw_score: 1.0 }]
+    }
+}
+
+#[test]
+fn backends_without_offsets_return_no_snippet() {
+    let repo = SqliteRepo::new();
+    let hits = IdsOnly.search_ids_with_snippets(&repo, "lighthouse", &[], &SearchOptions::default());
+    assert_eq!(hits.len(), 1);
+    assert!(hits[0].1.is_none());
+}
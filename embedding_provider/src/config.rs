@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use crate::embedder::OnnxStdIoConfig;
+use crate::embedder::{OnnxStdIoConfig, PoolingKind};
 
 /// Default settings for the local ONNX embedder.
 #[derive(Debug, Clone, Copy)]
@@ -40,5 +40,8 @@ pub fn default_stdio_config() -> OnnxStdIoConfig {
         embedding_model_id: ONNX_STDIO_DEFAULTS.embedding_model_id.into(),
         text_repr_version: ONNX_STDIO_DEFAULTS.text_repr_version.into(),
         preload_model_to_memory: false,
+        normalize: false,
+        pooling: PoolingKind::default(),
+        worker_count: 1,
     }
 }
@@ -2,6 +2,7 @@ use std::collections::hash_map::DefaultHasher;
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, OnceLock};
 
 use ndarray::Array2;
@@ -37,6 +38,18 @@ pub enum EmbedderError {
     },
     #[error("provider failure: {message}")]
     ProviderFailure { message: String },
+    /// The runtime ran out of memory (or a similar per-run resource) for this batch; the
+    /// same inputs may succeed in a smaller batch.
+    #[error("embedder resources exhausted: {message}")]
+    ResourceExhausted { message: String },
+}
+
+impl EmbedderError {
+    /// True when retrying with a smaller batch may succeed. Configuration, input and
+    /// protocol errors are fatal.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, EmbedderError::ResourceExhausted { .. })
+    }
 }
 
 /// Core interface for all embedder implementations.
@@ -46,6 +59,21 @@ pub trait Embedder: Send + Sync {
     fn info(&self) -> &EmbedderInfo;
 }
 
+/// How token-level model outputs are reduced to one sentence vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoolingKind {
+    /// First token (`[CLS]`/`<s>`) output; for models trained with CLS pooling such as
+    /// the BGE family.
+    Cls,
+    /// Plain average over every position, padding included. Only equivalent to masked mean
+    /// for unpadded (single or equal-length) inputs; kept for models exported that way.
+    Mean,
+    /// Average over positions whose attention mask is 1, so padding never contributes;
+    /// what sentence-transformers mean-pooling models (ruri, multilingual-e5, MiniLM) expect.
+    #[default]
+    MeanWithAttentionMask,
+}
+
 /// Configuration for a local ONNX embedder driven through stdio.
 #[derive(Debug, Clone)]
 pub struct OnnxStdIoConfig {
@@ -62,16 +90,31 @@ pub struct OnnxStdIoConfig {
     /// network share. Increases peak memory usage by roughly the model size
     /// during initialization.
     pub preload_model_to_memory: bool,
+    /// When true, scale every output vector to unit L2 norm after pooling, so cosine
+    /// similarity and inner product rank identically.
+    pub normalize: bool,
+    /// Pooling applied to the token outputs after the forward pass.
+    pub pooling: PoolingKind,
+    /// Number of model sessions. With more than one, `embed_batch` splits each batch into
+    /// that many parts, runs them on the sessions in parallel (rotating which session takes
+    /// the first part) and returns the vectors in input order; the cores are divided between
+    /// the sessions. A part that fails on one session is retried on the next. 0 or 1 keeps a
+    /// single session. With `PoolingKind::Mean` the vectors depend on each part's padding.
+    pub worker_count: usize,
 }
 
 /// ONNX-based embedder that executes models through the ONNX Runtime shared library.
 #[derive(Debug)]
 pub struct OnnxStdIoEmbedder {
     info: EmbedderInfo,
-    session: Mutex<Session>,
+    sessions: Vec<Mutex<Session>>,
+    /// Session that takes the next single input or first batch part (round-robin)
+    next_worker: AtomicUsize,
     tokenizer: Arc<Tokenizer>,
     pad_id: i64,
     max_input_length: usize,
+    normalize: bool,
+    pooling: PoolingKind,
 }
 
 #[derive(Debug)]
@@ -81,6 +124,9 @@ struct PreparedBatch {
     attention_rows: Vec<Vec<i64>>,
 }
 
+/// Vectors and per-text token counts, as returned by `embed_batch_counted`.
+type CountedBatch = (Vec<Vec<f32>>, Vec<usize>);
+
 static ORT_RUNTIME_PATH: OnceLock<PathBuf> = OnceLock::new();
 
 impl OnnxStdIoEmbedder {
@@ -105,20 +151,20 @@ impl OnnxStdIoEmbedder {
         let model_path = resolve_existing_path(&config.model_path, "ONNX model")?;
         let tokenizer_path = resolve_existing_path(&config.tokenizer_path, "tokenizer config")?;
 
-        let session = if config.preload_model_to_memory {
-            let bytes = std::fs::read(&model_path).map_err(|e| EmbedderError::ProviderFailure {
+        let model_bytes = if config.preload_model_to_memory {
+            Some(std::fs::read(&model_path).map_err(|e| EmbedderError::ProviderFailure {
                 message: format!("failed to read model `{}`: {e}", model_path.display()),
-            })?;
-            Session::builder()
-                .map_err(|err| map_ort_error("create session builder", err))?
-                .commit_from_memory(&bytes)
-                .map_err(|err| map_ort_error("load ONNX model from memory", err))?
+            })?)
         } else {
-            Session::builder()
-                .map_err(|err| map_ort_error("create session builder", err))?
-                .commit_from_file(&model_path)
-                .map_err(|err| map_ort_error("load ONNX model", err))?
+            None
         };
+        let workers = config.worker_count.max(1);
+        // Parallel sessions share the cores instead of each spawning one thread per core
+        let intra_threads = (workers > 1)
+            .then(|| (std::thread::available_parallelism().map_or(1, |n| n.get()) / workers).max(1));
+        let sessions = (0..workers)
+            .map(|_| build_session(&model_path, model_bytes.as_deref(), intra_threads).map(Mutex::new))
+            .collect::<Result<Vec<_>, _>>()?;
 
         let tokenizer = Tokenizer::from_file(&tokenizer_path)
             .map_err(|err| map_tokenizer_error("load tokenizer", err))?;
@@ -142,13 +188,34 @@ impl OnnxStdIoEmbedder {
 
         Ok(Self {
             info,
-            session: Mutex::new(session),
+            sessions,
+            next_worker: AtomicUsize::new(0),
             tokenizer: Arc::new(tokenizer),
             pad_id,
             max_input_length: config.max_input_length,
+            normalize: config.normalize,
+            pooling: config.pooling,
         })
     }
 
+    /// Whether output vectors are L2-normalized (see `OnnxStdIoConfig::normalize`).
+    pub fn normalizes(&self) -> bool {
+        self.normalize
+    }
+
+    /// Number of tokens the tokenizer produces for `text`, excluding the special tokens
+    /// added around each model input. Falls back to the character count if tokenizing fails.
+    pub fn count_tokens(&self, text: &str) -> usize {
+        count_tokens_with(&self.tokenizer, text)
+    }
+
+    /// Shareable `count_tokens` handle that outlives borrows of the embedder, e.g. for
+    /// token-measured chunking.
+    pub fn token_counter(&self) -> Arc<dyn Fn(&str) -> usize + Send + Sync> {
+        let tokenizer = Arc::clone(&self.tokenizer);
+        Arc::new(move |text: &str| count_tokens_with(&tokenizer, text))
+    }
+
     fn prepare_encodings(&self, texts: &[&str]) -> Result<Vec<Encoding>, EmbedderError> {
         if texts.is_empty() {
             return Ok(Vec::new());
@@ -213,15 +280,24 @@ impl OnnxStdIoEmbedder {
         })
     }
 
+    /// Index of the session to use next, round-robin.
+    fn pick_worker(&self) -> usize {
+        self.next_worker.fetch_add(1, Ordering::Relaxed) % self.sessions.len()
+    }
+
     fn run_session(
         &self,
+        worker: usize,
         input_ids: Tensor<i64>,
         attention_mask: Tensor<i64>,
     ) -> Result<(Vec<f32>, usize, usize, usize), EmbedderError> {
-        let mut session = self.session.lock().unwrap();
+        // A session poisoned by a panic fails like a provider error, so callers can retry elsewhere
+        let mut session = self.sessions[worker].lock().map_err(|_| EmbedderError::ProviderFailure {
+            message: format!("embedder session {worker} is unusable after a panic"),
+        })?;
         let outputs = session
             .run(ort::inputs![input_ids, attention_mask])
-            .map_err(|err| map_ort_error("execute ONNX session", err))?;
+            .map_err(|err| map_run_error("execute ONNX session", err))?;
 
         // Expect exactly one output tensor (index 0)
         let output = &outputs[0];
@@ -246,40 +322,56 @@ impl OnnxStdIoEmbedder {
         Ok((data.to_vec(), batch, seq_len, hidden))
     }
 
-    fn mean_pool(
+    fn pool(
         &self,
         data: &[f32],
         attention_rows: &[Vec<i64>],
         seq_len: usize,
         hidden: usize,
     ) -> Result<Vec<Vec<f32>>, EmbedderError> {
-        let batch = attention_rows.len();
-        let mut results = Vec::with_capacity(batch);
-        for b in 0..batch {
-            let mut sum = vec![0f32; hidden];
-            let mut count = 0f32;
-
-            for t in 0..seq_len {
-                if attention_rows[b][t] == 1 {
-                    let base = (b * seq_len + t) * hidden;
-                    for h in 0..hidden {
-                        sum[h] += data[base + h];
-                    }
-                    count += 1.0;
-                }
-            }
+        let expected = attention_rows.len() * seq_len * hidden;
+        if data.len() < expected {
+            return Err(EmbedderError::ProviderFailure {
+                message: format!("model output has {} values, expected {expected}", data.len()),
+            });
+        }
+        Ok(pool_token_outputs(self.pooling, data, attention_rows, seq_len, hidden))
+    }
+}
 
-            if count > 0.0 {
-                for h in 0..hidden {
-                    sum[h] /= count;
-                }
+/// Reduce `[batch, seq_len, hidden]` token outputs (row-major in `data`) to one vector per
+/// batch row using `kind`; `attention_rows` holds each row's attention mask.
+pub fn pool_token_outputs(
+    kind: PoolingKind,
+    data: &[f32],
+    attention_rows: &[Vec<i64>],
+    seq_len: usize,
+    hidden: usize,
+) -> Vec<Vec<f32>> {
+    let mut results = Vec::with_capacity(attention_rows.len());
+    for (b, mask) in attention_rows.iter().enumerate() {
+        let token = |t: usize| &data[(b * seq_len + t) * hidden..(b * seq_len + t + 1) * hidden];
+        if kind == PoolingKind::Cls {
+            results.push(if seq_len > 0 { token(0).to_vec() } else { vec![0f32; hidden] });
+            continue;
+        }
+        let mut sum = vec![0f32; hidden];
+        let mut count = 0f32;
+        for t in 0..seq_len {
+            if kind == PoolingKind::MeanWithAttentionMask && mask.get(t) != Some(&1) {
+                continue;
             }
-
-            results.push(sum);
+            for (s, x) in sum.iter_mut().zip(token(t)) {
+                *s += x;
+            }
+            count += 1.0;
         }
-
-        Ok(results)
+        if count > 0.0 {
+            sum.iter_mut().for_each(|s| *s /= count);
+        }
+        results.push(sum);
     }
+    results
 }
 
 impl Embedder for OnnxStdIoEmbedder {
@@ -288,7 +380,7 @@ impl Embedder for OnnxStdIoEmbedder {
         let prepared = self.build_input_tensors(&encodings)?;
 
         let (raw_data, batch, seq_len, hidden) =
-            self.run_session(prepared.input_ids, prepared.attention_mask)?;
+            self.run_session(self.pick_worker(), prepared.input_ids, prepared.attention_mask)?;
 
         if batch != 1 {
             return Err(EmbedderError::ProviderFailure {
@@ -296,8 +388,8 @@ impl Embedder for OnnxStdIoEmbedder {
             });
         }
 
-        let pooled = self.mean_pool(&raw_data, &prepared.attention_rows, seq_len, hidden)?;
-        let vector = pooled
+        let pooled = self.pool(&raw_data, &prepared.attention_rows, seq_len, hidden)?;
+        let mut vector = pooled
             .into_iter()
             .next()
             .ok_or_else(|| EmbedderError::ProviderFailure { message: "missing pooled output".into() })?;
@@ -311,20 +403,96 @@ impl Embedder for OnnxStdIoEmbedder {
             });
         }
 
+        if self.normalize {
+            l2_normalize(&mut vector);
+        }
         Ok(vector)
     }
 
     fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedderError> {
+        self.embed_batch_counted(texts).map(|(vectors, _)| vectors)
+    }
+
+    fn info(&self) -> &EmbedderInfo {
+        &self.info
+    }
+}
+
+impl OnnxStdIoEmbedder {
+    /// `embed_batch` that also returns each text's token count (as `count_tokens` reports
+    /// it), taken from the tokenization the embedding already performs.
+    pub fn embed_batch_counted(
+        &self,
+        texts: &[&str],
+    ) -> Result<(Vec<Vec<f32>>, Vec<usize>), EmbedderError> {
         if texts.is_empty() {
-            return Ok(Vec::new());
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let workers = self.sessions.len();
+        let first = self.pick_worker();
+        if workers == 1 || texts.len() == 1 {
+            return self.embed_batch_with_retry(first, texts);
         }
 
+        let part_len = texts.len().div_ceil(workers);
+        let parts: Vec<Result<CountedBatch, EmbedderError>> = std::thread::scope(|s| {
+            let handles: Vec<_> = texts
+                .chunks(part_len)
+                .enumerate()
+                .map(|(i, part)| s.spawn(move || self.embed_batch_with_retry((first + i) % workers, part)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join().unwrap_or_else(|_| {
+                        Err(EmbedderError::ProviderFailure { message: "embedder worker thread panicked".into() })
+                    })
+                })
+                .collect()
+        });
+        let mut vectors = Vec::with_capacity(texts.len());
+        let mut token_counts = Vec::with_capacity(texts.len());
+        for part in parts {
+            let (v, c) = part?;
+            vectors.extend(v);
+            token_counts.extend(c);
+        }
+        Ok((vectors, token_counts))
+    }
+
+    /// Embed `texts` on session `first`, moving on to the following sessions while it fails
+    /// with a provider error. Input errors (e.g. too long) are returned at once.
+    fn embed_batch_with_retry(
+        &self,
+        first: usize,
+        texts: &[&str],
+    ) -> Result<CountedBatch, EmbedderError> {
+        let workers = self.sessions.len();
+        let mut last_err = None;
+        for attempt in 0..workers {
+            match self.embed_batch_on((first + attempt) % workers, texts) {
+                Err(err @ EmbedderError::ProviderFailure { .. }) => last_err = Some(err),
+                res => return res,
+            }
+        }
+        Err(last_err.expect("at least one session"))
+    }
+
+    fn embed_batch_on(
+        &self,
+        worker: usize,
+        texts: &[&str],
+    ) -> Result<CountedBatch, EmbedderError> {
         let encodings = self.prepare_encodings(texts)?;
+        let token_counts: Vec<usize> = encodings
+            .iter()
+            .map(|e| e.get_special_tokens_mask().iter().filter(|&&m| m == 0).count())
+            .collect();
         let prepared = self.build_input_tensors(&encodings)?;
         let expected_seq_len = encodings.iter().map(Encoding::len).max().unwrap_or(0);
 
         let (raw_data, batch, seq_len_from_model, hidden) =
-            self.run_session(prepared.input_ids, prepared.attention_mask)?;
+            self.run_session(worker, prepared.input_ids, prepared.attention_mask)?;
 
         if batch != prepared.attention_rows.len() {
             return Err(EmbedderError::ProviderFailure {
@@ -353,11 +521,12 @@ impl Embedder for OnnxStdIoEmbedder {
             });
         }
 
-        self.mean_pool(&raw_data, &prepared.attention_rows, expected_seq_len, hidden)
-    }
-
-    fn info(&self) -> &EmbedderInfo {
-        &self.info
+        let mut vectors =
+            self.pool(&raw_data, &prepared.attention_rows, expected_seq_len, hidden)?;
+        if self.normalize {
+            vectors.iter_mut().for_each(|v| l2_normalize(v));
+        }
+        Ok((vectors, token_counts))
     }
 }
 
@@ -506,6 +675,14 @@ impl DeterministicEmbedderCore {
     }
 }
 
+/// Scale `vector` to unit L2 norm in place; an all-zero vector is left unchanged.
+pub fn l2_normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
 fn ensure_ort_initialized(runtime_library_path: &Path) -> Result<(), EmbedderError> {
     if let Some(existing) = ORT_RUNTIME_PATH.get() {
         if !paths_equal(existing, runtime_library_path) {
@@ -529,6 +706,27 @@ fn ensure_ort_initialized(runtime_library_path: &Path) -> Result<(), EmbedderErr
     Ok(())
 }
 
+fn build_session(
+    model_path: &Path,
+    model_bytes: Option<&[u8]>,
+    intra_threads: Option<usize>,
+) -> Result<Session, EmbedderError> {
+    let mut builder = Session::builder().map_err(|err| map_ort_error("create session builder", err))?;
+    if let Some(n) = intra_threads {
+        builder = builder
+            .with_intra_threads(n)
+            .map_err(|err| map_ort_error("set session intra-op threads", err))?;
+    }
+    match model_bytes {
+        Some(bytes) => builder
+            .commit_from_memory(bytes)
+            .map_err(|err| map_ort_error("load ONNX model from memory", err)),
+        None => builder
+            .commit_from_file(model_path)
+            .map_err(|err| map_ort_error("load ONNX model", err)),
+    }
+}
+
 fn resolve_existing_path(path: &Path, description: &str) -> Result<PathBuf, EmbedderError> {
     fs::metadata(path).map_err(|_| EmbedderError::InvalidConfiguration {
         message: format!("{description} `{}` does not exist", path.display()),
@@ -549,6 +747,24 @@ fn map_ort_error(context: &str, err: OrtError) -> EmbedderError {
     }
 }
 
+/// Like `map_ort_error`, but reports allocation failures during a run as `ResourceExhausted`.
+fn map_run_error(context: &str, err: OrtError) -> EmbedderError {
+    let message = format!("{context} failed: {err}");
+    let lower = message.to_lowercase();
+    if ["alloc", "out of memory", "insufficient memory", "memory limit"].iter().any(|m| lower.contains(m)) {
+        EmbedderError::ResourceExhausted { message }
+    } else {
+        EmbedderError::ProviderFailure { message }
+    }
+}
+
+fn count_tokens_with(tokenizer: &Tokenizer, text: &str) -> usize {
+    match tokenizer.encode(text, false) {
+        Ok(encoding) => encoding.len(),
+        Err(_) => text.chars().count(),
+    }
+}
+
 fn map_tokenizer_error(context: &str, err: tokenizers::Error) -> EmbedderError {
     EmbedderError::ProviderFailure {
         message: format!("{context} failed: {err}"),
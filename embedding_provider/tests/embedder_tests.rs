@@ -89,6 +89,19 @@ fn enforcing_max_input_length_returns_error() {
     }
 }
 
+#[test]
+fn missing_model_is_fatal_and_resource_exhaustion_is_transient() {
+    let mut config = stdio_config(ONNX_STDIO_DEFAULTS.max_input_tokens);
+    config.model_path = "does/not/exist/model.onnx".into();
+    let err = OnnxStdIoEmbedder::new(config).err().expect("missing model must fail");
+    assert!(matches!(err, EmbedderError::InvalidConfiguration { .. }), "{err:?}");
+    assert!(!err.is_transient());
+
+    let oom = EmbedderError::ResourceExhausted { message: "Failed to allocate memory".into() };
+    assert!(oom.is_transient());
+    assert!(!EmbedderError::ProviderFailure { message: "bad output shape".into() }.is_transient());
+}
+
 #[test]
 fn http_embedder_reports_provider_metadata_and_handles_empty_batch() {
     let config = OnnxHttpConfig {
@@ -113,3 +126,44 @@ fn http_embedder_reports_provider_metadata_and_handles_empty_batch() {
     assert!(batch.is_empty());
 }
 
+
+#[test]
+fn pooling_kinds_reduce_padded_token_outputs_differently() {
+    use embedding_provider::embedder::{pool_token_outputs, PoolingKind};
+
+    // One row, three positions of width 2; the last position is padding
+    let data = [1.0, 2.0, 3.0, 4.0, 8.0, 8.0];
+    let mask = vec![vec![1, 1, 0]];
+    let pool = |kind| pool_token_outputs(kind, &data, &mask, 3, 2).remove(0);
+
+    assert_eq!(pool(PoolingKind::Cls), vec![1.0, 2.0]);
+    assert_eq!(pool(PoolingKind::MeanWithAttentionMask), vec![2.0, 3.0]);
+    assert_eq!(pool(PoolingKind::Mean), vec![4.0, 14.0 / 3.0]);
+    assert_eq!(PoolingKind::default(), PoolingKind::MeanWithAttentionMask);
+}
+
+#[test]
+fn worker_sessions_return_batch_vectors_in_input_order() {
+    let single = OnnxStdIoEmbedder::new(stdio_config(ONNX_STDIO_DEFAULTS.max_input_tokens))
+        .expect("configuration is valid and model loads");
+    let mut config = stdio_config(ONNX_STDIO_DEFAULTS.max_input_tokens);
+    config.worker_count = 3;
+    let pooled = OnnxStdIoEmbedder::new(config).expect("worker sessions load");
+
+    let inputs = [
+        "tide tables",
+        "ferries pause when fog rolls in",
+        "buoys mark the channel",
+        "harbour cranes unload timber at dawn",
+        "pilot boats",
+    ];
+    let (expected, expected_counts) = single.embed_batch_counted(&inputs).expect("single session batch");
+    for _ in 0..2 {
+        let (vectors, counts) = pooled.embed_batch_counted(&inputs).expect("parallel batch");
+        assert_eq!(counts, expected_counts);
+        assert_eq!(vectors.len(), expected.len());
+        for (lhs, rhs) in vectors.iter().zip(&expected) {
+            assert_vectors_close(lhs, rhs);
+        }
+    }
+}
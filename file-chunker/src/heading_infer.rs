@@ -0,0 +1,88 @@
+//! Heading inference for plain text: promote lines with section numbering ("第1章", "1.1",
+//! "(a)") to `Heading` blocks so section grouping and `section_path` work without markup.
+
+use crate::unified_blocks::{BlockKind, UnifiedBlock};
+use regex::Regex;
+
+/// A numbering pattern; lines matching `regex` become headings of `level`.
+#[derive(Debug, Clone)]
+pub struct HeadingPattern {
+    pub regex: Regex,
+    pub level: u8,
+}
+
+impl HeadingPattern {
+    pub fn new(pattern: &str, level: u8) -> Result<Self, regex::Error> {
+        Ok(Self { regex: Regex::new(pattern)?, level })
+    }
+}
+
+/// Settings for `infer_headings`.
+#[derive(Debug, Clone)]
+pub struct HeadingInference {
+    /// Tried in order against each trimmed line; the first match decides the level.
+    pub patterns: Vec<HeadingPattern>,
+    /// Lines longer than this (in chars) stay body text even when they start with a number.
+    pub max_chars: usize,
+}
+
+impl Default for HeadingInference {
+    fn default() -> Self { Self { patterns: default_heading_patterns(), max_chars: 40 } }
+}
+
+/// Common Japanese and Latin numbering: 第N章 (1), 第N節 (2), "1." (1), "1.1" (2),
+/// "1.1.1" (3) and "(a)"/"(1)"/"（１）" (4).
+pub fn default_heading_patterns() -> Vec<HeadingPattern> {
+    const KANJI_NUM: &str = "[0-9０-９一二三四五六七八九十百千〇]+";
+    [
+        (format!("^第{KANJI_NUM}章"), 1),
+        (format!("^第{KANJI_NUM}節"), 2),
+        (r"^[0-9]+\.[0-9]+\.[0-9]+\.?(\s|$)".to_string(), 3),
+        (r"^[0-9]+\.[0-9]+\.?(\s|$)".to_string(), 2),
+        (r"^[0-9]+[.．](\s|$)".to_string(), 1),
+        (r"^[(（]([a-zａ-ｚ]|[0-9０-９]+)[)）]".to_string(), 4),
+    ]
+    .into_iter()
+    .map(|(p, level)| HeadingPattern::new(&p, level).expect("built-in heading pattern compiles"))
+    .collect()
+}
+
+/// Heading level of `line` under `cfg`, if it looks like a numbered heading: short, matching
+/// a pattern and not ending like a sentence.
+pub fn heading_level(line: &str, cfg: &HeadingInference) -> Option<u8> {
+    let line = line.trim();
+    if line.is_empty() || line.chars().count() > cfg.max_chars { return None; }
+    if line.ends_with(['。', '.', '．', '!', '?', '！', '？']) { return None; }
+    cfg.patterns.iter().find(|p| p.regex.is_match(line)).map(|p| p.level)
+}
+
+/// Split paragraph blocks at inferred heading lines, emitting each such line as a `Heading`
+/// block with its level. Other blocks pass through; reading order is renumbered.
+pub fn infer_headings(blocks: Vec<UnifiedBlock>, cfg: &HeadingInference) -> Vec<UnifiedBlock> {
+    let mut out: Vec<UnifiedBlock> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        if block.kind != BlockKind::Paragraph || !block.text.lines().any(|l| heading_level(l, cfg).is_some()) {
+            out.push(block);
+            continue;
+        }
+        let mut body: Vec<&str> = Vec::new();
+        let flush = |body: &mut Vec<&str>, out: &mut Vec<UnifiedBlock>| {
+            let text = body.join("\n");
+            body.clear();
+            if text.trim().is_empty() { return; }
+            out.push(UnifiedBlock { text: text.trim().to_string(), ..block.clone() });
+        };
+        for line in block.text.lines() {
+            match heading_level(line, cfg) {
+                Some(level) => {
+                    flush(&mut body, &mut out);
+                    out.push(UnifiedBlock { text: line.trim().to_string(), kind: BlockKind::Heading, heading_level: Some(level), ..block.clone() });
+                }
+                None => body.push(line),
+            }
+        }
+        flush(&mut body, &mut out);
+    }
+    for (i, b) in out.iter_mut().enumerate() { b.order = i as u32; }
+    out
+}
@@ -0,0 +1,19 @@
+/// Cheap script-based language guess for a chunk of text.
+/// Returns "ja" when kana/kanji are present in meaningful amounts, "en" for mostly Latin text,
+/// and None when the text has no letters to judge by.
+pub fn detect_lang(text: &str) -> Option<&'static str> {
+    let mut cjk = 0usize;
+    let mut latin = 0usize;
+    for ch in text.chars() {
+        match ch {
+            // Hiragana, Katakana, half-width Katakana, CJK ideographs (incl. ext. A)
+            '\u{3040}'..='\u{30FF}' | '\u{FF66}'..='\u{FF9F}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' => cjk += 1,
+            'a'..='z' | 'A'..='Z' => latin += 1,
+            _ => {}
+        }
+    }
+    if cjk == 0 && latin == 0 { return None; }
+    // One CJK char carries roughly a word; Latin words average ~5 letters. Japanese prose
+    // often embeds acronyms/code, so weight CJK generously.
+    if cjk * 5 >= latin { Some("ja") } else { Some("en") }
+}
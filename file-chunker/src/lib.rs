@@ -3,9 +3,15 @@ pub mod reader_docx;
 pub mod reader_txt;
 pub mod reader_excel;
 pub mod reader_pptx;
+pub mod reader_html;
 pub mod unified_blocks;
 pub mod chunker_rules_jp;
 pub mod text_segmenter;
+pub mod lang_detect;
+pub mod quality;
+pub mod page_image;
+pub mod heading_infer;
+pub mod scan;
 #[cfg(feature = "pdfium")] pub mod reader_pdf_pdfium;
 #[cfg(feature = "pure-pdf")] pub mod reader_pdf_pure;
 pub mod pdf_chunker;
@@ -26,44 +32,161 @@ pub struct ChunkOutput {
     pub chunks: Vec<ChunkRecord>,
 }
 
+/// Per-block text cleanup applied after reading and before segmentation.
+pub type TextPostprocess = std::sync::Arc<dyn Fn(BlockKind, &str) -> String + Send + Sync>;
+
+/// How doc and chunk ids are derived. Chunk ids are always `<doc_id>#<seq>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum IdScheme {
+    /// The file path is the doc id. Moving or renaming the file changes every id.
+    #[default]
+    Path,
+    /// `sha256-<hex>` of the file content is the doc id, so ids survive moves and renames
+    /// and stay the same across re-ingests of unchanged content.
+    ContentHash,
+    /// A caller-chosen stable doc id.
+    DocId(String),
+}
+
 /// Unified options for chunking behavior.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ChunkOptions {
     /// Encoding hint for text-like files (e.g., "shift_jis"). None means auto/UTF-8.
     pub encoding: Option<String>,
     /// Optional text segmentation parameters override.
     pub params: Option<text_segmenter::TextChunkParams>,
+    /// Treat the file as this MIME type (e.g., "application/pdf") regardless of its extension.
+    pub force_mime: Option<String>,
+    /// Source-specific cleanup of each block's text (e.g., dropping a vendor watermark),
+    /// given the block kind so it can target only `Header`/`Footer`. None is identity.
+    /// Blocks left empty by it are dropped.
+    pub text_postprocess: Option<TextPostprocess>,
+    /// For text-like files, promote numbered lines ("第1章", "1.1", "(a)") to headings so
+    /// chunks get grouped by section and carry a `section_path`. None leaves text as-is.
+    pub infer_headings: Option<heading_infer::HeadingInference>,
+    /// How doc and chunk ids are derived (path-based by default).
+    pub id_scheme: IdScheme,
 }
 
 impl Default for ChunkOptions {
-    fn default() -> Self { Self { encoding: None, params: None } }
+    fn default() -> Self { Self { encoding: None, params: None, force_mime: None, text_postprocess: None, infer_headings: None, id_scheme: IdScheme::Path } }
+}
+
+impl std::fmt::Debug for ChunkOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkOptions")
+            .field("encoding", &self.encoding)
+            .field("params", &self.params)
+            .field("force_mime", &self.force_mime)
+            .field("text_postprocess", &self.text_postprocess.as_ref().map(|_| "Fn"))
+            .field("infer_headings", &self.infer_headings)
+            .field("id_scheme", &self.id_scheme)
+            .finish()
+    }
+}
+
+/// Split oversized tables (see `text_segmenter::split_table_blocks`), then apply
+/// `opts.text_postprocess` to every block.
+fn postprocess_blocks(blocks: Vec<UnifiedBlock>, opts: &ChunkOptions) -> Vec<UnifiedBlock> {
+    let params = opts.params.clone().unwrap_or_default();
+    let mut blocks = text_segmenter::split_table_blocks(blocks, &params);
+    let Some(f) = &opts.text_postprocess else { return blocks };
+    blocks.retain_mut(|b| {
+        if b.text.is_empty() { return true; }
+        b.text = f(b.kind.clone(), &b.text);
+        !b.text.is_empty()
+    });
+    blocks
 }
 
+/// `ChunkOptions::force_mime` names a type no reader handles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedMime(pub String);
+
+impl std::fmt::Display for UnsupportedMime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unsupported MIME type: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedMime {}
+
 /// Unified entry to chunk a file with optional encoding and segmentation params.
-pub fn chunk_file_with_file_record_with_options(path: &str, opts: &ChunkOptions) -> ChunkOutput {
-    let lower = path.to_lowercase();
+/// Fails only when `opts.force_mime` is set to a type no reader handles.
+pub fn chunk_file_with_file_record_with_options(path: &str, opts: &ChunkOptions) -> Result<ChunkOutput, UnsupportedMime> {
+    let forced_ext = match opts.force_mime.as_deref() {
+        Some(mime) => Some(ext_for_mime(mime).ok_or_else(|| UnsupportedMime(mime.to_string()))?),
+        None => None,
+    };
+    Ok(chunk_file_with_ext(path, opts, forced_ext))
+}
+
+fn chunk_file_with_ext(path: &str, opts: &ChunkOptions, forced_ext: Option<&str>) -> ChunkOutput {
+    let mut out = chunk_file_by_kind(path, opts, forced_ext);
+    apply_id_scheme(&mut out, path, &opts.id_scheme);
+    out
+}
+
+/// Re-key `out` from path-based ids to the ids of `scheme`.
+fn apply_id_scheme(out: &mut ChunkOutput, path: &str, scheme: &IdScheme) {
+    let doc_id = match scheme {
+        IdScheme::Path => return,
+        IdScheme::ContentHash => {
+            let Some(hex) = out.file.content_sha256.clone().or_else(|| compute_sha256_hex(path)) else { return };
+            out.file.content_sha256 = Some(hex.clone());
+            content_hash_doc_id(&hex)
+        }
+        IdScheme::DocId(id) => id.clone(),
+    };
+    assign_doc_id(&mut out.file, &mut out.chunks, &doc_id);
+}
+
+/// Doc id of `IdScheme::ContentHash` for a file whose SHA-256 (lowercase hex) is `hex`.
+pub fn content_hash_doc_id(hex: &str) -> String {
+    format!("sha256-{hex}")
+}
+
+/// Move `file` and `chunks` to `doc_id`. Chunk ids become `<doc_id>#<seq>`, using the chunk's
+/// position for records without a `seq`, so ids stay put when chunks are dropped later.
+pub fn assign_doc_id(file: &mut FileRecord, chunks: &mut [ChunkRecord], doc_id: &str) {
+    for (i, c) in chunks.iter_mut().enumerate() {
+        c.doc_id = DocumentId(doc_id.to_string());
+        c.chunk_id = ChunkId(format!("{}#{}", doc_id, c.seq.unwrap_or(i as u32)));
+    }
+    file.doc_id = DocumentId(doc_id.to_string());
+}
+
+fn chunk_file_by_kind(path: &str, opts: &ChunkOptions, forced_ext: Option<&str>) -> ChunkOutput {
+    // Dispatch on a forced MIME's canonical extension when given, else on the path
+    let lower = match forced_ext { Some(ext) => ext.to_string(), None => path.to_lowercase() };
 
     // PDF
     if lower.ends_with(".pdf") {
-        if let Some(p) = opts.params {
-            let blocks: Vec<UnifiedBlock> = reader_pdf::read_pdf_to_blocks(path);
-            let segs = pdf_chunker::chunk_pdf_blocks_to_segments_with_text_params(&blocks, &p);
+        if let Some(p) = &opts.params {
+            let blocks: Vec<UnifiedBlock> = postprocess_blocks(reader_pdf::read_pdf_to_blocks(path), opts);
+            let segs = pdf_chunker::chunk_pdf_blocks_to_segments_with_text_params(&blocks, p);
+            let paths = text_segmenter::segment_section_paths(&blocks, &segs, &mut Vec::new());
             let page_count = segs.iter().filter_map(|(_, _ps, pe)| *pe).max();
+            let kinds = text_segmenter::segment_block_kinds(&blocks, &segs);
             let chunks: Vec<ChunkRecord> = segs
                 .into_iter()
+                .zip(kinds)
+                .zip(paths)
                 .enumerate()
-                .map(|(i, (text, ps, pe))| ChunkRecord {
+                .map(|(i, (((text, ps, pe), block_kinds), section_path))| ChunkRecord {
                     schema_version: chunk_model::SCHEMA_MAJOR,
                     doc_id: DocumentId(path.to_string()),
                     chunk_id: ChunkId(format!("{}#{}", path, i)),
+                    seq: Some(i as u32),
                     source_uri: path.to_string(),
                     source_mime: "application/pdf".into(),
                     extracted_at: String::new(),
                     page_start: ps,
                     page_end: pe,
                     text,
-                    section_path: None,
+                    section_path: Some(section_path),
                     meta: BTreeMap::new(),
+                    block_kinds,
                     extra: BTreeMap::new(),
                 })
                 .collect();
@@ -97,38 +220,46 @@ pub fn chunk_file_with_file_record_with_options(path: &str, opts: &ChunkOptions)
             return ChunkOutput { file, chunks };
         } else {
             let params = pdf_chunker::PdfChunkParams::default();
-            let (file, chunks) = pdf_chunker::chunk_pdf_file_with_file_record(path, &params);
+            let blocks = postprocess_blocks(reader_pdf::read_pdf_to_blocks(path), opts);
+            let (file, chunks) = pdf_chunker::chunk_pdf_blocks_with_file_record(path, &blocks, &params);
             return ChunkOutput { file, chunks };
         }
     }
 
     // DOCX (derive cut levels dynamically)
     if lower.ends_with(".docx") {
-        let blocks: Vec<UnifiedBlock> = reader_docx::read_docx_to_blocks(path);
-        let params = opts.params.unwrap_or_else(text_segmenter::TextChunkParams::default);
+        let blocks: Vec<UnifiedBlock> = postprocess_blocks(reader_docx::read_docx_to_blocks(path), opts);
+        let params = opts.params.clone().unwrap_or_default();
         let levels = derive_docx_cut_levels(&blocks);
-        let segs = if levels.is_empty() {
-            text_segmenter::chunk_blocks_to_segments(&blocks, &params)
+        let (segs, paths) = if levels.is_empty() {
+            let segs = text_segmenter::chunk_blocks_to_segments(&blocks, &params);
+            let paths = text_segmenter::segment_section_paths(&blocks, &segs, &mut Vec::new());
+            (segs, paths)
         } else {
             let pair = if levels.len() >= 2 { Some((levels[0], levels[1])) } else { None };
             chunk_blocks_grouped_by_levels(&blocks, &params, &levels, pair)
         };
         let page_count = segs.iter().filter_map(|(_, _ps, pe)| *pe).max();
+        let kinds = text_segmenter::segment_block_kinds(&blocks, &segs);
         let chunks: Vec<ChunkRecord> = segs
             .into_iter()
+            .zip(kinds)
+            .zip(paths)
             .enumerate()
-            .map(|(i, (text, ps, pe))| ChunkRecord {
+            .map(|(i, (((text, ps, pe), block_kinds), section_path))| ChunkRecord {
                 schema_version: chunk_model::SCHEMA_MAJOR,
                 doc_id: DocumentId(path.to_string()),
                 chunk_id: ChunkId(format!("{}#{}", path, i)),
+                seq: Some(i as u32),
                 source_uri: path.to_string(),
                 source_mime: "application/vnd.openxmlformats-officedocument.wordprocessingml.document".into(),
                 extracted_at: String::new(),
                 page_start: ps,
                 page_end: pe,
                 text,
-                section_path: None,
+                section_path: Some(section_path),
                 meta: BTreeMap::new(),
+                block_kinds,
                 extra: BTreeMap::new(),
             })
             .collect();
@@ -164,25 +295,30 @@ pub fn chunk_file_with_file_record_with_options(path: &str, opts: &ChunkOptions)
 
     // PPTX (slides as H1 boundaries; tables honored)
     if lower.ends_with(".pptx") {
-        let blocks: Vec<UnifiedBlock> = reader_pptx::read_pptx_to_blocks(path);
-        let params = opts.params.unwrap_or_else(text_segmenter::TextChunkParams::default);
-        let segs = chunk_blocks_grouped_by_h1(&blocks, &params);
+        let blocks: Vec<UnifiedBlock> = postprocess_blocks(reader_pptx::read_pptx_to_blocks(path), opts);
+        let params = opts.params.clone().unwrap_or_default();
+        let (segs, paths) = chunk_blocks_grouped_by_h1(&blocks, &params);
         let page_count = segs.iter().filter_map(|(_, _ps, pe)| *pe).max();
+        let kinds = text_segmenter::segment_block_kinds(&blocks, &segs);
         let chunks: Vec<ChunkRecord> = segs
             .into_iter()
+            .zip(kinds)
+            .zip(paths)
             .enumerate()
-            .map(|(i, (text, ps, pe))| ChunkRecord {
+            .map(|(i, (((text, ps, pe), block_kinds), section_path))| ChunkRecord {
                 schema_version: chunk_model::SCHEMA_MAJOR,
                 doc_id: DocumentId(path.to_string()),
                 chunk_id: ChunkId(format!("{}#{}", path, i)),
+                seq: Some(i as u32),
                 source_uri: path.to_string(),
                 source_mime: "application/vnd.openxmlformats-officedocument.presentationml.presentation".into(),
                 extracted_at: String::new(),
                 page_start: ps,
                 page_end: pe,
                 text,
-                section_path: None,
+                section_path: Some(section_path),
                 meta: BTreeMap::new(),
+                block_kinds,
                 extra: BTreeMap::new(),
             })
             .collect();
@@ -218,9 +354,9 @@ pub fn chunk_file_with_file_record_with_options(path: &str, opts: &ChunkOptions)
     }
     // Excel
     if lower.ends_with(".xlsx") || lower.ends_with(".xls") || lower.ends_with(".ods") {
-        let blocks: Vec<UnifiedBlock> = reader_excel::read_excel_to_blocks(path);
-        let params = opts.params.unwrap_or_else(text_segmenter::TextChunkParams::default);
-        let segs = chunk_blocks_grouped_by_h1(&blocks, &params);
+        let blocks: Vec<UnifiedBlock> = postprocess_blocks(reader_excel::read_excel_to_blocks(path), opts);
+        let params = opts.params.clone().unwrap_or_default();
+        let (segs, paths) = chunk_blocks_grouped_by_h1(&blocks, &params);
         let page_count = segs.iter().filter_map(|(_, _ps, pe)| *pe).max();
 
         let src_mime = if lower.ends_with(".xlsx") {
@@ -229,21 +365,26 @@ pub fn chunk_file_with_file_record_with_options(path: &str, opts: &ChunkOptions)
             "application/vnd.ms-excel"
         } else { "application/vnd.oasis.opendocument.spreadsheet" };
 
+        let kinds = text_segmenter::segment_block_kinds(&blocks, &segs);
         let chunks: Vec<ChunkRecord> = segs
             .into_iter()
+            .zip(kinds)
+            .zip(paths)
             .enumerate()
-            .map(|(i, (text, ps, pe))| ChunkRecord {
+            .map(|(i, (((text, ps, pe), block_kinds), section_path))| ChunkRecord {
                 schema_version: chunk_model::SCHEMA_MAJOR,
                 doc_id: DocumentId(path.to_string()),
                 chunk_id: ChunkId(format!("{}#{}", path, i)),
+                seq: Some(i as u32),
                 source_uri: path.to_string(),
                 source_mime: src_mime.into(),
                 extracted_at: String::new(),
                 page_start: ps,
                 page_end: pe,
                 text,
-                section_path: None,
+                section_path: Some(section_path),
                 meta: BTreeMap::new(),
+                block_kinds,
                 extra: BTreeMap::new(),
             })
             .collect();
@@ -278,29 +419,114 @@ pub fn chunk_file_with_file_record_with_options(path: &str, opts: &ChunkOptions)
         return ChunkOutput { file, chunks };
     }
 
+    // HTML (headings drive cut levels like DOCX)
+    if lower.ends_with(".html") || lower.ends_with(".htm") || lower.ends_with(".xhtml") {
+        let blocks: Vec<UnifiedBlock> = postprocess_blocks(reader_html::read_html_to_blocks(path), opts);
+        let params = opts.params.clone().unwrap_or_default();
+        let levels = derive_docx_cut_levels(&blocks);
+        let (segs, paths) = if levels.is_empty() {
+            let segs = text_segmenter::chunk_blocks_to_segments(&blocks, &params);
+            let paths = text_segmenter::segment_section_paths(&blocks, &segs, &mut Vec::new());
+            (segs, paths)
+        } else {
+            let pair = if levels.len() >= 2 { Some((levels[0], levels[1])) } else { None };
+            chunk_blocks_grouped_by_levels(&blocks, &params, &levels, pair)
+        };
+        let kinds = text_segmenter::segment_block_kinds(&blocks, &segs);
+        let chunks: Vec<ChunkRecord> = segs
+            .into_iter()
+            .zip(kinds)
+            .zip(paths)
+            .enumerate()
+            .map(|(i, (((text, _ps, _pe), block_kinds), section_path))| ChunkRecord {
+                schema_version: chunk_model::SCHEMA_MAJOR,
+                doc_id: DocumentId(path.to_string()),
+                chunk_id: ChunkId(format!("{}#{}", path, i)),
+                seq: Some(i as u32),
+                source_uri: path.to_string(),
+                source_mime: "text/html".into(),
+                extracted_at: String::new(),
+                page_start: Some(1),
+                page_end: Some(1),
+                text,
+                section_path: Some(section_path),
+                meta: BTreeMap::new(),
+                block_kinds,
+                extra: BTreeMap::new(),
+            })
+            .collect();
+        let mut file = FileRecord {
+            schema_version: chunk_model::SCHEMA_MAJOR,
+            doc_id: DocumentId(path.to_string()),
+            doc_revision: Some(1),
+            source_uri: path.to_string(),
+            source_mime: "text/html".into(),
+            file_size_bytes: None,
+            content_sha256: None,
+            page_count: Some(1),
+            extracted_at: String::new(),
+            created_at_meta: None,
+            updated_at_meta: None,
+            title_guess: None,
+            author_guess: None,
+            dominant_lang: None,
+            tags: Vec::new(),
+            ingest_tool: Some("file-chunker".into()),
+            ingest_tool_version: Some(env!("CARGO_PKG_VERSION").into()),
+            reader_backend: Some("html".into()),
+            ocr_used: None,
+            ocr_langs: Vec::new(),
+            chunk_count: Some(chunks.len() as u32),
+            total_tokens: None,
+            meta: BTreeMap::new(),
+            extra: BTreeMap::new(),
+        };
+        enrich_file_record_basic(&mut file, path);
+        return ChunkOutput { file, chunks };
+    }
+
     // Text-like
-    if is_text_like(path) {
+    if forced_ext == Some(".txt") || (forced_ext.is_none() && is_text_like(path)) {
         let blocks: Vec<UnifiedBlock> = match &opts.encoding {
             Some(enc) => reader_txt::read_txt_to_blocks_with_encoding(path, Some(enc.as_str())),
             None => reader_txt::read_txt_to_blocks(path),
         };
-        let params = opts.params.unwrap_or_else(text_segmenter::TextChunkParams::default);
-        let segs = text_segmenter::chunk_blocks_to_segments(&blocks, &params);
+        let blocks = postprocess_blocks(blocks, opts);
+        let blocks = match &opts.infer_headings {
+            Some(cfg) => heading_infer::infer_headings(blocks, cfg),
+            None => blocks,
+        };
+        let params = opts.params.clone().unwrap_or_default();
+        // Cut at inferred chapter/section headings like DOCX; no headings means no levels
+        let levels = derive_docx_cut_levels(&blocks);
+        let (segs, paths) = if levels.is_empty() {
+            let segs = text_segmenter::chunk_blocks_to_segments(&blocks, &params);
+            let paths = text_segmenter::segment_section_paths(&blocks, &segs, &mut Vec::new());
+            (segs, paths)
+        } else {
+            let pair = if levels.len() >= 2 { Some((levels[0], levels[1])) } else { None };
+            chunk_blocks_grouped_by_levels(&blocks, &params, &levels, pair)
+        };
+        let kinds = text_segmenter::segment_block_kinds(&blocks, &segs);
         let chunks: Vec<ChunkRecord> = segs
             .into_iter()
+            .zip(kinds)
+            .zip(paths)
             .enumerate()
-            .map(|(i, (text, _ps, _pe))| ChunkRecord {
+            .map(|(i, (((text, _ps, _pe), block_kinds), section_path))| ChunkRecord {
                 schema_version: chunk_model::SCHEMA_MAJOR,
                 doc_id: DocumentId(path.to_string()),
                 chunk_id: ChunkId(format!("{}#{}", path, i)),
+                seq: Some(i as u32),
                 source_uri: path.to_string(),
                 source_mime: "text/plain".into(),
                 extracted_at: String::new(),
                 page_start: Some(1),
                 page_end: Some(1),
                 text,
-                section_path: None,
+                section_path: Some(section_path),
                 meta: BTreeMap::new(),
+                block_kinds,
                 extra: BTreeMap::new(),
             })
             .collect();
@@ -344,14 +570,16 @@ pub fn chunk_file_with_file_record_with_options(path: &str, opts: &ChunkOptions)
             schema_version: chunk_model::SCHEMA_MAJOR,
             doc_id: DocumentId(path.to_string()),
             chunk_id: ChunkId(format!("{}#{}", path, i)),
+            seq: Some(i as u32),
             source_uri: path.to_string(),
             source_mime: "text/plain".into(),
             extracted_at: String::new(),
             page_start: None,
             page_end: None,
             text,
-            section_path: None,
+            section_path: Some(Vec::new()),
             meta: BTreeMap::new(),
+            block_kinds: Vec::new(),
             extra: BTreeMap::new(),
         })
         .collect();
@@ -389,14 +617,14 @@ pub fn chunk_file_with_file_record_with_options(path: &str, opts: &ChunkOptions)
 /// High-level entry to chunk a file by path and return file/chunks.
 /// This is a stubbed pipeline that returns a simple chunking for now.
 pub fn chunk_file_with_file_record(path: &str) -> ChunkOutput {
-    chunk_file_with_file_record_with_options(path, &ChunkOptions::default())
+    chunk_file_with_ext(path, &ChunkOptions::default(), None)
 }
 
 /// Variant with an explicit encoding hint for text-like files.
 /// For non-text formats (PDF/DOCX), the behavior is identical to `chunk_file_with_file_record`.
 pub fn chunk_file_with_file_record_with_encoding(path: &str, encoding: Option<&str>) -> ChunkOutput {
-    let opts = ChunkOptions { encoding: encoding.map(|s| s.to_string()), params: None };
-    chunk_file_with_file_record_with_options(path, &opts)
+    let opts = ChunkOptions { encoding: encoding.map(|s| s.to_string()), params: None, force_mime: None, text_postprocess: None, infer_headings: None, id_scheme: IdScheme::Path };
+    chunk_file_with_ext(path, &opts, None)
 }
 
 /// Unified entry with explicit TextChunkParams and optional encoding for text-like files.
@@ -405,8 +633,8 @@ pub fn chunk_file_with_file_record_with_params(
     encoding: Option<&str>,
     params: &text_segmenter::TextChunkParams,
 ) -> ChunkOutput {
-    let opts = ChunkOptions { encoding: encoding.map(|s| s.to_string()), params: Some(*params) };
-    chunk_file_with_file_record_with_options(path, &opts)
+    let opts = ChunkOptions { encoding: encoding.map(|s| s.to_string()), params: Some(params.clone()), force_mime: None, text_postprocess: None, infer_headings: None, id_scheme: IdScheme::Path };
+    chunk_file_with_ext(path, &opts, None)
 }
 
 /// Legacy helper returning only chunks for backward compatibility.
@@ -414,6 +642,22 @@ pub fn chunk_file(path: &str) -> Vec<ChunkRecord> {
     chunk_file_with_file_record(path).chunks
 }
 
+/// Map a MIME type to the extension the dispatcher uses for it.
+fn ext_for_mime(mime: &str) -> Option<&'static str> {
+    let m = mime.trim().to_ascii_lowercase();
+    match m.as_str() {
+        "application/pdf" => Some(".pdf"),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => Some(".docx"),
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation" => Some(".pptx"),
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => Some(".xlsx"),
+        "application/vnd.ms-excel" => Some(".xls"),
+        "application/vnd.oasis.opendocument.spreadsheet" => Some(".ods"),
+        "text/html" | "application/xhtml+xml" => Some(".html"),
+        _ if m.starts_with("text/") => Some(".txt"),
+        _ => None,
+    }
+}
+
 fn is_text_like(path: &str) -> bool {
     let lower = path.to_lowercase();
     // Common text-ish extensions
@@ -438,19 +682,25 @@ fn is_text_like(path: &str) -> bool {
     false
 }
 
+/// A segment's text with its page range, as produced by `text_segmenter`.
+type Segment = (String, Option<u32>, Option<u32>);
+
 /// Split blocks on top-level heading (Heading with level==1) and apply the generic text segmenter per group.
 /// This enforces that no chunk crosses a top-level heading boundary. If no such headings exist, the
-/// entire block list is treated as a single group.
+/// entire block list is treated as a single group. Also returns each segment's heading path.
 fn chunk_blocks_grouped_by_h1(
     blocks: &[UnifiedBlock],
     params: &text_segmenter::TextChunkParams,
-) -> Vec<(String, Option<u32>, Option<u32>)> {
-    let mut out: Vec<(String, Option<u32>, Option<u32>)> = Vec::new();
+) -> (Vec<Segment>, Vec<Vec<String>>) {
+    let mut out: Vec<Segment> = Vec::new();
+    let mut paths: Vec<Vec<String>> = Vec::new();
+    let mut stack: Vec<(u8, String)> = Vec::new();
     let mut cur: Vec<UnifiedBlock> = Vec::new();
     for b in blocks.iter() {
         let is_h1 = matches!(b.kind, BlockKind::Heading) && matches!(b.heading_level, Some(1));
         if is_h1 && !cur.is_empty() {
             let mut segs = text_segmenter::chunk_blocks_to_segments(&cur, params);
+            paths.append(&mut text_segmenter::segment_section_paths(&cur, &segs, &mut stack));
             out.append(&mut segs);
             cur.clear();
         }
@@ -458,9 +708,10 @@ fn chunk_blocks_grouped_by_h1(
     }
     if !cur.is_empty() {
         let mut segs = text_segmenter::chunk_blocks_to_segments(&cur, params);
+        paths.append(&mut text_segmenter::segment_section_paths(&cur, &segs, &mut stack));
         out.append(&mut segs);
     }
-    out
+    (out, paths)
 }
 
 /// Determine DOCX cut levels dynamically:
@@ -488,13 +739,16 @@ fn derive_docx_cut_levels(blocks: &[UnifiedBlock]) -> Vec<u8> {
 /// Split blocks when encountering a Heading whose level is in `cut_levels`.
 /// When `skip_h1_h2_gap` is true, do not cut between a newly-started H1 and an immediately-following H2
 /// (keeps H1 and its first H2 together at the start of the group).
+/// Also returns each segment's heading path; the heading stack carries across groups.
 fn chunk_blocks_grouped_by_levels(
     blocks: &[UnifiedBlock],
     params: &text_segmenter::TextChunkParams,
     cut_levels: &[u8],
     no_cut_pair: Option<(u8, u8)>,
-) -> Vec<(String, Option<u32>, Option<u32>)> {
-    let mut out: Vec<(String, Option<u32>, Option<u32>)> = Vec::new();
+) -> (Vec<Segment>, Vec<Vec<String>>) {
+    let mut out: Vec<Segment> = Vec::new();
+    let mut paths: Vec<Vec<String>> = Vec::new();
+    let mut stack: Vec<(u8, String)> = Vec::new();
     let mut cur: Vec<UnifiedBlock> = Vec::new();
     for b in blocks.iter() {
         let is_heading = matches!(b.kind, BlockKind::Heading);
@@ -516,6 +770,7 @@ fn chunk_blocks_grouped_by_levels(
             }
             if need_flush {
                 let mut segs = text_segmenter::chunk_blocks_to_segments(&cur, params);
+                paths.append(&mut text_segmenter::segment_section_paths(&cur, &segs, &mut stack));
                 out.append(&mut segs);
                 cur.clear();
             }
@@ -526,9 +781,10 @@ fn chunk_blocks_grouped_by_levels(
     }
     if !cur.is_empty() {
         let mut segs = text_segmenter::chunk_blocks_to_segments(&cur, params);
+        paths.append(&mut text_segmenter::segment_section_paths(&cur, &segs, &mut stack));
         out.append(&mut segs);
     }
-    out
+    (out, paths)
 }
 
 // --- Metadata enrichment helpers --------------------------------------------------------------
@@ -0,0 +1,13 @@
+//! Page-image rendering for "show the source page" views.
+
+/// Renders document pages (1-based) to PNG images.
+pub trait PageRenderer: Send + Sync {
+    /// Render `pages` of the document at `path` in order, handing each PNG to `on_page`.
+    /// Stops early, without error, once `on_page` returns false.
+    fn render_pages(
+        &self,
+        path: &str,
+        pages: &[u32],
+        on_page: &mut dyn FnMut(u32, Vec<u8>) -> bool,
+    ) -> Result<(), String>;
+}
@@ -33,6 +33,9 @@ fn chunk_pdf_blocks_to_segments(
         penalize_short_line: true,
         penalize_page_boundary_no_newline: true,
         short_merge_min_chars: 100,
+        keep_code_blocks: true,
+        overlap_chars: 0,
+        token_counter: None,
     };
     crate::text_segmenter::chunk_blocks_to_segments(blocks, &tparams)
 }
@@ -57,7 +60,12 @@ pub fn chunk_pdf_blocks_to_segments_with_text_params(
 /// High-level: read PDF -> chunk -> return FileRecord and ChunkRecords
 pub fn chunk_pdf_file_with_file_record(path: &str, params: &PdfChunkParams) -> (FileRecord, Vec<ChunkRecord>) {
     let blocks = read_pdf_to_blocks(path);
-    let segs = chunk_pdf_blocks_to_segments(&blocks, params);
+    chunk_pdf_blocks_with_file_record(path, &blocks, params)
+}
+
+/// Like `chunk_pdf_file_with_file_record`, for blocks already read from `path`.
+pub fn chunk_pdf_blocks_with_file_record(path: &str, blocks: &[UnifiedBlock], params: &PdfChunkParams) -> (FileRecord, Vec<ChunkRecord>) {
+    let segs = chunk_pdf_blocks_to_segments(blocks, params);
 
     let backend = match default_backend() {
         PdfBackend::Pdfium => "pdfium",
@@ -98,21 +106,27 @@ pub fn chunk_pdf_file_with_file_record(path: &str, params: &PdfChunkParams) -> (
     // Basic FS metadata + SHA256
     enrich_file_record_basic(&mut file, path);
 
+    let kinds = crate::text_segmenter::segment_block_kinds(blocks, &segs);
+    let paths = crate::text_segmenter::segment_section_paths(blocks, &segs, &mut Vec::new());
     let chunks: Vec<ChunkRecord> = segs
         .into_iter()
+        .zip(kinds)
+        .zip(paths)
         .enumerate()
-        .map(|(i, (text, pstart, pend))| ChunkRecord {
+        .map(|(i, (((text, pstart, pend), block_kinds), section_path))| ChunkRecord {
             schema_version: SCHEMA_MAJOR,
             doc_id: DocumentId(path.to_string()),
             chunk_id: ChunkId(format!("{}#{}", path, i)),
+            seq: Some(i as u32),
             source_uri: path.to_string(),
             source_mime: "application/pdf".into(),
             extracted_at: String::new(),
             page_start: pstart,
             page_end: pend,
             text,
-            section_path: None,
+            section_path: Some(section_path),
             meta: BTreeMap::new(),
+            block_kinds,
             extra: BTreeMap::new(),
         })
         .collect();
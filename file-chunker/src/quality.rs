@@ -0,0 +1,18 @@
+/// Ratio of distinct tokens to total tokens in `text` (0.0..=1.0).
+/// Tokens are whitespace-separated words; text with fewer than 3 words (e.g., Japanese
+/// without spaces or a long separator run) falls back to non-whitespace characters.
+/// Returns None when the text has no tokens at all.
+pub fn unique_token_ratio(text: &str) -> Option<f32> {
+    use std::collections::HashSet;
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let (unique, total) = if words.len() >= 3 {
+        let set: HashSet<String> = words.iter().map(|w| w.to_lowercase()).collect();
+        (set.len(), words.len())
+    } else {
+        let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let set: HashSet<char> = chars.iter().copied().collect();
+        (set.len(), chars.len())
+    };
+    if total == 0 { return None; }
+    Some(unique as f32 / total as f32)
+}
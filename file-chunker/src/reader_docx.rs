@@ -464,13 +464,13 @@ pub fn read_docx_to_blocks(path: &str) -> Vec<UnifiedBlock> {
     let mut in_p = false;
     let mut para_start_page: u32 = 1;
 
-    // Table extraction state: join cells with '\t' and rows with '\n'
+    // Table extraction state: cells per row, rows per table
     let mut in_tbl = false;
     let mut in_tr = false;
     let mut in_tc = false;
     let mut cell_text = String::new();
     let mut row_cells: Vec<String> = Vec::new();
-    let mut table_text = String::new();
+    let mut table_rows: Vec<Vec<String>> = Vec::new();
     let mut table_start_page: u32 = 1;
 
     loop {
@@ -483,7 +483,7 @@ pub fn read_docx_to_blocks(path: &str) -> Vec<UnifiedBlock> {
                             in_p = true; cur_text.clear(); pending_heading_level = None; para_start_page = current_page; current_style_id = None;
                         }
                     }
-                    b"tbl" => { in_tbl = true; table_text.clear(); table_start_page = current_page; }
+                    b"tbl" => { in_tbl = true; table_rows.clear(); table_start_page = current_page; }
                     b"tr" => { if in_tbl { in_tr = true; row_cells.clear(); } }
                     b"tc" => { if in_tr { in_tc = true; cell_text.clear(); } }
                     b"numPr" => { in_numpr = true; pending_num_id=None; pending_ilvl=None; }
@@ -618,34 +618,23 @@ pub fn read_docx_to_blocks(path: &str) -> Vec<UnifiedBlock> {
                     b"tr" => {
                         if in_tr {
                             in_tr = false;
-                            // finalize a row => join cells with tabs, add newline
-                            let line = row_cells.join("\t");
-                            table_text.push_str(&line);
-                            table_text.push('\n');
-                            row_cells.clear();
+                            table_rows.push(std::mem::take(&mut row_cells));
                         }
                     }
                     b"tbl" => {
                         if in_tbl {
                             in_tbl = false;
-                            // flush accumulated table text as a single block with HTML-like wrapper
-                            if !table_text.is_empty() {
-                                let mut wrapped = String::new();
+                            // flush the accumulated rows as a single table block
+                            if !table_rows.is_empty() {
                                 // Add a leading newline only if previous block does not end with one
                                 let need_leading_nl = blocks.last().map_or(false, |prev| !prev.text.ends_with('\n'));
-                                if need_leading_nl { wrapped.push('\n'); }
-                                let content = table_text.trim_end_matches('\n');
-                                wrapped.push_str(&format!("<table delim=\"tsv\" cell-nl=\"U+2028\">\n{}\n</table>\n", content));
-                                let mut b = UnifiedBlock::new(BlockKind::Paragraph, wrapped, order, path, "docx");
+                                let mut b = UnifiedBlock::table(std::mem::take(&mut table_rows), order, path, "docx");
+                                if need_leading_nl { b.text.insert(0, '\n'); }
                                 b.page_start = Some(table_start_page);
                                 b.page_end = Some(current_page);
-                                // optional hint that this is a table and the cell newline codepoint
-                                b.attrs.insert("is_table".to_string(), "true".to_string());
-                                b.attrs.insert("table_cell_nl".to_string(), "U+2028".to_string());
                                 blocks.push(b);
                                 order += 1;
                             }
-                            table_text.clear();
                         }
                     }
                     _ => {}
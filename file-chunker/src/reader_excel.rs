@@ -4,7 +4,7 @@ use calamine::Reader; // brings sheet_names/worksheet_range into scope
 /// Minimal Excel reader using `calamine` to convert sheets and rows into `UnifiedBlock`s.
 /// Formats: XLSX / XLS / ODS (auto-detected by calamine).
 /// - Inserts a Heading block per sheet (page = sheet index starting at 1)
-/// - Emits Paragraph blocks per row with tab-separated cell values
+/// - Emits one Table block per sheet (first non-empty row as header), rendered as TSV
 /// - Trims trailing empty cells per row and skips fully empty lines
 pub fn read_excel_to_blocks(path: &str) -> Vec<UnifiedBlock> {
     let mut blocks: Vec<UnifiedBlock> = Vec::new();
@@ -51,6 +51,7 @@ pub fn read_excel_to_blocks(path: &str) -> Vec<UnifiedBlock> {
             None => 0,
         };
 
+        let mut rows: Vec<Vec<String>> = Vec::new();
         for row in range.rows() {
             // Convert cells to strings and trim trailing empties
             let mut cells: Vec<String> = Vec::with_capacity(leading_empty_cols + row.len());
@@ -60,15 +61,16 @@ pub fn read_excel_to_blocks(path: &str) -> Vec<UnifiedBlock> {
             cells.extend(row.iter().map(cell_to_string));
             while let Some(last) = cells.last() { if last.trim().is_empty() { cells.pop(); } else { break; } }
             if cells.is_empty() { continue; }
-            let line = cells.join("\t");
-            // Keep leading tabs to preserve empty leading columns; only skip if all cells are empty
+            // Keep leading empty cells to preserve empty leading columns; only skip if all cells are empty
             let has_nonempty = cells.iter().any(|s| !s.trim().is_empty());
             if !has_nonempty { continue; }
-            // Append a newline so each row ends with an explicit line break in the concatenated text.
-            let mut p = UnifiedBlock::new(BlockKind::Paragraph, format!("{}\n", line), order, path, "excel");
-            p.page_start = Some(page);
-            p.page_end = Some(page);
-            blocks.push(p);
+            rows.push(cells);
+        }
+        if !rows.is_empty() {
+            let mut t = UnifiedBlock::table(rows, order, path, "excel");
+            t.page_start = Some(page);
+            t.page_end = Some(page);
+            blocks.push(t);
             order += 1;
         }
     }
@@ -0,0 +1,392 @@
+//! HTML reader: a lenient tag scanner (no DOM) mapping common elements to `UnifiedBlock`s.
+//! - `<h1>`..`<h6>` -> Heading with level; `<p>`, `<div>` and other containers -> Paragraph
+//! - `<li>` -> ListItem (ordered/nesting level from the enclosing `<ol>`/`<ul>`)
+//! - `<pre>` -> Code (whitespace kept)
+//! - `<table>` -> one Table block (rows in `table_rows`, text rendered as for DOCX/PPTX)
+//! - `<figcaption>`, or the `alt` of an `<img>` -> FigureCaption (`img_src` attr when known)
+//! - `<script>`, `<style>`, `<noscript>`, `<template>` and `<head>` content are dropped
+//! - `<a href>` ranges become `LinkRef`s (char offsets into the block text)
+
+use std::fs;
+
+use crate::unified_blocks::{BlockKind, LinkRef, ListInfo, UnifiedBlock};
+
+/// Read an HTML file (UTF-8, lossily decoded) into blocks.
+pub fn read_html_to_blocks(path: &str) -> Vec<UnifiedBlock> {
+    let bytes = match fs::read(path) {
+        Ok(b) => b,
+        Err(_) => return vec![UnifiedBlock::new(BlockKind::Paragraph, "(error) failed to read .html file", 0, path, "html")],
+    };
+    html_to_blocks(&String::from_utf8_lossy(&bytes), path)
+}
+
+/// Parse an HTML string into blocks; `origin` is recorded as the blocks' source.
+pub fn html_to_blocks(html: &str, origin: &str) -> Vec<UnifiedBlock> {
+    let mut r = HtmlBlocks::new(origin);
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            r.text(rest);
+            break;
+        };
+        r.text(&rest[..lt]);
+        rest = &rest[lt..];
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.find("-->").map_or("", |i| &after[i + 3..]);
+            continue;
+        }
+        let Some(tag) = parse_tag(rest) else {
+            // A lone '<' is text
+            r.text("<");
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[tag.len..];
+        if tag.name.starts_with('!') || tag.name.starts_with('?') { continue; }
+        if !tag.closing && matches!(tag.name.as_str(), "script" | "style" | "noscript" | "template") {
+            rest = skip_raw_text(rest, &tag.name);
+            continue;
+        }
+        r.tag(&tag);
+    }
+    r.finish()
+}
+
+struct Tag {
+    name: String,
+    closing: bool,
+    attrs: Vec<(String, String)>,
+    /// Bytes consumed, including `<` and `>`.
+    len: usize,
+}
+
+impl Tag {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Parse the tag at the start of `s` (which begins with `<`).
+fn parse_tag(s: &str) -> Option<Tag> {
+    let bytes = s.as_bytes();
+    let mut i = 1;
+    let closing = bytes.get(i) == Some(&b'/');
+    if closing { i += 1; }
+    let name_start = i;
+    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' && bytes[i] != b'/' { i += 1; }
+    let name = s[name_start..i].to_ascii_lowercase();
+    if name.is_empty() || !(name.as_bytes()[0].is_ascii_alphabetic() || name.starts_with('!') || name.starts_with('?')) {
+        return None;
+    }
+    let mut attrs = Vec::new();
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') { i += 1; }
+        if i >= bytes.len() { return None; }
+        if bytes[i] == b'>' { return Some(Tag { name, closing, attrs, len: i + 1 }); }
+        let key_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'=' | b'>' | b'/') { i += 1; }
+        let key = s[key_start..i].to_ascii_lowercase();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() { i += 1; }
+        let mut value = String::new();
+        if bytes.get(i) == Some(&b'=') {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() { i += 1; }
+            match bytes.get(i) {
+                Some(&q) if q == b'"' || q == b'\'' => {
+                    let end = s[i + 1..].find(q as char)? + i + 1;
+                    value = decode_entities(&s[i + 1..end]);
+                    i = end + 1;
+                }
+                _ => {
+                    let v_start = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' { i += 1; }
+                    value = decode_entities(&s[v_start..i]);
+                }
+            }
+        }
+        if !key.is_empty() { attrs.push((key, value)); }
+    }
+}
+
+/// Skip past the closing tag of a raw-text element (script/style), case-insensitively.
+fn skip_raw_text<'a>(s: &'a str, name: &str) -> &'a str {
+    let needle = format!("</{name}");
+    let lower = s.to_ascii_lowercase();
+    match lower.find(&needle) {
+        Some(i) => s[i..].find('>').map_or("", |j| &s[i + j + 1..]),
+        None => "",
+    }
+}
+
+/// Decode the common named entities and numeric character references.
+pub fn decode_entities(s: &str) -> String {
+    if !s.contains('&') { return s.to_string(); }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..].find(';').filter(|&n| n <= 10).and_then(|n| {
+            let ent = &rest[1..1 + n];
+            let ch = match ent {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => ent
+                    .strip_prefix("#x")
+                    .or_else(|| ent.strip_prefix("#X"))
+                    .and_then(|h| u32::from_str_radix(h, 16).ok())
+                    .or_else(|| ent.strip_prefix('#').and_then(|d| d.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            ch.map(|c| (c, n + 2))
+        });
+        match decoded {
+            Some((c, used)) => {
+                out.push(c);
+                rest = &rest[used..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Block under construction plus the element context needed to classify it.
+struct HtmlBlocks<'o> {
+    origin: &'o str,
+    out: Vec<UnifiedBlock>,
+    order: u32,
+    text: String,
+    kind: BlockKind,
+    heading_level: Option<u8>,
+    list: Option<ListInfo>,
+    links: Vec<LinkRef>,
+    open_link: Option<(u32, String)>,
+    /// `true` per open `<ol>`, `false` per `<ul>`.
+    lists: Vec<bool>,
+    head: bool,
+    pre: usize,
+    table_depth: usize,
+    rows: Vec<Vec<String>>,
+    cell: Option<String>,
+    figure: Option<Figure>,
+}
+
+struct Figure {
+    alt: Option<String>,
+    src: Option<String>,
+    captioned: bool,
+}
+
+impl<'o> HtmlBlocks<'o> {
+    fn new(origin: &'o str) -> Self {
+        Self {
+            origin,
+            out: Vec::new(),
+            order: 0,
+            text: String::new(),
+            kind: BlockKind::Paragraph,
+            heading_level: None,
+            list: None,
+            links: Vec::new(),
+            open_link: None,
+            lists: Vec::new(),
+            head: false,
+            pre: 0,
+            table_depth: 0,
+            rows: Vec::new(),
+            cell: None,
+            figure: None,
+        }
+    }
+
+    fn text(&mut self, raw: &str) {
+        if self.head || raw.is_empty() { return; }
+        let decoded = decode_entities(raw);
+        if self.table_depth > 0 {
+            if let Some(cell) = self.cell.as_mut() { push_collapsed(cell, &decoded); }
+            return;
+        }
+        if self.pre > 0 { self.text.push_str(&decoded); } else { push_collapsed(&mut self.text, &decoded); }
+    }
+
+    fn tag(&mut self, tag: &Tag) {
+        let name = tag.name.as_str();
+        if name == "head" { self.head = !tag.closing; return; }
+        if self.head { return; }
+        if self.table_depth > 0 && !matches!(name, "table" | "tr" | "td" | "th") {
+            if name == "br" { if let Some(c) = self.cell.as_mut() { c.push(' '); } }
+            return;
+        }
+        match (name, tag.closing) {
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                self.flush();
+                self.kind = BlockKind::Heading;
+                self.heading_level = name[1..].parse().ok();
+            }
+            ("li", false) => {
+                self.flush();
+                self.kind = BlockKind::ListItem;
+                let ordered = self.lists.last().copied().unwrap_or(false);
+                self.list = Some(ListInfo { ordered, level: self.lists.len().max(1) as u8, marker: None });
+            }
+            ("ol" | "ul", false) => {
+                self.flush();
+                self.lists.push(name == "ol");
+            }
+            ("ol" | "ul", true) => {
+                self.flush();
+                self.lists.pop();
+            }
+            ("pre", false) => {
+                self.flush();
+                self.kind = BlockKind::Code;
+                self.pre += 1;
+            }
+            ("pre", true) => {
+                self.flush();
+                self.pre = self.pre.saturating_sub(1);
+            }
+            ("br", _) => self.text.push('\n'),
+            ("a", false) => {
+                if let Some(href) = tag.attr("href").filter(|h| !h.is_empty()) {
+                    self.open_link = Some((self.text.chars().count() as u32, href.to_string()));
+                }
+            }
+            ("a", true) => {
+                if let Some((start, uri)) = self.open_link.take() {
+                    let end = self.text.chars().count() as u32;
+                    if end > start { self.links.push(LinkRef { start, end, uri }); }
+                }
+            }
+            ("table", false) => {
+                if self.table_depth == 0 { self.flush(); self.rows.clear(); }
+                self.table_depth += 1;
+            }
+            ("table", true) => {
+                if self.table_depth == 1 { self.end_cell(); self.emit_table(); }
+                self.table_depth = self.table_depth.saturating_sub(1);
+            }
+            ("figure", false) => {
+                self.flush();
+                self.figure = Some(Figure { alt: None, src: None, captioned: false });
+            }
+            ("figure", true) => {
+                self.flush();
+                if let Some(fig) = self.figure.take() {
+                    if let (false, Some(alt)) = (fig.captioned, fig.alt) { self.emit_caption(alt, fig.src); }
+                }
+            }
+            ("figcaption", false) => {
+                self.flush();
+                self.kind = BlockKind::FigureCaption;
+                if let Some(fig) = self.figure.as_mut() { fig.captioned = true; }
+            }
+            ("img", _) => {
+                let alt = tag.attr("alt").map(str::trim).filter(|a| !a.is_empty()).map(str::to_string);
+                let src = tag.attr("src").map(str::to_string);
+                match self.figure.as_mut() {
+                    Some(fig) => {
+                        if fig.alt.is_none() { fig.alt = alt; }
+                        if fig.src.is_none() { fig.src = src; }
+                    }
+                    None => {
+                        if let Some(alt) = alt {
+                            self.flush();
+                            self.emit_caption(alt, src);
+                        }
+                    }
+                }
+            }
+            ("p" | "div" | "section" | "article" | "main" | "header" | "footer" | "nav" | "aside" | "blockquote"
+                | "dl" | "dt" | "dd" | "body" | "html" | "form" | "fieldset" | "address" | "hr", _)
+            | ("h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "li" | "figcaption", true) => self.flush(),
+            _ => {}
+        }
+        if self.table_depth > 0 {
+            match (name, tag.closing) {
+                ("tr", false) => { self.end_cell(); self.rows.push(Vec::new()); }
+                ("tr", true) | ("td" | "th", true) => self.end_cell(),
+                ("td" | "th", false) => { self.end_cell(); self.cell = Some(String::new()); }
+                _ => {}
+            }
+        }
+    }
+
+    fn end_cell(&mut self) {
+        if self.table_depth > 1 { return; }
+        if let Some(cell) = self.cell.take() {
+            if self.rows.is_empty() { self.rows.push(Vec::new()); }
+            if let Some(row) = self.rows.last_mut() { row.push(cell.trim().replace('\t', " ")); }
+        }
+    }
+
+    fn emit_table(&mut self) {
+        let rows: Vec<Vec<String>> = self.rows.drain(..).filter(|r| !r.is_empty()).collect();
+        if rows.is_empty() { return; }
+        self.out.push(UnifiedBlock::table(rows, self.order, self.origin, "html"));
+        self.order += 1;
+    }
+
+    fn emit_caption(&mut self, text: String, src: Option<String>) {
+        let mut b = UnifiedBlock::new(BlockKind::FigureCaption, text, self.order, self.origin, "html");
+        if let Some(src) = src { b.attrs.insert("img_src".to_string(), src); }
+        self.out.push(b);
+        self.order += 1;
+    }
+
+    /// Emit the pending text as a block of the current kind and reset to a paragraph.
+    fn flush(&mut self) {
+        let raw = std::mem::take(&mut self.text);
+        let kind = std::mem::replace(&mut self.kind, BlockKind::Paragraph);
+        let heading_level = self.heading_level.take();
+        let list = self.list.take();
+        let mut links = std::mem::take(&mut self.links);
+        self.open_link = None;
+        let text = if kind == BlockKind::Code { raw.trim_matches('\n').to_string() } else { raw.trim().to_string() };
+        if text.is_empty() { return; }
+        // Shift link offsets by the trimmed prefix and clip them to the text
+        let lead = if kind == BlockKind::Code { raw.len() - raw.trim_start_matches('\n').len() } else { raw.len() - raw.trim_start().len() };
+        let lead = raw[..lead].chars().count() as u32;
+        let len = text.chars().count() as u32;
+        for l in &mut links {
+            l.start = l.start.saturating_sub(lead).min(len);
+            l.end = l.end.saturating_sub(lead).min(len);
+        }
+        links.retain(|l| l.end > l.start);
+        let mut b = UnifiedBlock::new(kind, text, self.order, self.origin, "html");
+        b.heading_level = heading_level;
+        b.list = list;
+        b.links = links;
+        self.out.push(b);
+        self.order += 1;
+    }
+
+    fn finish(mut self) -> Vec<UnifiedBlock> {
+        if self.table_depth > 0 { self.end_cell(); self.emit_table(); }
+        self.flush();
+        if self.out.is_empty() {
+            self.out.push(UnifiedBlock::new(BlockKind::Paragraph, String::new(), 0, self.origin, "html"));
+        }
+        self.out
+    }
+}
+
+/// Append `s` with whitespace runs collapsed to one space (also across the join).
+fn push_collapsed(buf: &mut String, s: &str) {
+    for ch in s.chars() {
+        if ch.is_whitespace() && ch != '\u{a0}' {
+            if !buf.is_empty() && !buf.ends_with([' ', '\n']) { buf.push(' '); }
+        } else {
+            buf.push(ch);
+        }
+    }
+}
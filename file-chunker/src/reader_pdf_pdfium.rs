@@ -2,6 +2,7 @@
 
 #![cfg(feature = "pdfium")]
 
+use crate::page_image::PageRenderer;
 use crate::unified_blocks::{UnifiedBlock, BlockKind};
 use pdfium_render::prelude::*;
 use std::path::PathBuf;
@@ -54,6 +55,60 @@ fn bind_pdfium_from_bundle() -> Option<Box<dyn PdfiumLibraryBindings>> {
     None
 }
 
+fn bind_pdfium() -> Result<Box<dyn PdfiumLibraryBindings>, PdfiumError> {
+    if let Some(b) = bind_pdfium_from_env() { return Ok(b); }
+    if let Some(b) = bind_pdfium_from_bundle() { return Ok(b); }
+    Pdfium::bind_to_system_library()
+}
+
+/// `PageRenderer` backed by PDFium. Pages are scaled to `target_width` pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdfiumPageRenderer {
+    pub target_width: u16,
+}
+
+impl Default for PdfiumPageRenderer {
+    fn default() -> Self { Self { target_width: 1000 } }
+}
+
+impl PageRenderer for PdfiumPageRenderer {
+    fn render_pages(
+        &self,
+        path: &str,
+        pages: &[u32],
+        on_page: &mut dyn FnMut(u32, Vec<u8>) -> bool,
+    ) -> Result<(), String> {
+        let bindings = bind_pdfium().map_err(|e| format!("[pdfium] failed to bind: {}", e))?;
+        let pdfium = Pdfium::new(bindings);
+        let document = pdfium
+            .load_pdf_from_file(path, None)
+            .map_err(|e| format!("[pdfium] failed to open PDF: {}", e))?;
+        let config = PdfRenderConfig::new().set_target_width(self.target_width as Pixels);
+        for &page_num in pages {
+            let index = page_num.checked_sub(1).and_then(|i| PdfPageIndex::try_from(i).ok())
+                .ok_or_else(|| format!("[pdfium] invalid page number {}", page_num))?;
+            let page = document.pages().get(index)
+                .map_err(|e| format!("[pdfium] page {}: {}", page_num, e))?;
+            let bitmap = page.render_with_config(&config)
+                .map_err(|e| format!("[pdfium] failed to render page {}: {}", page_num, e))?;
+            let png = encode_png(bitmap.width() as u32, bitmap.height() as u32, &bitmap.as_rgba_bytes())?;
+            if !on_page(page_num, png) { break; }
+        }
+        Ok(())
+    }
+}
+
+fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(rgba).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PdfStructureMode {
     /// Return a single paragraph block per page after normalization.
@@ -70,22 +125,16 @@ pub fn read_pdf_to_blocks_pdfium(path: &str) -> Vec<UnifiedBlock> {
 
 pub fn read_pdf_to_blocks_pdfium_with_mode(path: &str, mode: PdfStructureMode) -> Vec<UnifiedBlock> {
     // Try env override 竊・bundled under file-chunker/bin 竊・system library
-    let bindings = if let Some(b) = bind_pdfium_from_env() {
-        b
-    } else if let Some(b) = bind_pdfium_from_bundle() {
-        b
-    } else {
-        match Pdfium::bind_to_system_library() {
-            Ok(b) => b,
-            Err(err) => {
-                return vec![UnifiedBlock::new(
-                    BlockKind::Paragraph,
-                    format!("[pdfium] failed to bind: {}", err),
-                    0,
-                    path,
-                    "pdfium",
-                )];
-            }
+    let bindings = match bind_pdfium() {
+        Ok(b) => b,
+        Err(err) => {
+            return vec![UnifiedBlock::new(
+                BlockKind::Paragraph,
+                format!("[pdfium] failed to bind: {}", err),
+                0,
+                path,
+                "pdfium",
+            )];
         }
     };
 
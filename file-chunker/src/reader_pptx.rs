@@ -35,7 +35,7 @@ fn attr_val_q(e: &BytesStart<'_>, qname: &[u8]) -> Option<String> {
 /// Read PPTX and convert slides to UnifiedBlocks.
 /// - Adds a Heading per slide ("Slide: <title>\n" or fallback "Slide N\n") as level 1
 /// - Paragraph blocks for text paragraphs
-/// - Tables as TSV wrapped with <table delim="tsv" cell-nl="U+2028">...\n</table>\n and is_table attr
+/// - Tables as Table blocks (TSV wrapped with <table delim="tsv" cell-nl="U+2028">...\n</table>\n)
 pub fn read_pptx_to_blocks(path: &str) -> Vec<UnifiedBlock> {
     let mut blocks: Vec<UnifiedBlock> = Vec::new();
     let file = match File::open(path) { Ok(f) => f, Err(_) => return vec![UnifiedBlock::new(BlockKind::Paragraph, "(error) failed to open PPTX", 0, path, "pptx")] };
@@ -131,7 +131,7 @@ pub fn read_pptx_to_blocks(path: &str) -> Vec<UnifiedBlock> {
         let mut slide_title: Option<String> = None;
 
         let mut in_tbl = false; let mut in_tr = false; let mut in_tc = false;
-        let mut cell_text = String::new(); let mut row_cells: Vec<String> = Vec::new(); let mut table_text = String::new();
+        let mut cell_text = String::new(); let mut row_cells: Vec<String> = Vec::new(); let mut table_rows: Vec<Vec<String>> = Vec::new();
 
         // Position state (EMU) — kept but unused after revert
         let mut _in_sp_xfrm = false; let mut _cur_x: i64 = 0; let mut _cur_y: i64 = 0; let mut _cur_cy: i64 = 0;
@@ -168,7 +168,7 @@ pub fn read_pptx_to_blocks(path: &str) -> Vec<UnifiedBlock> {
                             if _in_gf_xfrm { if let Some(v) = attr_val(&e, b"cy") { if let Ok(n)=v.parse::<i64>(){ _tbl_cy=n; } } }
                         }
                         b"graphicFrame" => { _in_gf = true; _tbl_x=0; _tbl_y=0; _tbl_cy=0; }
-                        b"tbl" => { in_tbl = true; table_text.clear(); row_cells.clear(); }
+                        b"tbl" => { in_tbl = true; table_rows.clear(); row_cells.clear(); }
                         b"tr" => { if in_tbl { in_tr = true; row_cells.clear(); } }
                         b"tc" => { if in_tr { in_tc = true; cell_text.clear(); } }
                         _ => {}
@@ -239,27 +239,19 @@ pub fn read_pptx_to_blocks(path: &str) -> Vec<UnifiedBlock> {
                         b"tr" => {
                             if in_tr {
                                 in_tr = false;
-                                let line = row_cells.join("\t");
-                                table_text.push_str(&line); table_text.push('\n');
-                                row_cells.clear();
+                                table_rows.push(std::mem::take(&mut row_cells));
                             }
                         }
                         b"tbl" => {
                             if in_tbl {
                                 in_tbl = false;
-                                if !table_text.is_empty() {
-                                    let content = table_text.trim_end_matches('\n');
-                                    let mut wrapped = String::new();
+                                if !table_rows.is_empty() {
                                     let need_leading_nl = blocks.last().map_or(false, |prev| !prev.text.ends_with('\n'));
-                                    if need_leading_nl { wrapped.push('\n'); }
-                                    wrapped.push_str(&format!("<table delim=\"tsv\" cell-nl=\"U+2028\">\n{}\n</table>\n", content));
-                                    let mut b = UnifiedBlock::new(BlockKind::Paragraph, wrapped, order, path, "pptx");
+                                    let mut b = UnifiedBlock::table(std::mem::take(&mut table_rows), order, path, "pptx");
+                                    if need_leading_nl { b.text.insert(0, '\n'); }
                                     b.page_start = Some(slide_num); b.page_end = Some(slide_num);
-                                    b.attrs.insert("is_table".to_string(), "true".to_string());
-                                    b.attrs.insert("table_cell_nl".to_string(), "U+2028".to_string());
                                     blocks.push(b); order += 1;
                                 }
-                                table_text.clear();
                             }
                         }
                         _ => {}
@@ -0,0 +1,94 @@
+//! Folder scanning for ingest: a depth-limited walk with extension filtering, and
+//! detection of files whose content is already registered (size first, then SHA-256).
+
+use std::collections::HashSet;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use sha2::Digest;
+
+/// A regular file found by `scan_dir`/`walk_dir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// Whether `path` passes an extension filter. `exts` are compared case-insensitively as
+/// path suffixes after a dot (so `tar.gz` works); a leading dot is ignored. Empty or `*`
+/// accepts every file.
+pub fn ext_matches(path: &Path, exts: &[&str]) -> bool {
+    let exts: Vec<String> = exts.iter().map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase()).filter(|e| !e.is_empty()).collect();
+    if exts.is_empty() || exts.iter().any(|e| e == "*") { return true; }
+    let lower = path.to_string_lossy().to_ascii_lowercase();
+    exts.iter().any(|e| lower.ends_with(&format!(".{e}")))
+}
+
+/// Walk `root` down to `max_depth` directory levels (0 = only `root` itself), calling `visit`
+/// for each file passing `exts` (see `ext_matches`). With `sorted`, entries are visited in
+/// path order, depth first; otherwise in directory order. Unreadable entries are skipped.
+/// `visit` returning `Break` stops the walk.
+pub fn walk_dir(
+    root: &Path,
+    exts: &[&str],
+    max_depth: usize,
+    sorted: bool,
+    mut visit: impl FnMut(ScannedFile) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let mut stack: Vec<(PathBuf, usize)> = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = stack.pop() {
+        let Ok(rd) = std::fs::read_dir(&dir) else { continue };
+        let mut entries: Vec<std::fs::DirEntry> = rd.flatten().collect();
+        if sorted { entries.sort_by_key(|e| e.path()); }
+        let mut subdirs: Vec<(PathBuf, usize)> = Vec::new();
+        for entry in entries {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else { continue };
+            if meta.is_dir() {
+                if depth < max_depth { subdirs.push((path, depth + 1)); }
+            } else if meta.is_file() && ext_matches(&path, exts) {
+                visit(ScannedFile { path, size: meta.len(), modified: meta.modified().ok() })?;
+            }
+        }
+        // Pushed reversed so subdirectories pop in ascending order
+        if sorted { subdirs.reverse(); }
+        stack.extend(subdirs);
+    }
+    ControlFlow::Continue(())
+}
+
+/// Every file under `root` passing `exts`, down to `max_depth` levels, sorted by path.
+pub fn scan_dir(root: &Path, exts: &[&str], max_depth: usize) -> Vec<ScannedFile> {
+    let mut out = Vec::new();
+    let _ = walk_dir(root, exts, max_depth, true, |f| { out.push(f); ControlFlow::Continue(()) });
+    out.sort_by(|a, b| a.path.cmp(&b.path));
+    out
+}
+
+/// SHA-256 (lowercase hex) of a file's content, read in blocks.
+pub fn sha256_hex_file(path: &Path) -> std::io::Result<String> {
+    let mut f = std::fs::File::open(path)?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 { break; }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// True unless a known file has the same size and SHA-256. Only files whose size matches a
+/// known one are hashed; unreadable files count as unregistered.
+pub fn is_unregistered(file: &ScannedFile, known_hashes: &HashSet<String>, known_sizes: &HashSet<u64>) -> bool {
+    if !known_sizes.contains(&file.size) { return true; }
+    !sha256_hex_file(&file.path).is_ok_and(|h| known_hashes.contains(&h))
+}
+
+/// Keep the scanned files whose content is not among the known ones (see `is_unregistered`).
+pub fn filter_unregistered(scanned: Vec<ScannedFile>, known_hashes: &HashSet<String>, known_sizes: &HashSet<u64>) -> Vec<ScannedFile> {
+    scanned.into_iter().filter(|f| is_unregistered(f, known_hashes, known_sizes)).collect()
+}
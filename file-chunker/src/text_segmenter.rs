@@ -1,6 +1,10 @@
-use crate::unified_blocks::UnifiedBlock;
+use crate::unified_blocks::{render_table_tsv, BlockKind, UnifiedBlock};
+use std::sync::Arc;
 
-#[derive(Debug, Clone, Copy)]
+/// Counts the tokens of a text, e.g. with the embedder's tokenizer.
+pub type TokenCounter = Arc<dyn Fn(&str) -> usize + Send + Sync>;
+
+#[derive(Clone)]
 pub struct TextChunkParams {
     pub min_chars: usize,
     pub max_chars: usize,
@@ -12,11 +16,105 @@ pub struct TextChunkParams {
     /// Merge a too-short trailing segment (<= this many chars) into the previous one
     /// when the previous won't exceed cap_chars.
     pub short_merge_min_chars: usize,
+    /// Keep `BlockKind::Code` blocks in one segment when they fit under cap_chars;
+    /// larger code blocks are split on line boundaries only.
+    pub keep_code_blocks: bool,
+    /// Prefix every segment after the first with up to this many trailing chars of the
+    /// previous one (joined by a newline), so text straddling a cut appears in both. The
+    /// overlap counts toward `cap_chars` and shrinks when the segment leaves less room.
+    /// 0 disables it.
+    pub overlap_chars: usize,
+    /// When set, every size above (`min_chars`, `max_chars`, `cap_chars`,
+    /// `short_merge_min_chars`, `overlap_chars`) is measured in tokens counted by this
+    /// function instead of characters. None keeps the character-based sizes.
+    pub token_counter: Option<TokenCounter>,
 }
 
 impl Default for TextChunkParams {
     fn default() -> Self {
-        Self { min_chars: 400, max_chars: 600, cap_chars: 800, penalize_short_line: true, penalize_page_boundary_no_newline: true, short_merge_min_chars: 100 }
+        Self { min_chars: 400, max_chars: 600, cap_chars: 800, penalize_short_line: true, penalize_page_boundary_no_newline: true, short_merge_min_chars: 100, keep_code_blocks: true, overlap_chars: 0, token_counter: None }
+    }
+}
+
+impl std::fmt::Debug for TextChunkParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TextChunkParams")
+            .field("min_chars", &self.min_chars)
+            .field("max_chars", &self.max_chars)
+            .field("cap_chars", &self.cap_chars)
+            .field("penalize_short_line", &self.penalize_short_line)
+            .field("penalize_page_boundary_no_newline", &self.penalize_page_boundary_no_newline)
+            .field("short_merge_min_chars", &self.short_merge_min_chars)
+            .field("keep_code_blocks", &self.keep_code_blocks)
+            .field("overlap_chars", &self.overlap_chars)
+            .field("token_counter", &self.token_counter.as_ref().map(|_| "Fn"))
+            .finish()
+    }
+}
+
+impl TextChunkParams {
+    /// Token-measured params whose chunks stay within `budget` tokens of `counter`
+    /// (aiming for 1/2 to 3/4 of it), e.g. 480 for a 512-token model.
+    pub fn for_token_budget(counter: TokenCounter, budget: usize) -> Self {
+        Self {
+            min_chars: budget / 2,
+            max_chars: budget * 3 / 4,
+            cap_chars: budget,
+            short_merge_min_chars: budget / 8,
+            token_counter: Some(counter),
+            ..Default::default()
+        }
+    }
+
+    /// Length of `text` in the unit of the size fields (tokens or characters).
+    fn measure(&self, text: &str) -> usize {
+        match &self.token_counter {
+            Some(count) => count(text),
+            None => text.chars().count(),
+        }
+    }
+}
+
+/// Token positions over the concatenated text: cumulative counts at the cut candidates,
+/// measured piece by piece so each piece is tokenized once.
+struct TokenRuler<'a> {
+    text: &'a str,
+    count: &'a (dyn Fn(&str) -> usize + Send + Sync),
+    positions: Vec<usize>,
+    cum: Vec<usize>,
+}
+
+impl<'a> TokenRuler<'a> {
+    fn new(text: &'a str, count: &'a (dyn Fn(&str) -> usize + Send + Sync), cuts: impl Iterator<Item = usize>) -> Self {
+        let mut positions = vec![0];
+        positions.extend(cuts.filter(|&p| p > 0 && p < text.len()));
+        positions.push(text.len());
+        positions.dedup();
+        let mut cum = Vec::with_capacity(positions.len());
+        cum.push(0);
+        for w in positions.windows(2) {
+            let last = *cum.last().unwrap_or(&0);
+            cum.push(last + count(&text[w[0]..w[1]]));
+        }
+        Self { text, count, positions, cum }
+    }
+
+    fn tokens_at(&self, pos: usize) -> usize {
+        match self.positions.binary_search(&pos) {
+            Ok(i) => self.cum[i],
+            Err(i) => self.cum[i - 1] + (self.count)(&self.text[self.positions[i - 1]..pos]),
+        }
+    }
+
+    /// Furthest char boundary reachable from `start` within `budget` tokens.
+    fn advance(&self, start: usize, budget: usize) -> usize {
+        let target = self.tokens_at(start) + budget;
+        let i = self.cum.partition_point(|&c| c <= target);
+        if i == self.positions.len() { return self.text.len(); }
+        let (a, b) = (self.positions[i - 1], self.positions[i]);
+        let cands: Vec<usize> = self.text[a..b].char_indices().map(|(o, _)| a + o).collect();
+        let k = cands.partition_point(|&p| self.cum[i - 1] + (self.count)(&self.text[a..p]) <= target);
+        cands[k.max(1) - 1].max(start)
     }
 }
 
@@ -127,6 +225,109 @@ fn page_range_for_segment(start: usize, end: usize, spans: &[BlockSpan]) -> (Opt
     (min_p, max_p)
 }
 
+/// Move a cut that falls inside a code block: to the block end when the whole block fits
+/// under `cap`, before the block when it starts later, else onto a line boundary.
+fn adjust_cut_for_code(cut: usize, start: usize, cap: usize, code_ranges: &[(usize, usize)], scored: &[(usize, f32)]) -> usize {
+    let (cs, ce) = match code_ranges.iter().find(|(s, e)| cut > *s && cut < *e) { Some(r) => *r, None => return cut };
+    if ce <= cap { return ce; }
+    if cs > start { return cs; }
+    // Oversized block starting at `start`: interior boundaries are line ends only
+    if scored.iter().any(|(idx, _)| *idx == cut) { return cut; }
+    let mut before_cap: Option<usize> = None;
+    for (idx, _) in scored {
+        if *idx <= start { continue; }
+        if *idx > cap { return before_cap.unwrap_or((*idx).min(ce)); }
+        before_cap = Some(*idx);
+    }
+    before_cap.unwrap_or(ce)
+}
+
+/// Split Table blocks (`table_rows` set) whose text exceeds `params.max_chars` into several
+/// Table blocks of consecutive rows, each starting with the header row, so a table cut across
+/// chunks keeps its column names in every chunk. A row too long to fit under the header on its
+/// own is cut into fragment rows (see `split_oversized_row`), each in its own part; parts carry
+/// `table_part` = "i/n" (1-based). Other blocks pass through unchanged.
+pub fn split_table_blocks(blocks: Vec<UnifiedBlock>, params: &TextChunkParams) -> Vec<UnifiedBlock> {
+    let mut out = Vec::with_capacity(blocks.len());
+    for b in blocks {
+        let rows = match &b.table_rows {
+            Some(rows) if rows.len() > 2 && params.measure(&b.text) > params.max_chars => rows.clone(),
+            _ => { out.push(b); continue; }
+        };
+        let (header, body) = rows.split_at(1);
+        let mut parts: Vec<Vec<Vec<String>>> = Vec::new();
+        let mut cur: Vec<Vec<String>> = header.to_vec();
+        for row in body {
+            for piece in split_oversized_row(header, row, params) {
+                cur.push(piece);
+                if cur.len() > 2 && params.measure(&render_table_tsv(&cur)) > params.max_chars {
+                    let overflow = cur.pop().unwrap_or_default();
+                    parts.push(std::mem::replace(&mut cur, header.to_vec()));
+                    cur.push(overflow);
+                }
+            }
+        }
+        parts.push(cur);
+        let leading_nl = b.text.starts_with('\n');
+        let n = parts.len();
+        for (i, part_rows) in parts.into_iter().enumerate() {
+            let mut part = b.clone();
+            part.text = render_table_tsv(&part_rows);
+            if i == 0 && leading_nl { part.text.insert(0, '\n'); }
+            part.attrs.insert("table_part".to_string(), format!("{}/{}", i + 1, n));
+            part.table_rows = Some(part_rows);
+            out.push(part);
+        }
+    }
+    out
+}
+
+/// `row` as-is when it fits under `params.max_chars` together with `header`, else cut into
+/// fragment rows that do: cells are filled left to right, and a cell that does not fit is cut
+/// on a char boundary and continued at its column in the next fragment, with the columns
+/// before it left empty. Only a header that alone exceeds the budget yields oversized
+/// fragments (one char each at worst).
+fn split_oversized_row(header: &[Vec<String>], row: &[String], params: &TextChunkParams) -> Vec<Vec<String>> {
+    let fits = |frag: &[String]| {
+        let mut rows = header.to_vec();
+        rows.push(frag.to_vec());
+        params.measure(&render_table_tsv(&rows)) <= params.max_chars
+    };
+    if fits(row) { return vec![row.to_vec()]; }
+    let mut out: Vec<Vec<String>> = Vec::new();
+    let mut frag = vec![String::new(); row.len()];
+    for (col, cell) in row.iter().enumerate() {
+        let mut rest = cell.as_str();
+        loop {
+            frag[col] = rest.to_string();
+            if rest.is_empty() || fits(&frag) { break; }
+            // Longest char prefix of `rest` that still fits next to the cells already taken
+            let ends: Vec<usize> = rest.char_indices().map(|(i, _)| i).skip(1).chain(std::iter::once(rest.len())).collect();
+            let (mut lo, mut hi) = (0usize, ends.len());
+            while lo < hi {
+                let mid = (lo + hi).div_ceil(2);
+                frag[col] = rest[..ends[mid - 1]].to_string();
+                if fits(&frag) { lo = mid; } else { hi = mid - 1; }
+            }
+            let taken = if lo > 0 {
+                ends[lo - 1]
+            } else if frag[..col].iter().any(|c| !c.is_empty()) {
+                // Nothing of this cell fits beside the earlier cells: start a new fragment
+                frag[col].clear();
+                out.push(std::mem::replace(&mut frag, vec![String::new(); row.len()]));
+                continue;
+            } else {
+                ends[0]
+            };
+            frag[col] = rest[..taken].to_string();
+            out.push(std::mem::replace(&mut frag, vec![String::new(); row.len()]));
+            rest = &rest[taken..];
+        }
+    }
+    if frag.iter().any(|c| !c.is_empty()) { out.push(frag); }
+    out
+}
+
 /// Generic block-to-segments chunker shared by PDF/TXT/etc.
 pub fn chunk_blocks_to_segments(blocks: &[UnifiedBlock], params: &TextChunkParams) -> Vec<(String, Option<u32>, Option<u32>)> {
     let (text, boundaries, spans) = collect_text_and_boundaries(blocks);
@@ -146,6 +347,16 @@ pub fn chunk_blocks_to_segments(blocks: &[UnifiedBlock], params: &TextChunkParam
     }
     table_ranges.sort_by_key(|r| r.0);
 
+    // Code block spans; only line boundaries may split them
+    let mut code_ranges: Vec<(usize, usize)> = Vec::new();
+    if params.keep_code_blocks {
+        for (i, b) in blocks.iter().enumerate() {
+            if b.kind == BlockKind::Code {
+                if let Some(span) = spans.get(i) { code_ranges.push((span.start, span.end)); }
+            }
+        }
+    }
+
     let mut scored: Vec<(usize, f32)> = boundaries.iter().map(|b| {
         let mut s = b.base_score;
         if params.penalize_short_line { s -= penalize_after_short_line(&text, b.idx); }
@@ -194,6 +405,10 @@ pub fn chunk_blocks_to_segments(blocks: &[UnifiedBlock], params: &TextChunkParam
     scored.retain(|(idx, _)| {
         !table_ranges.iter().any(|(s, e)| *idx > *s && *idx < *e)
     });
+    // Inside code blocks keep only boundaries right after a newline
+    scored.retain(|(idx, _)| {
+        !code_ranges.iter().any(|(s, e)| *idx > *s && *idx < *e) || text.as_bytes()[*idx - 1] == b'\n'
+    });
 
     // Table-aware score tweaks:
     // - discourage cutting right before a table (prefer to keep the previous sentence with the table)
@@ -221,13 +436,19 @@ pub fn chunk_blocks_to_segments(blocks: &[UnifiedBlock], params: &TextChunkParam
         }
     }
 
+    let ruler = params.token_counter.as_deref().map(|count| TokenRuler::new(&text, count, scored.iter().map(|p| p.0)));
     let total = text.len();
     let mut start = 0usize;
     let mut out: Vec<(String, Option<u32>, Option<u32>)> = Vec::new();
     while start < total {
-        let min = start.saturating_add(params.min_chars.min(total - start));
-        let max = start.saturating_add(params.max_chars.min(total - start));
-        let cap = start.saturating_add(params.cap_chars.min(total - start));
+        let (min, max, cap) = match &ruler {
+            Some(r) => (r.advance(start, params.min_chars), r.advance(start, params.max_chars), r.advance(start, params.cap_chars)),
+            None => (
+                start.saturating_add(params.min_chars.min(total - start)),
+                start.saturating_add(params.max_chars.min(total - start)),
+                start.saturating_add(params.cap_chars.min(total - start)),
+            ),
+        };
         // Ensure we have a valid char boundary for a hard cap fallback
         let mut hard_cap = cap;
         while hard_cap > start && !text.is_char_boundary(hard_cap) { hard_cap -= 1; }
@@ -249,7 +470,7 @@ pub fn chunk_blocks_to_segments(blocks: &[UnifiedBlock], params: &TextChunkParam
             if moved { hard_cap = pos.max(start + 1); }
         }
 
-        if start + params.min_chars >= total {
+        if min >= total {
             let seg = text[start..total].trim_end();
             if !seg.is_empty() {
                 let (ps, pe) = page_range_for_segment(start, total, &spans);
@@ -281,6 +502,7 @@ pub fn chunk_blocks_to_segments(blocks: &[UnifiedBlock], params: &TextChunkParam
                         if let Some(b2) = prev2 { if b2 >= min { cut = b2; } }
                     }
                 }
+                cut = adjust_cut_for_code(cut, start, cap, &code_ranges, &scored);
                 let seg = text[start..cut].trim_end();
                 if !seg.is_empty() {
                     let (ps, pe) = page_range_for_segment(start, cut, &spans);
@@ -299,6 +521,7 @@ pub fn chunk_blocks_to_segments(blocks: &[UnifiedBlock], params: &TextChunkParam
         let mut cut = fallback_cut.unwrap_or(hard_cap);
         if cut > hard_cap { cut = hard_cap; }
         if cut <= start { cut = hard_cap; }
+        cut = adjust_cut_for_code(cut, start, cap, &code_ranges, &scored);
         if cut <= start { cut = total; } // safety to avoid infinite loop
         let seg = text[start..cut].trim_end();
         if !seg.is_empty() {
@@ -312,11 +535,11 @@ pub fn chunk_blocks_to_segments(blocks: &[UnifiedBlock], params: &TextChunkParam
     // Post-process: merge too-short trailing segments into the previous one when it doesn't exceed cap.
     // This helps avoid very small chunks caused by hard boundaries.
     let mut merged: Vec<(String, Option<u32>, Option<u32>)> = Vec::with_capacity(out.len());
-    let short_min: usize = params.short_merge_min_chars; // characters
+    let short_min: usize = params.short_merge_min_chars; // characters or tokens
     for (text, ps, pe) in out.into_iter() {
         if let Some((prev_text, prev_ps, prev_pe)) = merged.last_mut() {
-            let curr_len = text.chars().count();
-            let prev_len = prev_text.chars().count();
+            let curr_len = params.measure(&text);
+            let prev_len = params.measure(prev_text);
             if curr_len <= short_min && prev_len + curr_len <= params.cap_chars {
                 // Join with a newline separator if previous doesn't already end with one (it shouldn't due to trim_end)
                 if !prev_text.ends_with('\n') { prev_text.push('\n'); }
@@ -342,5 +565,152 @@ pub fn chunk_blocks_to_segments(blocks: &[UnifiedBlock], params: &TextChunkParam
         merged.push((text, ps, pe));
     }
     if merged.is_empty() { merged.push((String::new(), None, None)); }
+    if params.overlap_chars > 0 { merged = apply_overlap(merged, params); }
     merged
 }
+
+/// Prefix each segment after the first with the tail of its (original) predecessor.
+fn apply_overlap(segments: Vec<(String, Option<u32>, Option<u32>)>, params: &TextChunkParams) -> Vec<(String, Option<u32>, Option<u32>)> {
+    let mut out = Vec::with_capacity(segments.len());
+    let mut prev: Option<(String, Option<u32>)> = None;
+    for (text, ps, pe) in segments {
+        let mut seg = text.clone();
+        let mut seg_ps = ps;
+        if let Some((prev_text, prev_pe)) = &prev {
+            // The separator newline counts toward the cap too
+            let room = params.cap_chars.saturating_sub(params.measure(&text) + 1);
+            let n = params.overlap_chars.min(room);
+            let tail = if n == 0 { "" } else { tail_within(prev_text, n, params).trim_start() };
+            if !tail.is_empty() && !text.is_empty() {
+                seg = format!("{tail}\n{text}");
+                seg_ps = match (*prev_pe, ps) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+            }
+        }
+        prev = Some((text, pe));
+        out.push((seg, seg_ps, pe));
+    }
+    out
+}
+
+/// Longest suffix of `text` measuring at most `n` (chars, or tokens with a counter).
+fn tail_within<'t>(text: &'t str, n: usize, params: &TextChunkParams) -> &'t str {
+    let starts: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    if params.token_counter.is_none() {
+        let cut = starts.len().saturating_sub(n);
+        return starts.get(cut).map_or("", |&i| &text[i..]);
+    }
+    // Suffixes shrink as the start moves right; find the first start whose suffix fits
+    let k = starts.partition_point(|&i| params.measure(&text[i..]) > n);
+    starts.get(k).map_or("", |&i| &text[i..])
+}
+
+/// Byte length of the overlap (including the joining newline) that `next` repeats from the
+/// end of `prev`, as produced by `TextChunkParams::overlap_chars`; 0 when there is none.
+pub fn overlap_prefix_len(prev: &str, next: &str) -> usize {
+    next.match_indices('\n')
+        .rev()
+        .find(|(i, _)| *i > 0 && prev.ends_with(&next[..*i]))
+        .map_or(0, |(i, _)| i + 1)
+}
+
+const KIND_PROBE_CHARS: usize = 24;
+
+/// Locate the first and last segment covering each block (None when it cannot be found).
+///
+/// Segments are matched back to `blocks` by locating the head of each block's first non-empty line
+/// and the tail of its last one, in order, so it works on the output of any segmenter that preserves
+/// block text (including the grouped variants).
+fn block_segment_spans(blocks: &[UnifiedBlock], segments: &[(String, Option<u32>, Option<u32>)]) -> Vec<Option<(usize, usize)>> {
+    let mut out = Vec::with_capacity(blocks.len());
+    let mut seg_idx = 0usize;
+    let mut pos = 0usize;
+    for b in blocks {
+        let text = b.text.replace('\r', "");
+        let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+        let Some(first_line) = lines.next() else { out.push(None); continue };
+        let last_line = lines.next_back().unwrap_or(first_line);
+        // Short probes so a block cut mid-line is still located on both sides of the cut
+        let first = &first_line[..first_line.char_indices().nth(KIND_PROBE_CHARS).map_or(first_line.len(), |(i, _)| i)];
+        let last = &last_line[last_line.char_indices().rev().nth(KIND_PROBE_CHARS - 1).map_or(0, |(i, _)| i)..];
+        let Some((first_seg, first_start, first_end)) = find_from(segments, seg_idx, pos, first) else { out.push(None); continue };
+        // Search the tail from the head's start: on short lines the two probes overlap
+        let (last_seg, _, last_end) = find_from(segments, first_seg, first_start, last).unwrap_or((first_seg, first_start, first_end));
+        out.push(Some((first_seg, last_seg)));
+        seg_idx = last_seg;
+        pos = last_end;
+    }
+    out
+}
+
+/// Attribute each segment with the distinct kinds of the blocks it covers.
+/// Blocks that cannot be located in `segments` are skipped.
+pub fn segment_block_kinds(blocks: &[UnifiedBlock], segments: &[(String, Option<u32>, Option<u32>)]) -> Vec<Vec<chunk_model::BlockKind>> {
+    let mut out: Vec<Vec<chunk_model::BlockKind>> = vec![Vec::new(); segments.len()];
+    for (b, span) in blocks.iter().zip(block_segment_spans(blocks, segments)) {
+        let (Some(kind), Some((first, last))) = (b.kind.to_chunk_kind(), span) else { continue };
+        for kinds in &mut out[first..=last] {
+            if !kinds.contains(&kind) { kinds.push(kind); }
+        }
+    }
+    out
+}
+
+/// Heading path (outermost first) in effect at each block. `stack` holds `(level, title)` of the
+/// open headings and is updated in place, so callers can carry it across block groups.
+/// Headings without a level count as level 1.
+pub fn heading_paths(blocks: &[UnifiedBlock], stack: &mut Vec<(u8, String)>) -> Vec<Vec<String>> {
+    blocks
+        .iter()
+        .map(|b| {
+            if matches!(b.kind, BlockKind::Heading) {
+                let title = b.text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+                if !title.is_empty() {
+                    let level = b.heading_level.unwrap_or(1);
+                    while stack.last().is_some_and(|(lv, _)| *lv >= level) { stack.pop(); }
+                    stack.push((level, title.to_string()));
+                }
+            }
+            stack.iter().map(|(_, t)| t.clone()).collect()
+        })
+        .collect()
+}
+
+/// Section path for each segment: the heading path at the earliest block the segment covers.
+/// Segments whose blocks cannot be located inherit the previous segment's path; segments
+/// before any heading get an empty path. `stack` is threaded as in `heading_paths`.
+pub fn segment_section_paths(
+    blocks: &[UnifiedBlock],
+    segments: &[(String, Option<u32>, Option<u32>)],
+    stack: &mut Vec<(u8, String)>,
+) -> Vec<Vec<String>> {
+    let start: Vec<String> = stack.iter().map(|(_, t)| t.clone()).collect();
+    let paths = heading_paths(blocks, stack);
+    let mut out: Vec<Option<Vec<String>>> = vec![None; segments.len()];
+    for (path, span) in paths.into_iter().zip(block_segment_spans(blocks, segments)) {
+        let Some((first, last)) = span else { continue };
+        for slot in &mut out[first..=last] {
+            if slot.is_none() { *slot = Some(path.clone()); }
+        }
+    }
+    let mut prev = start;
+    out.into_iter()
+        .map(|p| {
+            if let Some(p) = p { prev = p; }
+            prev.clone()
+        })
+        .collect()
+}
+
+/// Find `needle` at or after (`seg`, `pos`); returns the segment index and the match's byte range.
+fn find_from(segments: &[(String, Option<u32>, Option<u32>)], seg: usize, pos: usize, needle: &str) -> Option<(usize, usize, usize)> {
+    for (i, (s, _, _)) in segments.iter().enumerate().skip(seg) {
+        let from = if i == seg { pos.min(s.len()) } else { 0 };
+        if let Some(off) = s.get(from..).and_then(|tail| tail.find(needle)) {
+            return Some((i, from + off, from + off + needle.len()));
+        }
+    }
+    None
+}
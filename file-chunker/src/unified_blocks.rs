@@ -8,6 +8,8 @@ pub enum BlockKind {
     Heading,
     ListItem,
     Code,
+    /// A whole table; its cells are in `UnifiedBlock::table_rows`.
+    Table,
     TableCell,
     FigureCaption,
     Header,
@@ -15,6 +17,22 @@ pub enum BlockKind {
     PageBreak,
 }
 
+impl BlockKind {
+    /// Map to the retrieval-facing `chunk_model::BlockKind`; `PageBreak` carries no content.
+    pub fn to_chunk_kind(&self) -> Option<chunk_model::BlockKind> {
+        match self {
+            BlockKind::Paragraph => Some(chunk_model::BlockKind::Paragraph),
+            BlockKind::Heading => Some(chunk_model::BlockKind::Heading),
+            BlockKind::ListItem => Some(chunk_model::BlockKind::ListItem),
+            BlockKind::Code => Some(chunk_model::BlockKind::Code),
+            BlockKind::Table | BlockKind::TableCell => Some(chunk_model::BlockKind::Table),
+            BlockKind::FigureCaption => Some(chunk_model::BlockKind::Caption),
+            BlockKind::Header | BlockKind::Footer => Some(chunk_model::BlockKind::HeaderFooter),
+            BlockKind::PageBreak => None,
+        }
+    }
+}
+
 /// List item info when kind == ListItem.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ListInfo {
@@ -89,6 +107,9 @@ pub struct UnifiedBlock {
     pub list: Option<ListInfo>,
     /// Table cell info when kind == TableCell.
     pub table: Option<TableInfo>,
+    /// Cell texts row by row when kind == Table; the first row is the header.
+    #[serde(default)]
+    pub table_rows: Option<Vec<Vec<String>>>,
     /// Bounding box when available.
     pub bbox: Option<BBox>,
     /// Language hint (e.g., "ja").
@@ -122,6 +143,7 @@ impl UnifiedBlock {
             heading_level: None,
             list: None,
             table: None,
+            table_rows: None,
             bbox: None,
             lang: None,
             source: SourceRef { reader: reader.into(), origin: origin.into(), local_id: String::new() },
@@ -133,4 +155,29 @@ impl UnifiedBlock {
             header_footer_hint: None,
         }
     }
+
+    /// Table block over `rows` (first row = header) with `text` from `render_table_tsv`.
+    pub fn table(rows: Vec<Vec<String>>, order: u32, origin: impl Into<String>, reader: impl Into<String>) -> Self {
+        let mut b = Self::new(BlockKind::Table, render_table_tsv(&rows), order, origin, reader);
+        b.attrs.insert("is_table".to_string(), "true".to_string());
+        b.attrs.insert("table_cell_nl".to_string(), "U+2028".to_string());
+        b.table_rows = Some(rows);
+        b
+    }
+}
+
+/// Render table rows as TSV wrapped in `<table delim="tsv" cell-nl="U+2028">...</table>\n`, the
+/// text form every reader uses. Tabs inside cells become spaces and in-cell line breaks U+2028,
+/// so `\t` and `\n` always separate cells and rows.
+pub fn render_table_tsv(rows: &[Vec<String>]) -> String {
+    let lines: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell.replace("\r\n", "\n").replace(['\r', '\n'], "\u{2028}").replace('\t', " "))
+                .collect::<Vec<_>>()
+                .join("\t")
+        })
+        .collect();
+    format!("<table delim=\"tsv\" cell-nl=\"U+2028\">\n{}\n</table>\n", lines.join("\n"))
 }
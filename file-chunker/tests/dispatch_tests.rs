@@ -0,0 +1,140 @@
+use file_chunker::unified_blocks::BlockKind;
+use file_chunker::{chunk_file_with_file_record_with_options, ChunkOptions, IdScheme, UnsupportedMime};
+
+#[test]
+fn forced_mime_routes_extensionless_pdf_to_pdf_reader() {
+    let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../testdata/public/PDF変換検証サンプル（Wordから保存したもの）.pdf");
+    let dst = std::env::temp_dir().join(format!("force-mime-{}", std::process::id()));
+    std::fs::copy(&src, &dst).expect("copy sample pdf");
+    let path = dst.to_string_lossy().to_string();
+
+    let opts = ChunkOptions { force_mime: Some("application/pdf".into()), ..Default::default() };
+    let out = chunk_file_with_file_record_with_options(&path, &opts).expect("chunk file");
+    let _ = std::fs::remove_file(&dst);
+
+    assert_eq!(out.file.source_mime, "application/pdf");
+    // The backend enabled by this build must have read it, not the text fallback
+    let expected = match file_chunker::reader_pdf::default_backend() {
+        file_chunker::reader_pdf::PdfBackend::Pdfium => "pdfium",
+        file_chunker::reader_pdf::PdfBackend::PureRust => "pure-pdf",
+        file_chunker::reader_pdf::PdfBackend::Stub => "stub.pdf",
+    };
+    assert_eq!(out.file.reader_backend.as_deref(), Some(expected));
+    assert!(!out.chunks.is_empty());
+    assert!(out.chunks.iter().all(|c| c.source_mime == "application/pdf"));
+}
+
+#[test]
+fn unknown_forced_mime_is_rejected() {
+    let path = std::env::temp_dir().join(format!("force-mime-unknown-{}.txt", std::process::id()));
+    std::fs::write(&path, "plain text").expect("write sample txt");
+    let opts = ChunkOptions { force_mime: Some("application/x-unknown".into()), ..Default::default() };
+    let err = chunk_file_with_file_record_with_options(&path.to_string_lossy(), &opts).expect_err("unknown MIME");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(err, UnsupportedMime("application/x-unknown".into()));
+}
+
+#[test]
+fn text_postprocess_strips_watermark_before_segmentation() {
+    const WATERMARK: &str = "CONFIDENTIAL - ACME PRINT SERVICES";
+    let path = std::env::temp_dir().join(format!("postprocess-{}.txt", std::process::id()));
+    let body = format!("First paragraph about budgets.\n{WATERMARK}\n\nSecond paragraph about hiring.\n\n{WATERMARK}\n");
+    std::fs::write(&path, body).expect("write sample txt");
+
+    let seen_kinds = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen = std::sync::Arc::clone(&seen_kinds);
+    let opts = ChunkOptions {
+        text_postprocess: Some(std::sync::Arc::new(move |kind: BlockKind, text: &str| {
+            seen.lock().unwrap().push(kind);
+            text.lines().filter(|l| l.trim() != WATERMARK).collect::<Vec<_>>().join("\n")
+        })),
+        ..Default::default()
+    };
+    let out = chunk_file_with_file_record_with_options(&path.to_string_lossy(), &opts).expect("chunk file");
+    let _ = std::fs::remove_file(&path);
+
+    let all: String = out.chunks.iter().map(|c| c.text.as_str()).collect();
+    assert!(!all.contains(WATERMARK), "watermark survived: {all}");
+    assert!(all.contains("budgets") && all.contains("hiring"));
+    assert!(seen_kinds.lock().unwrap().iter().all(|k| *k == BlockKind::Paragraph));
+}
+
+#[test]
+fn inferred_numbering_headings_populate_section_paths() {
+    let path = std::env::temp_dir().join(format!("infer-headings-{}.txt", std::process::id()));
+    let body = "第1章 総則\n1.1 目的\nこの規程は文書管理の手順を定める。\n\n1.2 適用範囲\n全ての部署に適用する。\n\n第2章 運用\n2.1 保管\n文書は五年間保管する。\n";
+    std::fs::write(&path, body).expect("write sample txt");
+    let path_str = path.to_string_lossy().to_string();
+
+    let plain = chunk_file_with_file_record_with_options(&path_str, &ChunkOptions::default()).expect("chunk file");
+    let opts = ChunkOptions { infer_headings: Some(Default::default()), ..Default::default() };
+    let out = chunk_file_with_file_record_with_options(&path_str, &opts).expect("chunk file");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(plain.chunks.iter().all(|c| c.section_path.as_deref().unwrap_or_default().is_empty()));
+    let section_of = |needle: &str| {
+        out.chunks.iter().find(|c| c.text.contains(needle)).and_then(|c| c.section_path.clone()).expect("chunk with text")
+    };
+    // A chapter's first section stays in the chapter's chunk, as for DOCX headings
+    assert_eq!(section_of("手順を定める"), vec!["第1章 総則".to_string()]);
+    assert_eq!(section_of("全ての部署"), vec!["第1章 総則".to_string(), "1.2 適用範囲".to_string()]);
+    assert_eq!(section_of("五年間保管"), vec!["第2章 運用".to_string()]);
+    assert_eq!(out.chunks.len(), 3);
+    assert!(out.chunks.iter().any(|c| c.block_kinds.contains(&chunk_model::BlockKind::Heading)));
+}
+
+#[test]
+fn html_files_map_elements_to_blocks_and_chunk_by_heading() {
+    let html = r#"<!DOCTYPE html><html><head><title>Ignored</title><style>p { color: red }</style></head>
+<body><h1>Guide</h1><p>Read the <a href="https://example.com/a">install notes</a> &amp; more.</p>
+<script>var secret = "do not index";</script>
+<h2>Lists</h2><ol><li>first</li><li>second<ul><li>nested</li></ul></li></ol>
+<table><tr><th>k</th><th>v</th></tr><tr><td>a</td><td>1</td></tr></table>
+<figure><img src="x.png" alt="A chart"></figure></body></html>"#;
+    let blocks = file_chunker::reader_html::html_to_blocks(html, "mem.html");
+    let kinds: Vec<BlockKind> = blocks.iter().map(|b| b.kind.clone()).collect();
+    assert_eq!(kinds, vec![
+        BlockKind::Heading, BlockKind::Paragraph, BlockKind::Heading, BlockKind::ListItem,
+        BlockKind::ListItem, BlockKind::ListItem, BlockKind::Table, BlockKind::FigureCaption,
+    ]);
+    assert_eq!(blocks[2].heading_level, Some(2));
+    let link = &blocks[1].links[0];
+    let linked: String = blocks[1].text.chars().skip(link.start as usize).take((link.end - link.start) as usize).collect();
+    assert_eq!((linked.as_str(), link.uri.as_str()), ("install notes", "https://example.com/a"));
+    assert_eq!(blocks[5].list.as_ref().map(|l| (l.ordered, l.level)), Some((false, 2)));
+    assert!(blocks[6].text.contains("k\tv\na\t1"));
+    assert_eq!(blocks[7].attrs.get("img_src").map(String::as_str), Some("x.png"));
+
+    let path = std::env::temp_dir().join(format!("reader-html-{}.html", std::process::id()));
+    std::fs::write(&path, html).expect("write sample html");
+    let out = chunk_file_with_file_record_with_options(&path.to_string_lossy(), &ChunkOptions::default()).expect("chunk file");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(out.file.reader_backend.as_deref(), Some("html"));
+    assert!(out.chunks.iter().all(|c| !c.text.contains("secret") && !c.text.contains("Ignored")));
+    assert!(out.chunks.iter().any(|c| c.section_path.as_deref() == Some(&["Guide".to_string()][..])));
+}
+
+#[test]
+fn content_hash_ids_survive_a_rename() {
+    let dir = std::env::temp_dir().join(format!("id-scheme-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("create temp dir");
+    let before = dir.join("notes.txt");
+    let after = dir.join("renamed.txt");
+    std::fs::write(&before, "Quarterly budget review.\n\nHiring plan for the next year.\n").expect("write sample txt");
+
+    let opts = ChunkOptions { id_scheme: IdScheme::ContentHash, ..Default::default() };
+    let first = chunk_file_with_file_record_with_options(&before.to_string_lossy(), &opts).expect("chunk file");
+    std::fs::rename(&before, &after).expect("rename sample");
+    let second = chunk_file_with_file_record_with_options(&after.to_string_lossy(), &opts).expect("chunk file");
+    let by_path = chunk_file_with_file_record_with_options(&after.to_string_lossy(), &ChunkOptions::default()).expect("chunk file");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let ids = |out: &file_chunker::ChunkOutput| out.chunks.iter().map(|c| c.chunk_id.0.clone()).collect::<Vec<_>>();
+    assert!(!first.chunks.is_empty());
+    assert!(first.file.doc_id.0.starts_with("sha256-"));
+    assert_eq!(first.file.doc_id, second.file.doc_id);
+    assert_eq!(ids(&first), ids(&second));
+    assert_eq!(first.chunks[0].chunk_id.0, format!("{}#0", first.file.doc_id.0));
+    assert!(by_path.chunks[0].chunk_id.0.starts_with(&*after.to_string_lossy()));
+}
@@ -0,0 +1,30 @@
+use std::collections::HashSet;
+
+use file_chunker::scan::{filter_unregistered, scan_dir, sha256_hex_file};
+
+#[test]
+fn scan_dir_walks_to_depth_filters_exts_and_skips_registered_content() {
+    let root = std::env::temp_dir().join(format!("scan-dir-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("sub/deeper")).unwrap();
+    std::fs::write(root.join("b.txt"), "beta").unwrap();
+    std::fs::write(root.join("a.TXT"), "alpha").unwrap();
+    std::fs::write(root.join("skip.pdf"), "not text").unwrap();
+    std::fs::write(root.join("sub/c.txt"), "gamma").unwrap();
+    std::fs::write(root.join("sub/deeper/d.txt"), "delta").unwrap();
+
+    let names = |files: &[file_chunker::scan::ScannedFile]| -> Vec<String> {
+        files.iter().map(|f| f.path.strip_prefix(&root).unwrap().to_string_lossy().replace('\\', "/")).collect()
+    };
+    let scanned = scan_dir(&root, &[".txt"], 1);
+    assert_eq!(names(&scanned), vec!["a.TXT", "b.txt", "sub/c.txt"]);
+    assert_eq!(scanned[0].size, 5);
+    assert_eq!(names(&scan_dir(&root, &["*"], 0)), vec!["a.TXT", "b.txt", "skip.pdf"]);
+
+    // "alpha" is registered; "gamma" only shares its size and must still be kept
+    let known_hashes: HashSet<String> = [sha256_hex_file(&root.join("a.TXT")).unwrap()].into();
+    let known_sizes: HashSet<u64> = [5].into();
+    let pending = filter_unregistered(scanned, &known_hashes, &known_sizes);
+    assert_eq!(names(&pending), vec!["b.txt", "sub/c.txt"]);
+    let _ = std::fs::remove_dir_all(&root);
+}
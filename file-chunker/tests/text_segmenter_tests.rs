@@ -0,0 +1,195 @@
+use file_chunker::text_segmenter::{chunk_blocks_to_segments, overlap_prefix_len, segment_block_kinds, segment_section_paths, split_table_blocks, TextChunkParams};
+use file_chunker::unified_blocks::{BlockKind, UnifiedBlock};
+
+fn code_lines(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("    let value_{i} = compute(input.field_{i}, 42); // step {i}")).collect()
+}
+
+fn blocks_with_code(code: &str) -> Vec<UnifiedBlock> {
+    let intro = "This paragraph introduces the example. It explains what the snippet does. ".repeat(3);
+    let outro = "After the snippet we summarize the behavior. The end result is stable. ".repeat(3);
+    vec![
+        UnifiedBlock::new(BlockKind::Paragraph, format!("{intro}\n"), 0, "test.md", "test"),
+        UnifiedBlock::new(BlockKind::Code, format!("{code}\n"), 1, "test.md", "test"),
+        UnifiedBlock::new(BlockKind::Paragraph, outro, 2, "test.md", "test"),
+    ]
+}
+
+#[test]
+fn code_block_under_cap_stays_in_one_segment() {
+    let code = code_lines(6).join("\n");
+    let params = TextChunkParams { min_chars: 200, max_chars: 300, cap_chars: 600, ..Default::default() };
+    let segs = chunk_blocks_to_segments(&blocks_with_code(&code), &params);
+    assert!(segs.iter().any(|(t, _, _)| t.contains(code.as_str())), "code block was split: {segs:#?}");
+}
+
+#[test]
+fn oversized_code_block_splits_on_line_boundaries_only() {
+    let lines = code_lines(20);
+    let code = lines.join("\n");
+    let params = TextChunkParams { min_chars: 200, max_chars: 300, cap_chars: 400, ..Default::default() };
+    let segs = chunk_blocks_to_segments(&blocks_with_code(&code), &params);
+    assert!(segs.len() > 1);
+    for (text, _, _) in &segs {
+        for line in text.lines().filter(|l| l.contains("let value_")) {
+            assert!(lines.iter().any(|full| full == line), "code line was split: {line:?}");
+        }
+    }
+}
+
+#[test]
+fn segments_carry_the_kinds_of_the_blocks_they_cover() {
+    let lines = code_lines(20);
+    let code = lines.join("\n");
+    let blocks = blocks_with_code(&code);
+    let params = TextChunkParams { min_chars: 200, max_chars: 300, cap_chars: 400, ..Default::default() };
+    let segs = chunk_blocks_to_segments(&blocks, &params);
+    let kinds = segment_block_kinds(&blocks, &segs);
+    assert_eq!(kinds.len(), segs.len());
+    for ((text, _, _), ks) in segs.iter().zip(&kinds) {
+        let has_code = text.contains("let value_");
+        assert_eq!(ks.contains(&chunk_model::BlockKind::Code), has_code, "kinds {ks:?} for {text:?}");
+    }
+    assert!(kinds.first().is_some_and(|ks| ks.contains(&chunk_model::BlockKind::Paragraph)));
+}
+
+fn heading(text: &str, level: u8, order: u32) -> UnifiedBlock {
+    let mut b = UnifiedBlock::new(BlockKind::Heading, format!("{text}\n"), order, "test.docx", "test");
+    b.heading_level = Some(level);
+    b
+}
+
+#[test]
+fn segments_get_the_enclosing_heading_path() {
+    let para = |text: &str, order: u32| UnifiedBlock::new(BlockKind::Paragraph, format!("{text}\n"), order, "test.docx", "test");
+    let blocks = vec![
+        para("Preface before any heading.", 0),
+        heading("第1章", 1, 1),
+        para("Chapter one introduction text.", 2),
+        heading("1.2 補償", 2, 3),
+        para("Compensation rules apply to every claim filed within the period.", 4),
+        heading("第2章", 1, 5),
+        para("Chapter two text.", 6),
+    ];
+    let seg = |t: &str| (t.to_string(), None, None);
+    let segs = vec![
+        seg("Preface before any heading."),
+        seg("第1章\nChapter one introduction text."),
+        seg("1.2 補償\nCompensation rules apply to every"),
+        seg("claim filed within the period."),
+        seg("第2章\nChapter two text."),
+    ];
+    let paths = segment_section_paths(&blocks, &segs, &mut Vec::new());
+    let expect = |p: &[&str]| p.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(paths, vec![
+        expect(&[]),
+        expect(&["第1章"]),
+        expect(&["第1章", "1.2 補償"]),
+        expect(&["第1章", "1.2 補償"]),
+        expect(&["第2章"]),
+    ]);
+
+    // No blocks at all still yields one empty path per segment.
+    assert_eq!(segment_section_paths(&[], &[seg("")], &mut Vec::new()), vec![Vec::<String>::new()]);
+}
+
+#[test]
+fn overlap_repeats_the_previous_tail_within_cap() {
+    let text = "Sentence number one is here. Another sentence follows it. ".repeat(30);
+    let blocks = vec![UnifiedBlock::new(BlockKind::Paragraph, text, 0, "test.txt", "test")];
+    let base = TextChunkParams { min_chars: 200, max_chars: 300, cap_chars: 400, ..Default::default() };
+    let plain = chunk_blocks_to_segments(&blocks, &base);
+    assert_eq!(plain, chunk_blocks_to_segments(&blocks, &TextChunkParams { overlap_chars: 0, ..base.clone() }));
+    assert!(plain.len() > 2);
+
+    let overlapped = chunk_blocks_to_segments(&blocks, &TextChunkParams { overlap_chars: 80, ..base.clone() });
+    assert_eq!(overlapped.len(), plain.len());
+    assert_eq!(overlapped[0], plain[0]);
+    for i in 1..plain.len() {
+        let (seg, _, _) = &overlapped[i];
+        assert!(seg.chars().count() <= base.cap_chars, "segment {i} exceeds cap");
+        assert!(seg.ends_with(plain[i].0.as_str()));
+        let n = overlap_prefix_len(&plain[i - 1].0, seg);
+        assert!(n > 0, "segment {i} has no overlap");
+        assert_eq!(&seg[n..], plain[i].0);
+        assert!(seg[..n - 1].chars().count() <= 80);
+    }
+}
+
+#[test]
+fn token_budget_bounds_chunks_with_uneven_tokenization() {
+    // CJK chars cost 2 tokens each, other words 1: char counts say little about tokens
+    let count = |t: &str| -> usize {
+        let cjk = t.chars().filter(|c| ('\u{3040}'..='\u{9fff}').contains(c)).count();
+        cjk * 2 + t.split_whitespace().filter(|w| w.chars().any(|c| c.is_ascii_alphanumeric())).count()
+    };
+    let text = "検索エンジンの評価を行います。\nThe ranking model is tuned on short queries.\n".repeat(30);
+    let blocks = vec![UnifiedBlock::new(BlockKind::Paragraph, text, 0, "test.txt", "test")];
+    let params = TextChunkParams::for_token_budget(std::sync::Arc::new(count), 120);
+    let segs = chunk_blocks_to_segments(&blocks, &params);
+    assert!(segs.len() > 3, "expected several chunks: {segs:#?}");
+    for (i, (seg, _, _)) in segs.iter().enumerate() {
+        assert!(count(seg) <= 120, "segment {i} has {} tokens", count(seg));
+    }
+    assert!(segs[..segs.len() - 1].iter().all(|(seg, _, _)| count(seg) >= 60));
+
+    // Without a counter the same numbers are characters
+    let chars = chunk_blocks_to_segments(&blocks, &TextChunkParams { token_counter: None, ..params });
+    assert!(chars.iter().all(|(seg, _, _)| seg.chars().count() <= 120));
+    assert_ne!(chars.len(), segs.len());
+}
+
+#[test]
+fn large_tables_split_on_rows_and_repeat_the_header() {
+    let mut rows = vec![vec!["Item".to_string(), "Price".to_string(), "Notes".to_string()]];
+    rows.extend((0..60).map(|i| vec![format!("item-{i}"), format!("{}", i * 10), format!("line one\nline two {i}")]));
+    let blocks = vec![
+        UnifiedBlock::new(BlockKind::Paragraph, "Price list follows.\n", 0, "test.xlsx", "test"),
+        UnifiedBlock::table(rows, 1, "test.xlsx", "test"),
+    ];
+    let params = TextChunkParams { min_chars: 200, max_chars: 300, cap_chars: 400, ..Default::default() };
+    let blocks = split_table_blocks(blocks, &params);
+    assert!(blocks.len() > 3, "table should be split into parts");
+    let segs = chunk_blocks_to_segments(&blocks, &params);
+
+    let table_segs: Vec<&str> = segs.iter().map(|(t, _, _)| t.as_str()).filter(|t| t.contains("item-")).collect();
+    assert!(table_segs.len() > 1);
+    for seg in &table_segs {
+        assert!(seg.contains("<table delim=\"tsv\" cell-nl=\"U+2028\">\nItem\tPrice\tNotes\n"), "header missing in {seg:?}");
+        assert!(seg.chars().count() <= params.cap_chars);
+    }
+    for i in 0..60 {
+        let row = format!("item-{i}\t{}\tline one\u{2028}line two {i}\n", i * 10);
+        assert_eq!(table_segs.iter().filter(|s| s.contains(&row)).count(), 1, "row {i}");
+    }
+    let kinds = segment_block_kinds(&blocks, &segs);
+    assert!(segs.iter().zip(&kinds).filter(|((t, _, _), _)| t.contains("item-")).all(|(_, ks)| ks.contains(&chunk_model::BlockKind::Table)));
+}
+
+#[test]
+fn oversized_table_rows_are_cut_into_fragments_under_the_header() {
+    let notes = "word ".repeat(200);
+    let rows = vec![
+        vec!["Item".to_string(), "Notes".to_string()],
+        vec!["small".to_string(), "short note".to_string()],
+        vec!["huge".to_string(), notes.clone()],
+        vec!["after".to_string(), "tail".to_string()],
+    ];
+    let blocks = vec![UnifiedBlock::table(rows, 1, "test.xlsx", "test")];
+    let params = TextChunkParams { min_chars: 100, max_chars: 200, cap_chars: 300, ..Default::default() };
+    let parts = split_table_blocks(blocks, &params);
+    assert!(parts.len() > 3, "the long row should span several parts");
+
+    let mut rebuilt = String::new();
+    for part in &parts {
+        assert!(part.text.chars().count() <= params.max_chars, "part over budget: {:?}", part.text);
+        let rows = part.table_rows.as_ref().expect("parts keep their rows");
+        assert_eq!(rows[0], vec!["Item".to_string(), "Notes".to_string()]);
+        for row in &rows[1..] {
+            if row[0] == "huge" || row[0].is_empty() { rebuilt.push_str(&row[1]); }
+        }
+    }
+    assert_eq!(rebuilt, notes);
+    assert!(parts.iter().any(|p| p.text.contains("small\tshort note")));
+    assert!(parts.last().is_some_and(|p| p.text.contains("after\ttail")));
+}
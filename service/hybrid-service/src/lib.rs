@@ -1,19 +1,31 @@
+pub mod prompt_builder;
+
+use std::borrow::Cow;
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock, atomic::{AtomicBool, AtomicU64, Ordering}};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, RwLock, atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}};
 
 use chrono::Utc;
+use serde::{Deserialize, Serialize};
 use chunk_model::{ChunkId, ChunkRecord, DocumentId, FileRecord};
+use chunking_store::embed_cache::{CachedEmbedding, EmbedCache};
 #[cfg(all(not(feature = "tantivy"), feature = "fts"))]
 use chunking_store::fts5_index::Fts5Index;
-use chunking_store::hnsw_index::HnswIndex;
-use chunking_store::orchestrator::{delete_by_filter_orchestrated, ingest_chunks_orchestrated, DeleteReport};
-use chunking_store::{ChunkStoreRead, FilterClause, SearchHit, SearchOptions, VectorSearcher};
+use chunking_store::hnsw_index::{sibling_dir, swap_in_dir, HnswError, HnswIndex};
+use chunking_store::orchestrator::{delete_by_filter_orchestrated, delete_ids_orchestrated, stage_ingest_chunks, DeleteReport, OrchestratorError};
+use chunking_store::{cap_hits_per_doc, group_hits_by_doc, plan_filter_pushdown, ChunkStoreRead, DocGroup, EmptyFallback, FilterClause, FilterPlan, ScoreBreakdown, SearchHit, SearchOptions, VectorSearcher};
 use chunking_store::sqlite_repo::SqliteRepo;
+pub use chunking_store::sqlite_repo::{DocSummary, OrphanReport, SearchLogEntry};
 #[cfg(feature = "tantivy")]
-use chunking_store::tantivy_index::{TantivyIndex, TokenCombine};
+use chunking_store::tantivy_index::{TantivyIndex, TantivyOpts, TokenCombine};
 use embedding_provider::config::default_stdio_config;
 use embedding_provider::embedder::{Embedder, OnnxStdIoConfig, OnnxStdIoEmbedder};
+pub use file_chunker::ChunkOptions;
+pub use file_chunker::scan::sha256_hex_file;
+pub use file_chunker::page_image::PageRenderer;
+use file_chunker::reader_pdf_pdfium::PdfiumPageRenderer;
+pub use chunking_store::hnsw_index::{HnswMetric, HnswParams, VectorDtype};
+pub use chunking_store::tantivy_index::TokenizerKind;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ServiceError {
@@ -25,6 +37,22 @@ pub enum ServiceError {
     Index(String),
     #[error("io error: {0}")]
     Io(String),
+    #[error("service is read-only")]
+    ReadOnly,
+    #[error("embedding drift: reference vector deviates by {0:.4} from the stored one (model changed?)")]
+    EmbedDrift(f32),
+    #[error("embedding dimension mismatch: expected {expected} (embedder.dimension) but the model produced {actual}; the model and the configured dimension likely disagree, set embedder.dimension to {actual} or load the matching model")]
+    DimensionMismatch { expected: usize, actual: usize },
+    #[error("HNSW index was built with dimension {found} but the embedder produces {expected}; reindex with re-embedding to rebuild it")]
+    IndexDimensionMismatch { expected: usize, found: usize },
+    #[error("document blob of {size} bytes exceeds the {max} byte limit")]
+    BlobTooLarge { size: usize, max: usize },
+    #[error("file content is already ingested as {doc_id}")]
+    DuplicateContent { doc_id: String },
+    #[error("partial ingest: {error} (DB rolled back; already applied to {applied:?})")]
+    PartialIngest { applied: Vec<String>, error: String },
+    #[error("{0}")]
+    UnsupportedMime(String),
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +70,294 @@ pub struct ServiceConfig {
     pub embed_initial_batch: usize,
     /// Minimum batch size to allow in auto mode.
     pub embed_min_batch: usize,
+    /// When true, tag each chunk with a detected language in `meta["lang"]` at ingest
+    /// so searches can be restricted via `SearchOptions::lang`.
+    pub tag_chunk_lang: bool,
+    /// When true, open the repo read-only and reject all ingest/delete calls
+    /// with `ServiceError::ReadOnly`. Indexes are still loaded for search.
+    pub read_only: bool,
+    /// Extra attempts when loading the HNSW snapshot fails (e.g., a file still being
+    /// written or locked by antivirus) before the state is marked `Error`.
+    pub hnsw_load_retries: u32,
+    /// Base backoff between HNSW load attempts; grows linearly per attempt.
+    pub hnsw_load_backoff_ms: u64,
+    /// Maximum number of background index loads (HNSW/Tantivy) running at once. Loads queued
+    /// for an older store epoch are dropped when a newer store is selected. 0 is treated as 1.
+    pub max_background_loaders: usize,
+    /// When true, prepend the file's `title_guess`/tags to the embedding input (not the
+    /// stored text) of documents shorter than `embed_title_max_doc_chars`.
+    pub embed_include_title: bool,
+    /// Document length (chars, summed over chunks) below which the title is prepended.
+    pub embed_title_max_doc_chars: usize,
+    /// When true, derive doc/chunk ids from content hashes (no path or timestamp) unless a
+    /// doc id hint is given, so the same corpus always yields the same ids.
+    pub content_based_ids: bool,
+    /// Minimum interval between progress callbacks; intermediate events are dropped.
+    /// Terminal `Finished`/`Canceled` events are always delivered. 0 disables throttling.
+    pub progress_min_interval_ms: u64,
+    /// Minimum unique-token ratio for file chunks; lower ones go through `quality_gate`.
+    /// 0.0 disables the gate.
+    pub quality_min_unique_ratio: f32,
+    /// What to do with chunks below `quality_min_unique_ratio`.
+    pub quality_gate: QualityGateAction,
+    /// Meta key whose value names a chunk's collection (e.g., `"collection"`). When set, the
+    /// store keeps an index on it and `search_in_collection` becomes available.
+    pub collection_meta_key: Option<String>,
+    /// Max allowed deviation (see `embedding_deviation`) between the model's embedding of a
+    /// fixed reference text and the one recorded in the store at first ingest. 0.0 disables it.
+    pub embed_drift_max_deviation: f32,
+    /// What to do when the drift check exceeds `embed_drift_max_deviation`.
+    pub embed_drift_action: DriftAction,
+    /// Tombstone share (deleted / total HNSW labels) at which `compact_hnsw_if_needed`
+    /// rebuilds the index.
+    pub hnsw_compact_tombstone_ratio: f32,
+    /// When one `delete_by_filter` call removes more than this fraction of the stored chunks,
+    /// run `compact_store` in the background. None disables it.
+    pub auto_compact_after_delete_ratio: Option<f32>,
+    /// What `import_ndjson` does with `extra` keys that collide with known field names.
+    pub import_extra_conflicts: ExtraConflictAction,
+    /// Max snippet length (chars) reported per document by `search_grouped`.
+    pub group_snippet_chars: usize,
+    /// When true, `ingest_chunks` also stores each vector in `extra["vector.f32"]` so an NDJSON
+    /// export is self-contained. Costs about 10 bytes per dimension of JSON text per chunk in
+    /// the DB and the export (the raw f32 payload alone is 4 * dim bytes). Stores with the
+    /// `F16` vector dtype use `extra["vector.f16"]` instead, 4 hex digits per dimension.
+    /// Off by default.
+    pub persist_vectors_in_records: bool,
+    /// When true, file ingests keep each completed file (by path and content SHA-256) in the
+    /// store's `ingest_journal` and `ingest_folder` skips journaled files, so a re-run after a
+    /// crash resumes.
+    pub ingest_journal: bool,
+    /// When true, file ingestion stops before embedding if a stored FileRecord already has
+    /// the file's `content_sha256`: it emits `SkippedDuplicate` and returns
+    /// `ServiceError::DuplicateContent`. Folder and parallel ingest skip such files.
+    pub skip_duplicate_sha256: bool,
+    /// Batch size from which the first ingest into an empty HNSW index inserts all vectors
+    /// in parallel instead of one by one. 0 always inserts incrementally.
+    pub hnsw_bulk_build_min: usize,
+    /// Distance metric for a newly created HNSW index; an existing snapshot keeps its own.
+    pub hnsw_metric: HnswMetric,
+    /// Graph parameters (M, ef_construction, capacity) for a newly created HNSW index; an
+    /// existing snapshot keeps its own. See `HnswParams` for the recall/build-time tradeoff.
+    pub hnsw_params: HnswParams,
+    /// Number of query embeddings kept (LRU, keyed by the exact query string) so repeated
+    /// searches skip the embedder. 0 disables the cache.
+    pub query_embed_cache_size: usize,
+    /// SQLite file caching chunk embeddings by `embedder_fingerprint` and text hash, so re-ingesting
+    /// unchanged text skips the embedder. Safe to share between processes. `None` disables it.
+    pub embed_cache: Option<PathBuf>,
+    /// Upper bound on the page size `list_files` / `list_files_page` will return.
+    /// 0 disables the cap.
+    pub list_files_max_limit: usize,
+    /// Largest attachment accepted by `put_doc_blob`, in bytes. 0 disables the cap.
+    pub doc_blob_max_bytes: usize,
+    /// Count the words of ingested chunks into the `suggest` vocabulary. Frequencies are
+    /// approximate: re-ingests add again and deletes do not subtract. Off by default.
+    pub suggest_index_terms: bool,
+    /// Most terms kept in the `suggest` vocabulary; the least frequent are pruned beyond it.
+    /// 0 disables the cap.
+    pub suggest_max_terms: usize,
+    /// Also record every search query (lowercased) in the `suggest` vocabulary.
+    pub suggest_record_queries: bool,
+    /// During PDF ingest, render each page a chunk starts on to PNG, store it as the document
+    /// blob `page_image_blob_key(page)` and reference it from the chunk's `META_PAGE_IMAGE`.
+    /// Off by default: rendering is much slower than text extraction.
+    pub pdf_page_images: bool,
+    /// Log every vector/hybrid search (query and returned chunk ids, best first) in the store,
+    /// e.g. as input for `export_rerank_dataset`.
+    pub search_log: bool,
+    /// Storage precision of HNSW snapshot vectors and of vectors persisted in records
+    /// (`persist_vectors_in_records`) for new stores. The dtype in effect is recorded in
+    /// `store_meta` on first vector write and wins over this setting afterwards.
+    pub vector_dtype: VectorDtype,
+    /// Analyzer for a newly created Tantivy index; an existing index keeps its own.
+    pub tantivy_tokenizer: TokenizerKind,
+    /// Weight of chunk heading (`section_path`) matches over body text in Tantivy search.
+    pub tantivy_heading_boost: f32,
+}
+
+/// Reaction to a detected embedding model drift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftAction {
+    /// Log a warning and continue ingesting.
+    Warn,
+    /// Reject the ingest with `ServiceError::EmbedDrift`.
+    Error,
+}
+
+/// Source of vectors for `HybridService::ingest_records`.
+#[derive(Debug, Clone)]
+pub enum EmbedPolicy {
+    /// Use these vectors as-is; records without one are stored but not vector-indexed.
+    UseProvided(Vec<(ChunkId, Vec<f32>)>),
+    /// Embed only records that have no live vector in the HNSW index yet.
+    EmbedMissing,
+    /// Embed every record, replacing existing vectors.
+    EmbedAll,
+}
+
+/// One line of `HybridService::export_ndjson`. `kind` tells files from chunks and the schema
+/// fields let an importer detect the format before decoding `record`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportLine<'a> {
+    File { schema_version: u16, schema_minor: u16, record: &'a FileRecord },
+    Chunk { schema_version: u16, schema_minor: u16, record: &'a ChunkRecord },
+}
+
+/// Owned counterpart of `ExportLine`, as read back by an importer.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ImportLine {
+    File { schema_version: u16, #[serde(default)] schema_minor: u16, record: FileRecord },
+    Chunk { schema_version: u16, #[serde(default)] schema_minor: u16, record: ChunkRecord },
+}
+
+/// Output layout of `HybridService::export_rerank_dataset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RerankExportFormat {
+    /// One `RerankPair` JSON object per line.
+    Jsonl,
+    /// `query<TAB>passage<TAB>label` lines (label empty when unlabeled); tabs and newlines
+    /// inside the texts become spaces.
+    Tsv,
+}
+
+/// One query–passage pair of `HybridService::export_rerank_dataset`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerankPair {
+    pub query: String,
+    pub chunk_id: String,
+    /// 0-based position of the chunk in the logged results.
+    pub rank: usize,
+    pub passage: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<f32>,
+}
+
+/// Weights of `HybridService::search_hybrid_weighted`, one per signal: the default Tantivy
+/// query, its token-AND and token-OR variants, and vector KNN. `None` leaves the signal out
+/// entirely (not searched, contributes no candidates); `Some(0.0)` still adds its candidates at
+/// zero weight. Weights are normalized over the enabled signals after clamping negative or
+/// NaN weights to 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct HybridWeights {
+    pub tv: Option<f32>,
+    pub tv_and: Option<f32>,
+    pub tv_or: Option<f32>,
+    pub vec: Option<f32>,
+}
+
+/// A search hit with the FileRecord of its document (`None` when no file row is stored).
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHitWithFile {
+    pub hit: SearchHit,
+    pub file: Option<FileRecord>,
+}
+
+/// Handling of `extra` keys that collide with known or historical record field names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtraConflictAction {
+    /// Only report the colliding keys; the record is left untouched.
+    Report,
+    /// Report them and move them under `extra["_conflict"]`.
+    Quarantine,
+}
+
+impl ImportLine {
+    /// Check the record's `extra` for colliding keys and apply `action`. Returns the keys found.
+    pub fn resolve_extra_conflicts(&mut self, action: ExtraConflictAction) -> Vec<String> {
+        match (self, action) {
+            (ImportLine::File { record, .. }, ExtraConflictAction::Report) => record.extra_conflicts(),
+            (ImportLine::File { record, .. }, ExtraConflictAction::Quarantine) => record.quarantine_extra_conflicts(),
+            (ImportLine::Chunk { record, .. }, ExtraConflictAction::Report) => record.extra_conflicts(),
+            (ImportLine::Chunk { record, .. }, ExtraConflictAction::Quarantine) => record.quarantine_extra_conflicts(),
+        }
+    }
+}
+
+/// Outcome of `HybridService::import_ndjson`. Line numbers are 1-based.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub files: usize,
+    pub chunks: usize,
+    /// Chunks whose vector was taken from `extra[EXTRA_EMBEDDING_KEY]` instead of the model.
+    pub reused_vectors: usize,
+    /// Lines that were not imported, with the reason.
+    pub skipped: Vec<(usize, String)>,
+    /// Lines whose record carried `extra` keys colliding with known field names.
+    pub conflicts: Vec<(usize, Vec<String>)>,
+}
+
+/// Chunk ids on which an index disagrees with the DB, see `HybridService::index_drift`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IdDrift {
+    /// In the DB but not in the index.
+    pub missing: Vec<ChunkId>,
+    /// In the index but no longer in the DB.
+    pub stale: Vec<ChunkId>,
+}
+
+/// Outcome of `HybridService::index_drift`; `None` for an index that is not built.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexDrift {
+    pub hnsw: Option<IdDrift>,
+    pub tantivy: Option<IdDrift>,
+}
+
+/// What `HybridService::repair` does with chunks whose document has no FileRecord.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OrphanChunkAction {
+    /// Delete them from the DB and every index.
+    #[default]
+    Delete,
+    /// Keep them and write a minimal FileRecord derived from their first chunk.
+    SynthesizeFile,
+}
+
+/// Options for `HybridService::repair`.
+#[derive(Debug, Clone)]
+pub struct RepairOpts {
+    /// Only report what would change.
+    pub dry_run: bool,
+    pub orphan_chunks: OrphanChunkAction,
+    /// Chunks deleted, indexed or embedded per step.
+    pub batch_size: usize,
+}
+
+impl Default for RepairOpts {
+    fn default() -> Self { Self { dry_run: false, orphan_chunks: OrphanChunkAction::Delete, batch_size: 256 } }
+}
+
+/// Outcome of `HybridService::repair`; in a dry run, what would have changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepairReport {
+    pub orphan_chunks_deleted: usize,
+    pub files_synthesized: usize,
+    /// Index entries removed because their chunk is no longer in the DB.
+    pub hnsw_stale_removed: usize,
+    pub tantivy_stale_removed: usize,
+    /// DB chunks added back to an index; HNSW ones without a persisted vector are re-embedded.
+    pub hnsw_reinserted: usize,
+    pub tantivy_reinserted: usize,
+    /// The run was canceled; the counts cover the steps applied before that.
+    pub canceled: bool,
+}
+
+impl RepairReport {
+    /// Total number of changes.
+    pub fn changes(&self) -> usize {
+        self.orphan_chunks_deleted + self.files_synthesized + self.hnsw_stale_removed + self.tantivy_stale_removed + self.hnsw_reinserted + self.tantivy_reinserted
+    }
+}
+
+/// Handling of chunks that fail the unique-token quality gate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityGateAction {
+    /// Skip the chunk entirely (not stored, not embedded).
+    Drop,
+    /// Keep the chunk but set `meta["low_quality"] = "true"`.
+    Tag,
 }
 
 impl Default for ServiceConfig {
@@ -55,6 +371,42 @@ impl Default for ServiceConfig {
             embed_auto: true,
             embed_initial_batch: 128,
             embed_min_batch: 8,
+            tag_chunk_lang: true,
+            read_only: false,
+            hnsw_load_retries: 3,
+            hnsw_load_backoff_ms: 200,
+            max_background_loaders: 2,
+            embed_include_title: false,
+            embed_title_max_doc_chars: 200,
+            content_based_ids: false,
+            progress_min_interval_ms: 0,
+            quality_min_unique_ratio: 0.0,
+            quality_gate: QualityGateAction::Tag,
+            collection_meta_key: None,
+            embed_drift_max_deviation: 0.05,
+            embed_drift_action: DriftAction::Warn,
+            hnsw_compact_tombstone_ratio: 0.2,
+            auto_compact_after_delete_ratio: None,
+            import_extra_conflicts: ExtraConflictAction::Quarantine,
+            group_snippet_chars: 160,
+            persist_vectors_in_records: false,
+            ingest_journal: false,
+            skip_duplicate_sha256: false,
+            hnsw_bulk_build_min: chunking_store::hnsw_index::DEFAULT_BULK_BUILD_MIN,
+            hnsw_metric: HnswMetric::Cosine,
+            hnsw_params: HnswParams::default(),
+            query_embed_cache_size: 256,
+            embed_cache: None,
+            list_files_max_limit: 10_000,
+            doc_blob_max_bytes: 4 * 1024 * 1024,
+            suggest_index_terms: false,
+            suggest_max_terms: 100_000,
+            suggest_record_queries: false,
+            pdf_page_images: false,
+            search_log: false,
+            vector_dtype: VectorDtype::F32,
+            tantivy_tokenizer: TokenizerKind::default(),
+            tantivy_heading_boost: chunking_store::tantivy_index::DEFAULT_HEADING_BOOST,
         }
     }
 }
@@ -78,11 +430,32 @@ pub struct HybridService {
     tantivy_state: Arc<RwLock<TantivyState>>,
     /// Monotonic epoch to invalidate stale background loads when paths change
     store_epoch: Arc<AtomicU64>,
+    /// Bounded executor running the background index loads
+    loaders: LoaderPool,
+    /// Store epoch for which the embedding drift check last passed (0 = never)
+    drift_checked_epoch: AtomicU64,
+    /// Store epoch whose database already has the `collection_meta_key` index (0 = never)
+    meta_index_epoch: AtomicU64,
+    /// Background compaction started by `delete_by_filter` (at most one at a time)
+    compaction: Mutex<Option<std::thread::JoinHandle<()>>>,
+    /// Query embeddings of this service's embedder. The embedder (model path, dimension) is
+    /// fixed for the service's lifetime, so entries never outlive the config they came from.
+    query_cache: Mutex<QueryEmbedCache>,
+    /// Renderer used for `pdf_page_images`
+    page_renderer: RwLock<Arc<dyn PageRenderer>>,
+    /// Opened `embed_cache`, if configured
+    embed_cache: Option<EmbedCache>,
+    /// Serializes index writers (ingest, delete, reindex), so `reindex_all` swaps in indexes
+    /// that no concurrent write has bypassed
+    writer: Mutex<()>,
+    /// `embedder_fingerprint` of the embedder config, fixed for the service's lifetime
+    embedder_fingerprint: String,
 }
 
-/// State of the resident HNSW index in memory.
+/// State of the resident HNSW index in memory. `DimensionMismatch` means the snapshot on
+/// disk was built for another embedding dimension and was left unloaded; reindex to fix.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum HnswState { Absent, Loading, Ready, Error }
+pub enum HnswState { Absent, Loading, Ready, Error, DimensionMismatch { expected: usize, found: usize } }
 
 #[cfg(feature = "tantivy")]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -108,6 +481,54 @@ pub enum ProgressEvent {
     SaveIndexes,
     Finished { total: usize },
     Canceled,
+    /// End of `HybridService::reindex_all`: chunks reindexed and wall time.
+    Reindexed { total: usize, elapsed_ms: u64 },
+    /// The file was not ingested: its content is already stored under `existing_doc_id`
+    /// (see `ServiceConfig::skip_duplicate_sha256`).
+    SkippedDuplicate { existing_doc_id: String },
+}
+
+/// Progress of one file within `HybridService::ingest_files_parallel`.
+#[derive(Debug, Clone)]
+pub struct FileProgress {
+    /// Position of the file in the input list (0-based); `total` for the run-level `Canceled`.
+    pub index: usize,
+    pub total: usize,
+    pub path: String,
+    pub event: ProgressEvent,
+}
+
+/// Wrap a progress callback so it fires at most once per `min_interval`.
+/// Terminal events (`Finished`, `Canceled`, `Reindexed`, `SkippedDuplicate`) always pass
+/// through so the final state is never lost.
+pub fn throttle_progress(
+    cb: Box<dyn FnMut(ProgressEvent) + Send>,
+    min_interval: std::time::Duration,
+) -> Box<dyn FnMut(ProgressEvent) + Send> {
+    throttle_progress_with_clock(cb, min_interval, std::time::Instant::now)
+}
+
+/// `throttle_progress` reading the time from `clock`, called once per event.
+pub fn throttle_progress_with_clock(
+    mut cb: Box<dyn FnMut(ProgressEvent) + Send>,
+    min_interval: std::time::Duration,
+    mut clock: impl FnMut() -> std::time::Instant + Send + 'static,
+) -> Box<dyn FnMut(ProgressEvent) + Send> {
+    let mut last: Option<std::time::Instant> = None;
+    Box::new(move |ev| {
+        let terminal = matches!(
+            ev,
+            ProgressEvent::Finished { .. } | ProgressEvent::Canceled | ProgressEvent::Reindexed { .. } | ProgressEvent::SkippedDuplicate { .. }
+        );
+        // Phase changes are rare and mark where a long step starts; never drop them
+        let phase = matches!(ev, ProgressEvent::IndexVector { .. } | ProgressEvent::SaveIndexes);
+        let now = clock();
+        let due = match last { Some(t) => now.duration_since(t) >= min_interval, None => true };
+        if terminal || phase || due {
+            last = Some(now);
+            cb(ev);
+        }
+    })
 }
 
 impl HybridService {
@@ -142,15 +563,20 @@ impl HybridService {
 
         // Try lazy-load from current directory
         let hdir = self.hnsw_dir();
-        let exists = std::path::Path::new(&hdir).join("map.tsv").exists();
+        let exists = HnswIndex::snapshot_exists(&hdir);
         if exists {
             if let Ok(mut s) = self.hnsw_state.write() { *s = HnswState::Loading; }
-            match HnswIndex::load(&hdir, self.embedder.info().dimension) {
+            match load_hnsw_with_retry(&hdir, self.embedder.info().dimension, self.cfg.hnsw_load_retries, self.cfg.hnsw_load_backoff_ms) {
                 Ok(h) => {
                     let _ = self.hnsw.write().map(|mut w| *w = Some(h));
                     if let Ok(mut s) = self.hnsw_state.write() { *s = HnswState::Ready; }
                 }
-                Err(_) => { let _ = self.hnsw_state.write().map(|mut s| *s = HnswState::Error); }
+                Err(e) => {
+                    let _ = self.hnsw_state.write().map(|mut s| *s = hnsw_error_state(&e));
+                    if let HnswError::DimensionMismatch { expected, found } = e {
+                        return Err(ServiceError::IndexDimensionMismatch { expected, found });
+                    }
+                }
             }
         } else {
             let _ = self.hnsw_state.write().map(|mut s| *s = HnswState::Absent);
@@ -181,6 +607,7 @@ impl HybridService {
         let tantivy_state: Arc<RwLock<TantivyState>> = Arc::new(RwLock::new(TantivyState::Absent));
         // Epoch counter to guard background loads from committing after store change
         let store_epoch: Arc<AtomicU64> = Arc::new(AtomicU64::new(1));
+        let loaders = LoaderPool::new(cfg.max_background_loaders);
 
         // Derive HNSW dir from config and kick preload immediately using configured dimension
         let hdir = match &cfg.hnsw_dir { Some(d) => d.clone(), None => derive_hnsw_dir(&cfg.db_path) };
@@ -193,13 +620,17 @@ impl HybridService {
             let dbp_for_warm = cfg.db_path.clone();
             let epoch_arc = Arc::clone(&store_epoch);
             let aggressive = cfg.aggressive_warmup;
+            let read_only = cfg.read_only;
+            let (retries, backoff_ms) = (cfg.hnsw_load_retries, cfg.hnsw_load_backoff_ms);
             #[cfg(feature = "tantivy")]
             let tv_cache = Arc::clone(&tantivy);
             #[cfg(feature = "tantivy")]
             let tv_state = Arc::clone(&tantivy_state);
-            std::thread::spawn(move || {
+            #[cfg(feature = "tantivy")]
+            let tv_opts = tantivy_opts(&cfg);
+            let epoch_start = epoch_arc.load(Ordering::SeqCst);
+            loaders.submit(epoch_start, move || {
                 // Verify target still current for this service instance (epoch + path)
-                let epoch_start = epoch_arc.load(Ordering::SeqCst);
                 let cur_db = db_arc.read().map(|p| p.clone()).unwrap_or_else(|_| dbp_for_warm.clone());
                 let cur_h = hnsw_arc.read().ok().and_then(|g| g.clone());
                 let cur_hdir = match cur_h { Some(d) => d, None => derive_hnsw_dir(&cur_db) };
@@ -207,7 +638,7 @@ impl HybridService {
                     // Store paths changed; abort without touching state/cache
                     return;
                 }
-                let exists = Path::new(&hdir).join("map.tsv").exists();
+                let exists = HnswIndex::snapshot_exists(&hdir);
                 if !exists {
                     if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; }
                     let _ = state.write().map(|mut s| *s = HnswState::Absent);
@@ -215,7 +646,7 @@ impl HybridService {
                 }
                 if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; }
                 let _ = state.write().map(|mut s| *s = HnswState::Loading);
-                match HnswIndex::load(&hdir, dim_cfg) {
+                match load_hnsw_with_retry(&hdir, dim_cfg, retries, backoff_ms) {
                     Ok(h) => {
                         // Re‑check on commit
                         let cur_db2 = db_arc.read().map(|p| p.clone()).unwrap_or_else(|_| dbp_for_warm.clone());
@@ -225,9 +656,9 @@ impl HybridService {
 
                         let _ = cache.write().map(|mut guard| *guard = Some(h));
                         // Optional KNN warm-up: open repo and run a trivial 1-NN to touch pages
-                        if let Ok(repo) = SqliteRepo::open(&dbp_for_warm) {
+                        if let Ok(repo) = open_repo_at(&dbp_for_warm, read_only) {
                             let qvec = vec![0.0f32; dim_cfg];
-                            let opts = SearchOptions { top_k: 1, fetch_factor: 1 };
+                            let opts = SearchOptions { top_k: 1, fetch_factor: 1, ..Default::default() };
                             if let Ok(guard) = cache.read() {
                                 if let Some(h) = guard.as_ref() {
                                     let _ = VectorSearcher::knn_ids(h, &repo, &qvec, &[], &opts);
@@ -237,7 +668,7 @@ impl HybridService {
                         if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; }
                         let _ = state.write().map(|mut s| *s = HnswState::Ready);
                     }
-                    Err(_) => { if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; } let _ = state.write().map(|mut s| *s = HnswState::Error); }
+                    Err(e) => { if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; } let _ = state.write().map(|mut s| *s = hnsw_error_state(&e)); }
                 }
 
                 // If aggressive OFF, sequentially open Tantivy after HNSW preload
@@ -253,7 +684,7 @@ impl HybridService {
                         let _ = tv_state.write().map(|mut s| *s = TantivyState::Error);
                         return;
                     }
-                    match TantivyIndex::open_or_create_dir(&tdir) {
+                    match TantivyIndex::open_or_create_dir_with_opts(&tdir, tv_opts) {
                         Ok(idx) => {
                             // Re-validate target before commit
                             let cur_db2 = db_arc.read().map(|p| p.clone()).unwrap_or_else(|_| dbp_for_warm.clone());
@@ -286,8 +717,9 @@ impl HybridService {
             let db_arc = Arc::clone(&db_path);
             let dbp_for_warm = cfg.db_path.clone();
             let epoch_arc = Arc::clone(&store_epoch);
-            std::thread::spawn(move || {
-                let epoch_start = epoch_arc.load(Ordering::SeqCst);
+            let tv_opts = tantivy_opts(&cfg);
+            let epoch_start = epoch_arc.load(Ordering::SeqCst);
+            loaders.submit(epoch_start, move || {
                 // Verify that current derived tantivy dir still matches target (epoch + path)
                 let cur_db = db_arc.read().map(|p| p.clone()).unwrap_or_else(|_| dbp_for_warm.clone());
                 let cur_tdir = cur_db.parent().map(|p| p.to_path_buf()).unwrap_or_else(|| PathBuf::from(".")).join("tantivy");
@@ -298,7 +730,7 @@ impl HybridService {
                     let _ = tv_state.write().map(|mut s| *s = TantivyState::Error);
                     return;
                 }
-                match TantivyIndex::open_or_create_dir(&tdir) {
+                match TantivyIndex::open_or_create_dir_with_opts(&tdir, tv_opts) {
                     Ok(idx) => {
                         // Re-check before commit
                         let cur_db2 = db_arc.read().map(|p| p.clone()).unwrap_or_else(|_| dbp_for_warm.clone());
@@ -318,7 +750,13 @@ impl HybridService {
         // Initialize embedder (may run concurrently with HNSW loading)
         let embedder = OnnxStdIoEmbedder::new(cfg.embedder.clone())
             .map_err(|e| ServiceError::Embed(e.to_string()))?;
+        let embedder_fingerprint = embedder_fingerprint(&cfg.embedder);
 
+        let cfg_query_cache = cfg.query_embed_cache_size;
+        let embed_cache = match &cfg.embed_cache {
+            Some(path) => Some(EmbedCache::open(path).map_err(|e| ServiceError::Repo(e.to_string()))?),
+            None => None,
+        };
         let svc = Self {
             cfg,
             embedder,
@@ -333,6 +771,15 @@ impl HybridService {
             #[cfg(feature = "tantivy")]
             tantivy_state,
             store_epoch,
+            loaders,
+            drift_checked_epoch: AtomicU64::new(0),
+            meta_index_epoch: AtomicU64::new(0),
+            compaction: Mutex::new(None),
+            query_cache: Mutex::new(QueryEmbedCache::new(cfg_query_cache)),
+            page_renderer: RwLock::new(Arc::new(PdfiumPageRenderer::default())),
+            embed_cache,
+            writer: Mutex::new(()),
+            embedder_fingerprint,
         };
         // Warm up ONNX session once (best-effort) when aggressive
         if svc.cfg.aggressive_warmup {
@@ -358,6 +805,21 @@ impl HybridService {
         }
     }
 
+    /// `embedder_fingerprint` of the active embedder.
+    pub fn embedding_fingerprint(&self) -> &str {
+        &self.embedder_fingerprint
+    }
+
+    /// Fingerprint of the embedder that built the current HNSW index: the resident index's,
+    /// else the one recorded in the snapshot on disk. `None` when no index exists or it
+    /// predates fingerprints. Compare with `embedding_fingerprint` to warn.
+    pub fn store_embedding_fingerprint(&self) -> Option<String> {
+        if let Some(fp) = self.hnsw.read().ok().and_then(|g| g.as_ref().map(|h| h.model_fingerprint().map(str::to_string))) {
+            return fp;
+        }
+        HnswIndex::snapshot_model_fingerprint(self.hnsw_dir()).ok().flatten()
+    }
+
     /// Current HNSW state (Absent/Loading/Ready/Error).
     pub fn hnsw_state(&self) -> HnswState {
         match self.hnsw_state.read() {
@@ -366,14 +828,206 @@ impl HybridService {
         }
     }
 
+    /// True when the resident HNSW index was loaded from the previous snapshot because the
+    /// current one was unreadable; vectors saved since then are missing until a repair or
+    /// reindex.
+    pub fn hnsw_loaded_from_previous(&self) -> bool {
+        self.hnsw.read().ok().and_then(|g| g.as_ref().map(HnswIndex::loaded_from_previous)).unwrap_or(false)
+    }
+
     fn open_repo(&self) -> Result<SqliteRepo, ServiceError> {
         // Before opening, allow dynamic update of active paths.
         self.ensure_store_paths_from_provider();
         let path = self.db_path.read().map(|p| p.clone()).unwrap_or_else(|_| self.cfg.db_path.clone());
-        let repo = SqliteRepo::open(&path).map_err(|e| ServiceError::Repo(e.to_string()))?;
+        let repo = open_repo_at(&path, self.cfg.read_only)?;
+        if let (false, Some(key)) = (self.cfg.read_only, &self.cfg.collection_meta_key) {
+            // Once per store: a path change bumps the epoch and re-runs it on the new database
+            let epoch = self.store_epoch.load(Ordering::SeqCst);
+            if self.meta_index_epoch.load(Ordering::SeqCst) != epoch {
+                repo.ensure_meta_index(key).map_err(|e| ServiceError::Repo(e.to_string()))?;
+                self.meta_index_epoch.store(epoch, Ordering::SeqCst);
+            }
+        }
         Ok(repo)
     }
 
+    /// Returns true when the service was configured as a read-only replica.
+    pub fn is_read_only(&self) -> bool { self.cfg.read_only }
+
+    /// Tag a chunk with its detected language unless disabled or already present.
+    fn tag_chunk_lang(&self, rec: &mut ChunkRecord) {
+        if !self.cfg.tag_chunk_lang || rec.meta.contains_key(chunk_model::META_LANG) { return; }
+        if let Some(lang) = file_chunker::lang_detect::detect_lang(&rec.text) {
+            rec.meta.insert(chunk_model::META_LANG.to_string(), lang.to_string());
+        }
+    }
+
+    /// Replace path-based ids with those of `file_chunker::IdScheme::ContentHash` when `content_based_ids` is set.
+    fn apply_content_ids(&self, path: &str, file: &mut FileRecord, records: &mut [ChunkRecord]) -> Result<(), ServiceError> {
+        if !self.cfg.content_based_ids { return Ok(()); }
+        let hex = match &file.content_sha256 {
+            Some(h) => h.clone(),
+            None => sha256_hex_file(Path::new(path)).map_err(|e| ServiceError::Io(e.to_string()))?,
+        };
+        file_chunker::assign_doc_id(file, records, &file_chunker::content_hash_doc_id(&hex));
+        file.content_sha256 = Some(hex);
+        Ok(())
+    }
+
+    /// With `skip_duplicate_sha256`, refuse a file whose content hash is already stored,
+    /// reporting `SkippedDuplicate` first. Runs before embedding.
+    fn skip_duplicate(&self, file: &FileRecord, progress: Option<&mut (dyn FnMut(ProgressEvent) + Send)>) -> Result<(), ServiceError> {
+        if !self.cfg.skip_duplicate_sha256 { return Ok(()); }
+        let Some(hex) = file.content_sha256.as_deref() else { return Ok(()) };
+        let existing = self.with_repo(|repo| repo.find_file_by_sha256(hex).map_err(|e| ServiceError::Repo(e.to_string())))?;
+        let Some(existing) = existing else { return Ok(()) };
+        if let Some(cb) = progress { cb(ProgressEvent::SkippedDuplicate { existing_doc_id: existing.doc_id.0.clone() }); }
+        Err(ServiceError::DuplicateContent { doc_id: existing.doc_id.0 })
+    }
+
+    /// Ingest every file under `root` (down to `max_depth` directory levels) in sorted path
+    /// order. `exts` filters by extension (without dot); empty means all files.
+    /// Returns the number of files ingested (files skipped via `ingest_journal` or
+    /// `skip_duplicate_sha256` are not counted).
+    pub fn ingest_folder(&self, root: &Path, max_depth: usize, exts: &[&str]) -> Result<usize, ServiceError> {
+        self.ingest_folder_with_progress(root, max_depth, exts, None, None)
+    }
+
+    /// `ingest_folder` with cancel/progress support. Events carry the file's position in the
+    /// sorted scan; a canceled run stops at the current file, which is not journaled.
+    pub fn ingest_folder_with_progress(
+        &self,
+        root: &Path,
+        max_depth: usize,
+        exts: &[&str],
+        cancel: Option<&CancelToken>,
+        mut progress: Option<Box<dyn FnMut(FileProgress) + Send>>,
+    ) -> Result<usize, ServiceError> {
+        self.ensure_writable()?;
+        self.check_embed_drift()?;
+        let files = scan_folder_sorted(root, max_depth, exts);
+        let total = files.len();
+        let mut ingested = 0usize;
+        for (index, f) in files.iter().enumerate() {
+            let path = f.to_string_lossy().into_owned();
+            // The chunker hashes the file while reading it, so the journal check reuses that hash
+            let out = file_chunker::chunk_file_with_file_record(&path);
+            let journal_sha = if self.cfg.ingest_journal { out.file.content_sha256.clone() } else { None };
+            if let Some(hex) = &journal_sha {
+                if self.with_repo(|repo| repo.ingest_journal_contains(&path, hex).map_err(|e| ServiceError::Repo(e.to_string())))? {
+                    continue;
+                }
+            }
+            let (file, records) = self.prepare_chunked(&path, None, out)?;
+            let mut cb = progress.as_mut().map(|cb| {
+                let path = path.clone();
+                move |event| cb(FileProgress { index, total, path: path.clone(), event })
+            });
+            let progress_ref = cb.as_mut().map(|f| f as &mut (dyn FnMut(ProgressEvent) + Send));
+            // Journaled as completed only once the file is fully indexed; a crash before that
+            // re-ingests it
+            match self.index_chunked(&path, file, records, cancel, progress_ref) {
+                Err(ServiceError::DuplicateContent { .. }) => continue,
+                other => other?,
+            }
+            ingested += 1;
+        }
+        Ok(ingested)
+    }
+
+    /// Content hashes and sizes of every registered file, for telling which files on disk
+    /// are not in the store yet (see `KnownFiles::is_registered`).
+    pub fn known_files(&self, cancel: Option<&CancelToken>) -> Result<KnownFiles, ServiceError> {
+        let mut known = KnownFiles::default();
+        let limit = 1000;
+        let mut offset = 0;
+        loop {
+            if cancel.is_some_and(CancelToken::is_canceled) { return Err(ServiceError::Embed("canceled".into())); }
+            let (list, total) = self.list_files_page(limit, offset)?;
+            if list.is_empty() { break; }
+            for rec in &list {
+                if let Some(h) = &rec.content_sha256 { known.hashes.insert(h.clone()); }
+                if let Some(sz) = rec.file_size_bytes { known.sizes.insert(sz); }
+            }
+            offset += list.len();
+            if offset as u64 >= total { break; }
+        }
+        Ok(known)
+    }
+
+    /// Files under `root` (as `scan_folder_sorted`) whose content is not registered yet.
+    pub fn scan_unregistered(&self, root: &Path, max_depth: usize, exts: &[&str]) -> Result<Vec<PathBuf>, ServiceError> {
+        let known = self.known_files(None)?;
+        let scanned = file_chunker::scan::scan_dir(root, exts, max_depth);
+        Ok(file_chunker::scan::filter_unregistered(scanned, &known.hashes, &known.sizes).into_iter().map(|f| f.path).collect())
+    }
+
+    /// Apply `progress_min_interval_ms` to an optional progress callback.
+    fn throttle(&self, progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>) -> Option<Box<dyn FnMut(ProgressEvent) + Send>> {
+        let ms = self.cfg.progress_min_interval_ms;
+        if ms == 0 { return progress; }
+        progress.map(|cb| throttle_progress(cb, std::time::Duration::from_millis(ms)))
+    }
+
+    /// Vector storage dtype recorded in the store, recording `cfg.vector_dtype` on first use.
+    fn store_vector_dtype(&self, repo: &SqliteRepo) -> Result<VectorDtype, ServiceError> {
+        let stored = repo.get_store_meta(STORE_META_VECTOR_DTYPE).map_err(|e| ServiceError::Repo(e.to_string()))?;
+        match stored {
+            Some(s) => VectorDtype::parse(&s).ok_or_else(|| ServiceError::Repo(format!("unknown vector dtype in store: {s}"))),
+            None => {
+                repo.set_store_meta(STORE_META_VECTOR_DTYPE, self.cfg.vector_dtype.as_str()).map_err(|e| ServiceError::Repo(e.to_string()))?;
+                Ok(self.cfg.vector_dtype)
+            }
+        }
+    }
+
+    /// Dimension the loaded model actually produces, for correcting `embedder.dimension`
+    /// after a `ServiceError::DimensionMismatch`. Runs one embedding of `EMBED_REFERENCE_TEXT`.
+    pub fn detected_embedding_dimension(&self) -> Result<usize, ServiceError> {
+        // `embed_batch` skips the embedder's own dimension check, so a mismatch still reports a length
+        let out = self.embedder.embed_batch(&[EMBED_REFERENCE_TEXT]).map_err(|e| ServiceError::Embed(e.to_string()))?;
+        out.first().map(Vec::len).ok_or_else(|| ServiceError::Embed("missing embedding output".into()))
+    }
+
+    /// Compare the model's embedding of `EMBED_REFERENCE_TEXT` with the reference recorded in
+    /// the store (recording it on first use). Runs once per store epoch.
+    fn check_embed_drift(&self) -> Result<(), ServiceError> {
+        if self.cfg.embed_drift_max_deviation <= 0.0 { return Ok(()); }
+        let epoch = self.store_epoch.load(Ordering::SeqCst);
+        if self.drift_checked_epoch.load(Ordering::SeqCst) == epoch { return Ok(()); }
+        let current = self.embedder.embed(EMBED_REFERENCE_TEXT).map_err(|e| ServiceError::Embed(e.to_string()))?;
+        let stored = self.with_repo(|repo| repo.get_store_meta(STORE_META_EMBED_REFERENCE).map_err(|e| ServiceError::Repo(e.to_string())))?;
+        let reference: Option<Vec<f32>> = stored.and_then(|s| serde_json::from_str(&s).ok());
+        match reference {
+            None => {
+                let json = serde_json::to_string(&current).map_err(|e| ServiceError::Repo(e.to_string()))?;
+                self.with_repo(|repo| repo.set_store_meta(STORE_META_EMBED_REFERENCE, &json).map_err(|e| ServiceError::Repo(e.to_string())))?;
+            }
+            Some(reference) => {
+                let dev = embedding_deviation(&reference, &current);
+                if dev > self.cfg.embed_drift_max_deviation {
+                    match self.cfg.embed_drift_action {
+                        DriftAction::Error => return Err(ServiceError::EmbedDrift(dev)),
+                        DriftAction::Warn => eprintln!("[embed] reference vector deviates by {dev:.4}; the embedding model may have changed"),
+                    }
+                }
+            }
+        }
+        self.drift_checked_epoch.store(epoch, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Reject write paths early when running in read-only mode.
+    fn ensure_writable(&self) -> Result<(), ServiceError> {
+        if self.cfg.read_only { return Err(ServiceError::ReadOnly); }
+        Ok(())
+    }
+
+    /// Take the index writer lock; see `HybridService::writer`.
+    fn lock_writer(&self) -> Result<std::sync::MutexGuard<'_, ()>, ServiceError> {
+        self.writer.lock().map_err(|_| ServiceError::Index("writer lock poisoned".into()))
+    }
+
     /// Install or replace the dynamic store path provider.
     pub fn set_store_path_provider(&self, provider: Arc<dyn Fn() -> (PathBuf, Option<PathBuf>) + Send + Sync>) {
         if let Ok(mut w) = self.store_provider.write() { *w = Some(provider); }
@@ -410,6 +1064,11 @@ impl HybridService {
         base.join("tantivy")
     }
 
+    #[cfg(feature = "tantivy")]
+    fn tantivy_opts(&self) -> TantivyOpts {
+        tantivy_opts(&self.cfg)
+    }
+
     #[cfg(feature = "tantivy")]
     pub fn with_tantivy<R, F>(&self, f: F) -> Result<Option<R>, ServiceError>
     where
@@ -421,7 +1080,7 @@ impl HybridService {
         if need_open {
             let dir = self.tantivy_dir();
             std::fs::create_dir_all(&dir).map_err(|e| ServiceError::Io(e.to_string()))?;
-            match TantivyIndex::open_or_create_dir(&dir) {
+            match TantivyIndex::open_or_create_dir_with_opts(&dir, self.tantivy_opts()) {
                 Ok(idx) => {
                     let _ = self.tantivy.write().map(|mut w| *w = Some(idx));
                     let _ = self.tantivy_state.write().map(|mut s| *s = TantivyState::Ready);
@@ -442,11 +1101,17 @@ impl HybridService {
 
     #[cfg(feature = "tantivy")]
     pub fn tantivy_triple(&self, query: &str, top_k: usize, filters: &[FilterClause]) -> Result<(Vec<chunking_store::TextMatch>, Vec<chunking_store::TextMatch>, Vec<chunking_store::TextMatch>), ServiceError> {
-        let opts = SearchOptions { top_k, fetch_factor: 10 };
+        let opts = SearchOptions { top_k, fetch_factor: 10, ..Default::default() };
+        self.tantivy_triple_with_options(query, filters, &opts)
+    }
+
+    /// `tantivy_triple` with explicit search options, used as-is for all three queries.
+    #[cfg(feature = "tantivy")]
+    pub fn tantivy_triple_with_options(&self, query: &str, filters: &[FilterClause], opts: &SearchOptions) -> Result<(Vec<chunking_store::TextMatch>, Vec<chunking_store::TextMatch>, Vec<chunking_store::TextMatch>), ServiceError> {
         match self.with_tantivy(|ti, repo| {
-            let a = chunking_store::TextSearcher::search_ids(ti, repo, query, filters, &opts);
-            let b = ti.search_ids_tokenized(repo, query, filters, &opts, TokenCombine::AND);
-            let c = ti.search_ids_tokenized(repo, query, filters, &opts, TokenCombine::OR);
+            let a = chunking_store::TextSearcher::search_ids(ti, repo, query, filters, opts);
+            let b = ti.search_ids_tokenized(repo, query, filters, opts, TokenCombine::AND);
+            let c = ti.search_ids_tokenized(repo, query, filters, opts, TokenCombine::OR);
             (a, b, c)
         })? {
             Some(x) => Ok(x),
@@ -481,7 +1146,7 @@ impl HybridService {
             let _ = self.tantivy_state.write().map(|mut s| *s = TantivyState::Absent);
         }
         // Bump epoch to invalidate in-flight background loads for previous paths
-        let _ = self.store_epoch.fetch_add(1, Ordering::SeqCst);
+        let epoch_start = self.store_epoch.fetch_add(1, Ordering::SeqCst) + 1;
         let hdir = self.hnsw_dir();
         let dim = self.embedder.info().dimension;
         let db_for_warm = self.db_path.read().map(|p| p.clone()).unwrap_or_else(|_| self.cfg.db_path.clone());
@@ -491,26 +1156,28 @@ impl HybridService {
         let h_arc = Arc::clone(&self.hnsw_dir_override);
         let epoch_arc = Arc::clone(&self.store_epoch);
         let aggressive = self.cfg.aggressive_warmup;
+        let read_only = self.cfg.read_only;
+        let (retries, backoff_ms) = (self.cfg.hnsw_load_retries, self.cfg.hnsw_load_backoff_ms);
         #[cfg(feature = "tantivy")]
         let tv_cache = Arc::clone(&self.tantivy);
         #[cfg(feature = "tantivy")]
         let tv_state = Arc::clone(&self.tantivy_state);
-        std::thread::spawn(move || {
-            let epoch_start = epoch_arc.load(Ordering::SeqCst);
+        #[cfg(feature = "tantivy")]
+        let tv_opts = self.tantivy_opts();
+        self.loaders.submit(epoch_start, move || {
             // Verify target still current
             let cur_db = db_arc.read().map(|p| p.clone()).unwrap_or_else(|_| db_for_warm.clone());
             let cur_h = h_arc.read().ok().and_then(|g| g.clone());
             let cur_hdir = match cur_h { Some(d) => d, None => derive_hnsw_dir(&cur_db) };
             if cur_hdir != hdir || epoch_arc.load(Ordering::SeqCst) != epoch_start { return; }
-            let idx = Path::new(&hdir).join("map.tsv");
-            if !idx.exists() {
+            if !HnswIndex::snapshot_exists(&hdir) {
                 if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; }
                 let _ = state.write().map(|mut s| *s = HnswState::Absent);
                 return;
             }
             if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; }
             let _ = state.write().map(|mut s| *s = HnswState::Loading);
-            match HnswIndex::load(&hdir, dim) {
+            match load_hnsw_with_retry(&hdir, dim, retries, backoff_ms) {
                 Ok(h) => {
                     // Re-check before commit
                     let cur_db2 = db_arc.read().map(|p| p.clone()).unwrap_or_else(|_| db_for_warm.clone());
@@ -519,9 +1186,9 @@ impl HybridService {
                     if cur_hdir2 != hdir || epoch_arc.load(Ordering::SeqCst) != epoch_start { return; }
                     let _ = cache.write().map(|mut w| *w = Some(h));
                     // KNN warm-up
-                    if let Ok(repo) = SqliteRepo::open(&db_for_warm) {
+                    if let Ok(repo) = open_repo_at(&db_for_warm, read_only) {
                         let qvec = vec![0.0f32; dim];
-                        let opts = SearchOptions { top_k: 1, fetch_factor: 1 };
+                        let opts = SearchOptions { top_k: 1, fetch_factor: 1, ..Default::default() };
                         if let Ok(guard) = cache.read() {
                             if let Some(h) = guard.as_ref() {
                                 let _ = VectorSearcher::knn_ids(h, &repo, &qvec, &[], &opts);
@@ -531,7 +1198,7 @@ impl HybridService {
                     if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; }
                     let _ = state.write().map(|mut s| *s = HnswState::Ready);
                 }
-                Err(_) => { if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; } let _ = state.write().map(|mut s| *s = HnswState::Error); }
+                Err(e) => { if epoch_arc.load(Ordering::SeqCst) != epoch_start { return; } let _ = state.write().map(|mut s| *s = hnsw_error_state(&e)); }
             }
 
             // If aggressive OFF, sequentially open Tantivy after HNSW reload
@@ -547,7 +1214,7 @@ impl HybridService {
                     let _ = tv_state.write().map(|mut s| *s = TantivyState::Error);
                     return;
                 }
-                match TantivyIndex::open_or_create_dir(&tdir) {
+                match TantivyIndex::open_or_create_dir_with_opts(&tdir, tv_opts) {
                     Ok(idx) => {
                         // Re-check before commit
                         let cur_db2 = db_arc.read().map(|p| p.clone()).unwrap_or_else(|_| db_for_warm.clone());
@@ -572,8 +1239,8 @@ impl HybridService {
             let tv_state = Arc::clone(&self.tantivy_state);
             let db_arc2 = Arc::clone(&self.db_path);
             let epoch_arc2 = Arc::clone(&self.store_epoch);
-            std::thread::spawn(move || {
-                let epoch_start = epoch_arc2.load(Ordering::SeqCst);
+            let tv_opts = self.tantivy_opts();
+            self.loaders.submit(epoch_start, move || {
                 // Verify still current derived tantivy dir before state changes
                 let cur_db = db_arc2.read().map(|p| p.clone()).unwrap_or_else(|_| PathBuf::from("."));
                 let cur_tdir = cur_db.parent().map(|p| p.to_path_buf()).unwrap_or_else(|| PathBuf::from(".")).join("tantivy");
@@ -584,7 +1251,7 @@ impl HybridService {
                     let _ = tv_state.write().map(|mut s| *s = TantivyState::Error);
                     return;
                 }
-                match TantivyIndex::open_or_create_dir(&tdir) {
+                match TantivyIndex::open_or_create_dir_with_opts(&tdir, tv_opts) {
                     Ok(idx) => {
                         // Re-check before commit
                         let cur_db2 = db_arc2.read().map(|p| p.clone()).unwrap_or_else(|_| PathBuf::from("."));
@@ -602,6 +1269,12 @@ impl HybridService {
         }
     }
 
+    /// Block until all queued and running background index loads have finished.
+    pub fn wait_for_background_loads(&self) { self.loaders.wait_idle(); }
+
+    /// Highest number of background index loads that ran concurrently so far.
+    pub fn peak_background_loads(&self) -> usize { self.loaders.peak() }
+
     #[cfg(feature = "tantivy")]
     pub fn tantivy_state(&self) -> TantivyState {
         match self.tantivy_state.read() {
@@ -610,10 +1283,47 @@ impl HybridService {
         }
     }
 
-    /// Ingest pre-built chunks with optional precomputed vectors.
+    /// Ingest pre-built chunks with optional precomputed vectors into the DB, text indexes and HNSW.
     pub fn ingest_chunks(&self, records: &[ChunkRecord], vectors: Option<&[(ChunkId, Vec<f32>)]>) -> Result<(), ServiceError> {
+        self.ingest_chunks_with_progress(records, vectors, None, None)
+    }
+
+    /// `ingest_chunks` emitting `IndexVector` before the HNSW upsert and `SaveIndexes` before
+    /// the snapshot is written, when vectors are given. `file` is written in the same
+    /// transaction as the chunks.
+    fn ingest_chunks_with_progress(
+        &self,
+        records: &[ChunkRecord],
+        vectors: Option<&[(ChunkId, Vec<f32>)]>,
+        file: Option<&FileRecord>,
+        mut progress: Option<&mut (dyn FnMut(ProgressEvent) + Send)>,
+    ) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
         if records.is_empty() { return Ok(()); }
+        let _writer = self.lock_writer()?;
+        let persist = self.cfg.persist_vectors_in_records && vectors.is_some();
         let mut repo = self.open_repo()?;
+        let dtype = self.store_vector_dtype(&repo)?;
+        let tagged: Vec<ChunkRecord>;
+        let records: &[ChunkRecord] = if self.cfg.tag_chunk_lang || persist {
+            let by_id: HashMap<&str, &Vec<f32>> = if persist {
+                vectors.unwrap_or_default().iter().map(|(cid, v)| (cid.0.as_str(), v)).collect()
+            } else {
+                HashMap::new()
+            };
+            tagged = records
+                .iter()
+                .cloned()
+                .map(|mut r| {
+                    self.tag_chunk_lang(&mut r);
+                    if let Some(v) = by_id.get(r.chunk_id.0.as_str()) { persist_vector(&mut r, v, dtype); }
+                    r
+                })
+                .collect();
+            &tagged
+        } else {
+            records
+        };
 
         // Prepare text index maintainers (optional FTS)
         #[cfg(feature = "fts")]
@@ -625,87 +1335,108 @@ impl HybridService {
 
         // Prepare/load HNSW
         let hdir = self.hnsw_dir();
-        let mut hnsw = if Path::new(&hdir).join("map.tsv").exists() {
-            HnswIndex::load(&hdir, self.embedder.info().dimension).map_err(|e| ServiceError::Io(e.to_string()))?
+        let mut hnsw = if HnswIndex::snapshot_exists(&hdir) {
+            HnswIndex::load(&hdir, self.embedder.info().dimension).map_err(hnsw_load_error)?
         } else {
-            HnswIndex::new(self.embedder.info().dimension, 10_000)
+            self.new_hnsw()
         };
+        hnsw.set_bulk_build_min(self.cfg.hnsw_bulk_build_min);
+        hnsw.set_dtype(dtype);
         let mut vec_m: [&mut dyn chunking_store::VectorIndexMaintainer; 1] = [&mut hnsw];
 
-        ingest_chunks_orchestrated(&mut repo, records, &text_m, &mut vec_m, vectors)
-            .map_err(|e| ServiceError::Index(e.to_string()))?;
-
-        // Persist HNSW snapshot if we touched vectors
+        // The DB commits last: the HNSW snapshot is saved and Tantivy takes the batch while the
+        // rows are still uncommitted, and a failure before the commit rolls the DB back
+        #[cfg(feature = "tantivy")]
+        self.with_tantivy(|_, _| ())?; // opens its own repo, so not inside the transaction
+        if let (Some(v), Some(cb)) = (vectors, progress.as_deref_mut()) { cb(ProgressEvent::IndexVector { total: v.len() }); }
+        let staged = stage_ingest_chunks(&mut repo, records, &text_m, &mut vec_m, vectors).map_err(orchestrator_error)?;
+        if let Some(file) = file {
+            if let Err(e) = staged.upsert_file(file) { return Err(orchestrator_error(staged.abort(e.to_string()))); }
+        }
+        if self.cfg.suggest_index_terms {
+            // Committed with the chunks, but best-effort: losing autocomplete terms must not fail the ingest
+            let terms = suggest_term_counts(records.iter().map(|r| r.text.as_str()));
+            let _ = staged.add_suggest_terms(&terms, self.cfg.suggest_max_terms);
+        }
         if vectors.is_some() {
-            hnsw.save(&hdir).map_err(|e| ServiceError::Io(e.to_string()))?;
+            if let Some(cb) = progress { cb(ProgressEvent::SaveIndexes); }
+            if let Err(e) = hnsw.save(&hdir) { return Err(orchestrator_error(staged.abort(format!("saving HNSW snapshot: {e}")))); }
         }
+        // Keep Tantivy in step with the DB for every ingestion path
+        #[cfg(feature = "tantivy")]
+        let tantivy = self.tantivy.read().map_err(|_| ServiceError::Index("tantivy lock poisoned".into()))?;
+        #[cfg(feature = "tantivy")]
+        let staged = match tantivy.as_ref() {
+            Some(ti) => staged.upsert_text_index("tantivy", ti, records).map_err(orchestrator_error)?,
+            None => staged,
+        };
+        staged.commit().map_err(orchestrator_error)?;
+        // Ensure FTS5 is populated in rare cases where triggers lag at first creation
+        let _ = repo.maybe_rebuild_fts();
+
         // Refresh resident cache and state
         if let Ok(mut guard) = self.hnsw.write() { *guard = Some(hnsw); }
         let _ = self.hnsw_state.write().map(|mut s| *s = HnswState::Ready);
         Ok(())
     }
 
+    /// Ingest pre-chunked records, choosing where their vectors come from. Validates provided
+    /// vectors (known chunk ids, model dimension); indexing is shared with `ingest_chunks`.
+    pub fn ingest_records(&self, records: &[ChunkRecord], embed: EmbedPolicy) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        if records.is_empty() { return Ok(()); }
+        let dim = self.embedder.info().dimension;
+        let to_embed: Vec<&ChunkRecord> = match &embed {
+            EmbedPolicy::UseProvided(_) => Vec::new(),
+            EmbedPolicy::EmbedAll => records.iter().collect(),
+            EmbedPolicy::EmbedMissing => {
+                let present: HashSet<String> = self
+                    .with_hnsw(|h, _| records.iter().filter(|r| h.contains(&r.chunk_id.0)).map(|r| r.chunk_id.0.clone()).collect())?
+                    .unwrap_or_default();
+                records.iter().filter(|r| !present.contains(&r.chunk_id.0)).collect()
+            }
+        };
+        let mut pairs: Vec<(ChunkId, Vec<f32>)> = match embed {
+            EmbedPolicy::UseProvided(vectors) => {
+                let known: HashSet<&str> = records.iter().map(|r| r.chunk_id.0.as_str()).collect();
+                if let Some((cid, _)) = vectors.iter().find(|(cid, _)| !known.contains(cid.0.as_str())) {
+                    return Err(ServiceError::Embed(format!("vector for unknown chunk id {}", cid.0)));
+                }
+                check_embedding_dimensions(dim, vectors.iter().map(|(_, v)| v.as_slice()))?;
+                vectors
+            }
+            _ => Vec::new(),
+        };
+        if !to_embed.is_empty() {
+            self.check_embed_drift()?;
+            let texts: Vec<&str> = to_embed.iter().map(|r| r.text.as_str()).collect();
+            let (vecs, _) = if self.cfg.embed_auto {
+                self.embed_texts_auto(&texts, None, None)?
+            } else {
+                self.embed_texts_batched(&texts, None, None)?
+            };
+            pairs.extend(to_embed.iter().map(|r| r.chunk_id.clone()).zip(vecs));
+        }
+        let vectors = if pairs.is_empty() { None } else { Some(pairs.as_slice()) };
+        self.ingest_chunks(records, vectors)
+    }
+
     /// Ingest a file by path with progress/cancel support: chunk -> embed -> upsert -> index.
     pub fn ingest_file_with_progress(
         &self,
         path: &str,
         doc_id_hint: Option<&str>,
         cancel: Option<&CancelToken>,
-        mut progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
+        progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
     ) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        self.check_embed_drift()?;
+        let mut progress = self.throttle(progress);
         let out = file_chunker::chunk_file_with_file_record(path);
-        let mut file: FileRecord = out.file;
-        let mut records = out.chunks;
-
-        // Stamp timestamps and optional doc_id override
-        let now = Utc::now().to_rfc3339();
-        for rec in &mut records {
-            if let Some(h) = doc_id_hint { rec.doc_id = DocumentId(h.to_string()); }
-            rec.extracted_at = now.clone();
-        }
-        // FileRecord stamps
-        if let Some(h) = doc_id_hint { file.doc_id = DocumentId(h.to_string()); }
-        file.extracted_at = now.clone();
-        file.chunk_count = Some(records.len() as u32);
-
-        // Upsert FileRecord before chunk/vectors
-        self.with_repo(|repo| repo.upsert_file(&file).map_err(|e| ServiceError::Repo(e.to_string())))?;
-
-        if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Start { total_chunks: records.len() }); }
-        if let Some(ct) = cancel { if ct.is_canceled() { if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Canceled); } return Err(ServiceError::Embed("canceled".into())); } }
-
-        // Embed text (auto or fixed batches) to control memory
-        let texts: Vec<&str> = records.iter().map(|c| c.text.as_str()).collect();
-        let vecs = if self.cfg.embed_auto {
-            let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
-                progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
-            self.embed_texts_auto(&texts, cancel, cb_opt)?
-        } else {
-            let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
-                progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
-            self.embed_texts_batched(&texts, cancel, cb_opt)?
-        };
-        if vecs.iter().any(|v| v.len() != self.embedder.info().dimension) {
-            return Err(ServiceError::Embed("embedding dimension mismatch".into()));
-        }
-        let pairs: Vec<(ChunkId, Vec<f32>)> = records
-            .iter()
-            .zip(vecs.into_iter())
-            .map(|(r, v)| (r.chunk_id.clone(), v))
-            .collect();
-        if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::UpsertDb { total: records.len() }); }
-        self.ingest_chunks(&records, Some(&pairs))
-            .and_then(|_| {
-                #[cfg(feature = "tantivy")]
-                { let _ = self.with_tantivy(|ti, _repo| { let _ = ti.upsert_records(&records); () }); }
-                if let Some(cb) = progress.as_deref_mut() {
-                    cb(ProgressEvent::IndexText { total: records.len() });
-                }
-                if let Some(cb) = progress.as_deref_mut() {
-                    cb(ProgressEvent::Finished { total: records.len() });
-                }
-                Ok(())
-            })
+        let (file, records) = self.prepare_chunked(path, doc_id_hint, out)?;
+        let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
+            progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
+        self.index_chunked(path, file, records, cancel, cb_opt)
     }
 
     /// Variant of ingest_file_with_progress that allows specifying text encoding for text-like files.
@@ -715,56 +1446,17 @@ impl HybridService {
         doc_id_hint: Option<&str>,
         encoding: Option<&str>,
         cancel: Option<&CancelToken>,
-        mut progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
+        progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
     ) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        self.check_embed_drift()?;
+        let mut progress = self.throttle(progress);
         // Use encoding-aware path for text-like files; for others it's identical
         let out = file_chunker::chunk_file_with_file_record_with_encoding(path, encoding);
-        let mut file: FileRecord = out.file;
-        let mut records = out.chunks;
-
-        // Stamp timestamps and optional doc_id override
-        let now = Utc::now().to_rfc3339();
-        for rec in &mut records {
-            if let Some(h) = doc_id_hint { rec.doc_id = DocumentId(h.to_string()); }
-            rec.extracted_at = now.clone();
-        }
-        if let Some(h) = doc_id_hint { file.doc_id = DocumentId(h.to_string()); }
-        file.extracted_at = now.clone();
-        file.chunk_count = Some(records.len() as u32);
-
-        self.with_repo(|repo| repo.upsert_file(&file).map_err(|e| ServiceError::Repo(e.to_string())))?;
-
-        if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Start { total_chunks: records.len() }); }
-        if let Some(ct) = cancel { if ct.is_canceled() { if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Canceled); } return Err(ServiceError::Embed("canceled".into())); } }
-
-        // Embed text (auto or fixed batches) to control memory
-        let texts: Vec<&str> = records.iter().map(|c| c.text.as_str()).collect();
-        let vecs = if self.cfg.embed_auto {
-            let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
-                progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
-            self.embed_texts_auto(&texts, cancel, cb_opt)?
-        } else {
-            let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
-                progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
-            self.embed_texts_batched(&texts, cancel, cb_opt)?
-        };
-        if vecs.iter().any(|v| v.len() != self.embedder.info().dimension) {
-            return Err(ServiceError::Embed("embedding dimension mismatch".into()));
-        }
-        let pairs: Vec<(ChunkId, Vec<f32>)> = records
-            .iter()
-            .zip(vecs.into_iter())
-            .map(|(r, v)| (r.chunk_id.clone(), v))
-            .collect();
-        if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::UpsertDb { total: records.len() }); }
-        self.ingest_chunks(&records, Some(&pairs))
-            .and_then(|_| {
-                #[cfg(feature = "tantivy")]
-                { let _ = self.with_tantivy(|ti, _repo| { let _ = ti.upsert_records(&records); () }); }
-                if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::IndexText { total: records.len() }); }
-                if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Finished { total: records.len() }); }
-                Ok(())
-            })
+        let (file, records) = self.prepare_chunked(path, doc_id_hint, out)?;
+        let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
+            progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
+        self.index_chunked(path, file, records, cancel, cb_opt)
     }
 
     /// Ingest with explicit chunking parameters (min/max/cap and penalties) and optional encoding for text-like files.
@@ -780,7 +1472,7 @@ impl HybridService {
         penalize_short_line: bool,
         penalize_page_boundary_no_newline: bool,
         cancel: Option<&CancelToken>,
-        mut progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
+        progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
     ) -> Result<(), ServiceError> {
         let tparams = file_chunker::text_segmenter::TextChunkParams {
             min_chars,
@@ -789,8 +1481,111 @@ impl HybridService {
             penalize_short_line,
             penalize_page_boundary_no_newline,
             short_merge_min_chars,
+            keep_code_blocks: true,
+            overlap_chars: 0,
+            token_counter: None,
         };
-        let out = file_chunker::chunk_file_with_file_record_with_params(path, encoding, &tparams);
+        let opts = ChunkOptions { encoding: encoding.map(|s| s.to_string()), params: Some(tparams), force_mime: None, text_postprocess: None, infer_headings: None, id_scheme: Default::default() };
+        self.ingest_file_with_options(path, doc_id_hint, &opts, cancel, progress)
+    }
+
+    /// Chunking params measured with this service's embedder tokenizer, keeping every chunk
+    /// within `budget` tokens (leave room below the model's input limit for special tokens).
+    pub fn token_chunk_params(&self, budget: usize) -> file_chunker::text_segmenter::TextChunkParams {
+        file_chunker::text_segmenter::TextChunkParams::for_token_budget(self.embedder.token_counter(), budget)
+    }
+
+    /// Ingest a file with full chunking options (encoding, segmentation params, forced MIME).
+    pub fn ingest_file_with_options(
+        &self,
+        path: &str,
+        doc_id_hint: Option<&str>,
+        opts: &ChunkOptions,
+        cancel: Option<&CancelToken>,
+        progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
+    ) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        self.check_embed_drift()?;
+        let mut progress = self.throttle(progress);
+        let out = file_chunker::chunk_file_with_file_record_with_options(path, opts)
+            .map_err(|e| ServiceError::UnsupportedMime(e.to_string()))?;
+        let (file, records) = self.prepare_chunked(path, doc_id_hint, out)?;
+        let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
+            progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
+        self.index_chunked(path, file, records, cancel, cb_opt)
+    }
+
+    /// Ingest many `(path, doc_id_hint)` files: up to `concurrency` workers chunk files in parallel
+    /// while the calling thread, as the single writer, embeds and indexes them one at a time in
+    /// completion order. Events carry the file's input index; each file ends with `Finished`, and
+    /// a canceled run ends with one `Canceled`, a duplicate skipped via `skip_duplicate_sha256`
+    /// with `SkippedDuplicate`. Returns the number of files ingested; the first other error
+    /// stops the run (files already written stay ingested).
+    pub fn ingest_files_parallel(
+        &self,
+        paths: &[(String, Option<String>)],
+        concurrency: usize,
+        cancel: Option<&CancelToken>,
+        mut progress: Option<Box<dyn FnMut(FileProgress) + Send>>,
+    ) -> Result<usize, ServiceError> {
+        self.ensure_writable()?;
+        self.check_embed_drift()?;
+        let total = paths.len();
+        let workers = concurrency.clamp(1, total.max(1));
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let halted = || stop.load(Ordering::Relaxed) || cancel.is_some_and(CancelToken::is_canceled);
+        type Prepared = Result<(FileRecord, Vec<ChunkRecord>), ServiceError>;
+        // Bounded so workers cannot run far ahead of the writer
+        let (tx, rx) = std::sync::mpsc::sync_channel::<(usize, Prepared)>(workers);
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                let tx = tx.clone();
+                let (next, halted) = (&next, &halted);
+                scope.spawn(move || {
+                    while !halted() {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((path, hint)) = paths.get(i) else { break };
+                        let out = file_chunker::chunk_file_with_file_record(path);
+                        if tx.send((i, self.prepare_chunked(path, hint.as_deref(), out))).is_err() { break; }
+                    }
+                });
+            }
+            drop(tx);
+            let mut result = Ok(0);
+            for (index, prepared) in rx.iter() {
+                if cancel.is_some_and(CancelToken::is_canceled) { break; }
+                let path = &paths[index].0;
+                // A file interrupted by cancellation is reported by the run-level `Canceled` below
+                let mut cb = progress.as_mut().map(|cb| move |event| {
+                    if !matches!(event, ProgressEvent::Canceled) { cb(FileProgress { index, total, path: path.clone(), event }) }
+                });
+                let progress_ref = cb.as_mut().map(|f| f as &mut (dyn FnMut(ProgressEvent) + Send));
+                let written = prepared.and_then(|(file, records)| self.index_chunked(path, file, records, cancel, progress_ref));
+                match (written, &mut result) {
+                    (Ok(()), Ok(n)) => *n += 1,
+                    (Err(ServiceError::DuplicateContent { .. }), _) => {}
+                    (Err(_), _) if cancel.is_some_and(CancelToken::is_canceled) => break,
+                    (Err(e), _) => { result = Err(e); break; }
+                    _ => {}
+                }
+            }
+            // Release workers blocked on a full channel before the scope joins them
+            stop.store(true, Ordering::Relaxed);
+            drop(rx);
+            if result.is_ok() && cancel.is_some_and(CancelToken::is_canceled) {
+                if let Some(cb) = progress.as_deref_mut() {
+                    cb(FileProgress { index: total, total, path: String::new(), event: ProgressEvent::Canceled });
+                }
+                return Err(ServiceError::Embed("canceled".into()));
+            }
+            result
+        })
+    }
+
+    /// Stamp freshly chunked records: doc id hint, timestamps, language tags, quality gate and
+    /// content-based ids. Safe to run on worker threads.
+    fn prepare_chunked(&self, path: &str, doc_id_hint: Option<&str>, out: file_chunker::ChunkOutput) -> Result<(FileRecord, Vec<ChunkRecord>), ServiceError> {
         let mut file: FileRecord = out.file;
         let mut records = out.chunks;
 
@@ -799,21 +1594,82 @@ impl HybridService {
         for rec in &mut records {
             if let Some(h) = doc_id_hint { rec.doc_id = DocumentId(h.to_string()); }
             rec.extracted_at = now.clone();
+            self.tag_chunk_lang(rec);
         }
+        apply_quality_gate(&self.cfg, &mut records);
         if let Some(h) = doc_id_hint { file.doc_id = DocumentId(h.to_string()); }
         file.extracted_at = now.clone();
         file.chunk_count = Some(records.len() as u32);
+        record_file_facts(path, &mut file);
+        if doc_id_hint.is_none() { self.apply_content_ids(path, &mut file, &mut records)?; }
+        Ok((file, records))
+    }
 
-        self.with_repo(|repo| repo.upsert_file(&file).map_err(|e| ServiceError::Repo(e.to_string())))?;
+    /// Embed prepared records and write the file, chunks and vectors to the DB and indexes.
+    fn index_chunked(
+        &self,
+        path: &str,
+        mut file: FileRecord,
+        mut records: Vec<ChunkRecord>,
+        cancel: Option<&CancelToken>,
+        mut progress: Option<&mut (dyn FnMut(ProgressEvent) + Send)>,
+    ) -> Result<(), ServiceError> {
+        self.skip_duplicate(&file, progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send)))?;
+        let journal = self.journal_begin(path, &file)?;
+        let pairs = self.embed_chunked(path, &mut file, &mut records, cancel, progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send)))?;
 
+        // The FileRecord (now with total_tokens and the embedder) commits with the chunks
+        self.stamp_embedder(&mut file);
+        if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::UpsertDb { total: records.len() }); }
+        self.ingest_chunks_with_progress(&records, Some(&pairs), Some(&file), progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send)))?;
+        journal.finish()?;
+        if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::IndexText { total: records.len() }); }
+        if let Some(cb) = progress { cb(ProgressEvent::Finished { total: records.len() }); }
+        Ok(())
+    }
 
-        if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Start { total_chunks: records.len() }); }
-        if let Some(ct) = cancel { if ct.is_canceled() { if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Canceled); } return Err(ServiceError::Embed("canceled".into())); } }
+    /// Record in `ingest_journal` that `path` is being ingested as `file`; see `recover`.
+    fn journal_begin(&self, path: &str, file: &FileRecord) -> Result<IngestJournalEntry<'_>, ServiceError> {
+        let entry = IngestJournalEntry { svc: self, path: path.to_string(), content_sha256: file.content_sha256.clone(), done: false };
+        let now = Utc::now().to_rfc3339();
+        self.with_repo(|repo| {
+            repo.record_ingest_started(path, file.content_sha256.as_deref(), &file.doc_id.0, &now).map_err(|e| ServiceError::Repo(e.to_string()))
+        })?;
+        Ok(entry)
+    }
 
-        // Embed
-        let texts: Vec<&str> = records.iter().map(|c| c.text.as_str()).collect();
-        let vecs = if self.cfg.embed_auto {
-            let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
+    /// File ingests that started but never finished or failed, i.e. were cut off by a crash,
+    /// read from the store's `ingest_journal` in start order. Meant for startup, to re-queue
+    /// them; an entry stays until its path is ingested again.
+    pub fn recover(&self) -> Result<Vec<InterruptedIngest>, ServiceError> {
+        let started = self.with_repo(|repo| repo.list_started_ingests().map_err(|e| ServiceError::Repo(e.to_string())))?;
+        Ok(started
+            .into_iter()
+            .map(|e| InterruptedIngest { path: e.source_uri, doc_id: e.doc_id, content_sha256: e.content_sha256, started_at: e.started_at })
+            .collect())
+    }
+
+    /// Embed prepared records (attaching PDF page images first when configured) and stamp token
+    /// counts on them. Emits `Start` and `EmbedBatch`; writes nothing.
+    fn embed_chunked(
+        &self,
+        path: &str,
+        file: &mut FileRecord,
+        records: &mut [ChunkRecord],
+        cancel: Option<&CancelToken>,
+        mut progress: Option<&mut (dyn FnMut(ProgressEvent) + Send)>,
+    ) -> Result<Vec<(ChunkId, Vec<f32>)>, ServiceError> {
+        if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Start { total_chunks: records.len() }); }
+        if let Some(ct) = cancel { if ct.is_canceled() { if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Canceled); } return Err(ServiceError::Embed("canceled".into())); } }
+        if self.cfg.pdf_page_images && file.source_mime == "application/pdf" {
+            self.attach_page_images(path, records, cancel)?;
+        }
+
+        // Embed
+        let inputs = embedding_inputs(&self.cfg, file, records);
+        let texts: Vec<&str> = inputs.iter().map(|t| t.as_ref()).collect();
+        let (vecs, tokens) = if self.cfg.embed_auto {
+            let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
                 progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
             self.embed_texts_auto(&texts, cancel, cb_opt)?
         } else {
@@ -821,27 +1677,10 @@ impl HybridService {
                 progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
             self.embed_texts_batched(&texts, cancel, cb_opt)?
         };
-        if vecs.iter().any(|v| v.len() != self.embedder.info().dimension) {
-            return Err(ServiceError::Embed("embedding dimension mismatch".into()));
-        }
-        let pairs: Vec<(ChunkId, Vec<f32>)> = records
-            .iter()
-            .zip(vecs.into_iter())
-            .map(|(r, v)| (r.chunk_id.clone(), v))
-            .collect();
-        if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::UpsertDb { total: records.len() }); }
-        self.ingest_chunks(&records, Some(&pairs))
-            .and_then(|_| {
-                #[cfg(feature = "tantivy")]
-                { let _ = self.with_tantivy(|ti, _repo| { let _ = ti.upsert_records(&records); () }); }
-                if let Some(cb) = progress.as_deref_mut() {
-                    cb(ProgressEvent::IndexText { total: records.len() });
-                }
-                if let Some(cb) = progress.as_deref_mut() {
-                    cb(ProgressEvent::Finished { total: records.len() });
-                }
-                Ok(())
-            })
+        check_embedding_dimensions(self.embedder.info().dimension, vecs.iter().map(Vec::as_slice))?;
+        let plain_inputs = inputs.iter().all(|t| matches!(t, Cow::Borrowed(_)));
+        self.apply_token_counts(file, records, if plain_inputs { Some(tokens) } else { None });
+        Ok(records.iter().map(|r| r.chunk_id.clone()).zip(vecs).collect())
     }
 
     /// Backwards compatible wrapper without progress/cancel.
@@ -849,17 +1688,60 @@ impl HybridService {
         self.ingest_file_with_progress(path, doc_id_hint, None, None)
     }
 
+    /// Replace the content of `doc_id` with a fresh ingest of `path` and bump its
+    /// `doc_revision`; old revisions are not kept. The new chunks are embedded before anything
+    /// is written, then upserted, and only then are the previous chunks they did not overwrite
+    /// deleted from the DB and indexes (in `delete_by_filter`-sized batches). The FileRecord
+    /// with the new revision goes last, so after a crash the old revision is still recorded
+    /// and re-running `reingest_file` completes the swap. An unknown `doc_id` is ingested as new.
+    pub fn reingest_file(&self, path: &str, doc_id: &str) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        self.check_embed_drift()?;
+        let out = file_chunker::chunk_file_with_file_record(path);
+        let (mut file, mut records) = self.prepare_chunked(path, Some(doc_id), out)?;
+        let previous = self.with_repo(|repo| repo.get_file(doc_id).map_err(|e| ServiceError::Repo(e.to_string())))?;
+        if let Some(prev) = &previous {
+            file.doc_revision = Some(prev.doc_revision.unwrap_or(1) + 1);
+        }
+        let pairs = self.embed_chunked(path, &mut file, &mut records, None, None)?;
+
+        let filters = [FilterClause { kind: chunking_store::FilterKind::Must, op: chunking_store::FilterOp::DocIdEq(doc_id.to_string()) }];
+        let batch = 1000;
+        let mut old_ids: Vec<ChunkId> = Vec::new();
+        self.with_repo(|repo| {
+            loop {
+                let page = repo.list_chunk_ids_by_filter(&filters, batch, old_ids.len()).map_err(|e| ServiceError::Repo(e.to_string()))?;
+                let last = page.len() < batch;
+                old_ids.extend(page);
+                if last { return Ok(()); }
+            }
+        })?;
+        self.ingest_chunks(&records, Some(&pairs))?;
+        let fresh: HashSet<&str> = records.iter().map(|r| r.chunk_id.0.as_str()).collect();
+        let stale: Vec<ChunkId> = old_ids.into_iter().filter(|id| !fresh.contains(id.0.as_str())).collect();
+        if !stale.is_empty() {
+            let mut repo = self.open_repo()?;
+            self.delete_orchestrated(&mut repo, |repo, text_m, vec_m| delete_ids_orchestrated(repo, &stale, batch, text_m, vec_m))?;
+            #[cfg(feature = "tantivy")]
+            { let _ = self.with_tantivy(|ti, _repo| { let _ = chunking_store::TextIndexMaintainer::delete_by_ids(ti, &stale); }); }
+        }
+        self.record_embedder(&mut file)
+    }
+
     /// Ingest a single text snippet as one chunk.
     pub fn ingest_text(&self, text: &str, doc_id_hint: Option<&str>) -> Result<(DocumentId, ChunkId), ServiceError> {
+        self.ensure_writable()?;
+        self.check_embed_drift()?;
         let text = text.trim();
         if text.is_empty() { return Err(ServiceError::Embed("text is empty".into())); }
         // IDs
-        let (doc_id, chunk_id) = make_ids_from_text(doc_id_hint, text);
+        let (doc_id, chunk_id) = make_ids_from_text(doc_id_hint, text, self.cfg.content_based_ids);
         // Build record
-        let rec = ChunkRecord {
+        let mut rec = ChunkRecord {
             schema_version: chunk_model::SCHEMA_MAJOR,
             doc_id: doc_id.clone(),
             chunk_id: chunk_id.clone(),
+            seq: None,
             source_uri: "user://input".into(),
             source_mime: "text/plain".into(),
             extracted_at: Utc::now().to_rfc3339(),
@@ -868,37 +1750,15 @@ impl HybridService {
             text: text.to_string(),
             section_path: None,
             meta: std::collections::BTreeMap::new(),
+            block_kinds: Vec::new(),
             extra: std::collections::BTreeMap::new(),
         };
+        self.tag_chunk_lang(&mut rec);
         // Embed
         let vec = self.embedder.embed(text).map_err(|e| ServiceError::Embed(e.to_string()))?;
         // Upsert
         let vectors = vec![(rec.chunk_id.clone(), vec)];
-        self.ingest_chunks(&[rec], Some(&vectors))?;
-        #[cfg(feature = "tantivy")]
-        {
-            // Upsert just-inserted record into Tantivy at current path
-            let records: Vec<ChunkRecord> = {
-                let mut r = Vec::new();
-                // recreate minimal record for upsert
-                r.push(ChunkRecord {
-                    schema_version: chunk_model::SCHEMA_MAJOR,
-                    doc_id: doc_id.clone(),
-                    chunk_id: chunk_id.clone(),
-                    source_uri: "user://input".into(),
-                    source_mime: "text/plain".into(),
-                    extracted_at: Utc::now().to_rfc3339(),
-                    page_start: None,
-                    page_end: None,
-                    text: text.to_string(),
-                    section_path: None,
-                    meta: std::collections::BTreeMap::new(),
-                    extra: std::collections::BTreeMap::new(),
-                });
-                r
-            };
-            let _ = self.with_tantivy(|ti, _repo| { let _ = ti.upsert_records(&records); () });
-        }
+        self.ingest_chunks(std::slice::from_ref(&rec), Some(&vectors))?;
         Ok((doc_id, chunk_id))
     }
 
@@ -908,17 +1768,21 @@ impl HybridService {
         text: &str,
         doc_id_hint: Option<&str>,
         cancel: Option<&CancelToken>,
-        mut progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
+        progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
     ) -> Result<(DocumentId, ChunkId), ServiceError> {
+        self.ensure_writable()?;
+        self.check_embed_drift()?;
+        let mut progress = self.throttle(progress);
         let text = text.trim();
         if text.is_empty() { return Err(ServiceError::Embed("text is empty".into())); }
         // IDs
-        let (doc_id, chunk_id) = make_ids_from_text(doc_id_hint, text);
+        let (doc_id, chunk_id) = make_ids_from_text(doc_id_hint, text, self.cfg.content_based_ids);
         // Build record
-        let rec = ChunkRecord {
+        let mut rec = ChunkRecord {
             schema_version: chunk_model::SCHEMA_MAJOR,
             doc_id: doc_id.clone(),
             chunk_id: chunk_id.clone(),
+            seq: None,
             source_uri: "user://input".into(),
             source_mime: "text/plain".into(),
             extracted_at: Utc::now().to_rfc3339(),
@@ -927,10 +1791,12 @@ impl HybridService {
             text: text.to_string(),
             section_path: None,
             meta: std::collections::BTreeMap::new(),
+            block_kinds: Vec::new(),
             extra: std::collections::BTreeMap::new(),
         };
+        self.tag_chunk_lang(&mut rec);
         // Also create/update a FileRecord so it appears in the Files tab
-        let file_rec = {
+        let mut file_rec = {
             // Build author as MACHINE\USER (best-effort, platform agnostic)
             let pc = std::env::var("COMPUTERNAME").ok().or_else(|| std::env::var("HOSTNAME").ok());
             let user = std::env::var("USERNAME").ok().or_else(|| std::env::var("USER").ok());
@@ -941,16 +1807,8 @@ impl HybridService {
                 _ => None,
             };
             // Compute SHA-256 of the text body
-            let content_sha256 = {
-                use sha2::{Digest, Sha256};
-                let mut hasher = Sha256::new();
-                hasher.update(text.as_bytes());
-                let digest = hasher.finalize();
-                let mut hex = String::with_capacity(64);
-                for b in digest { hex.push_str(&format!("{:02x}", b)); }
-                Some(hex)
-            };
-            let mut meta = std::collections::BTreeMap::new();
+            let content_sha256 = Some(sha256_hex(text.as_bytes()));
+            let meta = std::collections::BTreeMap::new();
             let extra = std::collections::BTreeMap::new();
             chunk_model::FileRecord {
                 schema_version: chunk_model::SCHEMA_MAJOR,
@@ -986,7 +1844,7 @@ impl HybridService {
         if let Some(ct) = cancel { if ct.is_canceled() { if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Canceled); } return Err(ServiceError::Embed("canceled".into())); } }
         // Embed via existing batched helper (one item)
         let texts_refs: [&str; 1] = [text];
-        let vecs = {
+        let (vecs, _) = {
             let cb_opt: Option<&mut (dyn FnMut(ProgressEvent) + Send)> =
                 progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send));
             self.embed_texts_batched(&texts_refs, cancel, cb_opt)?
@@ -994,39 +1852,47 @@ impl HybridService {
         // Upsert
         if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::UpsertDb { total: 1 }); }
         let vectors = vec![(rec.chunk_id.clone(), vecs.into_iter().next().unwrap_or_default())];
-        self.ingest_chunks(&[rec], Some(&vectors))?;
-        #[cfg(feature = "tantivy")]
-        {
-            let records: Vec<ChunkRecord> = {
-                let mut r = Vec::new();
-                r.push(ChunkRecord {
-                    schema_version: chunk_model::SCHEMA_MAJOR,
-                    doc_id: doc_id.clone(),
-                    chunk_id: chunk_id.clone(),
-                    source_uri: "user://input".into(),
-                    source_mime: "text/plain".into(),
-                    extracted_at: Utc::now().to_rfc3339(),
-                    page_start: None,
-                    page_end: None,
-                    text: text.to_string(),
-                    section_path: None,
-                    meta: std::collections::BTreeMap::new(),
-                    extra: std::collections::BTreeMap::new(),
-                });
-                r
-            };
-            let _ = self.with_tantivy(|ti, _repo| { let _ = ti.upsert_records(&records); () });
-        }
+        self.ingest_chunks_with_progress(std::slice::from_ref(&rec), Some(&vectors), None, progress.as_mut().map(|b| &mut **b as &mut (dyn FnMut(ProgressEvent) + Send)))?;
+        self.record_embedder(&mut file_rec)?;
         if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::IndexText { total: 1 }); }
         if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Finished { total: 1 }); }
         Ok((doc_id, chunk_id))
     }
 
+    /// Replace a chunk's text and keep all indexes consistent: updates the DB row (and FTS
+    /// mirror), re-embeds the chunk and replaces its HNSW vector, and refreshes Tantivy.
+    pub fn update_chunk_text(&self, chunk_id: &str, new_text: &str) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        self.check_embed_drift()?;
+        let text = new_text.trim();
+        if text.is_empty() { return Err(ServiceError::Embed("text is empty".into())); }
+        let id = ChunkId(chunk_id.to_string());
+        let mut rec = self
+            .with_repo(|repo| repo.get_chunk_by_id(&id).map_err(|e| ServiceError::Repo(e.to_string())))?
+            .ok_or_else(|| ServiceError::Repo(format!("chunk not found: {chunk_id}")))?;
+        rec.text = text.to_string();
+        // Language may have changed with the text; re-detect
+        if self.cfg.tag_chunk_lang {
+            rec.meta.remove(chunk_model::META_LANG);
+            self.tag_chunk_lang(&mut rec);
+        }
+        let vec = self.embedder.embed(text).map_err(|e| ServiceError::Embed(e.to_string()))?;
+        let vectors = vec![(id, vec)];
+        self.ingest_chunks(std::slice::from_ref(&rec), Some(&vectors))?;
+        Ok(())
+    }
+
     /// Text-only search (prefer Tantivy when available) with filters.
-    #[cfg(feature = "tantivy")]
     pub fn search_text(&self, query: &str, top_k: usize, filters: &[FilterClause]) -> Result<Vec<SearchHit>, ServiceError> {
-        let opts = SearchOptions { top_k, fetch_factor: 10 };
-        let tmatches: Vec<chunking_store::TextMatch> = match self.with_tantivy(|ti, repo| chunking_store::TextSearcher::search_ids(ti, repo, query, filters, &opts))? {
+        let opts = SearchOptions { top_k, fetch_factor: 10, ..Default::default() };
+        self.search_text_with_options(query, filters, &opts)
+    }
+
+    /// Text-only search with explicit search options (e.g., `lang`).
+    #[cfg(feature = "tantivy")]
+    pub fn search_text_with_options(&self, query: &str, filters: &[FilterClause], opts: &SearchOptions) -> Result<Vec<SearchHit>, ServiceError> {
+        let fetch = candidate_opts(opts);
+        let tmatches: Vec<chunking_store::TextMatch> = match self.with_tantivy(|ti, repo| chunking_store::TextSearcher::search_ids(ti, repo, query, filters, &fetch))? {
             Some(v) => v,
             None => Vec::new(),
         };
@@ -1041,106 +1907,480 @@ impl HybridService {
         let mut out: Vec<SearchHit> = Vec::with_capacity(recs.len());
         for rec in recs {
             if let Some(score) = score_map.get(&rec.chunk_id.0) {
-                out.push(SearchHit { chunk: rec, score: *score });
+                out.push(SearchHit { chunk: rec, score: *score, fallback: false, components: None });
             }
         }
-        Ok(out)
+        sort_hits_by_score(&mut out);
+        Ok(cap_per_doc(out, opts))
     }
 
     /// Fallback text-only search via FTS5 when Tantivy feature is disabled.
     #[cfg(all(not(feature = "tantivy"), feature = "fts"))]
-    pub fn search_text(&self, query: &str, top_k: usize, filters: &[FilterClause]) -> Result<Vec<SearchHit>, ServiceError> {
+    pub fn search_text_with_options(&self, query: &str, filters: &[FilterClause], opts: &SearchOptions) -> Result<Vec<SearchHit>, ServiceError> {
         let fts = chunking_store::fts5_index::Fts5Index::new();
-        let opts = SearchOptions { top_k, fetch_factor: 10 };
-        self.with_repo(|repo| Ok(fts.search(repo, query, filters, &opts)))
+        let hits = self.with_repo(|repo| Ok(fts.search(repo, query, filters, &candidate_opts(opts))))?;
+        Ok(cap_per_doc(hits, opts))
     }
     /// Fallback when neither Tantivy nor FTS are enabled: return empty.
     #[cfg(all(not(feature = "tantivy"), not(feature = "fts")))]
-    pub fn search_text(&self, _query: &str, _top_k: usize, _filters: &[FilterClause]) -> Result<Vec<SearchHit>, ServiceError> {
+    pub fn search_text_with_options(&self, _query: &str, _filters: &[FilterClause], _opts: &SearchOptions) -> Result<Vec<SearchHit>, ServiceError> {
         Ok(Vec::new())
     }
 
+    /// Vector-only search: embeds the query and ranks chunks by HNSW cosine similarity alone,
+    /// without touching any text backend. Empty when no HNSW index exists yet.
+    pub fn search_vector(&self, query: &str, top_k: usize, filters: &[FilterClause]) -> Result<Vec<SearchHit>, ServiceError> {
+        let opts = SearchOptions { top_k, fetch_factor: 10, ..Default::default() };
+        self.search_vector_with_options(query, filters, &opts)
+    }
+
+    /// Vector-only search with explicit options. Hit scores are the index's cosine similarities
+    /// as-is, best first.
+    pub fn search_vector_with_options(&self, query: &str, filters: &[FilterClause], opts: &SearchOptions) -> Result<Vec<SearchHit>, ServiceError> {
+        self.maybe_record_query(query);
+        self.ensure_warm();
+        let qvec = self.embed_query(query)?;
+        let fetch = candidate_opts(opts);
+        let Some(matches) = self.with_hnsw(|h, repo| h.knn_ids(repo, &qvec, filters, &fetch))? else {
+            return Ok(Vec::new());
+        };
+        let matches = matches.map_err(|e| ServiceError::Index(e.to_string()))?;
+        if matches.is_empty() { return Ok(Vec::new()); }
+        let ids: Vec<ChunkId> = matches.iter().map(|m| m.chunk_id.clone()).collect();
+        let mut recs: HashMap<String, ChunkRecord> = self
+            .with_repo(|repo| repo.get_chunks_by_ids(&ids).map_err(|e| ServiceError::Repo(e.to_string())))?
+            .into_iter()
+            .map(|r| (r.chunk_id.0.clone(), r))
+            .collect();
+        // knn_ids already ranks best first; keep that order
+        let hits: Vec<SearchHit> = matches
+            .into_iter()
+            .filter_map(|m| recs.remove(&m.chunk_id.0).map(|chunk| SearchHit { chunk, score: m.score, fallback: false, components: None }))
+            .collect();
+        let hits = cap_per_doc(hits, opts);
+        self.maybe_log_search(query, &hits);
+        Ok(hits)
+    }
+
+    /// Re-score `hits` by exact cosine similarity between the query and each chunk's stored
+    /// vector (`extra[EXTRA_EMBEDDING_KEY]`, written with `persist_vectors_in_records`), best
+    /// first. Meant for a small candidate set after fusion. Hits without a stored vector keep
+    /// their order after the re-scored ones; if none has one, or the query cannot be embedded,
+    /// `hits` is returned unchanged and a warning is logged.
+    pub fn rerank_by_vector(&self, query: &str, hits: Vec<SearchHit>) -> Vec<SearchHit> {
+        if hits.is_empty() { return hits; }
+        let vectors: Vec<Option<Vec<f32>>> = hits.iter().map(|h| stored_vector(&h.chunk)).collect();
+        if vectors.iter().all(Option::is_none) {
+            eprintln!("[rerank] no stored vectors on {} hits (enable persist_vectors_in_records); order unchanged", hits.len());
+            return hits;
+        }
+        let qvec = match self.embed_query(query) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("[rerank] embedding the query failed: {}; order unchanged", e);
+                return hits;
+            }
+        };
+        let mut scored: Vec<SearchHit> = Vec::with_capacity(hits.len());
+        let mut rest: Vec<SearchHit> = Vec::new();
+        for (mut hit, vector) in hits.into_iter().zip(vectors) {
+            match vector.and_then(|v| cosine_similarity(&qvec, &v)) {
+                Some(score) => {
+                    // The fused breakdown no longer explains a cosine score
+                    hit.score = score;
+                    hit.components = None;
+                    scored.push(hit);
+                }
+                None => rest.push(hit),
+            }
+        }
+        if !rest.is_empty() {
+            eprintln!("[rerank] {} hits have no usable stored vector; kept after the re-scored ones", rest.len());
+        }
+        sort_hits_by_score(&mut scored);
+        scored.extend(rest);
+        scored
+    }
+
+    /// Diversified hybrid search for prompt building; see `search_mmr_with_options`. Uses
+    /// `DEFAULT_W_TEXT` and `DEFAULT_W_VEC`.
+    pub fn search_mmr(&self, query: &str, top_k: usize, lambda: f32, filters: &[FilterClause]) -> Result<Vec<SearchHit>, ServiceError> {
+        let opts = SearchOptions { top_k, fetch_factor: 10, ..Default::default() };
+        self.search_mmr_with_options(query, filters, &opts, lambda, DEFAULT_W_TEXT, DEFAULT_W_VEC)
+    }
+
+    /// Maximal Marginal Relevance: fetch `opts.fetch_n()` hybrid candidates, then greedily pick
+    /// `opts.top_k` maximizing `lambda * relevance - (1 - lambda) * max_similarity_to_selected`.
+    /// Relevance is the fused score rescaled to [0, 1] over the pool; similarity is cosine over
+    /// the vectors persisted in the records or held by the HNSW index, embedding only chunks
+    /// with neither. `lambda` is clamped to [0, 1] and `lambda = 1` returns the plain relevance
+    /// ranking. Returned hits keep their fused scores.
+    pub fn search_mmr_with_options(&self, query: &str, filters: &[FilterClause], opts: &SearchOptions, lambda: f32, w_text: f32, w_vec: f32) -> Result<Vec<SearchHit>, ServiceError> {
+        // The pool is already `fetch_n()` wide; the signals must not widen it again
+        let pool_opts = SearchOptions { top_k: opts.fetch_n(), fetch_factor: 1, ..opts.clone() };
+        let pool = self.search_hybrid_with_options(query, filters, &pool_opts, w_text, w_vec)?;
+        self.mmr_select(pool, opts.top_k, lambda)
+    }
+
+    fn mmr_select(&self, mut pool: Vec<SearchHit>, k: usize, lambda: f32) -> Result<Vec<SearchHit>, ServiceError> {
+        let lambda = if lambda.is_nan() { 1.0 } else { lambda.clamp(0.0, 1.0) };
+        if lambda >= 1.0 || pool.len() <= 1 || k <= 1 {
+            pool.truncate(k);
+            return Ok(pool);
+        }
+
+        let mut vectors: Vec<Option<Vec<f32>>> = pool.iter().map(|h| stored_vector(&h.chunk)).collect();
+        if let Some(hnsw) = self.hnsw.read().ok().as_ref().and_then(|g| g.as_ref()) {
+            for (v, h) in vectors.iter_mut().zip(&pool).filter(|(v, _)| v.is_none()) { *v = hnsw.vector(&h.chunk.chunk_id.0); }
+        }
+        let missing: Vec<usize> = (0..pool.len()).filter(|&i| vectors[i].is_none()).collect();
+        if !missing.is_empty() {
+            let texts: Vec<&str> = missing.iter().map(|&i| pool[i].chunk.text.as_str()).collect();
+            let (vecs, _) = self.embed_texts_batched(&texts, None, None)?;
+            for (i, v) in missing.into_iter().zip(vecs) { vectors[i] = Some(v); }
+        }
+
+        let (lo, hi) = pool.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), h| (lo.min(h.score), hi.max(h.score)));
+        let relevance: Vec<f32> = pool
+            .iter()
+            .map(|h| if hi > lo { (h.score - lo) / (hi - lo) } else { 1.0 })
+            .collect();
+
+        let mut max_sim = vec![f32::NEG_INFINITY; pool.len()];
+        let mut taken = vec![false; pool.len()];
+        let mut order: Vec<usize> = Vec::with_capacity(k.min(pool.len()));
+        while order.len() < k && order.len() < pool.len() {
+            let mut best: Option<(usize, f32)> = None;
+            for i in (0..pool.len()).filter(|&i| !taken[i]) {
+                let redundancy = if order.is_empty() { 0.0 } else { max_sim[i].max(0.0) };
+                let mmr = lambda * relevance[i] - (1.0 - lambda) * redundancy;
+                if best.is_none_or(|(_, b)| mmr > b) { best = Some((i, mmr)); }
+            }
+            let Some((pick, _)) = best else { break };
+            taken[pick] = true;
+            order.push(pick);
+            for i in (0..pool.len()).filter(|&i| !taken[i]) {
+                let sim = match (&vectors[i], &vectors[pick]) {
+                    (Some(a), Some(b)) => cosine_similarity(a, b).unwrap_or(0.0),
+                    _ => 0.0,
+                };
+                max_sim[i] = max_sim[i].max(sim);
+            }
+        }
+
+        let mut slots: Vec<Option<SearchHit>> = pool.into_iter().map(Some).collect();
+        Ok(order.into_iter().filter_map(|i| slots[i].take()).collect())
+    }
+
+    /// Empty HNSW index for this service's embedder (dimension, normalization) and metric.
+    fn new_hnsw(&self) -> HnswIndex {
+        let mut h = HnswIndex::with_metric(self.embedder.info().dimension, self.cfg.hnsw_params, self.cfg.hnsw_metric);
+        h.set_normalized(self.embedder.normalizes());
+        h.set_model_fingerprint(Some(self.embedder_fingerprint.clone()));
+        h
+    }
+
+    /// Embed a search query, served from the query cache when possible.
+    fn embed_query(&self, query: &str) -> Result<Vec<f32>, ServiceError> {
+        if let Some(v) = self.query_cache.lock().ok().and_then(|mut c| c.get(query)) {
+            return Ok(v);
+        }
+        let v = self.embedder.embed(query).map_err(|e| ServiceError::Embed(e.to_string()))?;
+        if let Ok(mut c) = self.query_cache.lock() { c.insert(query, v.clone()); }
+        Ok(v)
+    }
+
     /// Hybrid search: fuse Text (Tantivy or FTS) and HNSW (vector) with weighted sum.
     pub fn search_hybrid(&self, query: &str, top_k: usize, filters: &[FilterClause], w_text: f32, w_vec: f32) -> Result<Vec<SearchHit>, ServiceError> {
-        let opts = SearchOptions { top_k, fetch_factor: 10 };
+        let opts = SearchOptions { top_k, fetch_factor: 10, ..Default::default() };
+        self.search_hybrid_with_options(query, filters, &opts, w_text, w_vec)
+    }
+
+    /// Hybrid search with explicit search options (e.g., `lang` restricts both signals).
+    /// `w_text` and `w_vec` are relative: a negative or NaN weight is clamped to 0, then the pair
+    /// is scaled to sum to 1, so `(2.0, 1.0)` ranks like `(0.67, 0.33)`. Without a positive
+    /// weight every hit scores 0.
+    pub fn search_hybrid_with_options(&self, query: &str, filters: &[FilterClause], opts: &SearchOptions, w_text: f32, w_vec: f32) -> Result<Vec<SearchHit>, ServiceError> {
+        self.maybe_record_query(query);
+        let fetch = candidate_opts(opts);
+        let text_matches = self.text_matches(query, filters, &fetch)?;
 
+        // Vector matches via HNSW guard (optional)
+        self.ensure_warm();
+        let qvec = self.embed_query(query)?;
+        let vec_matches: Vec<chunking_store::TextMatch> = match self.with_hnsw(|h, repo| h.knn_ids(repo, &qvec, filters, &fetch))? {
+            Some(v) => v.map_err(|e| ServiceError::Index(e.to_string()))?,
+            None => Vec::new(),
+        };
+        let hits = self.fuse_matches(text_matches, vec_matches, filters, opts, w_text, w_vec)?;
+        self.maybe_log_search(query, &hits);
+        Ok(hits)
+    }
+
+    /// Hybrid search for several queries sharing the same filters; see `search_hybrid_multi_with_options`.
+    pub fn search_hybrid_multi(&self, queries: &[&str], top_k: usize, filters: &[FilterClause], w_text: f32, w_vec: f32) -> Result<Vec<Vec<SearchHit>>, ServiceError> {
+        let per_query: Vec<(&str, &[FilterClause])> = queries.iter().map(|q| (*q, filters)).collect();
+        let opts = SearchOptions { top_k, ..Default::default() };
+        self.search_hybrid_multi_with_options(&per_query, &opts, w_text, w_vec)
+    }
+
+    /// Hybrid search for several `(query, filters)` pairs. Uncached queries are embedded in a
+    /// single batch and all KNN lookups share one HNSW guard and repo; results are returned in
+    /// query order with the same semantics as `search_hybrid_with_options`.
+    pub fn search_hybrid_multi_with_options(&self, queries: &[(&str, &[FilterClause])], opts: &SearchOptions, w_text: f32, w_vec: f32) -> Result<Vec<Vec<SearchHit>>, ServiceError> {
+        if queries.is_empty() { return Ok(Vec::new()); }
+        for (q, _) in queries { self.maybe_record_query(q); }
+        self.ensure_warm();
+        let texts: Vec<&str> = queries.iter().map(|(q, _)| *q).collect();
+        let qvecs = self.embed_queries(&texts)?;
+        let fetch = candidate_opts(opts);
+        let vec_matches: Vec<Vec<chunking_store::TextMatch>> = match self.with_hnsw(|h, repo| {
+            queries
+                .iter()
+                .zip(qvecs.iter())
+                .map(|((_, filters), qvec)| h.knn_ids(repo, qvec, filters, &fetch))
+                .collect::<Result<Vec<_>, _>>()
+        })? {
+            Some(v) => v.map_err(|e| ServiceError::Index(e.to_string()))?,
+            None => vec![Vec::new(); queries.len()],
+        };
+        let mut out = Vec::with_capacity(queries.len());
+        for ((query, filters), vec_m) in queries.iter().zip(vec_matches) {
+            let text_m = self.text_matches(query, filters, &fetch)?;
+            let hits = self.fuse_matches(text_m, vec_m, filters, opts, w_text, w_vec)?;
+            self.maybe_log_search(query, &hits);
+            out.push(hits);
+        }
+        Ok(out)
+    }
+
+    /// Embed several search queries, batching the ones missing from the query cache.
+    fn embed_queries(&self, queries: &[&str]) -> Result<Vec<Vec<f32>>, ServiceError> {
+        let mut out: Vec<Option<Vec<f32>>> = match self.query_cache.lock() {
+            Ok(mut c) => queries.iter().map(|q| c.get(q)).collect(),
+            Err(_) => vec![None; queries.len()],
+        };
+        let missing: Vec<usize> = (0..queries.len()).filter(|&i| out[i].is_none()).collect();
+        if !missing.is_empty() {
+            let texts: Vec<&str> = missing.iter().map(|&i| queries[i]).collect();
+            let vecs = self.embedder.embed_batch(&texts).map_err(|e| ServiceError::Embed(e.to_string()))?;
+            let mut cache = self.query_cache.lock().ok();
+            for (i, v) in missing.into_iter().zip(vecs) {
+                if let Some(c) = cache.as_mut() { c.insert(queries[i], v.clone()); }
+                out[i] = Some(v);
+            }
+        }
+        Ok(out.into_iter().map(|v| v.unwrap_or_default()).collect())
+    }
+
+    /// Text matches for `query` (Tantivy when enabled, else FTS5, else none).
+    fn text_matches(&self, query: &str, filters: &[FilterClause], opts: &SearchOptions) -> Result<Vec<chunking_store::TextMatch>, ServiceError> {
         // Text matches (prefer Tantivy when enabled)
         #[cfg(feature = "tantivy")]
-        let mut text_matches: Vec<chunking_store::TextMatch> = match self.with_tantivy(|ti, repo| chunking_store::TextSearcher::search_ids(ti, repo, query, filters, &opts))? {
+        let text_matches: Vec<chunking_store::TextMatch> = match self.with_tantivy(|ti, repo| chunking_store::TextSearcher::search_ids(ti, repo, query, filters, opts))? {
             Some(v) => v,
             None => Vec::new(),
         };
         #[cfg(all(not(feature = "tantivy"), feature = "fts"))]
-        let mut text_matches: Vec<chunking_store::TextMatch> = {
+        let text_matches: Vec<chunking_store::TextMatch> = {
             let fts = chunking_store::fts5_index::Fts5Index::new();
-            self.with_repo(|repo| Ok(chunking_store::TextSearcher::search_ids(&fts, repo, query, filters, &opts)))?
+            self.with_repo(|repo| Ok(chunking_store::TextSearcher::search_ids(&fts, repo, query, filters, opts)))?
         };
         #[cfg(all(not(feature = "tantivy"), not(feature = "fts")))]
-        let mut text_matches: Vec<chunking_store::TextMatch> = Vec::new();
+        let text_matches: Vec<chunking_store::TextMatch> = { let _ = (query, filters, opts); Vec::new() };
+        Ok(text_matches)
+    }
 
-        // Vector matches via HNSW guard (optional)
-        self.ensure_warm();
-        let qvec = self.embedder.embed(query).map_err(|e| ServiceError::Embed(e.to_string()))?;
-        let vec_matches: Vec<chunking_store::TextMatch> = match self.with_hnsw(|h, repo| VectorSearcher::knn_ids(h, repo, &qvec, filters, &opts))? {
-            Some(v) => v,
-            None => Vec::new(),
+    /// Fuse text and vector matches by weighted score and materialize the top hits.
+    fn fuse_matches(
+        &self,
+        text_matches: Vec<chunking_store::TextMatch>,
+        vec_matches: Vec<chunking_store::TextMatch>,
+        filters: &[FilterClause],
+        opts: &SearchOptions,
+        w_text: f32,
+        w_vec: f32,
+    ) -> Result<Vec<SearchHit>, ServiceError> {
+        // Combine scores; weights are normalized to sum to 1 so the fused score keeps the 0..1 scale
+        let w_sum = w_text.max(0.0) + w_vec.max(0.0);
+        let (w_text, w_vec) = if w_sum > 0.0 { (w_text.max(0.0) / w_sum, w_vec.max(0.0) / w_sum) } else { (0.0, 0.0) };
+        let empty = ScoreBreakdown { text_weight: w_text, vector_weight: w_vec, ..Default::default() };
+        let mut score_map: HashMap<String, ScoreBreakdown> = HashMap::new();
+        add_signal(&mut score_map, empty, text_matches, |b| &mut b.text);
+        add_signal(&mut score_map, empty, vec_matches, |b| &mut b.vector);
+        self.materialize_fused(score_map, filters, opts)
+    }
+
+    /// `search_hybrid` with each hit's FileRecord attached; see `attach_files`.
+    pub fn search_hybrid_with_files(&self, query: &str, top_k: usize, filters: &[FilterClause], w_text: f32, w_vec: f32) -> Result<Vec<SearchHitWithFile>, ServiceError> {
+        let hits = self.search_hybrid(query, top_k, filters, w_text, w_vec)?;
+        self.attach_files(hits)
+    }
+
+    /// Join the FileRecord of each hit's document, looking files up once per distinct doc id in
+    /// a single query. Works on the result of any `search_*` method; order is preserved.
+    pub fn attach_files(&self, hits: Vec<SearchHit>) -> Result<Vec<SearchHitWithFile>, ServiceError> {
+        let doc_ids: Vec<String> = hits.iter().map(|h| h.chunk.doc_id.0.clone()).collect::<BTreeSet<_>>().into_iter().collect();
+        let files = self.with_repo(|repo| repo.get_files_by_doc_ids(&doc_ids).map_err(|e| ServiceError::Repo(e.to_string())))?;
+        Ok(hits
+            .into_iter()
+            .map(|hit| {
+                let file = files.get(&hit.chunk.doc_id.0).cloned();
+                SearchHitWithFile { hit, file }
+            })
+            .collect())
+    }
+
+    /// Four-way hybrid search matching the GUI's weights: the default Tantivy query, its
+    /// token-AND and token-OR variants (`tantivy_triple`) and vector KNN, fused by `weights`
+    /// (see `HybridWeights`). Hits carry the per-signal `ScoreBreakdown`. Without the
+    /// `tantivy` feature `tv` falls back to the FTS5 text signal and `tv_and`/`tv_or` find
+    /// nothing.
+    pub fn search_hybrid_weighted(&self, query: &str, top_k: usize, filters: &[FilterClause], weights: HybridWeights) -> Result<Vec<SearchHit>, ServiceError> {
+        let opts = SearchOptions { top_k, fetch_factor: 10, ..Default::default() };
+        self.search_hybrid_weighted_with_options(query, filters, &opts, weights)
+    }
+
+    /// Weighted hybrid search with explicit search options. Every signal draws its candidates
+    /// from the same pool size (`top_k * fetch_factor`, widened for `max_per_doc`).
+    pub fn search_hybrid_weighted_with_options(&self, query: &str, filters: &[FilterClause], opts: &SearchOptions, weights: HybridWeights) -> Result<Vec<SearchHit>, ServiceError> {
+        self.maybe_record_query(query);
+        let fetch = candidate_opts(opts);
+
+        let enabled = [weights.tv, weights.tv_and, weights.tv_or, weights.vec].map(|w| w.map(|w| w.max(0.0)));
+        let w_sum: f32 = enabled.iter().flatten().sum();
+        let [w_tv, w_and, w_or, w_vec] = enabled.map(|w| match w {
+            Some(w) if w_sum > 0.0 => w / w_sum,
+            _ => 0.0,
+        });
+
+        #[cfg(feature = "tantivy")]
+        let (tv, tv_and, tv_or) = if weights.tv.is_some() || weights.tv_and.is_some() || weights.tv_or.is_some() {
+            self.tantivy_triple_with_options(query, filters, &fetch)?
+        } else {
+            (Vec::new(), Vec::new(), Vec::new())
+        };
+        #[cfg(not(feature = "tantivy"))]
+        let (tv, tv_and, tv_or) = if weights.tv.is_some() {
+            (self.text_matches(query, filters, &fetch)?, Vec::new(), Vec::new())
+        } else {
+            (Vec::new(), Vec::new(), Vec::new())
         };
 
-        // Combine scores
-        let mut score_map: HashMap<String, f32> = HashMap::new();
-        for m in text_matches.drain(..) {
-            let e = score_map.entry(m.chunk_id.0).or_insert(0.0);
-            *e += w_text * m.score;
-        }
-        for m in vec_matches.into_iter() {
-            let e = score_map.entry(m.chunk_id.0).or_insert(0.0);
-            *e += w_vec * m.score;
-        }
+        let vec_matches = if weights.vec.is_some() {
+            self.ensure_warm();
+            let qvec = self.embed_query(query)?;
+            match self.with_hnsw(|h, repo| h.knn_ids(repo, &qvec, filters, &fetch))? {
+                Some(v) => v.map_err(|e| ServiceError::Index(e.to_string()))?,
+                None => Vec::new(),
+            }
+        } else {
+            Vec::new()
+        };
 
-        // Rank and materialize
-        let mut items: Vec<(String, f32)> = score_map.into_iter().collect();
-        items.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        if items.len() > top_k { items.truncate(top_k); }
+        let empty = ScoreBreakdown { text_weight: w_tv, text_and_weight: w_and, text_or_weight: w_or, vector_weight: w_vec, ..Default::default() };
+        let mut score_map: HashMap<String, ScoreBreakdown> = HashMap::new();
+        if weights.tv.is_some() { add_signal(&mut score_map, empty, tv, |b| &mut b.text); }
+        if weights.tv_and.is_some() { add_signal(&mut score_map, empty, tv_and, |b| &mut b.text_and); }
+        if weights.tv_or.is_some() { add_signal(&mut score_map, empty, tv_or, |b| &mut b.text_or); }
+        add_signal(&mut score_map, empty, vec_matches, |b| &mut b.vector);
+        let hits = self.materialize_fused(score_map, filters, opts)?;
+        self.maybe_log_search(query, &hits);
+        Ok(hits)
+    }
+
+    /// Rank fused candidates and materialize the top hits with their breakdowns.
+    fn materialize_fused(&self, score_map: HashMap<String, ScoreBreakdown>, filters: &[FilterClause], opts: &SearchOptions) -> Result<Vec<SearchHit>, ServiceError> {
+        let top_k = opts.top_k;
+        let mut items: Vec<(String, ScoreBreakdown)> = score_map.into_iter().collect();
+        items.sort_by(|a, b| b.1.fused().partial_cmp(&a.1.fused()).unwrap_or(std::cmp::Ordering::Equal));
+        // With a per-doc cap, doc ids are needed first; materialize all and truncate after capping
+        if opts.max_per_doc.is_none() && items.len() > top_k { items.truncate(top_k); }
 
         let ids: Vec<ChunkId> = items.iter().map(|(cid, _)| ChunkId(cid.clone())).collect();
         let recs = self.with_repo(|repo| repo.get_chunks_by_ids(&ids).map_err(|e| ServiceError::Repo(e.to_string())))?;
-        let mut cscore: HashMap<String, f32> = HashMap::new();
+        let mut cscore: HashMap<String, ScoreBreakdown> = HashMap::new();
         for (cid, s) in items { cscore.insert(cid, s); }
         let mut out: Vec<SearchHit> = Vec::with_capacity(recs.len());
         for rec in recs {
-            if let Some(score) = cscore.get(&rec.chunk_id.0) {
-                out.push(SearchHit { chunk: rec, score: *score });
+            if let Some(parts) = cscore.get(&rec.chunk_id.0) {
+                out.push(SearchHit { chunk: rec, score: parts.fused(), fallback: false, components: Some(*parts) });
             }
         }
+        // Rank by fused score, not by the store's lookup order
+        sort_hits_by_score(&mut out);
+        let out = cap_per_doc(out, opts);
+        if out.is_empty() && opts.empty_fallback != EmptyFallback::None {
+            return self.fallback_hits(filters, opts);
+        }
         Ok(out)
     }
 
+    /// Fill an empty result per `opts.empty_fallback`; hits are flagged and scored 0.0.
+    fn fallback_hits(&self, filters: &[FilterClause], opts: &SearchOptions) -> Result<Vec<SearchHit>, ServiceError> {
+        self.with_repo(|repo| {
+            let ids = match opts.empty_fallback {
+                EmptyFallback::None => return Ok(Vec::new()),
+                EmptyFallback::Recent => repo.list_recent_chunk_ids(filters, opts.top_k),
+                EmptyFallback::Random => repo.list_random_chunk_ids(filters, opts.top_k),
+            }
+            .map_err(|e| ServiceError::Repo(e.to_string()))?;
+            let mut recs = repo.get_chunks_by_ids(&ids).map_err(|e| ServiceError::Repo(e.to_string()))?;
+            // Keep the policy's order rather than the store's lookup order
+            let pos: HashMap<String, usize> = ids.iter().enumerate().map(|(i, c)| (c.0.clone(), i)).collect();
+            recs.sort_by_key(|r| pos.get(&r.chunk_id.0).copied().unwrap_or(usize::MAX));
+            Ok(recs.into_iter().map(|chunk| SearchHit { chunk, score: 0.0, fallback: true, components: None }).collect())
+        })
+    }
+
+    /// Hybrid search grouped by document: up to `opts.top_k` documents, each with its best
+    /// chunk's page and a snippet. Chunks are fetched with `opts.fetch_n()` so several hits of
+    /// one file do not crowd out other files.
+    pub fn search_grouped(&self, query: &str, filters: &[FilterClause], opts: &SearchOptions, w_text: f32, w_vec: f32) -> Result<Vec<DocGroup>, ServiceError> {
+        let chunk_opts = SearchOptions { top_k: opts.fetch_n(), ..opts.clone() };
+        let hits = self.search_hybrid_with_options(query, filters, &chunk_opts, w_text, w_vec)?;
+        let mut groups = group_hits_by_doc(hits, query, self.cfg.group_snippet_chars);
+        groups.truncate(opts.top_k);
+        Ok(groups)
+    }
+
+    /// Hybrid search restricted to one collection (`meta[collection_meta_key] == collection`).
+    /// The restriction is passed to every signal as a `Must` filter; hits from a text index
+    /// that cannot prefilter meta are dropped before returning.
+    pub fn search_in_collection(&self, collection: &str, query: &str, filters: &[FilterClause], opts: &SearchOptions, w_text: f32, w_vec: f32) -> Result<Vec<SearchHit>, ServiceError> {
+        let Some(key) = self.cfg.collection_meta_key.clone() else {
+            return Err(ServiceError::Index("collection_meta_key is not configured".into()));
+        };
+        let mut scoped: Vec<FilterClause> = filters.to_vec();
+        scoped.push(FilterClause { kind: chunking_store::FilterKind::Must, op: chunking_store::FilterOp::MetaEq { key: key.clone(), value: collection.to_string(), case_insensitive: false } });
+        let mut hits = self.search_hybrid_with_options(query, &scoped, opts, w_text, w_vec)?;
+        hits.retain(|h| h.chunk.meta.get(&key).map(String::as_str) == Some(collection));
+        Ok(hits)
+    }
+
+    /// Report, per active search backend (text, then vector), whether each clause is pushed
+    /// down into the backend query or post-filtered on fetched candidates. Post-filtered clauses
+    /// are the usual cause of low recall with selective filters.
+    pub fn explain_filters(&self, filters: &[FilterClause]) -> Vec<FilterPlan> {
+        let mut out = Vec::new();
+        #[cfg(feature = "tantivy")]
+        out.extend(plan_filter_pushdown("tantivy", &chunking_store::tantivy_index::TANTIVY_CAPS, filters));
+        #[cfg(all(not(feature = "tantivy"), feature = "fts"))]
+        out.extend(plan_filter_pushdown("fts5", &chunking_store::TextSearcher::caps(&Fts5Index::new()), filters));
+        out.extend(plan_filter_pushdown("hnsw", &chunking_store::IndexCaps::NONE, filters));
+        out
+    }
+
     /// Delete by filters across DB and both indexes.
     pub fn delete_by_filter(&self, filters: &[FilterClause], batch_size: usize) -> Result<DeleteReport, ServiceError> {
+        self.ensure_writable()?;
         let mut repo = self.open_repo()?;
-        #[cfg(feature = "fts")]
-        let fts = chunking_store::fts5_index::Fts5Index::new();
-        #[cfg(feature = "fts")]
-        let text_m: [&dyn chunking_store::TextIndexMaintainer; 1] = [&fts];
-        #[cfg(not(feature = "fts"))]
-        let text_m: [&dyn chunking_store::TextIndexMaintainer; 0] = [];
-        // Load HNSW (if exists)
-        let hdir = self.hnsw_dir();
-        let mut hnsw = if Path::new(&hdir).join("map.tsv").exists() {
-            HnswIndex::load(&hdir, self.embedder.info().dimension).map_err(|e| ServiceError::Io(e.to_string()))?
-        } else { HnswIndex::new(self.embedder.info().dimension, 10_000) };
-        let mut vec_m: [&mut dyn chunking_store::VectorIndexMaintainer; 1] = [&mut hnsw];
-
-        let rep = delete_by_filter_orchestrated(&mut repo, filters, batch_size, &text_m, &mut vec_m)
-            .map_err(|e| ServiceError::Index(e.to_string()))?;
-
-        // Persist HNSW snapshot post-delete and refresh resident cache
-        hnsw.save(&hdir).map_err(|e| ServiceError::Io(e.to_string()))?;
-        if let Ok(mut guard) = self.hnsw.write() { *guard = Some(hnsw); }
-        let _ = self.hnsw_state.write().map(|mut s| *s = HnswState::Ready);
+        let total_before = repo.counts().map(|(n, _)| n).unwrap_or(0);
+        let rep = self.delete_orchestrated(&mut repo, |repo, text_m, vec_m| {
+            delete_by_filter_orchestrated(repo, filters, batch_size, text_m, vec_m)
+        })?;
 
         // Keep files table in sync: targeted delete for DocId filters, then orphan cleanup
-        // Targeted deletes
         let mut doc_ids: Vec<String> = Vec::new();
         for f in filters {
             match &f.op {
@@ -1154,9 +2394,244 @@ impl HybridService {
         }
         // Best-effort orphan cleanup (ignore errors)
         let _ = repo.cleanup_orphan_files();
+        drop(repo);
+
+        if let Some(ratio) = self.cfg.auto_compact_after_delete_ratio {
+            if total_before > 0 && rep.db_deleted as f32 / total_before as f32 > ratio {
+                self.spawn_compaction();
+            }
+        }
+        Ok(rep)
+    }
+
+    /// Run `delete` against the DB, FTS and the resident HNSW (loaded first if absent), then
+    /// persist the HNSW deletes.
+    fn delete_orchestrated<F>(&self, repo: &mut SqliteRepo, delete: F) -> Result<DeleteReport, ServiceError>
+    where
+        F: FnOnce(&mut SqliteRepo, &[&dyn chunking_store::TextIndexMaintainer], &mut [&mut dyn chunking_store::VectorIndexMaintainer]) -> Result<DeleteReport, OrchestratorError>,
+    {
+        let _writer = self.lock_writer()?;
+        #[cfg(feature = "fts")]
+        let fts = chunking_store::fts5_index::Fts5Index::new();
+        #[cfg(feature = "fts")]
+        let text_m: [&dyn chunking_store::TextIndexMaintainer; 1] = [&fts];
+        #[cfg(not(feature = "fts"))]
+        let text_m: [&dyn chunking_store::TextIndexMaintainer; 0] = [];
+        // Use the resident HNSW (load it only if absent) so deletes stay O(deleted)
+        let hdir = self.hnsw_dir();
+        let has_snapshot = HnswIndex::snapshot_exists(&hdir);
+        let mut guard = self.hnsw.write().map_err(|_| ServiceError::Index("hnsw lock poisoned".into()))?;
+        if guard.is_none() {
+            *guard = Some(if has_snapshot {
+                HnswIndex::load(&hdir, self.embedder.info().dimension).map_err(hnsw_load_error)?
+            } else { self.new_hnsw() });
+        }
+        let hnsw = guard.as_mut().expect("resident HNSW set above");
+        hnsw.set_dtype(self.store_vector_dtype(repo)?);
+        let mut vec_m: [&mut dyn chunking_store::VectorIndexMaintainer; 1] = [&mut *hnsw];
+        let rep = delete(repo, &text_m, &mut vec_m).map_err(|e| ServiceError::Index(e.to_string()))?;
+
+        // Soft deletes only append tombstones; the full snapshot is written on first save
+        if has_snapshot {
+            hnsw.flush_deletes(&hdir).map_err(|e| ServiceError::Io(e.to_string()))?;
+        } else {
+            hnsw.save(&hdir).map_err(|e| ServiceError::Io(e.to_string()))?;
+        }
+        drop(guard);
+        let _ = self.hnsw_state.write().map(|mut s| *s = HnswState::Ready);
         Ok(rep)
     }
 
+    /// Reclaim space: FTS optimize + VACUUM on the database, and drop HNSW tombstones
+    /// (rewriting the snapshot) when the index is resident.
+    pub fn compact_store(&self) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        let db = self.db_path.read().map(|p| p.clone()).unwrap_or_else(|_| self.cfg.db_path.clone());
+        compact_store_at(&db, &self.hnsw_dir(), &self.hnsw)
+    }
+
+    /// Block until a background compaction started by `delete_by_filter` (if any) finishes.
+    pub fn wait_for_compaction(&self) {
+        let job = self.compaction.lock().ok().and_then(|mut j| j.take());
+        if let Some(h) = job { let _ = h.join(); }
+    }
+
+    fn spawn_compaction(&self) {
+        let Ok(mut job) = self.compaction.lock() else { return };
+        if job.as_ref().is_some_and(|h| !h.is_finished()) { return; }
+        let db = self.db_path.read().map(|p| p.clone()).unwrap_or_else(|_| self.cfg.db_path.clone());
+        let hdir = self.hnsw_dir();
+        let hnsw = Arc::clone(&self.hnsw);
+        *job = Some(std::thread::spawn(move || {
+            if let Err(e) = compact_store_at(&db, &hdir, &hnsw) {
+                eprintln!("[compact] background compaction of {} failed: {}", db.display(), e);
+            }
+        }));
+    }
+
+    /// Physically drop HNSW tombstones once they exceed `hnsw_compact_tombstone_ratio`, then
+    /// rewrite the snapshot. Meant for idle time; returns true when a compaction ran.
+    pub fn compact_hnsw_if_needed(&self) -> Result<bool, ServiceError> {
+        self.ensure_writable()?;
+        let hdir = self.hnsw_dir();
+        let mut guard = self.hnsw.write().map_err(|_| ServiceError::Index("hnsw lock poisoned".into()))?;
+        let Some(hnsw) = guard.as_mut() else { return Ok(false) };
+        if hnsw.tombstone_ratio() < self.cfg.hnsw_compact_tombstone_ratio { return Ok(false); }
+        hnsw.compact();
+        hnsw.save(&hdir).map_err(|e| ServiceError::Io(e.to_string()))?;
+        Ok(true)
+    }
+
+    /// Rebuild every index from the chunks in the DB, e.g. after changing the embedding model or
+    /// tokenizer: the FTS5 mirror in place, Tantivy from scratch and, with `reembed`, HNSW from
+    /// freshly embedded vectors at the current model's dimension (re-recording the drift
+    /// reference). New indexes are built in sibling `*.rebuild` directories and swapped in by
+    /// rename, so searches keep using the old ones until then; re-embedded vectors and model
+    /// stamps are kept aside and written in one transaction committed with the HNSW swap. Other
+    /// writers wait for the whole run. Ends with `Reindexed`; returns the number of chunks
+    /// reindexed.
+    pub fn reindex_all(
+        &self,
+        reembed: bool,
+        cancel: Option<&CancelToken>,
+        progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
+    ) -> Result<usize, ServiceError> {
+        self.ensure_writable()?;
+        let _writer = self.lock_writer()?;
+        let started = std::time::Instant::now();
+        let mut progress = self.throttle(progress);
+        let mut emit = |ev: ProgressEvent| { if let Some(cb) = progress.as_deref_mut() { cb(ev); } };
+        let mut repo = self.open_repo()?;
+        let (total, _) = repo.counts().map_err(|e| ServiceError::Repo(e.to_string()))?;
+        let total = total.max(0) as usize;
+        emit(ProgressEvent::Start { total_chunks: total });
+        repo.rebuild_fts().map_err(|e| ServiceError::Repo(e.to_string()))?;
+
+        #[cfg(feature = "tantivy")]
+        let (tdir, tv_staged) = (self.tantivy_dir(), sibling_dir(&self.tantivy_dir(), "rebuild"));
+        let hdir = self.hnsw_dir();
+        let h_staged = sibling_dir(&hdir, "rebuild");
+        let discard_staged = || {
+            #[cfg(feature = "tantivy")]
+            let _ = std::fs::remove_dir_all(&tv_staged);
+            let _ = std::fs::remove_dir_all(&h_staged);
+        };
+        discard_staged();
+        let dim = self.embedder.info().dimension;
+        let dtype = self.store_vector_dtype(&repo)?;
+        let mut pairs: Vec<(ChunkId, Vec<f32>)> = Vec::new();
+        // Written to the DB only at the swap, so a failed or canceled run leaves it untouched
+        let mut stamped: Vec<FileRecord> = Vec::new();
+        let mut persisted: Vec<ChunkRecord> = Vec::new();
+        let mut built = || -> Result<usize, ServiceError> {
+            #[cfg(feature = "tantivy")]
+            let tantivy = TantivyIndex::open_or_create_dir_with_opts(&tv_staged, self.tantivy_opts())
+                .map_err(|e| ServiceError::Index(e.to_string()))?;
+            let mut done = 0usize;
+            // Whole documents per page, so title enrichment sees the same inputs as at ingest
+            let mut offset = 0;
+            loop {
+                let docs = repo.list_doc_ids(REINDEX_DOC_PAGE, offset).map_err(|e| ServiceError::Repo(e.to_string()))?;
+                if docs.is_empty() { break; }
+                offset += docs.len();
+                if cancel.is_some_and(CancelToken::is_canceled) { return Err(ServiceError::Embed("canceled".into())); }
+                let mut page: Vec<ChunkRecord> = Vec::new();
+                let mut inputs: Vec<String> = Vec::new();
+                for doc in &docs {
+                    let records = repo.get_chunks_by_doc_id(doc, u32::MAX as usize, 0).map_err(|e| ServiceError::Repo(e.to_string()))?;
+                    if reembed {
+                        match repo.get_file(doc).map_err(|e| ServiceError::Repo(e.to_string()))? {
+                            Some(mut file) => {
+                                inputs.extend(embedding_inputs(&self.cfg, &file, &records).into_iter().map(Cow::into_owned));
+                                self.stamp_embedder(&mut file);
+                                stamped.push(file);
+                            }
+                            None => inputs.extend(records.iter().map(|r| r.text.clone())),
+                        }
+                    }
+                    page.extend(records);
+                }
+                #[cfg(feature = "tantivy")]
+                tantivy.upsert_records(&page).map_err(|e| ServiceError::Index(e.to_string()))?;
+                done += page.len();
+                if !reembed {
+                    emit(ProgressEvent::IndexText { total: done });
+                    continue;
+                }
+                let texts: Vec<&str> = inputs.iter().map(String::as_str).collect();
+                let (vecs, _) = if self.cfg.embed_auto {
+                    self.embed_texts_auto(&texts, cancel, None)?
+                } else {
+                    self.embed_texts_batched(&texts, cancel, None)?
+                };
+                check_embedding_dimensions(dim, vecs.iter().map(Vec::as_slice))?;
+                if self.cfg.persist_vectors_in_records {
+                    for (r, v) in page.iter_mut().zip(&vecs) { persist_vector(r, v, dtype); }
+                    persisted.extend(page.iter().cloned());
+                }
+                pairs.extend(page.into_iter().map(|r| r.chunk_id).zip(vecs));
+                emit(ProgressEvent::EmbedBatch { done, total, batch: texts.len() });
+            }
+            Ok(done)
+        };
+        let done = match built() {
+            Ok(b) => b,
+            Err(e) => {
+                discard_staged();
+                if cancel.is_some_and(CancelToken::is_canceled) { emit(ProgressEvent::Canceled); }
+                return Err(e);
+            }
+        };
+        emit(ProgressEvent::IndexText { total: done });
+
+        if reembed {
+            let mut hnsw = self.new_hnsw();
+            hnsw.set_bulk_build_min(self.cfg.hnsw_bulk_build_min);
+            hnsw.set_dtype(dtype);
+            let saved = hnsw.upsert(&pairs).map_err(|e| ServiceError::Index(e.to_string())).and_then(|_| {
+                emit(ProgressEvent::IndexVector { total: pairs.len() });
+                emit(ProgressEvent::SaveIndexes);
+                hnsw.save(&h_staged).map_err(|e| ServiceError::Io(e.to_string()))
+            });
+            if let Err(e) = saved { discard_staged(); return Err(e); }
+            // The transaction rolls back on drop if the swap fails
+            let staged = match repo.stage_chunks(&persisted) {
+                Ok(staged) => staged,
+                Err(e) => { discard_staged(); return Err(ServiceError::Repo(e.to_string())); }
+            };
+            if let Some(e) = stamped.iter().find_map(|f| staged.upsert_file(f).err()) {
+                discard_staged();
+                return Err(ServiceError::Repo(e.to_string()));
+            }
+            let mut guard = self.hnsw.write().map_err(|_| ServiceError::Index("hnsw lock poisoned".into()))?;
+            if let Err(e) = swap_in_dir(&hdir, &h_staged) { discard_staged(); return Err(ServiceError::Io(e.to_string())); }
+            staged.commit().map_err(|e| ServiceError::Repo(e.to_string()))?;
+            // The replaced snapshot holds vectors from before the reindex; never fall back to it
+            let _ = std::fs::remove_dir_all(sibling_dir(&hdir, "prev"));
+            *guard = Some(hnsw);
+            drop(guard);
+            let _ = self.hnsw_state.write().map(|mut s| *s = HnswState::Ready);
+            // Vectors now come from the current model; make it the drift reference
+            let reference = self.embedder.embed(EMBED_REFERENCE_TEXT).map_err(|e| ServiceError::Embed(e.to_string()))?;
+            let json = serde_json::to_string(&reference).map_err(|e| ServiceError::Repo(e.to_string()))?;
+            repo.set_store_meta(STORE_META_EMBED_REFERENCE, &json).map_err(|e| ServiceError::Repo(e.to_string()))?;
+            self.drift_checked_epoch.store(self.store_epoch.load(Ordering::SeqCst), Ordering::SeqCst);
+        }
+        #[cfg(feature = "tantivy")]
+        {
+            // Hold the slot while swapping so `with_tantivy` cannot reopen a half-moved directory
+            let mut guard = self.tantivy.write().map_err(|_| ServiceError::Index("tantivy lock poisoned".into()))?;
+            *guard = None;
+            swap_in_dir(&tdir, &tv_staged).map_err(|e| ServiceError::Io(e.to_string()))?;
+            let _ = std::fs::remove_dir_all(sibling_dir(&tdir, "prev"));
+            let idx = TantivyIndex::open_or_create_dir_with_opts(&tdir, self.tantivy_opts()).map_err(|e| ServiceError::Index(e.to_string()))?;
+            *guard = Some(idx);
+            let _ = self.tantivy_state.write().map(|mut s| *s = TantivyState::Ready);
+        }
+        emit(ProgressEvent::Reindexed { total: done, elapsed_ms: started.elapsed().as_millis() as u64 });
+        Ok(done)
+    }
+
     /// Quick sanity/check API: counts for chunks and FTS mirror.
     pub fn repo_counts(&self) -> Result<(i64, i64), ServiceError> {
         let repo = self.open_repo()?;
@@ -1170,43 +2645,671 @@ impl HybridService {
             .map_err(|e| ServiceError::Repo(e.to_string())))
     }
 
+    /// Page through a document's chunks in document order (`seq`), e.g. for a viewer.
+    pub fn get_document_chunks(&self, doc_id: &str, limit: usize, offset: usize) -> Result<Vec<ChunkRecord>, ServiceError> {
+        self.with_repo(|repo| repo
+            .get_chunks_by_doc_id(doc_id, limit, offset)
+            .map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Return every chunk of the base chunk's section within its document, in document order.
+    pub fn section_chunks(&self, chunk_id: &str) -> Result<Vec<ChunkRecord>, ServiceError> {
+        self.with_repo(|repo| repo
+            .get_section_chunks(&ChunkId(chunk_id.to_string()))
+            .map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
     /// List FileRecords with pagination (for GUI file list).
     pub fn list_files(&self, limit: usize, offset: usize) -> Result<Vec<FileRecord>, ServiceError> {
+        let limit = self.capped_files_limit(limit);
         self.with_repo(|repo| repo.list_files(limit, offset).map_err(|e| ServiceError::Repo(e.to_string())))
     }
 
-    /// Helper: embed texts in smaller batches according to config to limit memory spikes.
-    fn embed_texts_batched<'p>(
+    /// One page of files plus the total number of files in the store.
+    pub fn list_files_page(&self, limit: usize, offset: usize) -> Result<(Vec<FileRecord>, u64), ServiceError> {
+        let limit = self.capped_files_limit(limit);
+        self.with_repo(|repo| repo.list_files_page(limit, offset).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// FileRecords without chunks and chunks without a FileRecord. Read-only.
+    pub fn find_orphans(&self) -> Result<OrphanReport, ServiceError> {
+        self.with_repo(|repo| repo.find_orphans().map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Compare the chunk ids in the DB with those in the HNSW and Tantivy indexes. Nothing is
+    /// written: an index without files on disk is reported as `None` rather than created.
+    pub fn index_drift(&self) -> Result<IndexDrift, ServiceError> {
+        let mut db_ids: HashSet<String> = HashSet::new();
+        self.with_repo(|repo| {
+            let mut offset = 0;
+            loop {
+                let page = repo.list_chunk_ids_by_filter(&[], 10_000, offset).map_err(|e| ServiceError::Repo(e.to_string()))?;
+                if page.is_empty() { return Ok(()); }
+                offset += page.len();
+                db_ids.extend(page.into_iter().map(|c| c.0));
+            }
+        })?;
+        let drift = |index_ids: HashSet<String>| {
+            let mut missing: Vec<ChunkId> = db_ids.difference(&index_ids).cloned().map(ChunkId).collect();
+            let mut stale: Vec<ChunkId> = index_ids.difference(&db_ids).cloned().map(ChunkId).collect();
+            missing.sort_by(|a, b| a.0.cmp(&b.0));
+            stale.sort_by(|a, b| a.0.cmp(&b.0));
+            IdDrift { missing, stale }
+        };
+        let hnsw = self.with_hnsw(|h, _| h.live_ids().map(str::to_string).collect::<HashSet<_>>())?.map(&drift);
+        #[cfg(feature = "tantivy")]
+        let tantivy = {
+            let resident = self.tantivy.read().map(|g| g.is_some()).unwrap_or(false);
+            if resident || self.tantivy_dir().join("meta.json").exists() {
+                self.with_tantivy(|t, _| t.chunk_ids())?
+                    .transpose()
+                    .map_err(|e| ServiceError::Index(e.to_string()))?
+                    .map(|ids| drift(ids.into_iter().collect()))
+            } else {
+                None
+            }
+        };
+        #[cfg(not(feature = "tantivy"))]
+        let tantivy = None;
+        Ok(IndexDrift { hnsw, tantivy })
+    }
+
+    /// Fix what `find_orphans` and `index_drift` report: chunks without a FileRecord per
+    /// `opts.orphan_chunks`, index entries whose chunk is gone, and DB chunks missing from an
+    /// index (for HNSW from their persisted vectors, else re-embedded). Works in
+    /// `opts.batch_size` steps and returns what was applied; canceling stops between steps and
+    /// returns the partial report with `canceled` set. Indexes that are not built are left alone.
+    pub fn repair(
         &self,
-        texts: &[&str],
+        opts: &RepairOpts,
         cancel: Option<&CancelToken>,
-        mut progress: Option<&'p mut (dyn FnMut(ProgressEvent) + Send)>,
-    ) -> Result<Vec<Vec<f32>>, ServiceError> {
-        if texts.is_empty() { return Ok(Vec::new()); }
-        let bsz = self.cfg.embed_batch_size.max(1);
+        progress: Option<Box<dyn FnMut(ProgressEvent) + Send>>,
+    ) -> Result<RepairReport, ServiceError> {
+        if !opts.dry_run { self.ensure_writable()?; }
+        let batch = opts.batch_size.max(1);
+        let orphan_ids = self.find_orphans()?.chunks_without_file;
+        let drift = self.index_drift()?;
+        // Orphans about to be deleted need no reindexing
+        let dropped: HashSet<&str> = match opts.orphan_chunks {
+            OrphanChunkAction::Delete => orphan_ids.iter().map(|c| c.0.as_str()).collect(),
+            OrphanChunkAction::SynthesizeFile => HashSet::new(),
+        };
+        let missing = |d: &Option<IdDrift>| -> Vec<ChunkId> {
+            d.iter().flat_map(|d| &d.missing).filter(|c| !dropped.contains(c.0.as_str())).cloned().collect()
+        };
+        let (hnsw_missing, tantivy_missing) = (missing(&drift.hnsw), missing(&drift.tantivy));
+        let hnsw_stale = drift.hnsw.as_ref().map(|d| d.stale.clone()).unwrap_or_default();
+        let tantivy_stale = drift.tantivy.as_ref().map(|d| d.stale.clone()).unwrap_or_default();
+
+        let mut report = RepairReport {
+            hnsw_stale_removed: hnsw_stale.len(),
+            tantivy_stale_removed: tantivy_stale.len(),
+            hnsw_reinserted: hnsw_missing.len(),
+            tantivy_reinserted: tantivy_missing.len(),
+            ..Default::default()
+        };
+        let mut repo = self.open_repo()?;
+        let mut synthesized: BTreeMap<String, (ChunkRecord, u32)> = BTreeMap::new();
+        match opts.orphan_chunks {
+            OrphanChunkAction::Delete => report.orphan_chunks_deleted = orphan_ids.len(),
+            OrphanChunkAction::SynthesizeFile => {
+                for ids in orphan_ids.chunks(batch) {
+                    for rec in repo.get_chunks_by_ids(ids).map_err(|e| ServiceError::Repo(e.to_string()))? {
+                        synthesized.entry(rec.doc_id.0.clone()).or_insert_with(|| (rec, 0)).1 += 1;
+                    }
+                }
+                report.files_synthesized = synthesized.len();
+            }
+        }
+        let mut progress = self.throttle(progress);
+        if opts.dry_run {
+            if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Finished { total: report.changes() }); }
+            return Ok(report);
+        }
+
+        let is_canceled = || cancel.is_some_and(CancelToken::is_canceled);
+        let mut done = RepairReport::default();
+        let mut emit = |ev: ProgressEvent| { if let Some(cb) = progress.as_deref_mut() { cb(ev); } };
+        emit(ProgressEvent::Start { total_chunks: report.changes() });
+        // Cancellation is checked before each step; a canceled run reports what it applied
+        let canceled = 'run: {
+            if report.orphan_chunks_deleted > 0 {
+                for ids in orphan_ids.chunks(batch) {
+                    if is_canceled() { break 'run true; }
+                    emit(ProgressEvent::UpsertDb { total: ids.len() });
+                    let rep = self.delete_orchestrated(&mut repo, |repo, text_m, vec_m| delete_ids_orchestrated(repo, ids, batch, text_m, vec_m))?;
+                    #[cfg(feature = "tantivy")]
+                    { let _ = self.with_tantivy(|ti, _repo| { let _ = chunking_store::TextIndexMaintainer::delete_by_ids(ti, ids); }); }
+                    done.orphan_chunks_deleted += rep.db_deleted;
+                }
+            }
+            for (first, count) in synthesized.into_values() {
+                if is_canceled() { break 'run true; }
+                repo.upsert_file(&synthesized_file_record(&first, count)).map_err(|e| ServiceError::Repo(e.to_string()))?;
+                done.files_synthesized += 1;
+            }
+            for ids in hnsw_stale.chunks(batch) {
+                if is_canceled() { break 'run true; }
+                emit(ProgressEvent::IndexVector { total: ids.len() });
+                // Not in the DB any more, so only the index entries go
+                self.delete_orchestrated(&mut repo, |repo, text_m, vec_m| delete_ids_orchestrated(repo, ids, batch, text_m, vec_m))?;
+                done.hnsw_stale_removed += ids.len();
+            }
+            #[cfg(feature = "tantivy")]
+            for ids in tantivy_stale.chunks(batch) {
+                if is_canceled() { break 'run true; }
+                emit(ProgressEvent::IndexText { total: ids.len() });
+                self.with_tantivy(|ti, _repo| chunking_store::TextIndexMaintainer::delete_by_ids(ti, ids))?
+                    .transpose()
+                    .map_err(|e| ServiceError::Index(e.to_string()))?;
+                done.tantivy_stale_removed += ids.len();
+            }
+            let dim = self.embedder.info().dimension;
+            let hdir = self.hnsw_dir();
+            for ids in hnsw_missing.chunks(batch) {
+                if is_canceled() { break 'run true; }
+                // Vectors persisted in the records are reused; only the rest is embedded
+                let mut pairs: Vec<(ChunkId, Vec<f32>)> = Vec::new();
+                let mut unembedded: Vec<ChunkRecord> = Vec::new();
+                for rec in repo.get_chunks_by_ids(ids).map_err(|e| ServiceError::Repo(e.to_string()))? {
+                    match stored_vector(&rec).filter(|v| v.len() == dim) {
+                        Some(v) => pairs.push((rec.chunk_id, v)),
+                        None => unembedded.push(rec),
+                    }
+                }
+                if !unembedded.is_empty() {
+                    emit(ProgressEvent::EmbedBatch { done: done.hnsw_reinserted, total: hnsw_missing.len(), batch: unembedded.len() });
+                    let inputs = self.stored_embedding_inputs(&repo, &unembedded)?;
+                    let texts: Vec<&str> = inputs.iter().map(String::as_str).collect();
+                    let embedded = if self.cfg.embed_auto {
+                        self.embed_texts_auto(&texts, cancel, None)
+                    } else {
+                        self.embed_texts_batched(&texts, cancel, None)
+                    };
+                    let vecs = match embedded {
+                        Ok((vecs, _)) => vecs,
+                        Err(_) if is_canceled() => break 'run true,
+                        Err(e) => return Err(e),
+                    };
+                    check_embedding_dimensions(dim, vecs.iter().map(Vec::as_slice))?;
+                    pairs.extend(unembedded.into_iter().map(|r| r.chunk_id).zip(vecs));
+                }
+                let _writer = self.lock_writer()?;
+                let mut guard = self.hnsw.write().map_err(|_| ServiceError::Index("hnsw lock poisoned".into()))?;
+                let hnsw = guard.as_mut().ok_or_else(|| ServiceError::Index("HNSW index is not loaded".into()))?;
+                hnsw.upsert(&pairs).map_err(|e| ServiceError::Index(e.to_string()))?;
+                hnsw.save(&hdir).map_err(|e| ServiceError::Io(e.to_string()))?;
+                done.hnsw_reinserted += pairs.len();
+            }
+            #[cfg(feature = "tantivy")]
+            for ids in tantivy_missing.chunks(batch) {
+                if is_canceled() { break 'run true; }
+                emit(ProgressEvent::IndexText { total: ids.len() });
+                let records = repo.get_chunks_by_ids(ids).map_err(|e| ServiceError::Repo(e.to_string()))?;
+                self.with_tantivy(|ti, _repo| ti.upsert_records(&records))?
+                    .transpose()
+                    .map_err(|e| ServiceError::Index(e.to_string()))?;
+                done.tantivy_reinserted += records.len();
+            }
+            #[cfg(not(feature = "tantivy"))]
+            let _ = (tantivy_stale, tantivy_missing);
+            false
+        };
+        done.canceled = canceled;
+        emit(if canceled { ProgressEvent::Canceled } else { ProgressEvent::Finished { total: done.changes() } });
+        Ok(done)
+    }
+
+    /// Embedding inputs for stored chunks, built from each chunk's whole document as at ingest
+    /// (see `embedding_inputs`); chunks without a FileRecord use their plain text.
+    fn stored_embedding_inputs(&self, repo: &SqliteRepo, records: &[ChunkRecord]) -> Result<Vec<String>, ServiceError> {
+        let mut by_id: HashMap<String, String> = HashMap::new();
+        let docs: BTreeSet<&str> = records.iter().map(|r| r.doc_id.0.as_str()).collect();
+        for doc in docs {
+            let Some(file) = repo.get_file(doc).map_err(|e| ServiceError::Repo(e.to_string()))? else { continue };
+            let all = repo.get_chunks_by_doc_id(doc, u32::MAX as usize, 0).map_err(|e| ServiceError::Repo(e.to_string()))?;
+            by_id.extend(all.iter().map(|r| r.chunk_id.0.clone()).zip(embedding_inputs(&self.cfg, &file, &all).into_iter().map(Cow::into_owned)));
+        }
+        Ok(records.iter().map(|r| by_id.remove(&r.chunk_id.0).unwrap_or_else(|| r.text.clone())).collect())
+    }
+
+    /// Like `list_files`, but each FileRecord comes with its live chunk count and page range
+    /// (the stored `chunk_count` can drift after partial deletes). Zero chunks means orphaned.
+    pub fn list_documents(&self, limit: usize, offset: usize) -> Result<Vec<DocSummary>, ServiceError> {
+        let limit = self.capped_files_limit(limit);
+        self.with_repo(|repo| repo.list_documents(limit, offset).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Number of files; with filters, only files owning at least one matching chunk.
+    pub fn count_files(&self, filters: &[FilterClause]) -> Result<u64, ServiceError> {
+        self.with_repo(|repo| repo.count_files(filters).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Number of chunks matching `filters` (same prefilter as search and `delete_by_filter`).
+    pub fn count_by_filter(&self, filters: &[FilterClause]) -> Result<i64, ServiceError> {
+        self.with_repo(|repo| repo.count_by_filter(filters).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Per-document breakdown of `count_by_filter`: `(doc_id, chunks)` sorted by doc id.
+    pub fn count_by_filter_per_doc(&self, filters: &[FilterClause]) -> Result<Vec<(String, i64)>, ServiceError> {
+        self.with_repo(|repo| repo.count_by_filter_per_doc(filters).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Autocomplete: up to `limit` known terms or past queries starting with `prefix`
+    /// (case-insensitive), most frequent first. A plain index lookup, no search is run;
+    /// errors yield no suggestions.
+    pub fn suggest(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let prefix = prefix.trim().to_lowercase();
+        if prefix.is_empty() || limit == 0 { return Vec::new(); }
+        self.with_repo(|repo| repo.suggest_terms(&prefix, limit).map_err(|e| ServiceError::Repo(e.to_string())))
+            .map(|v| v.into_iter().map(|(term, _)| term).collect())
+            .unwrap_or_default()
+    }
+
+    /// Add `query` (trimmed, lowercased) to the `suggest` vocabulary.
+    pub fn record_query(&self, query: &str) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        let q = query.trim().to_lowercase();
+        if q.is_empty() { return Ok(()); }
+        let mut repo = self.open_repo()?;
+        repo.add_suggest_terms(&[(q, 1)], self.cfg.suggest_max_terms).map_err(|e| ServiceError::Repo(e.to_string()))
+    }
+
+    /// Best-effort `record_query` when `suggest_record_queries` is on.
+    fn maybe_record_query(&self, query: &str) {
+        if self.cfg.suggest_record_queries && !self.cfg.read_only {
+            let _ = self.record_query(query);
+        }
+    }
+
+    /// Log a search of `query` that returned `chunk_ids` (best first); returns its log id,
+    /// which `label_search_hit` takes.
+    pub fn log_search(&self, query: &str, chunk_ids: &[ChunkId]) -> Result<i64, ServiceError> {
+        self.ensure_writable()?;
+        let mut repo = self.open_repo()?;
+        repo.log_search(query, &Utc::now().to_rfc3339(), chunk_ids).map_err(|e| ServiceError::Repo(e.to_string()))
+    }
+
+    /// Best-effort `log_search` of `hits` when `search_log` is on.
+    fn maybe_log_search(&self, query: &str, hits: &[SearchHit]) {
+        if self.cfg.search_log && !self.cfg.read_only {
+            let ids: Vec<ChunkId> = hits.iter().map(|h| h.chunk.chunk_id.clone()).collect();
+            let _ = self.log_search(query, &ids);
+        }
+    }
+
+    /// Label `chunk_id` in logged search `log_id` (e.g., 1.0 when the user opened it);
+    /// false when that search did not return the chunk.
+    pub fn label_search_hit(&self, log_id: i64, chunk_id: &str, label: f32) -> Result<bool, ServiceError> {
+        self.ensure_writable()?;
+        self.with_repo(|repo| repo.set_search_label(log_id, &ChunkId(chunk_id.to_string()), label).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Up to `limit` logged searches after `after_id` (0 for the first page), oldest first.
+    pub fn search_log(&self, after_id: i64, limit: usize) -> Result<Vec<SearchLogEntry>, ServiceError> {
+        self.with_repo(|repo| repo.list_search_log(after_id, limit).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Write one query–passage pair per logged search hit, joining the search log with the
+    /// current chunk texts, for reranker training or evaluation. Hits whose chunk no longer
+    /// exists are skipped. Returns the number of pairs written.
+    pub fn export_rerank_dataset(&self, mut writer: impl std::io::Write, format: RerankExportFormat) -> Result<usize, ServiceError> {
+        const PAGE: usize = 200;
+        let repo = self.open_repo()?;
+        let mut written = 0usize;
+        let mut after = 0i64;
+        loop {
+            let entries = repo.list_search_log(after, PAGE).map_err(|e| ServiceError::Repo(e.to_string()))?;
+            for entry in &entries {
+                let ids: Vec<ChunkId> = entry.hits.iter().map(|(id, _)| id.clone()).collect();
+                let texts: HashMap<String, String> = repo
+                    .get_chunks_by_ids(&ids)
+                    .map_err(|e| ServiceError::Repo(e.to_string()))?
+                    .into_iter()
+                    .map(|r| (r.chunk_id.0, r.text))
+                    .collect();
+                for (rank, (id, label)) in entry.hits.iter().enumerate() {
+                    let Some(passage) = texts.get(&id.0) else { continue };
+                    match format {
+                        RerankExportFormat::Jsonl => {
+                            let pair = RerankPair { query: entry.query.clone(), chunk_id: id.0.clone(), rank, passage: passage.clone(), label: *label };
+                            serde_json::to_writer(&mut writer, &pair).map_err(|e| ServiceError::Io(e.to_string()))?;
+                            writer.write_all(b"\n").map_err(|e| ServiceError::Io(e.to_string()))?;
+                        }
+                        RerankExportFormat::Tsv => {
+                            let clean = |t: &str| t.replace(['\t', '\n', '\r'], " ");
+                            let label = label.map(|l| l.to_string()).unwrap_or_default();
+                            writeln!(writer, "{}\t{}\t{}", clean(&entry.query), clean(passage), label).map_err(|e| ServiceError::Io(e.to_string()))?;
+                        }
+                    }
+                    written += 1;
+                }
+            }
+            match entries.last() {
+                Some(last) if entries.len() == PAGE => after = last.log_id,
+                _ => break,
+            }
+        }
+        writer.flush().map_err(|e| ServiceError::Io(e.to_string()))?;
+        Ok(written)
+    }
+
+    /// Store an attachment (e.g., a rendered first page or a summary) for `doc_id` under
+    /// `key`, replacing any previous one. Rejected above `doc_blob_max_bytes`.
+    pub fn put_doc_blob(&self, doc_id: &str, key: &str, blob: &[u8]) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        let max = self.cfg.doc_blob_max_bytes;
+        if max > 0 && blob.len() > max {
+            return Err(ServiceError::BlobTooLarge { size: blob.len(), max });
+        }
+        self.with_repo(|repo| repo.put_doc_blob(doc_id, key, blob).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Attachment `key` of `doc_id`, if any.
+    pub fn get_doc_blob(&self, doc_id: &str, key: &str) -> Result<Option<Vec<u8>>, ServiceError> {
+        self.with_repo(|repo| repo.get_doc_blob(doc_id, key).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// `(key, size in bytes)` of the attachments of `doc_id`.
+    pub fn list_doc_blobs(&self, doc_id: &str) -> Result<Vec<(String, u64)>, ServiceError> {
+        self.with_repo(|repo| repo.list_doc_blobs(doc_id).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Remove attachment `key` of `doc_id`; true when it existed.
+    pub fn delete_doc_blob(&self, doc_id: &str, key: &str) -> Result<bool, ServiceError> {
+        self.ensure_writable()?;
+        self.with_repo(|repo| repo.delete_doc_blob(doc_id, key).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Replace the renderer used for `pdf_page_images` (PDFium by default).
+    pub fn set_page_renderer(&self, renderer: Arc<dyn PageRenderer>) {
+        if let Ok(mut w) = self.page_renderer.write() { *w = renderer; }
+    }
+
+    /// Render the distinct start pages of `records` from the PDF at `path`, store each as a
+    /// blob of the chunks' document and set `META_PAGE_IMAGE` on the chunks of stored pages.
+    /// Checked for cancellation between pages; a canceled or failed run deletes the page
+    /// images it added (images from an earlier ingest of the document are kept).
+    fn attach_page_images(&self, path: &str, records: &mut [ChunkRecord], cancel: Option<&CancelToken>) -> Result<(), ServiceError> {
+        self.ensure_writable()?;
+        let Some(doc_id) = records.first().map(|r| r.doc_id.0.clone()) else { return Ok(()) };
+        let pages: Vec<u32> = records.iter().filter_map(|r| r.page_start).collect::<std::collections::BTreeSet<_>>().into_iter().collect();
+        if pages.is_empty() { return Ok(()); }
+        let renderer = self.page_renderer.read().map(|g| g.clone()).map_err(|_| ServiceError::Io("page renderer lock poisoned".into()))?;
+        let canceled = || cancel.is_some_and(|ct| ct.is_canceled());
+        if canceled() { return Err(ServiceError::Embed("canceled".into())); }
+        let existing: HashSet<String> = self.list_doc_blobs(&doc_id)?.into_iter().map(|(key, _)| key).collect();
+        let mut stored: HashSet<u32> = HashSet::new();
+        let mut store_err: Option<ServiceError> = None;
+        let rendered = renderer
+            .render_pages(path, &pages, &mut |page, png| {
+                if let Err(e) = self.put_doc_blob(&doc_id, &page_image_blob_key(page), &png) {
+                    store_err = Some(e);
+                    return false;
+                }
+                stored.insert(page);
+                !canceled()
+            })
+            .map_err(ServiceError::Io);
+        let failed = match (rendered, store_err) {
+            (Err(e), _) | (Ok(()), Some(e)) => Some(e),
+            (Ok(()), None) if canceled() => Some(ServiceError::Embed("canceled".into())),
+            _ => None,
+        };
+        if let Some(e) = failed {
+            for page in &stored {
+                let key = page_image_blob_key(*page);
+                if !existing.contains(&key) { let _ = self.delete_doc_blob(&doc_id, &key); }
+            }
+            return Err(e);
+        }
+        for rec in records.iter_mut() {
+            if let Some(page) = rec.page_start.filter(|p| stored.contains(p)) {
+                rec.meta.insert(chunk_model::META_PAGE_IMAGE.to_string(), page_image_blob_key(page));
+            }
+        }
+        Ok(())
+    }
+
+    fn capped_files_limit(&self, limit: usize) -> usize {
+        match self.cfg.list_files_max_limit {
+            0 => limit,
+            cap => limit.min(cap),
+        }
+    }
+
+    /// Stream the whole store as NDJSON: first every `FileRecord`, then every `ChunkRecord`,
+    /// one `ExportLine` per line. Pages through the repo so memory stays bounded.
+    /// Returns `(files, chunks)` written.
+    pub fn export_ndjson(&self, mut writer: impl std::io::Write) -> Result<(usize, usize), ServiceError> {
+        const PAGE: usize = 500;
+        let repo = self.open_repo()?;
+        let mut write_line = |line: &ExportLine<'_>| -> Result<(), ServiceError> {
+            serde_json::to_writer(&mut writer, line).map_err(|e| ServiceError::Io(e.to_string()))?;
+            writer.write_all(b"\n").map_err(|e| ServiceError::Io(e.to_string()))
+        };
+        let mut files = 0usize;
+        loop {
+            let page = repo.list_files(PAGE, files).map_err(|e| ServiceError::Repo(e.to_string()))?;
+            for record in &page {
+                write_line(&ExportLine::File { schema_version: chunk_model::SCHEMA_MAJOR, schema_minor: chunk_model::SCHEMA_MINOR, record })?;
+            }
+            files += page.len();
+            if page.len() < PAGE { break; }
+        }
+        let mut chunks = 0usize;
+        for rec in repo.iter_chunks(PAGE) {
+            let record = rec.map_err(|e| ServiceError::Repo(e.to_string()))?;
+            write_line(&ExportLine::Chunk { schema_version: chunk_model::SCHEMA_MAJOR, schema_minor: chunk_model::SCHEMA_MINOR, record: &record })?;
+            chunks += 1;
+        }
+        writer.flush().map_err(|e| ServiceError::Io(e.to_string()))?;
+        Ok((files, chunks))
+    }
+
+    /// Read `ExportLine`s back into the store. Chunks are upserted in batches through
+    /// `ingest_records`; with `reembed == false`, vectors found under `EXTRA_EMBEDDING_KEY`
+    /// are reused and only chunks without a usable one are embedded. Lines with a foreign
+    /// schema major, undecodable JSON or an empty text are skipped and reported.
+    pub fn import_ndjson(&self, reader: impl std::io::BufRead, reembed: bool) -> Result<ImportReport, ServiceError> {
+        const BATCH: usize = 256;
+        self.ensure_writable()?;
+        let dim = self.embedder.info().dimension;
+        let mut report = ImportReport::default();
+        let mut batch: Vec<(ChunkRecord, Option<Vec<f32>>)> = Vec::new();
+        let flush = |batch: &mut Vec<(ChunkRecord, Option<Vec<f32>>)>, report: &mut ImportReport| -> Result<(), ServiceError> {
+            let (reuse, embed): (Vec<_>, Vec<_>) = batch.drain(..).partition(|(_, v)| v.is_some());
+            let pairs: Vec<(ChunkId, Vec<f32>)> = reuse.iter().filter_map(|(r, v)| v.clone().map(|v| (r.chunk_id.clone(), v))).collect();
+            let reuse: Vec<ChunkRecord> = reuse.into_iter().map(|(r, _)| r).collect();
+            let embed: Vec<ChunkRecord> = embed.into_iter().map(|(r, _)| r).collect();
+            self.ingest_records(&reuse, EmbedPolicy::UseProvided(pairs))?;
+            self.ingest_records(&embed, EmbedPolicy::EmbedAll)?;
+            report.reused_vectors += reuse.len();
+            report.chunks += reuse.len() + embed.len();
+            Ok(())
+        };
+        for (idx, line) in reader.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = match line {
+                Ok(l) => l,
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    report.skipped.push((line_no, e.to_string()));
+                    continue;
+                }
+                Err(e) => return Err(ServiceError::Io(e.to_string())),
+            };
+            if line.trim().is_empty() { continue; }
+            let value: serde_json::Value = match serde_json::from_str(&line) {
+                Ok(v) => v,
+                Err(e) => {
+                    report.skipped.push((line_no, format!("invalid line: {e}")));
+                    continue;
+                }
+            };
+            // Check the version before decoding: records of another major version need not fit
+            // the current types, and "unsupported version" is the useful reason for them
+            if let Some(v) = value.get("schema_version").and_then(|v| v.as_u64()) {
+                if v != chunk_model::SCHEMA_MAJOR as u64 {
+                    report.skipped.push((line_no, format!("unsupported schema_version {v} (expected {})", chunk_model::SCHEMA_MAJOR)));
+                    continue;
+                }
+            }
+            let mut parsed: ImportLine = match serde_json::from_value(value) {
+                Ok(p) => p,
+                Err(e) => {
+                    report.skipped.push((line_no, format!("invalid line: {e}")));
+                    continue;
+                }
+            };
+            let conflicts = parsed.resolve_extra_conflicts(self.cfg.import_extra_conflicts);
+            if !conflicts.is_empty() { report.conflicts.push((line_no, conflicts)); }
+            match parsed {
+                ImportLine::File { record, .. } => {
+                    self.with_repo(|repo| repo.upsert_file(&record).map_err(|e| ServiceError::Repo(e.to_string())))?;
+                    report.files += 1;
+                }
+                ImportLine::Chunk { mut record, .. } => {
+                    if let Err(reason) = record.validate_soft() {
+                        report.skipped.push((line_no, reason));
+                        continue;
+                    }
+                    let stored = stored_vector(&record);
+                    record.extra.remove(EXTRA_EMBEDDING_KEY);
+                    record.extra.remove(EXTRA_EMBEDDING_F16_KEY);
+                    let vector = if reembed { None } else { stored.filter(|v| v.len() == dim) };
+                    batch.push((record, vector));
+                    if batch.len() >= BATCH { flush(&mut batch, &mut report)?; }
+                }
+            }
+        }
+        flush(&mut batch, &mut report)?;
+        Ok(report)
+    }
+
+    /// Set `meta[META_TOKENS]` on each record and `file.total_tokens` to their sum. `embedded`
+    /// are the counts from embedding, reused when the embedded inputs were the chunk texts;
+    /// otherwise (e.g., title-prefixed inputs) the texts are tokenized again.
+    fn apply_token_counts(&self, file: &mut FileRecord, records: &mut [ChunkRecord], embedded: Option<Vec<usize>>) {
+        let counts = match embedded {
+            Some(c) if c.len() == records.len() => c,
+            _ => records.iter().map(|r| self.embedder.count_tokens(&r.text)).collect(),
+        };
+        for (rec, n) in records.iter_mut().zip(&counts) {
+            rec.meta.insert(chunk_model::META_TOKENS.to_string(), n.to_string());
+        }
+        file.total_tokens = Some(counts.iter().sum::<usize>().min(u32::MAX as usize) as u32);
+    }
+
+    /// Record which model embedded the document, for auditing against the active one.
+    fn stamp_embedder(&self, file: &mut FileRecord) {
+        let info = self.embedder.info();
+        file.meta.insert(chunk_model::META_EMBED_MODEL_ID.to_string(), info.embedding_model_id.clone());
+        file.meta.insert(chunk_model::META_EMBED_DIM.to_string(), info.dimension.to_string());
+        file.meta.insert(chunk_model::META_EMBED_FINGERPRINT.to_string(), self.embedder_fingerprint.clone());
+    }
+
+    /// `stamp_embedder` and store the file; called only once its vectors are committed, so a
+    /// failed ingest never claims the active model.
+    fn record_embedder(&self, file: &mut FileRecord) -> Result<(), ServiceError> {
+        self.stamp_embedder(file);
+        self.with_repo(|repo| repo.upsert_file(file).map_err(|e| ServiceError::Repo(e.to_string())))
+    }
+
+    /// Run `embed` on the texts missing from `embed_cache` and merge the cached vectors back
+    /// in input order. New vectors are stored best-effort. Without a cache, embeds everything.
+    fn embed_through_cache(
+        &self,
+        texts: &[&str],
+        embed: impl FnOnce(&[&str]) -> Result<(Vec<Vec<f32>>, Vec<usize>), ServiceError>,
+    ) -> Result<(Vec<Vec<f32>>, Vec<usize>), ServiceError> {
+        let Some(cache) = self.embed_cache.as_ref() else { return embed(texts) };
+        // Keyed by the full fingerprint so a swapped model file or changed settings miss
+        let model_id = &self.embedder_fingerprint;
+        let keys: Vec<String> = texts.iter().map(|t| EmbedCache::key(model_id, t)).collect();
+        let mut hits = cache.get_many(&keys).unwrap_or_else(|e| {
+            eprintln!("[embed-cache] lookup failed: {}", e);
+            HashMap::new()
+        });
+        let miss_idx: Vec<usize> = (0..texts.len()).filter(|&i| !hits.contains_key(&keys[i])).collect();
+        if !miss_idx.is_empty() {
+            let miss_texts: Vec<&str> = miss_idx.iter().map(|&i| texts[i]).collect();
+            let (vecs, counts) = embed(&miss_texts)?;
+            let fresh: Vec<(String, CachedEmbedding)> = miss_idx
+                .iter()
+                .zip(vecs)
+                .zip(counts)
+                .map(|((&i, vector), tokens)| (keys[i].clone(), CachedEmbedding { vector, tokens }))
+                .collect();
+            if let Err(e) = cache.put_many(model_id, &fresh) {
+                eprintln!("[embed-cache] store failed: {}", e);
+            }
+            hits.extend(fresh);
+        }
+        let mut out = Vec::with_capacity(texts.len());
+        let mut tokens = Vec::with_capacity(texts.len());
+        for key in &keys {
+            match hits.get(key) {
+                Some(entry) => { out.push(entry.vector.clone()); tokens.push(entry.tokens); }
+                None => return Err(ServiceError::Embed("missing embedding output".into())),
+            }
+        }
+        Ok((out, tokens))
+    }
+
+    /// Helper: embed texts in smaller batches according to config to limit memory spikes.
+    /// Also returns each text's token count from the same tokenization.
+    fn embed_texts_batched(
+        &self,
+        texts: &[&str],
+        cancel: Option<&CancelToken>,
+        progress: Option<&mut (dyn FnMut(ProgressEvent) + Send)>,
+    ) -> Result<(Vec<Vec<f32>>, Vec<usize>), ServiceError> {
+        self.embed_through_cache(texts, |texts| self.embed_batched_uncached(texts, cancel, progress))
+    }
+
+    fn embed_batched_uncached(
+        &self,
+        texts: &[&str],
+        cancel: Option<&CancelToken>,
+        mut progress: Option<&mut (dyn FnMut(ProgressEvent) + Send)>,
+    ) -> Result<(Vec<Vec<f32>>, Vec<usize>), ServiceError> {
+        if texts.is_empty() { return Ok((Vec::new(), Vec::new())); }
+        let bsz = self.cfg.embed_batch_size.max(1);
         let mut out: Vec<Vec<f32>> = Vec::with_capacity(texts.len());
+        let mut tokens: Vec<usize> = Vec::with_capacity(texts.len());
         let mut done = 0usize;
         for chunk in texts.chunks(bsz) {
             if let Some(ct) = cancel { if ct.is_canceled() { if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Canceled); } return Err(ServiceError::Embed("canceled".into())); } }
-            let vecs = self
+            let (vecs, counts) = self
                 .embedder
-                .embed_batch(chunk)
+                .embed_batch_counted(chunk)
                 .map_err(|e| ServiceError::Embed(e.to_string()))?;
             out.extend(vecs);
+            tokens.extend(counts);
             done += chunk.len();
             if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::EmbedBatch { done, total: texts.len(), batch: chunk.len() }); }
         }
-        Ok(out)
+        Ok((out, tokens))
     }
 
     /// Helper: auto batch sizing with simple bucketing by length and backoff on failure.
-    fn embed_texts_auto<'p>(
+    /// Returns token counts like `embed_texts_batched`.
+    fn embed_texts_auto(
         &self,
         texts: &[&str],
         cancel: Option<&CancelToken>,
-        mut progress: Option<&'p mut (dyn FnMut(ProgressEvent) + Send)>,
-    ) -> Result<Vec<Vec<f32>>, ServiceError> {
-        if texts.is_empty() { return Ok(Vec::new()); }
+        progress: Option<&mut (dyn FnMut(ProgressEvent) + Send)>,
+    ) -> Result<(Vec<Vec<f32>>, Vec<usize>), ServiceError> {
+        self.embed_through_cache(texts, |texts| self.embed_auto_uncached(texts, cancel, progress))
+    }
+
+    fn embed_auto_uncached(
+        &self,
+        texts: &[&str],
+        cancel: Option<&CancelToken>,
+        mut progress: Option<&mut (dyn FnMut(ProgressEvent) + Send)>,
+    ) -> Result<(Vec<Vec<f32>>, Vec<usize>), ServiceError> {
+        if texts.is_empty() { return Ok((Vec::new(), Vec::new())); }
 
         // Build (index, approx_len) and sort by length (ascending)
         let mut items: Vec<(usize, usize)> = texts
@@ -1218,6 +3321,7 @@ impl HybridService {
 
         let max_input = self.cfg.embedder.max_input_length.max(1);
         let mut out: Vec<Option<Vec<f32>>> = vec![None; texts.len()];
+        let mut tokens: Vec<usize> = vec![0; texts.len()];
         let mut i = 0;
         let mut done_total = 0usize;
         while i < items.len() {
@@ -1244,10 +3348,11 @@ impl HybridService {
 
                 if let Some(ct) = cancel { if ct.is_canceled() { if let Some(cb) = progress.as_deref_mut() { cb(ProgressEvent::Canceled); } return Err(ServiceError::Embed("canceled".into())); } }
 
-                match self.embedder.embed_batch(&batch_texts) {
-                    Ok(vecs) => {
-                        for (bi, v) in batch_idx.iter().zip(vecs.into_iter()) {
+                match self.embedder.embed_batch_counted(&batch_texts) {
+                    Ok((vecs, counts)) => {
+                        for ((bi, v), n) in batch_idx.iter().zip(vecs.into_iter()).zip(counts) {
                             out[*bi] = Some(v);
+                            tokens[*bi] = n;
                         }
                         k = end; // advance
                         done_total += batch_idx.len();
@@ -1255,12 +3360,14 @@ impl HybridService {
                         // Optional: slowly increase bsz on success
                         if bsz < self.cfg.embed_initial_batch { bsz = (bsz * 2).min(self.cfg.embed_initial_batch); }
                     }
-                    Err(_e) => {
+                    Err(e) if e.is_transient() => {
                         // Backoff and retry smaller batch
-                        if bsz <= self.cfg.embed_min_batch { return Err(ServiceError::Embed("embed auto-batch failed even at minimum batch".into())); }
+                        if bsz <= self.cfg.embed_min_batch { return Err(ServiceError::Embed(format!("embed auto-batch failed even at minimum batch: {e}"))); }
                         bsz = (bsz / 2).max(self.cfg.embed_min_batch);
                         continue;
                     }
+                    // Configuration, input and protocol errors would fail at any batch size
+                    Err(e) => return Err(ServiceError::Embed(e.to_string())),
                 }
             }
             i = j;
@@ -1271,18 +3378,555 @@ impl HybridService {
         for v in out.into_iter() {
             match v { Some(vec) => result.push(vec), None => return Err(ServiceError::Embed("missing embedding output".into())) }
         }
-        Ok(result)
+        Ok((result, tokens))
     }
 }
 
+/// Options the search signals fetch with: `opts` itself, or `fetch_n()` candidates when
+/// `max_per_doc` is set (so the cap has room to pull in hits from other documents) or
+/// `min_fetch` exceeds `top_k`. Fusion truncates the result back to `opts.top_k`. The widened
+/// options carry a `fetch_factor` of 1, so a signal does not widen its fetch a second time.
+fn candidate_opts(opts: &SearchOptions) -> Cow<'_, SearchOptions> {
+    if opts.max_per_doc.is_some() || opts.min_fetch > opts.top_k {
+        Cow::Owned(SearchOptions { top_k: opts.fetch_n(), fetch_factor: 1, ..opts.clone() })
+    } else {
+        Cow::Borrowed(opts)
+    }
+}
+
+/// Add one signal's matches to the fused candidates, creating entries from `empty` (which
+/// carries the weights) and summing repeated ids into the field `slot` selects.
+fn add_signal(
+    score_map: &mut HashMap<String, ScoreBreakdown>,
+    empty: ScoreBreakdown,
+    matches: Vec<chunking_store::TextMatch>,
+    slot: impl Fn(&mut ScoreBreakdown) -> &mut Option<f32>,
+) {
+    for m in matches {
+        let field = slot(score_map.entry(m.chunk_id.0).or_insert(empty));
+        *field = Some(field.unwrap_or(0.0) + m.score);
+    }
+}
+
+/// Vector persisted under `EXTRA_EMBEDDING_KEY` or `EXTRA_EMBEDDING_F16_KEY`, if any.
+fn stored_vector(chunk: &ChunkRecord) -> Option<Vec<f32>> {
+    if let Some(v) = chunk.extra.get(EXTRA_EMBEDDING_KEY) {
+        return serde_json::from_value::<Vec<f32>>(v.clone()).ok();
+    }
+    let hex = chunk.extra.get(EXTRA_EMBEDDING_F16_KEY)?.as_str()?;
+    if hex.len() % 4 != 0 { return None; }
+    let bits: Option<Vec<u16>> = (0..hex.len()).step_by(4).map(|i| u16::from_str_radix(hex.get(i..i + 4)?, 16).ok()).collect();
+    Some(chunking_store::hnsw_index::decode_f16(&bits?))
+}
+
+/// Store `v` in `chunk.extra` at `dtype` precision, replacing a vector stored at the other one.
+fn persist_vector(chunk: &mut ChunkRecord, v: &[f32], dtype: VectorDtype) {
+    match dtype {
+        VectorDtype::F32 => {
+            chunk.extra.remove(EXTRA_EMBEDDING_F16_KEY);
+            chunk.extra.insert(EXTRA_EMBEDDING_KEY.to_string(), serde_json::json!(v));
+        }
+        VectorDtype::F16 => {
+            chunk.extra.remove(EXTRA_EMBEDDING_KEY);
+            let hex: String = chunking_store::hnsw_index::encode_f16(v).iter().map(|h| format!("{h:04x}")).collect();
+            chunk.extra.insert(EXTRA_EMBEDDING_F16_KEY.to_string(), serde_json::Value::String(hex));
+        }
+    }
+}
+
+/// Cosine similarity of `a` and `b`; `None` when the lengths differ or either is zero.
+fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() { return None; }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let na = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let nb = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if na == 0.0 || nb == 0.0 { return None; }
+    Some(dot / (na * nb))
+}
+
+/// Best score first; stable, so equal scores keep their order.
+fn sort_hits_by_score(hits: &mut [SearchHit]) {
+    hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+/// Apply `opts.max_per_doc` to ranked `hits`, then truncate to `opts.top_k`.
+fn cap_per_doc(mut hits: Vec<SearchHit>, opts: &SearchOptions) -> Vec<SearchHit> {
+    if let Some(n) = opts.max_per_doc {
+        cap_hits_per_doc(&mut hits, n);
+        hits.truncate(opts.top_k);
+    }
+    hits
+}
+
+/// Build the texts to embed for a file's chunks. Short documents get their title and
+/// tags prepended when `embed_include_title` is enabled; stored chunk text is unchanged.
+pub(crate) fn embedding_inputs<'a>(cfg: &ServiceConfig, file: &FileRecord, records: &'a [ChunkRecord]) -> Vec<Cow<'a, str>> {
+    let title = file.title_guess.as_deref().map(str::trim).filter(|t| !t.is_empty());
+    let doc_chars: usize = records.iter().map(|r| r.text.chars().count()).sum();
+    let enrich = cfg.embed_include_title
+        && doc_chars < cfg.embed_title_max_doc_chars
+        && (title.is_some() || !file.tags.is_empty());
+    if !enrich {
+        return records.iter().map(|r| Cow::Borrowed(r.text.as_str())).collect();
+    }
+    let mut prefix = String::new();
+    if let Some(t) = title { prefix.push_str(t); prefix.push('\n'); }
+    if !file.tags.is_empty() { prefix.push_str(&file.tags.join(", ")); prefix.push('\n'); }
+    records.iter().map(|r| Cow::Owned(format!("{prefix}{}", r.text))).collect()
+}
+
+/// Minimal FileRecord for a document that only exists as chunks, taking source and dates
+/// from `first`. Used by `HybridService::repair`.
+fn synthesized_file_record(first: &ChunkRecord, chunk_count: u32) -> FileRecord {
+    FileRecord {
+        schema_version: chunk_model::SCHEMA_MAJOR,
+        doc_id: first.doc_id.clone(),
+        doc_revision: None,
+        source_uri: first.source_uri.clone(),
+        source_mime: first.source_mime.clone(),
+        file_size_bytes: None,
+        content_sha256: None,
+        page_count: None,
+        extracted_at: first.extracted_at.clone(),
+        created_at_meta: None,
+        updated_at_meta: None,
+        title_guess: None,
+        author_guess: None,
+        dominant_lang: None,
+        tags: Vec::new(),
+        ingest_tool: Some("repair".into()),
+        ingest_tool_version: None,
+        reader_backend: None,
+        ocr_used: None,
+        ocr_langs: Vec::new(),
+        chunk_count: Some(chunk_count),
+        total_tokens: None,
+        meta: Default::default(),
+        extra: Default::default(),
+    }
+}
+
+/// Document-blob key of the rendered image of PDF page `page` (1-based).
+pub fn page_image_blob_key(page: u32) -> String { format!("page_image/{}", page) }
+
+/// Drop or tag chunks whose unique-token ratio is below `quality_min_unique_ratio`
+/// (repeated separators, OCR noise). No-op when the threshold is 0.
+pub fn apply_quality_gate(cfg: &ServiceConfig, records: &mut Vec<ChunkRecord>) {
+    if cfg.quality_min_unique_ratio <= 0.0 { return; }
+    let is_low = |r: &ChunkRecord| match file_chunker::quality::unique_token_ratio(&r.text) {
+        Some(ratio) => ratio < cfg.quality_min_unique_ratio,
+        None => true,
+    };
+    match cfg.quality_gate {
+        QualityGateAction::Drop => records.retain(|r| !is_low(r)),
+        QualityGateAction::Tag => {
+            for r in records.iter_mut() {
+                if is_low(r) { r.meta.insert(chunk_model::META_LOW_QUALITY.to_string(), "true".to_string()); }
+            }
+        }
+    }
+}
+
+fn compact_store_at(db: &Path, hdir: &Path, hnsw: &RwLock<Option<HnswIndex>>) -> Result<(), ServiceError> {
+    let repo = open_repo_at(db, false)?;
+    repo.compact().map_err(|e| ServiceError::Repo(e.to_string()))?;
+    drop(repo);
+    let mut guard = hnsw.write().map_err(|_| ServiceError::Index("hnsw lock poisoned".into()))?;
+    if let Some(h) = guard.as_mut() {
+        if h.tombstone_ratio() > 0.0 {
+            h.compact();
+            h.save(hdir).map_err(|e| ServiceError::Io(e.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+fn open_repo_at(path: &Path, read_only: bool) -> Result<SqliteRepo, ServiceError> {
+    let res = if read_only { SqliteRepo::open_read_only(path) } else { SqliteRepo::open(path) };
+    res.map_err(|e| ServiceError::Repo(e.to_string()))
+}
+
+/// Default hybrid weights shared by the CLI, server and `search_mmr` (text 1 : vector 4).
+pub const DEFAULT_W_TEXT: f32 = 1.0;
+pub const DEFAULT_W_VEC: f32 = 4.0;
+
+/// `extra` key holding a chunk's vector as a JSON array of floats. Written at ingest when
+/// `persist_vectors_in_records` is on and reused by `import_ndjson` unless re-embedding.
+pub const EXTRA_EMBEDDING_KEY: &str = "vector.f32";
+/// `extra` key used instead of `EXTRA_EMBEDDING_KEY` by stores with the `F16` vector dtype:
+/// the half-precision bits of each component as 4 lowercase hex digits, concatenated.
+pub const EXTRA_EMBEDDING_F16_KEY: &str = "vector.f16";
+
+type LoaderJob = Box<dyn FnOnce() + Send + 'static>;
+
+struct LoaderQueue {
+    jobs: VecDeque<(u64, LoaderJob)>,
+    running: usize,
+    peak: usize,
+    latest_epoch: u64,
+}
+
+struct LoaderShared {
+    limit: usize,
+    queue: Mutex<LoaderQueue>,
+    idle: Condvar,
+}
+
+/// Bounded executor for background index loads. At most `limit` jobs run at once; jobs
+/// submitted for an older store epoch than the newest one seen are dropped before they
+/// start (running ones are expected to check the epoch themselves and bail out).
+#[derive(Clone)]
+pub struct LoaderPool(Arc<LoaderShared>);
+
+impl LoaderPool {
+    pub fn new(limit: usize) -> Self {
+        let queue = LoaderQueue { jobs: VecDeque::new(), running: 0, peak: 0, latest_epoch: 0 };
+        Self(Arc::new(LoaderShared { limit: limit.max(1), queue: Mutex::new(queue), idle: Condvar::new() }))
+    }
+
+    /// Queue `job` for store `epoch`, preempting queued jobs of older epochs.
+    pub fn submit<F: FnOnce() + Send + 'static>(&self, epoch: u64, job: F) {
+        let mut q = self.0.queue.lock().unwrap_or_else(|e| e.into_inner());
+        if epoch < q.latest_epoch { return; }
+        if epoch > q.latest_epoch {
+            q.latest_epoch = epoch;
+            q.jobs.retain(|(e, _)| *e >= epoch);
+        }
+        q.jobs.push_back((epoch, Box::new(job)));
+        if q.running < self.0.limit {
+            q.running += 1;
+            q.peak = q.peak.max(q.running);
+            let shared = Arc::clone(&self.0);
+            std::thread::spawn(move || Self::work(shared));
+        }
+    }
+
+    fn work(shared: Arc<LoaderShared>) {
+        loop {
+            let job = {
+                let mut q = shared.queue.lock().unwrap_or_else(|e| e.into_inner());
+                match q.jobs.pop_front() {
+                    Some((_, job)) => job,
+                    None => {
+                        q.running -= 1;
+                        shared.idle.notify_all();
+                        return;
+                    }
+                }
+            };
+            // A panicking job must not take its worker's `running` slot down with it, or
+            // `wait_idle` would block forever; the panic itself is reported by the hook
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+        }
+    }
+
+    /// Jobs currently running.
+    pub fn running(&self) -> usize { self.0.queue.lock().map(|q| q.running).unwrap_or(0) }
+
+    /// Highest number of jobs that ran at the same time.
+    pub fn peak(&self) -> usize { self.0.queue.lock().map(|q| q.peak).unwrap_or(0) }
+
+    /// Block until no job is running or queued.
+    pub fn wait_idle(&self) {
+        let mut q = self.0.queue.lock().unwrap_or_else(|e| e.into_inner());
+        while q.running > 0 || !q.jobs.is_empty() {
+            q = self.0.idle.wait(q).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Least-recently-used map from query string to its embedding, with a fixed capacity.
+pub struct QueryEmbedCache {
+    capacity: usize,
+    entries: HashMap<String, (u64, Vec<f32>)>,
+    tick: u64,
+}
+
+impl QueryEmbedCache {
+    /// Cache holding at most `capacity` queries; 0 never stores anything.
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::new(), tick: 0 }
+    }
+
+    /// Embedding cached for exactly `query`, marking it most recently used.
+    pub fn get(&mut self, query: &str) -> Option<Vec<f32>> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(query).map(|(used, v)| {
+            *used = tick;
+            v.clone()
+        })
+    }
+
+    /// Store `vector` for `query`, evicting the least recently used entry when full.
+    pub fn insert(&mut self, query: &str, vector: Vec<f32>) {
+        if self.capacity == 0 { return; }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(query) {
+            if let Some(oldest) = self.entries.iter().min_by_key(|(_, (used, _))| *used).map(|(k, _)| k.clone()) {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(query.to_string(), (self.tick, vector));
+    }
+
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    pub fn clear(&mut self) { self.entries.clear(); }
+}
+
+/// Lowercased word counts over `texts` for the `suggest` vocabulary. Words are maximal
+/// alphanumeric runs of 2..=40 chars, so unsegmented CJK text yields whole runs.
+pub fn suggest_term_counts<'a>(texts: impl IntoIterator<Item = &'a str>) -> Vec<(String, u64)> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for text in texts {
+        for word in text.split(|c: char| !c.is_alphanumeric()) {
+            let n = word.chars().count();
+            if (2..=40).contains(&n) {
+                *counts.entry(word.to_lowercase()).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut out: Vec<(String, u64)> = counts.into_iter().collect();
+    out.sort();
+    out
+}
+
+/// `Err(DimensionMismatch)` for the first vector whose length differs from `expected`.
+pub fn check_embedding_dimensions<'a>(expected: usize, vectors: impl IntoIterator<Item = &'a [f32]>) -> Result<(), ServiceError> {
+    match vectors.into_iter().find(|v| v.len() != expected) {
+        Some(v) => Err(ServiceError::DimensionMismatch { expected, actual: v.len() }),
+        None => Ok(()),
+    }
+}
+
+/// Documents loaded per page by `reindex_all`.
+const REINDEX_DOC_PAGE: usize = 64;
+
+/// Fixed text embedded to detect model changes between ingests.
+pub const EMBED_REFERENCE_TEXT: &str = "hybrid search embedding reference vector";
+/// `store_meta` key holding the JSON-encoded reference vector.
+pub const STORE_META_EMBED_REFERENCE: &str = "embed_reference_vector";
+/// `store_meta` key holding the HNSW vector storage dtype (`"f32"` / `"f16"`).
+pub const STORE_META_VECTOR_DTYPE: &str = "vector_dtype";
+
+/// Deviation between a stored reference vector and a fresh one: the larger of `1 - cosine`
+/// (direction) and the relative norm change (scale/normalization). 1.0 when dimensions
+/// differ or a vector is zero.
+pub fn embedding_deviation(reference: &[f32], current: &[f32]) -> f32 {
+    if reference.len() != current.len() { return 1.0; }
+    let dot: f32 = reference.iter().zip(current).map(|(x, y)| x * y).sum();
+    let nr = reference.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let nc = current.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if nr == 0.0 || nc == 0.0 { return 1.0; }
+    let angle = 1.0 - dot / (nr * nc);
+    let scale = (nc / nr - 1.0).abs();
+    angle.max(scale)
+}
+
+/// Tantivy schema/analysis options taken from the service config.
+#[cfg(feature = "tantivy")]
+fn tantivy_opts(cfg: &ServiceConfig) -> TantivyOpts {
+    TantivyOpts { tokenizer: cfg.tantivy_tokenizer, heading_boost: cfg.tantivy_heading_boost }
+}
+
+/// Load an HNSW snapshot, retrying transient IO failures with a linear backoff.
+fn load_hnsw_with_retry(dir: &Path, dim: usize, retries: u32, backoff_ms: u64) -> Result<HnswIndex, HnswError> {
+    let mut attempt = 0u32;
+    loop {
+        match HnswIndex::load(dir, dim) {
+            Ok(h) => {
+                if h.loaded_from_previous() { eprintln!("[hnsw] {} was unreadable; loaded the previous snapshot", dir.display()); }
+                return Ok(h);
+            }
+            // A dimension mismatch does not heal by waiting; callers record it in `HnswState`
+            Err(e @ HnswError::DimensionMismatch { .. }) => return Err(e),
+            Err(e) if attempt < retries && is_transient_load_error(&e) => {
+                attempt += 1;
+                let wait = backoff_ms.saturating_mul(attempt as u64);
+                eprintln!("[hnsw] load from {} failed (attempt {}/{}): {}; retrying in {} ms", dir.display(), attempt, retries + 1, e, wait);
+                std::thread::sleep(std::time::Duration::from_millis(wait));
+            }
+            Err(e) => {
+                eprintln!("[hnsw] load from {} failed after {} attempts: {}", dir.display(), attempt + 1, e);
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// IO failures a concurrent writer can cause (a file mid-rename, locked or half-written);
+/// anything else, such as a corrupt snapshot (`InvalidData`), fails on the first attempt.
+fn is_transient_load_error(e: &HnswError) -> bool {
+    use std::io::ErrorKind;
+    match e {
+        HnswError::Io(e) => matches!(
+            e.kind(),
+            ErrorKind::NotFound | ErrorKind::PermissionDenied | ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::UnexpectedEof
+        ),
+        HnswError::DimensionMismatch { .. } => false,
+    }
+}
+
+/// Resident-index state after a failed snapshot load.
+fn hnsw_error_state(e: &HnswError) -> HnswState {
+    match e {
+        HnswError::DimensionMismatch { expected, found } => HnswState::DimensionMismatch { expected: *expected, found: *found },
+        HnswError::Io(_) => HnswState::Error,
+    }
+}
+
+fn hnsw_load_error(e: HnswError) -> ServiceError {
+    match e {
+        HnswError::DimensionMismatch { expected, found } => ServiceError::IndexDimensionMismatch { expected, found },
+        HnswError::Io(e) => ServiceError::Io(e.to_string()),
+    }
+}
+
+/// Collect files under `root` in a deterministic (sorted by path) order.
+/// `exts` filters by extension without the dot (see `file_chunker::scan::ext_matches`);
+/// empty accepts all files.
+pub fn scan_folder_sorted(root: &Path, max_depth: usize, exts: &[&str]) -> Vec<PathBuf> {
+    file_chunker::scan::scan_dir(root, exts, max_depth).into_iter().map(|f| f.path).collect()
+}
+
+/// A file ingest cut off by a crash, as reported by `HybridService::recover`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterruptedIngest {
+    pub path: String,
+    pub doc_id: String,
+    pub content_sha256: Option<String>,
+    /// RFC 3339 time the ingest started.
+    pub started_at: String,
+}
+
+/// A `started` row in `ingest_journal`. `finish` marks it completed when
+/// `ServiceConfig::ingest_journal` is on and removes it otherwise; dropping it unfinished (an
+/// error or cancel) removes it. A crash does neither, which is what `HybridService::recover`
+/// looks for.
+struct IngestJournalEntry<'a> {
+    svc: &'a HybridService,
+    path: String,
+    content_sha256: Option<String>,
+    done: bool,
+}
+
+impl IngestJournalEntry<'_> {
+    fn finish(mut self) -> Result<(), ServiceError> {
+        self.done = true;
+        let sha = self.content_sha256.as_deref();
+        self.svc.with_repo(|repo| {
+            match sha {
+                Some(hex) if self.svc.cfg.ingest_journal => repo.record_ingest_completed(&self.path, hex, &Utc::now().to_rfc3339()),
+                _ => repo.remove_ingest_entry(&self.path, sha).map(|_| ()),
+            }
+            .map_err(|e| ServiceError::Repo(e.to_string()))
+        })
+    }
+}
+
+impl Drop for IngestJournalEntry<'_> {
+    fn drop(&mut self) {
+        // Best effort: a row left behind is reported by `recover` like a crash would be
+        if !self.done { let _ = self.svc.with_repo(|repo| repo.remove_ingest_entry(&self.path, self.content_sha256.as_deref()).map_err(|e| ServiceError::Repo(e.to_string()))); }
+    }
+}
+
+/// Registered files by content, as collected by `HybridService::known_files`. Sizes come
+/// first so most unregistered files are told apart without hashing them.
+#[derive(Debug, Default, Clone)]
+pub struct KnownFiles {
+    hashes: HashSet<String>,
+    sizes: HashSet<u64>,
+}
+
+impl KnownFiles {
+    /// SHA-256 (lowercase hex) of every registered file that recorded one.
+    pub fn hashes(&self) -> &HashSet<String> { &self.hashes }
+
+    /// Sizes of every registered file that recorded one.
+    pub fn sizes(&self) -> &HashSet<u64> { &self.sizes }
+
+    /// False when no registered file has this size, so the file cannot be registered.
+    pub fn size_may_match(&self, size: u64) -> bool { self.sizes.contains(&size) }
+
+    /// True when a registered file has this SHA-256 (lowercase hex).
+    pub fn contains_hash(&self, sha256_hex: &str) -> bool { self.hashes.contains(sha256_hex) }
+
+    /// True when the file at `path` has the content of a registered file. Unreadable files
+    /// count as unregistered.
+    pub fn is_registered(&self, path: &Path) -> bool {
+        let Ok(meta) = std::fs::metadata(path) else { return false };
+        let file = file_chunker::scan::ScannedFile { path: path.to_path_buf(), size: meta.len(), modified: None };
+        !file_chunker::scan::is_unregistered(&file, &self.hashes, &self.sizes)
+    }
+}
+
+fn orchestrator_error(e: OrchestratorError) -> ServiceError {
+    match e {
+        OrchestratorError::PartialIngest { applied, error } => ServiceError::PartialIngest { applied, error },
+        e => ServiceError::Index(e.to_string()),
+    }
+}
+
+/// Fill in the source file's size and content hash when the reader left them unset, so
+/// `KnownFiles` can recognize the file later. Best effort: unreadable files keep `None`.
+fn record_file_facts(path: &str, file: &mut FileRecord) {
+    if file.file_size_bytes.is_none() {
+        file.file_size_bytes = std::fs::metadata(path).ok().map(|m| m.len());
+    }
+    if file.content_sha256.is_none() {
+        file.content_sha256 = sha256_hex_file(Path::new(path)).ok();
+    }
+}
+
+/// Identity of the vectors an embedder config produces, as `<model id>@<hash>`. The hash covers
+/// the model and tokenizer files (path, size, mtime) and the settings that change the output:
+/// dimension, pooling, normalization, max input length and text representation version.
+pub fn embedder_fingerprint(cfg: &OnnxStdIoConfig) -> String {
+    let file_id = |p: &Path| {
+        let (len, mtime) = std::fs::metadata(p)
+            .map(|m| (m.len(), m.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()).map_or(0, |d| d.as_secs())))
+            .unwrap_or((0, 0));
+        format!("{}|{}|{}", p.display(), len, mtime)
+    };
+    let parts = format!(
+        "{}\n{}\n{}\n{}\n{:?}\n{}\n{}\n{}",
+        cfg.embedding_model_id,
+        file_id(&cfg.model_path),
+        file_id(&cfg.tokenizer_path),
+        cfg.dimension,
+        cfg.pooling,
+        cfg.normalize,
+        cfg.max_input_length,
+        cfg.text_repr_version,
+    );
+    format!("{}@{}", cfg.embedding_model_id, &sha256_hex(parts.as_bytes())[..16])
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    let digest = Sha256::digest(bytes);
+    let mut hex = String::with_capacity(64);
+    for b in digest { hex.push_str(&format!("{:02x}", b)); }
+    hex
+}
+
 fn derive_hnsw_dir(db_path: &Path) -> PathBuf {
     let mut s = db_path.as_os_str().to_string_lossy().to_string();
     s.push_str(".hnsw");
     PathBuf::from(s)
 }
 
-fn make_ids_from_text(doc_hint: Option<&str>, text: &str) -> (DocumentId, ChunkId) {
+fn make_ids_from_text(doc_hint: Option<&str>, text: &str, content_based: bool) -> (DocumentId, ChunkId) {
     if let Some(h) = doc_hint { if !h.trim().is_empty() { return (DocumentId(h.to_string()), ChunkId(format!("{}#0", h))); } }
+    if content_based {
+        let doc_id = format!("sha256-{}", sha256_hex(text.as_bytes()));
+        let chunk_id = format!("{}#0", doc_id);
+        return (DocumentId(doc_id), ChunkId(chunk_id));
+    }
     use std::hash::{Hash, Hasher};
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
     text.hash(&mut hasher);
@@ -1292,3 +3936,75 @@ fn make_ids_from_text(doc_hint: Option<&str>, text: &str) -> (DocumentId, ChunkI
     let chunk_id = format!("{}#0", doc_id);
     (DocumentId(doc_id), ChunkId(chunk_id))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titled_file(title: &str) -> FileRecord {
+        FileRecord {
+            schema_version: chunk_model::SCHEMA_MAJOR,
+            doc_id: DocumentId("doc-note".into()),
+            doc_revision: None,
+            source_uri: "file://note.txt".into(),
+            source_mime: "text/plain".into(),
+            file_size_bytes: None,
+            content_sha256: None,
+            page_count: None,
+            extracted_at: String::new(),
+            created_at_meta: None,
+            updated_at_meta: None,
+            title_guess: Some(title.into()),
+            author_guess: None,
+            dominant_lang: None,
+            tags: vec!["meeting".into()],
+            ingest_tool: None,
+            ingest_tool_version: None,
+            reader_backend: None,
+            ocr_used: None,
+            ocr_langs: Vec::new(),
+            chunk_count: Some(1),
+            total_tokens: None,
+            meta: Default::default(),
+            extra: Default::default(),
+        }
+    }
+
+    fn note_chunk(text: &str) -> ChunkRecord {
+        ChunkRecord {
+            schema_version: chunk_model::SCHEMA_MAJOR,
+            doc_id: DocumentId("doc-note".into()),
+            chunk_id: ChunkId("n1".into()),
+            seq: None,
+            source_uri: "file://note.txt".into(),
+            source_mime: "text/plain".into(),
+            extracted_at: String::new(),
+            page_start: None,
+            page_end: None,
+            text: text.into(),
+            section_path: Some(vec!["Notes".into()]),
+            meta: Default::default(),
+            block_kinds: Vec::new(),
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn embedding_input_includes_title_only_for_short_docs_when_enabled() {
+        let file = titled_file("Quarterly planning");
+        let short = vec![note_chunk("Ship it on Friday.")];
+        let long = vec![note_chunk(&"Long body text. ".repeat(40))];
+
+        let mut cfg = ServiceConfig::default();
+        assert_eq!(embedding_inputs(&cfg, &file, &short)[0], "Ship it on Friday.");
+
+        cfg.embed_include_title = true;
+        let input = embedding_inputs(&cfg, &file, &short);
+        assert!(input[0].starts_with("Quarterly planning\nmeeting\n"));
+        assert!(input[0].ends_with("Ship it on Friday."));
+        assert_eq!(short[0].text, "Ship it on Friday.");
+
+        let input = embedding_inputs(&cfg, &file, &long);
+        assert!(!input[0].contains("Quarterly planning"));
+    }
+}
@@ -0,0 +1,282 @@
+//! Prompt templating shared by the GUI, CLI and server.
+//!
+//! A template has a header and footer rendered once and an item rendered per hit. Placeholders
+//! take the forms `<<Name>>`, `<<Name:transform>>` and `<<Name:transform(arg)>>`:
+//! - header/footer: `Query`, `TopK`
+//! - item: `Rank`, `File`, `Page`, `CID`, `SourceUri`, `Comma`, `Text`
+//!
+//! `Query` and `Text` accept the transforms `escape_json`, `snippet(n)` and `snippet_json(n)`
+//! (`n` defaults to 200 characters). Unknown names expand to nothing.
+//!
+//! `build_prompt_json` is the structured alternative: the same hits as a `serde_json::Value`,
+//! so chunk text never has to be escaped by hand.
+
+use chunk_model::{ChunkId, ChunkRecord};
+use chunking_store::sqlite_repo::SqliteRepo;
+use chunking_store::SearchHit;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const DEFAULT_SNIPPET_CHARS: usize = 200;
+
+/// A named prompt layout; the serialized form is the one the GUI stores in its config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub header: String,
+    pub item: String,
+    pub footer: String,
+    /// Maximum number of hits rendered (at least one).
+    pub items: usize,
+    /// Neighbouring chunks prepended/appended to each hit's `Text`.
+    pub prev: usize,
+    pub next: usize,
+}
+
+/// Values a template is rendered against besides the hits.
+#[derive(Clone, Copy, Default)]
+pub struct PromptOptions<'a> {
+    pub query: &'a str,
+    pub top_k: usize,
+    /// Repo used to expand `Text` with `prev`/`next` neighbours; without it only the hit's own
+    /// text is used.
+    pub repo: Option<&'a SqliteRepo>,
+}
+
+/// Per-hit values behind the item placeholders.
+#[derive(Debug, Clone)]
+pub struct PromptItem {
+    pub chunk_id: String,
+    pub file: String,
+    pub page: String,
+    pub source_uri: String,
+    pub text: String,
+    /// Relevance shown in the structured output; `None` serializes as `null`.
+    pub score: Option<f32>,
+}
+
+impl From<&SearchHit> for PromptItem {
+    fn from(hit: &SearchHit) -> Self {
+        let rec = &hit.chunk;
+        let file = std::path::Path::new(&rec.source_uri)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| rec.source_uri.clone());
+        PromptItem {
+            chunk_id: rec.chunk_id.0.clone(),
+            file,
+            page: page_label(rec),
+            source_uri: rec.source_uri.clone(),
+            text: rec.text.clone(),
+            score: Some(hit.score),
+        }
+    }
+}
+
+/// Render `template` over `hits` (in order).
+pub fn render(template: &PromptTemplate, hits: &[SearchHit], opts: &PromptOptions<'_>) -> String {
+    let items: Vec<PromptItem> = hits.iter().map(PromptItem::from).collect();
+    render_items(template, &items, opts)
+}
+
+/// Render `template` over prepared items; `render` for callers that do not hold `SearchHit`s.
+pub fn render_items(template: &PromptTemplate, items: &[PromptItem], opts: &PromptOptions<'_>) -> String {
+    let header = expand_template(&template.header, |name, transform, arg| query_placeholder(name, transform, arg, opts));
+
+    let mut body = String::new();
+    let item_count = template.items.max(1).min(items.len());
+    for (i, row) in items.iter().take(item_count).enumerate() {
+        let item = expand_template(&template.item, |name, transform, arg| match name {
+            "Rank" => (i + 1).to_string(),
+            "File" => row.file.clone(),
+            "Page" => row.page.clone(),
+            "CID" => row.chunk_id.clone(),
+            "SourceUri" => row.source_uri.clone(),
+            "Comma" => if i + 1 < item_count { ",".to_string() } else { String::new() },
+            "Text" => {
+                let text = text_with_context(opts.repo, &row.chunk_id, &row.text, template.prev, template.next);
+                apply_transform(&text, transform, arg)
+            }
+            _ => String::new(),
+        });
+        body.push_str(&item);
+        if !item.ends_with('\n') { body.push('\n'); }
+    }
+
+    let footer = expand_template(&template.footer, |name, transform, arg| query_placeholder(name, transform, arg, opts));
+
+    let mut out = String::new();
+    out.push_str(&header);
+    if !header.ends_with('\n') { out.push('\n'); }
+    out.push_str(&body);
+    if !body.ends_with('\n') { out.push('\n'); }
+    out.push_str(&footer);
+    if !out.ends_with('\n') { out.push('\n'); }
+    out
+}
+
+/// Structured counterpart of `render`: `{"query", "top_k", "results": [{"rank", "file",
+/// "page", "text", "score"}]}`. Uses the template's `items`, `prev` and `next` but not its
+/// header/item/footer text.
+pub fn build_prompt_json(template: &PromptTemplate, hits: &[SearchHit], opts: &PromptOptions<'_>) -> Value {
+    let items: Vec<PromptItem> = hits.iter().map(PromptItem::from).collect();
+    build_prompt_json_items(template, &items, opts)
+}
+
+/// `build_prompt_json` over prepared items.
+pub fn build_prompt_json_items(template: &PromptTemplate, items: &[PromptItem], opts: &PromptOptions<'_>) -> Value {
+    let item_count = template.items.max(1).min(items.len());
+    let results: Vec<Value> = items
+        .iter()
+        .take(item_count)
+        .enumerate()
+        .map(|(i, row)| {
+            json!({
+                "rank": i + 1,
+                "file": row.file,
+                "page": row.page,
+                "text": text_with_context(opts.repo, &row.chunk_id, &row.text, template.prev, template.next),
+                "score": row.score,
+            })
+        })
+        .collect();
+    json!({ "query": opts.query, "top_k": opts.top_k, "results": results })
+}
+
+fn query_placeholder(name: &str, transform: Option<&str>, arg: Option<&str>, opts: &PromptOptions<'_>) -> String {
+    match name {
+        "Query" => apply_transform(opts.query, transform, arg),
+        "TopK" => opts.top_k.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn apply_transform(value: &str, transform: Option<&str>, arg: Option<&str>) -> String {
+    let n = || arg.and_then(|s| s.parse::<usize>().ok()).unwrap_or(DEFAULT_SNIPPET_CHARS);
+    match transform {
+        Some("escape_json") => escape_json_str(value),
+        Some("snippet") => snippet_chars(value, n()),
+        Some("snippet_json") => escape_json_str(&snippet_chars(value, n())),
+        _ => value.to_string(),
+    }
+}
+
+/// Expand placeholders in `tmpl`; `resolver` receives (name, transform, arg) and returns the
+/// replacement. An unterminated `<<` is copied through as-is.
+pub fn expand_template<F>(tmpl: &str, mut resolver: F) -> String
+where
+    F: FnMut(&str, Option<&str>, Option<&str>) -> String,
+{
+    let mut out = String::with_capacity(tmpl.len());
+    let mut rest = tmpl;
+    loop {
+        match rest.find("<<") {
+            Some(start) => {
+                out.push_str(&rest[..start]);
+                let after = &rest[start + 2..];
+                if let Some(end_rel) = after.find(">>") {
+                    let (name, transform, arg) = parse_token(&after[..end_rel]);
+                    out.push_str(&resolver(name, transform, arg));
+                    rest = &after[end_rel + 2..];
+                } else {
+                    out.push_str(rest);
+                    break;
+                }
+            }
+            None => {
+                out.push_str(rest);
+                break;
+            }
+        }
+    }
+    out
+}
+
+/// Split `Name`, `Name:transform` or `Name:transform(arg)` into its trimmed parts.
+fn parse_token(token: &str) -> (&str, Option<&str>, Option<&str>) {
+    let Some((name, rest)) = token.split_once(':') else { return (token.trim(), None, None) };
+    if let Some(lp) = rest.find('(') {
+        let (tf, tail) = rest.split_at(lp);
+        if tail.ends_with(')') && tail.len() >= 2 {
+            return (name.trim(), Some(tf.trim()), Some(tail[1..tail.len() - 1].trim()));
+        }
+        return (name.trim(), Some(tf.trim()), None);
+    }
+    (name.trim(), Some(rest.trim()), None)
+}
+
+/// Escape `s` for use inside a JSON string literal (quotes not included).
+pub fn escape_json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// First `n` characters of `s`, with an ellipsis when something was cut.
+pub fn snippet_chars(s: &str, n: usize) -> String {
+    if n == 0 { return String::new(); }
+    let mut it = s.chars();
+    let taken: String = it.by_ref().take(n).collect();
+    if it.next().is_some() { format!("{}\u{2026}", taken) } else { taken }
+}
+
+/// Page label for display: `#3`, `#3-5`, or the `#n`/`#n-m` suffix of the chunk id when the
+/// record has no page range; empty when neither is available.
+pub fn page_label(rec: &ChunkRecord) -> String {
+    match (rec.page_start, rec.page_end) {
+        (Some(s), Some(e)) if s == e => format!("#{}", s),
+        (Some(s), Some(e)) => format!("#{}-{}", s, e),
+        (Some(s), None) => format!("#{}", s),
+        _ => page_label_from_chunk_id(&rec.chunk_id.0).unwrap_or_default(),
+    }
+}
+
+fn page_label_from_chunk_id(cid: &str) -> Option<String> {
+    let tail = &cid[cid.rfind('#')? + 1..];
+    let mut it = tail.splitn(2, '-');
+    let a = it.next()?;
+    if a.is_empty() || !a.chars().all(|c| c.is_ascii_digit()) { return None; }
+    match it.next() {
+        Some(b) if b.chars().all(|c| c.is_ascii_digit()) => Some(format!("#{}-{}", a, b)),
+        _ => Some(format!("#{}", a)),
+    }
+}
+
+/// `base_text` joined with up to `prev` preceding and `next` following chunks of the same
+/// document, one per line. Neighbour lookup stops quietly at the first gap or error.
+pub fn text_with_context(repo: Option<&SqliteRepo>, base_cid: &str, base_text: &str, prev: usize, next: usize) -> String {
+    let Some(repo) = repo else { return base_text.to_string() };
+    if prev == 0 && next == 0 { return base_text.to_string(); }
+
+    let mut prev_parts: Vec<String> = Vec::new();
+    let mut cur = ChunkId(base_cid.to_string());
+    for _ in 0..prev {
+        match repo.get_neighbor_chunks(&cur) {
+            Ok((Some(p), _)) => { prev_parts.push(p.text); cur = p.chunk_id; }
+            _ => break,
+        }
+    }
+    prev_parts.reverse();
+
+    let mut parts = prev_parts;
+    parts.push(base_text.to_string());
+    let mut cur = ChunkId(base_cid.to_string());
+    for _ in 0..next {
+        match repo.get_neighbor_chunks(&cur) {
+            Ok((_, Some(n))) => { parts.push(n.text); cur = n.chunk_id; }
+            _ => break,
+        }
+    }
+    parts.join("\n")
+}
@@ -0,0 +1,78 @@
+use chunk_model::{ChunkId, ChunkRecord, DocumentId, SCHEMA_MAJOR};
+use chunking_store::SearchHit;
+use hybrid_service::prompt_builder::{build_prompt_json, render, PromptOptions, PromptTemplate};
+
+fn hit(id: &str, uri: &str, page: Option<u32>, text: &str) -> SearchHit {
+    let chunk = ChunkRecord {
+        schema_version: SCHEMA_MAJOR,
+        doc_id: DocumentId("doc".into()),
+        chunk_id: ChunkId(id.into()),
+        seq: None,
+        source_uri: uri.into(),
+        source_mime: "text/plain".into(),
+        extracted_at: String::new(),
+        page_start: page,
+        page_end: page,
+        text: text.into(),
+        section_path: None,
+        meta: Default::default(),
+        block_kinds: Vec::new(),
+        extra: Default::default(),
+    };
+    SearchHit { chunk, score: 1.0, fallback: false, components: None }
+}
+
+#[test]
+fn render_expands_saved_json_template_placeholders() {
+    let template = PromptTemplate {
+        name: "json".into(),
+        header: "{\"query\": \"<<Query:escape_json>>\", \"k\": <<TopK>>, \"results\": [".into(),
+        item: "{\"rank\": <<Rank>>, \"file\": \"<<File:escape_json>>\", \"page\": \"<<Page>>\", \"text\": \"<<Text:snippet_json(6)>>\"}<<Comma>>".into(),
+        footer: "]} <<Unknown>><<Query:snippet(3)>>".into(),
+        items: 2,
+        prev: 1,
+        next: 1,
+    };
+    let hits = [
+        hit("a#0", "/docs/a.txt", Some(3), "line\tone\nmore"),
+        hit("b#7-9", "b.md", None, "short"),
+        hit("c#0", "c.md", None, "not rendered"),
+    ];
+    let opts = PromptOptions { query: "say \"hi\"", top_k: 5, repo: None };
+    let out = render(&template, &hits, &opts);
+    assert_eq!(
+        out,
+        "{\"query\": \"say \\\"hi\\\"\", \"k\": 5, \"results\": [\n\
+         {\"rank\": 1, \"file\": \"a.txt\", \"page\": \"#3\", \"text\": \"line\\to\u{2026}\"},\n\
+         {\"rank\": 2, \"file\": \"b.md\", \"page\": \"#7-9\", \"text\": \"short\"}\n\
+         ]} say\u{2026}\n"
+    );
+}
+
+#[test]
+fn prompt_json_keeps_quotes_and_control_characters_intact() {
+    let template = PromptTemplate {
+        name: "strict".into(),
+        header: String::new(),
+        item: String::new(),
+        footer: String::new(),
+        items: 1,
+        prev: 0,
+        next: 0,
+    };
+    let text = "He said \"stop\" \\ then\u{0}left}\n]";
+    let hits = [hit("a#0", "a.txt", Some(2), text), hit("b#0", "b.txt", None, "dropped")];
+    let opts = PromptOptions { query: "what \"stop\"?", top_k: 3, repo: None };
+    let value = build_prompt_json(&template, &hits, &opts);
+
+    let parsed: serde_json::Value = serde_json::from_str(&value.to_string()).expect("valid JSON");
+    assert_eq!(parsed["query"], "what \"stop\"?");
+    assert_eq!(parsed["top_k"], 3);
+    let results = parsed["results"].as_array().expect("results array");
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["rank"], 1);
+    assert_eq!(results[0]["file"], "a.txt");
+    assert_eq!(results[0]["page"], "#2");
+    assert_eq!(results[0]["text"], text);
+    assert_eq!(results[0]["score"], 1.0);
+}
@@ -0,0 +1,1507 @@
+use std::path::Path;
+
+use chunking_store::{EmptyFallback, SearchOptions};
+use hybrid_service::{apply_quality_gate, embedding_deviation, throttle_progress, throttle_progress_with_clock, DriftAction, EmbedPolicy, ExtraConflictAction, HnswState, HybridService, HybridWeights, ImportLine, OrphanChunkAction, ProgressEvent, QualityGateAction, RepairOpts, ServiceConfig, ServiceError, EXTRA_EMBEDDING_F16_KEY, EXTRA_EMBEDDING_KEY, STORE_META_EMBED_REFERENCE, VectorDtype};
+
+fn service_at(dir: &Path, configure: impl FnOnce(&mut ServiceConfig)) -> HybridService {
+    let mut cfg = ServiceConfig::default();
+    cfg.db_path = dir.join("chunks.db");
+    cfg.aggressive_warmup = false;
+    configure(&mut cfg);
+    HybridService::new(cfg).expect("service initializes with the default model")
+}
+
+#[test]
+fn read_only_service_searches_but_rejects_writes() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    {
+        let svc = service_at(dir.path(), |_| {});
+        svc.ingest_text("Rust makes systems programming safer.", Some("doc-ro"))
+            .expect("seed ingest succeeds");
+    }
+
+    let svc = service_at(dir.path(), |cfg| cfg.read_only = true);
+    let hits = svc
+        .search_hybrid("systems programming", 5, &[], 0.0, 1.0)
+        .expect("search works on a read-only service");
+    assert!(hits.iter().any(|h| h.chunk.doc_id.0 == "doc-ro"));
+
+    let err = svc
+        .ingest_text("another snippet", None)
+        .expect_err("ingest is rejected");
+    assert!(matches!(err, ServiceError::ReadOnly));
+    assert!(matches!(svc.delete_by_filter(&[], 100), Err(ServiceError::ReadOnly)));
+}
+
+#[test]
+fn lang_restricted_search_returns_only_matching_chunks() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_text("検索エンジンは文書をベクトルに変換して類似度で並べ替えます。", Some("doc-ja"))
+        .expect("ingest japanese");
+    svc.ingest_text("The search engine converts documents into vectors and ranks them by similarity.", Some("doc-en"))
+        .expect("ingest english");
+
+    let opts = SearchOptions { top_k: 10, lang: Some("ja".into()), ..Default::default() };
+    let hits = svc
+        .search_hybrid_with_options("search engine vectors", &[], &opts, 0.5, 0.5)
+        .expect("search succeeds");
+    assert!(!hits.is_empty());
+    for h in &hits {
+        assert_eq!(h.chunk.meta.get(chunk_model::META_LANG).map(String::as_str), Some("ja"));
+    }
+}
+
+fn section_chunk(doc: &str, id: &str, section: &[&str], text: &str) -> chunk_model::ChunkRecord {
+    chunk_model::ChunkRecord {
+        schema_version: chunk_model::SCHEMA_MAJOR,
+        doc_id: chunk_model::DocumentId(doc.into()),
+        chunk_id: chunk_model::ChunkId(id.into()),
+        seq: None,
+        source_uri: format!("file://{doc}.md"),
+        source_mime: "text/markdown".into(),
+        extracted_at: String::new(),
+        page_start: None,
+        page_end: None,
+        text: text.into(),
+        section_path: Some(section.iter().map(|s| s.to_string()).collect()),
+        meta: Default::default(),
+        block_kinds: Vec::new(),
+        extra: Default::default(),
+    }
+}
+
+#[test]
+fn section_chunks_returns_only_the_containing_section() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let records = vec![
+        section_chunk("doc-sec", "c1", &["Intro"], "intro text"),
+        section_chunk("doc-sec", "c2", &["Setup"], "setup part one"),
+        section_chunk("doc-sec", "c3", &["Setup"], "setup part two"),
+        section_chunk("doc-sec", "c4", &["Setup", "Linux"], "linux notes"),
+        section_chunk("doc-sec", "c5", &["Setup"], "setup part three"),
+        section_chunk("doc-other", "o1", &["Setup"], "other document"),
+    ];
+    svc.ingest_chunks(&records, None).expect("ingest chunks");
+
+    let ids: Vec<String> = svc
+        .section_chunks("c3")
+        .expect("section lookup")
+        .into_iter()
+        .map(|c| c.chunk_id.0)
+        .collect();
+    assert_eq!(ids, vec!["c2", "c3", "c5"]);
+}
+
+#[test]
+fn hnsw_load_retries_transient_failure_until_ready() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    {
+        let svc = service_at(dir.path(), |_| {});
+        svc.ingest_text("Snapshots are written before being loaded.", Some("doc-hnsw"))
+            .expect("seed ingest succeeds");
+    }
+
+    // Hide the vector file so the first load attempts fail, then restore it shortly after.
+    let hdir = dir.path().join("chunks.db.hnsw");
+    let vectors = hdir.join("vectors.bin");
+    let hidden = hdir.join("vectors.bin.hidden");
+    std::fs::rename(&vectors, &hidden).expect("hide vectors");
+    let restore = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(150));
+        std::fs::rename(&hidden, &vectors).expect("restore vectors");
+    });
+
+    let svc = service_at(dir.path(), |cfg| {
+        cfg.hnsw_load_retries = 20;
+        cfg.hnsw_load_backoff_ms = 25;
+    });
+    restore.join().expect("restore thread");
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+    while svc.hnsw_state() != HnswState::Ready && std::time::Instant::now() < deadline {
+        assert_ne!(svc.hnsw_state(), HnswState::Error);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert_eq!(svc.hnsw_state(), HnswState::Ready);
+}
+
+#[test]
+fn hnsw_load_does_not_retry_a_corrupt_snapshot() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    {
+        let svc = service_at(dir.path(), |_| {});
+        svc.ingest_text("Corrupt snapshots fail fast.", Some("doc-corrupt"))
+            .expect("seed ingest succeeds");
+    }
+    std::fs::write(dir.path().join("chunks.db.hnsw").join("metric.txt"), "bogus").expect("corrupt metric");
+
+    // With this backoff a single retry would take longer than the deadline below
+    let svc = service_at(dir.path(), |cfg| {
+        cfg.hnsw_load_retries = 5;
+        cfg.hnsw_load_backoff_ms = 60_000;
+    });
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+    while svc.hnsw_state() != HnswState::Error && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert_eq!(svc.hnsw_state(), HnswState::Error);
+}
+
+#[test]
+fn folder_ingest_with_content_ids_is_reproducible() {
+    let corpus = tempfile::tempdir().expect("create corpus dir");
+    std::fs::create_dir_all(corpus.path().join("sub")).expect("create subdir");
+    std::fs::write(corpus.path().join("b.txt"), "Second file about vector indexes.").expect("write b");
+    std::fs::write(corpus.path().join("a.txt"), "First file about full text search.").expect("write a");
+    std::fs::write(corpus.path().join("sub").join("c.txt"), "Nested file about hybrid ranking.").expect("write c");
+
+    let ingest_once = || {
+        let store = tempfile::tempdir().expect("create store dir");
+        let svc = service_at(store.path(), |cfg| cfg.content_based_ids = true);
+        let n = svc.ingest_folder(corpus.path(), 1, &["txt"]).expect("folder ingest");
+        let ids = svc
+            .with_repo(|repo| repo
+                .list_chunk_ids_by_filter(&[], 1000, 0)
+                .map_err(|e| ServiceError::Repo(e.to_string())))
+            .expect("list chunk ids");
+        (n, svc.repo_counts().expect("counts").0, ids.into_iter().map(|c| c.0).collect::<Vec<_>>())
+    };
+
+    let first = ingest_once();
+    let second = ingest_once();
+    assert_eq!(first.0, 3);
+    assert_eq!(first, second);
+    assert!(first.2.iter().all(|id| id.starts_with("sha256-")));
+}
+
+#[test]
+fn journaled_folder_ingest_resumes_after_a_crash() {
+    let corpus = tempfile::tempdir().expect("create corpus dir");
+    for (name, text) in [("a.txt", "Apples ripen in autumn."), ("b.txt", "Bees pollinate flowers."), ("c.txt", "Cedars grow slowly."), ("d.txt", "Dunes shift with the wind.")] {
+        std::fs::write(corpus.path().join(name), text).expect("write corpus file");
+    }
+    let store = tempfile::tempdir().expect("create store dir");
+    let svc = service_at(store.path(), |cfg| { cfg.ingest_journal = true; cfg.content_based_ids = true; });
+
+    // Interrupt the run once c.txt has started embedding: a and b are journaled, c never is.
+    let cancel = hybrid_service::CancelToken::new();
+    let stop = cancel.clone();
+    let progress: Box<dyn FnMut(hybrid_service::FileProgress) + Send> = Box::new(move |p: hybrid_service::FileProgress| {
+        if p.path.ends_with("c.txt") && matches!(p.event, ProgressEvent::Start { .. }) { stop.cancel(); }
+    });
+    let interrupted = svc.ingest_folder_with_progress(corpus.path(), 0, &["txt"], Some(&cancel), Some(progress));
+    assert!(interrupted.is_err(), "the run should stop at c.txt");
+    assert_eq!(svc.repo_counts().expect("counts after interruption").0, 2);
+
+    assert_eq!(svc.ingest_folder(corpus.path(), 0, &["txt"]).expect("resumed run"), 2);
+    assert_eq!(svc.ingest_folder(corpus.path(), 0, &["txt"]).expect("completed run"), 0);
+    assert_eq!(svc.repo_counts().expect("counts").0, 4);
+}
+
+#[test]
+fn content_based_ids_match_the_chunker_content_hash_scheme() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let path = dir.path().join("notes.txt");
+    std::fs::write(&path, "Quarterly budget review.\n\nHiring plan for the next year.\n").expect("write input");
+    let path = path.to_string_lossy().into_owned();
+    let svc = service_at(dir.path(), |cfg| cfg.content_based_ids = true);
+    svc.ingest_file(&path, None).expect("ingest");
+
+    let opts = file_chunker::ChunkOptions { id_scheme: file_chunker::IdScheme::ContentHash, ..Default::default() };
+    let chunked = file_chunker::chunk_file_with_file_record_with_options(&path, &opts).expect("chunk file");
+    let stored = svc.with_repo(|repo| repo.get_chunks_by_doc_id(&chunked.file.doc_id.0, 100, 0).map_err(|e| ServiceError::Repo(e.to_string()))).expect("stored chunks");
+    let ids = |chunks: &[chunk_model::ChunkRecord]| chunks.iter().map(|c| c.chunk_id.0.clone()).collect::<Vec<_>>();
+    assert!(!stored.is_empty());
+    assert_eq!(ids(&stored), ids(&chunked.chunks));
+}
+
+#[test]
+fn update_chunk_text_refreshes_text_and_vector_search() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let (_, chunk_id) = svc
+        .ingest_text("Bananas are a yellow tropical fruit rich in potassium.", Some("doc-edit"))
+        .expect("seed ingest");
+    svc.ingest_text("Cooking pasta requires boiling salted water.", Some("doc-other"))
+        .expect("second ingest");
+
+    svc.update_chunk_text(&chunk_id.0, "Kubernetes schedules containers across a cluster of nodes.")
+        .expect("update text");
+
+    let fts_count = |q: &str| svc
+        .with_repo(|repo| repo.fts_match_count(q).map_err(|e| ServiceError::Repo(e.to_string())))
+        .expect("fts count");
+    assert_eq!(fts_count("Kubernetes"), 1);
+    assert_eq!(fts_count("Bananas"), 0);
+
+    let vec_hits = svc
+        .search_hybrid("container orchestration cluster", 1, &[], 0.0, 1.0)
+        .expect("vector search");
+    assert_eq!(vec_hits[0].chunk.chunk_id, chunk_id);
+    assert!(vec_hits[0].chunk.text.starts_with("Kubernetes"));
+}
+
+#[test]
+fn progress_throttle_bounds_event_rate_and_keeps_terminal_event() {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    let seen: Arc<Mutex<Vec<ProgressEvent>>> = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    // Each event advances the clock by 1ms, so only every 20th batch is due
+    let base = Instant::now();
+    let mut ticks = 0u64;
+    let clock = move || { ticks += 1; base + Duration::from_millis(ticks) };
+    let mut cb = throttle_progress_with_clock(Box::new(move |ev| sink.lock().unwrap().push(ev)), Duration::from_millis(20), clock);
+
+    let total = 10_000;
+    cb(ProgressEvent::Start { total_chunks: total });
+    for done in 1..=total {
+        cb(ProgressEvent::EmbedBatch { done, total, batch: 1 });
+    }
+    cb(ProgressEvent::Finished { total });
+
+    let seen = seen.lock().unwrap();
+    // Start, one batch per 20 ticks, Finished
+    assert_eq!(seen.len(), 1 + total / 20 + 1);
+    assert!(matches!(seen[1], ProgressEvent::EmbedBatch { done: 20, .. }));
+    assert!(matches!(seen.first(), Some(ProgressEvent::Start { .. })));
+    assert!(matches!(seen.last(), Some(ProgressEvent::Finished { .. })));
+}
+
+#[test]
+fn progress_throttle_keeps_vector_indexing_phase_events() {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    let seen: Arc<Mutex<Vec<ProgressEvent>>> = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    let mut cb = throttle_progress(Box::new(move |ev| sink.lock().unwrap().push(ev)), Duration::from_secs(60));
+
+    cb(ProgressEvent::EmbedBatch { done: 4, total: 4, batch: 4 });
+    cb(ProgressEvent::UpsertDb { total: 4 });
+    cb(ProgressEvent::IndexVector { total: 4 });
+    cb(ProgressEvent::SaveIndexes);
+    cb(ProgressEvent::Finished { total: 4 });
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 4, "{:?}", *seen);
+    assert!(matches!(seen[1], ProgressEvent::IndexVector { total: 4 }));
+    assert!(matches!(seen[2], ProgressEvent::SaveIndexes));
+}
+
+#[test]
+fn quality_gate_drops_or_tags_repetitive_chunks() {
+    let chunks = vec![
+        section_chunk("doc-q", "q0", &["Body"], "Hybrid search blends lexical and semantic signals for ranking."),
+        section_chunk("doc-q", "q1", &["Body"], &"-".repeat(80)),
+        section_chunk("doc-q", "q2", &["Body"], "page page page page page page page page"),
+    ];
+    let mut cfg = ServiceConfig { quality_min_unique_ratio: 0.3, quality_gate: QualityGateAction::Drop, ..Default::default() };
+    let mut dropped = chunks.clone();
+    apply_quality_gate(&cfg, &mut dropped);
+    assert_eq!(dropped.iter().map(|c| c.chunk_id.0.as_str()).collect::<Vec<_>>(), vec!["q0"]);
+
+    cfg.quality_gate = QualityGateAction::Tag;
+    let mut tagged = chunks.clone();
+    apply_quality_gate(&cfg, &mut tagged);
+    assert_eq!(tagged.len(), 3);
+    let flags: Vec<bool> = tagged.iter().map(|c| c.meta.contains_key(chunk_model::META_LOW_QUALITY)).collect();
+    assert_eq!(flags, vec![false, true, true]);
+}
+
+#[test]
+fn collection_scoped_search_returns_only_that_collection() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |cfg| cfg.collection_meta_key = Some("collection".into()));
+    let tagged = |id: &str, collection: &str, text: &str| {
+        let mut rec = section_chunk(&format!("doc-{id}"), id, &["Body"], text);
+        rec.meta.insert("collection".into(), collection.into());
+        rec
+    };
+    let records = vec![
+        tagged("l1", "legal", "The contract terminates after thirty days notice."),
+        tagged("l2", "legal", "Liability under this agreement is limited."),
+        tagged("h1", "hr", "Employees may terminate their contract with thirty days notice."),
+        tagged("h2", "hr", "Vacation requests need manager approval."),
+    ];
+    svc.ingest_chunks(&records, None).expect("ingest chunks");
+    // Re-embed each chunk so the vector signal has entries for both collections.
+    for rec in &records {
+        svc.update_chunk_text(&rec.chunk_id.0, &rec.text).expect("embed chunk");
+    }
+
+    let opts = SearchOptions { top_k: 10, ..Default::default() };
+    let hits = svc
+        .search_in_collection("legal", "contract termination notice", &[], &opts, 0.5, 0.5)
+        .expect("collection search");
+    assert!(!hits.is_empty());
+    for h in &hits {
+        assert_eq!(h.chunk.meta.get("collection").map(String::as_str), Some("legal"));
+    }
+
+    let unscoped = service_at(dir.path(), |_| {});
+    assert!(matches!(
+        unscoped.search_in_collection("legal", "contract", &[], &opts, 0.5, 0.5),
+        Err(ServiceError::Index(_))
+    ));
+}
+
+#[test]
+fn embedding_drift_check_fires_when_model_output_changes() {
+    let same = [0.6f32, 0.8];
+    assert!(embedding_deviation(&same, &same) < 1e-6);
+    assert!(embedding_deviation(&same, &[1.2, 1.6]) > 0.5);
+    assert!(embedding_deviation(&same, &[0.8, -0.6]) > 0.5);
+
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |cfg| cfg.embed_drift_action = DriftAction::Error);
+    svc.ingest_text("First snippet records the reference vector.", Some("doc-a"))
+        .expect("first ingest");
+    let stored: Vec<f32> = svc
+        .with_repo(|repo| repo.get_store_meta(STORE_META_EMBED_REFERENCE).map_err(|e| ServiceError::Repo(e.to_string())))
+        .expect("read store meta")
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .expect("reference vector stored");
+
+    // Simulate a swapped model: the stored reference now disagrees with the live embedder.
+    let perturbed: Vec<f32> = stored.iter().enumerate().map(|(i, v)| if i % 2 == 0 { -v } else { v * 3.0 }).collect();
+    svc.with_repo(|repo| {
+        repo.set_store_meta(STORE_META_EMBED_REFERENCE, &serde_json::to_string(&perturbed).unwrap())
+            .map_err(|e| ServiceError::Repo(e.to_string()))
+    })
+    .expect("write perturbed reference");
+
+    let fresh = service_at(dir.path(), |cfg| cfg.embed_drift_action = DriftAction::Error);
+    let err = fresh
+        .ingest_text("Second snippet after the model swap.", Some("doc-b"))
+        .expect_err("drift check rejects ingest");
+    assert!(matches!(err, ServiceError::EmbedDrift(_)));
+}
+
+#[test]
+fn empty_result_uses_recent_fallback_when_requested() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let dated = |id: &str, at: &str| {
+        let mut rec = section_chunk("doc-news", id, &["News"], &format!("bulletin {id}"));
+        rec.extracted_at = at.into();
+        rec
+    };
+    // No vectors and no text index features: both signals come back empty.
+    let records = vec![
+        dated("n1", "2024-01-01T00:00:00Z"),
+        dated("n2", "2024-03-01T00:00:00Z"),
+        dated("n3", "2024-02-01T00:00:00Z"),
+    ];
+    svc.ingest_chunks(&records, None).expect("ingest chunks");
+
+    let query = "quantum chromodynamics lattice gauge";
+    let plain = SearchOptions { top_k: 2, ..Default::default() };
+    assert!(svc.search_hybrid_with_options(query, &[], &plain, 0.5, 0.5).expect("search").is_empty());
+
+    let opts = SearchOptions { top_k: 2, empty_fallback: EmptyFallback::Recent, ..Default::default() };
+    let hits = svc.search_hybrid_with_options(query, &[], &opts, 0.5, 0.5).expect("search with fallback");
+    let ids: Vec<&str> = hits.iter().map(|h| h.chunk.chunk_id.0.as_str()).collect();
+    assert_eq!(ids, vec!["n2", "n3"]);
+    assert!(hits.iter().all(|h| h.fallback && h.score == 0.0));
+}
+
+#[test]
+fn delete_tombstones_vectors_without_rewriting_snapshot_until_compaction() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let hdir = dir.path().join("chunks.db.hnsw");
+    let svc = service_at(dir.path(), |cfg| cfg.hnsw_compact_tombstone_ratio = 0.3);
+    svc.ingest_text("Volcanoes erupt molten lava and ash.", Some("doc-volcano")).expect("ingest");
+    svc.ingest_text("Glaciers carve valleys over millennia.", Some("doc-glacier")).expect("ingest");
+    svc.ingest_text("Deserts receive very little rainfall.", Some("doc-desert")).expect("ingest");
+    let snapshot_before = std::fs::read(hdir.join("vectors.bin")).expect("read snapshot");
+
+    let filter = chunking_store::FilterClause {
+        kind: chunking_store::FilterKind::Must,
+        op: chunking_store::FilterOp::DocIdEq("doc-volcano".into()),
+    };
+    svc.delete_by_filter(&[filter], 100).expect("delete");
+    assert_eq!(std::fs::read(hdir.join("vectors.bin")).expect("read snapshot"), snapshot_before);
+    assert!(hdir.join("tombstones.tsv").exists());
+
+    let query = "lava from an erupting volcano";
+    let not_deleted = |svc: &HybridService| {
+        let hits = svc.search_hybrid(query, 3, &[], 0.0, 1.0).expect("vector search");
+        assert!(!hits.is_empty());
+        assert!(hits.iter().all(|h| h.chunk.doc_id.0 != "doc-volcano"));
+    };
+    not_deleted(&svc);
+    // Reloading the snapshot applies the persisted tombstones.
+    let reloaded = service_at(dir.path(), |cfg| cfg.hnsw_compact_tombstone_ratio = 0.3);
+    not_deleted(&reloaded);
+    let ratio = reloaded.with_hnsw(|h, _| h.tombstone_ratio()).expect("hnsw access").expect("hnsw loaded");
+    assert!((ratio - 1.0 / 3.0).abs() < 1e-6);
+
+    assert!(svc.compact_hnsw_if_needed().expect("compact"));
+    assert!(!hdir.join("tombstones.tsv").exists());
+    assert!(std::fs::read(hdir.join("vectors.bin")).expect("read snapshot").len() < snapshot_before.len());
+    assert_eq!(svc.with_hnsw(|h, _| h.tombstone_ratio()).expect("hnsw access"), Some(0.0));
+    assert!(!svc.compact_hnsw_if_needed().expect("nothing left to compact"));
+    not_deleted(&service_at(dir.path(), |_| {}));
+}
+
+#[test]
+fn ingest_records_indexes_text_and_vectors() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let records = vec![
+        section_chunk("doc-rec", "r1", &["Ocean"], "Coral reefs shelter many marine species."),
+        section_chunk("doc-rec", "r2", &["Space"], "Jupiter is the largest planet in the solar system."),
+    ];
+    svc.ingest_records(&records, EmbedPolicy::EmbedAll).expect("ingest records");
+
+    let fts_count = |q: &str| svc
+        .with_repo(|repo| repo.fts_match_count(q).map_err(|e| ServiceError::Repo(e.to_string())))
+        .expect("fts count");
+    assert_eq!(fts_count("Jupiter"), 1);
+    let hits = svc.search_hybrid("largest planet", 1, &[], 0.0, 1.0).expect("vector search");
+    assert_eq!(hits[0].chunk.chunk_id.0, "r2");
+
+    // Already-embedded records are skipped; provided vectors must belong to the batch.
+    svc.ingest_records(&records, EmbedPolicy::EmbedMissing).expect("nothing to embed");
+    let stray = vec![(chunk_model::ChunkId("unknown".into()), vec![0.0; 4])];
+    assert!(matches!(
+        svc.ingest_records(&records, EmbedPolicy::UseProvided(stray)),
+        Err(ServiceError::Embed(_))
+    ));
+}
+
+#[test]
+fn export_ndjson_writes_files_then_chunks_with_schema_version() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_text("Lighthouses guide ships along rocky coasts.", Some("doc-exp")).expect("ingest text");
+    let mut rec = section_chunk("doc-rec", "e1", &["Harbor"], "Tugboats push cargo ships into port.");
+    rec.meta.insert("lang".into(), "en".into());
+    rec.extra.insert("layout.span".into(), serde_json::json!([3, 9]));
+    svc.ingest_records(&[rec], EmbedPolicy::UseProvided(Vec::new())).expect("ingest records");
+
+    let mut out = Vec::new();
+    let (files, chunks) = svc.export_ndjson(&mut out).expect("export");
+    let lines: Vec<serde_json::Value> = String::from_utf8(out)
+        .expect("utf8")
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("each line is json"))
+        .collect();
+    assert_eq!(lines.len(), files + chunks);
+    assert!(files >= 1 && chunks >= 2);
+    assert!(lines.iter().all(|l| l["schema_version"] == chunk_model::SCHEMA_MAJOR));
+    assert!(lines[..files].iter().all(|l| l["kind"] == "file"));
+
+    let exported = lines
+        .iter()
+        .find(|l| l["kind"] == "chunk" && l["record"]["chunk_id"] == "e1")
+        .expect("record chunk exported");
+    let back: chunk_model::ChunkRecord = serde_json::from_value(exported["record"].clone()).expect("decode chunk");
+    assert_eq!(back.meta.get("lang").map(String::as_str), Some("en"));
+    assert_eq!(back.extra.get("layout.span"), Some(&serde_json::json!([3, 9])));
+}
+
+#[test]
+fn import_line_quarantines_extra_keys_colliding_with_historical_fields() {
+    let mut rec = serde_json::to_value(section_chunk("doc-imp", "i1", &["Intro"], "imported text")).expect("encode");
+    // A raw SQLite dump names the meta column `meta_json`; flatten puts it in `extra`.
+    rec["meta_json"] = serde_json::json!("{\"lang\":\"en\"}");
+    rec["layout.page"] = serde_json::json!(2);
+    let line = serde_json::json!({ "kind": "chunk", "schema_version": chunk_model::SCHEMA_MAJOR, "record": rec }).to_string();
+
+    let mut reported: ImportLine = serde_json::from_str(&line).expect("decode line");
+    assert_eq!(reported.resolve_extra_conflicts(ExtraConflictAction::Report), vec!["meta_json".to_string()]);
+    let ImportLine::Chunk { record, .. } = &reported else { panic!("expected a chunk line") };
+    assert!(record.extra.contains_key("meta_json"));
+
+    let mut quarantined: ImportLine = serde_json::from_str(&line).expect("decode line");
+    assert_eq!(quarantined.resolve_extra_conflicts(ExtraConflictAction::Quarantine), vec!["meta_json".to_string()]);
+    let ImportLine::Chunk { record, .. } = &quarantined else { panic!("expected a chunk line") };
+    assert!(!record.extra.contains_key("meta_json"));
+    assert_eq!(record.extra[chunk_model::EXTRA_CONFLICT_KEY]["meta_json"], "{\"lang\":\"en\"}");
+    assert_eq!(record.extra.get("layout.page"), Some(&serde_json::json!(2)));
+}
+
+#[test]
+fn import_ndjson_restores_an_export_and_reports_bad_lines() {
+    let src_dir = tempfile::tempdir().expect("create temp dir");
+    let src = service_at(src_dir.path(), |_| {});
+    src.ingest_text("Glaciers carve valleys over thousands of years.", Some("doc-ice")).expect("ingest text");
+    let mut dump = Vec::new();
+    let (files, chunks) = src.export_ndjson(&mut dump).expect("export");
+    dump.extend_from_slice(b"not json\n");
+    let foreign = serde_json::json!({ "kind": "chunk", "schema_version": 99, "record": {} });
+    dump.extend_from_slice(format!("{foreign}\n").as_bytes());
+
+    let dst_dir = tempfile::tempdir().expect("create temp dir");
+    let dst = service_at(dst_dir.path(), |_| {});
+    let report = dst.import_ndjson(dump.as_slice(), false).expect("import");
+    assert_eq!((report.files, report.chunks, report.reused_vectors), (files, chunks, 0));
+    let skipped: Vec<usize> = report.skipped.iter().map(|(line, _)| *line).collect();
+    assert_eq!(skipped, vec![files + chunks + 1, files + chunks + 2]);
+    assert!(report.skipped[0].1.starts_with("invalid line"), "{:?}", report.skipped[0]);
+    assert!(report.skipped[1].1.starts_with("unsupported schema_version 99"), "{:?}", report.skipped[1]);
+
+    let hits = dst.search_hybrid("glaciers valleys", 1, &[], 0.0, 1.0).expect("vector search");
+    assert_eq!(hits[0].chunk.doc_id.0, "doc-ice");
+}
+
+#[test]
+fn persisted_vectors_make_the_export_self_contained() {
+    let src_dir = tempfile::tempdir().expect("create temp dir");
+    let src = service_at(src_dir.path(), |cfg| cfg.persist_vectors_in_records = true);
+    src.ingest_text("Volcanic soil is rich in minerals.", Some("doc-vol")).expect("ingest text");
+    let mut dump = Vec::new();
+    let (_, chunks) = src.export_ndjson(&mut dump).expect("export");
+    let text = String::from_utf8(dump.clone()).expect("utf8");
+    let chunk_line: serde_json::Value = text
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).expect("json"))
+        .find(|l| l["kind"] == "chunk")
+        .expect("chunk line");
+    assert!(chunk_line["record"][EXTRA_EMBEDDING_KEY].as_array().is_some_and(|v| !v.is_empty()));
+
+    let dst_dir = tempfile::tempdir().expect("create temp dir");
+    let dst = service_at(dst_dir.path(), |_| {});
+    let report = dst.import_ndjson(dump.as_slice(), false).expect("import");
+    assert_eq!(report.reused_vectors, chunks);
+}
+
+#[test]
+fn vector_search_ranks_by_cosine_and_is_empty_without_an_index() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    assert!(svc.search_vector("anything", 5, &[]).expect("search without index").is_empty());
+
+    svc.ingest_text("Penguins huddle together to survive the Antarctic winter.", Some("doc-peng")).expect("ingest penguins");
+    svc.ingest_text("Compilers translate source code into machine instructions.", Some("doc-comp")).expect("ingest compilers");
+    let hits = svc.search_vector("birds in the cold south", 2, &[]).expect("vector search");
+    assert_eq!(hits[0].chunk.doc_id.0, "doc-peng");
+    assert!(hits.windows(2).all(|w| w[0].score >= w[1].score));
+    assert!(hits.iter().all(|h| h.score <= 1.0 && !h.fallback));
+}
+
+#[test]
+fn embed_cache_is_hit_on_reingest_and_missed_after_an_embedder_change() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let cache = dir.path().join("embed-cache.db");
+    let cached_rows = || -> i64 {
+        let raw = rusqlite::Connection::open(&cache).expect("open cache");
+        raw.query_row("SELECT COUNT(*) FROM embed_cache", [], |r| r.get(0)).expect("count")
+    };
+    let notes = dir.path().join("notes.txt");
+    std::fs::write(&notes, "Tide tables list high water times.").unwrap();
+    let notes = notes.to_string_lossy().into_owned();
+    let store_a = dir.path().join("a");
+    std::fs::create_dir_all(&store_a).unwrap();
+    let svc = service_at(&store_a, |cfg| cfg.embed_cache = Some(cache.clone()));
+    svc.ingest_file(&notes, Some("doc-a")).expect("ingest");
+    let first = cached_rows();
+    assert!(first > 0);
+    svc.ingest_file(&notes, Some("doc-a")).expect("re-ingest");
+    assert_eq!(cached_rows(), first, "unchanged text is served from the cache");
+    drop(svc);
+
+    // Same model id, different settings: the fingerprint changes, so nothing is reused
+    let store_b = dir.path().join("b");
+    std::fs::create_dir_all(&store_b).unwrap();
+    let svc = service_at(&store_b, |cfg| {
+        cfg.embed_cache = Some(cache.clone());
+        cfg.embedder.text_repr_version = format!("{}-changed", cfg.embedder.text_repr_version);
+    });
+    svc.ingest_file(&notes, Some("doc-a")).expect("ingest with another embedder");
+    assert_eq!(cached_rows(), first * 2);
+}
+
+#[test]
+fn query_embed_cache_evicts_least_recently_used() {
+    let mut cache = hybrid_service::QueryEmbedCache::new(2);
+    cache.insert("a", vec![1.0]);
+    cache.insert("b", vec![2.0]);
+    assert_eq!(cache.get("a"), Some(vec![1.0]));
+    cache.insert("c", vec![3.0]);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get("b"), None);
+    assert_eq!(cache.get("a"), Some(vec![1.0]));
+    assert_eq!(cache.get("c"), Some(vec![3.0]));
+
+    let mut off = hybrid_service::QueryEmbedCache::new(0);
+    off.insert("a", vec![1.0]);
+    assert!(off.is_empty());
+}
+
+#[test]
+fn rapid_store_switches_keep_loads_within_the_bound() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let stores: Vec<_> = (0..4).map(|i| dir.path().join(format!("store{i}"))).collect();
+    for (i, store) in stores.iter().enumerate() {
+        std::fs::create_dir_all(store).expect("create store dir");
+        let svc = service_at(store, |_| {});
+        svc.ingest_text(&format!("store number {i} talks about lighthouses"), Some(&format!("doc-{i}")))
+            .expect("seed ingest succeeds");
+    }
+
+    let svc = service_at(&stores[0], |cfg| cfg.max_background_loaders = 1);
+    for _ in 0..3 {
+        for store in &stores {
+            svc.set_store_paths(store.join("chunks.db"), None);
+        }
+    }
+    svc.wait_for_background_loads();
+
+    assert!(svc.peak_background_loads() <= 1);
+    assert_eq!(svc.hnsw_state(), HnswState::Ready);
+    let hits = svc.search_vector("lighthouses", 5, &[]).expect("search succeeds");
+    assert!(!hits.is_empty());
+    assert!(hits.iter().all(|h| h.chunk.doc_id.0 == "doc-3"));
+}
+
+#[test]
+fn loader_pool_drops_queued_jobs_of_older_epochs() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    let pool = hybrid_service::LoaderPool::new(2);
+    let active = Arc::new(AtomicUsize::new(0));
+    let max_seen = Arc::new(AtomicUsize::new(0));
+    let last = Arc::new(Mutex::new(Vec::new()));
+    for epoch in 1..=20u64 {
+        for _ in 0..2 {
+            let (active, max_seen, last) = (Arc::clone(&active), Arc::clone(&max_seen), Arc::clone(&last));
+            pool.submit(epoch, move || {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                max_seen.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                last.lock().unwrap().push(epoch);
+                active.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    }
+    pool.submit(3, || panic!("stale epoch must not run"));
+    pool.wait_idle();
+
+    assert!(max_seen.load(Ordering::SeqCst) <= 2);
+    assert!(pool.peak() <= 2);
+    let ran = last.lock().unwrap();
+    assert!(ran.len() < 40, "older queued loads were preempted");
+    assert_eq!(ran.iter().filter(|e| **e == 20).count(), 2);
+}
+
+#[test]
+fn loader_pool_survives_a_panicking_job() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let pool = hybrid_service::LoaderPool::new(1);
+    let ran_after = Arc::new(AtomicBool::new(false));
+    pool.submit(1, || panic!("load failed"));
+    let flag = Arc::clone(&ran_after);
+    pool.submit(1, move || flag.store(true, Ordering::SeqCst));
+    pool.wait_idle();
+
+    assert_eq!(pool.running(), 0);
+    assert!(ran_after.load(Ordering::SeqCst), "the job queued behind the panic still runs");
+}
+
+#[test]
+fn multi_query_search_matches_individual_searches() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_text("Lighthouses guide ships along rocky coasts at night.", Some("doc-light"))
+        .expect("ingest lighthouse doc");
+    svc.ingest_text("Sourdough bread needs a lively starter and a long proof.", Some("doc-bread"))
+        .expect("ingest bread doc");
+
+    let queries = ["ships near the coast", "baking bread"];
+    let multi = svc.search_hybrid_multi(&queries, 2, &[], 0.5, 0.5).expect("multi search succeeds");
+    assert_eq!(multi.len(), queries.len());
+    for (q, hits) in queries.iter().zip(&multi) {
+        let single = svc.search_hybrid(q, 2, &[], 0.5, 0.5).expect("single search succeeds");
+        let ids = |hs: &[chunking_store::SearchHit]| hs.iter().map(|h| h.chunk.chunk_id.0.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(hits), ids(&single));
+    }
+    assert_eq!(multi[0][0].chunk.doc_id.0, "doc-light");
+    assert_eq!(multi[1][0].chunk.doc_id.0, "doc-bread");
+}
+
+#[test]
+fn dimension_mismatch_error_names_both_dimensions() {
+    let good = vec![0.0f32; 768];
+    let bad = vec![0.0f32; 384];
+    assert!(hybrid_service::check_embedding_dimensions(768, [good.as_slice()]).is_ok());
+    let err = hybrid_service::check_embedding_dimensions(768, [good.as_slice(), bad.as_slice()])
+        .expect_err("mismatch is reported");
+    assert!(matches!(err, ServiceError::DimensionMismatch { expected: 768, actual: 384 }));
+    let msg = err.to_string();
+    assert!(msg.contains("768") && msg.contains("384"), "{msg}");
+}
+
+#[test]
+fn suggest_ranks_terms_and_history_by_frequency() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |cfg| { cfg.suggest_index_terms = true; cfg.suggest_record_queries = true; });
+    svc.ingest_text("Hybrid retrieval mixes lexical and vector retrieval; hybrid scores are fused.", Some("doc-1"))
+        .expect("ingest");
+    for _ in 0..3 {
+        svc.record_query("Hybrid search tuning").expect("record query");
+    }
+
+    assert_eq!(svc.suggest("HY", 10), vec!["hybrid search tuning".to_string(), "hybrid".to_string()]);
+    assert_eq!(svc.suggest("retr", 10), vec!["retrieval".to_string()]);
+    assert!(svc.suggest("zzz", 10).is_empty());
+}
+
+/// Renders every requested page as `png-<page>`; with `cancel`, cancels it once the first
+/// page is handed over, like a user canceling mid-render.
+struct StubPageRenderer {
+    cancel: Option<hybrid_service::CancelToken>,
+}
+
+impl hybrid_service::PageRenderer for StubPageRenderer {
+    fn render_pages(&self, _path: &str, pages: &[u32], on_page: &mut dyn FnMut(u32, Vec<u8>) -> bool) -> Result<(), String> {
+        for &p in pages {
+            let more = on_page(p, format!("png-{p}").into_bytes());
+            if let Some(ct) = &self.cancel { ct.cancel(); }
+            if !more { break; }
+        }
+        Ok(())
+    }
+}
+
+fn sample_pdf() -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../testdata/public/PDF変換検証サンプル（Wordから保存したもの）.pdf")
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn pdf_chunks_reference_their_stored_page_images() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |cfg| cfg.pdf_page_images = true);
+    svc.set_page_renderer(std::sync::Arc::new(StubPageRenderer { cancel: None }));
+    svc.ingest_file(&sample_pdf(), Some("doc-pdf")).expect("ingest succeeds");
+
+    let stored = svc.get_document_chunks("doc-pdf", 1000, 0).expect("chunks load");
+    assert!(!stored.is_empty());
+    let mut pages = std::collections::BTreeSet::new();
+    for rec in &stored {
+        let page = rec.page_start.expect("pdf chunks carry a page");
+        pages.insert(page);
+        let key = rec.meta.get(chunk_model::META_PAGE_IMAGE).expect("chunk references a page image");
+        assert_eq!(key, &hybrid_service::page_image_blob_key(page));
+        let blob = svc.get_doc_blob("doc-pdf", key).expect("blob lookup works").expect("page image stored");
+        assert_eq!(blob, format!("png-{page}").into_bytes());
+    }
+    assert_eq!(svc.list_doc_blobs("doc-pdf").expect("list works").len(), pages.len());
+}
+
+#[test]
+fn canceled_pdf_ingest_leaves_no_page_images_behind() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |cfg| cfg.pdf_page_images = true);
+    let cancel = hybrid_service::CancelToken::new();
+    svc.set_page_renderer(std::sync::Arc::new(StubPageRenderer { cancel: Some(cancel.clone()) }));
+
+    let err = svc.ingest_file_with_progress(&sample_pdf(), Some("doc-pdf"), Some(&cancel), None).unwrap_err();
+    assert!(err.to_string().contains("canceled"));
+    assert!(svc.list_doc_blobs("doc-pdf").expect("list works").is_empty(), "the page rendered before the cancel is removed");
+    assert!(svc.get_document_chunks("doc-pdf", 10, 0).expect("chunks load").is_empty());
+}
+
+#[test]
+fn rerank_export_pairs_logged_queries_with_returned_chunk_texts() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |cfg| cfg.search_log = true);
+    svc.ingest_text("Rust ownership prevents data races.", Some("doc-a")).expect("ingest a");
+    svc.ingest_text("SQLite stores the chunk table.", Some("doc-b")).expect("ingest b");
+
+    let hits = svc.search_hybrid("ownership in rust", 2, &[], 0.5, 0.5).expect("search");
+    assert!(!hits.is_empty());
+    let log = svc.search_log(0, 10).expect("search log");
+    assert_eq!(log.len(), 1);
+    assert!(svc.label_search_hit(log[0].log_id, &hits[0].chunk.chunk_id.0, 1.0).expect("label"));
+
+    let mut out = Vec::new();
+    let n = svc.export_rerank_dataset(&mut out, hybrid_service::RerankExportFormat::Jsonl).expect("export");
+    let rows: Vec<hybrid_service::RerankPair> = String::from_utf8(out)
+        .expect("utf-8")
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("row decodes"))
+        .collect();
+    assert_eq!(n, hits.len());
+    assert_eq!(rows.len(), hits.len());
+    for (row, hit) in rows.iter().zip(&hits) {
+        assert_eq!(row.query, "ownership in rust");
+        assert_eq!(row.chunk_id, hit.chunk.chunk_id.0);
+        assert_eq!(row.passage, hit.chunk.text);
+    }
+    assert_eq!(rows[0].label, Some(1.0));
+}
+
+#[test]
+fn file_ingest_records_chunk_and_total_token_counts() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let path = dir.path().join("notes.txt");
+    let para = "Hybrid search combines lexical and vector signals. ".repeat(20);
+    std::fs::write(&path, format!("{para}\n\n{para}\n\n{para}")).expect("write input");
+    svc.ingest_file_with_progress(path.to_str().unwrap(), Some("doc-tok"), None, None).expect("ingest succeeds");
+
+    let repo = chunking_store::sqlite_repo::SqliteRepo::open(dir.path().join("chunks.db")).expect("open repo");
+    let chunks: Vec<_> = repo.iter_chunks(100).map(|r| r.expect("chunk loads")).collect();
+    assert!(!chunks.is_empty());
+    let mut sum = 0u32;
+    for c in &chunks {
+        let n: u32 = c.meta.get(chunk_model::META_TOKENS).expect("chunk has a token count").parse().expect("numeric");
+        assert!(n > 0);
+        sum += n;
+    }
+    let files = svc.list_files(10, 0).expect("list files");
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].total_tokens, Some(sum));
+}
+
+#[test]
+fn parallel_file_ingest_reports_coherent_per_file_progress() {
+    use std::sync::{Arc, Mutex};
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let paths: Vec<(String, Option<String>)> = (0..6)
+        .map(|i| {
+            let path = dir.path().join(format!("note-{i}.txt"));
+            std::fs::write(&path, format!("Note {i} covers topic number {i} in some detail.")).expect("write input");
+            (path.to_string_lossy().into_owned(), Some(format!("doc-{i}")))
+        })
+        .collect();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    let progress: Box<dyn FnMut(hybrid_service::FileProgress) + Send> = Box::new(move |p| sink.lock().unwrap().push(p));
+    let n = svc.ingest_files_parallel(&paths, 3, None, Some(progress)).expect("parallel ingest");
+    assert_eq!(n, paths.len());
+
+    let seen = seen.lock().unwrap();
+    for i in 0..paths.len() {
+        let events: Vec<_> = seen.iter().filter(|p| p.index == i).collect();
+        assert!(events.iter().all(|p| p.total == paths.len() && p.path == paths[i].0));
+        assert!(matches!(events.first().map(|p| &p.event), Some(ProgressEvent::Start { .. })), "file {i} starts");
+        assert!(matches!(events.last().map(|p| &p.event), Some(ProgressEvent::Finished { .. })), "file {i} finishes");
+        assert!(!svc.get_document_chunks(&format!("doc-{i}"), 10, 0).expect("doc chunks").is_empty());
+    }
+    // The single writer finishes one file before starting the next
+    let mut open = None;
+    for p in seen.iter() {
+        match p.event {
+            ProgressEvent::Start { .. } => { assert_eq!(open, None); open = Some(p.index); }
+            ProgressEvent::Finished { .. } => { assert_eq!(open, Some(p.index)); open = None; }
+            _ => assert_eq!(open, Some(p.index)),
+        }
+    }
+
+    let canceled = hybrid_service::CancelToken::new();
+    canceled.cancel();
+    let err = svc.ingest_files_parallel(&paths, 3, Some(&canceled), None).expect_err("canceled run fails");
+    assert!(matches!(err, ServiceError::Embed(ref m) if m == "canceled"));
+
+    // Canceling mid-file reports one run-level `Canceled`, not one per interrupted file
+    let cancel = hybrid_service::CancelToken::new();
+    let (trigger, seen) = (cancel.clone(), Arc::new(Mutex::new(Vec::new())));
+    let sink = Arc::clone(&seen);
+    let progress: Box<dyn FnMut(hybrid_service::FileProgress) + Send> = Box::new(move |p| {
+        if matches!(p.event, ProgressEvent::Start { .. }) { trigger.cancel(); }
+        sink.lock().unwrap().push(p);
+    });
+    let err = svc.ingest_files_parallel(&paths, 3, Some(&cancel), Some(progress)).expect_err("canceled run fails");
+    assert!(matches!(err, ServiceError::Embed(ref m) if m == "canceled"));
+    let seen = seen.lock().unwrap();
+    let canceled: Vec<_> = seen.iter().filter(|p| matches!(p.event, ProgressEvent::Canceled)).collect();
+    assert_eq!(canceled.len(), 1);
+    assert_eq!((canceled[0].index, seen.last().map(|p| p.index)), (paths.len(), Some(paths.len())));
+}
+
+#[test]
+fn file_ingest_reports_vector_indexing_and_saving() {
+    use std::sync::{Arc, Mutex};
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let path = dir.path().join("notes.txt");
+    let para = "Hybrid search combines lexical and vector signals. ".repeat(20);
+    std::fs::write(&path, format!("{para}\n\n{para}\n\n{para}")).expect("write input");
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    let progress: Box<dyn FnMut(ProgressEvent) + Send> = Box::new(move |ev| sink.lock().unwrap().push(ev));
+    svc.ingest_file_with_progress(path.to_str().unwrap(), Some("doc-ev"), None, Some(progress)).expect("ingest succeeds");
+
+    let (chunks, _) = svc.repo_counts().expect("counts");
+    let seen = seen.lock().unwrap();
+    let indexed = seen.iter().position(|ev| matches!(ev, ProgressEvent::IndexVector { total } if *total as i64 == chunks));
+    let saved = seen.iter().position(|ev| matches!(ev, ProgressEvent::SaveIndexes));
+    let finished = seen.iter().position(|ev| matches!(ev, ProgressEvent::Finished { .. }));
+    assert!(indexed.is_some() && indexed < saved && saved < finished, "{seen:?}");
+}
+
+#[test]
+fn reindex_all_rebuilds_indexes_from_the_db_and_swaps_them_in() {
+    use std::sync::{Arc, Mutex};
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    for (doc, text) in [("doc-a", "Alpha notes about vector search."), ("doc-b", "Beta notes about lexical ranking.")] {
+        svc.ingest_text(text, Some(doc)).expect("ingest text");
+    }
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    let progress: Box<dyn FnMut(ProgressEvent) + Send> = Box::new(move |ev| sink.lock().unwrap().push(ev));
+    let n = svc.reindex_all(true, None, Some(progress)).expect("reindex");
+    assert_eq!(n, 2);
+    assert!(matches!(seen.lock().unwrap().last(), Some(ProgressEvent::Reindexed { total: 2, .. })));
+
+    // No staging or pre-reindex copies are left behind and the swapped-in HNSW serves searches
+    let leftovers: Vec<_> = std::fs::read_dir(dir.path()).unwrap().flatten()
+        .filter(|e| [".rebuild", ".prev", ".tmp"].iter().any(|s| e.file_name().to_string_lossy().ends_with(s)))
+        .collect();
+    assert!(leftovers.is_empty(), "staging dirs left: {leftovers:?}");
+    let hits = svc.search_hybrid("vector search", 2, &[], 0.0, 1.0).expect("search after reindex");
+    assert_eq!(hits.first().map(|h| h.chunk.doc_id.0.as_str()), Some("doc-a"));
+
+    let canceled = hybrid_service::CancelToken::new();
+    canceled.cancel();
+    assert!(svc.reindex_all(true, Some(&canceled), None).is_err());
+}
+
+#[test]
+fn canceled_reindex_leaves_model_stamps_and_persisted_vectors_untouched() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    // More documents than one reindex page, so the run is canceled after a page was embedded
+    let paths: Vec<(String, Option<String>)> = (0..65)
+        .map(|i| {
+            let path = dir.path().join(format!("note-{i}.txt"));
+            std::fs::write(&path, format!("Note {i} describes harbour number {i}.")).expect("write input");
+            (path.to_string_lossy().into_owned(), Some(format!("doc-{i}")))
+        })
+        .collect();
+    let configure = |model: &str| {
+        let model = model.to_string();
+        move |cfg: &mut ServiceConfig| {
+            cfg.embedder.embedding_model_id = model;
+            cfg.persist_vectors_in_records = true;
+            cfg.progress_min_interval_ms = 0;
+        }
+    };
+    let svc = service_at(dir.path(), configure("model-a"));
+    svc.ingest_files_parallel(&paths, 2, None, None).expect("ingest files");
+    let extras = |svc: &HybridService| -> Vec<_> {
+        svc.get_document_chunks("doc-0", 10, 0).expect("doc chunks").into_iter().map(|c| c.extra).collect()
+    };
+    let extras_before = extras(&svc);
+    drop(svc);
+
+    let svc = service_at(dir.path(), configure("model-b"));
+    let model_of = |svc: &HybridService| {
+        let file = svc.with_repo(|repo| repo.get_file("doc-0").map_err(|e| ServiceError::Repo(e.to_string()))).expect("get file").expect("file");
+        file.meta.get(chunk_model::META_EMBED_MODEL_ID).cloned()
+    };
+    let cancel = hybrid_service::CancelToken::new();
+    let trigger = cancel.clone();
+    let progress: Box<dyn FnMut(ProgressEvent) + Send> = Box::new(move |ev| {
+        if matches!(ev, ProgressEvent::EmbedBatch { .. }) { trigger.cancel(); }
+    });
+    assert!(svc.reindex_all(true, Some(&cancel), Some(progress)).is_err());
+    assert_eq!(model_of(&svc).as_deref(), Some("model-a"));
+    assert_eq!(extras(&svc), extras_before);
+
+    assert_eq!(svc.reindex_all(true, None, None).expect("reindex"), paths.len());
+    assert_eq!(model_of(&svc).as_deref(), Some("model-b"));
+}
+
+#[test]
+fn ingest_records_the_embedding_model_on_files_and_the_hnsw_snapshot() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |cfg| cfg.embedder.embedding_model_id = "ruri-test-model".into());
+    let dim = svc.detected_embedding_dimension().expect("dimension");
+    svc.ingest_text("Harbour seals rest on the breakwater at low tide.", Some("doc-seal")).expect("ingest text");
+
+    let file = svc
+        .with_repo(|repo| repo.get_file("doc-seal").map_err(|e| ServiceError::Repo(e.to_string())))
+        .expect("read file")
+        .expect("file record");
+    assert_eq!(file.meta.get(chunk_model::META_EMBED_MODEL_ID).map(String::as_str), Some("ruri-test-model"));
+    assert_eq!(file.meta.get(chunk_model::META_EMBED_DIM), Some(&dim.to_string()));
+    assert!(svc.embedding_fingerprint().starts_with("ruri-test-model@"));
+    assert_eq!(file.meta.get(chunk_model::META_EMBED_FINGERPRINT).map(String::as_str), Some(svc.embedding_fingerprint()));
+    assert_eq!(svc.store_embedding_fingerprint().as_deref(), Some(svc.embedding_fingerprint()));
+}
+
+#[test]
+fn failed_hnsw_snapshot_save_rolls_the_whole_ingest_back() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    // A regular file where the snapshot directory should go makes every save fail
+    let blocked = dir.path().join("hnsw-blocked");
+    std::fs::write(&blocked, b"not a directory").expect("write blocker");
+    let svc = service_at(dir.path(), |cfg| cfg.hnsw_dir = Some(blocked.clone()));
+    let notes = dir.path().join("pelicans.txt");
+    std::fs::write(&notes, "Pelicans dive for fish near the pier.").expect("write notes");
+
+    let err = svc.ingest_file(&notes.to_string_lossy(), Some("doc-pelican")).expect_err("snapshot save fails");
+    assert!(matches!(err, ServiceError::Index(ref m) if m.contains("saving HNSW snapshot")), "{err:?}");
+    // Neither the chunks nor the file row were committed, and no index serves the document
+    assert!(svc.get_document_chunks("doc-pelican", 10, 0).expect("doc chunks").is_empty());
+    assert!(svc.list_files(10, 0).expect("list files").is_empty());
+    assert!(svc.search_vector("pelicans fishing", 5, &[]).expect("vector search").is_empty());
+    #[cfg(feature = "tantivy")]
+    {
+        let (tv, tv_and, tv_or) = svc.tantivy_triple("pelicans", 5, &[]).expect("tantivy search");
+        assert!(tv.is_empty() && tv_and.is_empty() && tv_or.is_empty());
+    }
+}
+
+#[test]
+fn embedder_fingerprint_changes_with_the_model_file_and_output_settings() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let mut cfg = ServiceConfig::default().embedder;
+    cfg.model_path = dir.path().join("model.onnx");
+    std::fs::write(&cfg.model_path, b"weights v1").expect("write model");
+    let base = hybrid_service::embedder_fingerprint(&cfg);
+    assert_eq!(hybrid_service::embedder_fingerprint(&cfg), base);
+
+    let mut other = cfg.clone();
+    other.max_input_length += 1;
+    assert_ne!(hybrid_service::embedder_fingerprint(&other), base);
+    let mut other = cfg.clone();
+    other.normalize = !other.normalize;
+    assert_ne!(hybrid_service::embedder_fingerprint(&other), base);
+    std::fs::write(&cfg.model_path, b"weights v2, retrained").expect("rewrite model");
+    assert_ne!(hybrid_service::embedder_fingerprint(&cfg), base);
+}
+
+#[test]
+fn scan_unregistered_skips_files_whose_content_is_already_ingested() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let docs = dir.path().join("docs");
+    std::fs::create_dir_all(&docs).unwrap();
+    std::fs::write(docs.join("a.txt"), "Lighthouses guide ships along the rocky coast.").unwrap();
+    std::fs::write(docs.join("b.txt"), "Ferries cross the strait twice a day.").unwrap();
+    // Same size as a.txt, different content: the size prefilter alone must not hide it
+    std::fs::write(docs.join("c.txt"), "Lighthouses guide ships along the rocky coasT.").unwrap();
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_file(&docs.join("a.txt").to_string_lossy(), None).expect("ingest a");
+
+    let pending = svc.scan_unregistered(&docs, 0, &["txt"]).expect("scan");
+    assert_eq!(pending, vec![docs.join("b.txt"), docs.join("c.txt")]);
+    let known = svc.known_files(None).expect("known files");
+    assert!(known.is_registered(&docs.join("a.txt")));
+    assert!(!known.is_registered(&docs.join("missing.txt")));
+}
+
+#[test]
+fn skip_duplicate_sha256_stops_before_embedding_a_known_file() {
+    use std::sync::{Arc, Mutex};
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let docs = dir.path().join("docs");
+    std::fs::create_dir_all(&docs).unwrap();
+    let text = "Tide tables list high and low water for each harbour.";
+    std::fs::write(docs.join("a.txt"), text).unwrap();
+    std::fs::write(docs.join("copy-of-a.txt"), text).unwrap();
+    let svc = service_at(dir.path(), |cfg| cfg.skip_duplicate_sha256 = true);
+    svc.ingest_file(&docs.join("a.txt").to_string_lossy(), Some("doc-a")).expect("ingest a");
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    let progress: Box<dyn FnMut(ProgressEvent) + Send> = Box::new(move |ev| sink.lock().unwrap().push(ev));
+    let err = svc
+        .ingest_file_with_progress(&docs.join("copy-of-a.txt").to_string_lossy(), None, None, Some(progress))
+        .expect_err("duplicate must be refused");
+    assert!(matches!(err, ServiceError::DuplicateContent { ref doc_id } if doc_id == "doc-a"), "{err:?}");
+    let seen = seen.lock().unwrap();
+    assert!(matches!(seen.as_slice(), [ProgressEvent::SkippedDuplicate { existing_doc_id }] if existing_doc_id == "doc-a"), "{seen:?}");
+    assert_eq!(svc.list_files_page(10, 0).expect("list files").1, 1);
+
+    // Folder ingest skips the duplicate instead of failing
+    std::fs::write(docs.join("b.txt"), "Ferries leave the pier on the hour.").unwrap();
+    assert_eq!(svc.ingest_folder(&docs, 0, &["txt"]).expect("ingest folder"), 1);
+}
+
+#[test]
+fn reingest_file_replaces_chunks_and_bumps_the_revision() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let v1 = dir.path().join("notes-v1.txt");
+    let v2 = dir.path().join("notes-v2.txt");
+    std::fs::write(&v1, "Lighthouse keepers log the weather every four hours.").unwrap();
+    std::fs::write(&v2, "Pilot boats meet container ships outside the breakwater.").unwrap();
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_file(&v1.to_string_lossy(), Some("doc-notes")).expect("ingest v1");
+
+    svc.reingest_file(&v2.to_string_lossy(), "doc-notes").expect("reingest");
+    let (file, chunks) = svc
+        .with_repo(|repo| {
+            let file = repo.get_file("doc-notes").map_err(|e| ServiceError::Repo(e.to_string()))?;
+            let chunks = repo.get_chunks_by_doc_id("doc-notes", 100, 0).map_err(|e| ServiceError::Repo(e.to_string()))?;
+            Ok((file, chunks))
+        })
+        .expect("read back");
+    assert_eq!(file.expect("file record").doc_revision, Some(2));
+    assert!(!chunks.is_empty());
+    assert!(chunks.iter().all(|c| c.text.contains("Pilot boats")), "old chunks left: {chunks:?}");
+    assert_eq!(svc.list_files_page(10, 0).expect("list files").1, 1);
+    let hits = svc.search_vector("lighthouse keepers weather log", 5, &[]).expect("vector search");
+    assert!(hits.iter().all(|h| h.chunk.text.contains("Pilot boats")));
+}
+
+#[test]
+fn repair_dry_run_reports_and_a_real_run_clears_the_drift() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    for (doc, text) in [("doc-a", "Tide tables list high water times."), ("doc-b", "Ferries pause when fog rolls in."), ("doc-c", "Buoys mark the channel edges.")] {
+        svc.ingest_text(text, Some(doc)).expect("ingest");
+    }
+    // doc-b loses its FileRecord; doc-c's chunk vanishes from the DB but stays in HNSW
+    svc.with_repo(|repo| repo.delete_files_by_doc_ids(&["doc-b".into()]).map_err(|e| ServiceError::Repo(e.to_string()))).expect("drop file");
+    let raw = rusqlite::Connection::open(dir.path().join("chunks.db")).expect("open raw connection");
+    raw.execute("DELETE FROM chunks WHERE doc_id = 'doc-c'", []).expect("drop chunk");
+
+    let dry = svc.repair(&RepairOpts { dry_run: true, ..Default::default() }, None, None).expect("dry run");
+    assert_eq!((dry.orphan_chunks_deleted, dry.hnsw_stale_removed, dry.hnsw_reinserted), (1, 1, 0));
+    assert_eq!(svc.find_orphans().expect("orphans").chunks_without_file.len(), 1, "dry run changed the store");
+
+    assert_eq!(svc.repair(&RepairOpts::default(), None, None).expect("repair"), dry);
+    assert!(svc.find_orphans().expect("orphans").chunks_without_file.is_empty());
+    let hnsw = svc.index_drift().expect("drift").hnsw.expect("HNSW built");
+    assert!(hnsw.missing.is_empty() && hnsw.stale.is_empty(), "{hnsw:?}");
+}
+
+#[test]
+fn repair_reinserts_chunks_missing_from_hnsw_reusing_persisted_vectors() {
+    use chunking_store::ChunkPrimaryStore;
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |cfg| cfg.persist_vectors_in_records = true);
+    svc.ingest_text("Tide tables list high water times.", Some("doc-a")).expect("ingest");
+    svc.ingest_text("Ferries pause when fog rolls in.", Some("doc-b")).expect("ingest");
+
+    // Two chunks written straight to the DB: one keeps doc-b's persisted vector, one has none
+    let mut repo = chunking_store::sqlite_repo::SqliteRepo::open(dir.path().join("chunks.db")).expect("open repo");
+    let base = repo.get_chunks_by_doc_id("doc-b", 1, 0).expect("chunks").remove(0);
+    let file = repo.get_file("doc-b").expect("file").expect("doc-b has a file");
+    let mut added = Vec::new();
+    for (doc, text, keep_vector) in [("doc-c", base.text.as_str(), true), ("doc-d", "Lighthouses guide ships past the reef at night.", false)] {
+        let mut rec = base.clone();
+        rec.doc_id = chunk_model::DocumentId(doc.into());
+        rec.chunk_id = chunk_model::ChunkId(format!("{doc}#0"));
+        rec.text = text.into();
+        if !keep_vector { rec.extra.clear(); }
+        repo.upsert_file(&chunk_model::FileRecord { doc_id: rec.doc_id.clone(), ..file.clone() }).expect("file");
+        added.push(rec);
+    }
+    repo.upsert_chunks(added).expect("chunks");
+    assert_eq!(svc.index_drift().expect("drift").hnsw.expect("HNSW built").missing.len(), 2);
+
+    let report = svc.repair(&RepairOpts::default(), None, None).expect("repair");
+    assert_eq!((report.hnsw_reinserted, report.canceled), (2, false));
+    assert!(svc.index_drift().expect("drift").hnsw.expect("HNSW built").missing.is_empty());
+    let hits = svc.search_vector("lighthouse guiding ships at night", 1, &[]).expect("search");
+    assert_eq!(hits[0].chunk.doc_id.0, "doc-d");
+}
+
+#[test]
+fn repair_can_synthesize_file_records_for_orphan_chunks() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_text("Buoys mark the channel edges.", Some("doc-a")).expect("ingest");
+    svc.with_repo(|repo| repo.delete_files_by_doc_ids(&["doc-a".into()]).map_err(|e| ServiceError::Repo(e.to_string()))).expect("drop file");
+
+    let opts = RepairOpts { orphan_chunks: OrphanChunkAction::SynthesizeFile, ..Default::default() };
+    let report = svc.repair(&opts, None, None).expect("repair");
+    assert_eq!((report.files_synthesized, report.orphan_chunks_deleted), (1, 0));
+    assert!(svc.find_orphans().expect("orphans").chunks_without_file.is_empty());
+    let file = svc.with_repo(|repo| repo.get_file("doc-a").map_err(|e| ServiceError::Repo(e.to_string()))).expect("get file");
+    assert_eq!(file.expect("synthesized file").chunk_count, Some(1));
+}
+
+#[test]
+fn recover_reports_ingests_cut_off_by_a_crash() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let notes = dir.path().join("notes.txt");
+    std::fs::write(&notes, "Harbour cranes unload timber at dawn.").unwrap();
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_file(&notes.to_string_lossy(), Some("doc-notes")).expect("ingest");
+    assert!(svc.recover().expect("recover").is_empty());
+    let raw = rusqlite::Connection::open(dir.path().join("chunks.db")).expect("open raw connection");
+    let rows: i64 = raw.query_row("SELECT COUNT(*) FROM ingest_journal", [], |r| r.get(0)).unwrap();
+    assert_eq!(rows, 0, "a clean run without ingest_journal leaves no entry");
+
+    // What a process killed mid-ingest leaves behind
+    let path = dir.path().join("atlas.pdf").to_string_lossy().into_owned();
+    svc.with_repo(|repo| repo.record_ingest_started(&path, None, "doc-atlas", "2026-01-01T00:00:00+00:00").map_err(|e| ServiceError::Repo(e.to_string())))
+        .expect("started entry");
+    let interrupted = svc.recover().expect("recover");
+    assert_eq!(interrupted.len(), 1);
+    assert_eq!((interrupted[0].path.as_str(), interrupted[0].doc_id.as_str()), (path.as_str(), "doc-atlas"));
+
+    // Survives an unrelated clean ingest
+    svc.ingest_file(&notes.to_string_lossy(), Some("doc-notes")).expect("ingest again");
+    assert_eq!(svc.recover().expect("recover"), interrupted);
+}
+
+#[test]
+fn max_per_doc_caps_hits_from_one_document_in_every_search() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let path = dir.path().join("volcanoes.txt");
+    let para = |i: usize| format!("Volcano note {i}: lava flows and volcanic ash shape the land. ").repeat(12);
+    std::fs::write(&path, (0..4).map(para).collect::<Vec<_>>().join("\n\n")).expect("write input");
+    svc.ingest_file(&path.to_string_lossy(), Some("doc-long")).expect("ingest long doc");
+    svc.ingest_text("Volcanic lava cools into basalt rock.", Some("doc-short")).expect("ingest short doc");
+    assert!(svc.repo_counts().expect("counts").0 > 3, "the long document should span several chunks");
+
+    let opts = SearchOptions { top_k: 3, max_per_doc: Some(1), ..Default::default() };
+    let docs = |hits: Vec<chunking_store::SearchHit>| {
+        let mut docs: Vec<String> = hits.into_iter().map(|h| h.chunk.doc_id.0).collect();
+        docs.sort();
+        docs
+    };
+    let both = vec!["doc-long".to_string(), "doc-short".to_string()];
+    assert_eq!(docs(svc.search_vector_with_options("volcanic lava", &[], &opts).expect("vector")), both);
+    assert_eq!(docs(svc.search_hybrid_with_options("volcanic lava", &[], &opts, 0.5, 0.5).expect("hybrid")), both);
+    let text = docs(svc.search_text_with_options("lava", &[], &opts).expect("text"));
+    assert!(text.windows(2).all(|w| w[0] != w[1]), "one hit per document: {text:?}");
+}
+
+#[test]
+fn search_hits_come_back_in_descending_score_order() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    for (i, text) in [
+        "Volcanoes erupt molten rock called lava.",
+        "Glaciers carve deep valleys over centuries.",
+        "Lava flows cool into basalt near volcanoes.",
+        "Tea ceremonies follow a precise ritual.",
+        "Volcanic ash can ground aircraft for days.",
+    ]
+    .iter()
+    .enumerate()
+    {
+        svc.ingest_text(text, Some(&format!("doc-{i}"))).expect("ingest");
+    }
+
+    let descending = |hits: &[chunking_store::SearchHit]| hits.windows(2).all(|w| w[0].score >= w[1].score);
+    let hybrid = svc.search_hybrid("volcano lava", 5, &[], 0.5, 0.5).expect("hybrid search");
+    assert!(hybrid.len() > 1);
+    assert!(descending(&hybrid), "{:?}", hybrid.iter().map(|h| h.score).collect::<Vec<_>>());
+    let text = svc.search_text("volcanoes", 5, &[]).expect("text search");
+    assert!(descending(&text), "{:?}", text.iter().map(|h| h.score).collect::<Vec<_>>());
+}
+
+#[test]
+fn rerank_by_vector_rescores_with_exact_cosine_and_needs_stored_vectors() {
+    let texts = [
+        "Volcanoes erupt molten rock called lava.",
+        "Glaciers carve deep valleys over centuries.",
+        "Tea ceremonies follow a precise ritual.",
+    ];
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |cfg| cfg.persist_vectors_in_records = true);
+    for (i, text) in texts.iter().enumerate() {
+        svc.ingest_text(text, Some(&format!("doc-{i}"))).expect("ingest");
+    }
+    let hits = svc.search_hybrid("lava from a volcano", 3, &[], 1.0, 0.0).expect("search");
+    let reranked = svc.rerank_by_vector("lava from a volcano", hits.clone());
+    assert_eq!(reranked.len(), hits.len());
+    assert!(reranked.windows(2).all(|w| w[0].score >= w[1].score));
+    assert_eq!(reranked[0].chunk.doc_id.0, "doc-0");
+    assert!(reranked.iter().all(|h| (-1.0..=1.0001).contains(&h.score)));
+
+    // Without stored vectors the input comes back as-is
+    let plain_dir = tempfile::tempdir().expect("create temp dir");
+    let plain = service_at(plain_dir.path(), |_| {});
+    plain.ingest_text(texts[0], Some("doc-0")).expect("ingest");
+    let hits = plain.search_hybrid("lava", 3, &[], 1.0, 0.0).expect("search");
+    let scores: Vec<f32> = hits.iter().map(|h| h.score).collect();
+    let same: Vec<f32> = plain.rerank_by_vector("lava", hits).iter().map(|h| h.score).collect();
+    assert_eq!(scores, same);
+}
+
+#[test]
+fn f16_stores_persist_record_vectors_at_half_precision() {
+    let texts = ["Volcanoes erupt molten rock called lava.", "Tea ceremonies follow a precise ritual."];
+    let stored_extra = |dtype: VectorDtype| {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let svc = service_at(dir.path(), |cfg| { cfg.persist_vectors_in_records = true; cfg.vector_dtype = dtype; });
+        for (i, text) in texts.iter().enumerate() {
+            svc.ingest_text(text, Some(&format!("doc-{i}"))).expect("ingest");
+        }
+        let hits = svc.search_hybrid("lava from a volcano", 2, &[], 1.0, 0.0).expect("search");
+        let reranked = svc.rerank_by_vector("lava from a volcano", hits);
+        (reranked, dir)
+    };
+    let (full, _full_dir) = stored_extra(VectorDtype::F32);
+    let (half, _half_dir) = stored_extra(VectorDtype::F16);
+
+    let f16 = half[0].chunk.extra.get(EXTRA_EMBEDDING_F16_KEY).and_then(|v| v.as_str()).expect("f16 vector stored");
+    assert!(!half[0].chunk.extra.contains_key(EXTRA_EMBEDDING_KEY));
+    let f32_json = full[0].chunk.extra[EXTRA_EMBEDDING_KEY].to_string();
+    assert!(f16.len() < f32_json.len(), "{} vs {} bytes", f16.len(), f32_json.len());
+    // rerank_by_vector read the half-precision vectors back: same order, scores within tolerance
+    assert_eq!(half.iter().map(|h| &h.chunk.chunk_id).collect::<Vec<_>>(), full.iter().map(|h| &h.chunk.chunk_id).collect::<Vec<_>>());
+    for (h, f) in half.iter().zip(&full) {
+        assert!((h.score - f.score).abs() < 1e-2, "{} vs {}", h.score, f.score);
+    }
+}
+
+#[test]
+fn search_mmr_skips_near_duplicates_and_reduces_to_relevance_at_lambda_one() {
+    let texts = [
+        "Volcanoes erupt molten rock called lava.",
+        "Volcanoes erupt molten rock that is called lava.",
+        "Lava flows cool into basalt near volcanoes.",
+        "Tea ceremonies follow a precise ritual.",
+    ];
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    for (i, text) in texts.iter().enumerate() {
+        svc.ingest_text(text, Some(&format!("doc-{i}"))).expect("ingest");
+    }
+    let query = "volcano lava";
+
+    let plain = svc.search_hybrid(query, 2, &[], 1.0, 4.0).expect("search");
+    let relevance_only = svc.search_mmr(query, 2, 1.0, &[]).expect("mmr");
+    let ids = |hits: &[chunking_store::SearchHit]| hits.iter().map(|h| h.chunk.chunk_id.0.clone()).collect::<Vec<_>>();
+    assert_eq!(ids(&plain), ids(&relevance_only));
+
+    let diverse = svc.search_mmr(query, 2, 0.3, &[]).expect("mmr");
+    assert_eq!(diverse.len(), 2);
+    let docs: Vec<&str> = diverse.iter().map(|h| h.chunk.doc_id.0.as_str()).collect();
+    assert!(!(docs.contains(&"doc-0") && docs.contains(&"doc-1")), "near-duplicates both selected: {docs:?}");
+}
+
+#[test]
+fn hybrid_hits_carry_a_score_breakdown_that_sums_to_the_fused_score() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_text("Volcanoes erupt molten rock called lava.", Some("doc-0")).expect("ingest");
+    svc.ingest_text("Tea ceremonies follow a precise ritual.", Some("doc-1")).expect("ingest");
+
+    let hits = svc.search_hybrid("lava", 5, &[], 1.0, 3.0).expect("search");
+    assert!(!hits.is_empty());
+    for h in &hits {
+        let parts = h.components.expect("hybrid hits explain their score");
+        assert!((parts.text_weight - 0.25).abs() < 1e-6 && (parts.vector_weight - 0.75).abs() < 1e-6);
+        assert!(parts.text.is_some() || parts.vector.is_some());
+        assert!((parts.fused() - h.score).abs() < 1e-6);
+    }
+
+    // Single-signal searches leave it unset
+    assert!(svc.search_vector("lava", 5, &[]).expect("search").iter().all(|h| h.components.is_none()));
+}
+
+#[test]
+fn weighted_hybrid_excludes_signals_without_a_weight() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_text("Volcanoes erupt molten rock called lava.", Some("doc-0")).expect("ingest");
+    svc.ingest_text("Tea ceremonies follow a precise ritual.", Some("doc-1")).expect("ingest");
+
+    let vector_only = HybridWeights { vec: Some(2.0), ..Default::default() };
+    let hits = svc.search_hybrid_weighted("lava", 5, &[], vector_only).expect("search");
+    assert!(!hits.is_empty());
+    for h in &hits {
+        let parts = h.components.expect("breakdown");
+        assert_eq!((parts.text, parts.text_and, parts.text_or), (None, None, None));
+        assert!(parts.vector.is_some());
+        assert_eq!(parts.vector_weight, 1.0);
+        assert!((parts.fused() - h.score).abs() < 1e-6);
+    }
+    let plain: Vec<String> = svc.search_vector("lava", 5, &[]).expect("search").into_iter().map(|h| h.chunk.chunk_id.0).collect();
+    let weighted: Vec<String> = hits.into_iter().map(|h| h.chunk.chunk_id.0).collect();
+    assert_eq!(plain, weighted);
+
+    let text_only = HybridWeights { tv: Some(1.0), tv_and: Some(1.0), tv_or: Some(0.0), vec: None };
+    let hits = svc.search_hybrid_weighted("lava", 5, &[], text_only).expect("search");
+    #[cfg(feature = "tantivy")]
+    assert!(!hits.is_empty());
+    for h in &hits {
+        let parts = h.components.expect("breakdown");
+        assert_eq!(parts.vector, None);
+        assert_eq!((parts.text_weight, parts.text_and_weight, parts.text_or_weight, parts.vector_weight), (0.5, 0.5, 0.0, 0.0));
+    }
+}
+
+#[test]
+fn min_fetch_widens_signals_but_truncates_the_fused_result() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    svc.ingest_text("Glaciers carve deep valleys.", Some("doc-a")).expect("ingest");
+    svc.ingest_text("Glaciers retreat in warm summers.", Some("doc-b")).expect("ingest");
+    svc.ingest_text("A zebra grazes near the glaciers.", Some("doc-c")).expect("ingest");
+
+    let opts = SearchOptions { top_k: 1, fetch_factor: 1, min_fetch: 3, ..Default::default() };
+    let hits = svc.search_hybrid_with_options("zebra", &[], &opts, 0.5, 0.5).expect("search");
+    assert_eq!(hits.len(), 1);
+    // Only doc-c matches the text; the widened vector fetch lets both signals agree on it
+    assert_eq!(hits[0].chunk.doc_id.0, "doc-c");
+    let parts = hits[0].components.expect("breakdown");
+    assert!(parts.vector.is_some());
+    #[cfg(any(feature = "tantivy", feature = "fts"))]
+    assert!(parts.text.is_some());
+}
+
+#[test]
+fn bulk_delete_triggers_background_compaction() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let db = dir.path().join("chunks.db");
+    // WAL mode: count the log too, since un-checkpointed pages live there
+    let store_bytes = || {
+        let wal = dir.path().join("chunks.db-wal");
+        std::fs::metadata(&db).map(|m| m.len()).unwrap_or(0) + std::fs::metadata(&wal).map(|m| m.len()).unwrap_or(0)
+    };
+    let svc = service_at(dir.path(), |cfg| cfg.auto_compact_after_delete_ratio = Some(0.5));
+    let body = "Tide pools hold anemones, crabs and small fish between the rocks. ".repeat(40);
+    let records: Vec<_> = (0..200)
+        .map(|i| section_chunk(if i < 190 { "doc-bulk" } else { "doc-keep" }, &format!("b{i}"), &["Shore"], &format!("{i} {body}")))
+        .collect();
+    svc.ingest_records(&records, EmbedPolicy::UseProvided(Vec::new())).expect("ingest records");
+    let before = store_bytes();
+
+    let filter = chunking_store::FilterClause {
+        kind: chunking_store::FilterKind::Must,
+        op: chunking_store::FilterOp::DocIdEq("doc-bulk".into()),
+    };
+    let rep = svc.delete_by_filter(&[filter], 500).expect("delete");
+    assert_eq!(rep.db_deleted, 190);
+    svc.wait_for_compaction();
+
+    assert!(store_bytes() < before / 2, "store did not shrink: {} -> {}", before, store_bytes());
+    assert_eq!(svc.repo_counts().expect("counts").0, 10);
+}
+
+#[test]
+fn explain_filters_reports_vector_post_filtering() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let filters = vec![chunking_store::FilterClause {
+        kind: chunking_store::FilterKind::Must,
+        op: chunking_store::FilterOp::DocIdEq("doc-1".into()),
+    }];
+    let plans = svc.explain_filters(&filters);
+    let hnsw: Vec<_> = plans.iter().filter(|p| p.backend == "hnsw").collect();
+    assert_eq!(hnsw.len(), 1);
+    assert_eq!(hnsw[0].placement, chunking_store::FilterPlacement::PostFilter);
+    // Every text backend pushes doc id equality down.
+    assert!(plans.iter().filter(|p| p.backend != "hnsw").all(|p| p.placement == chunking_store::FilterPlacement::Prefilter));
+}
+
+#[cfg(feature = "tantivy")]
+#[test]
+fn ingest_chunks_is_visible_to_tantivy_text_search() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let svc = service_at(dir.path(), |_| {});
+    let records = vec![
+        section_chunk("doc-tv", "t1", &["Birds"], "Albatrosses glide for hours over the ocean."),
+        section_chunk("doc-tv", "t2", &["Trees"], "Sequoias are among the tallest trees."),
+    ];
+    svc.ingest_chunks(&records, None).expect("ingest chunks");
+
+    let hits = svc.search_text("Albatrosses", 5, &[]).expect("text search");
+    assert_eq!(hits.iter().map(|h| h.chunk.chunk_id.0.as_str()).collect::<Vec<_>>(), vec!["t1"]);
+}
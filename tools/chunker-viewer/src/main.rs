@@ -167,6 +167,9 @@ fn chunk_file_auto(path: &str, params: PdfChunkParams) -> Result<(FileRecord, Ve
             short_merge_min_chars: 100,
             penalize_short_line: true,
             penalize_page_boundary_no_newline: true,
+            keep_code_blocks: true,
+            overlap_chars: 0,
+            token_counter: None,
         };
         let segs = file_chunker::text_segmenter::chunk_blocks_to_segments(&blocks, &tparams);
 
@@ -197,21 +200,27 @@ fn chunk_file_auto(path: &str, params: PdfChunkParams) -> Result<(FileRecord, Ve
             extra: std::collections::BTreeMap::new(),
         };
 
+        let kinds = file_chunker::text_segmenter::segment_block_kinds(&blocks, &segs);
+        let paths = file_chunker::text_segmenter::segment_section_paths(&blocks, &segs, &mut Vec::new());
         let chunks: Vec<ChunkRecord> = segs
             .into_iter()
+            .zip(kinds)
+            .zip(paths)
             .enumerate()
-            .map(|(i, (text, ps, pe))| ChunkRecord {
+            .map(|(i, (((text, ps, pe), block_kinds), section_path))| ChunkRecord {
                 schema_version: SCHEMA_MAJOR,
                 doc_id: DocumentId(path.to_string()),
                 chunk_id: ChunkId(format!("{}#{}", path, i)),
+                seq: Some(i as u32),
                 source_uri: path.to_string(),
                 source_mime: "application/vnd.openxmlformats-officedocument.wordprocessingml.document".into(),
                 extracted_at: String::new(),
                 page_start: ps,
                 page_end: pe,
                 text,
-                section_path: None,
+                section_path: Some(section_path),
                 meta: std::collections::BTreeMap::new(),
+                block_kinds,
                 extra: std::collections::BTreeMap::new(),
             })
             .collect();
@@ -227,6 +236,9 @@ fn chunk_file_auto(path: &str, params: PdfChunkParams) -> Result<(FileRecord, Ve
             short_merge_min_chars: 100,
             penalize_short_line: true,
             penalize_page_boundary_no_newline: true,
+            keep_code_blocks: true,
+            overlap_chars: 0,
+            token_counter: None,
         };
         let segs = file_chunker::text_segmenter::chunk_blocks_to_segments(&blocks, &tparams);
 
@@ -257,21 +269,27 @@ fn chunk_file_auto(path: &str, params: PdfChunkParams) -> Result<(FileRecord, Ve
             extra: std::collections::BTreeMap::new(),
         };
 
+        let kinds = file_chunker::text_segmenter::segment_block_kinds(&blocks, &segs);
+        let paths = file_chunker::text_segmenter::segment_section_paths(&blocks, &segs, &mut Vec::new());
         let chunks: Vec<ChunkRecord> = segs
             .into_iter()
+            .zip(kinds)
+            .zip(paths)
             .enumerate()
-            .map(|(i, (text, ps, pe))| ChunkRecord {
+            .map(|(i, (((text, ps, pe), block_kinds), section_path))| ChunkRecord {
                 schema_version: SCHEMA_MAJOR,
                 doc_id: DocumentId(path.to_string()),
                 chunk_id: ChunkId(format!("{}#{}", path, i)),
+                seq: Some(i as u32),
                 source_uri: path.to_string(),
                 source_mime: "text/plain".into(),
                 extracted_at: String::new(),
                 page_start: Some(ps.unwrap_or(1)),
                 page_end: Some(pe.unwrap_or(1)),
                 text,
-                section_path: None,
+                section_path: Some(section_path),
                 meta: std::collections::BTreeMap::new(),
+                block_kinds,
                 extra: std::collections::BTreeMap::new(),
             })
             .collect();
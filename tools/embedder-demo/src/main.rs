@@ -184,6 +184,9 @@ impl DemoApp {
             embedding_model_id: ONNX_STDIO_DEFAULTS.embedding_model_id.into(),
             text_repr_version: ONNX_STDIO_DEFAULTS.text_repr_version.into(),
             preload_model_to_memory: self.preload_model_to_memory,
+            normalize: false,
+            pooling: Default::default(),
+            worker_count: 1,
         })
     }
 
@@ -0,0 +1,187 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use chunking_store::{FilterClause, FilterKind, FilterOp};
+use hybrid_service::{ChunkOptions, HybridService, ServiceConfig, DEFAULT_W_TEXT, DEFAULT_W_VEC};
+use serde_json::json;
+
+fn print_usage() {
+    eprintln!(
+        "Usage:\n\
+         hybrid-cli [OPTIONS] ingest <path> [--encoding ENC] [--doc-id ID]\n\
+         hybrid-cli [OPTIONS] ingest-dir <dir> [--ext pdf,txt] [--depth N] [--all]\n\
+         hybrid-cli [OPTIONS] search <query> [--mode hybrid|text|vector] [--top-k N] [--doc-id-filter ID]\n\
+         hybrid-cli [OPTIONS] delete --doc-id ID\n\
+         hybrid-cli [OPTIONS] recover [--requeue]\n\
+         \n\
+         Options:\n\
+           --store-root DIR    store directory (chunks.db, hnsw/); defaults to $HYBRID_STORE_ROOT,\n\
+                               else target/demo\n\
+           --model PATH_ONNX   --tokenizer PATH_JSON   --runtime PATH_DLL   --dim N   --max-tokens N\n\
+         Notes: ingest-dir skips files whose content is already registered unless --all is given.\n\
+         recover lists file ingests cut off by a crash; --requeue ingests them again.\n\
+         Results are printed to stdout as JSON.\n"
+    );
+}
+
+/// Pull `--name VALUE` out of `args`, leaving the other arguments in order.
+fn take_opt(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let Some(i) = args.iter().position(|a| a == name) else { return Ok(None) };
+    if i + 1 >= args.len() { return Err(format!("{name} requires a value")); }
+    let v = args.remove(i + 1);
+    args.remove(i);
+    Ok(Some(v))
+}
+
+/// Pull a bare `--name` switch out of `args`.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|a| a == name) {
+        Some(i) => { args.remove(i); true }
+        None => false,
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(name: &str, v: Option<String>) -> Result<Option<T>, String> {
+    v.map(|s| s.parse().map_err(|_| format!("{name}: not a number: {s}"))).transpose()
+}
+
+/// Store paths follow the GUI's layout under a store root: `<root>/chunks.db` and `<root>/hnsw`.
+fn build_config(args: &mut Vec<String>) -> Result<ServiceConfig, String> {
+    let mut cfg = ServiceConfig::default();
+    let root = take_opt(args, "--store-root")?.or_else(|| env::var("HYBRID_STORE_ROOT").ok().filter(|r| !r.trim().is_empty()));
+    if let Some(root) = root {
+        let root = PathBuf::from(root.trim());
+        cfg.db_path = root.join("chunks.db");
+        cfg.hnsw_dir = Some(root.join("hnsw"));
+    }
+    if let Some(v) = take_opt(args, "--model")? { cfg.embedder.model_path = PathBuf::from(v); }
+    if let Some(v) = take_opt(args, "--tokenizer")? { cfg.embedder.tokenizer_path = PathBuf::from(v); }
+    if let Some(v) = take_opt(args, "--runtime")? { cfg.embedder.runtime_library_path = PathBuf::from(v); }
+    if let Some(v) = parse_num("--dim", take_opt(args, "--dim")?)? { cfg.embedder.dimension = v; }
+    if let Some(v) = parse_num("--max-tokens", take_opt(args, "--max-tokens")?)? { cfg.embedder.max_input_length = v; }
+    Ok(cfg)
+}
+
+fn positional(args: &[String], what: &str) -> Result<String, String> {
+    match args {
+        [one] if !one.starts_with("--") => Ok(one.clone()),
+        [] => Err(format!("missing {what}")),
+        _ => Err(format!("unexpected arguments: {}", args.join(" "))),
+    }
+}
+
+fn open_service(cfg: ServiceConfig) -> Result<HybridService, String> {
+    HybridService::new(cfg).map_err(|e| format!("service init failed: {e}"))
+}
+
+fn cmd_ingest(cfg: ServiceConfig, mut args: Vec<String>) -> Result<serde_json::Value, String> {
+    let encoding = take_opt(&mut args, "--encoding")?;
+    let doc_id = take_opt(&mut args, "--doc-id")?;
+    let path = positional(&args, "<path>")?;
+    let svc = open_service(cfg)?;
+    let opts = ChunkOptions { encoding, ..Default::default() };
+    svc.ingest_file_with_options(&path, doc_id.as_deref(), &opts, None, None).map_err(|e| format!("ingest {path}: {e}"))?;
+    Ok(json!({ "ingested": [path] }))
+}
+
+fn cmd_ingest_dir(cfg: ServiceConfig, mut args: Vec<String>) -> Result<serde_json::Value, String> {
+    let exts: Vec<String> = take_opt(&mut args, "--ext")?
+        .map(|v| v.split(',').map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase()).filter(|e| !e.is_empty()).collect())
+        .unwrap_or_default();
+    let depth: usize = parse_num("--depth", take_opt(&mut args, "--depth")?)?.unwrap_or(1);
+    let all = take_flag(&mut args, "--all");
+    let dir = positional(&args, "<dir>")?;
+    let svc = open_service(cfg)?;
+    let ext_refs: Vec<&str> = exts.iter().map(String::as_str).collect();
+    let root = Path::new(&dir);
+    let files = if all {
+        hybrid_service::scan_folder_sorted(root, depth, &ext_refs)
+    } else {
+        svc.scan_unregistered(root, depth, &ext_refs).map_err(|e| format!("scan {dir}: {e}"))?
+    };
+    let mut ingested = Vec::new();
+    let mut failed = Vec::new();
+    for f in &files {
+        let path = f.to_string_lossy();
+        match svc.ingest_file(&path, None) {
+            Ok(()) => ingested.push(path.into_owned()),
+            Err(e) => failed.push(json!({ "path": path, "error": e.to_string() })),
+        }
+    }
+    Ok(json!({ "ingested": ingested, "failed": failed }))
+}
+
+fn cmd_search(cfg: ServiceConfig, mut args: Vec<String>) -> Result<serde_json::Value, String> {
+    let mode = take_opt(&mut args, "--mode")?.unwrap_or_else(|| "hybrid".into());
+    let top_k: usize = parse_num("--top-k", take_opt(&mut args, "--top-k")?)?.unwrap_or(10);
+    let doc_id = take_opt(&mut args, "--doc-id-filter")?;
+    let query = positional(&args, "<query>")?;
+    let filters: Vec<FilterClause> = doc_id.into_iter().map(|d| FilterClause { kind: FilterKind::Must, op: FilterOp::DocIdEq(d) }).collect();
+    let svc = open_service(cfg)?;
+    // Same default weights as the GUI (text 1 : vector 4)
+    let hits = match mode.as_str() {
+        "hybrid" => svc.search_hybrid(&query, top_k, &filters, DEFAULT_W_TEXT, DEFAULT_W_VEC),
+        "text" => svc.search_text(&query, top_k, &filters),
+        "vector" => svc.search_vector(&query, top_k, &filters),
+        other => return Err(format!("unknown --mode: {other} (expected hybrid, text or vector)")),
+    }
+    .map_err(|e| format!("search: {e}"))?;
+    serde_json::to_value(hits).map_err(|e| e.to_string())
+}
+
+fn cmd_delete(cfg: ServiceConfig, mut args: Vec<String>) -> Result<serde_json::Value, String> {
+    let doc_id = take_opt(&mut args, "--doc-id")?.ok_or("delete requires --doc-id")?;
+    if !args.is_empty() { return Err(format!("unexpected arguments: {}", args.join(" "))); }
+    let svc = open_service(cfg)?;
+    let filters = [FilterClause { kind: FilterKind::Must, op: FilterOp::DocIdEq(doc_id.clone()) }];
+    let rep = svc.delete_by_filter(&filters, 1000).map_err(|e| format!("delete {doc_id}: {e}"))?;
+    Ok(json!({ "doc_id": doc_id, "deleted_chunks": rep.db_deleted }))
+}
+
+fn cmd_recover(cfg: ServiceConfig, mut args: Vec<String>) -> Result<serde_json::Value, String> {
+    let requeue = take_flag(&mut args, "--requeue");
+    if !args.is_empty() { return Err(format!("unexpected arguments: {}", args.join(" "))); }
+    let svc = open_service(cfg)?;
+    let interrupted = svc.recover().map_err(|e| format!("recover: {e}"))?;
+    let listed: Vec<serde_json::Value> = interrupted
+        .iter()
+        .map(|i| json!({ "path": i.path, "doc_id": i.doc_id, "content_sha256": i.content_sha256, "started_at": i.started_at }))
+        .collect();
+    if !requeue { return Ok(json!({ "interrupted": listed })); }
+    let mut ingested = Vec::new();
+    let mut failed = Vec::new();
+    for i in &interrupted {
+        match svc.ingest_file(&i.path, Some(&i.doc_id)) {
+            Ok(()) => ingested.push(i.path.clone()),
+            Err(e) => failed.push(json!({ "path": i.path, "error": e.to_string() })),
+        }
+    }
+    Ok(json!({ "interrupted": listed, "ingested": ingested, "failed": failed }))
+}
+
+fn run(mut args: Vec<String>) -> Result<serde_json::Value, String> {
+    let cfg = build_config(&mut args)?;
+    if args.is_empty() { return Err("missing subcommand".into()); }
+    let cmd = args.remove(0);
+    match cmd.as_str() {
+        "ingest" => cmd_ingest(cfg, args),
+        "ingest-dir" => cmd_ingest_dir(cfg, args),
+        "search" => cmd_search(cfg, args),
+        "delete" => cmd_delete(cfg, args),
+        "recover" => cmd_recover(cfg, args),
+        other => Err(format!("unknown subcommand: {other}")),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") { print_usage(); return; }
+    match run(args) {
+        Ok(out) => {
+            println!("{}", serde_json::to_string_pretty(&out).unwrap_or_default());
+            // Partial ingest failures are listed in the output but still fail the run
+            if out.get("failed").and_then(|f| f.as_array()).is_some_and(|f| !f.is_empty()) { std::process::exit(1); }
+        }
+        Err(e) => { eprintln!("error: {e}"); std::process::exit(2); }
+    }
+}
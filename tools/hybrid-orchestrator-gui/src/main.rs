@@ -159,6 +159,9 @@ impl AppState {
             embedding_model_id: ONNX_STDIO_DEFAULTS.embedding_model_id.into(),
             text_repr_version: ONNX_STDIO_DEFAULTS.text_repr_version.into(),
             preload_model_to_memory: self.preload_model_to_memory,
+            normalize: false,
+            pooling: Default::default(),
+            worker_count: 1,
         })
     }
 
@@ -211,6 +214,7 @@ impl AppState {
             schema_version: SCHEMA_MAJOR,
             doc_id: doc_id.clone(),
             chunk_id: chunk_id.clone(),
+            seq: None,
             source_uri: "user://input".into(),
             source_mime: "text/plain".into(),
             extracted_at: Utc::now().to_rfc3339(),
@@ -219,6 +223,7 @@ impl AppState {
             text: text.clone(),
             section_path: None,
             meta,
+            block_kinds: Vec::new(),
             extra: std::collections::BTreeMap::new(),
         };
 
@@ -260,7 +265,7 @@ impl AppState {
         let repo = match SqliteRepo::open(db) { Ok(r) => r, Err(e) => { self.status = format!("Open DB failed: {e}"); return; } };
         let _ = repo.maybe_rebuild_fts();
         let fts = Fts5Index::new();
-        let opts = SearchOptions { top_k: self.top_k, fetch_factor: 10 };
+        let opts = SearchOptions { top_k: self.top_k, fetch_factor: 10, ..Default::default() };
 
         // Run all available engines; combine and display separate scores.
         // Always run FTS5. Run vector if HNSW snapshot exists. Run Tantivy if available and initialized.
@@ -273,7 +278,7 @@ impl AppState {
                 if let Ok(qvec) = e.embed(q) {
                     if PathBuf::from(&hdir).join("map.tsv").exists() {
                         if let Ok(h) = HnswIndex::load(&hdir, qvec.len()) {
-                            VectorSearcher::knn_ids(&h, &repo, &qvec, &[], &opts)
+                            VectorSearcher::knn_ids(&h, &repo, &qvec, &[], &opts).unwrap_or_default()
                         } else { Vec::new() }
                     } else { Vec::new() }
                 } else { Vec::new() }
@@ -415,6 +420,7 @@ impl AppState {
                     schema_version: SCHEMA_MAJOR,
                     doc_id: doc_id.clone(),
                     chunk_id: chunk_id.clone(),
+                    seq: None,
                     source_uri: "excel://row".into(),
                     source_mime: "text/plain".into(),
                     extracted_at: chrono::Utc::now().to_rfc3339(),
@@ -423,6 +429,7 @@ impl AppState {
                     text: text.clone(),
                     section_path: None,
                     meta,
+                    block_kinds: Vec::new(),
                     extra: std::collections::BTreeMap::new(),
                 };
                 records.push(rec);
@@ -941,6 +948,7 @@ fn rec_clone_for_tantivy(doc: &DocumentId, cid: &ChunkId, text: &str) -> ChunkRe
         schema_version: SCHEMA_MAJOR,
         doc_id: DocumentId(doc.0.clone()),
         chunk_id: ChunkId(cid.0.clone()),
+        seq: None,
         source_uri: "user://input".into(),
         source_mime: "text/plain".into(),
         extracted_at: chrono::Utc::now().to_rfc3339(),
@@ -949,6 +957,7 @@ fn rec_clone_for_tantivy(doc: &DocumentId, cid: &ChunkId, text: &str) -> ChunkRe
         text: text.to_string(),
         section_path: None,
         meta: BTreeMap::new(),
+        block_kinds: Vec::new(),
         extra: BTreeMap::new(),
     }
 }
@@ -116,6 +116,7 @@ fn do_insert(mut tail: Vec<String>) -> Result<(), String> {
         schema_version: SCHEMA_MAJOR,
         doc_id: doc_id.clone(),
         chunk_id: chunk_id.clone(),
+        seq: None,
         source_uri: "user://input".into(),
         source_mime: "text/plain".into(),
         extracted_at: now_iso(),
@@ -124,6 +125,7 @@ fn do_insert(mut tail: Vec<String>) -> Result<(), String> {
         text: input_text.clone(),
         section_path: None,
         meta,
+        block_kinds: Vec::new(),
         extra: BTreeMap::new(),
     };
 
@@ -180,7 +182,7 @@ fn do_search(mut tail: Vec<String>) -> Result<(), String> {
     let repo = SqliteRepo::open(&db_path).map_err(|e| e.to_string())?;
     let _ = repo.maybe_rebuild_fts();
     let fts = Fts5Index::new();
-    let opts = SearchOptions { top_k: k, fetch_factor: 10 };
+    let opts = SearchOptions { top_k: k, fetch_factor: 10, ..Default::default() };
 
     // Text-only path
     if !do_hybrid {
@@ -206,7 +208,10 @@ fn do_search(mut tail: Vec<String>) -> Result<(), String> {
 
     let mut text_matches = TextSearcher::search_ids(&fts, &repo, &q, &[], &opts);
     let mut vec_matches = if let Some(h) = &maybe_hnsw {
-        VectorSearcher::knn_ids(h, &repo, &qvec, &[], &opts)
+        match VectorSearcher::knn_ids(h, &repo, &qvec, &[], &opts) {
+            Ok(m) => m,
+            Err(e) => { eprintln!("warn: HNSW search: {}", e); Vec::new() }
+        }
     } else { Vec::new() };
 
     // Combine with simple weighted sum (0.5 / 0.5)
@@ -0,0 +1,300 @@
+use std::env;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chunk_model::FileRecord;
+use chunking_store::{FilterClause, SearchHit};
+use hybrid_service::{HybridService, ServiceConfig, ServiceError, DEFAULT_W_TEXT, DEFAULT_W_VEC};
+use serde::{Deserialize, Serialize};
+
+fn print_usage() {
+    eprintln!(
+        "Usage:\n\
+         hybrid-server [--bind ADDR] [--db PATH] [--hnsw DIR] [--read-only] [--ingest-root DIR]\n\
+         \n\
+         Model overrides (optional):\n\
+           --model PATH_ONNX   --tokenizer PATH_JSON   --runtime PATH_DLL   --dim N   --max-tokens N\n\
+         Notes: bind defaults to 127.0.0.1:8080; db defaults to target/demo/chunks.db;\n\
+         POST /ingest/file only accepts paths under --ingest-root and is disabled without it\n"
+    );
+}
+
+/// Shared handler state: one service for every request, plus a gate so only one
+/// ingest writes to the store at a time while searches run concurrently.
+#[derive(Clone)]
+struct AppState {
+    svc: Arc<HybridService>,
+    writes: Arc<Mutex<()>>,
+    /// Canonical directory `/ingest/file` may read from; `None` disables the endpoint.
+    ingest_root: Option<Arc<PathBuf>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SearchMode {
+    #[default]
+    Hybrid,
+    Text,
+    Vector,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchRequest {
+    query: String,
+    #[serde(default = "default_top_k")]
+    top_k: usize,
+    #[serde(default)]
+    mode: SearchMode,
+    #[serde(default)]
+    filters: Vec<FilterClause>,
+    /// Hybrid weights; the defaults match the GUI (text 1 : vector 4).
+    #[serde(default = "default_w_text")]
+    w_text: f32,
+    #[serde(default = "default_w_vec")]
+    w_vec: f32,
+}
+
+fn default_top_k() -> usize { 10 }
+fn default_w_text() -> f32 { DEFAULT_W_TEXT }
+fn default_w_vec() -> f32 { DEFAULT_W_VEC }
+
+#[derive(Debug, Deserialize)]
+struct IngestTextRequest {
+    text: String,
+    doc_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct IngestTextResponse {
+    doc_id: String,
+    chunk_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IngestFileRequest {
+    path: String,
+    doc_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FilesQuery {
+    #[serde(default = "default_files_limit")]
+    limit: usize,
+    #[serde(default)]
+    offset: usize,
+}
+
+fn default_files_limit() -> usize { 100 }
+
+#[derive(Debug, Serialize)]
+struct FilesResponse {
+    files: Vec<FileRecord>,
+    total: u64,
+}
+
+/// Error response: `{"error": "..."}` with a status derived from the service error.
+struct ApiError(StatusCode, String);
+
+impl From<ServiceError> for ApiError {
+    fn from(e: ServiceError) -> Self {
+        let status = match e {
+            ServiceError::ReadOnly => StatusCode::FORBIDDEN,
+            ServiceError::BlobTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            ServiceError::UnsupportedMime(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ServiceError::IndexDimensionMismatch { .. } | ServiceError::EmbedDrift(_) | ServiceError::DuplicateContent { .. } => StatusCode::CONFLICT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Run a blocking service call off the async workers.
+async fn blocking<T, F>(f: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, ServiceError> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(res) => res.map_err(ApiError::from),
+        Err(e) => Err(ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("worker failed: {e}"))),
+    }
+}
+
+async fn search(State(st): State<AppState>, Json(req): Json<SearchRequest>) -> Result<Json<Vec<SearchHit>>, ApiError> {
+    let hits = blocking(move || match req.mode {
+        SearchMode::Hybrid => st.svc.search_hybrid(&req.query, req.top_k, &req.filters, req.w_text, req.w_vec),
+        SearchMode::Text => st.svc.search_text(&req.query, req.top_k, &req.filters),
+        SearchMode::Vector => st.svc.search_vector(&req.query, req.top_k, &req.filters),
+    })
+    .await?;
+    Ok(Json(hits))
+}
+
+async fn ingest_text(State(st): State<AppState>, Json(req): Json<IngestTextRequest>) -> Result<Json<IngestTextResponse>, ApiError> {
+    let (doc_id, chunk_id) = blocking(move || {
+        let _write = st.writes.lock().unwrap_or_else(|p| p.into_inner());
+        st.svc.ingest_text(&req.text, req.doc_id.as_deref())
+    })
+    .await?;
+    Ok(Json(IngestTextResponse { doc_id: doc_id.0, chunk_id: chunk_id.0 }))
+}
+
+/// Resolve a requested path (relative ones against `root`) to its canonical form, rejecting
+/// anything that lands outside `root` once `..` and symlinks are followed.
+fn resolve_ingest_path(root: &Path, requested: &str) -> Result<PathBuf, ApiError> {
+    let path = root.join(requested).canonicalize().map_err(|e| ApiError(StatusCode::NOT_FOUND, format!("{requested}: {e}")))?;
+    if !path.starts_with(root) {
+        return Err(ApiError(StatusCode::FORBIDDEN, format!("{requested} is outside the ingest root")));
+    }
+    Ok(path)
+}
+
+async fn ingest_file(State(st): State<AppState>, Json(req): Json<IngestFileRequest>) -> Result<StatusCode, ApiError> {
+    let Some(root) = st.ingest_root.clone() else {
+        return Err(ApiError(StatusCode::FORBIDDEN, "file ingest is disabled; start the server with --ingest-root".into()));
+    };
+    let path = resolve_ingest_path(&root, &req.path)?;
+    blocking(move || {
+        let _write = st.writes.lock().unwrap_or_else(|p| p.into_inner());
+        st.svc.ingest_file(&path.to_string_lossy(), req.doc_id.as_deref())
+    })
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_files(State(st): State<AppState>, Query(q): Query<FilesQuery>) -> Result<Json<FilesResponse>, ApiError> {
+    let (files, total) = blocking(move || st.svc.list_files_page(q.limit, q.offset)).await?;
+    Ok(Json(FilesResponse { files, total }))
+}
+
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/search", post(search))
+        .route("/ingest/text", post(ingest_text))
+        .route("/ingest/file", post(ingest_file))
+        .route("/files", get(list_files))
+        .with_state(state)
+}
+
+struct Args {
+    bind: SocketAddr,
+    cfg: ServiceConfig,
+    ingest_root: Option<PathBuf>,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut bind: SocketAddr = "127.0.0.1:8080".parse().expect("valid default address");
+    let mut cfg = ServiceConfig::default();
+    let mut ingest_root = None;
+    let mut i = 0;
+    while i < args.len() {
+        let value = || args.get(i + 1).ok_or_else(|| format!("{} requires a value", args[i]));
+        match args[i].as_str() {
+            "--bind" => { bind = value()?.parse().map_err(|e| format!("--bind: {e}"))?; i += 2; }
+            "--db" => { cfg.db_path = PathBuf::from(value()?); i += 2; }
+            "--hnsw" => { cfg.hnsw_dir = Some(PathBuf::from(value()?)); i += 2; }
+            "--read-only" => { cfg.read_only = true; i += 1; }
+            "--ingest-root" => {
+                let dir = PathBuf::from(value()?);
+                ingest_root = Some(dir.canonicalize().map_err(|e| format!("--ingest-root {}: {e}", dir.display()))?);
+                i += 2;
+            }
+            "--model" => { cfg.embedder.model_path = PathBuf::from(value()?); i += 2; }
+            "--tokenizer" => { cfg.embedder.tokenizer_path = PathBuf::from(value()?); i += 2; }
+            "--runtime" => { cfg.embedder.runtime_library_path = PathBuf::from(value()?); i += 2; }
+            "--dim" => { cfg.embedder.dimension = value()?.parse().map_err(|e| format!("--dim: {e}"))?; i += 2; }
+            "--max-tokens" => { cfg.embedder.max_input_length = value()?.parse().map_err(|e| format!("--max-tokens: {e}"))?; i += 2; }
+            other => return Err(format!("unknown argument: {other}")),
+        }
+    }
+    Ok(Args { bind, cfg, ingest_root })
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") { print_usage(); return; }
+    let Args { bind, cfg, ingest_root } = match parse_args(&args) {
+        Ok(v) => v,
+        Err(e) => { eprintln!("error: {e}"); print_usage(); std::process::exit(2); }
+    };
+    let svc = match tokio::task::spawn_blocking(move || HybridService::new(cfg)).await {
+        Ok(Ok(svc)) => svc,
+        Ok(Err(e)) => { eprintln!("error: service init failed: {e}"); std::process::exit(1); }
+        Err(e) => { eprintln!("error: service init panicked: {e}"); std::process::exit(1); }
+    };
+    let state = AppState { svc: Arc::new(svc), writes: Arc::new(Mutex::new(())), ingest_root: ingest_root.map(Arc::new) };
+    let listener = match tokio::net::TcpListener::bind(bind).await {
+        Ok(l) => l,
+        Err(e) => { eprintln!("error: bind {bind}: {e}"); std::process::exit(1); }
+    };
+    eprintln!("hybrid-server listening on http://{bind}");
+    let shutdown = async { let _ = tokio::signal::ctrl_c().await; };
+    if let Err(e) = axum::serve(listener, router(state)).with_graceful_shutdown(shutdown).await {
+        eprintln!("error: server: {e}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn state_at(dir: &Path, ingest_root: Option<&Path>) -> AppState {
+        let cfg = ServiceConfig { db_path: dir.join("chunks.db"), aggressive_warmup: false, ..ServiceConfig::default() };
+        let svc = HybridService::new(cfg).expect("service initializes with the default model");
+        let ingest_root = ingest_root.map(|r| Arc::new(r.canonicalize().expect("canonical root")));
+        AppState { svc: Arc::new(svc), writes: Arc::new(Mutex::new(())), ingest_root }
+    }
+
+    async fn post_ingest_file(state: &AppState, path: &str) -> StatusCode {
+        let body = serde_json::json!({ "path": path }).to_string();
+        let req = Request::post("/ingest/file").header("content-type", "application/json").body(Body::from(body)).expect("request");
+        router(state.clone()).oneshot(req).await.expect("response").status()
+    }
+
+    #[tokio::test]
+    async fn ingest_file_only_reads_under_the_ingest_root() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let root = dir.path().join("shared");
+        std::fs::create_dir_all(&root).expect("create root");
+        std::fs::write(root.join("inside.txt"), "Tide tables for the northern harbour.").expect("write inside");
+        let outside = dir.path().join("outside.txt");
+        std::fs::write(&outside, "Private notes that must stay off the server.").expect("write outside");
+        let state = state_at(dir.path(), Some(&root));
+
+        assert_eq!(post_ingest_file(&state, &outside.to_string_lossy()).await, StatusCode::FORBIDDEN);
+        assert_eq!(post_ingest_file(&state, "../outside.txt").await, StatusCode::FORBIDDEN);
+        assert_eq!(post_ingest_file(&state, "missing.txt").await, StatusCode::NOT_FOUND);
+        assert_eq!(state.svc.list_files_page(10, 0).expect("list files").1, 0);
+
+        assert_eq!(post_ingest_file(&state, "inside.txt").await, StatusCode::NO_CONTENT);
+        let abs = root.join("inside.txt");
+        assert_eq!(post_ingest_file(&state, &abs.to_string_lossy()).await, StatusCode::NO_CONTENT);
+        assert_eq!(state.svc.list_files_page(10, 0).expect("list files").1, 1);
+    }
+
+    #[tokio::test]
+    async fn ingest_file_is_disabled_without_an_ingest_root() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let file = dir.path().join("note.txt");
+        std::fs::write(&file, "Some text.").expect("write file");
+        let state = state_at(dir.path(), None);
+        assert_eq!(post_ingest_file(&state, &file.to_string_lossy()).await, StatusCode::FORBIDDEN);
+    }
+}
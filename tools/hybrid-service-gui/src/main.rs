@@ -23,7 +23,8 @@ use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
 // use rayon::prelude::*; // no parallel iterators in this module currently
 
-use hybrid_service::{HybridService, ServiceConfig, CancelToken, ProgressEvent, HnswState};
+use hybrid_service::{HybridService, ServiceConfig, CancelToken, ChunkOptions, ProgressEvent, HnswState};
+use hybrid_service::prompt_builder::{self, PromptItem, PromptOptions, PromptTemplate};
 use embedding_provider::config::ONNX_STDIO_DEFAULTS;
 use chunking_store::{FilterClause, FilterKind, FilterOp};
 use chunking_store::ChunkStoreRead;
@@ -31,6 +32,7 @@ use chunking_store::ChunkStoreRead;
 #[cfg(feature = "tantivy")]
 use chunking_store::tantivy_index::TantivyIndex;
 use chunk_model::{ChunkId, DocumentId, ChunkRecord, FileRecord};
+use file_chunker::text_segmenter::overlap_prefix_len;
 
 fn main() -> eframe::Result<()> {
     let options = NativeOptions::default();
@@ -88,6 +90,8 @@ enum FilesSortKey {
 enum UiProgressEvent {
     FileStart { index: usize, total: usize, path: String },
     Service(ProgressEvent),
+    /// A job that has no terminal service event of its own failed
+    Failed(String),
 }
 
 #[derive(Debug, Clone)]
@@ -125,6 +129,8 @@ struct HitRow {
     tv_and: Option<f32>,
     tv_or: Option<f32>,
     vec: Option<f32>,
+    /// Ranking key the row was sorted by (mode-dependent).
+    score: f32,
 }
 
 struct AppState {
@@ -168,6 +174,8 @@ struct AppState {
     ingest_depth: usize,
     ingest_files: Vec<IngestFileItem>,
     ingest_only_unregistered: bool,
+    // Scan folders in sorted path order for reproducible ingestion
+    ingest_sorted: bool,
     // Insert Files UI: show absolute paths instead of relative to chosen folder
     ingest_show_abs_paths: bool,
     // Insert Files UI: sorting state
@@ -179,6 +187,7 @@ struct AppState {
     chunk_max: String,
     chunk_cap: String,
     chunk_merge_min: String,
+    chunk_overlap: String,
     chunk_penalize_short_line: bool,
     chunk_penalize_page_no_nl: bool,
 
@@ -218,6 +227,7 @@ struct AppState {
     prompt_items_count: usize,
     prompt_prev: usize,
     prompt_next: usize,
+    prompt_strict_json: bool,
     // Multiple templates support
     prompt_templates: Vec<PromptTemplate>,
     selected_prompt: Option<String>,
@@ -282,6 +292,7 @@ struct AppState {
     files_loading: bool,
     files_page: usize,
     files_page_size: usize,
+    files_total: u64,
     files_selected_doc: Option<String>,
     files_selected_display: String,
     files_selected_detail: String,
@@ -295,7 +306,7 @@ struct AppState {
     // Preserve default order across toggles
     files_default_ord: std::collections::HashMap<String, usize>,
     // Files tab: async loader channel
-    files_rx: Option<Receiver<Result<Vec<FileRecord>, String>>>,
+    files_rx: Option<Receiver<Result<(Vec<FileRecord>, u64), String>>>,
     // Files tab: async delete selected
     files_del_rx: Option<Receiver<DeleteEvent>>,
     files_del_cancel: Option<CancelToken>,
@@ -324,6 +335,8 @@ struct IngestFileItem {
     ordinal: usize,
     // Optional per-file encoding override; None means use global setting
     encoding: Option<String>,
+    // Optional per-file MIME override; None means infer from extension
+    mime: Option<String>,
     // Cached preview state for quick mojibake check in the table
     preview_cached_enc: Option<String>,
     preview_cached_text: Option<String>,
@@ -364,6 +377,8 @@ struct ChunkCfg {
     chunk_penalize_page_no_nl: bool,
     #[serde(default)]
     short_merge_min: Option<usize>,
+    #[serde(default)]
+    overlap_chars: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -391,17 +406,6 @@ struct PromptCfg {
     selected: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct PromptTemplate {
-    name: String,
-    header: String,
-    item: String,
-    footer: String,
-    items: usize,
-    prev: usize,
-    next: usize,
-}
-
 // Backward-compatible (flat) config format used previously
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct HybridGuiConfigV1 {
@@ -515,8 +519,8 @@ impl AppState {
                 if self.files_page > 0 { self.files_page -= 1; self.refresh_files(); }
             }
             if ui.add(Button::new("Next")).clicked() {
-                // Optimistic paging; disable if last fetch was shorter than page size
-                if self.files.len() >= self.files_page_size { self.files_page += 1; self.refresh_files(); }
+                let next_offset = (self.files_page + 1).saturating_mul(self.files_page_size) as u64;
+                if next_offset < self.files_total { self.files_page += 1; self.refresh_files(); }
             }
             if self.files_loading { ui.add(Spinner::new()); }
             // Bulk delete selected
@@ -759,7 +763,15 @@ impl AppState {
         if let Some(svc) = &self.svc {
             let svc = Arc::clone(svc);
             std::thread::spawn(move || {
-                let res = svc.list_files(limit, offset).map_err(|e| e.to_string());
+                // Live chunk counts rather than the count stored at ingest time
+                let res = svc
+                    .list_documents(limit, offset)
+                    .and_then(|docs| Ok((docs, svc.count_files(&[])?)))
+                    .map(|(docs, total)| {
+                        let files = docs.into_iter().map(|d| FileRecord { chunk_count: Some(d.live_chunks as u32), ..d.file }).collect();
+                        (files, total)
+                    })
+                    .map_err(|e| e.to_string());
                 let _ = tx.send(res);
             });
         }
@@ -768,14 +780,16 @@ impl AppState {
     fn poll_files_task(&mut self) {
         if let Some(rx) = &self.files_rx {
             match rx.try_recv() {
-                Ok(Ok(list)) => {
+                Ok(Ok((list, total))) => {
                     self.files = list;
+                    self.files_total = total;
                     self.files_default_ord.clear();
                     for (i, rec) in self.files.iter().enumerate() {
                         self.files_default_ord.insert(rec.doc_id.0.clone(), i);
                     }
                     self.apply_files_sort();
-                    self.status = format!("Loaded files page {} ({} items)", self.files_page + 1, self.files.len());
+                    let pages = self.files_total.div_ceil(self.files_page_size.max(1) as u64).max(1);
+                    self.status = format!("Loaded files page {}/{} ({} of {} files)", self.files_page + 1, pages, self.files.len(), self.files_total);
                     self.files_loading = false;
                     self.files_rx = None;
                 }
@@ -926,6 +940,7 @@ impl AppState {
                 ui.label("max"); ui.add(TextEdit::singleline(&mut self.chunk_max).desired_width(60.0));
                 ui.label("cap"); ui.add(TextEdit::singleline(&mut self.chunk_cap).desired_width(60.0));
                 ui.label("merge<="); ui.add(TextEdit::singleline(&mut self.chunk_merge_min).desired_width(60.0));
+                ui.label("overlap"); ui.add(TextEdit::singleline(&mut self.chunk_overlap).desired_width(60.0));
             });
             ui.horizontal(|ui| {
                 ui.checkbox(&mut self.chunk_penalize_short_line, "Penalize after short line");
@@ -944,6 +959,15 @@ impl AppState {
             ui.label(egui::RichText::new(msg).color(ui.visuals().warn_fg_color));
             ui.horizontal(|ui| {
                 ui.label("Dim"); ui.add(TextEdit::singleline(&mut self.embedding_dimension).desired_width(80.0));
+                let detect = ui.add_enabled(self.svc.is_some(), Button::new("Detect")).on_hover_text("Set Dim to what the loaded model actually outputs");
+                if detect.clicked() {
+                    if let Some(svc) = self.svc.as_ref() {
+                        match svc.detected_embedding_dimension() {
+                            Ok(d) => { self.embedding_dimension = d.to_string(); self.status = format!("Model outputs {d} dimensions; re-init to apply"); }
+                            Err(e) => { self.status = format!("Detect dimension failed: {e}"); }
+                        }
+                    }
+                }
                 ui.label("MaxTokens"); ui.add(TextEdit::singleline(&mut self.max_tokens).desired_width(80.0));
                 ui.label("Batch"); ui.add(TextEdit::singleline(&mut self.embed_batch_size).desired_width(60.0));
             });
@@ -976,6 +1000,7 @@ impl AppState {
                 chunk_penalize_short_line: self.chunk_penalize_short_line,
                 chunk_penalize_page_no_nl: self.chunk_penalize_page_no_nl,
                 short_merge_min: Some(self.chunk_merge_min.trim().parse().unwrap_or(100)),
+                overlap_chars: Some(self.chunk_overlap.trim().parse().unwrap_or(0)),
             },
             model: ModelCfg {
                 model_path: self.model_path.trim().to_string(),
@@ -1014,6 +1039,7 @@ impl AppState {
         self.chunk_penalize_short_line = cfg.chunk.chunk_penalize_short_line;
         self.chunk_penalize_page_no_nl = cfg.chunk.chunk_penalize_page_no_nl;
         self.chunk_merge_min = cfg.chunk.short_merge_min.unwrap_or(100).to_string();
+        self.chunk_overlap = cfg.chunk.overlap_chars.unwrap_or(0).to_string();
         // Model
         self.model_path = cfg.model.model_path;
         self.tokenizer_path = cfg.model.tokenizer_path;
@@ -1053,6 +1079,7 @@ impl AppState {
         self.chunk_penalize_short_line = cfg.chunk_penalize_short_line;
         self.chunk_penalize_page_no_nl = cfg.chunk_penalize_page_no_nl;
         self.chunk_merge_min = "100".into();
+        self.chunk_overlap = "0".into();
         // Model
         self.model_path = cfg.model_path;
         self.tokenizer_path = cfg.tokenizer_path;
@@ -1342,6 +1369,7 @@ impl AppState {
             ingest_depth: 1,
             ingest_files: Vec::new(),
             ingest_only_unregistered: true,
+            ingest_sorted: false,
             ingest_show_abs_paths: false,
             ingest_sort_key: IngestSortKey::Default,
             ingest_sort_asc: true,
@@ -1350,6 +1378,7 @@ impl AppState {
             chunk_max: String::from("600"),
             chunk_cap: String::from("800"),
             chunk_merge_min: String::from("100"),
+            chunk_overlap: String::from("0"),
             chunk_penalize_short_line: true,
             chunk_penalize_page_no_nl: true,
 
@@ -1387,6 +1416,7 @@ impl AppState {
             prompt_items_count: 5,
             prompt_prev: 1,
             prompt_next: 1,
+            prompt_strict_json: false,
             prompt_templates: Vec::new(),
             selected_prompt: None,
             prompt_name_edit: String::new(),
@@ -1437,6 +1467,7 @@ impl AppState {
             files_loading: false,
             files_page: 0,
             files_page_size: 20,
+            files_total: 0,
             files_selected_doc: None,
             files_selected_display: String::new(),
             files_selected_detail: String::new(),
@@ -1712,7 +1743,7 @@ impl App for AppState {
                         if self.svc_task.is_some() { ("loading", "absent") } else { ("released", "absent") }
                     } else {
                         let st = self.svc.as_ref().map(|s| s.hnsw_state()).unwrap_or(HnswState::Error);
-                        let idx = match st { HnswState::Absent => "absent", HnswState::Loading => "loading", HnswState::Ready => "ready", HnswState::Error => "error" };
+                        let idx = match st { HnswState::Absent => "absent", HnswState::Loading => "loading", HnswState::Ready => "ready", HnswState::Error => "error", HnswState::DimensionMismatch { .. } => "mismatch" };
                         ("ready", idx)
                     };
                     let dll_status = if self.ort_runtime_committed.is_some() { "fixed" } else { "editable" };
@@ -1722,7 +1753,7 @@ impl App for AppState {
                     let err = egui::Color32::RED;
                     let model_color = match model_status { "ready" => ok, "loading" | "released" => warn, _ => err };
                     let dll_color = match dll_status { "fixed" => ok, "editable" => warn, _ => warn };
-                    let index_color = match index_status { "ready" => ok, "loading" | "absent" => warn, "error" => err, _ => warn };
+                    let index_color = match index_status { "ready" => ok, "loading" | "absent" => warn, "error" | "mismatch" => err, _ => warn };
             ui.horizontal(|ui| {
                 ui.label(egui::RichText::new(format!("Model: {}", model_status)).color(model_color));
                 ui.label(", ");
@@ -1734,6 +1765,13 @@ impl App for AppState {
                     else { index_status };
                 let idx_color = if self.svc_task.is_some() || self.store_paths_stale { ui.visuals().warn_fg_color } else { index_color };
                 ui.label(egui::RichText::new(format!("Vector (HNSW): {}", idx_disp)).color(idx_color));
+                // A snapshot built for another dimension stays unloaded until it is rebuilt
+                if let Some(HnswState::DimensionMismatch { expected, found }) = self.svc.as_ref().map(|s| s.hnsw_state()) {
+                    ui.label(egui::RichText::new(format!("(index dim {found}, model dim {expected})")).color(err));
+                    if ui.add_enabled(!self.ingest_running, Button::new("Reindex")).on_hover_text("Re-embed every chunk with the current model and rebuild the indexes").clicked() {
+                        self.do_reindex_all();
+                    }
+                }
                 // Show Tantivy (text index) status separately when available
                 #[cfg(feature = "tantivy")]
                 {
@@ -1908,7 +1946,10 @@ impl AppState {
                             ui.add(DragValue::new(&mut self.ingest_depth).clamp_range(0..=16));
                         });
                         // Row 3: Scan buttons under Extensions
-                        ui.horizontal(|ui| { ui.checkbox(&mut self.ingest_only_unregistered, "Check unregistered files"); });
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.ingest_only_unregistered, "Check unregistered files");
+                            ui.checkbox(&mut self.ingest_sorted, "Sort by path");
+                        });
                         ui.horizontal(|ui| {
                             if ui.button("Scan").clicked() {
                                 self.scan_ingest_folder_async();
@@ -1983,6 +2024,7 @@ impl AppState {
                                         .column(Column::initial(72.0))    // Size (0.6x)
                                         .column(Column::initial(80.0))    // Date (yyyy/mm/dd, 0.8x)
                                         .column(Column::initial(112.0))   // Encoding override (0.8x)
+                                        .column(Column::initial(112.0))   // Type (MIME) override
                                         .column(Column::initial(220.0));  // Preview (first chars)
 
                                     table
@@ -2024,6 +2066,7 @@ impl AppState {
                                             }
                                         });
                                         header.col(|ui| { ui.label("Encoding"); });
+                                        header.col(|ui| { ui.label("Type"); });
                                         header.col(|ui| {
                                             let active = matches!(self.ingest_sort_key, IngestSortKey::Preview);
                                             let arrow = if active { if self.ingest_sort_asc { " ▲" } else { " ▼" } } else { "" };
@@ -2057,6 +2100,25 @@ impl AppState {
                                                         });
                                                     if sel == "(global)" { it.encoding = None; } else { it.encoding = Some(sel); }
                                                 });
+                                                row.col(|ui| {
+                                                    // Force a reader regardless of extension (e.g., extensionless PDFs)
+                                                    let types = [
+                                                        ("(auto)", "(auto)"),
+                                                        ("pdf", "application/pdf"),
+                                                        ("text", "text/plain"),
+                                                        ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+                                                        ("pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation"),
+                                                        ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+                                                    ];
+                                                    let mut sel = it.mime.clone().unwrap_or_else(|| String::from("(auto)"));
+                                                    let label = types.iter().find(|(_, m)| *m == sel).map(|(l, _)| *l).unwrap_or("(auto)");
+                                                    ComboBox::from_id_source(format!("mime_{}", it.path))
+                                                        .selected_text(label)
+                                                        .show_ui(ui, |ui| {
+                                                            for (l, m) in types { ui.selectable_value(&mut sel, m.to_string(), l); }
+                                                        });
+                                                    if sel == "(auto)" { it.mime = None; } else { it.mime = Some(sel); }
+                                                });
                                                 row.col(|ui| {
                                                     // Show a short preview for text-like files with the effective encoding
                                                     let lower = it.path.to_ascii_lowercase();
@@ -2130,6 +2192,7 @@ impl AppState {
         if let Ok(v) = self.chunk_max.trim().parse::<usize>() { if v > 0 { params.max_chars = v; } }
         if let Ok(v) = self.chunk_cap.trim().parse::<usize>() { if v > 0 { params.cap_chars = v; } }
         if let Ok(v) = self.chunk_merge_min.trim().parse::<usize>() { params.short_merge_min_chars = v; }
+        if let Ok(v) = self.chunk_overlap.trim().parse::<usize>() { params.overlap_chars = v; }
         params.penalize_short_line = self.chunk_penalize_short_line;
         params.penalize_page_boundary_no_newline = self.chunk_penalize_page_no_nl;
 
@@ -2203,7 +2266,9 @@ impl AppState {
                                 .auto_shrink([false, false])
                                 .show(ui, |ui| {
                                 for (i, c) in self.preview_chunks.iter().enumerate() {
-                                    let preview = truncate_for_preview(&c.text, 80);
+                                    let overlap = i.checked_sub(1).map_or(0, |p| overlap_prefix_len(&self.preview_chunks[p].text, &c.text));
+                                    let preview = truncate_for_preview(&c.text[overlap..], 80);
+                                    let preview = if overlap > 0 { format!("[+{}] {}", c.text[..overlap].trim_end().chars().count(), preview) } else { preview };
                                     let page_label = match (c.page_start, c.page_end) {
                                         (Some(s), Some(e)) if s == e => format!("{}", s),
                                         (Some(s), Some(e)) => format!("{}-{}", s, e),
@@ -2242,9 +2307,31 @@ impl AppState {
                                         ui.monospace(format!("len={} bytes  |  {}", c.text.len(), page_label));
                                     }
                                     ui.separator();
+                                    // Text repeated from the previous chunk (overlap) is shown dimmed and highlighted
+                                    let overlap = i.checked_sub(1).and_then(|p| self.preview_chunks.get(p)).map_or(0, |prev| overlap_prefix_len(&prev.text, &c.text));
                                     egui::ScrollArea::vertical()
                                         .id_source("preview_selected_text")
-                                        .show(ui, |ui| { ui.monospace(text); });
+                                        .show(ui, |ui| {
+                                            if overlap == 0 { ui.monospace(text); return; }
+                                            let esc = |t: &str| if self.preview_show_tab_escape { escape_tabs(t) } else { t.to_string() };
+                                            let mut job = egui::text::LayoutJob::default();
+                                            job.wrap.max_width = ui.available_width();
+                                            let overlap_fmt = egui::text::TextFormat {
+                                                font_id: egui::FontId::monospace(12.0),
+                                                color: ui.visuals().weak_text_color(),
+                                                background: ui.visuals().faint_bg_color,
+                                                italics: true,
+                                                ..Default::default()
+                                            };
+                                            job.append(&esc(&c.text[..overlap]), 0.0, overlap_fmt);
+                                            let body_fmt = egui::text::TextFormat {
+                                                font_id: egui::FontId::monospace(12.0),
+                                                color: ui.visuals().text_color(),
+                                                ..Default::default()
+                                            };
+                                            job.append(&esc(&c.text[overlap..]), 0.0, body_fmt);
+                                            ui.add(egui::Label::new(egui::WidgetText::LayoutJob(job)));
+                                        });
 
                                     // Metadata and JSON detail sections removed for a simpler view
                                 }}
@@ -2262,14 +2349,7 @@ impl AppState {
         }
     }
 
-    fn is_text_like_path(&self, path: &str) -> bool {
-        let lower = path.to_ascii_lowercase();
-        let exts = [
-            ".txt", ".md", ".markdown", ".csv", ".tsv", ".log", ".json", ".yaml", ".yml",
-            ".ini", ".toml", ".cfg", ".conf", ".rst", ".tex", ".srt", ".properties",
-        ];
-        exts.iter().any(|e| lower.ends_with(e))
-    }
+    fn is_text_like_path(&self, path: &str) -> bool { is_text_like_file(path) }
 
     fn refresh_ingest_preview(&mut self) {
         use std::fs;
@@ -2509,6 +2589,29 @@ impl AppState {
         self.status = "Inserting text…".into();
     }
 
+    fn do_reindex_all(&mut self) {
+        if !self.ensure_store_paths_current() { return; }
+        let Some(svc) = self.svc.as_ref() else { self.status = "Model not initialized".into(); return; };
+        let svc = Arc::clone(svc);
+        let (tx, rx) = mpsc::channel::<UiProgressEvent>();
+        let cancel = CancelToken::new();
+        self.ingest_rx = Some(rx);
+        self.ingest_cancel = Some(cancel.clone());
+        self.ingest_running = true;
+        self.ingest_done = 0;
+        self.ingest_total = 0;
+        self.ingest_last_batch = 0;
+        self.ingest_started = Some(Instant::now());
+        self.status = "Reindexing...".into();
+        std::thread::spawn(move || {
+            let tx2 = tx.clone(); let cb: Box<dyn FnMut(ProgressEvent) + Send> = Box::new(move |ev: ProgressEvent| { let _ = tx2.send(UiProgressEvent::Service(ev)); });
+            if let Err(e) = svc.reindex_all(true, Some(&cancel), Some(cb)) {
+                // Cancellation is reported through the progress callback
+                if !cancel.is_canceled() { let _ = tx.send(UiProgressEvent::Failed(e.to_string())); }
+            }
+        });
+    }
+
     fn do_ingest_file(&mut self) {
         // Auto-apply Store Root before ingesting
         if !self.ensure_store_paths_current() { return; }
@@ -2536,16 +2639,30 @@ impl AppState {
         let ps = self.chunk_penalize_short_line;
         let pp = self.chunk_penalize_page_no_nl;
         let merge_min = self.chunk_merge_min.trim().parse().unwrap_or(100);
+        let overlap = self.chunk_overlap.trim().parse().unwrap_or(0);
         std::thread::spawn(move || {
             let hint = doc_hint_opt.as_deref();
             let tx2 = tx.clone(); let cb: Box<dyn FnMut(ProgressEvent) + Send> = Box::new(move |ev: ProgressEvent| { let _ = tx2.send(UiProgressEvent::Service(ev)); });
             let _ = tx.send(UiProgressEvent::FileStart { index: 1, total: 1, path: path_owned.clone() });
-            let _ = svc.ingest_file_with_progress_custom(
-                &path_owned, hint,
-                enc_opt.as_deref(),
-                min, max, cap, merge_min, ps, pp,
-                Some(&cancel), Some(cb)
-            );
+            let opts = ChunkOptions {
+                encoding: enc_opt,
+                params: Some(file_chunker::text_segmenter::TextChunkParams {
+                    min_chars: min,
+                    max_chars: max,
+                    cap_chars: cap,
+                    penalize_short_line: ps,
+                    penalize_page_boundary_no_newline: pp,
+                    short_merge_min_chars: merge_min,
+                    keep_code_blocks: true,
+                    overlap_chars: overlap,
+                    token_counter: None,
+                }),
+                force_mime: None,
+                text_postprocess: None,
+                infer_headings: None,
+                id_scheme: Default::default(),
+            };
+            let _ = svc.ingest_file_with_options(&path_owned, hint, &opts, Some(&cancel), Some(cb));
             // The service emits Finished/Canceled; no-op here.
         });
     }
@@ -2564,8 +2681,8 @@ impl AppState {
             .map(|s| s.trim().trim_start_matches('.').to_ascii_lowercase())
             .filter(|s| !s.is_empty())
             .collect();
-        let use_all = filters.is_empty() || filters.iter().any(|f| f == "*");
         let only_unreg = self.ingest_only_unregistered;
+        let sorted = self.ingest_sorted;
         // Capture current global encoding for preview prefetch (may be "auto")
         let enc_global = self.ingest_encoding.trim().to_string();
         let cancel = CancelToken::new();
@@ -2575,131 +2692,66 @@ impl AppState {
         self.ingest_scanning = true;
         let svc_opt = self.svc.as_ref().map(Arc::clone);
         std::thread::spawn(move || {
-            // Optional: build known sets
-            use std::collections::HashSet;
-            let mut known: HashSet<String> = HashSet::new();
-            let mut known_sizes: HashSet<u64> = HashSet::new();
+            // Optional: registered content (hashes + sizes) for unregistered detection
+            let mut known = hybrid_service::KnownFiles::default();
             if only_unreg {
                 if let Some(svc) = &svc_opt {
-                    let limit: usize = 1000; let mut offset: usize = 0;
-                    loop {
-                        if cancel.is_canceled() { let _ = tx.send(ScanEvent::Canceled); return; }
-                        match svc.list_files(limit, offset) {
-                            Ok(list) => {
-                                if list.is_empty() { break; }
-                                for rec in &list {
-                                    if let Some(h) = rec.content_sha256.clone() { known.insert(h); }
-                                    if let Some(sz) = rec.file_size_bytes { known_sizes.insert(sz); }
-                                }
-                                if list.len() < limit { break; }
-                                offset += limit;
-                            }
-                            Err(e) => { let _ = tx.send(ScanEvent::Error(format!("Fetch known hashes failed: {e}"))); return; }
-                        }
+                    match svc.known_files(Some(&cancel)) {
+                        Ok(k) => known = k,
+                        Err(_) if cancel.is_canceled() => { let _ = tx.send(ScanEvent::Canceled); return; }
+                        Err(e) => { let _ = tx.send(ScanEvent::Error(format!("Fetch known hashes failed: {e}"))); return; }
                     }
                 } else { let _ = tx.send(ScanEvent::Error("Model not initialized".into())); return; }
             }
-            // DFS walk
-            let mut stack: Vec<(std::path::PathBuf, usize)> = vec![(std::path::PathBuf::from(&root), 0)];
+            // Walk (depth-limited, extension-filtered); registered-size files are queued for hashing
+            let ext_refs: Vec<&str> = filters.iter().map(String::as_str).collect();
             let mut scanned: usize = 0; let mut kept: usize = 0; let mut seq: usize = 0;
             let mut batch: Vec<IngestFileItem> = Vec::new();
-            let mut needs_hash: Vec<(String, u64, Option<String>, usize)> = Vec::new();
-            while let Some((dir, depth)) = stack.pop() {
-                if cancel.is_canceled() { let _ = tx.send(ScanEvent::Canceled); return; }
-                let Ok(rd) = std::fs::read_dir(&dir) else { continue };
-                for entry in rd.flatten() {
-                    if cancel.is_canceled() { let _ = tx.send(ScanEvent::Canceled); return; }
-                    let path = entry.path();
-                    if let Ok(meta) = entry.metadata() {
-                        if meta.is_dir() {
-                            if depth < max_depth { stack.push((path, depth + 1)); }
-                        } else if meta.is_file() {
-                            let pstr = path.display().to_string();
-                            let lower = pstr.to_ascii_lowercase();
-                            let matched = if use_all { true } else {
-                                let mut ok = false;
-                                for f in &filters { if lower.ends_with(&format!(".{}", f)) { ok = true; break; } }
-                                ok
-                            };
-                            if !matched { continue; }
-                            scanned += 1;
-                            // Heuristic: text-like preview prefetch (same logic as UI is_text_like_path)
-                            let is_text_like = {
-                                let exts = [
-                                    ".txt", ".md", ".markdown", ".csv", ".tsv", ".log", ".json", ".yaml", ".yml",
-                                    ".ini", ".toml", ".cfg", ".conf", ".rst", ".tex", ".srt", ".properties",
-                                ];
-                                exts.iter().any(|e| lower.ends_with(e))
-                            };
-                            if only_unreg {
-                                let fsz = meta.len();
-                                if !known_sizes.contains(&fsz) {
-                                    let mdy = meta.modified().ok().map(|st| format_ymd(st));
-                                    // Prefetch preview for text-like files using global encoding
-                                    let (pv_enc, pv_text) = if is_text_like {
-                                        (Some(enc_global.clone()), preview_text_for_file(&pstr, &enc_global, 4096, 48))
-                                    } else { (None, None) };
-                                    batch.push(IngestFileItem {
-                                        include: true,
-                                        path: pstr,
-                                        size: fsz,
-                                        ordinal: seq,
-                                        encoding: None,
-                                        preview_cached_enc: pv_enc,
-                                        preview_cached_text: pv_text,
-                                        modified_ymd: mdy,
-                                    });
-                                    seq += 1; kept += 1;
-                                } else {
-                                    let mdy = meta.modified().ok().map(|st| format_ymd(st));
-                                    needs_hash.push((pstr, fsz, mdy, seq));
-                                    seq += 1;
-                                }
-                            } else {
-                                let mdy = meta.modified().ok().map(|st| format_ymd(st));
-                                let (pv_enc, pv_text) = if is_text_like {
-                                    (Some(enc_global.clone()), preview_text_for_file(&pstr, &enc_global, 4096, 48))
-                                } else { (None, None) };
-                                batch.push(IngestFileItem {
-                                    include: true,
-                                    path: pstr,
-                                    size: meta.len(),
-                                    ordinal: seq,
-                                    encoding: None,
-                                    preview_cached_enc: pv_enc,
-                                    preview_cached_text: pv_text,
-                                    modified_ymd: mdy,
-                                });
-                                seq += 1; kept += 1;
-                            }
-                            if batch.len() >= 64 {
-                                let _ = tx.send(ScanEvent::Batch(std::mem::take(&mut batch)));
-                                let _ = tx.send(ScanEvent::Progress { scanned, kept });
-                            }
-                        }
-                    }
+            let mut needs_hash: Vec<(file_chunker::scan::ScannedFile, usize)> = Vec::new();
+            let walk = file_chunker::scan::walk_dir(std::path::Path::new(&root), &ext_refs, max_depth, sorted, |file| {
+                if cancel.is_canceled() { return std::ops::ControlFlow::Break(()); }
+                scanned += 1;
+                if only_unreg && known.size_may_match(file.size) {
+                    needs_hash.push((file, seq));
+                    seq += 1;
+                    return std::ops::ControlFlow::Continue(());
                 }
-            }
+                let pstr = file.path.display().to_string();
+                // Prefetch preview for text-like files using global encoding
+                let (pv_enc, pv_text) = if is_text_like_file(&pstr) {
+                    (Some(enc_global.clone()), preview_text_for_file(&pstr, &enc_global, 4096, 48))
+                } else { (None, None) };
+                batch.push(IngestFileItem {
+                    include: true,
+                    path: pstr,
+                    size: file.size,
+                    ordinal: seq,
+                    encoding: None,
+                    mime: None,
+                    preview_cached_enc: pv_enc,
+                    preview_cached_text: pv_text,
+                    modified_ymd: file.modified.map(format_ymd),
+                });
+                seq += 1; kept += 1;
+                if batch.len() >= 64 {
+                    let _ = tx.send(ScanEvent::Batch(std::mem::take(&mut batch)));
+                    let _ = tx.send(ScanEvent::Progress { scanned, kept });
+                }
+                std::ops::ControlFlow::Continue(())
+            });
+            if walk.is_break() { let _ = tx.send(ScanEvent::Canceled); return; }
             if !batch.is_empty() { let _ = tx.send(ScanEvent::Batch(batch)); }
             // Hash queued files when required (sequential to honor cancel)
             if only_unreg {
-                for (i, (p, fsz, mdy, ord)) in needs_hash.into_iter().enumerate() {
+                for (i, (file, ord)) in needs_hash.into_iter().enumerate() {
                     if cancel.is_canceled() { let _ = tx.send(ScanEvent::Canceled); return; }
-                    let mut include = true;
-                    if let Some(hx) = sha256_hex_file(&p) { if known.contains(&hx) { include = false; } }
+                    let include = file_chunker::scan::is_unregistered(&file, known.hashes(), known.sizes());
+                    let p = file.path.display().to_string();
                     // Prefetch preview for text-like files
-                    let lower = p.to_ascii_lowercase();
-                    let is_text_like = {
-                        let exts = [
-                            ".txt", ".md", ".markdown", ".csv", ".tsv", ".log", ".json", ".yaml", ".yml",
-                            ".ini", ".toml", ".cfg", ".conf", ".rst", ".tex", ".srt", ".properties",
-                        ];
-                        exts.iter().any(|e| lower.ends_with(e))
-                    };
-                    let (pv_enc, pv_text) = if include && is_text_like {
+                    let (pv_enc, pv_text) = if include && is_text_like_file(&p) {
                         (Some(enc_global.clone()), preview_text_for_file(&p, &enc_global, 4096, 48))
                     } else { (None, None) };
-                    let it = IngestFileItem { include, path: p, size: fsz, ordinal: ord, encoding: None, preview_cached_enc: pv_enc, preview_cached_text: pv_text, modified_ymd: mdy };
+                    let it = IngestFileItem { include, path: p, size: file.size, ordinal: ord, encoding: None, mime: None, preview_cached_enc: pv_enc, preview_cached_text: pv_text, modified_ymd: file.modified.map(format_ymd) };
                     let _ = tx.send(ScanEvent::Batch(vec![it]));
                     if include { kept += 1; }
                     if (i + 1) % 16 == 0 { let _ = tx.send(ScanEvent::Progress { scanned, kept }); }
@@ -2712,10 +2764,10 @@ impl AppState {
     fn do_ingest_files_batch(&mut self) {
         if !self.ensure_store_paths_current() { return; }
         let Some(svc) = self.svc.as_ref() else { self.status = "Model not initialized".into(); return; };
-        let selected: Vec<(String, Option<String>)> = self.ingest_files
+        let selected: Vec<(String, Option<String>, Option<String>)> = self.ingest_files
             .iter()
             .filter(|i| i.include)
-            .map(|i| (i.path.clone(), i.encoding.clone()))
+            .map(|i| (i.path.clone(), i.encoding.clone(), i.mime.clone()))
             .collect();
         if selected.is_empty() { self.status = "No files selected".into(); return; }
         let svc = Arc::clone(svc);
@@ -2736,9 +2788,10 @@ impl AppState {
         let ps = self.chunk_penalize_short_line;
         let pp = self.chunk_penalize_page_no_nl;
         let merge_min = self.chunk_merge_min.trim().parse().unwrap_or(100);
+        let overlap = self.chunk_overlap.trim().parse().unwrap_or(0);
         let doc_hint = if self.doc_hint.trim().is_empty() { None } else { Some(self.doc_hint.trim().to_string()) };
         std::thread::spawn(move || {
-            for (idx, (p, enc_override)) in selected.iter().enumerate() {
+            for (idx, (p, enc_override, mime_override)) in selected.iter().enumerate() {
                 let hint = doc_hint.as_deref();
                 let tx2 = tx.clone(); let cb: Box<dyn FnMut(ProgressEvent) + Send> = Box::new(move |ev: ProgressEvent| { let _ = tx2.send(UiProgressEvent::Service(ev)); });
                 // Choose per-file encoding if provided; otherwise use global
@@ -2747,12 +2800,25 @@ impl AppState {
                     _ => enc_opt.clone(),
                 };
                 let _ = tx.send(UiProgressEvent::FileStart { index: idx + 1, total: selected.len(), path: p.clone() });
-                let _ = svc.ingest_file_with_progress_custom(
-                    p, hint,
-                    enc_use_owned.as_deref(),
-                    min, max, cap, merge_min, ps, pp,
-                    Some(&cancel), Some(cb)
-                );
+                let opts = ChunkOptions {
+                    encoding: enc_use_owned,
+                    params: Some(file_chunker::text_segmenter::TextChunkParams {
+                        min_chars: min,
+                        max_chars: max,
+                        cap_chars: cap,
+                        penalize_short_line: ps,
+                        penalize_page_boundary_no_newline: pp,
+                        short_merge_min_chars: merge_min,
+                        keep_code_blocks: true,
+                        overlap_chars: overlap,
+                        token_counter: None,
+                    }),
+                    force_mime: mime_override.clone(),
+                    text_postprocess: None,
+                    infer_headings: None,
+                    id_scheme: Default::default(),
+                };
+                let _ = svc.ingest_file_with_options(p, hint, &opts, Some(&cancel), Some(cb));
                 if cancel.is_canceled() { let _ = tx.send(UiProgressEvent::Service(ProgressEvent::Canceled)); return; }
                 if idx + 1 == selected.len() { /* Finished will arrive from service */ }
             }
@@ -2797,8 +2863,25 @@ impl AppState {
                             UiProgressEvent::Service(ProgressEvent::SaveIndexes) => {
                                 self.status = "Saving indexes...".into();
                             }
-                            UiProgressEvent::Service(ProgressEvent::Finished { total }) => {
-                                // Service emits Finished per file. Only finalize when the last file is done.
+                            UiProgressEvent::Service(ProgressEvent::Reindexed { total, elapsed_ms }) => {
+                                self.ingest_running = false;
+                                self.ingest_cancel = None;
+                                self.ingest_rx = None;
+                                self.ingest_started = None;
+                                self.status = format!("Reindexed {} chunks in {:.1}s.", total, elapsed_ms as f32 / 1000.0);
+                                break;
+                            }
+                            UiProgressEvent::Failed(e) => {
+                                self.ingest_running = false;
+                                self.ingest_cancel = None;
+                                self.ingest_rx = None;
+                                self.ingest_started = None;
+                                self.status = format!("Failed: {}", e);
+                                break;
+                            }
+                            UiProgressEvent::Service(ev @ (ProgressEvent::Finished { .. } | ProgressEvent::SkippedDuplicate { .. })) => {
+                                // Service emits Finished (or SkippedDuplicate) per file. Only finalize when the last file is done.
+                                let total = if let ProgressEvent::Finished { total } = ev { total } else { 0 };
                                 let is_last_file = self.ingest_file_total == 0 || self.ingest_file_idx >= self.ingest_file_total;
                                 if is_last_file {
                                     self.ingest_running = false;
@@ -2819,7 +2902,10 @@ impl AppState {
                                     break;
                                 } else {
                                     // Intermediate file finished; keep running for next file.
-                                    self.status = format!("Finished file {} / {}", self.ingest_file_idx, self.ingest_file_total);
+                                    self.status = match &ev {
+                                        ProgressEvent::SkippedDuplicate { existing_doc_id } => format!("Skipped file {} / {} (already ingested as {})", self.ingest_file_idx, self.ingest_file_total, existing_doc_id),
+                                        _ => format!("Finished file {} / {}", self.ingest_file_idx, self.ingest_file_total),
+                                    };
                                     // Reset per-file chunk counters for clearer next-file progress
                                     self.ingest_done = 0;
                                     self.ingest_total = 0;
@@ -2929,8 +3015,8 @@ impl AppState {
                     let wa = self.w_tv_and.unwrap_or(0.0);
                     let wo = self.w_tv_or.unwrap_or(0.0);
                     let wv = self.w_vec.unwrap_or(0.0);
-                    let denom = (wt + wa + wo + wv).max(1e-6);
-                    ui.label(format!("% TV:{:.0} AND:{:.0} OR:{:.0} VEC:{:.0}", (wt/denom)*100.0, (wa/denom)*100.0, (wo/denom)*100.0, (wv/denom)*100.0));
+                    let pct = chunking_store::to_percentages(&[wt, wa, wo, wv]);
+                    ui.label(format!("% TV:{:.0} AND:{:.0} OR:{:.0} VEC:{:.0}", pct[0], pct[1], pct[2], pct[3]));
                 });
             }
 
@@ -2941,6 +3027,8 @@ impl AppState {
                     self.prompt_rendered = out;
                     self.prompt_popup_visible = true;
                 }
+                ui.checkbox(&mut self.prompt_strict_json, "Strict JSON")
+                    .on_hover_text("Build the results as real JSON (query, top_k, results) instead of expanding the text templates");
                 ui.add_space(8.0);
                 if ui.button("Import Templates").clicked() {
                     self.import_prompt_templates_via_dialog();
@@ -3216,7 +3304,7 @@ impl AppState {
                 Vec::new()
             } else {
                 if let Some(svc) = &svc_opt {
-                    match svc.search_hybrid(&q, top_k, filters, 0.0, 1.0) {
+                    match svc.search_vector(&q, top_k, filters) {
                         Ok(hits) => hits.into_iter().map(|h| chunking_store::TextMatch { chunk_id: chunk_model::ChunkId(h.chunk.chunk_id.0), score: h.score, raw_score: h.score }).collect(),
                         Err(_) => Vec::new(),
                     }
@@ -3238,40 +3326,31 @@ impl AppState {
             let wa = wa_raw / denom;
             let wo = wo_raw / denom;
             let wv = wv_raw / denom;
-            items.sort_by(|a, b| {
-                let (tv_a, tv_and_a, tv_or_a, vec_a) = (a.1.0.unwrap_or(0.0), a.1.1.unwrap_or(0.0), a.1.2.unwrap_or(0.0), a.1.3.unwrap_or(0.0));
-                let (tv_b, tv_and_b, tv_or_b, vec_b) = (b.1.0.unwrap_or(0.0), b.1.1.unwrap_or(0.0), b.1.2.unwrap_or(0.0), b.1.3.unwrap_or(0.0));
-                let key_a = match mode {
-                    SearchMode::Hybrid => wt * tv_a + wa * tv_and_a + wo * tv_or_a + wv * vec_a,
-                    SearchMode::Tantivy => tv_a.max(tv_and_a).max(tv_or_a),
-                    SearchMode::Vec => vec_a,
-                };
-                let key_b = match mode {
-                    SearchMode::Hybrid => wt * tv_b + wa * tv_and_b + wo * tv_or_b + wv * vec_b,
-                    SearchMode::Tantivy => tv_b.max(tv_and_b).max(tv_or_b),
-                    SearchMode::Vec => vec_b,
-                };
-                key_b.partial_cmp(&key_a).unwrap_or(std::cmp::Ordering::Equal)
-            });
+            let rank_key = |s: &(Option<f32>, Option<f32>, Option<f32>, Option<f32>)| {
+                let (tv, tv_and, tv_or, vec) = (s.0.unwrap_or(0.0), s.1.unwrap_or(0.0), s.2.unwrap_or(0.0), s.3.unwrap_or(0.0));
+                match mode {
+                    SearchMode::Hybrid => wt * tv + wa * tv_and + wo * tv_or + wv * vec,
+                    SearchMode::Tantivy => tv.max(tv_and).max(tv_or),
+                    SearchMode::Vec => vec,
+                }
+            };
+            items.sort_by(|a, b| rank_key(&b.1).partial_cmp(&rank_key(&a.1)).unwrap_or(std::cmp::Ordering::Equal));
             if items.len() > top_k { items.truncate(top_k); }
             let ids: Vec<chunk_model::ChunkId> = items.iter().map(|(cid, _)| chunk_model::ChunkId(cid.clone())).collect();
             let recs = match repo.get_chunks_by_ids(&ids) { Ok(r) => r, Err(e) => { let _ = tx.send(Err(format!("Fetch records failed: {e}"))); return; } };
             let mut rec_map: HashMap<String, chunk_model::ChunkRecord> = HashMap::new();
             for r in recs { rec_map.insert(r.chunk_id.0.clone(), r); }
             let mut out_rows: Vec<HitRow> = Vec::new();
-            for (cid, (sc_tv, sc_and, sc_or, sc_vec)) in items.into_iter() {
+            for (cid, scores) in items.into_iter() {
+                let score = rank_key(&scores);
+                let (sc_tv, sc_and, sc_or, sc_vec) = scores;
                 if let Some(rec) = rec_map.remove(&cid) {
                     let file = match std::path::Path::new(&rec.source_uri).file_name().and_then(|s| s.to_str()) { Some(s) => s.to_string(), None => rec.source_uri.clone() };
-                    let page = match (rec.page_start, rec.page_end) {
-                        (Some(s), Some(e)) if s == e => format!("#{}", s),
-                        (Some(s), Some(e)) => format!("#{}-{}", s, e),
-                        (Some(s), None) => format!("#{}", s),
-                        _ => page_label_from_chunk_id(&rec.chunk_id.0).unwrap_or_default(),
-                    };
+                    let page = prompt_builder::page_label(&rec);
                     let flat: String = rec.text.replace(['\n', '\r', '\t'], " ");
                     let mut text_preview: String = flat.chars().take(80).collect();
                     if flat.chars().count() > 80 { text_preview.push('\u{2026}'); }
-                    out_rows.push(HitRow { cid: rec.chunk_id.0, file, file_path: rec.source_uri.clone(), page, text_preview, text_full: rec.text, tv: sc_tv, tv_and: sc_and, tv_or: sc_or, vec: sc_vec });
+                    out_rows.push(HitRow { cid: rec.chunk_id.0, file, file_path: rec.source_uri.clone(), page, text_preview, text_full: rec.text, tv: sc_tv, tv_and: sc_and, tv_or: sc_or, vec: sc_vec, score });
                 }
             }
             let _ = tx.send(Ok(out_rows));
@@ -3279,92 +3358,30 @@ impl AppState {
     }
 
     fn render_prompt(&self) -> String {
-        // Expand header
-        let header = expand_template(&self.prompt_header_tmpl, |name, transform, arg| {
-            match name {
-                "Query" => match transform {
-                    Some("escape_json") => escape_json_str(&self.query),
-                    Some("snippet") => {
-                        let n = arg.and_then(|s| s.parse::<usize>().ok()).unwrap_or(200);
-                        snippet_chars(&self.query, n)
-                    }
-                    Some("snippet_json") => {
-                        let n = arg.and_then(|s| s.parse::<usize>().ok()).unwrap_or(200);
-                        escape_json_str(&snippet_chars(&self.query, n))
-                    }
-                    _ => self.query.clone(),
-                },
-                "TopK" => self.top_k.to_string(),
-                _ => String::new(),
-            }
-        });
-
-        // Expand items
+        let template = PromptTemplate {
+            name: String::new(),
+            header: self.prompt_header_tmpl.clone(),
+            item: self.prompt_item_tmpl.clone(),
+            footer: self.prompt_footer_tmpl.clone(),
+            items: self.prompt_items_count,
+            prev: self.prompt_prev,
+            next: self.prompt_next,
+        };
+        let items: Vec<PromptItem> = self
+            .results
+            .iter()
+            .map(|row| PromptItem { chunk_id: row.cid.clone(), file: row.file.clone(), page: row.page.clone(), source_uri: row.file_path.clone(), text: row.text_full.clone(), score: Some(row.score) })
+            .collect();
         // Open repo once for optional context expansion
         let repo_opt = chunking_store::sqlite_repo::SqliteRepo::open(self.db_path.trim()).ok();
-
-        let mut body = String::new();
-        let item_count = self.prompt_items_count.max(1).min(self.results.len());
-        for (i, row) in self.results.iter().take(item_count).enumerate() {
-            let item = expand_template(&self.prompt_item_tmpl, |name, transform, arg| {
-                match name {
-                    "Rank" => (i + 1).to_string(),
-                    "File" => row.file.clone(),
-                    "Page" => row.page.clone(),
-                    "CID" => row.cid.clone(),
-                    "SourceUri" => row.file_path.clone(),
-                    "Comma" => if i + 1 < item_count { ",".to_string() } else { String::new() },
-                    "Text" => match transform {
-                        Some("snippet") => {
-                            let n = arg.and_then(|s| s.parse::<usize>().ok()).unwrap_or(200);
-                            let combined = build_text_with_context_repo(repo_opt.as_ref(), &row.cid, &row.text_full, self.prompt_prev, self.prompt_next);
-                            snippet_chars(&combined, n)
-                        }
-                        Some("escape_json") => {
-                            escape_json_str(&build_text_with_context_repo(repo_opt.as_ref(), &row.cid, &row.text_full, self.prompt_prev, self.prompt_next))
-                        }
-                        Some("snippet_json") => {
-                            let n = arg.and_then(|s| s.parse::<usize>().ok()).unwrap_or(200);
-                            let combined = build_text_with_context_repo(repo_opt.as_ref(), &row.cid, &row.text_full, self.prompt_prev, self.prompt_next);
-                            escape_json_str(&snippet_chars(&combined, n))
-                        }
-                        _ => build_text_with_context_repo(repo_opt.as_ref(), &row.cid, &row.text_full, self.prompt_prev, self.prompt_next),
-                    },
-                    _ => String::new(),
-                }
-            });
-            body.push_str(&item);
-            if !item.ends_with('\n') { body.push('\n'); }
+        let opts = PromptOptions { query: &self.query, top_k: self.top_k, repo: repo_opt.as_ref() };
+        if self.prompt_strict_json {
+            let value = prompt_builder::build_prompt_json_items(&template, &items, &opts);
+            let mut out = serde_json::to_string_pretty(&value).unwrap_or_default();
+            out.push('\n');
+            return out;
         }
-
-        // Expand footer
-        let footer = expand_template(&self.prompt_footer_tmpl, |name, transform, arg| {
-            match name {
-                "Query" => match transform {
-                    Some("escape_json") => escape_json_str(&self.query),
-                    Some("snippet") => {
-                        let n = arg.and_then(|s| s.parse::<usize>().ok()).unwrap_or(200);
-                        snippet_chars(&self.query, n)
-                    }
-                    Some("snippet_json") => {
-                        let n = arg.and_then(|s| s.parse::<usize>().ok()).unwrap_or(200);
-                        escape_json_str(&snippet_chars(&self.query, n))
-                    }
-                    _ => self.query.clone(),
-                },
-                "TopK" => self.top_k.to_string(),
-                _ => String::new(),
-            }
-        });
-
-        let mut out = String::new();
-        out.push_str(&header);
-        if !header.ends_with('\n') { out.push('\n'); }
-        out.push_str(&body);
-        if !body.ends_with('\n') { out.push('\n'); }
-        out.push_str(&footer);
-        if !out.ends_with('\n') { out.push('\n'); }
-        out
+        prompt_builder::render_items(&template, &items, &opts)
     }
 
     fn ui_prompt_window(&mut self, ctx: &egui::Context) {
@@ -3449,25 +3466,6 @@ fn derive_hnsw_dir(root: &str) -> String { format!("{}/hnsw", root) }
 #[cfg(feature = "tantivy")]
 fn derive_tantivy_dir(root: &str) -> String { format!("{}/tantivy", root) }
 
-// Fallback: derive page label from trailing part of chunk_id like "...#12" or "...#3-4"
-fn page_label_from_chunk_id(cid: &str) -> Option<String> {
-    if let Some(pos) = cid.rfind('#') {
-        let tail = &cid[pos + 1..];
-        // parse digits or digits-digits
-        let mut it = tail.splitn(2, '-');
-        let a = it.next()?;
-        if a.is_empty() || !a.chars().all(|c| c.is_ascii_digit()) { return None; }
-        if let Some(b) = it.next() {
-            if b.chars().all(|c| c.is_ascii_digit()) {
-                return Some(format!("#{}-{}", a, b));
-            }
-            return Some(format!("#{}", a));
-        }
-        return Some(format!("#{}", a));
-    }
-    None
-}
-
 // removed unused rec_clone_for_tantivy (was only used for older Tantivy upsert path)
 
 // --- Japanese font fallback (CJK) ------------------------------------------------------------
@@ -3570,6 +3568,16 @@ fn display_path_with_root(path: &str, root: &str, absolute: bool) -> String {
     }
 }
 
+/// Extensions whose files get a text preview (the scan worker prefetches these).
+fn is_text_like_file(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    let exts = [
+        ".txt", ".md", ".markdown", ".csv", ".tsv", ".log", ".json", ".yaml", ".yml",
+        ".ini", ".toml", ".cfg", ".conf", ".rst", ".tex", ".srt", ".properties",
+    ];
+    exts.iter().any(|e| lower.ends_with(e))
+}
+
 // Format SystemTime to yyyy/mm/dd for table display
 fn format_ymd(st: std::time::SystemTime) -> String {
     let dt: chrono::DateTime<chrono::Local> = st.into();
@@ -3615,158 +3623,6 @@ fn preview_text_for_file(path: &str, enc: &str, max_bytes: usize, max_chars: usi
     Some(truncate_for_preview(&text, max_chars))
 }
 
-// Compute SHA-256 hex digest of a file path (streaming).
-fn sha256_hex_file(path: &str) -> Option<String> {
-    use std::fs::File;
-    use std::io::Read;
-    use sha2::Digest;
-    let mut f = File::open(path).ok()?;
-    let mut hasher = sha2::Sha256::new();
-    let mut buf = [0u8; 8192];
-    loop {
-        let n = match f.read(&mut buf) { Ok(n) => n, Err(_) => return None };
-        if n == 0 { break; }
-        hasher.update(&buf[..n]);
-    }
-    let digest = hasher.finalize();
-    Some(to_hex(&digest))
-}
-
-fn to_hex(bytes: &[u8]) -> String {
-    let mut s = String::with_capacity(bytes.len() * 2);
-    for b in bytes { s.push_str(&format!("{:02x}", b)); }
-    s
-}
-
-// Expand placeholders in a template string.
-// Supported forms: <<Name>>, <<Name:transform>>, <<Name:transform(arg)>>
-// Resolver receives (name, transform, arg) and returns replacement text.
-fn expand_template<F>(tmpl: &str, mut resolver: F) -> String
-where
-    F: FnMut(&str, Option<&str>, Option<&str>) -> String,
-{
-    let mut out = String::with_capacity(tmpl.len());
-    let mut rest = tmpl;
-    loop {
-        match rest.find("<<") {
-            Some(start) => {
-                // Push the prefix as-is (preserves UTF-8)
-                out.push_str(&rest[..start]);
-                let after = &rest[start + 2..];
-                if let Some(end_rel) = after.find(">>") {
-                    let token = &after[..end_rel];
-                    let (name, transform, arg) = parse_token(token);
-                    let rep = resolver(name, transform.as_deref(), arg.as_deref());
-                    out.push_str(&rep);
-                    rest = &after[end_rel + 2..];
-                } else {
-                    // No closing, push remainder and break
-                    out.push_str(rest);
-                    break;
-                }
-            }
-            None => {
-                out.push_str(rest);
-                break;
-            }
-        }
-    }
-    out
-}
-
-fn parse_token(token: &str) -> (
-    &str,                // name
-    Option<String>,      // transform
-    Option<String>,      // arg
-) {
-    if let Some(colon) = token.find(':') {
-        let (name, rest) = token.split_at(colon);
-        let rest = &rest[1..]; // skip ':'
-        if let Some(lp) = rest.find('(') {
-            let (tf, tail) = rest.split_at(lp);
-            if tail.ends_with(')') && tail.len() >= 2 {
-                let arg = &tail[1..tail.len() - 1];
-                return (name.trim(), Some(tf.trim().to_string()), Some(arg.trim().to_string()));
-            }
-            return (name.trim(), Some(tf.trim().to_string()), None);
-        } else {
-            return (name.trim(), Some(rest.trim().to_string()), None);
-        }
-    }
-    (token.trim(), None, None)
-}
-
-fn snippet_chars(s: &str, n: usize) -> String {
-    if n == 0 { return String::new(); }
-    let mut it = s.chars();
-    let taken: String = it.by_ref().take(n).collect();
-    if it.next().is_some() { format!("{}窶ｦ", taken) } else { taken }
-}
-
-fn build_text_with_context_repo(
-    repo_opt: Option<&chunking_store::sqlite_repo::SqliteRepo>,
-    base_cid: &str,
-    base_text: &str,
-    prev: usize,
-    next: usize,
-) -> String {
-    if repo_opt.is_none() || (prev == 0 && next == 0) {
-        return base_text.to_string();
-    }
-    let repo = repo_opt.unwrap();
-    use chunk_model::ChunkId;
-    let mut parts: Vec<String> = Vec::new();
-    // Collect prev
-    if prev > 0 {
-        let mut cur = ChunkId(base_cid.to_string());
-        let mut prev_parts: Vec<String> = Vec::new();
-        for _ in 0..prev {
-            match repo.get_neighbor_chunks(&cur) {
-                Ok((p, _)) => {
-                    if let Some(pr) = p { prev_parts.push(pr.text.clone()); cur = pr.chunk_id; } else { break; }
-                }
-                Err(_) => break,
-            }
-        }
-        prev_parts.reverse();
-        parts.extend(prev_parts);
-    }
-    // Base
-    parts.push(base_text.to_string());
-    // Collect next
-    if next > 0 {
-        let mut cur = ChunkId(base_cid.to_string());
-        for _ in 0..next {
-            match repo.get_neighbor_chunks(&cur) {
-                Ok((_, nopt)) => {
-                    if let Some(nx) = nopt { parts.push(nx.text.clone()); cur = nx.chunk_id; } else { break; }
-                }
-                Err(_) => break,
-            }
-        }
-    }
-    parts.join("\n")
-}
-
-fn escape_json_str(s: &str) -> String {
-    let mut out = String::with_capacity(s.len());
-    for ch in s.chars() {
-        match ch {
-            '"' => out.push_str("\\\""),
-            '\\' => out.push_str("\\\\"),
-            '\n' => out.push_str("\\n"),
-            '\r' => out.push_str("\\r"),
-            '\t' => out.push_str("\\t"),
-            c if c.is_control() => {
-                let code = c as u32;
-                out.push_str(&format!("\\u{:04X}", code));
-            }
-            _ => out.push(ch),
-        }
-    }
-    out
-}
-
 // Create a safe filename component while preserving Unicode characters.
 // Replaces forbidden characters (\\ / : * ? " < > |) and control chars with '-',
 // converts whitespace to '-', collapses repeated '-', and trims leading/trailing '-'.